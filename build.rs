@@ -1,3 +1,9 @@
 fn main() {
-    println!("cargo:rustc-link-search=/home/weimin/build/runner/");
+    #[cfg(feature = "nvtx")]
+    println!(
+        "cargo:rustc-link-search={}/lib64",
+        std::env::var("CUDA_PATH").unwrap_or("/usr/local/cuda".to_string())
+    );
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/collector.proto").expect("failed to compile collector.proto");
 }
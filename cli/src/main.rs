@@ -1,30 +1,91 @@
-use crate::TargetType::{Address, Glob};
-use clap::Parser;
-use ityfuzz::evm::config::{Config, FuzzerTypes, StorageFetchingMode};
+use crate::TargetType::{Address, Bytecode, Glob, Project};
+use clap::{Parser, Subcommand};
+use ityfuzz::evm::config::{Config, FuzzerTypes, StorageFetchingMode, DEFAULT_BLOCK_GAS_LIMIT};
 use ityfuzz::evm::contract_utils::{set_hash, ContractLoader};
+use ityfuzz::evm::address_pool::AddressWeights;
+use ityfuzz::evm::amm::load_amm_pools;
+use ityfuzz::evm::weird_tokens::load_weird_tokens;
+use ityfuzz::evm::corpus_format::{upgrade_corpus_dir, CORPUS_FORMAT_VERSION};
+use ityfuzz::evm::cov_map::render_cov_map;
+use ityfuzz::evm::cov_merge::merge_runs;
+use ityfuzz::evm::distill::distill;
 use ityfuzz::evm::host::PANIC_ON_BUG;
 use ityfuzz::evm::input::EVMInput;
+use ityfuzz::evm::input_yaml::{decode_input_file, encode_input_file};
 use ityfuzz::evm::middlewares::middleware::Middleware;
 use ityfuzz::evm::onchain::endpoints::{Chain, OnChainConfig};
+use ityfuzz::evm::onchain::abi_decompiler::RESOLVE_SIGNATURES;
+use ityfuzz::evm::onchain::signature_workers::SIGNATURE_WORKERS;
 use ityfuzz::evm::onchain::flashloan::{DummyPriceOracle, Flashloan};
 use ityfuzz::evm::oracles::bug::BugOracle;
+use ityfuzz::evm::oracles::call_depth::CallDepthOracle;
+use ityfuzz::evm::oracles::oog_griefing::OogGriefingOracle;
+use ityfuzz::evm::oracles::reentrancy::ReentrancyOracle;
+use ityfuzz::evm::oracles::selfdestruct::SelfdestructOracle;
+use ityfuzz::evm::oracles::initializer::InitializerOracle;
+use ityfuzz::evm::oracles::withdrawal::WithdrawalOracle;
+use ityfuzz::evm::oracles::storage_write::StorageWriteOracle;
+use ityfuzz::evm::storage_write::STORAGE_WRITE_CHECK;
 use ityfuzz::evm::oracles::erc20::IERC20OracleFlashloan;
 use ityfuzz::evm::oracles::v2_pair::PairBalanceOracle;
 use ityfuzz::evm::producers::erc20::ERC20Producer;
+use ityfuzz::evm::packs::{fetch_pack, load_packs, DEFAULT_PACK_URL, PACKS, PACK_DIR};
 use ityfuzz::evm::producers::pair::PairProducer;
+use ityfuzz::host_mem::parse_mem_size;
+use ityfuzz::batch_composer::BatchMode;
+use ityfuzz::scheduler::{PowerSchedule, PruningPolicy};
+use ityfuzz::evm::entry_points::load_entry_points;
+use ityfuzz::evm::exploit_export::{export_exploit, ExportFormat, ExportOptions};
+use ityfuzz::evm::directed::DirectedTarget;
+use ityfuzz::evm::evm_version::EVMVersion;
+use ityfuzz::evm::pausable::PausedMode;
+use ityfuzz::evm::callers::{load_named_callers, parse_sender_roles, register_named_callers, SENDER_ROLES};
+use ityfuzz::evm::grammar::load_grammar;
+use ityfuzz::evm::ptx_binding::sign_ptx;
+use ityfuzz::evm::sanitizers::{parse_sanitizers, Sanitizer};
+use ityfuzz::evm::shell::run_shell;
+use ityfuzz::campaign::{run_campaign, CAMPAIGN_COMMAND};
+use ityfuzz::experiment::{run_experiment, run_schedule_bench, BENCH_COMMAND, EXPERIMENT_COMMAND};
+use ityfuzz::quickstart::{quick_args, QUICK_COMMAND};
+use ityfuzz::evm::state_diff::state_diff_report;
+use ityfuzz::evm::storage_layout::load_storage_layouts;
+use ityfuzz::evm::target_spec::{parse_address, parse_targets, resolve_ens, target_chain, TargetSpec, DEFAULT_ENS_REGISTRY};
+use ityfuzz::evm::view_invariants::load_view_invariants;
+use ityfuzz::evm::goals::load_goals;
+use ityfuzz::evm::adversary::load_adversary;
+use ityfuzz::evm::price_feeds::load_price_feeds;
+use ityfuzz::evm::force_feed::FORCE_FEED;
+use ityfuzz::evm::tx_gas::GAS_METERING;
+use ityfuzz::evm::cfg_refinement::REFINE_CFG;
+use ityfuzz::evm::bug_likelihood::BUG_PRIORS;
+use ityfuzz::evm::opcode_stats::OPCODE_STATS;
+use ityfuzz::differential::DIFFERENTIAL;
+use ityfuzz::evm::block_advance::{parse_policies, set_block_advance_policies};
+use ityfuzz::evm::flashloan_provider::{parse_provider, set_flashloan_provider};
+use ityfuzz::evm::scenario::load_scenarios;
+use ityfuzz::evm::token_minting::parse_token_mints;
 use ityfuzz::evm::types::{EVMAddress, EVMFuzzState, EVMU256};
 use ityfuzz::evm::vm::EVMState;
+use ityfuzz::evm::value_hints::{scan_natspec, VALUE_HINTS};
+use ityfuzz::evm::withdrawal::WITHDRAWAL_CHECK;
+use ityfuzz::backend_cost::ROUTE_BY_COST;
+use ityfuzz::corpus_journal::{init_corpus_journal, JOURNAL_FILE};
+use ityfuzz::error::MauError;
 use ityfuzz::fuzzers::evm_fuzzer::evm_fuzzer;
+use ityfuzz::logging::init_logging;
 use ityfuzz::oracle::{Oracle, Producer};
 use ityfuzz::state::FuzzState;
+use ityfuzz::workdir::{corpus_dir, init_work_dir, stats_dir, Manifest};
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::path::Path;
 use std::rc::Rc;
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::fs::File;
 use std::io::Write;
+use std::process::exit;
 
 pub fn init_sentry() {
     let _guard = sentry::init(("https://96f3517bd77346ea835d28f956a84b9d@o4504503751344128.ingest.sentry.io/4504503752523776", sentry::ClientOptions {
@@ -45,7 +106,7 @@ pub fn init_sentry() {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Glob pattern / address to find contracts
+    /// glob of .abi / .bin files, project root, comma separated addresses (0x<address>[@chain], <chain>:0x<address>) or ENS names (name.eth[@chain]), or bytecode:0x<creation code> / runtime:0x<runtime code>
     #[arg(short, long)]
     target: String,
 
@@ -57,14 +118,30 @@ struct Args {
     #[arg(long, default_value = "0")]
     gpu_dev: i32,
 
-    /// address to find the PTX smart contract
-    #[arg(short, long, default_value = "./cov.txt")]
+    /// the GPU runner library, loaded at runtime (searched in LD_LIBRARY_PATH unless it is a path)
+    #[arg(long, default_value = "librunner.so")]
+    runner_lib: String,
+
+    /// path of the coverage file (Default: stats/cov.txt in the work directory)
+    #[arg(short, long, default_value = "")]
     cov_path: String,
 
-    /// Target type (glob, address) (Default: Automatically infer from target)
+    /// Target type (glob, address, project, bytecode) (Default: Automatically infer from target)
     #[arg(long)]
     target_type: Option<String>,
 
+    /// ENS registry resolving the ENS names among the targets
+    #[arg(long, default_value = DEFAULT_ENS_REGISTRY)]
+    ens_registry: String,
+
+    /// mainnet RPC the ENS names are resolved through (Default: the public mainnet RPC)
+    #[arg(long)]
+    ens_rpc: Option<String>,
+
+    /// contracts of a Foundry / Hardhat project to fuzz, by name or <source>:<name> (comma separated); the deployable contracts of the project outside of dependencies, tests and scripts by default
+    #[arg(long, default_value = "")]
+    contracts: String,
+
     /// Fuzzer type
     #[arg(long, default_value = "cmp")]
     fuzzer_type: String,
@@ -121,6 +198,10 @@ struct Args {
     #[arg(long, default_value = "dummy")]
     flashloan_price_oracle: String,
 
+    /// flashloan provider the borrows come from and whose callback the targets receive: aave-v3, balancer, erc3156:<lender>[:<fee bps>], uniswap-v2:<pair>, with an optional :<lender> for the first two
+    #[arg(long, default_value = "")]
+    flashloan_provider: String,
+
     /// Enable ierc20 oracle
     #[arg(short, long, default_value = "false")]
     ierc20_oracle: bool,
@@ -144,21 +225,625 @@ struct Args {
     #[arg(long)]
     replay_file: Option<String>,
 
-    // allow users to pass the path through CLI
-    #[arg(long, default_value = "corpus")]
+    // allow users to pass the path through CLI (Default: corpus/ in the work directory)
+    #[arg(long, default_value = "")]
     corpus_path: String,
 
+    /// work directory holding the corpus, solutions, stats, artifacts and manifest.json of the run
+    #[arg(long, default_value = "workdir")]
+    work_dir: String,
+
+    /// keep the corpus in memory and write its files in the background through a memory-mapped journal (corpus.journal in the work directory), recovered after a crash
+    #[arg(long, default_value = "false")]
+    async_corpus: bool,
+
     // random seed
     #[arg(long, default_value = "1667840158231589000")]
     seed: u64,
+
+    /// JSON file with named scenario presets (env / storage overrides) to cycle through
+    #[arg(long)]
+    scenario_file: Option<String>,
+
+    /// JSON file with third-party transactions (ERC-20 transfers, price updates, ...) the fuzzer may inject before its own
+    #[arg(long)]
+    adversary_file: Option<String>,
+
+    /// JSON file declaring the price feeds whose reported prices the fuzzer sets within bounds
+    #[arg(long)]
+    price_feeds: Option<String>,
+
+    /// JSON file declaring simulated Uniswap v2 / v3 pools to deploy as dependencies of the targets
+    #[arg(long)]
+    amm_pools: Option<String>,
+
+    /// JSON file declaring ERC-20 tokens with weird behaviors (fee-on-transfer, rebasing, blacklistable, return-false) to deploy as the tokens of the targets
+    #[arg(long)]
+    weird_tokens: Option<String>,
+
+    /// Upper bound of the block gas limit env mutation (Default: inferred from chain-type, 30M otherwise)
+    #[arg(long)]
+    block_gas_limit: Option<u64>,
+
+    /// Also explore pathological env values (e.g., gas limit beyond the block gas limit)
+    #[arg(long, default_value = "false")]
+    explore_pathological_env: bool,
+
+    /// Replay bugs found in GPU on revm and only report the confirmed ones
+    #[arg(long, default_value = "true")]
+    verify_gpu_solutions: bool,
+
+    /// number of recent PCs recorded per GPU thread and dumped when a bug is found (0 to disable)
+    #[arg(long, default_value = "32")]
+    pc_trace_size: usize,
+
+    /// reverts within this many instructions of a transaction count as guard reverts in stats/revert_rates.json
+    #[arg(long, default_value = "64")]
+    guard_phase_instructions: usize,
+
+    /// gRPC endpoint of the collector to stream corpus, solutions and stats to (requires the `grpc` feature)
+    #[arg(long)]
+    collector: Option<String>,
+
+    /// hit count at which loop back-edges stop counting as new coverage (0 to disable)
+    #[arg(long, default_value = "2")]
+    loop_bound: u8,
+
+    /// Before fuzzing, let attackers make the calls anyone can make to change the state (writing storage without a caller check, e.g., public mint, approve) and start from the resulting states
+    #[arg(long, default_value = "true")]
+    prepare_attacker_states: bool,
+
+    /// per-module log verbosity, e.g., ityfuzz::gpu_stage=debug,ityfuzz::fuzzer=info (Default: RUST_LOG or info)
+    #[arg(long, default_value = "")]
+    log: String,
+
+    /// emit logs as JSON lines
+    #[arg(long, default_value = "false")]
+    log_json: bool,
+
+    /// flag reads of uninitialized EVM memory and out-of-bounds CALLDATACOPY/CODECOPY in GPU
+    #[arg(long, default_value = "false")]
+    gpu_memory_sanitizer: bool,
+
+    /// JSON file declaring entry points (calldata prefixes or PCs) of contracts with non-standard dispatchers
+    #[arg(long)]
+    entry_points: Option<String>,
+
+    /// tokens to force-set caller balances and allowances of, as name_or_address[:amount],... (amount defaults to 1e24)
+    #[arg(long, default_value = "")]
+    mint_tokens: String,
+
+    /// composition of GPU batches: seed (mutate one seed in GPU), homogeneous (seeds grouped by selector) or mixed
+    #[arg(long, default_value = "seed")]
+    gpu_batching: String,
+
+    /// in the seed batching mode, keep this many seeds of the corpus resident on the GPU and sample them across multi-launch kernel runs (0 to disable)
+    #[arg(long, default_value = "0")]
+    gpu_resident: usize,
+
+    /// kernel launches run on the resident seeds between two reads of the host
+    #[arg(long, default_value = "8")]
+    resident_launches: u32,
+
+    /// adapt the number of threads of the GPU batches to the occupancy of the kernel and the measured throughput (stats/batch_size.json)
+    #[arg(long, default_value = "false")]
+    adaptive_batch: bool,
+
+    /// once the GPU phase starts, fuzz each seed on revm or on the GPU, whichever the cost model learned during the campaign predicts cheaper
+    #[arg(long, default_value = "false")]
+    route_by_cost: bool,
+
+    /// bias the mutation of the integer args towards the ranges stated by the revert strings and the natspec of the functions (sources of projects)
+    #[arg(long, default_value = "false")]
+    value_hints: bool,
+
+    /// model the ether balances of the targets and force-feed ether to them before transactions (selfdestruct to the target, coinbase payments)
+    #[arg(long, default_value = "false")]
+    force_feed: bool,
+
+    /// meter the gas of the transactions on the host and mutate their gas limit, so that inner calls can run out of gas (on with --sanitize oog-griefing)
+    #[arg(long, default_value = "false")]
+    tx_gas: bool,
+
+    /// policies the block of a transaction can advance by from the previous one of the sequence: same, next (block), day (e.g. same,next,day)
+    #[arg(long, default_value = "")]
+    block_advance: String,
+
+    /// add the jumps observed at runtime to the CFG of the targets when they reach blocks heimdall missed, refining the directed distances and later analyses
+    #[arg(long, default_value = "false")]
+    refine_cfg: bool,
+
+    /// fuzz only the view / pure functions and check each GPU batch against revm, dumping the minimized divergences in artifacts/divergences (requires a PTX kernel)
+    #[arg(long, default_value = "false")]
+    differential: bool,
+
+    /// score the functions of the targets by a static pass (value calls, delegatecall, selfdestruct, unchecked arithmetic, unguarded storage writes) and give their seeds extra rounds of the power schedule accordingly
+    #[arg(long, default_value = "false")]
+    bug_priors: bool,
+
+    /// count the opcodes executed on revm and on the GPU, printing the n hottest opcodes / pcs of each target per backend and writing them to stats/opcodes.json (0 to disable)
+    #[arg(long, default_value = "0")]
+    opcode_stats: usize,
+
+    /// weights of the classes address args are drawn from: eoa, contract, zero, this (the called contract), precompile
+    #[arg(long, default_value = "eoa=45,contract=35,zero=10,this=5,precompile=5")]
+    address_weights: String,
+
+    /// EVM version the targets are executed under: london, shanghai, cancun or latest
+    #[arg(long, default_value = "latest")]
+    evm_version: String,
+
+    /// embed the hash of this bytecode (.bin given to ptxsema) in the PTX kernel at --ptx-path and exit
+    #[arg(long)]
+    sign_ptx: Option<String>,
+
+    /// load the PTX kernel even if it is not translated from one of the targets
+    #[arg(long, default_value = "false")]
+    force: bool,
+
+    /// look up the selectors of decompiled functions in the signature database (sig.eth.samczsun.com); names resolved once are kept in cache/signatures.json and reused offline
+    #[arg(long, default_value = "false")]
+    resolve_signatures: bool,
+
+    /// number of threads looking signatures up in the background with --resolve-signatures
+    #[arg(long, default_value = "4")]
+    signature_workers: usize,
+
+    /// direct the fuzzer toward a function or PC, as [contract:]<selector or pc>, e.g., Vault:0x2e1a7d4d
+    #[arg(long)]
+    direct_to: Option<String>,
+
+    /// JSON grammar (contract name or address -> JSON ABI) whose functions are added to the targets, e.g., exported by another campaign
+    #[arg(long)]
+    grammar: Option<String>,
+
+    /// solc output (standard JSON or --combined-json storage-layout) whose storage layouts name the slots of the targets
+    #[arg(long)]
+    storage_layout: Option<String>,
+
+    /// number of threads executing mutants in parallel when the GPU is not used (1 disables the CPU pool)
+    #[arg(long, default_value = "1")]
+    cpu_workers: usize,
+
+    /// which staged states are evicted when there are more than --max-states: votes, lru (least recently productive) or dominance (by coverage contribution)
+    #[arg(long, default_value = "votes")]
+    state_pruning: String,
+
+    /// maximum number of staged states kept before pruning
+    #[arg(long, default_value = "500")]
+    max_states: usize,
+
+    /// balance of each caller in wei, bounding the mutated transaction values
+    #[arg(long, default_value = "100000000000000000000")]
+    caller_balance: String,
+
+    /// energy of the inputs of the corpus: queue (uniform cycling), explore, fast, coe or quad (AFLFast schedules)
+    #[arg(long, default_value = "queue")]
+    power_schedule: String,
+
+    /// executions of each executor (revm, then the GPU runner) benchmarked by `cli perf`
+    #[arg(long, default_value = "100000")]
+    perf_executions: u64,
+
+    /// seconds of fuzzing before stopping and printing the summary, 0 for no limit
+    #[arg(long, default_value = "0")]
+    time_budget: u64,
+
+    /// memory budget of the process (e.g. 16G): the state corpus shrinks when the resident set nears it, and the campaign stops with its summary when it stays above
+    #[arg(long)]
+    max_host_mem: Option<String>,
+
+    /// directory persisting the learned dictionaries (comparison operands, keccak preimages, magic values) per target, reloaded by later campaigns on the same targets
+    #[arg(long)]
+    dictionary_dir: Option<String>,
+
+    /// work directory of a run on a similar target (e.g., the protocol this one forks): its corpus is mapped onto the targets by function signature and seeds the campaign
+    #[arg(long)]
+    warm_start: Option<String>,
+
+    /// do not enable the conformance oracles of the token interfaces (ERC-20/721/1155/4626) detected on the targets
+    #[arg(long, default_value = "false")]
+    no_interface_oracles: bool,
+
+    /// JSON file declaring invariants between view functions of the targets (e.g., totalAssets() >= totalSupply()), checked after each transaction
+    #[arg(long)]
+    view_invariants: Option<String>,
+
+    /// JSON file declaring the goals of the attacker (e.g., a slot of a target set to the attacker, a token balance of the attacker increased), reported when reached and directing the fuzzer toward the writes of the goal slots
+    #[arg(long)]
+    goals: Option<String>,
+
+    /// bug classes to detect: ibsan, msan, bug, reentrancy, selfdestruct, initializer, storage-write, call-depth, oog-griefing, withdrawal (comma separated); the PTX kernel must be instrumented for ibsan, msan and bug
+    #[arg(long, default_value = "")]
+    sanitize: String,
+
+    /// directory of the corpus / dictionary packs merged into the campaign (see `cli fetch-pack`)
+    #[arg(long, default_value = PACK_DIR)]
+    pack_dir: String,
+
+    /// do not merge the packs into the campaign
+    #[arg(long, default_value = "false")]
+    no_packs: bool,
+
+    /// targets paused in the initial state (paused() returns true): halt (report them and stop), explore (also fuzz them unpaused, as the `unpaused` scenario) or ignore
+    #[arg(long, default_value = "halt")]
+    paused: String,
+
+    /// JSON file naming callers (name, optional address, role: attacker, user or privileged), printed by name in all the outputs
+    #[arg(long)]
+    callers: Option<String>,
+
+    /// roles of the senders the mutators pick, comma separated (e.g., attacker to only send as attackers), any when not set
+    #[arg(long)]
+    sender_roles: Option<String>,
+}
+
+/// Tools working on the outputs of campaigns, e.g., `cli cov merge run1/ run2/ -o merged/`
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct ToolArgs {
+    #[command(subcommand)]
+    command: Tool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Tool {
+    /// coverage of campaigns
+    #[command(subcommand)]
+    Cov(CovTool),
+    /// corpora of campaigns
+    #[command(subcommand)]
+    Corpus(CorpusTool),
+    /// inputs of the corpus as human-editable YAML
+    #[command(subcommand)]
+    Input(InputTool),
+    /// scripts reproducing a solution on a fork: a JSON-RPC batch and a cast script for anvil, or a Tenderly bundle simulation
+    ExportExploit {
+        /// solution file, e.g., <work_dir>/solutions/<oracle>/<timestamp>
+        solution: String,
+
+        /// output directory of the scripts
+        #[arg(short, long)]
+        output: String,
+
+        /// anvil or tenderly
+        #[arg(long, default_value = "anvil")]
+        format: String,
+
+        /// network of the Tenderly simulations
+        #[arg(long, default_value = "1")]
+        network_id: String,
+
+        /// balance in wei each sender is funded with, on top of the value it sends
+        #[arg(long, default_value = "100000000000000000000")]
+        balance: String,
+
+        /// block number of the fork, the block advances of the sequence are relative to it in the Tenderly simulations
+        #[arg(long)]
+        fork_block: Option<u64>,
+
+        /// timestamp of the block of the fork
+        #[arg(long)]
+        fork_timestamp: Option<u64>,
+    },
+    /// slots changed between two infant states of a campaign run with --dump-corpus
+    StateDiff {
+        /// index of the parent state
+        parent: usize,
+
+        /// index of the child state
+        child: usize,
+
+        /// work directory of the campaign
+        #[arg(long, default_value = "workdir")]
+        work_dir: String,
+    },
+    /// explore the corpus of a run: decode, replay and edit its entries, and push them to the running campaign
+    Shell {
+        /// work directory of the run
+        run: String,
+    },
+    /// minimal corpus of a run covering the same branches, with the callers anonymized, verified by replaying it
+    Distill {
+        /// work directory of the run
+        run: String,
+
+        /// output directory of the distilled corpus
+        #[arg(short, long)]
+        output: String,
+    },
+    /// download a seed corpus / dictionary pack of a protocol family (erc20, uniswap, openzeppelin) merged into later campaigns
+    FetchPack {
+        /// name of the pack
+        name: String,
+
+        /// location of the packs
+        #[arg(long, default_value = DEFAULT_PACK_URL)]
+        url: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CorpusTool {
+    /// rewrite the corpus files of a directory in the latest corpus format
+    Upgrade {
+        /// corpus directory, e.g., <work_dir>/corpus
+        dir: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum InputTool {
+    /// print a binary input of the corpus (input-NNNNNN.bin) as YAML
+    Decode {
+        /// binary input
+        input: String,
+
+        /// YAML file, printed when not set
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// JSON file naming callers, as given to the campaign
+        #[arg(long)]
+        callers: Option<String>,
+    },
+    /// write the input of a YAML document as a binary input
+    Encode {
+        /// YAML document
+        yaml: String,
+
+        /// binary input
+        #[arg(short, long)]
+        output: String,
+
+        /// binary input whose staged VM state the new input keeps
+        #[arg(long)]
+        base: Option<String>,
+
+        /// JSON file naming callers, as given to the campaign
+        #[arg(long)]
+        callers: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CovTool {
+    /// union the coverage of runs (work directories whose corpus was replayed) and report the branches hit only in each
+    Merge {
+        /// work directories or coverage.json files of the runs
+        #[arg(required = true, num_args = 2..)]
+        runs: Vec<String>,
+
+        /// directory of the merged coverage
+        #[arg(short, long)]
+        output: String,
+    },
+    /// render the disassembly with the hit count of each instruction, the unreached blocks and the comparisons guarding the branches taken one way only
+    Map {
+        /// work directory or coverage.json file of a run whose corpus was replayed
+        run: String,
+
+        /// HTML file of the map
+        #[arg(short, long, default_value = "cov_map.html")]
+        output: String,
+    },
+}
+
+/// First arguments selecting a tool instead of a campaign
+const TOOLS: [&str; 8] = ["cov", "corpus", "input", "export-exploit", "state-diff", "shell", "distill", "fetch-pack"];
+
+/// First argument benchmarking the GPU runner against revm on the targets of a campaign,
+/// e.g., `cli perf -t 'tests/*' --perf-executions 100000`
+const PERF_COMMAND: &str = "perf";
+
+/// Campaign arguments of `cli quick Token.sol [args...]`, exits when the source cannot be prepared
+fn quick_campaign_args() -> Vec<String> {
+    let source = match env::args().nth(2) {
+        Some(source) => source,
+        None => {
+            eprintln!("usage: cli quick <file.sol> [campaign arguments...]");
+            exit(1);
+        }
+    };
+    let extra = env::args().skip(3).collect::<Vec<_>>();
+    init_logging("", false);
+    match quick_args(source.as_str(), &extra) {
+        Ok(args) => std::iter::once(env::args().next().unwrap()).chain(args).collect(),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Report an error of the pipelines and exit
+fn exit_with(e: MauError) -> ! {
+    eprintln!("error: {}", e);
+    exit(1);
+}
+
+fn run_tool(args: ToolArgs) {
+    match args.command {
+        Tool::Cov(CovTool::Merge { runs, output }) => match merge_runs(&runs, output.as_str()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Tool::Cov(CovTool::Map { run, output }) => match render_cov_map(run.as_str(), output.as_str()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Tool::Corpus(CorpusTool::Upgrade { dir }) => match upgrade_corpus_dir(dir.as_str()) {
+            Ok(upgraded) => println!(
+                "Upgraded {} corpus files in {} to format version {}",
+                upgraded, dir, CORPUS_FORMAT_VERSION
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Tool::Input(InputTool::Decode { input, output, callers }) => match {
+            if let Some(file) = callers {
+                register_named_callers(load_named_callers(file.as_str()).unwrap_or_else(|e| exit_with(e)));
+            }
+            decode_input_file(input.as_str())
+        } {
+            Ok(yaml) => match output {
+                Some(output) => {
+                    if let Err(e) = std::fs::write(&output, yaml) {
+                        eprintln!("failed to write {}: {}", output, e);
+                        exit(1);
+                    }
+                }
+                None => print!("{}", yaml),
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Tool::Input(InputTool::Encode { yaml, output, base, callers }) => {
+            if let Some(file) = callers {
+                register_named_callers(load_named_callers(file.as_str()).unwrap_or_else(|e| exit_with(e)));
+            }
+            match encode_input_file(yaml.as_str(), output.as_str(), base.as_deref()) {
+                Ok(()) => println!("Wrote {}", output),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+        Tool::ExportExploit { solution, output, format, network_id, balance, fork_block, fork_timestamp } => {
+            let options = ExportFormat::from_str(format.as_str()).and_then(|format| {
+                Ok(ExportOptions {
+                    format,
+                    network_id,
+                    balance: EVMU256::from_str_radix(balance.as_str(), 10).map_err(|e| format!("invalid balance {}: {}", balance, e))?,
+                    fork_block,
+                    fork_timestamp,
+                })
+            });
+            match options.and_then(|options| export_exploit(solution.as_str(), output.as_str(), &options)) {
+                Ok(report) => print!("{}", report),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+        Tool::StateDiff { parent, child, work_dir } => match state_diff_report(work_dir.as_str(), parent, child) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Tool::FetchPack { name, url } => {
+            if !PACKS.contains(&name.as_str()) {
+                eprintln!("{} is not one of the curated packs ({})", name, PACKS.join(", "));
+                exit(1);
+            }
+            match fetch_pack(name.as_str(), url.as_str()) {
+                Ok(report) => print!("{}", report),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+        Tool::Shell { run } => {
+            if let Err(e) = run_shell(run.as_str()) {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        Tool::Distill { run, output } => match distill(run.as_str(), output.as_str()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+    }
 }
 
 enum TargetType {
     Glob,
     Address,
+    Project,
+    Bytecode,
 }
 
 fn main() {
+    if env::args().nth(1).map_or(false, |arg| TOOLS.contains(&arg.as_str())) {
+        run_tool(ToolArgs::parse());
+        return;
+    }
+    if env::args()
+        .nth(1)
+        .map_or(false, |arg| [CAMPAIGN_COMMAND, EXPERIMENT_COMMAND, BENCH_COMMAND].contains(&arg.as_str()))
+    {
+        init_logging("", false);
+    }
+    if env::args().nth(1).map_or(false, |arg| arg == CAMPAIGN_COMMAND) {
+        let pattern = match env::args().nth(2) {
+            Some(pattern) => pattern,
+            None => {
+                eprintln!("usage: cli campaign '<targets>/*' [campaign arguments...]");
+                exit(1);
+            }
+        };
+        match run_campaign(pattern.as_str(), &env::args().skip(3).collect::<Vec<_>>()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if env::args().nth(1).map_or(false, |arg| arg == EXPERIMENT_COMMAND) {
+        let (runs, config_a, config_b) = match (env::args().nth(2).and_then(|runs| runs.parse::<usize>().ok()), env::args().nth(3), env::args().nth(4)) {
+            (Some(runs), Some(a), Some(b)) if runs > 0 => (runs, a, b),
+            _ => {
+                eprintln!("usage: cli experiment <runs> '<arguments of A>' '<arguments of B>' [common arguments...]");
+                exit(1);
+            }
+        };
+        match run_experiment(runs, config_a.as_str(), config_b.as_str(), &env::args().skip(5).collect::<Vec<_>>()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if env::args().nth(1).map_or(false, |arg| arg == BENCH_COMMAND) {
+        let runs = match env::args().nth(2).and_then(|runs| runs.parse::<usize>().ok()) {
+            Some(runs) if runs > 0 => runs,
+            _ => {
+                eprintln!("usage: cli bench <runs> [common arguments...]");
+                exit(1);
+            }
+        };
+        match run_schedule_bench(runs, &env::args().skip(3).collect::<Vec<_>>()) {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+
     println!("Began at {}", 
         SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -166,27 +851,76 @@ fn main() {
         .as_nanos());
 
     init_sentry();
-    let args = Args::parse();
+    let perf = env::args().nth(1).map_or(false, |arg| arg == PERF_COMMAND);
+    let args = if perf {
+        Args::parse_from(env::args().enumerate().filter(|(i, _)| *i != 1).map(|(_, arg)| arg))
+    } else if env::args().nth(1).map_or(false, |arg| arg == QUICK_COMMAND) {
+        Args::parse_from(quick_campaign_args())
+    } else {
+        Args::parse()
+    };
+    init_logging(args.log.as_str(), args.log_json);
+    let sanitizers = parse_sanitizers(args.sanitize.as_str()).expect("invalid sanitizers");
+    if let Some(bin) = args.sign_ptx {
+        let version = EVMVersion::from_str(args.evm_version.as_str()).expect("invalid EVM version");
+        let hash = sign_ptx(args.ptx_path.as_str(), bin.as_str(), &sanitizers, version).unwrap_or_else(|e| exit_with(e));
+        println!("Signed PTX kernel {} with bytecode hash {}", args.ptx_path, hash);
+        return;
+    }
     ityfuzz::telemetry::report_campaign(args.onchain, args.target.clone());
+    let targets = match parse_targets(args.target.as_str()) {
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("Invalid target: {}", e);
+            exit(1);
+        }
+    };
     let target_type: TargetType = match args.target_type {
         Some(v) => match v.as_str() {
             "glob" => Glob,
             "address" => Address,
+            "project" => Project,
+            "bytecode" => Bytecode,
             _ => {
                 panic!("Invalid target type")
             }
         },
-        None => {
-            if args.target.starts_with("0x") {
-                Address
-            } else {
-                Glob
-            }
+        None => match targets[0] {
+            TargetSpec::Address { .. } | TargetSpec::Ens { .. } => Address,
+            TargetSpec::Bytecode { .. } => Bytecode,
+            TargetSpec::Project(_) => Project,
+            TargetSpec::Glob(_) => Glob,
+        },
+    };
+    // a chain bound to the targets stands for --chain-type
+    let target_chain = match target_chain(&targets) {
+        Ok(chain) => chain,
+        Err(e) => {
+            eprintln!("Invalid target: {}", e);
+            exit(1);
         }
     };
+    let chain_type = match (args.chain_type, target_chain) {
+        (Some(chain_str), Some(chain)) if Chain::from_str(&chain_str) != Some(chain) => {
+            eprintln!("Targets are on {} but --chain-type is {}", chain.to_lowercase(), chain_str);
+            exit(1);
+        }
+        (Some(chain_str), _) => Some(chain_str),
+        (None, chain) => chain.map(|chain| chain.to_lowercase()),
+    };
+
+    let block_gas_limit = match args.block_gas_limit {
+        Some(limit) => limit,
+        None => match chain_type {
+            Some(ref chain_str) => Chain::from_str(chain_str)
+                .expect("Invalid chain type")
+                .get_block_gas_limit(),
+            None => DEFAULT_BLOCK_GAS_LIMIT,
+        },
+    };
 
     let mut onchain = if args.onchain {
-        match args.chain_type {
+        match chain_type {
             Some(chain_str) => {
                 let chain = Chain::from_str(&chain_str).expect("Invalid chain type");
                 let block_number = args.onchain_block_number.unwrap();
@@ -252,7 +986,7 @@ fn main() {
         oracles.push(flashloan_oracle.clone());
     }
 
-    if args.bug_oracle {
+    if args.bug_oracle || sanitizers.contains(&Sanitizer::Bug) {
         oracles.push(Rc::new(RefCell::new(BugOracle::new())));
 
         if args.panic_on_bug {
@@ -262,6 +996,61 @@ fn main() {
         }
     }
 
+    if sanitizers.contains(&Sanitizer::Reentrancy) {
+        oracles.push(Rc::new(RefCell::new(ReentrancyOracle::new())));
+    }
+
+    if sanitizers.contains(&Sanitizer::Selfdestruct) {
+        oracles.push(Rc::new(RefCell::new(SelfdestructOracle::new())));
+    }
+
+    if sanitizers.contains(&Sanitizer::Initializer) {
+        oracles.push(Rc::new(RefCell::new(InitializerOracle::new())));
+    }
+
+    if sanitizers.contains(&Sanitizer::StorageWrite) {
+        unsafe {
+            STORAGE_WRITE_CHECK = true;
+        }
+        oracles.push(Rc::new(RefCell::new(StorageWriteOracle::new())));
+    }
+
+    // before the call depth oracle, whose execution at the depth limit resets the calls out of gas
+    if sanitizers.contains(&Sanitizer::OogGriefing) {
+        oracles.push(Rc::new(RefCell::new(OogGriefingOracle::new())));
+    }
+
+    if sanitizers.contains(&Sanitizer::CallDepth) {
+        oracles.push(Rc::new(RefCell::new(CallDepthOracle::new())));
+    }
+
+    if sanitizers.contains(&Sanitizer::Withdrawal) {
+        unsafe {
+            WITHDRAWAL_CHECK = true;
+        }
+        oracles.push(Rc::new(RefCell::new(WithdrawalOracle::new())));
+    }
+
+    unsafe {
+        RESOLVE_SIGNATURES = args.resolve_signatures;
+        SIGNATURE_WORKERS = args.signature_workers;
+        ROUTE_BY_COST = args.route_by_cost;
+        if let Some(roles) = args.sender_roles.as_ref() {
+            SENDER_ROLES = parse_sender_roles(roles).expect("invalid --sender-roles");
+        }
+        VALUE_HINTS = args.value_hints;
+        FORCE_FEED = args.force_feed;
+        GAS_METERING = args.tx_gas || sanitizers.contains(&Sanitizer::OogGriefing);
+        REFINE_CFG = args.refine_cfg;
+        DIFFERENTIAL = args.differential;
+        BUG_PRIORS = args.bug_priors;
+        OPCODE_STATS = args.opcode_stats;
+    }
+    set_block_advance_policies(parse_policies(&args.block_advance).expect("invalid --block-advance"));
+    if !args.flashloan_provider.is_empty() {
+        set_flashloan_provider(Some(parse_provider(&args.flashloan_provider).expect("invalid --flashloan-provider")));
+    }
+
     if args.ierc20_oracle || args.pair_oracle {
         producers.push(pair_producer);
     }
@@ -271,43 +1060,119 @@ fn main() {
     }
 
     let is_onchain = onchain.is_some();
+    init_work_dir(args.work_dir.as_str());
+    if args.async_corpus {
+        if let Err(e) = init_corpus_journal() {
+            exit_with(MauError::Io { path: JOURNAL_FILE.to_string(), msg: e });
+        }
+    }
     let mut state: EVMFuzzState = FuzzState::new(args.seed);
+    let project = match target_type {
+        Project => match ityfuzz::evm::project::Project::load(args.target.as_str()) {
+            Ok(project) => Some(project),
+            Err(e) => exit_with(MauError::Project(e)),
+        },
+        _ => None,
+    };
+    if let (Some(project), true) = (&project, args.value_hints) {
+        let sources = project.contracts.iter().map(|c| c.source.clone()).collect::<HashSet<_>>();
+        for source in sources {
+            if let Ok(text) = std::fs::read_to_string(Path::new(args.target.as_str()).join(&source)) {
+                scan_natspec(&text);
+            }
+        }
+    }
+    let mut storage_layouts = match &project {
+        Some(project) => project.storage_layouts(),
+        None => HashMap::new(),
+    };
+    if let Some(file) = &args.storage_layout {
+        storage_layouts.extend(load_storage_layouts(file.as_str()));
+    }
 
     let config = Config {
         fuzzer_type: FuzzerTypes::from_str(args.fuzzer_type.as_str()).expect("unknown fuzzer"),
         contract_info: match target_type {
             Glob => ContractLoader::from_glob(args.target.as_str(), &mut state).contracts,
+            Project => {
+                let names = args
+                    .contracts
+                    .split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.trim().to_string())
+                    .collect();
+                match ContractLoader::from_project(project.as_ref().unwrap(), &names, &mut state) {
+                    Ok(loader) => loader.contracts,
+                    Err(e) => exit_with(e),
+                }
+            }
             Address => {
                 if onchain.is_none() {
                     panic!("Onchain is required for address target type");
                 }
-                let mut args_target = args.target.clone();
+                let chain = Chain::from_str(&onchain.as_ref().unwrap().chain_name).unwrap_or(Chain::ETH);
+                let ens = OnChainConfig::new_raw(
+                    args.ens_rpc.clone().unwrap_or(Chain::ETH.get_chain_rpc()),
+                    1,
+                    0,
+                    String::new(),
+                    "eth".to_string(),
+                );
+                let registry = parse_address(args.ens_registry.as_str()).expect("invalid ENS registry");
+                let mut addresses: Vec<EVMAddress> = vec![];
+                for target in &targets {
+                    match target {
+                        TargetSpec::Address { address, .. } => addresses.push(*address),
+                        TargetSpec::Ens { name, .. } => match resolve_ens(&ens, registry, name, chain) {
+                            Ok(address) => {
+                                println!("Resolved {} to {:?}", name, address);
+                                addresses.push(address);
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to resolve ENS name {}", e);
+                                exit(1);
+                            }
+                        },
+                        _ => {
+                            eprintln!("Invalid address target {}", args.target);
+                            exit(1);
+                        }
+                    }
+                }
 
                 if args.ierc20_oracle || args.flashloan {
                     const ETH_ADDRESS: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
                     const BSC_ADDRESS: &str = "0x10ed43c718714eb63d5aa57b78b54704e256024e";
-                    if "bsc" == onchain.as_ref().unwrap().chain_name {
-                        if args_target.find(BSC_ADDRESS) == None {
-                            args_target.push_str(",");
-                            args_target.push_str(BSC_ADDRESS);
-                        }
-                    } else if "eth" == onchain.as_ref().unwrap().chain_name {
-                        if args_target.find(ETH_ADDRESS) == None {
-                            args_target.push_str(",");
-                            args_target.push_str(ETH_ADDRESS);
+                    let router = match onchain.as_ref().unwrap().chain_name.as_str() {
+                        "bsc" => Some(BSC_ADDRESS),
+                        "eth" => Some(ETH_ADDRESS),
+                        _ => None,
+                    };
+                    if let Some(router) = router {
+                        let router = EVMAddress::from_str(router).unwrap();
+                        if !addresses.contains(&router) {
+                            addresses.push(router);
                         }
                     }
                 }
-                let addresses: Vec<EVMAddress> = args_target
-                    .split(",")
-                    .map(|s| EVMAddress::from_str(s).unwrap())
-                    .collect();
                 ContractLoader::from_address(
                     &mut onchain.as_mut().unwrap(),
                     HashSet::from_iter(addresses),
                 )
                 .contracts
             }
+            Bytecode => targets
+                .iter()
+                .flat_map(|target| match target {
+                    TargetSpec::Bytecode { code, deployed } => {
+                        ContractLoader::from_bytecode(code.clone(), *deployed, &mut state).contracts
+                    }
+                    _ => {
+                        eprintln!("Invalid bytecode target {}", args.target);
+                        exit(1);
+                    }
+                })
+                .collect(),
         },
         onchain,
         concolic: args.concolic,
@@ -330,20 +1195,120 @@ fn main() {
         },
         replay_file: args.replay_file,
         flashloan_oracle,
-        corpus_path: args.corpus_path,
+        corpus_path: if args.corpus_path.is_empty() {
+            corpus_dir()
+        } else {
+            args.corpus_path
+        },
         ptx_path: args.ptx_path,
         gpu_dev: args.gpu_dev,
+        runner_lib: args.runner_lib,
         run_forever: args.run_forever,
-        cov_path: args.cov_path,
+        cov_path: if args.cov_path.is_empty() {
+            format!("{}/cov.txt", stats_dir())
+        } else {
+            args.cov_path
+        },
+        scenarios: match args.scenario_file {
+            Some(file) => load_scenarios(file.as_str()),
+            None => vec![],
+        },
+        adversary: match args.adversary_file {
+            Some(file) => load_adversary(file.as_str()).unwrap_or_else(|e| exit_with(e)),
+            None => vec![],
+        },
+        price_feeds: match args.price_feeds {
+            Some(file) => load_price_feeds(file.as_str()),
+            None => vec![],
+        },
+        amm_pools: match args.amm_pools {
+            Some(file) => load_amm_pools(file.as_str()).unwrap_or_else(|e| exit_with(e)),
+            None => vec![],
+        },
+        weird_tokens: match args.weird_tokens {
+            Some(file) => load_weird_tokens(file.as_str()),
+            None => vec![],
+        },
+        block_gas_limit,
+        explore_pathological_env: args.explore_pathological_env,
+        verify_gpu_solutions: args.verify_gpu_solutions,
+        pc_trace_size: args.pc_trace_size,
+        guard_phase_instructions: args.guard_phase_instructions,
+        collector: args.collector,
+        loop_bound: args.loop_bound,
+        prepare_attacker_states: args.prepare_attacker_states,
+        gpu_memory_sanitizer: args.gpu_memory_sanitizer || sanitizers.contains(&Sanitizer::Msan),
+        entry_points: match args.entry_points {
+            Some(file) => load_entry_points(file.as_str()).unwrap_or_else(|e| exit_with(e)),
+            None => HashMap::new(),
+        },
+        token_mints: parse_token_mints(args.mint_tokens.as_str()),
+        batch_mode: BatchMode::from_str(args.gpu_batching.as_str()).expect("invalid GPU batching mode"),
+        adaptive_batch: args.adaptive_batch,
+        gpu_resident: args.gpu_resident,
+        resident_launches: args.resident_launches,
+        address_weights: AddressWeights::from_str(args.address_weights.as_str()).expect("invalid address weights"),
+        evm_version: EVMVersion::from_str(args.evm_version.as_str()).expect("invalid EVM version"),
+        force_ptx: args.force,
+        direct_to: args
+            .direct_to
+            .map(|target| DirectedTarget::from_str(target.as_str()).expect("invalid directed target")),
+        grammar: match args.grammar {
+            Some(file) => load_grammar(file.as_str()),
+            None => HashMap::new(),
+        },
+        storage_layouts,
+        cpu_workers: args.cpu_workers,
+        state_pruning: PruningPolicy::from_str(args.state_pruning.as_str()).expect("invalid state pruning policy"),
+        max_states: args.max_states,
+        caller_balance: EVMU256::from_str(args.caller_balance.as_str()).expect("invalid caller balance"),
+        power_schedule: PowerSchedule::from_str(args.power_schedule.as_str()).expect("invalid power schedule"),
+        perf_executions: if perf { Some(args.perf_executions) } else { None },
+        time_budget: args.time_budget,
+        max_host_mem: args
+            .max_host_mem
+            .map(|size| parse_mem_size(&size).expect("invalid --max-host-mem"))
+            .unwrap_or(0),
+        dictionary_dir: args.dictionary_dir,
+        warm_start: args.warm_start,
+        interface_oracles: !args.no_interface_oracles,
+        view_invariants: match args.view_invariants {
+            Some(file) => load_view_invariants(file.as_str()),
+            None => vec![],
+        },
+        goals: match args.goals {
+            Some(file) => load_goals(file.as_str()),
+            None => vec![],
+        },
+        sanitizers,
+        packs: if args.no_packs { vec![] } else { load_packs(args.pack_dir.as_str()) },
+        paused_mode: PausedMode::from_str(args.paused.as_str()).expect("invalid --paused"),
+        named_callers: match args.callers {
+            Some(file) => load_named_callers(file.as_str()).unwrap_or_else(|e| exit_with(e)),
+            None => vec![],
+        },
+        hooks: vec![],
+        targets: vec![],
     };
 
+    let mut manifest = Manifest::new(env::args().collect(), args.seed);
+    for contract in &config.contract_info {
+        manifest.add_target(contract.name.clone(), &contract.code);
+    }
+    manifest.set_ptx(config.ptx_path.as_str());
+    manifest.save();
+
     // for contract in config.contract_info.clone() {
     //     let path_to_code = format!("./code/{}", contract.name);
     //     let mut file = File::create(path_to_code).unwrap();
     //     file.write_all(&contract.code).unwrap();
     // }
     match config.fuzzer_type {
-        FuzzerTypes::CMP => evm_fuzzer(config, &mut state),
+        FuzzerTypes::CMP => {
+            if let Err(e) = evm_fuzzer(config, &mut state) {
+                exit_with(e);
+            }
+        }
         // FuzzerTypes::BASIC => basic_fuzzer(config)
         _ => {}
     }
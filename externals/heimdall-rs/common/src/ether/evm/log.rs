@@ -29,4 +29,31 @@ impl Log {
     }
 
     
+}
+
+
+// An event topic as 64 hex digits, without `0x`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventTopic(String);
+
+
+impl EventTopic {
+
+    // Parses a topic of at most 32 bytes given in hex, left padding it to 32 bytes
+    pub fn from_hex(topic: &str) -> Option<EventTopic> {
+        let topic = topic.trim_start_matches("0x");
+        if topic.len() > 64 || !topic.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(EventTopic(format!("{:0>64}", topic.to_lowercase())))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // The first 4 bytes of the topic, naming the events whose signature is not resolved
+    pub fn selector(&self) -> &str {
+        &self.0[0..8]
+    }
 }
\ No newline at end of file
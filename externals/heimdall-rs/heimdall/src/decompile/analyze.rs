@@ -10,6 +10,7 @@ use ethers::{
 use heimdall_common::{
     ether::{
         evm::{
+            log::EventTopic,
             opcodes::WrappedOpcode,
             types::{convert_bitmask, byte_size_to_type},
         },
@@ -125,9 +126,9 @@ impl VMTrace {
                     function.logic.push(format!(
                         "emit Event_{}({}{});",
                         
-                        match &logged_event.topics.first() {
-                            Some(topic) => topic.get(0..8).unwrap(),
-                            None => "00000000",
+                        match logged_event.topics.first().and_then(|topic| EventTopic::from_hex(topic)) {
+                            Some(topic) => topic.selector().to_string(),
+                            None => "00000000".to_string(),
                         },
                         match logged_event.topics.get(1..) {
                             Some(topics) => match logged_event.data.len() > 0 && topics.len() > 0 {
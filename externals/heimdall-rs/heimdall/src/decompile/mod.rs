@@ -18,7 +18,7 @@ use indicatif::ProgressBar;
 
 use clap::{AppSettings, Parser};
 use ethers::{
-    core::types::{Address},
+    core::types::{Address, U256},
     providers::{Middleware, Provider, Http},
 };
 use heimdall_common::{
@@ -27,6 +27,7 @@ use heimdall_common::{
             DisassemblerArgs,
             disassemble
         },
+        log::EventTopic,
         vm::VM
     },
     ether::signatures::*,
@@ -376,8 +377,12 @@ pub fn decompile_with_bytecode(contract_bytecode: String, output_dir: String) ->
             // resolve custom event signatures
             resolved_counter = 0;
             for (event_selector, (_, raw_event)) in analyzed_function.events.clone() {
-                decompilation_progress.set_message(format!("resolving event 0x{}", &event_selector.get(0..8).unwrap().to_string()));
-                let resolved_event_selectors = resolve_event_signature(&event_selector.get(0..64).unwrap().to_string());
+                let topic = match EventTopic::from_hex(&event_selector) {
+                    Some(topic) => topic,
+                    None => continue,
+                };
+                decompilation_progress.set_message(format!("resolving event 0x{}", topic.selector()));
+                let resolved_event_selectors = resolve_event_signature(&topic.as_str().to_string());
 
                 // only continue if we have matches
                 match resolved_event_selectors {
@@ -441,3 +446,71 @@ pub fn decompile_with_bytecode(contract_bytecode: String, output_dir: String) ->
 
     // trace.display();
 }
+
+// symbolically executes each function selector and returns its entry point and execution tree
+fn map_selectors(contract_bytecode: String, output_dir: String) -> Vec<(String, u64, VMTrace)> {
+    let (_logger, mut trace)= Logger::new("TRACE");
+
+    let decompile_call = trace.add_call(
+        0, line!(),
+        "heimdall".to_string(),
+        "map_selectors".to_string(),
+        vec!["target".to_string()],
+        "()".to_string()
+    );
+
+    let disassembled_bytecode = disassemble(contract_bytecode.clone(), output_dir);
+
+    let evm = VM::new(
+        contract_bytecode,
+        String::from("0x"),
+        String::from("0x6865696d64616c6c000000000061646472657373"),
+        String::from("0x6865696d64616c6c0000000000006f726967696e"),
+        String::from("0x6865696d64616c6c00000000000063616c6c6572"),
+        0,
+        u128::max_value(),
+    );
+
+    let mut maps = Vec::new();
+    for selector in find_function_selectors(&evm.clone(), disassembled_bytecode) {
+        let function_entry_point = resolve_entry_point(&evm.clone(), selector.clone());
+
+        // false-positive selector
+        if function_entry_point == 0 {
+            continue;
+        }
+
+        let (map, _) = map_selector(&evm.clone(), &trace, decompile_call, selector.clone(), function_entry_point);
+        maps.push((selector, function_entry_point, map));
+    }
+    maps
+}
+
+// maps each function selector to the constants its branches are guarded by, so that
+// a fuzzer can build a dictionary per function instead of a global one.
+pub fn decompile_selector_constants(contract_bytecode: String, output_dir: String) -> HashMap<String, Vec<U256>> {
+    let mut selector_constants = HashMap::new();
+    for (selector, _, map) in map_selectors(contract_bytecode, output_dir) {
+        let constants = map.guard_constants();
+        if constants.len() > 0 {
+            selector_constants.insert(selector, constants);
+        }
+    }
+
+    selector_constants
+}
+
+// returns the entry point of each function selector and the jumps taken by their execution
+// trees, i.e. the edges of the control flow graph that static analysis cannot resolve.
+pub fn decompile_jump_edges(contract_bytecode: String, output_dir: String) -> (HashMap<String, u64>, Vec<(u128, u128)>) {
+    let mut entry_points = HashMap::new();
+    let mut edges = Vec::new();
+    for (selector, entry_point, map) in map_selectors(contract_bytecode, output_dir) {
+        entry_points.insert(selector, entry_point);
+        edges.extend(map.jump_edges());
+    }
+    edges.sort();
+    edges.dedup();
+
+    (entry_points, edges)
+}
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use heimdall_common::ether::evm::log::EventTopic;
 use heimdall_common::io::{logging::{TraceFactory, Logger}, file::{short_path, write_file}};
 use indicatif::ProgressBar;
 
@@ -238,7 +239,11 @@ pub fn build_output(
                         ABIStructure::Error(
                             ErrorABI {
                                 type_: "event".to_string(),
-                                name: format!("Event_{}", event_selector.get(0..8).unwrap()),
+                                name: format!(
+                                    "Event_{}",
+                                    EventTopic::from_hex(event_selector)
+                                        .map_or(event_selector.clone(), |topic| topic.selector().to_string())
+                                ),
                                 inputs: Vec::new(),
                             }
                         )
@@ -11,7 +11,7 @@ use heimdall_common::{
     ether::{
         evm::{
             log::Log,
-            opcodes::WrappedOpcode,
+            opcodes::{WrappedInput, WrappedOpcode},
             vm::{State, VM}
         }, signatures::{ResolvedFunction, ResolvedError, ResolvedLog},
     },
@@ -410,6 +410,62 @@ pub fn recursive_map(
     vm_trace
 }
 
+// collects the raw constants a wrapped operation is computed from
+fn collect_raw_inputs(operation: &WrappedOpcode, constants: &mut Vec<U256>) {
+    for input in &operation.inputs {
+        match input {
+            WrappedInput::Raw(value) => constants.push(value.clone()),
+            WrappedInput::Opcode(inner) => collect_raw_inputs(inner, constants),
+        }
+    }
+}
+
+impl VMTrace {
+
+    // returns the constants the JUMPI conditions of the trace are compared against,
+    // e.g. `if (arg0 == 0x1337)` yields 0x1337. bitmasks and booleans are skipped.
+    pub fn guard_constants(&self) -> Vec<U256> {
+        let mut constants = Vec::new();
+        for operation in &self.operations {
+            let instruction = &operation.last_instruction;
+            if instruction.opcode == "57" {
+                if let Some(condition) = instruction.input_operations.get(1) {
+                    collect_raw_inputs(condition, &mut constants);
+                }
+            }
+        }
+        for child in &self.children {
+            constants.extend(child.guard_constants());
+        }
+
+        constants.retain(|value| {
+            // 2^n - 1 is a bitmask
+            *value > U256::one() && (*value & value.overflowing_add(U256::one()).0) != U256::zero()
+        });
+        constants.sort();
+        constants.dedup();
+        constants
+    }
+
+    // returns the (JUMP/JUMPI, destination) pairs taken by the trace and its children
+    pub fn jump_edges(&self) -> Vec<(u128, u128)> {
+        let mut edges = Vec::new();
+        for pair in self.operations.windows(2) {
+            let instruction = &pair[0].last_instruction;
+            if instruction.opcode == "56" || instruction.opcode == "57" {
+                edges.push((instruction.instruction, pair[1].last_instruction.instruction));
+            }
+        }
+        for child in &self.children {
+            if let (Some(last), Some(first)) = (self.operations.last(), child.operations.first()) {
+                edges.push((last.last_instruction.instruction, first.last_instruction.instruction));
+            }
+            edges.extend(child.jump_edges());
+        }
+        edges
+    }
+}
+
 pub fn find_balanced_parentheses(s: String) -> (usize, usize, bool) {
     let mut open = 0;
     let mut close = 0;
@@ -73,6 +73,7 @@ pub fn eval<T, H: Host<T>, S: Spec>(opcode: u8, interp: &mut Interpreter, host:
         opcode::PC => control::pc(interp, host),
         opcode::MSIZE => memory::msize(interp, host),
         opcode::JUMPDEST => control::jumpdest(interp, host),
+        opcode::MCOPY => memory::mcopy::<T, S>(interp, host),
         opcode::PUSH0 => stack::push0::<T, S>(interp, host),
         opcode::PUSH1 => stack::push::<T, 1>(interp, host),
         opcode::PUSH2 => stack::push::<T, 2>(interp, host),
@@ -1,4 +1,9 @@
-use crate::{gas, interpreter::Interpreter, primitives::U256, Host, InstructionResult};
+use crate::{
+    gas,
+    interpreter::Interpreter,
+    primitives::{Spec, SpecId::CANCUN, U256},
+    Host, InstructionResult,
+};
 
 pub fn mload<T>(interpreter: &mut Interpreter, _host: &mut dyn Host<T>) {
     gas!(interpreter, gas::VERYLOW);
@@ -35,3 +40,19 @@ pub fn msize<T>(interpreter: &mut Interpreter, _host: &mut dyn Host<T>) {
     gas!(interpreter, gas::BASE);
     push!(interpreter, U256::from(interpreter.memory.effective_len()));
 }
+
+/// EIP-5656: MCOPY - Memory copying instruction
+pub fn mcopy<T, SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host<T>) {
+    check!(interpreter, SPEC::enabled(CANCUN));
+    pop!(interpreter, dst, src, len);
+    let len = as_usize_or_fail!(interpreter, len, InstructionResult::InvalidOperandOOG);
+    gas_or_fail!(interpreter, gas::verylowcopy_cost(len as u64));
+    if len == 0 {
+        return;
+    }
+    let dst = as_usize_or_fail!(interpreter, dst, InstructionResult::InvalidOperandOOG);
+    let src = as_usize_or_fail!(interpreter, src, InstructionResult::InvalidOperandOOG);
+    memory_resize!(interpreter, core::cmp::max(dst, src), len);
+    let data = interpreter.memory.get_slice(src, len).to_vec();
+    interpreter.memory.set(dst, &data);
+}
@@ -1 +1 @@
-~/build/sema/src/standalone-ptxsema $1/$2.bin -o ./bytecode.ll --hex --dump && llvm-link ~/build/rt.o.bc ./bytecode.ll -o ./kernel.bc && llvm-dis kernel.bc -o kernel.ll && ~/wasmfuzz/ethfuzz/repo/scripts/llc-16 -mcpu=sm_86 kernel.bc -o kernel.ptx && LD_LIBRARY_PATH=/home/weimin/build/runner/ /data_HDD/weimin/EXP-Artifact/ityfuzz/cli/target/release/cli  -t "$1/*" --corpus-path "$1/corpus"   --ptx-path kernel.ptx --gpu-dev 0
+~/build/sema/src/standalone-ptxsema $1/$2.bin -o ./bytecode.ll --hex --dump && llvm-link ~/build/rt.o.bc ./bytecode.ll -o ./kernel.bc && llvm-dis kernel.bc -o kernel.ll && ~/wasmfuzz/ethfuzz/repo/scripts/llc-16 -mcpu=sm_86 kernel.bc -o kernel.ptx && /data_HDD/weimin/EXP-Artifact/ityfuzz/cli/target/release/cli -t "$1/*" --ptx-path kernel.ptx --sign-ptx $1/$2.bin && LD_LIBRARY_PATH=/home/weimin/build/runner/ /data_HDD/weimin/EXP-Artifact/ityfuzz/cli/target/release/cli  -t "$1/*" --corpus-path "$1/corpus"   --ptx-path kernel.ptx --gpu-dev 0
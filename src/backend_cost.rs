@@ -0,0 +1,210 @@
+//! Routing of the seeds between revm and the GPU runner by predicted cost (`--route-by-cost`)
+//!
+//! A GPU batch pays a latency (upload of the env, storage and seed, kernel, readback of every
+//! thread) that only pays off when it replaces enough revm executions: the mutants of a short
+//! function with small calldata run in a few microseconds each on revm, while a long path
+//! executed by every thread of a batch amortizes it. The model keeps the average path length
+//! (instructions executed on revm) of the transactions of each selector, and fits online the revm
+//! time of an execution against its path length, the upload time of a batch against the calldata
+//! size, and its kernel time against the path length. Once the GPU phase starts, the mutants of a
+//! seed are fuzzed on the GPU when the predicted batch is cheaper than as many revm executions,
+//! and on revm otherwise; [`EXPLORE_PERCENT`] of the seeds go to the other backend so that
+//! both models follow the campaign.
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Smoothing of the fits and of the path lengths, weight of a new sample
+const ALPHA: f64 = 0.05;
+
+/// Samples a fit needs before predicting
+pub const MIN_SAMPLES: f64 = 8.0;
+
+/// Percentage of the seeds routed to the backend predicted to be slower
+pub const EXPLORE_PERCENT: u64 = 5;
+
+pub static mut ROUTE_BY_COST: bool = false;
+
+static mut MODEL: Option<CostModel> = None;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Cpu,
+    Gpu,
+}
+
+/// Least squares fit of `y = a + b * x`, older samples decaying
+#[derive(Clone, Debug, Default)]
+pub struct LinearFit {
+    n: f64,
+    sx: f64,
+    sy: f64,
+    sxx: f64,
+    sxy: f64,
+    samples: f64,
+}
+
+impl LinearFit {
+    pub fn add(&mut self, x: f64, y: f64) {
+        let keep = 1.0 - ALPHA;
+        self.n = self.n * keep + 1.0;
+        self.sx = self.sx * keep + x;
+        self.sy = self.sy * keep + y;
+        self.sxx = self.sxx * keep + x * x;
+        self.sxy = self.sxy * keep + x * y;
+        self.samples += 1.0;
+    }
+
+    pub fn predict(&self, x: f64) -> Option<f64> {
+        if self.samples < MIN_SAMPLES {
+            return None;
+        }
+        let denominator = self.n * self.sxx - self.sx * self.sx;
+        // a single x seen so far, the mean is the best guess
+        let slope = if denominator.abs() <= 1e-9 * self.n * self.sxx.max(1.0) {
+            0.0
+        } else {
+            (self.n * self.sxy - self.sx * self.sy) / denominator
+        };
+        let intercept = (self.sy - slope * self.sx) / self.n;
+        Some((intercept + slope * x).max(0.0))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CostModel {
+    /// Average instructions executed by the transactions of each selector
+    path_lengths: HashMap<[u8; 4], f64>,
+    /// Seconds of a revm execution by path length
+    pub cpu: LinearFit,
+    /// Seconds of the upload of a batch by calldata size
+    pub upload: LinearFit,
+    /// Seconds of the kernel by path length
+    pub kernel: LinearFit,
+    /// Seconds of the readback of a batch by threads
+    pub readback: LinearFit,
+}
+
+impl CostModel {
+    pub fn path_length(&self, selector: &[u8; 4]) -> f64 {
+        match self.path_lengths.get(selector) {
+            Some(length) => *length,
+            // unknown function, the average one
+            None if !self.path_lengths.is_empty() => {
+                self.path_lengths.values().sum::<f64>() / self.path_lengths.len() as f64
+            }
+            None => 0.0,
+        }
+    }
+
+    pub fn record_path(&mut self, selector: [u8; 4], instructions: usize) {
+        let length = self.path_lengths.entry(selector).or_insert(instructions as f64);
+        *length = *length * (1.0 - ALPHA) + instructions as f64 * ALPHA;
+    }
+
+    /// Predicted seconds of the mutants of the seed on revm and of a GPU batch of `threads`
+    pub fn predict(&self, selector: &[u8; 4], calldata_len: usize, threads: u32) -> (Option<f64>, Option<f64>) {
+        let path = self.path_length(selector);
+        let cpu = self.cpu.predict(path).map(|exec| exec * threads as f64);
+        let gpu = match (
+            self.upload.predict(calldata_len as f64),
+            self.kernel.predict(path),
+            self.readback.predict(threads as f64),
+        ) {
+            (Some(upload), Some(kernel), Some(readback)) => Some(upload + kernel + readback),
+            _ => None,
+        };
+        (cpu, gpu)
+    }
+
+    /// Cheaper backend for the seed, `explore` picking the other one. A backend whose cost is
+    /// not known yet is tried first.
+    pub fn route(&self, selector: &[u8; 4], calldata_len: usize, threads: u32, explore: bool) -> Backend {
+        let cheaper = match self.predict(selector, calldata_len, threads) {
+            (_, None) => return Backend::Gpu,
+            (None, _) => return Backend::Cpu,
+            (Some(cpu), Some(gpu)) if gpu < cpu => Backend::Gpu,
+            _ => Backend::Cpu,
+        };
+        match (cheaper, explore) {
+            (Backend::Gpu, true) => Backend::Cpu,
+            (Backend::Cpu, true) => Backend::Gpu,
+            (backend, false) => backend,
+        }
+    }
+}
+
+fn model() -> &'static mut CostModel {
+    unsafe { MODEL.get_or_insert_with(Default::default) }
+}
+
+fn selector(calldata: &[u8]) -> Option<[u8; 4]> {
+    calldata.get(..4).and_then(|s| s.try_into().ok())
+}
+
+/// Record the path length of a transaction executed on revm
+pub fn record_path_length(calldata: &[u8], instructions: usize) {
+    if let Some(selector) = selector(calldata) {
+        model().record_path(selector, instructions);
+    }
+}
+
+/// Record the time of a revm execution
+pub fn record_cpu_execution(calldata: &[u8], elapsed: Duration) {
+    if let Some(selector) = selector(calldata) {
+        let model = model();
+        let path = model.path_length(&selector);
+        model.cpu.add(path, elapsed.as_secs_f64());
+    }
+}
+
+/// Record the phases of a GPU batch fuzzing the seed
+pub fn record_gpu_batch(calldata: &[u8], threads: u32, upload: Duration, kernel: Duration, readback: Duration) {
+    let model = model();
+    let path = selector(calldata).map_or(0.0, |selector| model.path_length(&selector));
+    model.upload.add(calldata.len() as f64, upload.as_secs_f64());
+    model.kernel.add(path, kernel.as_secs_f64());
+    model.readback.add(threads as f64, readback.as_secs_f64());
+}
+
+/// Backend the mutants of the seed are executed on
+pub fn route(calldata: &[u8], threads: u32, explore: bool) -> Backend {
+    match selector(calldata) {
+        Some(selector) => model().route(&selector, calldata.len(), threads, explore),
+        // plain transfers are cheap and do not run on the runner
+        None => Backend::Cpu,
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let mut fit = LinearFit::default();
+        for x in 0..20 {
+            fit.add(x as f64, 2.0 + 3.0 * x as f64);
+        }
+        assert!((fit.predict(100.0).unwrap() - 302.0).abs() < 1e-6);
+
+        let (short, long) = ([0x11; 4], [0x22; 4]);
+        let mut model = CostModel::default();
+        // nothing measured on the GPU yet
+        assert_eq!(model.route(&short, 36, 1024, false), Backend::Gpu);
+        model.record_path(short, 100);
+        model.record_path(long, 100000);
+        for _ in 0..10 {
+            // 10 ns per instruction on revm
+            model.cpu.add(100.0, 1e-6);
+            model.cpu.add(100000.0, 1e-3);
+            // batches of 1024 threads: 2 ms to upload and read back, kernel 1 us per 100 instructions
+            model.upload.add(36.0, 1e-3);
+            model.readback.add(1024.0, 1e-3);
+            model.kernel.add(100.0, 1e-6);
+            model.kernel.add(100000.0, 1e-3);
+        }
+        // 1 ms of revm against a 2 ms batch, vs 1 s of revm against a 3 ms batch
+        assert_eq!(model.route(&short, 36, 1024, false), Backend::Cpu);
+        assert_eq!(model.route(&long, 36, 1024, false), Backend::Gpu);
+        assert_eq!(model.route(&long, 36, 1024, true), Backend::Cpu);
+    }
+}
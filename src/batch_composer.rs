@@ -0,0 +1,203 @@
+//! Composing GPU batches from several seeds
+//!
+//! Threads of a warp execute in lockstep, so a warp whose threads take different paths is
+//! serialized (divergence). Homogeneous batching sorts the seeds of a batch by selector and
+//! staged state, so that neighbouring threads most likely share a path prefix. Mixed batching
+//! shuffles them. Both modes keep per-mode throughput and divergence stats so that the benefit
+//! can be measured on a given target.
+//!
+//! The env of the runner (`to`, timestamp, block number) is shared by the threads of a launch,
+//! so the seeds of a batch are launched in groups of the same env ([`group_by_env`]).
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use libafl::bolts::rands::Rand;
+use tracing::info;
+
+use crate::evm::input::EVMInputT;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::input::VMInputT;
+
+/// Number of threads executing in lockstep
+pub const WARP_SIZE: usize = 32;
+
+/// Print the batching stats every this many batches
+pub const BATCH_STATS_INTERVAL: usize = 100;
+
+/// How GPU batches are composed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchMode {
+    /// All threads mutate a single seed in GPU (default)
+    Seed,
+    /// Seeds of several corpus entries, grouped by selector and staged state
+    Homogeneous,
+    /// Seeds of several corpus entries, shuffled
+    Mixed,
+}
+
+impl FromStr for BatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "seed" => Ok(BatchMode::Seed),
+            "homogeneous" => Ok(BatchMode::Homogeneous),
+            "mixed" => Ok(BatchMode::Mixed),
+            _ => Err(format!("Unknown batch mode: {}", s)),
+        }
+    }
+}
+
+pub static mut BATCH_MODE: BatchMode = BatchMode::Seed;
+
+/// Key grouping seeds that likely share a path prefix: selector, then staged state
+fn path_key<I>(input: &I) -> (Vec<u8>, usize)
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    let calldata = input.get_calldata();
+    (calldata[..calldata.len().min(4)].to_vec(), input.get_state_idx())
+}
+
+/// Env shared by the threads of a launch: the target, the timestamp and the block number
+fn env_key<I>(input: &I) -> (EVMAddress, EVMU256, EVMU256)
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    let block = &input.get_vm_env().block;
+    (input.get_contract(), block.timestamp, block.number)
+}
+
+/// Split the seeds into groups of the same key, in the order of their first seed, keeping the
+/// order of the seeds within a group
+pub fn group_by<I, K: PartialEq>(inputs: Vec<I>, key: impl Fn(&I) -> K) -> Vec<Vec<I>> {
+    let mut groups: Vec<(K, Vec<I>)> = vec![];
+    for input in inputs {
+        let k = key(&input);
+        match groups.iter_mut().find(|(group_key, _)| *group_key == k) {
+            Some((_, group)) => group.push(input),
+            None => groups.push((k, vec![input])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Split the seeds of a batch into the launches sharing an env
+pub fn group_by_env<I>(inputs: Vec<I>) -> Vec<Vec<I>>
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    group_by(inputs, env_key)
+}
+
+/// Order the seeds of a batch according to the mode
+pub fn compose<I, R>(mut inputs: Vec<I>, mode: BatchMode, rand: &mut R) -> Vec<I>
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+    R: Rand,
+{
+    match mode {
+        BatchMode::Homogeneous => inputs.sort_by_cached_key(path_key),
+        BatchMode::Mixed => {
+            // Fisher-Yates
+            for i in (1..inputs.len()).rev() {
+                let j = rand.below(i as u64 + 1) as usize;
+                inputs.swap(i, j);
+            }
+        }
+        BatchMode::Seed => {}
+    }
+    inputs
+}
+
+/// Average number of distinct selectors per warp, 1.0 means no selector divergence
+pub fn warp_divergence<I>(inputs: &Vec<I>) -> f64
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    if inputs.is_empty() {
+        return 0.0;
+    }
+    let warps = inputs.chunks(WARP_SIZE).collect::<Vec<_>>();
+    let distinct: usize = warps
+        .iter()
+        .map(|warp| warp.iter().map(|i| path_key(i).0).collect::<HashSet<_>>().len())
+        .sum();
+    distinct as f64 / warps.len() as f64
+}
+
+/// Batching stats of one mode
+#[derive(Clone, Debug, Default)]
+pub struct BatchStats {
+    pub batches: usize,
+    pub threads: usize,
+    pub divergence: f64,
+    pub elapsed: Duration,
+}
+
+impl BatchStats {
+    /// Executions per second in GPU
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.threads as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Average of [`warp_divergence`] over the batches
+    pub fn avg_divergence(&self) -> f64 {
+        if self.batches == 0 {
+            return 0.0;
+        }
+        self.divergence / self.batches as f64
+    }
+}
+
+static mut HOMOGENEOUS_STATS: Option<BatchStats> = None;
+static mut MIXED_STATS: Option<BatchStats> = None;
+
+fn stats_mut(mode: BatchMode) -> Option<&'static mut BatchStats> {
+    unsafe {
+        match mode {
+            BatchMode::Homogeneous => Some(HOMOGENEOUS_STATS.get_or_insert_with(Default::default)),
+            BatchMode::Mixed => Some(MIXED_STATS.get_or_insert_with(Default::default)),
+            BatchMode::Seed => None,
+        }
+    }
+}
+
+/// Record the GPU execution time of a composed batch
+pub fn record_batch(mode: BatchMode, threads: usize, divergence: f64, elapsed: Duration) {
+    if let Some(stats) = stats_mut(mode) {
+        stats.batches += 1;
+        stats.threads += threads;
+        stats.divergence += divergence;
+        stats.elapsed += elapsed;
+        if stats.batches % BATCH_STATS_INTERVAL == 0 {
+            info!(
+                "[batch] {:?}: {} batches, {:.2} selectors/warp, {:.0} execs/sec in GPU",
+                mode,
+                stats.batches,
+                stats.avg_divergence(),
+                stats.throughput()
+            );
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_mode() {
+        assert_eq!(BatchMode::from_str("homogeneous"), Ok(BatchMode::Homogeneous));
+        assert_eq!(BatchMode::from_str("mixed"), Ok(BatchMode::Mixed));
+        assert!(BatchMode::from_str("random").is_err());
+
+        let groups = group_by(vec![(1, 'a'), (2, 'b'), (1, 'c'), (3, 'd')], |(k, _)| *k);
+        assert_eq!(groups, vec![vec![(1, 'a'), (1, 'c')], vec![(2, 'b')], vec![(3, 'd')]]);
+    }
+}
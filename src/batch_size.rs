@@ -0,0 +1,214 @@
+//! Adaptive size of the GPU batches (`--adaptive-batch`)
+//!
+//! A fixed batch of [`NJOBS`] threads leaves most of a big GPU idle on small contracts, while
+//! on large contracts (many registers / shared memory per thread) fewer threads fit at once and
+//! the extra ones only run in later waves. The runner reports how many threads of the kernel can
+//! be active at once (`cudaOccupancyMaxActiveBlocksPerMultiprocessor` times the number of SMs),
+//! which gives the initial size. The size then climbs a ladder of warp multiples, up to the
+//! allocated [`NJOBS`], towards the best measured kernel throughput, and the neighbours of the
+//! best size are probed again from time to time since the paths executed change during the
+//! campaign. The observed throughputs are written to `stats/batch_size.json`.
+use crate::batch_composer::WARP_SIZE;
+use crate::evm::config::NJOBS;
+use crate::workdir::stats_dir;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const BATCH_SIZE_FILE: &str = "batch_size.json";
+
+/// Smallest batch tried
+pub const MIN_BATCH_SIZE: u32 = 4 * WARP_SIZE as u32;
+
+/// Batches measured before deciding the next size
+pub const ADAPT_WINDOW: usize = 32;
+
+/// Probe a neighbour of the best size every this many windows
+pub const REPROBE_WINDOWS: usize = 8;
+
+pub static mut ADAPTIVE_BATCH: bool = false;
+
+static mut BATCH_SIZE: u32 = NJOBS;
+static mut SIZER: Option<BatchSizer> = None;
+
+/// Threads of the next GPU batch
+pub fn batch_size() -> u32 {
+    unsafe { BATCH_SIZE }
+}
+
+/// Sizes of the ladder, from [`MIN_BATCH_SIZE`] doubling up to `max`
+fn ladder(max: u32) -> Vec<u32> {
+    let mut sizes = vec![];
+    let mut size = MIN_BATCH_SIZE.min(max);
+    while size < max {
+        sizes.push(size);
+        size *= 2;
+    }
+    sizes.push(max);
+    sizes
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BatchSizer {
+    /// Active threads reported by the runner
+    pub occupancy_hint: u32,
+    pub sizes: Vec<u32>,
+    /// Index of the current size in the ladder
+    pub current: usize,
+    /// Kernel executions per second of each size, smoothed
+    pub throughput: BTreeMap<u32, f64>,
+    windows: usize,
+    window_batches: usize,
+    window_threads: usize,
+    window_elapsed: Duration,
+}
+
+impl BatchSizer {
+    pub fn new(occupancy_hint: u32, max: u32) -> Self {
+        let sizes = ladder(max);
+        // largest size fitting at once
+        let current = sizes.iter().rposition(|s| *s <= occupancy_hint.max(MIN_BATCH_SIZE)).unwrap_or(0);
+        Self {
+            occupancy_hint,
+            sizes,
+            current,
+            ..Default::default()
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.sizes[self.current]
+    }
+
+    pub fn best(&self) -> usize {
+        self.sizes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((i, *self.throughput.get(s)?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(self.current, |(i, _)| i)
+    }
+
+    /// Record a batch, returns the new size when it changes
+    pub fn record(&mut self, threads: usize, elapsed: Duration) -> Option<u32> {
+        self.window_batches += 1;
+        self.window_threads += threads;
+        self.window_elapsed += elapsed;
+        if self.window_batches < ADAPT_WINDOW || self.window_elapsed.is_zero() {
+            return None;
+        }
+        let measured = self.window_threads as f64 / self.window_elapsed.as_secs_f64();
+        let size = self.size();
+        let smoothed = match self.throughput.get(&size) {
+            Some(previous) => (previous + measured) / 2.0,
+            None => measured,
+        };
+        self.throughput.insert(size, smoothed);
+        self.windows += 1;
+        self.window_batches = 0;
+        self.window_threads = 0;
+        self.window_elapsed = Duration::ZERO;
+
+        let best = self.best();
+        let neighbours = [best.checked_sub(1), Some(best + 1).filter(|i| *i < self.sizes.len())];
+        let unmeasured = neighbours
+            .iter()
+            .flatten()
+            .find(|i| !self.throughput.contains_key(&self.sizes[**i]));
+        let next = match unmeasured {
+            Some(i) => *i,
+            None if self.windows % REPROBE_WINDOWS == 0 => {
+                let candidates = neighbours.iter().flatten().cloned().collect::<Vec<_>>();
+                match candidates.len() {
+                    0 => best,
+                    n => candidates[(self.windows / REPROBE_WINDOWS) % n],
+                }
+            }
+            None => best,
+        };
+        if next == self.current {
+            return None;
+        }
+        self.current = next;
+        Some(self.size())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "occupancy_hint": self.occupancy_hint,
+            "current": self.size(),
+            "best": self.sizes[self.best()],
+            "execs_per_sec": self
+                .throughput
+                .iter()
+                .map(|(size, t)| (size.to_string(), serde_json::json!(t.round())))
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+/// Start adapting the batch size from the number of threads the runner can run at once
+pub fn init_batch_size(occupancy_hint: u32) -> u32 {
+    let sizer = BatchSizer::new(occupancy_hint, NJOBS);
+    info!(
+        "[batch] {} threads of the kernel can be active at once, starting with batches of {}",
+        occupancy_hint,
+        sizer.size()
+    );
+    unsafe {
+        BATCH_SIZE = sizer.size();
+        SIZER = Some(sizer);
+    }
+    batch_size()
+}
+
+/// Record the kernel time of a GPU batch, returns the new size when it changes
+pub fn record_batch_size(threads: usize, elapsed: Duration) -> Option<u32> {
+    let sizer = unsafe { SIZER.as_mut()? };
+    let size = sizer.record(threads, elapsed)?;
+    info!(
+        "[batch] batch size {} -> {} (best {} so far)",
+        unsafe { BATCH_SIZE },
+        size,
+        sizer.sizes[sizer.best()]
+    );
+    unsafe {
+        BATCH_SIZE = size;
+    }
+    let path = Path::new(&stats_dir()).join(BATCH_SIZE_FILE);
+    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&sizer.to_json()).unwrap()) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+    Some(size)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_sizer() {
+        assert_eq!(ladder(1024), vec![128, 256, 512, 1024]);
+        let mut sizer = BatchSizer::new(300, 1024);
+        assert_eq!(sizer.size(), 256);
+
+        // throughput peaks at 512 threads
+        let elapsed = |size: u32| Duration::from_micros(match size {
+            128 => 200,
+            256 => 250,
+            512 => 300,
+            _ => 900,
+        });
+        let mut sizes = vec![];
+        for _ in 0..ADAPT_WINDOW * 20 {
+            let size = sizer.size();
+            if let Some(next) = sizer.record(size as usize, elapsed(size)) {
+                sizes.push(next);
+            }
+        }
+        assert_eq!(sizer.sizes[sizer.best()], 512);
+        assert!(sizes.contains(&1024));
+        assert_eq!(sizer.throughput.len(), 4);
+    }
+}
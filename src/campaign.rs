@@ -0,0 +1,234 @@
+//! Campaigns over many targets (`cli campaign 'contracts/*' [campaign arguments...]`)
+//!
+//! Each directory matching the pattern holds the `.abi` / `.bin` files of one target, fuzzed in
+//! turn by a campaign of its own in `<work_dir>/<name>` (a child process, as the state of the
+//! fuzzer is global) for `--time-budget` seconds, [`QUICK_TIME_BUDGET`] unless given. A pool of
+//! workers pre-processes the targets ahead of the fuzzing: the main contract is translated to a
+//! PTX kernel when ptxsema is available (see [`build_kernel`]), kept in
+//! [`KERNEL_CACHE_DIR`]`/<bytecode hash>/`, and the heimdall analyses of each contract are
+//! stored in [`crate::evm::onchain::abi_decompiler::HEIMDALL_CACHE_DIR`], so that target N+1 is
+//! ready by the time target N is fuzzed.
+use crate::evm::hex_utils::HexBytecode;
+use crate::evm::onchain::abi_decompiler::fetch_selector_constants_heimdall;
+use crate::evm::ptx_binding::bytecode_hash;
+use crate::quickstart::{build_kernel, has_flag, main_bin, QUICK_TIME_BUDGET};
+use glob::glob;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{info, warn};
+
+/// First argument of a campaign over many targets
+pub const CAMPAIGN_COMMAND: &str = "campaign";
+
+/// Kernels translated by the workers, by hash of the bytecode
+pub const KERNEL_CACHE_DIR: &str = "cache/kernels";
+
+/// Preludes (free memory pointer setup) of the code emitted by solc
+const PRELUDES: [&[u8]; 2] = [&[0x60, 0x80, 0x60, 0x40, 0x52], &[0x60, 0x60, 0x60, 0x40, 0x52]];
+
+/// Target ready to be fuzzed
+#[derive(Debug, Clone)]
+pub struct PreparedTarget {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Signed PTX kernel of the main contract, `None` to fuzz on the CPU only
+    pub ptx: Option<String>,
+}
+
+fn read_bin(path: &Path) -> Result<Vec<u8>, String> {
+    let hex_code = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    HexBytecode::from_hex(&hex_code)
+        .map(HexBytecode::into_bytes)
+        .map_err(|e| format!("invalid bytecode {}: {}", path.display(), e))
+}
+
+/// Runtime code embedded in the creation code of a solc contract, which is what the fuzzer
+/// analyses once the contract is deployed (without the values of its immutables)
+pub fn runtime_template(creation: &[u8]) -> Option<&[u8]> {
+    PRELUDES.iter().find_map(|prelude| {
+        creation
+            .windows(prelude.len())
+            .skip(1)
+            .position(|window| window == *prelude)
+            .map(|start| &creation[start + 1..])
+    })
+}
+
+/// Kernel of the bytecode, translated unless it already is
+fn cached_kernel(bin: &Path) -> Result<Option<String>, String> {
+    let hash = bytecode_hash(&read_bin(bin)?);
+    let dir = Path::new(KERNEL_CACHE_DIR).join(&hash);
+    let ptx = dir.join("kernel.ptx");
+    if ptx.exists() {
+        return Ok(Some(ptx.to_string_lossy().to_string()));
+    }
+    // translated aside then moved, concurrent campaigns never load a partial kernel
+    let tmp = Path::new(KERNEL_CACHE_DIR).join(format!("{}.tmp-{}", hash, std::process::id()));
+    fs::create_dir_all(&tmp).map_err(|e| format!("failed to create {}: {}", tmp.display(), e))?;
+    if build_kernel(bin, &tmp.to_string_lossy())?.is_none() {
+        let _ = fs::remove_dir_all(&tmp);
+        return Ok(None);
+    }
+    if fs::rename(&tmp, &dir).is_err() {
+        // translated by another campaign meanwhile
+        let _ = fs::remove_dir_all(&tmp);
+    }
+    Ok(Some(ptx.to_string_lossy().to_string()))
+}
+
+/// Translate the main contract of the target and analyse all its contracts
+pub fn prepare_target(dir: &Path) -> Result<PreparedTarget, String> {
+    let name = dir
+        .file_name()
+        .ok_or(format!("invalid target {}", dir.display()))?
+        .to_string_lossy()
+        .to_string();
+    let bins = fs::read_dir(dir)
+        .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "bin"))
+        .collect::<Vec<_>>();
+    if bins.is_empty() {
+        return Err(format!("no .bin file in {}", dir.display()));
+    }
+    for bin in &bins {
+        let code = read_bin(bin)?;
+        let runtime = runtime_template(&code).unwrap_or(&code);
+        if let Err(e) = fetch_selector_constants_heimdall(hex::encode(runtime)) {
+            warn!("[campaign] failed to analyse {}: {}", bin.display(), e);
+        }
+    }
+    let ptx = match main_bin(&dir.to_string_lossy(), &name) {
+        Some(bin) => cached_kernel(&bin).unwrap_or_else(|e| {
+            warn!("[campaign] ptxsema failed on {}, fuzzing it on the CPU only: {}", name, e);
+            None
+        }),
+        None => None,
+    };
+    Ok(PreparedTarget { name, dir: dir.to_path_buf(), ptx })
+}
+
+/// Remove the flag and return its value
+fn take_flag(args: &mut Vec<String>, long: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg == long || arg.starts_with(&format!("{}=", long)))?;
+    let arg = args.remove(idx);
+    match arg.split_once('=') {
+        Some((_, value)) => Some(value.to_string()),
+        None if idx < args.len() => Some(args.remove(idx)),
+        None => None,
+    }
+}
+
+/// Arguments of the campaign of a target, `extra` being the arguments of the whole campaign
+fn target_args(target: &PreparedTarget, work_dir: &str, extra: &[String]) -> Vec<String> {
+    let mut args = vec![
+        "-t".to_string(),
+        format!("{}/*", target.dir.to_string_lossy()),
+        "--work-dir".to_string(),
+        Path::new(work_dir).join(&target.name).to_string_lossy().to_string(),
+    ];
+    if let (Some(ptx), false) = (&target.ptx, has_flag(extra, "--ptx-path", Some("-p"))) {
+        args.extend(["--ptx-path".to_string(), ptx.clone()]);
+    }
+    if !has_flag(extra, "--time-budget", None) {
+        args.extend(["--time-budget".to_string(), QUICK_TIME_BUDGET.to_string()]);
+    }
+    args.extend(extra.iter().cloned());
+    args
+}
+
+/// Fuzz the targets matching the pattern one after another while the next ones are prepared,
+/// returns the outcome of each target
+pub fn run_campaign(pattern: &str, extra: &[String]) -> Result<String, String> {
+    let mut extra = extra.to_vec();
+    let work_dir = take_flag(&mut extra, "--work-dir").unwrap_or("workdir".to_string());
+    let targets = glob(pattern)
+        .map_err(|e| format!("invalid pattern {}: {}", pattern, e))?
+        .flatten()
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        return Err(format!("no target directory matches {}", pattern));
+    }
+    let count = targets.len();
+    let jobs = thread::available_parallelism().map_or(1, |n| n.get()).min(count);
+    info!("[campaign] {} targets, preparing them on {} threads", count, jobs);
+
+    let queue = Arc::new(Mutex::new(targets.into_iter().enumerate().collect::<VecDeque<_>>()));
+    let (sender, receiver) = channel();
+    for _ in 0..jobs {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let (idx, dir) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            let _ = sender.send((idx, dir.clone(), prepare_target(&dir)));
+        });
+    }
+    drop(sender);
+
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let mut ready = HashMap::new();
+    let mut report = String::new();
+    for idx in 0..count {
+        // wait for this target, the following ones keep being prepared meanwhile
+        while !ready.contains_key(&idx) {
+            match receiver.recv() {
+                Ok((done, dir, prepared)) => {
+                    ready.insert(done, (dir, prepared));
+                }
+                Err(_) => return Err("the preparation workers stopped".to_string()),
+            }
+        }
+        let (dir, prepared) = ready.remove(&idx).unwrap();
+        let target = match prepared {
+            Ok(target) => target,
+            Err(e) => {
+                report.push_str(&format!("{}: skipped, {}\n", dir.display(), e));
+                continue;
+            }
+        };
+        info!("[campaign] {}/{}: fuzzing {}", idx + 1, count, target.name);
+        let outcome = match Command::new(&exe).args(target_args(&target, &work_dir, &extra)).status() {
+            Ok(status) if status.success() => "done".to_string(),
+            Ok(status) => format!("exited with {}", status),
+            Err(e) => format!("failed to start: {}", e),
+        };
+        report.push_str(&format!("{}: {}\n", target.name, outcome));
+    }
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_campaign_args() {
+        let creation = hex::decode("6080604052348015600f57600080fd5b50603f80601d6000396000f3fe6080604052600080fdfea164736f6c6343000813000a").unwrap();
+        let runtime = runtime_template(&creation).unwrap();
+        assert_eq!(hex::encode(runtime), "6080604052600080fdfea164736f6c6343000813000a");
+        assert!(runtime_template(&[0x60, 0x80, 0x60, 0x40, 0x52, 0x00]).is_none());
+
+        let mut extra = vec!["--work-dir=runs".to_string(), "--run-forever".to_string()];
+        assert_eq!(take_flag(&mut extra, "--work-dir"), Some("runs".to_string()));
+        assert_eq!(extra, vec!["--run-forever".to_string()]);
+        let mut extra = vec!["--work-dir".to_string(), "runs".to_string()];
+        assert_eq!(take_flag(&mut extra, "--work-dir"), Some("runs".to_string()));
+        assert!(extra.is_empty());
+
+        let target = PreparedTarget { name: "token".to_string(), dir: PathBuf::from("targets/token"), ptx: None };
+        let args = target_args(&target, "runs", &["--time-budget".to_string(), "60".to_string()]);
+        assert_eq!(args[..4], ["-t", "targets/token/*", "--work-dir", "runs/token"]);
+        assert_eq!(args.iter().filter(|arg| *arg == "--time-budget").count(), 1);
+    }
+}
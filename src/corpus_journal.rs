@@ -0,0 +1,385 @@
+//! Asynchronous persistence of the corpus through a memory-mapped journal (`--async-corpus`)
+//!
+//! Every interesting input is written to `corpus/` (and its infant state to `states/`) right
+//! when it is found, which at high execution rates gates the fuzzer on file creation. With
+//! `--async-corpus`, the testcases stay in memory and their files are appended to
+//! `corpus.journal`, a file mapped in memory: an append is a copy into the page cache, which
+//! survives a crash of the fuzzer, and a writer thread creates the files in the background.
+//! Each record carries a checksum and is marked done once its file is written. When the arena is
+//! full while files are still pending, it grows (the overflow is mapped again, doubled) up to
+//! [`MAX_ARENA_SIZE`], past which the files are written right away; it is recycled once every
+//! record is done. A file the writer thread fails to write is retried [`WRITE_RETRIES`] times,
+//! then reported and no longer counted as pending, so that it does not keep the arena from being
+//! recycled.
+//! At startup, the records left pending by a crash are
+//! written before fuzzing, a record torn by the crash failing its checksum.
+use crate::workdir::work_path;
+use nix::sys::mman::{mmap, msync, munmap, MapFlags, MsFlags, ProtFlags};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const JOURNAL_FILE: &str = "corpus.journal";
+
+/// Initial size of the arena
+pub const ARENA_SIZE: usize = 16 << 20;
+
+/// Size past which the arena does not grow
+pub const MAX_ARENA_SIZE: usize = 1 << 30;
+
+/// Attempts to write a file after the first one fails
+pub const WRITE_RETRIES: usize = 3;
+
+/// Length, checksum and done flag (padded) of a record
+const HEADER_SIZE: usize = 12;
+
+pub static mut ASYNC_CORPUS: bool = false;
+
+static mut JOURNAL: Option<CorpusJournal> = None;
+
+/// CRC-32 (IEEE) of the data
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Payload of a record: path of the file, then its content
+fn encode_record(path: &str, content: &[u8]) -> Vec<u8> {
+    let mut payload = (path.len() as u16).to_le_bytes().to_vec();
+    payload.extend_from_slice(path.as_bytes());
+    payload.extend_from_slice(content);
+    payload
+}
+
+fn decode_record(payload: &[u8]) -> Option<(String, &[u8])> {
+    let path_len = u16::from_le_bytes(payload.get(..2)?.try_into().ok()?) as usize;
+    let path = String::from_utf8(payload.get(2..2 + path_len)?.to_vec()).ok()?;
+    Some((path, &payload[2 + path_len..]))
+}
+
+/// Append-only records in a file mapped in memory
+pub struct MmapArena {
+    file: File,
+    ptr: *mut u8,
+    len: usize,
+    /// Offset of the next record
+    head: usize,
+}
+
+impl MmapArena {
+    pub fn open(path: &str, len: usize) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("failed to open {}: {}", path, e))?;
+        let len = (file.metadata().map_err(|e| e.to_string())?.len() as usize).max(len);
+        let mut arena = Self { file, ptr: std::ptr::null_mut(), len: 0, head: 0 };
+        arena.map(len)?;
+        Ok(arena)
+    }
+
+    fn map(&mut self, len: usize) -> Result<(), String> {
+        if !self.ptr.is_null() {
+            unsafe { munmap(self.ptr as *mut _, self.len) }.map_err(|e| e.to_string())?;
+            self.ptr = std::ptr::null_mut();
+        }
+        self.file.set_len(len as u64).map_err(|e| e.to_string())?;
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                self.file.as_raw_fd(),
+                0,
+            )
+        }
+        .map_err(|e| format!("failed to map the corpus journal: {}", e))?;
+        self.ptr = ptr as *mut u8;
+        self.len = len;
+        Ok(())
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Whether a record fits without growing the arena past [`MAX_ARENA_SIZE`]
+    pub fn fits(&self, payload: &[u8]) -> bool {
+        // a zero length terminates the records
+        self.head + HEADER_SIZE + payload.len() + 4 <= self.len.max(MAX_ARENA_SIZE)
+    }
+
+    /// Append a record, growing the arena if it does not fit, and return its offset
+    pub fn append(&mut self, payload: &[u8]) -> Result<usize, String> {
+        if !self.fits(payload) {
+            return Err(format!("the corpus journal is full ({} bytes)", self.len));
+        }
+        let size = HEADER_SIZE + payload.len();
+        let needed = self.head + size + 4;
+        if needed > self.len {
+            let mut len = self.len * 2;
+            while len < needed {
+                len *= 2;
+            }
+            self.map(len.min(self.len.max(MAX_ARENA_SIZE)))?;
+        }
+        let offset = self.head;
+        let bytes = self.bytes_mut();
+        // payload first, a record torn before its header is written is never seen
+        bytes[offset + HEADER_SIZE..offset + size].copy_from_slice(payload);
+        bytes[offset + 4..offset + 8].copy_from_slice(&crc32(payload).to_le_bytes());
+        bytes[offset + 8] = 0;
+        bytes[offset..offset + 4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes[offset + size..offset + size + 4].fill(0);
+        self.head += size;
+        // schedule the writeback, without waiting for it
+        let _ = unsafe { msync(self.ptr as *mut _, self.len, MsFlags::MS_ASYNC) };
+        Ok(offset)
+    }
+
+    pub fn mark_done(&mut self, offset: usize) {
+        self.bytes_mut()[offset + 8] = 1;
+    }
+
+    /// Valid records from the start: offset, payload and whether they are done. Stops at the
+    /// end of the records or at the first torn one.
+    pub fn records(&self) -> Vec<(usize, Vec<u8>, bool)> {
+        let bytes = self.bytes();
+        let mut records = vec![];
+        let mut offset = 0;
+        while offset + HEADER_SIZE <= bytes.len() {
+            let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            let end = offset + HEADER_SIZE + len;
+            if len == 0 || end > bytes.len() {
+                break;
+            }
+            let payload = &bytes[offset + HEADER_SIZE..end];
+            if crc32(payload) != u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) {
+                break;
+            }
+            records.push((offset, payload.to_vec(), bytes[offset + 8] != 0));
+            offset = end;
+        }
+        records
+    }
+
+    /// Forget all the records
+    pub fn reset(&mut self) {
+        self.bytes_mut()[..4].fill(0);
+        self.head = 0;
+    }
+}
+
+impl Drop for MmapArena {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            let _ = unsafe { msync(self.ptr as *mut _, self.len, MsFlags::MS_SYNC) };
+            let _ = unsafe { munmap(self.ptr as *mut _, self.len) };
+        }
+    }
+}
+
+fn write_file(path: &str, content: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    File::create(path)?.write_all(content)
+}
+
+/// Write the file, retrying [`WRITE_RETRIES`] times on failure
+fn write_file_retrying(path: &str, content: &[u8]) -> std::io::Result<()> {
+    let mut attempt = 0;
+    loop {
+        match write_file(path, content) {
+            Err(e) if attempt < WRITE_RETRIES => {
+                attempt += 1;
+                warn!("failed to write {} (attempt {}): {}", path, attempt, e);
+                std::thread::sleep(Duration::from_millis(100 << attempt));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Arena and writer thread of the corpus files
+pub struct CorpusJournal {
+    arena: MmapArena,
+    pending: usize,
+    files: Option<Sender<(usize, String, Vec<u8>)>>,
+    /// Offsets of the records handled by the writer thread, and whether their file is written
+    done: Receiver<(usize, bool)>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl CorpusJournal {
+    /// Open the journal, writing the files left pending by a previous run
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut arena = MmapArena::open(path, ARENA_SIZE)?;
+        let mut recovered = 0;
+        for (_, payload, done) in arena.records() {
+            if done {
+                continue;
+            }
+            if let Some((path, content)) = decode_record(&payload) {
+                write_file(&path, content).map_err(|e| format!("failed to write {}: {}", path, e))?;
+                recovered += 1;
+            }
+        }
+        if recovered > 0 {
+            info!("Recovered {} corpus files from the journal", recovered);
+        }
+        arena.reset();
+
+        let (files, queue) = channel::<(usize, String, Vec<u8>)>();
+        let (written, done) = channel();
+        let writer = std::thread::spawn(move || {
+            for (offset, path, content) in queue {
+                // a file that cannot be written is reported and given up, its record left pending
+                // (written at the next start if the fuzzer crashes) until the arena is recycled
+                let result = write_file_retrying(&path, &content);
+                if let Err(e) = &result {
+                    warn!("giving up writing {}: {}", path, e);
+                }
+                let _ = written.send((offset, result.is_ok()));
+            }
+        });
+        Ok(Self { arena, pending: 0, files: Some(files), done, writer: Some(writer) })
+    }
+
+    fn collect_done(&mut self) {
+        while let Ok((offset, written)) = self.done.try_recv() {
+            if written {
+                self.arena.mark_done(offset);
+            }
+            self.pending -= 1;
+        }
+        if self.pending == 0 {
+            self.arena.reset();
+        }
+    }
+
+    pub fn persist(&mut self, path: &str, content: Vec<u8>) -> Result<(), String> {
+        self.collect_done();
+        let payload = encode_record(path, &content);
+        if !self.arena.fits(&payload) {
+            // the arena stopped growing with the files still pending
+            return write_file(path, &content).map_err(|e| format!("failed to write {}: {}", path, e));
+        }
+        let files = match self.files.as_ref() {
+            Some(files) => files,
+            // flushed, the writer thread is gone
+            None => return write_file(path, &content).map_err(|e| format!("failed to write {}: {}", path, e)),
+        };
+        let offset = self.arena.append(&payload)?;
+        self.pending += 1;
+        if let Err(unsent) = files.send((offset, path.to_string(), content)) {
+            // the writer thread died, the file is written here
+            self.pending -= 1;
+            let (_, _, content) = unsent.0;
+            return write_file(path, &content).map_err(|e| format!("failed to write {}: {}", path, e));
+        }
+        Ok(())
+    }
+
+    /// Wait for the pending files to be written
+    pub fn flush(&mut self) {
+        drop(self.files.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        while let Ok((offset, written)) = self.done.try_recv() {
+            if written {
+                self.arena.mark_done(offset);
+            }
+            self.pending -= 1;
+        }
+    }
+}
+
+/// Start the asynchronous persistence, after the work directory is set up
+pub fn init_corpus_journal() -> Result<(), String> {
+    let journal = CorpusJournal::open(&work_path(JOURNAL_FILE))?;
+    unsafe {
+        ASYNC_CORPUS = true;
+        JOURNAL = Some(journal);
+    }
+    Ok(())
+}
+
+/// Write the file, in the background with `--async-corpus`
+pub fn persist_file(path: &str, content: Vec<u8>) -> Result<(), String> {
+    match unsafe { JOURNAL.as_mut() } {
+        Some(journal) => journal.persist(path, content),
+        None => write_file(path, &content).map_err(|e| format!("failed to write {}: {}", path, e)),
+    }
+}
+
+/// Wait for the files persisted in the background, at the end of the campaign
+pub fn flush_corpus_journal() {
+    if let Some(journal) = unsafe { JOURNAL.as_mut() } {
+        journal.flush();
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_recovery() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let dir = std::env::temp_dir().join(format!("mau-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join(JOURNAL_FILE).to_string_lossy().to_string();
+        let (first, second) = (
+            dir.join("corpus/1_replayable").to_string_lossy().to_string(),
+            dir.join("corpus/2_replayable").to_string_lossy().to_string(),
+        );
+        {
+            // a tiny arena overflows on the second record
+            let mut arena = MmapArena::open(&journal, 64).unwrap();
+            let offset = arena.append(&encode_record(&first, b"written")).unwrap();
+            arena.mark_done(offset);
+            arena.append(&encode_record(&second, &[7u8; 100])).unwrap();
+            assert_eq!(arena.records().len(), 2);
+        }
+        // the pending record is written at the next start, the done one is not
+        let mut recovered = CorpusJournal::open(&journal).unwrap();
+        assert_eq!(fs::read(&second).unwrap(), vec![7u8; 100]);
+        assert!(!Path::new(&first).exists());
+
+        recovered.persist(&first, b"async".to_vec()).unwrap();
+        recovered.flush();
+        assert_eq!(fs::read(&first).unwrap(), b"async".to_vec());
+        // once flushed, the files are written right away
+        recovered.persist(&second, b"flushed".to_vec()).unwrap();
+        assert_eq!(fs::read(&second).unwrap(), b"flushed".to_vec());
+        assert_eq!(recovered.pending, 0);
+
+        // a file that cannot be written (its directory is a file) does not stay pending
+        let mut journal = CorpusJournal::open(&journal).unwrap();
+        let unwritable = Path::new(&first).join("3_replayable").to_string_lossy().to_string();
+        journal.persist(&unwritable, b"lost".to_vec()).unwrap();
+        journal.flush();
+        assert_eq!(journal.pending, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,410 @@
+//! Parallel CPU execution of mutants on hosts without CUDA
+//!
+//! The fuzz host is single-threaded (shared middlewares, global coverage maps), so the CPU path
+//! does not scale with cores. Like the GPU path, the pool executes a batch of mutants on
+//! lightweight VMs, one per worker thread, each owning the staged storage of its transaction and
+//! a private coverage map. The private maps are merged into a shared map with atomic bit
+//! operations, without locking. Only the mutants gaining coverage, hitting `bug()` or reaching
+//! what the pool cannot emulate (control leaks, creations, unknown contracts) are re-evaluated
+//! on the main executor, which runs the feedbacks and oracles. A sample of the other mutants
+//! (one in [`POOL_ORACLE_SAMPLE`]) is re-evaluated as well, the oracles and the comparison /
+//! dataflow feedbacks would otherwise never see a mutant that does not gain pool coverage.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use revm_interpreter::InstructionResult::{Continue, ControlLeak, Revert};
+use revm_interpreter::{
+    BytecodeLocked, CallContext, CallInputs, CallScheme, Contract, CreateInputs, Gas, Host, InstructionResult,
+    Interpreter, SelfDestructResult,
+};
+use revm_primitives::{Env, B256};
+
+use crate::evm::force_feed::{balance_of, credit, self_destruct, transfer, FORCE_FEED};
+use crate::evm::input::EVMInputT;
+use crate::evm::types::{as_u64, is_zero, EVMAddress, EVMU256};
+use crate::evm::vm::{EVMState, EVMStorage};
+use crate::generic_vm::vm_executor::MAP_SIZE;
+use crate::input::VMInputT;
+use crate::run_inspect_with_version;
+
+/// Number of worker threads, the pool is disabled with a single worker
+pub static mut CPU_WORKERS: usize = 1;
+
+/// Mutants executed by each worker per batch
+pub const POOL_BATCH_PER_WORKER: usize = 64;
+
+/// One in this many mutants not gaining coverage is still re-evaluated on the main executor
+pub const POOL_ORACLE_SAMPLE: u64 = 16;
+
+/// Nested calls followed by the pool VMs
+const POOL_CALL_DEPTH: usize = 64;
+
+/// Code of the deployed contracts, shared by the workers
+pub static mut POOL_CODE: Option<HashMap<EVMAddress, Arc<BytecodeLocked>>> = None;
+
+const EMPTY_EDGE: AtomicU8 = AtomicU8::new(0);
+/// Hit-count buckets of the edges covered by the pool so far
+pub static POOL_COVERAGE: [AtomicU8; MAP_SIZE] = [EMPTY_EDGE; MAP_SIZE];
+
+pub fn register_code(code: HashMap<EVMAddress, Arc<BytecodeLocked>>) {
+    unsafe {
+        POOL_CODE = Some(code);
+    }
+}
+
+/// Index of an edge in the pool coverage map
+pub fn edge_index(address: &EVMAddress, pc: usize, dest: usize) -> usize {
+    let mut s = DefaultHasher::new();
+    (address, pc, dest).hash(&mut s);
+    (s.finish() as usize) % MAP_SIZE
+}
+
+/// AFL hit-count bucket of an edge
+pub fn bucket(hits: u8) -> u8 {
+    match hits {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        3 => 4,
+        4..=7 => 8,
+        8..=15 => 16,
+        16..=31 => 32,
+        32..=127 => 64,
+        _ => 128,
+    }
+}
+
+/// Merge a private coverage map into [`POOL_COVERAGE`], returns whether it covers new buckets
+pub fn merge_coverage(hits: &[u8]) -> bool {
+    let mut new_coverage = false;
+    for (idx, hit) in hits.iter().enumerate() {
+        let b = bucket(*hit);
+        if b != 0 && POOL_COVERAGE[idx].fetch_or(b, Ordering::Relaxed) & b == 0 {
+            new_coverage = true;
+        }
+    }
+    new_coverage
+}
+
+/// Transaction executed in the pool, detached from the fuzz state so that it can be sent to the
+/// workers
+#[derive(Clone, Debug)]
+pub struct PoolTxn {
+    pub caller: EVMAddress,
+    pub contract: EVMAddress,
+    pub value: EVMU256,
+    pub data: Bytes,
+    pub env: Env,
+    pub storage: HashMap<EVMAddress, EVMStorage>,
+    /// Modeled balances of the staged state, see [`crate::evm::force_feed`]
+    pub balances: HashMap<EVMAddress, EVMU256>,
+}
+
+impl PoolTxn {
+    pub fn from_input<I>(input: &I) -> Self
+    where
+        I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+    {
+        Self {
+            caller: input.get_caller(),
+            contract: input.get_contract(),
+            value: input.get_txn_value().unwrap_or(EVMU256::ZERO),
+            data: Bytes::from(input.get_calldata()),
+            env: input.get_vm_env().clone(),
+            storage: input.get_state().state.clone(),
+            balances: {
+                // ether force-fed to the target and the value land before it runs, as on revm
+                let mut balances = input.get_state().balances.clone();
+                if unsafe { FORCE_FEED } {
+                    let fed = input.get_force_feed().unwrap_or(EVMU256::ZERO);
+                    let value = input.get_txn_value().unwrap_or(EVMU256::ZERO);
+                    credit(&mut balances, input.get_contract(), fed.saturating_add(value));
+                }
+                balances
+            },
+        }
+    }
+}
+
+/// Result of a transaction in the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolOutcome {
+    pub new_coverage: bool,
+    pub bug_hit: bool,
+    /// The transaction needs the fuzz host (control leak, creation, unknown contract)
+    pub unsupported: bool,
+    pub reverted: bool,
+}
+
+impl PoolOutcome {
+    /// Whether the mutant has to be re-evaluated on the main executor
+    pub fn is_interesting(&self) -> bool {
+        self.new_coverage || self.bug_hit || self.unsupported
+    }
+}
+
+/// VM of a worker thread
+pub struct PoolHost<'a> {
+    pub env: Env,
+    pub code: &'a HashMap<EVMAddress, Arc<BytecodeLocked>>,
+    pub storage: HashMap<EVMAddress, EVMStorage>,
+    pub balances: HashMap<EVMAddress, EVMU256>,
+    pub hits: Vec<u8>,
+    pub origin: EVMAddress,
+    pub depth: usize,
+    pub bug_hit: bool,
+    pub unsupported: bool,
+}
+
+impl<'a> PoolHost<'a> {
+    pub fn new(code: &'a HashMap<EVMAddress, Arc<BytecodeLocked>>) -> Self {
+        Self {
+            env: Env::default(),
+            code,
+            storage: HashMap::new(),
+            balances: HashMap::new(),
+            hits: vec![0; MAP_SIZE],
+            origin: EVMAddress::zero(),
+            depth: 0,
+            bug_hit: false,
+            unsupported: false,
+        }
+    }
+
+    /// Execute a transaction, reusing the VM of the previous one
+    pub fn execute(&mut self, txn: PoolTxn) -> PoolOutcome {
+        self.env = txn.env;
+        self.storage = txn.storage;
+        self.balances = txn.balances;
+        self.hits.iter_mut().for_each(|h| *h = 0);
+        self.origin = txn.caller;
+        self.depth = 0;
+        self.bug_hit = false;
+        self.unsupported = false;
+
+        let code = match self.code.get(&txn.contract) {
+            Some(code) => code.clone(),
+            None => {
+                return PoolOutcome {
+                    unsupported: true,
+                    ..Default::default()
+                }
+            }
+        };
+        let mut interp = Interpreter::new(
+            Contract::new_with_context_analyzed(
+                txn.data,
+                code,
+                &CallContext {
+                    address: txn.contract,
+                    caller: txn.caller,
+                    code_address: txn.contract,
+                    apparent_value: txn.value,
+                    scheme: CallScheme::Call,
+                },
+            ),
+            1e10 as u64,
+            false,
+        );
+        let ret = run_inspect_with_version!(interp, (), PoolHost, self, &mut ());
+        PoolOutcome {
+            new_coverage: merge_coverage(&self.hits),
+            bug_hit: self.bug_hit,
+            unsupported: self.unsupported || ret == ControlLeak,
+            reverted: ret == Revert,
+        }
+    }
+}
+
+impl<'a> Host<()> for PoolHost<'a> {
+    fn step(&mut self, interp: &mut Interpreter, _: &mut ()) -> InstructionResult {
+        let stack = interp.stack.data();
+        let pc = interp.program_counter();
+        let dest = match unsafe { *interp.instruction_pointer } {
+            // JUMP
+            0x56 if stack.len() >= 1 => as_u64(stack[stack.len() - 1]) as usize,
+            // JUMPI
+            0x57 if stack.len() >= 2 => {
+                if is_zero(stack[stack.len() - 2]) {
+                    pc + 1
+                } else {
+                    as_u64(stack[stack.len() - 1]) as usize
+                }
+            }
+            _ => return Continue,
+        };
+        let idx = edge_index(&interp.contract.address, pc, dest);
+        self.hits[idx] = self.hits[idx].saturating_add(1);
+        Continue
+    }
+
+    fn step_end(&mut self, _: &mut Interpreter, _: InstructionResult, _: &mut ()) -> InstructionResult {
+        Continue
+    }
+
+    fn env(&mut self) -> &mut Env {
+        &mut self.env
+    }
+
+    fn load_account(&mut self, _address: EVMAddress) -> Option<(bool, bool)> {
+        Some((true, true))
+    }
+
+    fn block_hash(&mut self, _number: EVMU256) -> Option<B256> {
+        Some(B256::zero())
+    }
+
+    fn balance(&mut self, address: EVMAddress) -> Option<(EVMU256, bool)> {
+        // same model as the fuzz host
+        if unsafe { FORCE_FEED } {
+            if let Some(balance) = balance_of(&self.balances, &address) {
+                return Some((balance, true));
+            }
+        }
+        Some((EVMU256::MAX, true))
+    }
+
+    fn code(&mut self, address: EVMAddress) -> Option<(Arc<BytecodeLocked>, bool)> {
+        match self.code.get(&address) {
+            Some(code) => Some((code.clone(), true)),
+            None => Some((Arc::new(BytecodeLocked::default()), true)),
+        }
+    }
+
+    fn code_hash(&mut self, _address: EVMAddress) -> Option<(B256, bool)> {
+        Some((B256::zero(), true))
+    }
+
+    fn sload(&mut self, address: EVMAddress, index: EVMU256) -> Option<(EVMU256, bool)> {
+        let value = self
+            .storage
+            .get(&address)
+            .and_then(|account| account.get(&index))
+            .cloned()
+            .unwrap_or(EVMU256::ZERO);
+        Some((value, true))
+    }
+
+    fn sstore(
+        &mut self,
+        address: EVMAddress,
+        index: EVMU256,
+        value: EVMU256,
+    ) -> Option<(EVMU256, EVMU256, EVMU256, bool)> {
+        self.storage.entry(address).or_default().insert(index, value);
+        Some((EVMU256::from(0), EVMU256::from(0), EVMU256::from(0), true))
+    }
+
+    fn log(&mut self, _address: EVMAddress, topics: Vec<B256>, _data: Bytes) {
+        // bug()
+        if topics.len() == 1 && topics[0].0[31] == 0x37 {
+            self.bug_hit = true;
+        }
+    }
+
+    fn selfdestruct(&mut self, address: EVMAddress, target: EVMAddress) -> Option<SelfDestructResult> {
+        if unsafe { FORCE_FEED } {
+            self_destruct(&mut self.balances, address, target);
+        }
+        Some(SelfDestructResult::default())
+    }
+
+    fn create(
+        &mut self,
+        _inputs: &mut CreateInputs,
+        _: &mut (),
+    ) -> (InstructionResult, Option<EVMAddress>, Gas, Bytes) {
+        // addresses are drawn from the fuzz state
+        self.unsupported = true;
+        (Revert, None, Gas::new(0), Bytes::new())
+    }
+
+    fn call(&mut self, input: &mut CallInputs, _: &mut ()) -> (InstructionResult, Gas, Bytes) {
+        // reentrancy is handled by the fuzz host
+        if input.contract == self.origin || self.depth >= POOL_CALL_DEPTH {
+            self.unsupported = true;
+            return (ControlLeak, Gas::new(0), Bytes::new());
+        }
+        if unsafe { FORCE_FEED }
+            && !transfer(&mut self.balances, input.transfer.source, input.transfer.target, input.transfer.value)
+        {
+            return (InstructionResult::OutOfFund, Gas::new(0), Bytes::new());
+        }
+        if let Some(code) = self.code.get(&input.context.code_address).cloned() {
+            let mut interp = Interpreter::new(
+                Contract::new_with_context_analyzed(input.input.clone(), code, &input.context),
+                1e10 as u64,
+                false,
+            );
+            self.depth += 1;
+            let ret = run_inspect_with_version!(interp, (), PoolHost, self, &mut ());
+            self.depth -= 1;
+            return (ret, Gas::new(0), interp.return_value());
+        }
+        // transfer
+        if input.input.len() < 4 {
+            return (Continue, Gas::new(0), Bytes::new());
+        }
+        // the fuzz host may fetch the code on chain or answer with a middleware
+        self.unsupported = true;
+        (Revert, Gas::new(0), Bytes::new())
+    }
+}
+
+/// Execute the transactions on `workers` threads, outcomes are in the order of the transactions
+pub fn execute_batch(txns: Vec<PoolTxn>, workers: usize) -> Vec<PoolOutcome> {
+    let code = match unsafe { POOL_CODE.as_ref() } {
+        Some(code) => code,
+        None => {
+            return vec![
+                PoolOutcome {
+                    unsupported: true,
+                    ..Default::default()
+                };
+                txns.len()
+            ]
+        }
+    };
+    let workers = workers.max(1);
+    let chunk_size = ((txns.len() + workers - 1) / workers).max(1);
+    let mut chunks = vec![];
+    let mut txns = txns.into_iter().peekable();
+    while txns.peek().is_some() {
+        chunks.push(txns.by_ref().take(chunk_size).collect::<Vec<_>>());
+    }
+    std::thread::scope(|s| {
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                s.spawn(move || {
+                    let mut host = PoolHost::new(code);
+                    chunk.into_iter().map(|txn| host.execute(txn)).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("CPU pool worker panicked"))
+            .collect()
+    })
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_coverage() {
+        let mut hits = vec![0; MAP_SIZE];
+        hits[MAP_SIZE - 1] = 1;
+        assert!(merge_coverage(&hits));
+        assert!(!merge_coverage(&hits));
+        // more hits of the same edge fall in another bucket
+        hits[MAP_SIZE - 1] = 5;
+        assert!(merge_coverage(&hits));
+        assert_eq!(bucket(200), 128);
+    }
+}
@@ -0,0 +1,146 @@
+//! Differential testing of the GPU backend (`--differential`)
+//!
+//! The kernel is translated from the bytecode by ptxsema, and a mistranslated opcode only shows
+//! up when a bug found on the GPU is not confirmed on revm. In differential mode, the fuzzer is
+//! turned into a semantics tester of the translation: only the `view` / `pure` functions of the
+//! targets are fuzzed, so every transaction is a function of its calldata and of the storage,
+//! and each batch evaluated on the GPU is evaluated again on revm. A thread whose outcome
+//! (reverted or not, and the returned data) differs from revm's is a divergence: its calldata
+//! is minimized, keeping the selector, by dropping the trailing words and then zeroing (or
+//! setting to 1) each remaining word as long as the divergence holds, and the minimized input
+//! is dumped in `artifacts/divergences`.
+use crate::evm::input::EVMInputT;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::input::VMInputT;
+use crate::runner_sys::cuReadReturnData;
+use crate::workdir::write_artifact;
+use tracing::warn;
+
+/// Whether only `view` / `pure` functions are fuzzed and checked against revm, on with
+/// `--differential`
+pub static mut DIFFERENTIAL: bool = false;
+
+/// Directory (in the artifacts of the work directory) where the divergences are dumped
+pub const DIVERGENCES_PATH: &str = "divergences";
+
+/// Number of divergences found
+pub static mut DIVERGENCES: usize = 0;
+
+/// Returned data read back from a GPU thread, longer data is truncated
+pub const RETURN_DATA_SIZE: usize = 4096;
+
+/// Executions a minimization can spend
+pub const MAX_MINIMIZATION_RUNS: usize = 256;
+
+/// Outcome of a transaction compared between the backends
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub reverted: bool,
+    pub output: Vec<u8>,
+}
+
+/// Whether the outcomes differ. The data returned by a reverted transaction is not compared, the
+/// kernel does not keep the revert reasons, and the data of revm is compared up to the
+/// [`RETURN_DATA_SIZE`] bytes read back from the GPU.
+pub fn diverges(gpu: &Outcome, cpu: &Outcome) -> bool {
+    let cpu_output = &cpu.output[..cpu.output.len().min(RETURN_DATA_SIZE)];
+    gpu.reverted != cpu.reverted || (!gpu.reverted && gpu.output != cpu_output)
+}
+
+/// Outcome of the last transaction executed by the GPU thread
+pub fn read_gpu_outcome(thread_id: u32) -> Outcome {
+    let mut output = vec![0u8; RETURN_DATA_SIZE];
+    let mut reverted = false;
+    let len = unsafe { cuReadReturnData(thread_id, output.as_mut_ptr(), output.len() as u32, &mut reverted) };
+    output.truncate(len as usize);
+    Outcome { reverted, output }
+}
+
+/// Smallest calldata found that still diverges, see the module documentation. `diverges` runs
+/// the calldata on both backends.
+pub fn minimize_calldata(calldata: &[u8], mut diverges: impl FnMut(&[u8]) -> bool) -> Vec<u8> {
+    if calldata.len() <= 4 {
+        return calldata.to_vec();
+    }
+    let mut runs = 0;
+    let mut check = |candidate: &[u8]| {
+        runs += 1;
+        runs <= MAX_MINIMIZATION_RUNS && diverges(candidate)
+    };
+    let mut minimized = calldata.to_vec();
+    // trailing words
+    while minimized.len() > 4 {
+        let len = 4 + (minimized.len() - 5) / 32 * 32;
+        if !check(&minimized[..len]) {
+            break;
+        }
+        minimized.truncate(len);
+    }
+    // simplest values of the remaining words
+    for word in (4..minimized.len()).step_by(32) {
+        let end = (word + 32).min(minimized.len());
+        for value in [0u8, 1] {
+            let mut candidate = minimized.clone();
+            candidate[word..end].fill(0);
+            candidate[end - 1] = value;
+            if candidate != minimized && check(&candidate) {
+                minimized = candidate;
+                break;
+            }
+        }
+    }
+    minimized
+}
+
+/// Dump a divergence, with the minimized calldata and the outcomes of the backends on it
+pub fn record_divergence<I>(input: &I, minimized: &[u8], gpu: &Outcome, cpu: &Outcome)
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    let count = unsafe {
+        DIVERGENCES += 1;
+        DIVERGENCES
+    };
+    warn!(
+        "[differential] GPU and revm diverge on {} ({} in total)",
+        hex::encode(minimized),
+        count
+    );
+
+    let data = format!(
+        "caller: {:?}\ncontract: {:?}\nvalue: {}\ncalldata: {}\nminimized: {}\ngpu: reverted {}, output {}\nrevm: reverted {}, output {}\n",
+        input.get_caller(),
+        input.get_contract(),
+        input.get_txn_value().unwrap_or(EVMU256::ZERO),
+        hex::encode(input.get_calldata()),
+        hex::encode(minimized),
+        gpu.reverted,
+        hex::encode(&gpu.output),
+        cpu.reverted,
+        hex::encode(&cpu.output),
+    );
+    write_artifact(DIVERGENCES_PATH, &count.to_string(), &data);
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_calldata() {
+        let word = |value: u8| [vec![0u8; 31], vec![value]].concat();
+        let selector = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        let calldata = [selector.clone(), word(5), word(7), word(9)].concat();
+        // diverges when the second word is 7
+        let minimized = minimize_calldata(&calldata, |candidate| candidate.get(36..68) == Some(&word(7)[..]));
+        assert_eq!(minimized, [selector, word(0), word(7)].concat());
+
+        let ok = Outcome { reverted: false, output: word(1) };
+        assert!(diverges(&ok, &Outcome { reverted: false, output: word(2) }));
+        assert!(!diverges(&Outcome { reverted: true, output: vec![] }, &Outcome { reverted: true, output: word(2) }));
+        // the GPU only hands back the first RETURN_DATA_SIZE bytes
+        let long = vec![3u8; RETURN_DATA_SIZE + 32];
+        let read_back = Outcome { reverted: false, output: long[..RETURN_DATA_SIZE].to_vec() };
+        assert!(!diverges(&read_back, &Outcome { reverted: false, output: long }));
+    }
+}
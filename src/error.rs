@@ -0,0 +1,138 @@
+//! Errors of the decompile and fuzz pipelines
+//!
+//! Library code returns them instead of exiting so that the pipelines can be embedded; only the
+//! CLI prints them and exits. The end of a campaign (solution found without `--run-forever`,
+//! time budget exhausted, perf measurements done) is not an error: the fuzzer and the stages
+//! unwind with [`libafl::Error::ShuttingDown`] and [`crate::fuzzers::evm_fuzzer::evm_fuzzer`]
+//! returns `Ok`.
+use crate::evm::hex_utils::HexError;
+use crate::ffi_marshal::MarshalError;
+use crate::runner_sys::RunnerError;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+#[derive(Debug)]
+pub enum MauError {
+    /// A file could not be read or written
+    Io { path: String, msg: String },
+    /// A file given on the command line is malformed
+    Config { path: String, msg: String },
+    /// The PTX kernel was not translated from the targets
+    PtxBinding(String),
+    /// The sanitizers requested are not instrumented in the PTX kernel
+    Sanitizers(String),
+    /// A Foundry / Hardhat project could not be loaded or linked
+    Project(String),
+    /// Targets are paused in the initial state (`--paused halt`)
+    Paused(Vec<String>),
+    /// No caller has one of the roles of `--sender-roles`
+    NoSenders,
+    /// The decompiler failed on a bytecode
+    Decompile(String),
+    /// A selector, topic, address or bytecode of an artifact is malformed
+    Hex(HexError),
+    /// A payload does not fit the GPU runner
+    Marshal(MarshalError),
+    /// The GPU runner could not be loaded
+    Runner(RunnerError),
+    /// The fuzzer failed
+    Fuzz(libafl::Error),
+}
+
+impl Display for MauError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MauError::Io { path, msg } => write!(f, "{}: {}", path, msg),
+            MauError::Config { path, msg } => write!(f, "{}: malformed, {}", path, msg),
+            MauError::PtxBinding(e) => write!(f, "{}, re-run ptxsema or pass --force", e),
+            MauError::Sanitizers(e) => write!(f, "--sanitize: {}", e),
+            MauError::Project(e) => write!(f, "project: {}", e),
+            MauError::Paused(contracts) => write!(
+                f,
+                "{} paused in the initial state, the calls guarded by whenNotPaused would revert. \
+                 Pass --paused explore to also fuzz the unpaused variant, or --paused ignore",
+                contracts.join(", ")
+            ),
+            MauError::NoSenders => {
+                write!(f, "no caller has one of the roles of --sender-roles, nothing can send transactions")
+            }
+            MauError::Decompile(e) => write!(f, "decompiler: {}", e),
+            MauError::Hex(e) => write!(f, "malformed artifact: {}", e),
+            MauError::Marshal(e) => write!(f, "gpu: {}", e),
+            MauError::Runner(e) => write!(f, "gpu: {}, check --runner-lib", e),
+            MauError::Fuzz(e) => write!(f, "fuzzing failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MauError {}
+
+impl From<MarshalError> for MauError {
+    fn from(e: MarshalError) -> Self {
+        MauError::Marshal(e)
+    }
+}
+
+impl From<HexError> for MauError {
+    fn from(e: HexError) -> Self {
+        MauError::Hex(e)
+    }
+}
+
+impl From<RunnerError> for MauError {
+    fn from(e: RunnerError) -> Self {
+        MauError::Runner(e)
+    }
+}
+
+impl From<libafl::Error> for MauError {
+    fn from(e: libafl::Error) -> Self {
+        MauError::Fuzz(e)
+    }
+}
+
+impl MauError {
+    pub fn io(path: &str, e: std::io::Error) -> Self {
+        MauError::Io { path: path.to_string(), msg: e.to_string() }
+    }
+}
+
+pub type MauResult<T> = Result<T, MauError>;
+
+/// Read a JSON file given on the command line
+pub fn load_json<T: DeserializeOwned>(path: &str) -> MauResult<T> {
+    let data = fs::read_to_string(path).map_err(|e| MauError::io(path, e))?;
+    serde_json::from_str(&data).map_err(|e| MauError::Config { path: path.to_string(), msg: e.to_string() })
+}
+
+/// `Ok` if the campaign stopped by itself rather than failed
+pub fn campaign_result<T>(result: Result<T, libafl::Error>) -> MauResult<()> {
+    match result {
+        Ok(_) | Err(libafl::Error::ShuttingDown) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors() {
+        let e: MauError = MarshalError::SeedTooLarge { calldata: 5000, max: 4000 }.into();
+        assert!(matches!(e, MauError::Marshal(MarshalError::SeedTooLarge { .. })));
+        assert!(e.to_string().starts_with("gpu: calldata of 5000 bytes"));
+        assert!(MauError::io("a.abi", std::io::Error::from(std::io::ErrorKind::NotFound))
+            .to_string()
+            .starts_with("a.abi: "));
+        assert!(matches!(load_json::<Vec<u64>>("/nonexistent.json"), Err(MauError::Io { .. })));
+        assert!(MauError::Paused(vec!["Vault".to_string()]).to_string().starts_with("Vault paused"));
+
+        assert!(campaign_result::<usize>(Ok(0)).is_ok());
+        assert!(campaign_result::<usize>(Err(libafl::Error::ShuttingDown)).is_ok());
+        assert!(matches!(
+            campaign_result::<usize>(Err(libafl::Error::illegal_state("x"))),
+            Err(MauError::Fuzz(_))
+        ));
+    }
+}
@@ -2,7 +2,11 @@
 
 use crate::evm::abi::ABILossyType::{TArray, TDynamic, TEmpty, TUnknown, T256};
 use crate::evm::abi::BasicVarType::{*};
+use crate::evm::address_pool::pick_address;
+use crate::evm::bytes_mutation::{mutate_dynamic, BYTES_MUTATION_PROB, STRING_MUTATION_PROB};
 use crate::evm::mutation_utils::{byte_mutator, byte_mutator_with_expansion};
+use crate::evm::provenance::{clear_hint, mutated_provenance, ArgProvenance};
+use crate::evm::token_flow::mutate_amount;
 use crate::generic_vm::vm_state::VMStateT;
 use crate::state::{HasCaller, HasItyState};
 use bytes::Bytes;
@@ -21,7 +25,8 @@ use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter, Write};
 use std::ops::{Deref, DerefMut};
 use std::convert::TryFrom;
-use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::types::{caller_alias, EVMAddress, EVMU256};
+use crate::evm::vm::EVMStorage;
 
 use super::concolic::concolic_host::Expr;
 
@@ -29,6 +34,9 @@ use super::concolic::concolic_host::Expr;
 /// Mapping from known signature to function name
 static mut FUNCTION_SIG: Lazy<HashMap<[u8; 4], String>> = Lazy::new(|| HashMap::new());
 
+/// Mapping from known signature to the (name, type) of its args, names may be empty
+static mut FUNCTION_ARGS: Lazy<HashMap<[u8; 4], Vec<(String, String)>>> = Lazy::new(|| HashMap::new());
+
 /// todo: remove this
 static mut CONCOLIC_COUNTER: u64 = 0;
 
@@ -261,6 +269,43 @@ impl BoxedABI {
         }
     }
 
+    /// Get the function name (or hash if the name is unknown), empty when resuming from a control leak
+    pub fn get_func_name(&self) -> String {
+        if self.function == [0; 4] {
+            return "".to_string();
+        }
+        unsafe {
+            FUNCTION_SIG
+                .get(&self.function)
+                .unwrap_or(&hex::encode(self.function))
+                .clone()
+        }
+    }
+
+    /// Convert function hash and args to a human-friendly string, with the args decoded by
+    /// name and type when the ABI of the function is known (e.g., `transfer(to=attacker1, amount=1e18)`)
+    pub fn to_decoded_string(&self) -> String {
+        if self.function == [0; 4] {
+            return self.to_string();
+        }
+        let args = unsafe { FUNCTION_ARGS.get(&self.function).cloned().unwrap_or_default() };
+        let mut b = self.clone();
+        let values = match b.b.deref_mut().as_any().downcast_mut::<AArray>() {
+            Some(aarray) if !aarray.dynamic_size => aarray.data.clone(),
+            _ => vec![self.clone()],
+        };
+        let decoded = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| match args.get(i) {
+                Some((name, ty)) if !name.is_empty() => format!("{}={}", name, decode_arg(v, ty)),
+                Some((_, ty)) => decode_arg(v, ty),
+                None => decode_arg(v, ""),
+            })
+            .join(", ");
+        format!("{}({})", self.get_func_name(), decoded)
+    }
+
     /// Convert function hash and args to string (for debugging)
     pub fn to_string(&self) -> String {
         if self.function == [0; 4] {
@@ -321,12 +366,14 @@ where
                 is_address: false,
                 dont_mutate: false,
                 is_bytes: false,
+                provenance: Default::default(),
             })),
             1 => BoxedABI::new(Box::new(A256 {
                 data: state.get_rand_address().0.into(),
                 is_address: true,
                 dont_mutate: false,
                 is_bytes: false,
+                provenance: Default::default(),
             })),
             _ => unreachable!(),
         }
@@ -339,6 +386,8 @@ where
             0 => BoxedABI::new(Box::new(ADynamic {
                 data: vec![state.rand_mut().below(255) as u8; vec_size],
                 multiplier: 32,
+                is_string: false,
+                provenance: Default::default(),
             })),
             // tuple
             1 => BoxedABI::new(Box::new(AArray {
@@ -398,7 +447,7 @@ impl BoxedABI {
     pub fn mutate_with_vm_slots<Loc, Addr, VS, S>(
         &mut self,
         state: &mut S,
-        vm_slots: Option<HashMap<EVMU256, EVMU256>>,
+        vm_slots: Option<EVMStorage>,
     ) -> MutationResult
     where
         S: State
@@ -422,15 +471,24 @@ impl BoxedABI {
                     return MutationResult::Skipped;
                 }
                 if a256.is_address {
-                    if state.rand_mut().below(100) < 90 {
-                        a256.data = state.get_rand_address().0.to_vec();
-                    } else {
-                        a256.data = [0; 20].to_vec();
-                    }
-
+                    a256.data = pick_address(state).0.to_vec();
+                    a256.provenance = ArgProvenance::Address;
+                    MutationResult::Mutated
+                } else if let Some(amount) = (a256.data.len() == 32 && !a256.is_bytes)
+                    .then(|| mutate_amount(state))
+                    .flatten()
+                {
+                    let data: [u8; 32] = amount.to_be_bytes();
+                    a256.data = data.to_vec();
+                    a256.provenance = ArgProvenance::Amount;
                     MutationResult::Mutated
                 } else {
-                    byte_mutator(state, a256, vm_slots)
+                    clear_hint();
+                    let result = byte_mutator(state, a256, vm_slots);
+                    if result == MutationResult::Mutated {
+                        a256.provenance = mutated_provenance(&a256.data);
+                    }
+                    result
                 }
             }
             // mutate dynamic args
@@ -442,7 +500,20 @@ impl BoxedABI {
                     .downcast_mut::<ADynamic>()
                     .unwrap();
                 // self.b.downcast_ref::<A256>().unwrap().mutate(state);
-                byte_mutator_with_expansion(state, adyn, vm_slots)
+                let structured_prob = if adyn.is_string { STRING_MUTATION_PROB } else { BYTES_MUTATION_PROB };
+                if state.rand_mut().below(100) < structured_prob {
+                    let result = mutate_dynamic(state, &mut adyn.data, adyn.is_string);
+                    if result == MutationResult::Mutated {
+                        adyn.provenance = ArgProvenance::Structured;
+                        return result;
+                    }
+                }
+                clear_hint();
+                let result = byte_mutator_with_expansion(state, adyn, vm_slots);
+                if result == MutationResult::Mutated {
+                    adyn.provenance = mutated_provenance(&adyn.data);
+                }
+                result
             }
             // mutate tuple/array args
             TArray => {
@@ -587,6 +658,9 @@ pub struct A256 {
     /// whether this arg should not be mutated
     pub dont_mutate: bool,
     pub is_bytes: bool,
+    /// where the current value comes from, see [`crate::evm::provenance`]
+    #[serde(default)]
+    pub provenance: ArgProvenance,
 }
 
 impl Input for A256 {
@@ -688,9 +762,15 @@ impl ABI for A256 {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ADynamic {
     /// data representing the arg
-    data: Vec<u8>,
+    pub(crate) data: Vec<u8>,
     /// multiplier used to round up the size of the data
-    multiplier: usize,
+    pub(crate) multiplier: usize,
+    /// whether the arg is a `string` (rather than `bytes`)
+    #[serde(default)]
+    pub(crate) is_string: bool,
+    /// where the current value comes from, see [`crate::evm::provenance`]
+    #[serde(default)]
+    pub provenance: ArgProvenance,
 }
 
 impl Input for ADynamic {
@@ -1256,6 +1336,106 @@ impl ABI for AUnknown {
     }
 }
 
+/// Register the names and types of the args of a function, to decode its calls
+pub fn register_function_args(function: [u8; 4], args: Vec<(String, String)>) {
+    unsafe {
+        FUNCTION_ARGS.insert(function, args);
+    }
+}
+
+/// Register the name of a function, to print its calls
+pub fn register_function_name(function: [u8; 4], name: String) {
+    unsafe {
+        FUNCTION_SIG.insert(function, name);
+    }
+}
+
+/// Names and types of the args of a function, if its ABI is known
+pub fn function_args(function: &[u8; 4]) -> Option<Vec<(String, String)>> {
+    unsafe { FUNCTION_ARGS.get(function).cloned() }
+}
+
+/// Format an integer in decimal, with an exponent when it is a round multiple of a power of ten
+/// (e.g., `1e18`)
+pub fn format_uint(v: EVMU256) -> String {
+    let s = v.to_string();
+    let zeros = s.len() - s.trim_end_matches('0').len();
+    if zeros >= 6 && v != EVMU256::ZERO {
+        format!("{}e{}", &s[..s.len() - zeros], zeros)
+    } else {
+        s
+    }
+}
+
+/// Format an address with its alias (e.g., `attacker1`), or abbreviated (e.g., `0x8ef5…79dd`)
+pub fn format_address(address: &EVMAddress) -> String {
+    if let Some(alias) = caller_alias(address) {
+        return alias;
+    }
+    let hex = hex::encode(address.0);
+    format!("0x{}…{}", &hex[..4], &hex[36..])
+}
+
+/// Decode an arg according to its ABI type (e.g., `uint256`, `address[]`, `(bool,bytes)`)
+fn decode_arg(arg: &BoxedABI, ty: &str) -> String {
+    let mut arg = arg.clone();
+    let any = arg.b.deref_mut().as_any();
+    if let Some(a256) = any.downcast_mut::<A256>() {
+        let word = EVMU256::try_from_be_slice(&a256.data).unwrap_or_default();
+        return if a256.is_address || ty == "address" {
+            format_address(&EVMAddress::from_slice(&word.to_be_bytes::<32>()[12..]))
+        } else if ty == "bool" {
+            (word != EVMU256::ZERO).to_string()
+        } else if ty.starts_with("int") && word.bit(255) {
+            format!("-{}", format_uint(EVMU256::ZERO.wrapping_sub(word)))
+        } else if ty.starts_with("uint") || ty.starts_with("int") {
+            format_uint(word)
+        } else {
+            vec_to_hex(&a256.data)
+        };
+    }
+    if let Some(adyn) = any.downcast_mut::<ADynamic>() {
+        return match String::from_utf8(adyn.data.clone()) {
+            Ok(s) if ty == "string" => format!("{:?}", s),
+            _ => vec_to_hex(&adyn.data),
+        };
+    }
+    if let Some(aarray) = any.downcast_mut::<AArray>() {
+        if let Some(elem_ty) = ty.strip_suffix("[]") {
+            return format!(
+                "[{}]",
+                aarray.data.iter().map(|x| decode_arg(x, elem_ty)).join(", ")
+            );
+        }
+        // tuple or fixed size array
+        let tys = match ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            Some(inner) => split_with_parenthesis(inner),
+            None => vec![ty.split('[').next().unwrap_or_default().to_string()],
+        };
+        let elems = aarray
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, x)| decode_arg(x, tys.get(i).or(tys.last()).map_or("", |t| t.as_str())))
+            .join(", ");
+        return if ty.starts_with('(') { format!("({})", elems) } else { format!("[{}]", elems) };
+    }
+    if let Some(aunknown) = any.downcast_mut::<AUnknown>() {
+        return decode_arg(&aunknown.concrete, ty);
+    }
+    arg.b.to_string()
+}
+
+/// Name of the function with the given hash, or the hash if the name is unknown
+pub fn function_sig_name(function: &[u8; 4]) -> String {
+    unsafe {
+        FUNCTION_SIG
+            .get(function)
+            .cloned()
+            .unwrap_or(hex::encode(function))
+    }
+}
+
 /// Create a [`BoxedABI`] with default arg given the ABI type in string
 pub fn get_abi_type_boxed(abi_name: &String) -> BoxedABI {
     return BoxedABI {
@@ -1381,26 +1561,33 @@ fn get_abi_type_basic(
             is_address: false,
             dont_mutate: false,
             is_bytes: false,
+            provenance: Default::default(),
         }),
         "address" => Box::new(A256 {
             data: with_address.to_owned().unwrap_or(vec![0; 20]),
             is_address: true,
             dont_mutate: false,
             is_bytes: false,
+            provenance: Default::default(),
         }),
         "bool" => Box::new(A256 {
             data: vec![0; 1],
             is_address: false,
             dont_mutate: false,
             is_bytes: false,
+            provenance: Default::default(),
         }),
         "bytes" => Box::new(ADynamic {
             data: Vec::new(),
             multiplier: 32,
+            is_string: false,
+            provenance: Default::default(),
         }),
         "string" => Box::new(ADynamic {
             data: Vec::new(),
             multiplier: 32,
+            is_string: true,
+            provenance: Default::default(),
         }),
         _ => {
             if abi_name.starts_with("uint") {
@@ -1427,6 +1614,7 @@ fn get_abi_type_basic(
                     is_address: false,
                     dont_mutate: false,
                     is_bytes: true,
+                    provenance: Default::default(),
                 });
             } else if abi_name.len() == 0 {
                 return Box::new(AEmpty {});
@@ -1575,4 +1763,25 @@ mod tests {
             hex::encode(abi.get_bytes())
         );
     }
+
+    #[test]
+    fn test_decoded_string() {
+        assert_eq!(format_uint(EVMU256::from(1_000_000_000_000_000_000u64)), "1e18");
+        assert_eq!(format_uint(EVMU256::from(1234)), "1234");
+
+        let owner = EVMAddress::from_slice(&[0x11; 20]);
+        crate::evm::types::register_caller_alias(owner, "owner");
+        let mut abi = get_abi_type_boxed(&String::from("(address,uint256)"));
+        abi.set_func_with_name([0xa9, 0x05, 0x9c, 0xbb], "transfer".to_string());
+        register_function_args(
+            [0xa9, 0x05, 0x9c, 0xbb],
+            vec![("to".to_string(), "address".to_string()), ("amount".to_string(), "uint256".to_string())],
+        );
+        let mut calldata = vec![0xa9, 0x05, 0x9c, 0xbb];
+        calldata.extend([0; 12]);
+        calldata.extend([0x11; 20]);
+        calldata.extend(EVMU256::from(2_000_000_000_000_000_000u64).to_be_bytes::<32>());
+        abi.set_bytes(calldata);
+        assert_eq!(abi.to_decoded_string(), "transfer(to=owner, amount=2e18)");
+    }
 }
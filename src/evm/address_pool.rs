@@ -0,0 +1,158 @@
+/// Stratified pools for address-typed args
+///
+/// Address args are drawn from semantically distinct classes, which trigger very different code
+/// paths (`isContract` checks, `address(0)` guards, self-calls, precompile calls):
+/// - known EOAs (the callers)
+/// - deployed contracts
+/// - `address(0)`
+/// - `address(this)`, the contract the input calls
+/// - precompiles `0x1` to `0x9`
+///
+/// The weights of the classes are set with `--address-weights eoa=40,contract=30,...`.
+use crate::evm::types::EVMAddress;
+use crate::state::HasCaller;
+use libafl::prelude::{HasRand, Rand};
+use std::str::FromStr;
+
+/// Number of the highest precompile (`0x9`, blake2f)
+pub const PRECOMPILE_COUNT: u64 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressWeights {
+    pub eoa: u64,
+    pub contract: u64,
+    pub zero: u64,
+    pub this: u64,
+    pub precompile: u64,
+}
+
+impl Default for AddressWeights {
+    fn default() -> Self {
+        Self {
+            eoa: 45,
+            contract: 35,
+            zero: 10,
+            this: 5,
+            precompile: 5,
+        }
+    }
+}
+
+impl FromStr for AddressWeights {
+    type Err = String;
+
+    /// Parse `class=weight` pairs, the classes not listed keep their default weight
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (class, weight) = pair
+                .split_once('=')
+                .ok_or(format!("expected class=weight, got {}", pair))?;
+            let weight = weight
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid weight {}: {}", weight, e))?;
+            match class.trim() {
+                "eoa" => weights.eoa = weight,
+                "contract" => weights.contract = weight,
+                "zero" => weights.zero = weight,
+                "this" => weights.this = weight,
+                "precompile" => weights.precompile = weight,
+                other => return Err(format!("unknown address class {}", other)),
+            }
+        }
+        if weights.total() == 0 {
+            return Err("all address weights are zero".to_string());
+        }
+        Ok(weights)
+    }
+}
+
+impl AddressWeights {
+    pub fn total(&self) -> u64 {
+        self.eoa + self.contract + self.zero + self.this + self.precompile
+    }
+}
+
+pub static mut ADDRESS_WEIGHTS: AddressWeights = AddressWeights {
+    eoa: 45,
+    contract: 35,
+    zero: 10,
+    this: 5,
+    precompile: 5,
+};
+
+/// Deployed contracts (targets and contracts fetched on-chain)
+pub static mut CONTRACT_ADDRESSES: Vec<EVMAddress> = Vec::new();
+
+/// Contract called by the input whose args are being mutated, i.e., `address(this)`
+pub static mut CURRENT_TARGET: Option<EVMAddress> = None;
+
+pub fn register_contract_address(address: EVMAddress) {
+    unsafe {
+        if !CONTRACT_ADDRESSES.contains(&address) {
+            CONTRACT_ADDRESSES.push(address);
+        }
+    }
+}
+
+/// Set the contract called by the input whose args are going to be mutated
+pub fn set_current_target(address: EVMAddress) {
+    unsafe {
+        CURRENT_TARGET = Some(address);
+    }
+}
+
+/// Precompile with the given index (1-based)
+pub fn precompile(idx: u64) -> EVMAddress {
+    let mut address = EVMAddress::zero();
+    address.0[19] = idx as u8;
+    address
+}
+
+/// Draw an address for an address-typed arg according to [`ADDRESS_WEIGHTS`]
+pub fn pick_address<S>(state: &mut S) -> EVMAddress
+where
+    S: HasRand + HasCaller<EVMAddress>,
+{
+    let weights = unsafe { ADDRESS_WEIGHTS };
+    let mut roll = state.rand_mut().below(weights.total());
+    if roll < weights.eoa {
+        return state.get_rand_caller();
+    }
+    roll -= weights.eoa;
+    if roll < weights.contract {
+        let contracts = unsafe { &CONTRACT_ADDRESSES };
+        return if contracts.is_empty() {
+            state.get_rand_address()
+        } else {
+            contracts[state.rand_mut().below(contracts.len() as u64) as usize]
+        };
+    }
+    roll -= weights.contract;
+    if roll < weights.zero {
+        return EVMAddress::zero();
+    }
+    roll -= weights.zero;
+    if roll < weights.this {
+        if let Some(target) = unsafe { CURRENT_TARGET } {
+            return target;
+        }
+    }
+    precompile(state.rand_mut().below(PRECOMPILE_COUNT) + 1)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_weights() {
+        let weights = AddressWeights::from_str("eoa=10, zero=0").unwrap();
+        assert_eq!(weights.eoa, 10);
+        assert_eq!(weights.zero, 0);
+        assert_eq!(weights.contract, AddressWeights::default().contract);
+        assert!(AddressWeights::from_str("admin=1").is_err());
+        assert!(AddressWeights::from_str("eoa=0,contract=0,zero=0,this=0,precompile=0").is_err());
+        assert_eq!(precompile(9).0[19], 9);
+    }
+}
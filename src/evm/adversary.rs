@@ -0,0 +1,159 @@
+/// Mempool-style adversary injecting third-party transactions between the fuzzer transactions
+///
+/// The adversary is a list of plausible transactions of other actors (e.g., ERC-20 transfers,
+/// price updates of an oracle by its keeper) loaded from `--adversary-file`. An input can carry
+/// one of them ([`EVMInput::adversary`]), which is then executed right before the input on the
+/// same state, as if it landed in the block first. This exposes frontrunning / sandwich
+/// sensitive logic that a single actor sequence misses. The injected transaction is part of the
+/// replayable trace.
+use crate::evm::corpus_format::{CorpusTxn, TxnKind};
+use crate::evm::interfaces::encode_call;
+use crate::evm::types::{EVMAddress, EVMU256};
+use bytes::Bytes;
+use crate::error::{load_json, MauError, MauResult};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+/// A transaction of another actor, either a raw call or an ERC-20 transfer
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AdversaryTxn {
+    pub name: String,
+    pub caller: EVMAddress,
+    pub contract: EVMAddress,
+    /// Hex encoded calldata of a raw call
+    #[serde(default)]
+    pub calldata: Option<String>,
+    /// Recipient of an ERC-20 `transfer` of `amount` tokens of `contract`
+    #[serde(default)]
+    pub transfer_to: Option<EVMAddress>,
+    #[serde(default)]
+    pub amount: EVMU256,
+    #[serde(default)]
+    pub value: EVMU256,
+}
+
+impl AdversaryTxn {
+    /// Calldata of the transaction, empty when the raw calldata is not hex (rejected by
+    /// [`load_adversary`])
+    pub fn calldata(&self) -> Bytes {
+        match (&self.transfer_to, &self.calldata) {
+            (Some(to), _) => encode_call(TRANSFER, &[*to], &[self.amount.to_be_bytes()]),
+            (None, Some(calldata)) => Bytes::from(raw_calldata(calldata).unwrap_or_default()),
+            (None, None) => Bytes::new(),
+        }
+    }
+
+    /// The transaction in a replayable trace (see [`crate::tracer::TxnTrace::to_corpus_txns`])
+    pub fn to_corpus_txn(&self) -> CorpusTxn {
+        let calldata = self.calldata();
+        CorpusTxn {
+            kind: TxnKind::Abi,
+            caller: self.caller,
+            contract: self.contract,
+            data: if calldata.is_empty() { "00".to_string() } else { hex::encode(calldata) },
+            value: self.value,
+            liquidation_percent: 0,
+            warp_to: 0,
+            repeat: 1,
+            reentrancy_limit: u32::MAX,
+            step: false,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
+            function: None,
+            meta: None,
+        }
+    }
+}
+
+/// Transactions of the adversary, indexed by [`EVMInput::adversary`]
+pub static mut ADVERSARY_TXNS: Vec<AdversaryTxn> = Vec::new();
+
+/// Probability (in percent) that a mutation changes the adversary transaction of the input
+pub const ADVERSARY_MUTATION_PROBABILITY: u64 = 5;
+
+fn raw_calldata(calldata: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(calldata.trim_start_matches("0x"))
+}
+
+/// Load the adversary from a JSON file containing an array of [`AdversaryTxn`]
+pub fn load_adversary(path: &str) -> MauResult<Vec<AdversaryTxn>> {
+    let txns: Vec<AdversaryTxn> = load_json(path)?;
+    for txn in &txns {
+        if let Some(Err(e)) = txn.calldata.as_deref().map(raw_calldata) {
+            return Err(MauError::Config {
+                path: path.to_string(),
+                msg: format!("calldata of {}: {}", txn.name, e),
+            });
+        }
+    }
+    Ok(txns)
+}
+
+pub fn register_adversary(txns: Vec<AdversaryTxn>) {
+    for txn in &txns {
+        info!("Registered adversary transaction: {}", txn.name);
+    }
+    unsafe {
+        ADVERSARY_TXNS = txns;
+    }
+}
+
+pub fn adversary_count() -> usize {
+    unsafe { ADVERSARY_TXNS.len() }
+}
+
+pub fn adversary_txn(idx: usize) -> Option<&'static AdversaryTxn> {
+    unsafe { ADVERSARY_TXNS.get(idx) }
+}
+
+/// Prefix of the pretty transaction of an input carrying an adversary transaction
+pub fn adversary_prefix(idx: Option<usize>) -> String {
+    match idx {
+        Some(idx) => format!("(adversary #{}) ", idx),
+        None => String::new(),
+    }
+}
+
+/// Adversary transaction of a pretty transaction, see [`adversary_prefix`]
+pub fn parse_adversary_prefix(pretty_txn: &str) -> Option<usize> {
+    pretty_txn
+        .strip_prefix("(adversary #")?
+        .split(')')
+        .next()?
+        .parse::<usize>()
+        .ok()
+}
+
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_adversary_txn() {
+        let txns: Vec<AdversaryTxn> = serde_json::from_str(
+            r#"[{"name": "whale transfer", "caller": "0x0000000000000000000000000000000000000001",
+                 "contract": "0x0000000000000000000000000000000000000002",
+                 "transfer_to": "0x0000000000000000000000000000000000000003", "amount": "0x64"}]"#,
+        )
+        .unwrap();
+        let calldata = txns[0].calldata();
+        assert_eq!(calldata[..4], TRANSFER);
+        assert_eq!(calldata.len(), 68);
+        assert_eq!(calldata[67], 100);
+        let txn = txns[0].to_corpus_txn();
+        assert_eq!(txn.data, hex::encode(&calldata));
+        let line = txn.to_legacy_line();
+        assert!(line.starts_with("abi 0x0000000000000000000000000000000000000001"));
+        assert_eq!(
+            EVMAddress::from_str(line.split(' ').nth(2).unwrap()).unwrap(),
+            txns[0].contract
+        );
+
+        assert_eq!(parse_adversary_prefix(&format!("{}ABI with None ETH", adversary_prefix(Some(3)))), Some(3));
+        assert_eq!(parse_adversary_prefix("ABI with None ETH"), None);
+    }
+}
@@ -0,0 +1,324 @@
+/// Simulated AMM pools (Uniswap v2 / v3 stubs)
+///
+/// The pools declared in `--amm-pools` are deployed into the initial state with a placeholder
+/// code, their reserves (v2) or price and liquidity (v3) are kept in their storage, initialized
+/// at the first call so that the state of the pools is part of the VM state. Calls to them are
+/// answered by the host with a native model of the pool instead of executing code, so targets
+/// depending on a pool get realistic swap and price behavior without forking mainnet:
+/// - constant product pools answer `token0()`, `token1()`, `getReserves()` and
+///   `swap(amount0Out, amount1Out, to, data)`
+/// - concentrated liquidity pools answer `token0()`, `token1()`, `slot0()`, `liquidity()` and
+///   exact input `swap(recipient, zeroForOne, amountSpecified, sqrtPriceLimitX96, data)` within a
+///   single liquidity range
+///
+/// The pools only account for the reserves, no token is transferred. Swaps of other actors can
+/// be injected with `--adversary-file` (see [`crate::evm::adversary`]).
+use crate::error::{load_json, MauResult};
+use crate::evm::token_minting::address_word;
+use crate::evm::types::{EVMAddress, EVMU256, EVMU512};
+use crate::evm::vm::{EVMState, EVMStorage};
+use bytes::Bytes;
+use revm_interpreter::InstructionResult;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+const TOKEN0: [u8; 4] = [0x0d, 0xfe, 0x16, 0x81];
+const TOKEN1: [u8; 4] = [0xd2, 0x12, 0x20, 0xa7];
+const GET_RESERVES: [u8; 4] = [0x09, 0x02, 0xf1, 0xac];
+const V2_SWAP: [u8; 4] = [0x02, 0x2c, 0x0d, 0x9f];
+const SLOT0: [u8; 4] = [0x38, 0x50, 0xc7, 0xbd];
+const LIQUIDITY: [u8; 4] = [0x1a, 0x68, 0x65, 0x02];
+const V3_SWAP: [u8; 4] = [0x12, 0x8a, 0xcb, 0x08];
+
+/// Storage slots of the pool state
+const RESERVE0_SLOT: u64 = 0;
+const RESERVE1_SLOT: u64 = 1;
+const SQRT_PRICE_SLOT: u64 = 0;
+const LIQUIDITY_SLOT: u64 = 1;
+
+/// Code of the pools, never executed (the host answers the calls), `INVALID` in case it is
+pub const POOL_CODE: [u8; 1] = [0xfe];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolModel {
+    /// Uniswap v2 like constant product pool
+    ConstantProduct,
+    /// Uniswap v3 like pool, with all the liquidity in a single range
+    ConcentratedLiquidity,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AmmPool {
+    pub name: String,
+    pub address: EVMAddress,
+    pub model: PoolModel,
+    pub token0: EVMAddress,
+    pub token1: EVMAddress,
+    /// Initial reserves of a constant product pool
+    #[serde(default)]
+    pub reserve0: EVMU256,
+    #[serde(default)]
+    pub reserve1: EVMU256,
+    /// Initial sqrt(price) * 2^96 of a concentrated liquidity pool
+    #[serde(default)]
+    pub sqrt_price_x96: EVMU256,
+    #[serde(default)]
+    pub liquidity: EVMU256,
+    /// Swap fee in basis points
+    #[serde(default = "default_fee_bps")]
+    pub fee_bps: u64,
+}
+
+fn default_fee_bps() -> u64 {
+    30
+}
+
+/// Pools declared in the config
+pub static mut AMM_POOLS: Vec<AmmPool> = Vec::new();
+
+pub fn load_amm_pools(path: &str) -> MauResult<Vec<AmmPool>> {
+    load_json(path)
+}
+
+pub fn register_amm_pools(pools: Vec<AmmPool>) {
+    for pool in &pools {
+        info!("Registered {:?} pool {} at {:?}", pool.model, pool.name, pool.address);
+    }
+    unsafe {
+        AMM_POOLS = pools;
+    }
+}
+
+/// Initial storage of a pool
+pub fn initial_storage(pool: &AmmPool) -> EVMStorage {
+    match pool.model {
+        PoolModel::ConstantProduct => EVMStorage::from(vec![
+            (EVMU256::from(RESERVE0_SLOT), pool.reserve0),
+            (EVMU256::from(RESERVE1_SLOT), pool.reserve1),
+        ]),
+        PoolModel::ConcentratedLiquidity => EVMStorage::from(vec![
+            (EVMU256::from(SQRT_PRICE_SLOT), pool.sqrt_price_x96),
+            (EVMU256::from(LIQUIDITY_SLOT), pool.liquidity),
+        ]),
+    }
+}
+
+fn widen(v: EVMU256) -> EVMU512 {
+    EVMU512::from_be_slice(&v.to_be_bytes::<32>())
+}
+
+fn narrow(v: EVMU512) -> Option<EVMU256> {
+    let bytes = v.to_be_bytes::<64>();
+    if bytes[..32].iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(EVMU256::from_be_slice(&bytes[32..]))
+}
+
+fn word(v: EVMU256) -> Vec<u8> {
+    v.to_be_bytes::<32>().to_vec()
+}
+
+fn arg(calldata: &[u8], i: usize) -> Option<EVMU256> {
+    calldata.get(4 + 32 * i..4 + 32 * (i + 1)).map(EVMU256::from_be_slice)
+}
+
+fn q96() -> EVMU512 {
+    EVMU512::from(1) << 96
+}
+
+/// Output of a constant product swap of `amount_in`
+pub fn v2_amount_out(amount_in: EVMU256, reserve_in: EVMU256, reserve_out: EVMU256, fee_bps: u64) -> EVMU256 {
+    let in_with_fee = widen(amount_in) * EVMU512::from(10000 - fee_bps.min(10000));
+    let denominator = widen(reserve_in) * EVMU512::from(10000) + in_with_fee;
+    if denominator == EVMU512::ZERO {
+        return EVMU256::ZERO;
+    }
+    narrow(in_with_fee * widen(reserve_out) / denominator).unwrap_or(EVMU256::ZERO)
+}
+
+/// Input needed by a constant product swap to get `amount_out`, `None` if the pool cannot pay
+pub fn v2_amount_in(amount_out: EVMU256, reserve_in: EVMU256, reserve_out: EVMU256, fee_bps: u64) -> Option<EVMU256> {
+    if amount_out >= reserve_out || fee_bps >= 10000 {
+        return None;
+    }
+    let numerator = widen(reserve_in) * widen(amount_out) * EVMU512::from(10000);
+    let denominator = widen(reserve_out - amount_out) * EVMU512::from(10000 - fee_bps);
+    narrow(numerator / denominator + EVMU512::from(1))
+}
+
+/// New sqrt price and output of an exact input swap within a single liquidity range
+pub fn v3_swap(
+    sqrt_price: EVMU256,
+    liquidity: EVMU256,
+    amount_in: EVMU256,
+    zero_for_one: bool,
+    fee_bps: u64,
+) -> Option<(EVMU256, EVMU256)> {
+    if liquidity == EVMU256::ZERO || sqrt_price == EVMU256::ZERO || fee_bps >= 10000 {
+        return None;
+    }
+    let amount_in = widen(amount_in) * EVMU512::from(10000 - fee_bps) / EVMU512::from(10000);
+    let (l, p) = (widen(liquidity), widen(sqrt_price));
+    if zero_for_one {
+        // sqrtP' = L * Q96 * sqrtP / (L * Q96 + dx * sqrtP), dy = L * (sqrtP - sqrtP') / Q96
+        let numerator = l.checked_mul(q96())?.checked_mul(p)?;
+        let new_price = numerator / l.checked_mul(q96())?.checked_add(amount_in.checked_mul(p)?)?;
+        let out = l * (p - new_price) / q96();
+        Some((narrow(new_price)?, narrow(out)?))
+    } else {
+        // sqrtP' = sqrtP + dy * Q96 / L, dx = L * Q96 * (sqrtP' - sqrtP) / (sqrtP * sqrtP')
+        let new_price = p + amount_in.checked_mul(q96())? / l;
+        let out = l.checked_mul(q96())?.checked_mul(new_price - p)? / p.checked_mul(new_price)?;
+        Some((narrow(new_price)?, narrow(out)?))
+    }
+}
+
+/// Tick of a sqrt price, i.e., log base 1.0001 of the price
+fn tick(sqrt_price: EVMU256) -> i32 {
+    let mut price = 0.0;
+    for (idx, limb) in sqrt_price.as_limbs().iter().enumerate() {
+        price += *limb as f64 * 2f64.powi(64 * idx as i32 - 96);
+    }
+    if price == 0.0 {
+        return 0;
+    }
+    ((price * price).ln() / 1.0001f64.ln()).floor() as i32
+}
+
+fn int_word(v: i64) -> Vec<u8> {
+    if v >= 0 {
+        word(EVMU256::from(v as u64))
+    } else {
+        word(EVMU256::ZERO.wrapping_sub(EVMU256::from(v.unsigned_abs())))
+    }
+}
+
+fn revert() -> (InstructionResult, Bytes) {
+    (InstructionResult::Revert, Bytes::new())
+}
+
+/// Answer a call to a pool and update its state, `None` if the address is not a pool
+pub fn amm_call(
+    address: EVMAddress,
+    calldata: &[u8],
+    evmstate: &mut EVMState,
+    timestamp: EVMU256,
+) -> Option<(InstructionResult, Bytes)> {
+    let pool = unsafe { AMM_POOLS.iter().find(|pool| pool.address == address) }?;
+    let storage = match evmstate.get_mut(&address) {
+        Some(storage) => storage,
+        None => {
+            evmstate.insert(address, initial_storage(pool));
+            evmstate.get_mut(&address).unwrap()
+        }
+    };
+    let slot = |storage: &EVMStorage, slot: u64| {
+        storage.get(&EVMU256::from(slot)).cloned().unwrap_or_default()
+    };
+    let selector: [u8; 4] = match calldata.get(..4).and_then(|s| s.try_into().ok()) {
+        Some(selector) => selector,
+        None => return Some(revert()),
+    };
+    let ok = |data: Vec<u8>| Some((InstructionResult::Return, Bytes::from(data)));
+    match (pool.model, selector) {
+        (_, TOKEN0) => ok(address_word(&pool.token0).to_vec()),
+        (_, TOKEN1) => ok(address_word(&pool.token1).to_vec()),
+        (PoolModel::ConstantProduct, GET_RESERVES) => {
+            let timestamp = timestamp & EVMU256::from(u32::MAX);
+            ok([word(slot(storage, RESERVE0_SLOT)), word(slot(storage, RESERVE1_SLOT)), word(timestamp)].concat())
+        }
+        (PoolModel::ConstantProduct, V2_SWAP) => {
+            let (out0, out1) = match (arg(calldata, 0), arg(calldata, 1)) {
+                (Some(out0), Some(out1)) => (out0, out1),
+                _ => return Some(revert()),
+            };
+            let (reserve0, reserve1) = (slot(storage, RESERVE0_SLOT), slot(storage, RESERVE1_SLOT));
+            let (new0, new1) = match (out0 == EVMU256::ZERO, out1 == EVMU256::ZERO) {
+                // token0 in, token1 out
+                (true, false) => {
+                    match v2_amount_in(out1, reserve0, reserve1, pool.fee_bps).and_then(|amount_in| reserve0.checked_add(amount_in)) {
+                        Some(new0) => (new0, reserve1 - out1),
+                        None => return Some(revert()),
+                    }
+                }
+                (false, true) => {
+                    match v2_amount_in(out0, reserve1, reserve0, pool.fee_bps).and_then(|amount_in| reserve1.checked_add(amount_in)) {
+                        Some(new1) => (reserve0 - out0, new1),
+                        None => return Some(revert()),
+                    }
+                }
+                _ => return Some(revert()),
+            };
+            storage.insert(EVMU256::from(RESERVE0_SLOT), new0);
+            storage.insert(EVMU256::from(RESERVE1_SLOT), new1);
+            ok(vec![])
+        }
+        (PoolModel::ConcentratedLiquidity, SLOT0) => {
+            let sqrt_price = slot(storage, SQRT_PRICE_SLOT);
+            // sqrtPriceX96, tick, observationIndex, observationCardinality,
+            // observationCardinalityNext, feeProtocol, unlocked
+            ok([
+                word(sqrt_price),
+                int_word(tick(sqrt_price) as i64),
+                word(EVMU256::ZERO),
+                word(EVMU256::from(1)),
+                word(EVMU256::from(1)),
+                word(EVMU256::ZERO),
+                word(EVMU256::from(1)),
+            ]
+            .concat())
+        }
+        (PoolModel::ConcentratedLiquidity, LIQUIDITY) => ok(word(slot(storage, LIQUIDITY_SLOT))),
+        (PoolModel::ConcentratedLiquidity, V3_SWAP) => {
+            let (zero_for_one, amount, limit) = match (arg(calldata, 1), arg(calldata, 2), arg(calldata, 3)) {
+                (Some(zero_for_one), Some(amount), Some(limit)) => (zero_for_one != EVMU256::ZERO, amount, limit),
+                _ => return Some(revert()),
+            };
+            // only exact input swaps (positive amountSpecified) are modelled
+            if amount == EVMU256::ZERO || amount.bit(255) {
+                return Some(revert());
+            }
+            let (sqrt_price, liquidity) = (slot(storage, SQRT_PRICE_SLOT), slot(storage, LIQUIDITY_SLOT));
+            let (new_price, out) = match v3_swap(sqrt_price, liquidity, amount, zero_for_one, pool.fee_bps) {
+                Some(res) => res,
+                None => return Some(revert()),
+            };
+            if (zero_for_one && new_price < limit) || (!zero_for_one && limit != EVMU256::ZERO && new_price > limit) {
+                return Some(revert());
+            }
+            storage.insert(EVMU256::from(SQRT_PRICE_SLOT), new_price);
+            let negative_out = EVMU256::ZERO.wrapping_sub(out);
+            let (amount0, amount1) = if zero_for_one { (amount, negative_out) } else { (negative_out, amount) };
+            ok([word(amount0), word(amount1)].concat())
+        }
+        _ => Some(revert()),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_models() {
+        let e18 = EVMU256::from(1_000_000_000_000_000_000u64);
+        let reserve = e18 * EVMU256::from(1000);
+        // 1 token in against 1000/1000 reserves with 0.3% fee
+        let out = v2_amount_out(e18, reserve, reserve, 30);
+        assert!(out < e18 && out > e18 * EVMU256::from(99) / EVMU256::from(100));
+        let amount_in = v2_amount_in(out, reserve, reserve, 30).unwrap();
+        assert!(amount_in >= e18 - EVMU256::from(1) && amount_in <= e18 + EVMU256::from(1));
+        assert!(v2_amount_in(reserve, reserve, reserve, 30).is_none());
+
+        // price 1 (sqrt price 2^96): selling token0 lowers the price
+        let sqrt_price = EVMU256::from(1) << 96;
+        let (new_price, out) = v3_swap(sqrt_price, reserve, e18, true, 0).unwrap();
+        assert!(new_price < sqrt_price);
+        assert!(out < e18 && out > EVMU256::ZERO);
+        let (new_price, _) = v3_swap(sqrt_price, reserve, e18, false, 0).unwrap();
+        assert!(new_price > sqrt_price);
+        assert_eq!(tick(sqrt_price), 0);
+        assert_eq!(int_word(-1), vec![0xff; 32]);
+    }
+}
@@ -0,0 +1,180 @@
+/// Block advancement between the transactions of a sequence (`--block-advance`)
+///
+/// The block of an input is otherwise mutated on its own, so a sequence has no notion of time
+/// passing: a `deposit` followed by a `withdraw` gated by a one-day cooldown needs the mutator to
+/// stumble on a timestamp of the second transaction exactly a day after the first one. With
+/// `--block-advance <policies>`, the state carries the block of the last transaction
+/// ([`EVMState::clock`]) and an input can carry a [`BlockAdvance`]: it is executed in the same
+/// block, the next one or a day later, relative to that block. Vesting schedules, auctions and
+/// cooldowns are then reached by sequences like `deposit, (+1 day) withdraw`.
+///
+/// The policies the mutator draws from are listed in the flag, e.g. `same,next,day`. The clock
+/// does not take part in the hash of the state, two states differing only in time are the same
+/// infant state.
+use crate::evm::types::EVMU256;
+use libafl::prelude::{HasRand, Rand};
+use revm_primitives::Env;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Seconds between two blocks
+pub const BLOCK_TIME: u64 = 12;
+
+const DAY: u64 = 86400;
+
+/// Probability (in percent) that a mutation changes the block advancement of the input
+pub const BLOCK_ADVANCE_MUTATION_PROBABILITY: u64 = 10;
+
+/// Policies the mutator draws from, set with `--block-advance`
+pub static mut BLOCK_ADVANCE_POLICIES: Vec<BlockAdvance> = Vec::new();
+
+/// How the block of a transaction relates to the block of the previous one
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockAdvance {
+    /// Same block number and timestamp
+    Same,
+    /// Next block, [`BLOCK_TIME`] seconds later
+    Next,
+    /// A day later, in the block mined by then
+    Day,
+}
+
+impl BlockAdvance {
+    /// Blocks and seconds the policy advances the clock by
+    pub fn delta(&self) -> (u64, u64) {
+        match self {
+            BlockAdvance::Same => (0, 0),
+            BlockAdvance::Next => (1, BLOCK_TIME),
+            BlockAdvance::Day => (DAY / BLOCK_TIME, DAY),
+        }
+    }
+}
+
+impl FromStr for BlockAdvance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "same" => Ok(BlockAdvance::Same),
+            "next" => Ok(BlockAdvance::Next),
+            "day" => Ok(BlockAdvance::Day),
+            other => Err(format!("unknown block advancement policy {} (same, next, day)", other)),
+        }
+    }
+}
+
+impl Display for BlockAdvance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockAdvance::Same => write!(f, "same"),
+            BlockAdvance::Next => write!(f, "next"),
+            BlockAdvance::Day => write!(f, "day"),
+        }
+    }
+}
+
+/// Block of the last transaction executed on a state
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockClock {
+    pub number: EVMU256,
+    pub timestamp: EVMU256,
+}
+
+/// Parse the comma separated policies of `--block-advance`
+pub fn parse_policies(list: &str) -> Result<Vec<BlockAdvance>, String> {
+    list.split(',')
+        .filter(|policy| !policy.trim().is_empty())
+        .map(BlockAdvance::from_str)
+        .collect()
+}
+
+pub fn set_block_advance_policies(policies: Vec<BlockAdvance>) {
+    unsafe {
+        BLOCK_ADVANCE_POLICIES = policies;
+    }
+}
+
+pub fn block_advance_enabled() -> bool {
+    unsafe { !BLOCK_ADVANCE_POLICIES.is_empty() }
+}
+
+/// Executes the input in the block the policy leads to from the clock of the state, and moves
+/// the clock there. The first transaction of a sequence starts the clock at its own block.
+/// Without a policy, the input keeps its own block and the clock is left as is.
+pub fn advance_block(env: &mut Env, clock: &mut Option<BlockClock>, advance: Option<BlockAdvance>) {
+    let advance = match advance {
+        Some(advance) => advance,
+        None => return,
+    };
+    let (blocks, seconds) = advance.delta();
+    let next = match clock {
+        Some(clock) => BlockClock {
+            number: clock.number.saturating_add(EVMU256::from(blocks)),
+            timestamp: clock.timestamp.saturating_add(EVMU256::from(seconds)),
+        },
+        None => BlockClock { number: env.block.number, timestamp: env.block.timestamp },
+    };
+    env.block.number = next.number;
+    env.block.timestamp = next.timestamp;
+    *clock = Some(next);
+}
+
+/// A policy drawn from the configured ones, or none (the input keeps its own block)
+pub fn mutate_block_advance<S: HasRand>(state: &mut S) -> Option<BlockAdvance> {
+    let policies = unsafe { &BLOCK_ADVANCE_POLICIES };
+    match state.rand_mut().below(policies.len() as u64 + 1) as usize {
+        idx if idx == policies.len() => None,
+        idx => Some(policies[idx]),
+    }
+}
+
+/// Prefix of the pretty transaction of an input advancing the block
+pub fn block_advance_prefix(advance: Option<BlockAdvance>) -> String {
+    match advance {
+        Some(BlockAdvance::Same) => "(same block) ".to_string(),
+        Some(BlockAdvance::Next) => "(next block) ".to_string(),
+        Some(BlockAdvance::Day) => "(+1 day) ".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Block advancement of a pretty transaction, see [`block_advance_prefix`]
+pub fn parse_block_advance_prefix(pretty_txn: &str) -> Option<BlockAdvance> {
+    [BlockAdvance::Same, BlockAdvance::Next, BlockAdvance::Day]
+        .into_iter()
+        .find(|advance| pretty_txn.contains(block_advance_prefix(Some(*advance)).as_str()))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_advance() {
+        assert_eq!(
+            parse_policies("same, next,day").unwrap(),
+            vec![BlockAdvance::Same, BlockAdvance::Next, BlockAdvance::Day]
+        );
+        assert!(parse_policies("week").is_err());
+
+        let mut env = Env::default();
+        env.block.number = EVMU256::from(100);
+        env.block.timestamp = EVMU256::from(1000);
+        let mut clock = None;
+        advance_block(&mut env, &mut clock, Some(BlockAdvance::Next));
+        assert_eq!(clock.unwrap().number, EVMU256::from(100));
+
+        // the next transactions follow the clock, not their own block
+        let mut env = Env::default();
+        advance_block(&mut env, &mut clock, Some(BlockAdvance::Day));
+        assert_eq!((env.block.number, env.block.timestamp), (EVMU256::from(7300), EVMU256::from(87400)));
+        advance_block(&mut env, &mut clock, None);
+        advance_block(&mut env, &mut clock, Some(BlockAdvance::Same));
+        assert_eq!(clock.unwrap().timestamp, EVMU256::from(87400));
+
+        let pretty = format!("{}ABI with None ETH", block_advance_prefix(Some(BlockAdvance::Day)));
+        assert_eq!(parse_block_advance_prefix(&pretty), Some(BlockAdvance::Day));
+        assert_eq!(parse_block_advance_prefix("ABI with None ETH"), None);
+    }
+}
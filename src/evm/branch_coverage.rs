@@ -0,0 +1,135 @@
+/// Branch coverage: the directions (taken / not taken) of each JUMPI covered by the campaign
+///
+/// The jump map counts edges of all jumps, keyed by source and destination PC, which is the
+/// feedback of the fuzzer but not the branch coverage reported by the evaluations. Here each
+/// direction of a JUMPI is a slot of [`BRANCH_MAP`], keyed by the contract and PC of the JUMPI:
+/// - on revm, the host sets the slot of the direction taken, and [`BranchCoverageObserver`]
+///   accumulates the slots of each execution
+/// - on the GPU, the runner ORs the directions taken by the threads of a batch into a bitmap
+///   indexed by `pc * 2 + taken` (`cuReadBranchCoverage`), merged for the contract of the seed
+///   after the batch
+///
+/// The number of directions covered is the "Branch Covered" of the coverage log.
+use crate::evm::types::EVMAddress;
+use crate::generic_vm::vm_executor::MAP_SIZE;
+use libafl::bolts::tuples::Named;
+use libafl::executors::ExitKind;
+use libafl::observers::Observer;
+use libafl::Error;
+use serde::{Deserialize, Serialize};
+
+/// Bytes of the bitmap read back from the GPU: two directions per PC of a code of at most
+/// 24576 bytes (EIP-170)
+pub const GPU_BRANCH_BITMAP_SIZE: usize = 24576 * 2 / 8;
+
+/// Directions taken during the current execution on revm
+pub static mut BRANCH_MAP: [u8; MAP_SIZE] = [0; MAP_SIZE];
+
+/// Directions taken so far, on revm and on the GPU
+static mut BRANCHES_COVERED: [u8; MAP_SIZE] = [0; MAP_SIZE];
+static mut BRANCH_COUNT: usize = 0;
+
+/// Slot of a direction of the JUMPI at `pc` of the contract
+pub fn branch_index(contract: &EVMAddress, pc: usize, taken: bool) -> usize {
+    let salt = u16::from_be_bytes([contract.0[18], contract.0[19]]) as usize;
+    ((pc ^ (salt << 8)) << 1 | taken as usize) % MAP_SIZE
+}
+
+/// Called by the host on each JUMPI
+pub fn record_branch(contract: &EVMAddress, pc: usize, taken: bool) {
+    unsafe {
+        BRANCH_MAP[branch_index(contract, pc, taken)] = 1;
+    }
+}
+
+fn cover(idx: usize) {
+    unsafe {
+        if BRANCHES_COVERED[idx] == 0 {
+            BRANCHES_COVERED[idx] = 1;
+            BRANCH_COUNT += 1;
+        }
+    }
+}
+
+/// Merge the bitmap read back from the GPU, bit `pc * 2 + taken` for the JUMPIs of the contract
+pub fn merge_gpu_branches(contract: &EVMAddress, bitmap: &[u8]) {
+    for (byte_idx, byte) in bitmap.iter().enumerate().filter(|(_, b)| **b != 0) {
+        for bit in 0..8 {
+            if byte & (1 << bit) != 0 {
+                let direction = byte_idx * 8 + bit;
+                cover(branch_index(contract, direction >> 1, direction & 1 == 1));
+            }
+        }
+    }
+}
+
+/// Branch directions covered so far
+pub fn branches_covered() -> usize {
+    unsafe { BRANCH_COUNT }
+}
+
+/// Accumulates the branch directions taken by the executions on revm
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BranchCoverageObserver {
+    name: String,
+}
+
+impl BranchCoverageObserver {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+}
+
+impl Named for BranchCoverageObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for BranchCoverageObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        unsafe {
+            BRANCH_MAP = [0; MAP_SIZE];
+        }
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        for idx in 0..MAP_SIZE {
+            if unsafe { BRANCH_MAP[idx] } != 0 {
+                cover(idx);
+            }
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_coverage() {
+        let contract = EVMAddress::from_slice(&[0x11; 20]);
+        assert_ne!(branch_index(&contract, 0x40, true), branch_index(&contract, 0x40, false));
+        assert_ne!(
+            branch_index(&contract, 0x40, true),
+            branch_index(&EVMAddress::from_slice(&[0x22; 20]), 0x40, true)
+        );
+
+        let before = branches_covered();
+        // both directions of the JUMPI at 0x40, taken at 0x41
+        let mut bitmap = vec![0u8; 0x42 * 2 / 8 + 1];
+        for direction in [0x80, 0x81, 0x83] {
+            bitmap[direction / 8] |= 1 << (direction % 8);
+        }
+        merge_gpu_branches(&contract, &bitmap);
+        assert_eq!(branches_covered(), before + 3);
+
+        let mut observer = BranchCoverageObserver::new("branches");
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        record_branch(&contract, 0x40, true);
+        record_branch(&contract, 0x50, false);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(branches_covered(), before + 4);
+    }
+}
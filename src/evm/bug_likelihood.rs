@@ -0,0 +1,203 @@
+/// Bug-likelihood priors of the functions (`--bug-priors`)
+///
+/// Before fuzzing, a static pass walks the CFG of each target (see
+/// [`crate::evm::directed::build_cfg`], completed with the jumps resolved by heimdall) from the
+/// entry point of each function and looks for the patterns bugs come from:
+/// - a `CALL`, which can transfer value, or a `CALLCODE`
+/// - a `DELEGATECALL`
+/// - a `SELFDESTRUCT`
+/// - an `ADD` / `SUB` / `MUL` in a function that never reaches the overflow panic of Solidity
+///   0.8 (`Panic(0x11)`), i.e., unchecked arithmetic
+/// - an `SSTORE` reachable without a branch on `CALLER` / `ORIGIN`, i.e., without an
+///   authorization check
+///
+/// The weighted sum of the patterns found is the score of the function. The seeds calling a
+/// function get extra rounds of the power schedule in proportion to its score, halved each time
+/// they are picked, so that the first GPU batches concentrate on the dangerous code and the
+/// schedule takes over once the priors have worn off.
+use crate::evm::directed::{block_of, block_starts, build_cfg};
+use crate::evm::entry_points::disassemble;
+use crate::evm::onchain::abi_decompiler::fetch_jump_edges_heimdall;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const ADD: u8 = 0x01;
+const MUL: u8 = 0x02;
+const SUB: u8 = 0x03;
+const ORIGIN: u8 = 0x32;
+const CALLER: u8 = 0x33;
+const SSTORE: u8 = 0x55;
+const JUMPI: u8 = 0x57;
+const PUSH1: u8 = 0x60;
+const PUSH4: u8 = 0x63;
+const CALL: u8 = 0xf1;
+const CALLCODE: u8 = 0xf2;
+const DELEGATECALL: u8 = 0xf4;
+const SELFDESTRUCT: u8 = 0xff;
+
+/// Selector of `Panic(uint256)`
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+/// Panic code of an arithmetic overflow or underflow
+const PANIC_OVERFLOW: u8 = 0x11;
+
+/// Whether the seeds get priors from the static pass, on with `--bug-priors`
+pub static mut BUG_PRIORS: bool = false;
+
+/// Scores of the functions of the targets, by selector, filled by the static pass
+static mut FUNCTION_SCORES: Option<HashMap<[u8; 4], usize>> = None;
+
+/// Patterns found in the code reachable from the entry point of a function
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RiskFeatures {
+    pub value_call: bool,
+    pub delegatecall: bool,
+    pub selfdestruct: bool,
+    pub unchecked_arithmetic: bool,
+    pub unguarded_sstore: bool,
+}
+
+impl RiskFeatures {
+    /// Weighted sum of the patterns found, at most 23
+    pub fn score(&self) -> usize {
+        [
+            (self.selfdestruct, 8),
+            (self.delegatecall, 6),
+            (self.value_call, 4),
+            (self.unguarded_sstore, 3),
+            (self.unchecked_arithmetic, 2),
+        ]
+        .iter()
+        .filter(|(found, _)| *found)
+        .map(|(_, weight)| weight)
+        .sum()
+    }
+
+    /// Whether anyone can call the function to change the state: it writes storage without a
+    /// branch on the caller, and does not destroy the contract (e.g., a public mint, an
+    /// `approve`, an open enrollment)
+    pub fn prepares_state(&self) -> bool {
+        self.unguarded_sstore && !self.selfdestruct
+    }
+}
+
+/// Record the scores of the functions of a target, for the triage report
+pub fn record_scores(features: &HashMap<[u8; 4], RiskFeatures>) {
+    let scores = unsafe { FUNCTION_SCORES.get_or_insert_with(Default::default) };
+    for (selector, features) in features {
+        let score = scores.entry(*selector).or_default();
+        *score = (*score).max(features.score());
+    }
+}
+
+/// Scores of the functions by selector, `None` if the static pass did not run
+pub fn function_scores() -> Option<HashMap<[u8; 4], usize>> {
+    unsafe { FUNCTION_SCORES.clone() }
+}
+
+/// Extra rounds of the power schedule given to the seeds of a function with the score
+pub fn prior_energy(score: usize) -> usize {
+    (score + 1) / 2
+}
+
+/// Opcodes of each block, and the blocks raising `Panic(0x11)`
+fn block_ops(code: &[u8]) -> (HashMap<usize, Vec<u8>>, HashSet<usize>) {
+    let starts = block_starts(code);
+    let mut ops: HashMap<usize, Vec<u8>> = HashMap::new();
+    let mut panic_selector = HashSet::new();
+    let mut overflow_panics = HashSet::new();
+    for (pc, op, imm) in disassemble(code) {
+        let block = block_of(&starts, pc);
+        ops.entry(block).or_default().push(op);
+        match op {
+            PUSH4 if imm == PANIC_SELECTOR => {
+                panic_selector.insert(block);
+            }
+            PUSH1 if imm == [PANIC_OVERFLOW] && panic_selector.contains(&block) => {
+                overflow_panics.insert(block);
+            }
+            _ => {}
+        }
+    }
+    (ops, overflow_panics)
+}
+
+fn analyze_function(
+    ops: &HashMap<usize, Vec<u8>>,
+    overflow_panics: &HashSet<usize>,
+    cfg: &HashMap<usize, HashSet<usize>>,
+    entry: usize,
+) -> RiskFeatures {
+    let mut features = RiskFeatures::default();
+    let (mut arithmetic, mut overflow_checked) = (false, false);
+    let mut visited = HashSet::new();
+    // (block, caller read, behind a branch on the caller)
+    let mut queue = VecDeque::from([(entry, false, false)]);
+    while let Some((block, mut caller_read, mut guarded)) = queue.pop_front() {
+        if !visited.insert((block, caller_read, guarded)) {
+            continue;
+        }
+        overflow_checked |= overflow_panics.contains(&block);
+        for op in ops.get(&block).map_or(&[][..], |ops| ops.as_slice()) {
+            match *op {
+                CALLER | ORIGIN => caller_read = true,
+                JUMPI if caller_read => guarded = true,
+                ADD | SUB | MUL => arithmetic = true,
+                SSTORE if !guarded => features.unguarded_sstore = true,
+                CALL | CALLCODE => features.value_call = true,
+                DELEGATECALL => features.delegatecall = true,
+                SELFDESTRUCT => features.selfdestruct = true,
+                _ => {}
+            }
+        }
+        for next in cfg.get(&block).into_iter().flatten() {
+            queue.push_back((*next, caller_read, guarded));
+        }
+    }
+    features.unchecked_arithmetic = arithmetic && !overflow_checked;
+    features
+}
+
+/// Patterns found in each function of the code, `None` if the code could not be decompiled
+pub fn score_functions(code: &[u8]) -> Option<HashMap<[u8; 4], RiskFeatures>> {
+    let (entry_points, jump_edges) = fetch_jump_edges_heimdall(hex::encode(code)).ok()?;
+    let cfg = build_cfg(code, &jump_edges);
+    let (ops, overflow_panics) = block_ops(code);
+    let starts = block_starts(code);
+    Some(
+        entry_points
+            .into_iter()
+            .map(|(selector, entry)| {
+                let features = analyze_function(&ops, &overflow_panics, &cfg, block_of(&starts, entry));
+                (selector, features)
+            })
+            .collect(),
+    )
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risk_features() {
+        // 0: CALLVALUE PUSH1 0x05 JUMPI STOP  5: JUMPDEST PUSH1 1 PUSH1 2 ADD PUSH1 0 SSTORE STOP
+        let unguarded = hex::decode("34600557005b600160020160005500").unwrap();
+        let cfg = build_cfg(&unguarded, &vec![]);
+        let (ops, panics) = block_ops(&unguarded);
+        let features = analyze_function(&ops, &panics, &cfg, 0);
+        assert!(features.unguarded_sstore && features.unchecked_arithmetic);
+        assert_eq!(features.score(), 5);
+        assert!(features.prepares_state());
+
+        // 0: CALLER PUSH1 0x05 JUMPI STOP  5: JUMPDEST PUSH1 0 SSTORE CALLER SELFDESTRUCT
+        let guarded = hex::decode("33600557005b60005533ff").unwrap();
+        let cfg = build_cfg(&guarded, &vec![]);
+        let (ops, panics) = block_ops(&guarded);
+        let features = analyze_function(&ops, &panics, &cfg, 0);
+        assert!(!features.unguarded_sstore && features.selfdestruct);
+        assert_eq!(prior_energy(features.score()), 4);
+        assert!(!features.prepares_state());
+
+        // overflow panic: PUSH4 0x4e487b71 ... PUSH1 0x11
+        let (_, panics) = block_ops(&hex::decode("634e487b7160e01b600052601160045260246000fd").unwrap());
+        assert!(panics.contains(&0));
+    }
+}
@@ -1,18 +1,22 @@
 /// Analysis passes for EVM bytecode
-use crate::evm::mutation_utils::ConstantPoolMetadata;
+use crate::evm::contract_metadata::strip_metadata;
+use crate::evm::mutation_utils::{ConstantPoolMetadata, SelectorConstantsMetadata};
+use crate::evm::onchain::abi_decompiler::fetch_selector_constants_heimdall;
 use libafl::state::{HasMetadata, State};
 
 use revm_primitives::Bytecode;
 use std::collections::HashSet;
+use tracing::warn;
 
 /// Find all constants in the bytecode by observing PUSH instructions.
 ///
 /// Check tests below for usage.
 pub fn find_constants(bytecode: &Bytecode) -> HashSet<Vec<u8>> {
     let mut idx = 0;
-    let bytecode_len = bytecode.len();
-    let mut constants = HashSet::new();
     let bytes = bytecode.bytes();
+    // the trailing compiler metadata is not code
+    let bytecode_len = strip_metadata(&bytes[..bytecode.len()]).len();
+    let mut constants = HashSet::new();
     loop {
         if idx >= bytecode_len {
             break;
@@ -93,6 +97,33 @@ where
     }
 }
 
+/// Add the constants guarding each function to the global state's [`SelectorConstantsMetadata`]
+/// this runs the decompiler, so only call it once per contract
+pub fn add_selector_constants_to_state<S>(bytecode: &Bytecode, state: &mut S)
+where
+    S: HasMetadata + State,
+{
+    let selector_constants = match fetch_selector_constants_heimdall(hex::encode(bytecode.bytes())) {
+        Ok(selector_constants) => selector_constants,
+        Err(e) => {
+            warn!("Skipping the selector constants: {}", e);
+            return;
+        }
+    };
+    if !state.metadata().contains::<SelectorConstantsMetadata>() {
+        state.metadata_mut().insert(SelectorConstantsMetadata::default());
+    }
+    let meta = state.metadata_mut().get_mut::<SelectorConstantsMetadata>().unwrap();
+    for (function, constants) in selector_constants {
+        let dictionary = meta.constants.entry(function).or_default();
+        for constant in constants {
+            if !dictionary.contains(&constant) {
+                dictionary.push(constant);
+            }
+        }
+    }
+}
+
 pub fn collect_constants(bytecode: &Bytecode)
 {   
     // let constants = find_constants(bytecode);
@@ -0,0 +1,164 @@
+/// Structured mutations of the `bytes` / `string` args
+///
+/// The byte-level mutators only produce random bytes, while the bugs of string handling need
+/// structured values: an `abi.decode` of a JSON payload, a URI concatenated into a token URL, a
+/// copy loop off by one at the 32-byte word boundary, a length check on a huge string. Part of
+/// the mutations of a dynamic arg set it to one of:
+/// - valid UTF-8, mixing ASCII with multi-byte, combining and bidirectional characters
+/// - a URL or a JSON document (e.g., token metadata)
+/// - the current value resized to a boundary length (0, 31, 32, 33 or 4096 bytes)
+/// - a short pattern repeated (format strings, path traversal, quotes, zeros, `0xff`)
+/// - a copy of another arg of the call or of an address, raw or ABI-encoded
+use crate::evm::address_pool::pick_address;
+use crate::evm::types::EVMAddress;
+use crate::state::HasCaller;
+use libafl::mutators::MutationResult;
+use libafl::prelude::{HasRand, Rand};
+
+/// Probability (in percent) that a mutation of a `string` arg is structured
+pub const STRING_MUTATION_PROB: u64 = 50;
+/// Probability (in percent) that a mutation of a `bytes` arg is structured
+pub const BYTES_MUTATION_PROB: u64 = 25;
+
+pub const BOUNDARY_LENGTHS: [usize; 5] = [0, 31, 32, 33, 4096];
+
+const CHARS: [&str; 12] = ["a", "Z", "0", " ", "é", "ß", "中", "😀", "\u{301}", "\u{200d}", "\u{202e}", "\u{0}"];
+
+const PATTERNS: [&[u8]; 8] = [b"A", b"%s%n", b"../", b"\"", b"\\", b"<script>", &[0x00], &[0xff]];
+
+const URL_SCHEMES: [&str; 5] = ["https://", "http://", "ipfs://", "ar://", "data:application/json;base64,"];
+
+/// Calldata of the call whose arg is mutated, for the copies of the other args
+static mut CALL_ARGS: Vec<u8> = Vec::new();
+
+/// Set the calldata of the call before its args are mutated
+pub fn set_call_args(calldata: Vec<u8>) {
+    unsafe {
+        CALL_ARGS = calldata;
+    }
+}
+
+fn random_utf8<S: HasRand>(state: &mut S, max_chars: u64) -> String {
+    let len = state.rand_mut().below(max_chars) + 1;
+    (0..len)
+        .map(|_| CHARS[state.rand_mut().below(CHARS.len() as u64) as usize])
+        .collect()
+}
+
+fn random_word<S: HasRand>(state: &mut S) -> String {
+    let len = state.rand_mut().below(12) + 1;
+    (0..len)
+        .map(|_| (b'a' + state.rand_mut().below(26) as u8) as char)
+        .collect()
+}
+
+fn random_url<S: HasRand>(state: &mut S) -> String {
+    let scheme = URL_SCHEMES[state.rand_mut().below(URL_SCHEMES.len() as u64) as usize];
+    match state.rand_mut().below(3) {
+        0 => format!("{}{}.com/{}/{}", scheme, random_word(state), random_word(state), state.rand_mut().below(10000)),
+        1 => format!("{}{}?{}={}#{}", scheme, random_word(state), random_word(state), random_utf8(state, 8), random_word(state)),
+        _ => scheme.to_string(),
+    }
+}
+
+fn random_json<S: HasRand>(state: &mut S) -> String {
+    match state.rand_mut().below(5) {
+        0 => format!(
+            r#"{{"name":"{}","description":"{}","image":"{}"}}"#,
+            random_word(state),
+            random_utf8(state, 16),
+            random_url(state)
+        ),
+        1 => format!("[{},{},\"{}\"]", state.rand_mut().next(), state.rand_mut().below(2) == 0, random_word(state)),
+        // deeply nested
+        2 => {
+            let depth = state.rand_mut().below(64) as usize + 1;
+            format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+        }
+        // truncated
+        3 => format!(r#"{{"{}":"#, random_word(state)),
+        _ => ["{}", "[]", "null", "\"\""][state.rand_mut().below(4) as usize].to_string(),
+    }
+}
+
+/// Copy of a part of the calldata of the call (an arg, a word or a tail) or of an address
+fn copied_bytes<S: HasRand + HasCaller<EVMAddress>>(state: &mut S) -> Vec<u8> {
+    let calldata = unsafe { &CALL_ARGS };
+    let args = calldata.get(4..).unwrap_or(&[]);
+    if args.len() < 32 || state.rand_mut().below(2) == 0 {
+        let address = pick_address(state);
+        return match state.rand_mut().below(2) {
+            0 => address.0.to_vec(),
+            _ => [vec![0u8; 12], address.0.to_vec()].concat(),
+        };
+    }
+    let word = state.rand_mut().below((args.len() / 32) as u64) as usize;
+    match state.rand_mut().below(3) {
+        0 => args[word * 32..(word + 1) * 32].to_vec(),
+        1 => args[word * 32..].to_vec(),
+        _ => calldata.clone(),
+    }
+}
+
+/// Resize to the length, repeating the current value (or zeros if it is empty)
+fn resize_to(data: &Vec<u8>, len: usize) -> Vec<u8> {
+    if data.is_empty() {
+        return vec![0; len];
+    }
+    data.iter().cycle().take(len).cloned().collect()
+}
+
+/// Apply a structured mutation to a dynamic arg, see the module documentation
+pub fn mutate_dynamic<S>(state: &mut S, data: &mut Vec<u8>, is_string: bool) -> MutationResult
+where
+    S: HasRand + HasCaller<EVMAddress>,
+{
+    let mutated = match state.rand_mut().below(if is_string { 6 } else { 5 }) {
+        0 => {
+            let len = BOUNDARY_LENGTHS[state.rand_mut().below(BOUNDARY_LENGTHS.len() as u64) as usize];
+            resize_to(data, len)
+        }
+        1 => {
+            let pattern = PATTERNS[state.rand_mut().below(PATTERNS.len() as u64) as usize];
+            let len = match state.rand_mut().below(2) {
+                0 => BOUNDARY_LENGTHS[state.rand_mut().below(BOUNDARY_LENGTHS.len() as u64) as usize],
+                _ => state.rand_mut().below(256) as usize,
+            };
+            pattern.iter().cycle().take(len).cloned().collect()
+        }
+        2 => copied_bytes(state),
+        3 => random_utf8(state, 64).into_bytes(),
+        4 if is_string => random_url(state).into_bytes(),
+        _ => random_json(state).into_bytes(),
+    };
+    if mutated == *data {
+        return MutationResult::Skipped;
+    }
+    *data = mutated;
+    MutationResult::Mutated
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::types::EVMFuzzState;
+    use crate::state::FuzzState;
+
+    #[test]
+    fn test_mutate_dynamic() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        state.add_caller(&EVMAddress::from_slice(&[0x11; 20]));
+        set_call_args([vec![0xaa; 4], vec![0x22; 64]].concat());
+        let mut lengths = std::collections::HashSet::new();
+        for _ in 0..1000 {
+            let mut data = b"hello".to_vec();
+            if mutate_dynamic(&mut state, &mut data, true) == MutationResult::Mutated {
+                lengths.insert(data.len());
+            }
+        }
+        // boundary lengths are all reached, huge ones included
+        assert!(BOUNDARY_LENGTHS.iter().all(|len| lengths.contains(len)));
+
+        assert_eq!(resize_to(&b"ab".to_vec(), 5), b"ababa".to_vec());
+        assert_eq!(resize_to(&vec![], 3), vec![0; 3]);
+    }
+}
@@ -0,0 +1,133 @@
+/// Call-graph coverage of inter-contract calls
+///
+/// In multi-contract setups, most bugs live in the interaction between contracts (e.g., a vault
+/// calling a strategy calling a pool), which the PC-level maps cannot tell apart from the
+/// coverage of each contract alone. Every message call records the edge
+/// `(caller contract, callee contract, selector)` and the call depth, an execution taking a new
+/// edge or reaching a deeper call is interesting. The edges are saved to
+/// `stats/call_graph.json`.
+use crate::evm::abi::function_sig_name;
+use crate::evm::types::EVMAddress;
+use crate::workdir::stats_dir;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{debug, warn};
+
+pub const CALL_GRAPH_FILE: &str = "call_graph.json";
+
+/// Save the call graph every this many executions
+pub const CALL_GRAPH_SAVE_INTERVAL: usize = 100000;
+
+/// `(caller, callee, selector)`, the selector is zero for calls without calldata
+pub type CallEdge = (EVMAddress, EVMAddress, [u8; 4]);
+
+/// Edges taken so far
+static mut CALL_EDGES: Option<HashSet<CallEdge>> = None;
+/// Depth of the current message call, 0 in the transaction itself
+pub static mut CALL_DEPTH: usize = 0;
+/// Deepest call reached so far
+pub static mut MAX_CALL_DEPTH: usize = 0;
+/// Whether the current execution took a new edge or reached a deeper call
+static mut CALL_GRAPH_PROGRESS: bool = false;
+/// Whether the last execution increased the call-graph coverage
+pub static mut CALL_GRAPH_INTERESTING: bool = false;
+
+fn call_edges() -> &'static mut HashSet<CallEdge> {
+    unsafe { CALL_EDGES.get_or_insert_with(HashSet::new) }
+}
+
+/// Record a message call from `caller` to `callee`, to be paired with [`exit_call`]
+pub fn enter_call(caller: EVMAddress, callee: EVMAddress, calldata: &[u8]) {
+    let mut selector = [0u8; 4];
+    if calldata.len() >= 4 {
+        selector.copy_from_slice(&calldata[..4]);
+    }
+    unsafe {
+        CALL_DEPTH += 1;
+        if CALL_DEPTH > MAX_CALL_DEPTH {
+            MAX_CALL_DEPTH = CALL_DEPTH;
+            CALL_GRAPH_PROGRESS = true;
+        }
+    }
+    if call_edges().insert((caller, callee, selector)) {
+        debug!(
+            "new call edge {:?} -> {:?}.{}",
+            caller,
+            callee,
+            function_sig_name(&selector)
+        );
+        unsafe {
+            CALL_GRAPH_PROGRESS = true;
+        }
+    }
+}
+
+pub fn exit_call() {
+    unsafe {
+        CALL_DEPTH = CALL_DEPTH.saturating_sub(1);
+    }
+}
+
+/// Whether the current execution increased the call-graph coverage.
+/// Resets the progress and the depth of the current execution.
+pub fn take_call_graph_progress() -> bool {
+    unsafe {
+        CALL_DEPTH = 0;
+        let progress = CALL_GRAPH_PROGRESS;
+        CALL_GRAPH_PROGRESS = false;
+        progress
+    }
+}
+
+pub fn call_edge_count() -> usize {
+    call_edges().len()
+}
+
+/// Save the edges taken so far, sorted
+pub fn save_call_graph() {
+    let mut edges = call_edges().iter().cloned().collect::<Vec<_>>();
+    edges.sort();
+    let edges = edges
+        .iter()
+        .map(|(caller, callee, selector)| {
+            serde_json::json!({
+                "caller": format!("{:?}", caller),
+                "callee": format!("{:?}", callee),
+                "function": function_sig_name(selector),
+            })
+        })
+        .collect::<Vec<_>>();
+    let report = serde_json::json!({
+        "max_depth": unsafe { MAX_CALL_DEPTH },
+        "edges": edges,
+    });
+    let path = Path::new(&stats_dir()).join(CALL_GRAPH_FILE);
+    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&report).unwrap()) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_graph_progress() {
+        let a = EVMAddress::from_slice(&[1; 20]);
+        let b = EVMAddress::from_slice(&[2; 20]);
+        take_call_graph_progress();
+        enter_call(a, b, &[0xaa, 0xbb, 0xcc, 0xdd, 0x00]);
+        exit_call();
+        assert!(take_call_graph_progress());
+
+        // same edge at the same depth
+        enter_call(a, b, &[0xaa, 0xbb, 0xcc, 0xdd]);
+        exit_call();
+        assert!(!take_call_graph_progress());
+
+        // same selector to another callee
+        enter_call(b, a, &[0xaa, 0xbb, 0xcc, 0xdd]);
+        exit_call();
+        assert!(take_call_graph_progress());
+    }
+}
@@ -0,0 +1,64 @@
+/// Constraints of the transaction value mutation
+///
+/// A value sent to a non-payable function, or larger than the balance of the caller, makes the
+/// transaction revert right away. The value mutation therefore follows the payability of the
+/// function (from the ABI, or the ABI recovered by the decompiler for on-chain targets) and the
+/// modeled balance of the callers (`--caller-balance`), except for one mutation out of
+/// [`VIOLATION_RATE`] which deliberately breaks them to exercise the guards.
+use crate::evm::types::{EVMAddress, EVMU256};
+use std::collections::HashMap;
+
+/// One value mutation out of this many ignores the payability and the balance
+pub const VIOLATION_RATE: u64 = 20;
+
+/// Balance of each caller, 100 ether by default
+pub static mut CALLER_BALANCE: EVMU256 = EVMU256::from_limbs([0x6bc75e2d63100000, 0x5, 0, 0]);
+
+static mut PAYABILITY: Option<HashMap<(EVMAddress, [u8; 4]), bool>> = None;
+
+pub fn register_payability(contract: EVMAddress, selector: [u8; 4], payable: bool) {
+    unsafe {
+        PAYABILITY.get_or_insert_with(HashMap::new).insert((contract, selector), payable);
+    }
+}
+
+/// Whether the function accepts a value, `None` if its ABI is unknown
+pub fn is_payable(contract: &EVMAddress, selector: &[u8; 4]) -> Option<bool> {
+    unsafe { PAYABILITY.as_ref().and_then(|p| p.get(&(*contract, *selector)).cloned()) }
+}
+
+/// Constrain a mutated value: zero for non-payable functions, at most the balance of the caller
+/// otherwise. A violation instead makes sure the value breaks one of the constraints.
+pub fn constrain_value(value: EVMU256, payable: Option<bool>, violation: bool) -> EVMU256 {
+    let balance = unsafe { CALLER_BALANCE };
+    match (payable, violation) {
+        (Some(false), false) => EVMU256::ZERO,
+        (Some(false), true) if value == EVMU256::ZERO => EVMU256::from(1),
+        (Some(false), true) => value,
+        (_, false) if value > balance => value % (balance + EVMU256::from(1)),
+        (_, false) => value,
+        (_, true) if value <= balance => balance.saturating_add(value.max(EVMU256::from(1))),
+        (_, true) => value,
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constrain_value() {
+        let balance = unsafe { CALLER_BALANCE };
+        assert_eq!(balance, EVMU256::from(100_000_000_000_000_000_000u128));
+        let value = EVMU256::from(5);
+        assert_eq!(constrain_value(value, Some(false), false), EVMU256::ZERO);
+        assert_eq!(constrain_value(EVMU256::ZERO, Some(false), true), EVMU256::from(1));
+        assert_eq!(constrain_value(value, Some(true), false), value);
+        assert!(constrain_value(balance * EVMU256::from(3), None, false) <= balance);
+        assert!(constrain_value(value, Some(true), true) > balance);
+
+        let contract = EVMAddress::zero();
+        register_payability(contract, [1, 2, 3, 4], false);
+        assert_eq!(is_payable(&contract, &[1, 2, 3, 4]), Some(false));
+        assert_eq!(is_payable(&contract, &[4, 3, 2, 1]), None);
+    }
+}
@@ -0,0 +1,163 @@
+/// Callers named in the config (`--callers callers.json`) and the roles of the senders
+///
+/// The fuzzer names its own callers: `owner` for the deployer, `attacker1`... for the default
+/// callers and `attacker_contract1`... for the contract callers. The actors of the protocol can
+/// be named as well, with their role:
+/// ```json
+/// [{"name": "attacker", "address": "0xe1A425f1AC34A8a441566f93c82dD730639c8510"},
+///  {"name": "liquidity_provider", "role": "user"},
+///  {"name": "guardian", "address": "0x...", "role": "privileged"}]
+/// ```
+/// - a name replaces the alias of a built-in caller at the same address (`attacker` above is
+///   `attacker1`)
+/// - without an address, the caller gets one derived from its name (the last 20 bytes of its
+///   keccak)
+/// - the role is `attacker` (default), `user` or `privileged`; the default and contract callers
+///   are attackers, the deployer is privileged
+///
+/// Named callers join the caller pool. `--sender-roles attacker,user` restricts the senders the
+/// mutators pick to the callers of these roles, e.g., to only look for bugs an unprivileged
+/// account can trigger. Every output printing an address prints its name instead: the pretty
+/// transactions (solutions, traces, summary, shell), the state diffs and the YAML inputs, which
+/// also accept the names (`cli input decode --callers callers.json`).
+use crate::error::{load_json, MauResult};
+use crate::evm::token_minting::keccak;
+use crate::evm::types::{caller_alias, register_caller_alias, EVMAddress};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::info;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CallerRole {
+    /// Any account, e.g., the default callers of the fuzzer
+    #[default]
+    Attacker,
+    /// An unprivileged actor of the protocol (liquidity provider, borrower...)
+    User,
+    /// An account the protocol trusts (owner, guardian, keeper...)
+    Privileged,
+}
+
+impl FromStr for CallerRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "attacker" => Ok(CallerRole::Attacker),
+            "user" => Ok(CallerRole::User),
+            "privileged" => Ok(CallerRole::Privileged),
+            _ => Err(format!("unknown caller role {} (attacker, user, privileged)", s)),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NamedCaller {
+    pub name: String,
+    #[serde(default)]
+    pub address: Option<EVMAddress>,
+    #[serde(default)]
+    pub role: CallerRole,
+}
+
+impl NamedCaller {
+    /// Address of the caller, derived from its name if not given
+    pub fn address(&self) -> EVMAddress {
+        self.address.unwrap_or_else(|| {
+            EVMAddress::from_slice(&keccak(self.name.as_bytes()).to_be_bytes::<32>()[12..])
+        })
+    }
+}
+
+static mut NAMED_CALLERS: Vec<NamedCaller> = Vec::new();
+
+static mut CALLER_ROLES: Option<HashMap<EVMAddress, CallerRole>> = None;
+
+/// Roles of the senders picked by the mutators (`--sender-roles`), any when empty
+pub static mut SENDER_ROLES: Vec<CallerRole> = Vec::new();
+
+pub fn load_named_callers(path: &str) -> MauResult<Vec<NamedCaller>> {
+    load_json(path)
+}
+
+/// Roles of `--sender-roles`, comma separated
+pub fn parse_sender_roles(s: &str) -> Result<Vec<CallerRole>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|role| !role.is_empty())
+        .map(CallerRole::from_str)
+        .collect()
+}
+
+pub fn register_caller_role(address: EVMAddress, role: CallerRole) {
+    unsafe {
+        CALLER_ROLES.get_or_insert_with(Default::default).insert(address, role);
+    }
+}
+
+pub fn caller_role(address: &EVMAddress) -> Option<CallerRole> {
+    unsafe { CALLER_ROLES.as_ref()?.get(address).cloned() }
+}
+
+/// Whether the mutators may pick the caller as a sender
+pub fn sender_allowed(address: &EVMAddress) -> bool {
+    let roles = unsafe { &SENDER_ROLES };
+    roles.is_empty() || caller_role(address).map_or(false, |role| roles.contains(&role))
+}
+
+pub fn register_named_callers(callers: Vec<NamedCaller>) {
+    unsafe {
+        NAMED_CALLERS = callers;
+    }
+    apply_named_callers();
+}
+
+/// Register the names and roles of the named callers, over the aliases of the built-in callers
+pub fn apply_named_callers() {
+    for caller in unsafe { NAMED_CALLERS.iter() } {
+        let address = caller.address();
+        if let Some(alias) = caller_alias(&address).filter(|alias| *alias != caller.name) {
+            info!("Caller {} ({:?}) is now named {}", alias, address, caller.name);
+        }
+        register_caller_alias(address, &caller.name);
+        register_caller_role(address, caller.role);
+    }
+}
+
+pub fn named_callers() -> Vec<NamedCaller> {
+    unsafe { NAMED_CALLERS.clone() }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::types::alias_address;
+
+    #[test]
+    fn test_named_callers() {
+        let builtin = EVMAddress::from_slice(&[0xe1; 20]);
+        register_caller_alias(builtin, "attacker1");
+        register_caller_role(builtin, CallerRole::Attacker);
+        let callers: Vec<NamedCaller> = serde_json::from_str(&format!(
+            r#"[{{"name": "attacker", "address": "{:?}"}}, {{"name": "lp", "role": "user"}}]"#,
+            builtin
+        ))
+        .unwrap();
+        let lp = callers[1].address();
+        register_named_callers(callers);
+        assert_eq!(caller_alias(&builtin), Some("attacker".to_string()));
+        assert_eq!(alias_address("lp"), Some(lp));
+        assert_eq!(caller_role(&lp), Some(CallerRole::User));
+
+        unsafe {
+            SENDER_ROLES = parse_sender_roles("attacker").unwrap();
+        }
+        assert!(sender_allowed(&builtin));
+        assert!(!sender_allowed(&lp));
+        unsafe {
+            SENDER_ROLES = vec![];
+        }
+        assert!(parse_sender_roles("attacker,admin").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+/// Refinement of the static CFG of the targets with the jumps observed at runtime (`--refine-cfg`)
+///
+/// The CFG built from the bytecode and heimdall's symbolic execution misses the dynamic jumps
+/// heimdall cannot resolve (function pointers, jump tables, jumps on values loaded from
+/// storage), so the blocks behind them look unreachable: the directed distances ignore them and
+/// the withdrawal scan never visits them. With `--refine-cfg`, the host reports the jumps of
+/// each execution, and a jump landing in a block the static CFG does not reach triggers a
+/// re-analysis pass:
+/// - the observed jumps missing from the CFG are added to it, and the blocks they make
+///   reachable are counted as discovered
+/// - the directed distances of the contract are recomputed on the refined CFG
+/// - the jumps are stored along the heimdall analyses of the bytecode
+///   ([`crate::evm::onchain::abi_decompiler::store_observed_jump_edges`]), so that every later
+///   analysis of the bytecode, in this run or the next ones, starts from the refined CFG
+use crate::evm::directed::{block_of, block_starts, build_cfg, distances, register_distances, target_block, DirectTarget};
+use crate::evm::onchain::abi_decompiler::{fetch_jump_edges_heimdall, store_observed_jump_edges};
+use crate::evm::types::EVMAddress;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use tracing::{info, warn};
+
+/// Whether the host reports the jumps, on with `--refine-cfg`
+pub static mut REFINE_CFG: bool = false;
+
+static mut REFINED_CFGS: Option<HashMap<EVMAddress, RefinedCfg>> = None;
+
+/// CFG of a target, refined with the jumps observed at runtime
+#[derive(Clone, Debug)]
+pub struct RefinedCfg {
+    code: Vec<u8>,
+    starts: BTreeSet<usize>,
+    /// Jumps resolved statically, then the observed ones added by the refinements
+    edges: Vec<(usize, usize)>,
+    reachable: HashSet<usize>,
+    /// Observed jumps to blocks not reachable yet, waiting for the next refinement
+    pending: HashSet<(usize, usize)>,
+    /// Block of the directed target, when the contract is directed to
+    target_block: Option<usize>,
+    /// Blocks discovered by the refinements
+    pub discovered: usize,
+}
+
+/// Blocks reachable from the entry of the code
+fn reachable_blocks(cfg: &HashMap<usize, HashSet<usize>>) -> HashSet<usize> {
+    let mut reachable = HashSet::from([0]);
+    let mut queue = VecDeque::from([0]);
+    while let Some(block) = queue.pop_front() {
+        for next in cfg.get(&block).into_iter().flatten() {
+            if reachable.insert(*next) {
+                queue.push_back(*next);
+            }
+        }
+    }
+    reachable
+}
+
+impl RefinedCfg {
+    pub fn new(code: Vec<u8>, edges: Vec<(usize, usize)>, target_block: Option<usize>) -> Self {
+        let reachable = reachable_blocks(&build_cfg(&code, &edges));
+        Self {
+            starts: block_starts(&code),
+            code,
+            edges,
+            reachable,
+            pending: HashSet::new(),
+            target_block,
+            discovered: 0,
+        }
+    }
+
+    /// Record a jump taken at runtime, returns whether it lands in a block not reachable so far
+    pub fn observe(&mut self, from: usize, to: usize) -> bool {
+        if self.reachable.contains(&block_of(&self.starts, to)) {
+            return false;
+        }
+        self.pending.insert((from, to));
+        true
+    }
+
+    /// Add the pending jumps to the CFG, returns them and the number of blocks they make
+    /// reachable
+    pub fn refine(&mut self) -> (Vec<(usize, usize)>, usize) {
+        let mut added = self.pending.drain().collect::<Vec<_>>();
+        added.sort();
+        self.edges.extend(added.iter().cloned());
+        let reachable = reachable_blocks(&build_cfg(&self.code, &self.edges));
+        let discovered = reachable.difference(&self.reachable).count();
+        self.reachable = reachable;
+        self.discovered += discovered;
+        (added, discovered)
+    }
+
+    /// Distances to the directed target on the refined CFG
+    pub fn distances(&self) -> Option<HashMap<usize, u32>> {
+        let target = self.target_block?;
+        Some(distances(&build_cfg(&self.code, &self.edges), &HashSet::from([target])))
+    }
+}
+
+/// Register the CFG of a deployed target, with the directed target when it is directed to
+pub fn register_cfg(address: EVMAddress, code: Vec<u8>, target: Option<&DirectTarget>) {
+    let (entry_points, edges) = match fetch_jump_edges_heimdall(hex::encode(&code)) {
+        Ok(analysis) => analysis,
+        Err(e) => {
+            warn!("[cfg] {:?}: decompilation failed ({}), refining the CFG of the bytecode", address, e);
+            (HashMap::new(), vec![])
+        }
+    };
+    let target_block = target.and_then(|target| target_block(&code, &entry_points, target));
+    unsafe {
+        REFINED_CFGS
+            .get_or_insert_with(HashMap::new)
+            .insert(address, RefinedCfg::new(code, edges, target_block));
+    }
+}
+
+/// Called by the host on each jump taken when [`REFINE_CFG`] is on
+pub fn record_observed_jump(address: &EVMAddress, from: usize, to: usize) {
+    if let Some(cfg) = unsafe { REFINED_CFGS.as_mut() }.and_then(|cfgs| cfgs.get_mut(address)) {
+        cfg.observe(from, to);
+    }
+}
+
+/// Re-analyse the targets whose executions reached blocks the CFG did not, after an execution
+pub fn refine_pending_cfgs() {
+    let cfgs = match unsafe { REFINED_CFGS.as_mut() } {
+        Some(cfgs) => cfgs,
+        None => return,
+    };
+    for (address, cfg) in cfgs.iter_mut().filter(|(_, cfg)| !cfg.pending.is_empty()) {
+        let (added, discovered) = cfg.refine();
+        info!(
+            "[cfg] {:?}: {} jumps observed at runtime added, {} blocks discovered ({} in total)",
+            address,
+            added.len(),
+            discovered,
+            cfg.discovered
+        );
+        if let Some(distances) = cfg.distances() {
+            register_distances(*address, distances);
+        }
+        if let Err(e) = store_observed_jump_edges(hex::encode(&cfg.code), &added) {
+            warn!("[cfg] failed to store the observed jumps: {}", e);
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refine_cfg() {
+        // 0: PUSH1 0x00 CALLDATALOAD JUMP  4: JUMPDEST PUSH1 0x09 JUMP  8: INVALID  9: JUMPDEST STOP
+        let code = hex::decode("600035565b600956fe5b00").unwrap();
+        let mut cfg = RefinedCfg::new(code, vec![], Some(9));
+        assert!(cfg.distances().unwrap().get(&0).is_none());
+
+        // the dynamic jump of the entry block lands in the block at 4
+        assert!(cfg.observe(3, 4));
+        let (added, discovered) = cfg.refine();
+        assert_eq!((added, discovered), (vec![(3, 4)], 2));
+        assert!(!cfg.observe(3, 4));
+        assert_eq!(cfg.distances().unwrap().get(&0), Some(&2));
+    }
+}
@@ -5,6 +5,7 @@ use crate::evm::input::{EVMInput, EVMInputT};
 use crate::evm::middlewares::middleware::MiddlewareType::Concolic;
 use crate::evm::middlewares::middleware::{add_corpus, Middleware, MiddlewareType};
 
+use crate::evm::hex_utils::parse_hex;
 use crate::evm::host::{FuzzHost, JMP_MAP};
 use crate::generic_vm::vm_executor::MAP_SIZE;
 use crate::generic_vm::vm_state::VMStateT;
@@ -516,7 +517,7 @@ impl<I, VS> ConcolicHost<I, VS> {
 
     fn string_to_bytes(s: &str) -> Vec<u8> {
         // s: #x....
-        hex::decode(&s[2..]).unwrap()
+        parse_hex(s.trim_start_matches("#x")).unwrap_or_default()
     }
 
     pub fn solve(&self) -> Option<String> {
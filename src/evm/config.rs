@@ -1,10 +1,33 @@
 /// Configuration for the EVM fuzzer
-use crate::evm::contract_utils::ContractInfo;
+use crate::batch_composer::BatchMode;
+use crate::evm::contract_utils::{ABIConfig, ContractInfo};
+use crate::evm::entry_points::EntryPoint;
+use crate::evm::directed::DirectedTarget;
+use crate::evm::storage_layout::StorageLayout;
+use crate::evm::view_invariants::ViewInvariant;
+use crate::evm::goals::Goal;
+use crate::evm::evm_version::EVMVersion;
 use crate::evm::onchain::endpoints::{OnChainConfig, PriceOracle};
 
 use crate::evm::oracles::erc20::IERC20OracleFlashloan;
+use crate::evm::fuzz_target::FuzzTarget;
+use crate::evm::hooks::ExecutorHook;
+use crate::evm::packs::CorpusPack;
+use crate::evm::pausable::PausedMode;
+use crate::evm::callers::NamedCaller;
+use crate::evm::sanitizers::Sanitizer;
+use crate::evm::address_pool::AddressWeights;
+use crate::evm::adversary::AdversaryTxn;
+use crate::evm::amm::AmmPool;
+use crate::evm::weird_tokens::WeirdToken;
+use crate::evm::price_feeds::PriceFeed;
+use crate::evm::scenario::Scenario;
+use crate::evm::token_minting::TokenMint;
+use crate::evm::types::{EVMAddress, EVMU256};
 use crate::oracle::{Oracle, Producer};
+use crate::scheduler::{PowerSchedule, PruningPolicy};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub enum FuzzerTypes {
@@ -56,8 +79,63 @@ pub struct Config<VS, Addr, Code, By, Loc, SlotTy, Out, I, S> {
     pub corpus_path: String,
     pub ptx_path: String,
     pub gpu_dev: i32,
+    pub runner_lib: String,
     pub run_forever: bool,
     pub cov_path: String,
+    pub scenarios: Vec<Scenario>,
+    pub adversary: Vec<AdversaryTxn>,
+    pub price_feeds: Vec<PriceFeed>,
+    pub amm_pools: Vec<AmmPool>,
+    pub weird_tokens: Vec<WeirdToken>,
+    pub block_gas_limit: u64,
+    pub explore_pathological_env: bool,
+    pub verify_gpu_solutions: bool,
+    pub pc_trace_size: usize,
+    pub guard_phase_instructions: usize,
+    pub collector: Option<String>,
+    pub loop_bound: u8,
+    pub prepare_attacker_states: bool,
+    pub gpu_memory_sanitizer: bool,
+    pub entry_points: HashMap<String, Vec<EntryPoint>>,
+    pub token_mints: Vec<TokenMint>,
+    pub batch_mode: BatchMode,
+    pub adaptive_batch: bool,
+    pub gpu_resident: usize,
+    pub resident_launches: u32,
+    pub address_weights: AddressWeights,
+    pub evm_version: EVMVersion,
+    pub force_ptx: bool,
+    pub direct_to: Option<DirectedTarget>,
+    pub grammar: HashMap<String, Vec<ABIConfig>>,
+    pub storage_layouts: HashMap<String, StorageLayout>,
+    pub cpu_workers: usize,
+    pub state_pruning: PruningPolicy,
+    pub max_states: usize,
+    pub power_schedule: PowerSchedule,
+    /// Executions of each executor benchmarked by `cli perf`, `None` for a campaign
+    pub perf_executions: Option<u64>,
+    /// Seconds of fuzzing before stopping, 0 for no limit
+    pub time_budget: u64,
+    /// Budget of the resident set size of the process in bytes, 0 for none
+    pub max_host_mem: usize,
+    pub caller_balance: EVMU256,
+    pub dictionary_dir: Option<String>,
+    /// Work directory of a run on a similar target whose corpus seeds the campaign
+    pub warm_start: Option<String>,
+    pub interface_oracles: bool,
+    pub view_invariants: Vec<ViewInvariant>,
+    /// Attacker goals, see [`crate::evm::goals`]
+    pub goals: Vec<Goal>,
+    pub sanitizers: Vec<Sanitizer>,
+    pub packs: Vec<CorpusPack>,
+    /// What to do with the targets paused in the initial state, see [`crate::evm::pausable`]
+    pub paused_mode: PausedMode,
+    /// Callers named in the config, see [`crate::evm::callers`]
+    pub named_callers: Vec<NamedCaller>,
+    /// Observers of the executions, see [`crate::evm::hooks`]
+    pub hooks: Vec<Rc<RefCell<dyn ExecutorHook>>>,
+    /// Custom harnesses, see [`crate::evm::fuzz_target`]
+    pub targets: Vec<Rc<RefCell<dyn FuzzTarget>>>,
 }
 
 
@@ -68,8 +146,29 @@ pub const NJOBS: u32 = 1024;//8192;
 
 pub static mut RUN_FOREVER: bool = false;
 
+/// Seconds of fuzzing before the campaign stops, 0 for no limit
+pub static mut TIME_BUDGET: u64 = 0;
+
 pub static mut GPU_ENABLE: bool = false;
 
+/// Contract whose code the PTX kernel is translated from
+pub static mut GPU_KERNEL_ADDRESS: Option<EVMAddress> = None;
+
 pub static mut DUMP_CORPUS: bool = false;
 
+/// Replay bugs found in GPU on revm and only report the confirmed ones
+pub static mut VERIFY_GPU_SOLUTIONS: bool = true;
+
+/// Number of recent PCs kept per GPU thread for bug reports, 0 disables the ring buffer
+pub static mut PC_TRACE_SIZE: usize = 0;
+
+/// Block gas limit of mainnet, used when the chain profile is unknown
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Upper bound of the `gas_limit` env mutation
+pub static mut BLOCK_GAS_LIMIT: u64 = DEFAULT_BLOCK_GAS_LIMIT;
+
+/// Allow env mutation to generate pathological values (e.g., gas limit beyond any block limit)
+pub static mut EXPLORE_PATHOLOGICAL_ENV: bool = false;
+
 pub const STATS_CPU_DEFAULT: u64 = 300;
\ No newline at end of file
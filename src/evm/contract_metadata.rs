@@ -0,0 +1,187 @@
+/// Compiler metadata appended to the runtime code
+///
+/// solc (and vyper) append a CBOR map to the runtime code, followed by its length on two bytes:
+/// `a2 64 'ipfs' 58 22 <multihash> 64 'solc' 43 <major minor patch> 00 33`. These bytes are
+/// not code: they are stripped before the bytecode analyses and the decompiler, which would
+/// otherwise collect constants or functions from them. The decoded metadata is written to the
+/// run manifest.
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractMetadata {
+    /// IPFS hash of the metadata JSON (base58)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipfs: Option<String>,
+    /// Swarm hash of the metadata JSON (hex), legacy compilers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bzzr: Option<String>,
+    /// Compiler version, e.g., `0.8.19`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solc: Option<String>,
+    /// Whether experimental features are enabled
+    pub experimental: bool,
+    /// Number of bytes of the metadata, including the length
+    pub length: usize,
+}
+
+/// Number of trailing metadata bytes of the runtime code, including the length, 0 if none
+pub fn metadata_length(code: &[u8]) -> usize {
+    if code.len() < 2 {
+        return 0;
+    }
+    let len = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    if len == 0 || len + 2 > code.len() {
+        return 0;
+    }
+    // the CBOR payload is a map
+    match code[code.len() - 2 - len] {
+        0xa1..=0xb7 => len + 2,
+        _ => 0,
+    }
+}
+
+/// Runtime code without the trailing metadata
+pub fn strip_metadata(code: &[u8]) -> &[u8] {
+    &code[..code.len() - metadata_length(code)]
+}
+
+/// [`strip_metadata`] on hex encoded code, as given to the decompiler
+pub fn strip_metadata_hex(code: &str) -> String {
+    match hex::decode(code.trim_start_matches("0x")) {
+        Ok(bytes) => hex::encode(strip_metadata(&bytes)),
+        Err(_) => code.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CborValue {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Bool(bool),
+}
+
+/// Minimal CBOR reader for the metadata map
+struct CborReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CborReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    /// Major type and argument of the next item
+    fn header(&mut self) -> Option<(u8, u64)> {
+        let initial = *self.take(1)?.first()?;
+        let arg = match initial & 0x1f {
+            n @ 0..=23 => n as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().ok()?) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().ok()?) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().ok()?),
+            _ => return None,
+        };
+        Some((initial >> 5, arg))
+    }
+
+    fn value(&mut self) -> Option<CborValue> {
+        match self.header()? {
+            (0, n) => Some(CborValue::Uint(n)),
+            (2, n) => Some(CborValue::Bytes(self.take(n as usize)?.to_vec())),
+            (3, n) => Some(CborValue::Text(String::from_utf8(self.take(n as usize)?.to_vec()).ok()?)),
+            (7, 20) => Some(CborValue::Bool(false)),
+            (7, 21) => Some(CborValue::Bool(true)),
+            _ => None,
+        }
+    }
+}
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58(data: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for byte in data {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat(b'1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|d| BASE58_ALPHABET[*d as usize]))
+        .map(|c| c as char)
+        .collect()
+}
+
+/// Decode the metadata of the runtime code, `None` if it has none or it is malformed
+pub fn decode_metadata(code: &[u8]) -> Option<ContractMetadata> {
+    let length = metadata_length(code);
+    if length == 0 {
+        return None;
+    }
+    let mut reader = CborReader {
+        data: &code[code.len() - length..code.len() - 2],
+        pos: 0,
+    };
+    let (major, entries) = reader.header()?;
+    if major != 5 {
+        return None;
+    }
+    let mut metadata = ContractMetadata {
+        length,
+        ..Default::default()
+    };
+    for _ in 0..entries {
+        let key = match reader.value()? {
+            CborValue::Text(key) => key,
+            _ => return None,
+        };
+        match (key.as_str(), reader.value()?) {
+            ("ipfs", CborValue::Bytes(hash)) => metadata.ipfs = Some(base58(&hash)),
+            ("bzzr0" | "bzzr1", CborValue::Bytes(hash)) => metadata.bzzr = Some(hex::encode(hash)),
+            // release builds store the version as 3 bytes, others as a string
+            ("solc", CborValue::Bytes(v)) if v.len() == 3 => {
+                metadata.solc = Some(format!("{}.{}.{}", v[0], v[1], v[2]))
+            }
+            ("solc", CborValue::Text(v)) => metadata.solc = Some(v),
+            ("experimental", CborValue::Bool(b)) => metadata.experimental = b,
+            _ => {}
+        }
+    }
+    Some(metadata)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_metadata() {
+        // tail of a solc 0.8.19 runtime code
+        let code = hex::decode(
+            "6080604052600080fdfea2646970667358221220\
+             5377adc79bb987de03049c655529acbf51a3f7d36bb14c89a2d2f790d2b8b5e8\
+             64736f6c63430008130033",
+        )
+        .unwrap();
+        assert_eq!(strip_metadata(&code), hex::decode("6080604052600080fdfe").unwrap().as_slice());
+        let metadata = decode_metadata(&code).unwrap();
+        assert_eq!(metadata.solc, Some("0.8.19".to_string()));
+        assert!(metadata.ipfs.unwrap().starts_with("Qm"));
+        assert_eq!(metadata.length, 53);
+
+        let code = hex::decode("6080604052600080fd").unwrap();
+        assert_eq!(strip_metadata(&code), code.as_slice());
+        assert_eq!(decode_metadata(&code), None);
+    }
+}
@@ -8,11 +8,16 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use itertools::Itertools;
+use tracing::{debug, info, warn};
 use crate::state::FuzzState;
 extern crate crypto;
 
-use crate::evm::abi::get_abi_type_boxed_with_address;
+use crate::evm::abi::{get_abi_type_boxed_with_address, register_function_args};
+use crate::evm::onchain::abi_decompiler::fetch_abi_heimdall;
 use crate::evm::onchain::endpoints::OnChainConfig;
+use crate::evm::hex_utils::{parse_address, HexBytecode};
+use crate::evm::project::{link, Project};
+use crate::error::{MauError, MauResult};
 use crate::evm::srcmap::parser::{decode_instructions, SourceMapLocation};
 
 use self::crypto::digest::Digest;
@@ -96,6 +101,7 @@ impl ContractLoader {
                         "constructor"
                     };
                     let mut abi_name: Vec<String> = vec![];
+                    let mut arg_names: Vec<String> = vec![];
                     abi["inputs"]
                         .as_array()
                         .expect("failed to parse abi inputs")
@@ -105,20 +111,22 @@ impl ContractLoader {
                                 input["type"].as_str().unwrap().to_string(),
                                 input,
                             ));
+                            arg_names.push(input["name"].as_str().unwrap_or_default().to_string());
                         });
                     let mut abi_config = ABIConfig {
                         abi: format!("({})", abi_name.join(",")),
                         function: [0; 4],
                         function_name: name.to_string(),
-                        is_static: abi["stateMutability"].as_str().unwrap() == "view",
+                        is_static: matches!(abi["stateMutability"].as_str().unwrap(), "view" | "pure"),
                         is_payable: abi["stateMutability"].as_str().unwrap() == "payable",
                         is_constructor: abi["type"] == "constructor",
                     };
                     let function_to_hash = format!("{}({})", name, abi_name.join(","));
                     // print name and abi_name
-                    println!("{}({})", name, abi_name.join(","));
+                    debug!("{}({})", name, abi_name.join(","));
 
                     set_hash(function_to_hash.as_str(), &mut abi_config.function);
+                    register_function_args(abi_config.function, arg_names.into_iter().zip(abi_name).collect());
                     Some(abi_config)
                 } else {
                     None
@@ -127,11 +135,11 @@ impl ContractLoader {
             .collect()
     }
 
-    fn parse_hex_file(path: &Path) -> Vec<u8> {
+    fn read_hex_file(path: &Path) -> String {
         let mut file = File::open(path).unwrap();
         let mut data = String::new();
         file.read_to_string(&mut data).unwrap();
-        hex::decode(data).expect("Failed to parse hex file")
+        data
     }
 
     pub fn from_prefix(prefix: &str, state: &mut EVMFuzzState, source_map_info: Option<ContractsSourceMapInfo>) -> Self {
@@ -146,7 +154,7 @@ impl ContractLoader {
                 info.get(prefix).expect("combined.json provided but contract not found").clone()
             ),
         };
-        println!("Loading contract {}", prefix);
+        info!("Loading contract {}", prefix);
         for i in glob(prefix).expect("not such path for prefix") {
             match i {
                 Ok(path) => {
@@ -156,21 +164,35 @@ impl ContractLoader {
                         // println!("ABI: {:?}", result.abi);
                     } else if path.to_str().unwrap().ends_with(".bin") {
                         // this is an BIN file
-                        result.code = Self::parse_hex_file(&path);
+                        match HexBytecode::from_hex(&Self::read_hex_file(&path)) {
+                            Ok(code) => result.code = code.into_bytes(),
+                            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+                        }
                     } else if path.to_str().unwrap().ends_with(".address") {
                         // this is deployed address
-                        result
-                            .deployed_address
-                            .0
-                            .clone_from_slice(Self::parse_hex_file(&path).as_slice());
+                        match parse_address(&Self::read_hex_file(&path)) {
+                            Ok(address) => result.deployed_address = address,
+                            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+                        }
                     } else {
-                        println!("Found unknown file: {:?}", path.display())
+                        warn!("Found unknown file: {:?}", path.display())
                     }
                 }
-                Err(e) => println!("{:?}", e),
+                Err(e) => warn!("{:?}", e),
             }
         }
 
+        Self::append_constructor_args(&mut result);
+        return Self {
+            contracts: if result.code.len() > 0 {
+                vec![result]
+            } else {
+                vec![]
+            },
+        };
+    }
+
+    fn append_constructor_args(result: &mut ContractInfo) {
         if let Some(abi) = result.abi.iter().find(|abi| abi.is_constructor) {
             let mut abi_instance =
                 get_abi_type_boxed_with_address(&abi.abi, fixed_address(FIX_DEPLOYER).0.to_vec());
@@ -189,13 +211,6 @@ impl ContractLoader {
             // println!("Constructor args: {:?}", result.constructor_args);
             result.code.extend(result.constructor_args.clone());
         }
-        return Self {
-            contracts: if result.code.len() > 0 {
-                vec![result]
-            } else {
-                vec![]
-            },
-        };
     }
 
     // This function loads constructs Contract infos from path p
@@ -223,10 +238,10 @@ impl ContractLoader {
                     } else if path_str.ends_with("combined.json") {
                         contract_combined_json_info = Some(path_str.to_string());
                     } else {
-                        println!("Found unknown file in folder: {:?}", path.display())
+                        warn!("Found unknown file in folder: {:?}", path.display())
                     }
                 }
-                Err(e) => println!("{:?}", e),
+                Err(e) => warn!("{:?}", e),
             }
         }
 
@@ -256,13 +271,40 @@ impl ContractLoader {
         ContractLoader { contracts }
     }
 
+    // Loads the targets of a Foundry / Hardhat project, along with the libraries they link
+    pub fn from_project(project: &Project, names: &Vec<String>, state: &mut EVMFuzzState) -> MauResult<Self> {
+        let order = project
+            .targets(names)
+            .and_then(|targets| project.deployment_order(&targets))
+            .map_err(MauError::Project)?;
+        let mut addresses = HashMap::new();
+        let mut contracts: Vec<ContractInfo> = vec![];
+        for idx in order {
+            let contract = &project.contracts[idx];
+            info!("Loading contract {}", contract.qualified_name());
+            let mut result = ContractInfo {
+                name: contract.name.clone(),
+                abi: Self::parse_abi_str(&contract.abi),
+                code: link(&contract.bytecode, &contract.link_references, &addresses)
+                    .map_err(|e| MauError::Project(format!("failed to link {}: {}", contract.name, e)))?,
+                is_code_deployed: false,
+                constructor_args: vec![],
+                deployed_address: generate_random_address(state),
+                source_map: None,
+            };
+            Self::append_constructor_args(&mut result);
+            addresses.insert((contract.source.clone(), contract.name.clone()), result.deployed_address);
+            contracts.push(result);
+        }
+        Ok(Self { contracts })
+    }
 
     pub fn from_address(onchain: &mut OnChainConfig, address: HashSet<EVMAddress>) -> Self {
         let mut contracts: Vec<ContractInfo> = vec![];
         for addr in address {
             let abi = onchain.fetch_abi(addr);
             if abi.is_none() {
-                println!("ABI not found for {}", addr);
+                warn!("ABI not found for {}", addr);
                 continue;
             }
             let contract = ContractInfo {
@@ -278,6 +320,34 @@ impl ContractLoader {
         }
         Self { contracts }
     }
+
+    // Loads a contract given as bytecode (creation code, or runtime code when `deployed`), with
+    // the ABI recovered by the decompiler
+    pub fn from_bytecode(code: Vec<u8>, deployed: bool, state: &mut EVMFuzzState) -> Self {
+        let runtime = if deployed {
+            code.as_slice()
+        } else {
+            crate::campaign::runtime_template(&code).unwrap_or(&code)
+        };
+        let abi = fetch_abi_heimdall(hex::encode(runtime)).unwrap_or_else(|e| {
+            warn!("failed to decompile the bytecode: {}", e);
+            vec![]
+        });
+        let mut hash = [0u8; 32];
+        let mut hasher = Sha3::keccak256();
+        hasher.input(&code);
+        hasher.result(&mut hash);
+        let contract = ContractInfo {
+            name: format!("bytecode_{}", hex::encode(&hash[..4])),
+            abi,
+            code,
+            is_code_deployed: deployed,
+            constructor_args: vec![],
+            deployed_address: generate_random_address(state),
+            source_map: None,
+        };
+        Self { contracts: vec![contract] }
+    }
 }
 
 type ContractSourceMap = HashMap<usize, SourceMapLocation>;
@@ -301,7 +371,13 @@ pub fn parse_combined_json(json: String) -> ContractsSourceMapInfo {
         let contract_name = splitter.last().unwrap().to_string();
 
         let bin_runtime = contract_info["bin-runtime"].as_str().expect("bin-runtime not found");
-        let bin_runtime_bytes = hex::decode(bin_runtime).expect("bin-runtime is not hex");
+        let bin_runtime_bytes = match HexBytecode::from_hex(bin_runtime) {
+            Ok(code) => code.into_bytes(),
+            Err(e) => {
+                warn!("Skipping the source map of {}: {}", contract_name, e);
+                continue;
+            }
+        };
 
         let srcmap_runtime = contract_info["srcmap-runtime"].as_str().expect("srcmap-runtime not found");
 
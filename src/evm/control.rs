@@ -0,0 +1,80 @@
+/// Control socket of a campaign, `<work_dir>/control.sock`
+///
+/// A client (e.g., `cli shell`) connects, writes a corpus file (any version, see
+/// [`crate::evm::corpus_format`]), shuts down its side of the connection and reads back a one
+/// line reply. The transactions are queued and replayed by the mutational stage from the
+/// initial state, so that inputs edited by hand join the corpus when they are interesting.
+use crate::evm::corpus_format::{parse_corpus_file, CorpusFile, CorpusTxn};
+use crate::workdir::work_path;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use tracing::{info, warn};
+
+pub const CONTROL_SOCKET: &str = "control.sock";
+
+/// Sequences received, replayed by the next stage run
+static PUSHED: Mutex<Vec<Vec<CorpusTxn>>> = Mutex::new(vec![]);
+
+fn handle(mut stream: UnixStream) -> Result<String, String> {
+    let mut content = String::new();
+    stream.read_to_string(&mut content).map_err(|e| e.to_string())?;
+    let (_, file) = parse_corpus_file(&content)?;
+    let txns = file.txns();
+    if txns.is_empty() {
+        return Err("no transaction".to_string());
+    }
+    let count = txns.len();
+    PUSHED.lock().unwrap().push(txns);
+    Ok(format!("queued {} transactions", count))
+}
+
+/// Listen on the control socket of the work directory
+pub fn start_control_socket() {
+    let path = work_path(CONTROL_SOCKET);
+    // stale socket of a previous campaign
+    let _ = fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("[control] failed to listen on {}: {}", path, e);
+            return;
+        }
+    };
+    info!("[control] listening on {}", path);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let reply = match stream.try_clone().map_err(|e| e.to_string()).and_then(handle) {
+                Ok(reply) => reply,
+                Err(e) => format!("error: {}", e),
+            };
+            let mut stream = stream;
+            let _ = stream.write_all(format!("{}\n", reply).as_bytes());
+        }
+    });
+}
+
+/// Sequences pushed since the last call
+pub fn take_pushed() -> Vec<Vec<CorpusTxn>> {
+    std::mem::take(&mut *PUSHED.lock().unwrap())
+}
+
+/// Push a corpus file to the campaign running in the work directory, returns its reply
+pub fn push_to_campaign(work_dir: &str, file: &CorpusFile) -> Result<String, String> {
+    let path = Path::new(work_dir).join(CONTROL_SOCKET);
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("no campaign listening on {}: {}", path.display(), e))?;
+    stream.write_all(file.to_json().as_bytes()).map_err(|e| e.to_string())?;
+    stream.shutdown(Shutdown::Write).map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).map_err(|e| e.to_string())?;
+    let reply = reply.trim().to_string();
+    match reply.strip_prefix("error: ") {
+        Some(e) => Err(e.to_string()),
+        None => Ok(reply),
+    }
+}
@@ -0,0 +1,452 @@
+/// Versioned format of the replayable corpus files
+///
+/// Corpus files are JSON documents tagged with the version of their format
+/// (`{"version": "1", "txns": [...]}`), so that changing the transactions (e.g., adding a field
+/// to [`EVMInput`]) does not make older corpora unreadable: add a variant to [`CorpusFile`] and
+/// migrate the previous one in [`CorpusFile::upgrade`]. Files without a tag are the legacy
+/// space separated text format (version 0, see [`crate::tracer::TxnTrace::to_file_str`]).
+/// `cli corpus upgrade <dir>` rewrites the files of a corpus in the latest format.
+///
+/// The last transaction of a file written by the fuzzer carries the scheduling metadata of the
+/// input that made it interesting ([`InputMeta`]): its access pattern, randomness, branch
+/// distance, whether the GPU found it and its scenario and adversary, so that a campaign resumed
+/// from the corpus (`--warm-start`, replays) mutates and schedules it as the run did.
+use crate::evm::abi::{function_args, function_sig_name, register_function_args, register_function_name};
+use crate::evm::block_advance::BlockAdvance;
+use crate::evm::input::EVMInput;
+#[cfg(feature = "flashloan_v2")]
+use crate::evm::input::EVMInputTy;
+use crate::evm::mutator::AccessPattern;
+use crate::evm::onchain::abi_decompiler::parse_signature;
+use crate::evm::price_feeds::parse_price_list;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::state_input::StagedVMState;
+use bytes::Bytes;
+use revm_primitives::{BlockEnv, Env};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Version of the corpus files written by the fuzzer
+pub const CORPUS_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TxnKind {
+    /// Call with ABI encoded calldata
+    Abi,
+    /// Flashloan borrow of the token at `contract`
+    Borrow,
+}
+
+/// A transaction of a corpus file (version 1)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CorpusTxn {
+    pub kind: TxnKind,
+    pub caller: EVMAddress,
+    pub contract: EVMAddress,
+    /// Hex encoded calldata, or randomness of a borrow
+    pub data: String,
+    pub value: EVMU256,
+    pub liquidation_percent: u8,
+    /// Block number the transaction is executed at, the timestamp is 1000 times it
+    pub warp_to: u64,
+    pub repeat: usize,
+    /// Number of calls before re-entering (see [`crate::evm::host::CALL_UNTIL`])
+    pub reentrancy_limit: u32,
+    /// Whether the transaction stops at the control leak
+    pub step: bool,
+    /// Prices reported by the price feeds
+    #[serde(default)]
+    pub feed_prices: Vec<EVMU256>,
+    /// Ether force-fed to the target before the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_feed: Option<EVMU256>,
+    /// Block of the transaction relative to the previous one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_advance: Option<BlockAdvance>,
+    /// Gas limit of the transaction, the block gas limit if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<u64>,
+    /// Signature of the function called when its name is known (e.g., resolved for a decompiled
+    /// target), so that the call is printed with it where the ABI is not available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// Scheduling metadata of the input of the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<InputMeta>,
+}
+
+/// Per-input metadata used by the mutators and the scheduler, lost by the calldata alone
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct InputMeta {
+    pub access_pattern: AccessPattern,
+    /// Hex encoded additional random bytes of the mutator
+    pub randomness: String,
+    pub branch_distance: usize,
+    pub is_cuda: bool,
+    pub scenario: Option<usize>,
+    pub adversary: Option<usize>,
+}
+
+impl InputMeta {
+    pub fn of(input: &EVMInput) -> Self {
+        Self {
+            access_pattern: input.access_pattern.borrow().clone(),
+            randomness: hex::encode(&input.randomness),
+            branch_distance: input.branch_distance,
+            is_cuda: input.is_cuda,
+            scenario: input.scenario,
+            adversary: input.adversary,
+        }
+    }
+
+    /// Restore the metadata on an input replaying the transaction
+    pub fn apply(&self, input: &mut EVMInput) {
+        input.access_pattern = Rc::new(RefCell::new(self.access_pattern.clone()));
+        if input.randomness.is_empty() {
+            input.randomness = hex::decode(&self.randomness).unwrap_or_default();
+        }
+        input.branch_distance = self.branch_distance;
+        input.is_cuda = self.is_cuda;
+        input.scenario = self.scenario;
+        input.adversary = self.adversary;
+    }
+}
+
+/// Corpus file, tagged with its version
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "version")]
+pub enum CorpusFile {
+    #[serde(rename = "1")]
+    V1 { txns: Vec<CorpusTxn> },
+}
+
+impl CorpusFile {
+    pub fn version(&self) -> u32 {
+        match self {
+            CorpusFile::V1 { .. } => 1,
+        }
+    }
+
+    /// Migrate the file to the latest version
+    pub fn upgrade(self) -> Self {
+        match self {
+            CorpusFile::V1 { .. } => self,
+        }
+    }
+
+    /// Transactions of the file, in the latest version
+    pub fn txns(self) -> Vec<CorpusTxn> {
+        match self.upgrade() {
+            CorpusFile::V1 { txns } => txns,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+fn column<T: FromStr>(columns: &Vec<&str>, idx: usize, default: T) -> T {
+    columns.get(idx).and_then(|c| c.parse::<T>().ok()).unwrap_or(default)
+}
+
+/// Parse the legacy text format:
+/// `abi|borrow <caller> <contract> <data> <value> <liq percent> <warp to> [<repeat> <reentrancy> <step> <feed prices> <force feed> <block advance> <gas limit>]`
+pub fn parse_legacy(content: &str) -> Result<Vec<CorpusTxn>, String> {
+    let mut txns = vec![];
+    for (line_no, line) in content.lines().enumerate() {
+        let columns = line.split_whitespace().collect::<Vec<&str>>();
+        if columns.len() < 5 {
+            continue;
+        }
+        let err = |e: String| format!("line {}: {}", line_no + 1, e);
+        let kind = match columns[0] {
+            "abi" => TxnKind::Abi,
+            "borrow" => TxnKind::Borrow,
+            other => return Err(err(format!("unknown transaction type {}", other))),
+        };
+        let caller = EVMAddress::from_str(columns[1]).map_err(|e| err(format!("{:?}", e)))?;
+        let contract = EVMAddress::from_str(columns[2]).map_err(|e| err(format!("{:?}", e)))?;
+        hex::decode(columns[3]).map_err(|e| err(e.to_string()))?;
+        let value = EVMU256::from_str_radix(columns[4], 10).map_err(|e| err(e.to_string()))?;
+        txns.push(CorpusTxn {
+            kind,
+            caller,
+            contract,
+            data: columns[3].to_string(),
+            value,
+            liquidation_percent: column(&columns, 5, 0),
+            warp_to: column(&columns, 6, 0),
+            repeat: match kind {
+                TxnKind::Abi => column(&columns, 7, 0),
+                TxnKind::Borrow => 1,
+            },
+            reentrancy_limit: match kind {
+                TxnKind::Abi => column(&columns, 8, u32::MAX),
+                TxnKind::Borrow => u32::MAX,
+            },
+            step: kind == TxnKind::Abi && column(&columns, 9, false),
+            feed_prices: columns.get(10).map(|list| parse_price_list(list)).unwrap_or_default(),
+            force_feed: columns.get(11).and_then(|value| EVMU256::from_str_radix(value, 10).ok()),
+            block_advance: columns.get(12).and_then(|advance| advance.parse::<BlockAdvance>().ok()),
+            gas_limit: columns.get(13).and_then(|limit| limit.parse().ok()),
+            function: None,
+            meta: None,
+        });
+    }
+    Ok(txns)
+}
+
+/// Parse a corpus file of any version, returns its version and the file in the latest version
+pub fn parse_corpus_file(content: &str) -> Result<(u32, CorpusFile), String> {
+    if content.trim_start().starts_with('{') {
+        let file: CorpusFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        Ok((file.version(), file.upgrade()))
+    } else {
+        Ok((0, CorpusFile::V1 { txns: parse_legacy(content)? }))
+    }
+}
+
+pub fn load_corpus_file(path: &str) -> Result<Vec<CorpusTxn>, String> {
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+    parse_corpus_file(&content)
+        .map(|(_, file)| file.txns())
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Rewrite the corpus files of the directory in the latest format, returns the number of files
+/// upgraded. Files that are not corpus files (e.g., the binary inputs of LibAFL) are skipped.
+pub fn upgrade_corpus_dir(dir: &str) -> Result<usize, String> {
+    let mut upgraded = 0;
+    for entry in fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir, e))? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if !path.is_file() || !path.to_string_lossy().ends_with("_replayable") {
+            continue;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let (version, file) = parse_corpus_file(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if version < CORPUS_FORMAT_VERSION {
+            write_corpus_file(&path.to_string_lossy(), &file)?;
+            upgraded += 1;
+        }
+    }
+    Ok(upgraded)
+}
+
+pub fn write_corpus_file(path: &str, file: &CorpusFile) -> Result<(), String> {
+    File::create(Path::new(path))
+        .and_then(|mut f| f.write_all(file.to_json().as_bytes()))
+        .map_err(|e| format!("failed to write {}: {}", path, e))
+}
+
+impl CorpusTxn {
+    /// Line of the transaction in the legacy text format, see [`parse_legacy`]
+    pub fn to_legacy_line(&self) -> String {
+        let mut columns = vec![
+            match self.kind {
+                TxnKind::Abi => "abi".to_string(),
+                TxnKind::Borrow => "borrow".to_string(),
+            },
+            format!("{:?}", self.caller),
+            format!("{:?}", self.contract),
+            self.data.clone(),
+            self.value.to_string(),
+            self.liquidation_percent.to_string(),
+            self.warp_to.to_string(),
+        ];
+        if self.kind == TxnKind::Abi {
+            columns.extend([self.repeat.to_string(), self.reentrancy_limit.to_string(), self.step.to_string()]);
+            // trailing columns: feed prices, force-fed ether, block advancement and gas limit, "-" for none
+            let mut env_columns = vec![
+                if self.feed_prices.is_empty() {
+                    "-".to_string()
+                } else {
+                    self.feed_prices.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",")
+                },
+                self.force_feed.map_or("-".to_string(), |value| value.to_string()),
+                self.block_advance.map_or("-".to_string(), |advance| advance.to_string()),
+                self.gas_limit.map_or("-".to_string(), |limit| limit.to_string()),
+            ];
+            while env_columns.last().map_or(false, |column| column == "-") {
+                env_columns.pop();
+            }
+            columns.extend(env_columns);
+        }
+        format!("{}\n", columns.join(" "))
+    }
+
+    fn selector(&self) -> Option<[u8; 4]> {
+        let data = hex::decode(&self.data).ok()?;
+        match self.kind {
+            TxnKind::Abi if data.len() >= 4 => data[..4].try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// Record the signature of the function called, if its name is known
+    pub fn annotate_function(&mut self) {
+        let selector = match self.selector() {
+            Some(selector) => selector,
+            None => return,
+        };
+        let name = function_sig_name(&selector);
+        if name == hex::encode(selector) {
+            return;
+        }
+        let types = function_args(&selector)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(_, ty)| ty)
+            .collect::<Vec<_>>();
+        self.function = Some(format!("{}({})", name, types.join(",")));
+    }
+
+    /// Register the name and argument types of the function called from the recorded
+    /// signature, unless the ABIs loaded already know them
+    pub fn register_function(&self) {
+        let (selector, signature) = match (self.selector(), &self.function) {
+            (Some(selector), Some(signature)) => (selector, signature),
+            _ => return,
+        };
+        let (name, types) = parse_signature(signature);
+        if function_sig_name(&selector) == hex::encode(selector) {
+            register_function_name(selector, name);
+        }
+        if function_args(&selector).is_none() {
+            register_function_args(selector, types.into_iter().map(|ty| (String::new(), ty)).collect());
+        }
+    }
+
+    /// Input replaying the transaction on the given state
+    pub fn to_input(&self, sstate: StagedVMState<EVMAddress, EVMAddress, EVMState>) -> EVMInput {
+        let data = hex::decode(&self.data).unwrap_or_default();
+        let mut input = EVMInput {
+            caller: self.caller,
+            contract: self.contract,
+            data: None,
+            sstate,
+            sstate_idx: 0,
+            branch_distance: 0,
+            txn_value: if self.value == EVMU256::ZERO { None } else { Some(self.value) },
+            step: self.step,
+            env: Env {
+                cfg: Default::default(),
+                block: BlockEnv {
+                    number: EVMU256::from(self.warp_to),
+                    coinbase: Default::default(),
+                    timestamp: EVMU256::from(self.warp_to * 1000),
+                    difficulty: Default::default(),
+                    prevrandao: None,
+                    basefee: Default::default(),
+                    gas_limit: Default::default(),
+                },
+                tx: Default::default(),
+            },
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            #[cfg(feature = "flashloan_v2")]
+            liquidation_percent: self.liquidation_percent,
+            #[cfg(feature = "flashloan_v2")]
+            input_type: match self.kind {
+                TxnKind::Abi => EVMInputTy::ABI,
+                TxnKind::Borrow => EVMInputTy::Borrow,
+            },
+            direct_data: match self.kind {
+                // a single zero byte encodes empty calldata
+                TxnKind::Abi if !(data.len() == 1 && data[0] == 0) => Bytes::from(data.clone()),
+                _ => Bytes::new(),
+            },
+            randomness: match self.kind {
+                TxnKind::Borrow => data,
+                TxnKind::Abi => vec![],
+            },
+            repeat: self.repeat,
+            cu_data: vec![],
+            is_cuda: false,
+            scenario: None,
+            adversary: None,
+            feed_prices: self.feed_prices.clone(),
+            force_feed: self.force_feed,
+            block_advance: self.block_advance,
+            gas_limit: self.gas_limit,
+        };
+        if let Some(meta) = &self.meta {
+            meta.apply(&mut input);
+        }
+        input
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_legacy_corpus() {
+        let legacy = "abi 0x8b21e662154b4bbc1ec0754d0238875fe3d22fa6 0x1000000000000000000000000000000000000000 a9059cbb 5 0 2 1 3 true \n\n\
+                      borrow 0x8b21e662154b4bbc1ec0754d0238875fe3d22fa6 0x1000000000000000000000000000000000000000 00 0 0 0 \n";
+        let (version, file) = parse_corpus_file(legacy).unwrap();
+        assert_eq!(version, 0);
+        let txns = file.clone().txns();
+        assert_eq!(txns.len(), 2);
+        assert_eq!(txns[0].value, EVMU256::from(5));
+        assert_eq!(txns[0].reentrancy_limit, 3);
+        assert!(txns[0].step);
+        assert_eq!(txns[1].kind, TxnKind::Borrow);
+
+        let (version, upgraded) = parse_corpus_file(&file.to_json()).unwrap();
+        assert_eq!(version, CORPUS_FORMAT_VERSION);
+        assert_eq!(upgraded, file);
+        assert!(parse_corpus_file("call 0x0 0x0 00 0").is_err());
+    }
+
+    #[test]
+    fn test_input_meta_round_trip() {
+        let legacy = "abi 0x8b21e662154b4bbc1ec0754d0238875fe3d22fa6 0x1000000000000000000000000000000000000000 a9059cbb 0 0 2 1\n";
+        let mut input = parse_legacy(legacy).unwrap()[0].to_input(StagedVMState::new_uninitialized());
+        input.access_pattern.borrow_mut().timestamp = true;
+        input.access_pattern.borrow_mut().balance.push(input.caller);
+        input.randomness = vec![1, 2, 3];
+        input.branch_distance = 7;
+        input.is_cuda = true;
+        input.scenario = Some(2);
+        input.adversary = Some(0);
+        let meta = InputMeta::of(&input);
+
+        // the libafl corpus
+        let restored: EVMInput = serde_json::from_str(&serde_json::to_string(&input).unwrap()).unwrap();
+        assert_eq!(InputMeta::of(&restored), meta);
+
+        // the replayable corpus
+        let mut txns = parse_legacy(legacy).unwrap();
+        txns[0].meta = Some(meta.clone());
+        let (_, file) = parse_corpus_file(&CorpusFile::V1 { txns }.to_json()).unwrap();
+        let restored = file.txns()[0].to_input(StagedVMState::new_uninitialized());
+        assert_eq!(InputMeta::of(&restored), meta);
+        assert!(restored.access_pattern.borrow().timestamp);
+
+        // the legacy line of a transaction parses back to it
+        let mut txn = parse_legacy(legacy).unwrap().remove(0);
+        txn.feed_prices = vec![EVMU256::from(3), EVMU256::from(4)];
+        txn.gas_limit = Some(80000);
+        assert_eq!(parse_legacy(&txn.to_legacy_line()).unwrap(), vec![txn]);
+
+        // files written before the metadata load with the defaults
+        let (_, file) = parse_corpus_file(&CorpusFile::V1 { txns: parse_legacy(legacy).unwrap() }.to_json()).unwrap();
+        assert_eq!(file.txns()[0].meta, None);
+        let partial: InputMeta = serde_json::from_str(r#"{"branch_distance": 3, "access_pattern": {"caller": true}}"#).unwrap();
+        assert!(partial.access_pattern.caller && !partial.is_cuda);
+    }
+}
@@ -1,32 +1,70 @@
 /// Utilities to initialize the corpus
 /// Add all potential calls with default args to the corpus
-use crate::evm::abi::get_abi_type_boxed;
+use crate::differential::DIFFERENTIAL;
+use crate::evm::abi::{function_sig_name, get_abi_type_boxed};
+use crate::evm::address_pool::register_contract_address;
+use crate::evm::call_value::register_payability;
 use crate::evm::bytecode_analyzer;
-use crate::evm::contract_utils::{ABIConfig, ContractInfo};
+use crate::evm::contract_utils::{ABIConfig, ContractInfo, FIX_DEPLOYER};
+use crate::evm::flashloan_provider::callback_caller;
+use crate::evm::initializer::{is_initializer_function, record_uninitialized, register_initializer};
+use crate::evm::diamond::{decode_facets, facets_from_storage, is_diamond, register_facets, FACETS_SELECTOR};
+use crate::evm::entry_points::{detect_prefixes, prefix_to_function, resolve_prefixes, EntryPoint};
 use crate::evm::input::{EVMInput, EVMInputTy};
 use crate::evm::mutator::AccessPattern;
+use crate::evm::mutation_utils::SelectorConstantsMetadata;
+use crate::evm::seed_synthesis::synthesize_seeds;
+use crate::evm::dictionary::add_dictionary_to_state;
+use crate::evm::packs::CorpusPack;
 
 use crate::evm::onchain::onchain::BLACKLIST_ADDR;
-use crate::evm::types::{EVMAddress, EVMFuzzState, EVMInfantStateState, EVMStagedVMState, EVMU256, fixed_address};
+use crate::evm::types::{register_caller_alias, EVMAddress, EVMFuzzState, EVMInfantStateState, EVMStagedVMState, EVMU256, fixed_address};
+use crate::evm::callers::{apply_named_callers, named_callers, register_caller_role, sender_allowed, CallerRole};
 use crate::evm::vm::{EVMExecutor, EVMState};
 use crate::generic_vm::vm_executor::GenericVM;
 
+use crate::generic_vm::vm_state::VMStateT;
+use crate::input::VMInputT;
 use crate::state::HasCaller;
+use crate::tracer::build_basic_txn;
 use crate::state_input::StagedVMState;
 use bytes::Bytes;
 use libafl::corpus::{Corpus, Testcase};
 
 use libafl::schedulers::Scheduler;
-use libafl::state::HasCorpus;
+use libafl::state::{HasCorpus, HasMetadata};
 use revm_primitives::Bytecode;
 
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
+use std::path::Path;
 
 use crate::evm::onchain::flashloan::register_borrow_txn;
 use crate::evm::presets::presets::Preset;
+use crate::evm::scenario::{scenario_count, scenario_mut};
+use crate::evm::storage_alias::{detect_aliases, warn_double_snapshots};
+use crate::evm::pausable::{find_paused_flag, is_paused, PausedMode, UNPAUSED_SCENARIO};
+use crate::evm::solution_dedup::set_initial_state;
+use crate::evm::state_diff::save_snapshot;
+use crate::evm::storage_layout::{register_storage_layout, save_storage_layouts, StorageLayout};
+use crate::evm::config::DUMP_CORPUS;
+use crate::evm::directed::{compute_distances, merge_distances, register_distances, DirectedTarget};
+use crate::evm::goals::{goal_distances, Goal};
+use crate::evm::cfg_refinement::{register_cfg, REFINE_CFG};
+use crate::evm::withdrawal::{register_candidates, scan_withdrawals, WITHDRAWAL_CHECK};
+use crate::evm::bug_likelihood::{prior_energy, record_scores, score_functions, BUG_PRIORS};
+use crate::scheduler::set_prior;
+use crate::workdir::artifacts_dir;
+use crate::evm::grammar::{export_grammar, GRAMMAR_FILE};
+use crate::evm::interfaces::{detect_by_selectors, probe_interfaces, TokenInterface};
+use crate::evm::token_flow::{
+    balance_slot, is_amount_function, register_amount_function, register_balance_slot, BALANCE_OF_SELECTOR,
+};
+use crate::evm::token_minting::{approve, find_allowance_slot, find_balance_slot, mint, TokenMint};
 use std::rc::Rc;
+use crate::error::{MauError, MauResult};
+use tracing::{debug, info, warn};
 use std::time::Duration;
 use itertools::Itertools;
 use crate::evm::srcmap::parser::{decode_instructions, SourceMapLocation};
@@ -38,6 +76,74 @@ pub struct EVMCorpusInitializer<'a> {
     state: &'a mut EVMFuzzState,
     #[cfg(feature = "use_presets")]
     presets: Vec<&'a dyn Preset<EVMInput, EVMFuzzState, EVMState>>,
+    /// Whether to run the attacker state preparation pre-pass
+    prepare_attacker_states: bool,
+    /// Calls changing the state, candidates for the preparation pre-pass
+    preparation_inputs: Vec<EVMInput>,
+    /// Calls to the initializers of upgradeable contracts, run in the init phase
+    init_inputs: Vec<EVMInput>,
+    /// Index of the initial VM state in the infant state corpus
+    initial_state_idx: usize,
+    /// Manually declared entry points, keyed by contract name or address
+    entry_points: HashMap<String, Vec<EntryPoint>>,
+    /// Tokens whose balances / allowances are force-set for the callers
+    token_mints: Vec<TokenMint>,
+    storage_layouts: HashMap<String, StorageLayout>,
+    direct_to: Option<DirectedTarget>,
+    /// Attacker goals, whose slot writes the fuzzer is directed toward
+    goals: Vec<Goal>,
+    grammar: HashMap<String, Vec<ABIConfig>>,
+    /// Seeds and dictionaries of protocol families
+    packs: Vec<CorpusPack>,
+    /// Token interfaces implemented by the deployed contracts
+    interfaces: HashMap<EVMAddress, Vec<TokenInterface>>,
+    /// What to do with the targets paused in the initial state
+    paused_mode: PausedMode,
+}
+
+/// Externally owned callers, named `attacker1`, `attacker2`...
+pub const DEFAULT_CALLERS: [&str; 2] = [
+    "8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd",
+    "35c9dfd76bf02107ff4f7128Bd69716612d31dDb",
+    // "5E6B78f0748ACd4Fb4868dF6eCcfE41398aE09cb",
+];
+
+/// Contract callers, named `attacker_contract1`, `attacker_contract2`...
+pub const CONTRACT_CALLERS: [&str; 2] = [
+    "e1A425f1AC34A8a441566f93c82dD730639c8510",
+    "68Dd4F5AC792eAaa5e36f4f4e0474E0625dc9024",
+    // "aF97EE5eef1B02E12B650B8127D8E8a6cD722bD2",
+];
+
+/// Args of inputs calling a non-standard entry point: raw calldata words after the prefix
+const ENTRY_POINT_ARGS: &str = "(uint256,uint256,uint256,uint256)";
+
+/// Keywords of function names that plausibly grant approvals / roles to the caller
+/// (e.g., public mint, open enrollment), the preparation calls of the targets the static pass
+/// cannot decompile
+const GRANT_FUNCTION_KEYWORDS: [&str; 16] = [
+    "approve",
+    "allowance",
+    "permit",
+    "mint",
+    "enroll",
+    "register",
+    "join",
+    "grant",
+    "claim",
+    "deposit",
+    "stake",
+    "subscribe",
+    "whitelist",
+    "signup",
+    "delegate",
+    "role",
+];
+
+/// Whether the function plausibly grants approvals / roles to the caller
+pub fn is_grant_function(name: &str) -> bool {
+    let name = name.to_lowercase();
+    GRANT_FUNCTION_KEYWORDS.iter().any(|k| name.contains(k))
 }
 
 #[macro_export]
@@ -98,23 +204,95 @@ impl<'a> EVMCorpusInitializer<'a> {
             state,
             #[cfg(feature = "use_presets")]
             presets: vec![],
+            prepare_attacker_states: false,
+            preparation_inputs: vec![],
+            init_inputs: vec![],
+            initial_state_idx: 0,
+            entry_points: HashMap::new(),
+            token_mints: vec![],
+            storage_layouts: HashMap::new(),
+            direct_to: None,
+            goals: vec![],
+            grammar: HashMap::new(),
+            packs: vec![],
+            interfaces: HashMap::new(),
+            paused_mode: PausedMode::Halt,
         }
     }
 
+    /// Token interfaces detected on the deployed contracts, available after [`Self::initialize`]
+    pub fn interfaces(&self) -> HashMap<EVMAddress, Vec<TokenInterface>> {
+        self.interfaces.clone()
+    }
+
+    /// Import function structures (e.g., exported by another campaign) of the contracts
+    pub fn set_grammar(&mut self, grammar: HashMap<String, Vec<ABIConfig>>) {
+        self.grammar = grammar;
+    }
+
+    /// Stop, explore the unpaused variant or only warn when a target is paused after its deployment
+    pub fn set_paused_mode(&mut self, paused_mode: PausedMode) {
+        self.paused_mode = paused_mode;
+    }
+
+    /// Merge the seeds of the packs into the corpus of the functions with the same signature
+    pub fn set_packs(&mut self, packs: Vec<CorpusPack>) {
+        self.packs = packs;
+    }
+
+    /// Direct the fuzzer toward a function or PC of the deployed contracts
+    pub fn set_direct_to(&mut self, direct_to: Option<DirectedTarget>) {
+        self.direct_to = direct_to;
+    }
+
+    /// Direct the fuzzer toward the writes of the slots of the goals
+    pub fn set_goals(&mut self, goals: Vec<Goal>) {
+        self.goals = goals;
+    }
+
+    /// Force-set balances and allowances of the given tokens for the callers before fuzzing
+    pub fn set_token_mints(&mut self, token_mints: Vec<TokenMint>) {
+        self.token_mints = token_mints;
+    }
+
+    /// Name the slots of the contracts after the variables of their solc storage layouts
+    pub fn set_storage_layouts(&mut self, storage_layouts: HashMap<String, StorageLayout>) {
+        self.storage_layouts = storage_layouts;
+    }
+
+    /// Declare entry points (calldata prefixes or PCs) of contracts with non-standard dispatchers
+    pub fn set_entry_points(&mut self, entry_points: HashMap<String, Vec<EntryPoint>>) {
+        self.entry_points = entry_points;
+    }
+
+    /// Enable the pre-pass granting the attackers approvals / roles before fuzzing
+    pub fn set_prepare_attacker_states(&mut self, enabled: bool) {
+        self.prepare_attacker_states = enabled;
+    }
+
     #[cfg(feature = "use_presets")]
     pub fn register_preset(&mut self, preset: &'a dyn Preset<EVMInput, EVMFuzzState, EVMState>) {
         self.presets.push(preset);
     }
 
-    pub fn initialize(&mut self, contracts: Vec<ContractInfo>) {
+    pub fn initialize(&mut self, contracts: Vec<ContractInfo>) -> MauResult<()> {
         self.setup_default_callers();
         self.setup_contract_callers();
-        self.initialize_corpus(contracts);
+        self.setup_named_callers()?;
+        self.initialize_corpus(contracts)?;
+        self.initialize_contracts();
+        if self.prepare_attacker_states {
+            self.prepare_states();
+        }
+        Ok(())
     }
 
-    pub fn initialize_corpus(&mut self, contracts: Vec<ContractInfo>) {
+    pub fn initialize_corpus(&mut self, contracts: Vec<ContractInfo>) -> MauResult<()> {
+        let mut deployed_contracts = vec![];
+        let mut known_abis = HashMap::new();
+        let mut paused_contracts = vec![];
         for contract in contracts {
-            println!("Deploying contract: {}", contract.name);
+            info!("Deploying contract: {}", contract.name);
             bytecode_analyzer::collect_constants(&Bytecode::new_raw(Bytes::from(contract.code.clone())));
             let deployed_address = if !contract.is_code_deployed {
                 match self.executor.deploy(
@@ -125,7 +303,7 @@ impl<'a> EVMCorpusInitializer<'a> {
                 ) {
                     Some(addr) => addr,
                     None => {
-                        println!("Failed to deploy contract: {}", contract.name);
+                        warn!("Failed to deploy contract: {}", contract.name);
                         // we could also panic here
                         continue;
                     }
@@ -151,6 +329,8 @@ impl<'a> EVMCorpusInitializer<'a> {
             }
 
             self.state.add_address(&deployed_address);
+            register_contract_address(deployed_address);
+            deployed_contracts.push((contract.name.clone(), deployed_address));
 
             if unsafe {
                 BLACKLIST_ADDR.is_some()
@@ -159,9 +339,37 @@ impl<'a> EVMCorpusInitializer<'a> {
                 continue;
             }
 
-            for abi in contract.abi {
+            if let Some(code) = self.executor.host.code.get(&deployed_address) {
+                let code = Bytecode::new_raw(code.deref().clone().unlock().original_bytes());
+                bytecode_analyzer::add_selector_constants_to_state(&code, self.state);
+            }
+            // before its functions are seeded, so that they are also seeded unpaused
+            if self.check_paused(&contract.name, deployed_address) {
+                paused_contracts.push(contract.name.clone());
+            }
+
+            let mut abis = contract.abi.clone();
+            if let Some(imported) = self
+                .grammar
+                .get(&contract.name)
+                .or_else(|| self.grammar.get(&format!("{:?}", deployed_address)))
+            {
+                let known = abis.iter().map(|abi| abi.function).collect::<HashSet<_>>();
+                let imported = imported
+                    .iter()
+                    .filter(|abi| !known.contains(&abi.function))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                info!("Imported {} functions of {} from grammar", imported.len(), contract.name);
+                abis.extend(imported);
+            }
+
+            known_abis.insert(deployed_address, abis.clone());
+            let has_abi = abis.iter().any(|abi| !abi.is_constructor);
+            for abi in abis {
                 self.add_abi(&abi, self.scheduler, deployed_address);
             }
+            self.add_entry_points(&contract.name, deployed_address, has_abi);
             // add transfer txn
             {
                 let input = EVMInput {
@@ -184,10 +392,39 @@ impl<'a> EVMCorpusInitializer<'a> {
                     repeat: 1,
                     cu_data: vec![],
                     is_cuda: false,
+                    scenario: None,
+                    adversary: None,
+                    feed_prices: vec![],
+                    force_feed: None,
+                    block_advance: None,
+                    gas_limit: None,
                 };
                 add_input_to_corpus!(self.state, self.scheduler, input);
             }
         }
+        if !paused_contracts.is_empty() && self.paused_mode == PausedMode::Halt {
+            return Err(MauError::Paused(paused_contracts));
+        }
+        for (name, address) in &deployed_contracts {
+            self.add_diamond_facets(name, *address, &known_abis);
+        }
+        self.mint_tokens(&deployed_contracts);
+        self.register_token_balances(&known_abis);
+        self.direct_to_target(&deployed_contracts);
+        self.direct_to_goals(&deployed_contracts);
+        self.register_cfgs(&deployed_contracts);
+        self.scan_withdrawals(&deployed_contracts);
+        self.seed_bug_priors(&deployed_contracts);
+        self.register_storage_layouts(&deployed_contracts);
+        self.detect_interfaces(&deployed_contracts, &known_abis);
+        self.detect_storage_aliases(&deployed_contracts);
+        export_grammar(
+            &Path::new(&artifacts_dir()).join(GRAMMAR_FILE),
+            &deployed_contracts
+                .iter()
+                .map(|(name, address)| (name.clone(), *address, known_abis.get(address).cloned().unwrap_or_default()))
+                .collect(),
+        );
         let mut tc = Testcase::new(StagedVMState::new_with_state(
             self.executor.host.evmstate.clone(),
         ));
@@ -201,34 +438,544 @@ impl<'a> EVMCorpusInitializer<'a> {
         self.infant_scheduler
             .on_add(&mut self.state.infant_states_state, idx)
             .expect("failed to call infant scheduler on_add");
+        self.initial_state_idx = idx;
+        if unsafe { DUMP_CORPUS } {
+            save_snapshot(idx, None, &self.executor.host.evmstate.state);
+        }
+        set_initial_state(self.state, self.executor.host.evmstate.clone());
+        Ok(())
     }
 
-    pub fn setup_default_callers(&mut self) {
-        let default_callers = HashSet::from([
-            fixed_address("8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd"),
-            fixed_address("35c9dfd76bf02107ff4f7128Bd69716612d31dDb"),
-            // fixed_address("5E6B78f0748ACd4Fb4868dF6eCcfE41398aE09cb"),
-        ]);
+    /// Init phase of upgradeable contracts: each initializer is first called by an attacker on
+    /// the initial state, a success means the contract is left uninitialized. It is then called
+    /// by the deployer, and the initialized state is added to the infant states so that fuzzing
+    /// also continues from the initialized contracts.
+    pub fn initialize_contracts(&mut self) {
+        if self.init_inputs.is_empty() {
+            return;
+        }
+        let deployer = fixed_address(FIX_DEPLOYER);
+        let attacker = self.state.callers_pool.iter().find(|c| **c != deployer).cloned();
+        let initial_state = StagedVMState::new_with_state(self.executor.host.evmstate.clone());
+        let mut vm_state = initial_state.clone();
+        let mut state_idx = self.initial_state_idx;
+        let mut initialized = 0;
+        for init_input in self.init_inputs.clone() {
+            let contract = init_input.contract;
+            if let Some(attacker) = attacker {
+                let mut input = init_input.clone();
+                input.caller = attacker;
+                input.set_staged_state(initial_state.clone(), self.initial_state_idx);
+                let res = self.executor.execute(&input, self.state);
+                if !res.reverted && res.new_state.state.get(&contract) != initial_state.state.get(&contract) {
+                    warn!(
+                        "{:?} is not initialized, anyone can call {}",
+                        contract,
+                        input.data.as_ref().map(|abi| abi.get_func_name()).unwrap_or_default()
+                    );
+                    record_uninitialized(contract);
+                }
+            }
 
-        for caller in default_callers {
-            self.state.add_caller(&caller);
+            let mut input = init_input.clone();
+            input.caller = deployer;
+            input.set_staged_state(vm_state.clone(), state_idx);
+            let mut res = self.executor.execute(&input, self.state);
+            if res.reverted || res.new_state.state.get_hash() == vm_state.state.get_hash() {
+                continue;
+            }
+            let txn = build_basic_txn(&input, &res);
+            res.new_state.trace = vm_state.trace.clone();
+            res.new_state.trace.from_idx = Some(state_idx);
+            res.new_state.trace.add_txn(txn);
+
+            let mut tc = Testcase::new(res.new_state.clone());
+            tc.set_exec_time(Duration::from_secs(0));
+            state_idx = self
+                .state
+                .infant_states_state
+                .corpus_mut()
+                .add(tc)
+                .expect("failed to add");
+            self.infant_scheduler
+                .on_add(&mut self.state.infant_states_state, state_idx)
+                .expect("failed to call infant scheduler on_add");
+            vm_state = res.new_state;
+            initialized += 1;
+        }
+        info!(
+            "Init phase: {} of {} initializers succeeded as the deployer",
+            initialized,
+            self.init_inputs.len()
+        );
+    }
+
+    /// Calls anyone can make to change the state, found by the static pass of
+    /// [`crate::evm::bug_likelihood`]: the functions reaching an `SSTORE` without a branch on the
+    /// caller (see [`crate::evm::bug_likelihood::RiskFeatures::prepares_state`]). The functions of a target that cannot be
+    /// decompiled are picked by their names instead ([`is_grant_function`]).
+    fn preparation_calls(&self) -> Vec<EVMInput> {
+        let mut features = HashMap::new();
+        let mut calls = vec![];
+        for input in &self.preparation_inputs {
+            let selector = input.data.as_ref().unwrap().function;
+            let contract_features = features.entry(input.contract).or_insert_with(|| {
+                let code = self.executor.host.code.get(&input.contract)?.deref().clone().unlock().original_bytes();
+                let scored = score_functions(&code);
+                if scored.is_none() {
+                    warn!("[prepare] {:?}: decompilation failed, preparing with the functions named like grants", input.contract);
+                }
+                scored
+            });
+            let prepares = match contract_features {
+                Some(contract_features) => contract_features.get(&selector).map_or(false, |f| f.prepares_state()),
+                None => is_grant_function(&function_sig_name(&selector)),
+            };
+            if prepares {
+                calls.push(input.clone());
+            }
+        }
+        calls
+    }
+
+    /// Reachability pre-pass: every attacker (caller) makes the calls anyone can make to change
+    /// the state ([`Self::preparation_calls`]) on top of the initial state, one after another.
+    /// Each call that succeeds and changes the state yields a prepared infant state, so that the
+    /// main fuzzing phase can start from states where the attacker already holds what anyone
+    /// could have obtained (approvals, roles, minted tokens...).
+    pub fn prepare_states(&mut self) {
+        let initial_state = self.executor.host.evmstate.clone();
+        let calls = self.preparation_calls();
+        let mut prepared = 0;
+        for caller in self.state.callers_pool.clone() {
+            let mut vm_state = StagedVMState::new_with_state(initial_state.clone());
+            let mut state_idx = self.initial_state_idx;
+            for call in &calls {
+                let mut input = call.clone();
+                input.caller = caller;
+                input.set_staged_state(vm_state.clone(), state_idx);
+
+                let mut res = self.executor.execute(&input, self.state);
+                if res.reverted || res.new_state.state.get_hash() == vm_state.state.get_hash() {
+                    continue;
+                }
+                let txn = build_basic_txn(&input, &res);
+                res.new_state.trace = vm_state.trace.clone();
+                res.new_state.trace.from_idx = Some(state_idx);
+                res.new_state.trace.add_txn(txn);
+
+                let mut tc = Testcase::new(res.new_state.clone());
+                tc.set_exec_time(Duration::from_secs(0));
+                state_idx = self
+                    .state
+                    .infant_states_state
+                    .corpus_mut()
+                    .add(tc)
+                    .expect("failed to add");
+                self.infant_scheduler
+                    .on_add(&mut self.state.infant_states_state, state_idx)
+                    .expect("failed to call infant scheduler on_add");
+                vm_state = res.new_state;
+                prepared += 1;
+            }
+        }
+        info!("Prepared {} attacker states from {} preparation calls", prepared, calls.len());
+    }
+
+    /// Whether the contract is paused, in which case the `unpaused` scenario clears its flag
+    /// with `--paused explore`
+    fn check_paused(&mut self, name: &String, address: EVMAddress) -> bool {
+        let vm_state = self.executor.host.evmstate.clone();
+        let paused = is_paused(self.executor, self.state, &vm_state, address) == Some(true);
+        if paused {
+            warn!("{} is paused in the initial state", name);
+            if self.paused_mode == PausedMode::Explore {
+                match find_paused_flag(self.executor, self.state, &vm_state, address) {
+                    Some((slot, value)) => {
+                        info!("{} is unpaused in the {} scenario (slot {:#x})", name, UNPAUSED_SCENARIO, slot);
+                        scenario_mut(UNPAUSED_SCENARIO)
+                            .storage
+                            .entry(address)
+                            .or_default()
+                            .insert(slot, value);
+                    }
+                    None => warn!("Failed to locate the paused flag of {}, only fuzzing it paused", name),
+                }
+            }
+        }
+        self.executor.host.evmstate = vm_state;
+        paused
+    }
+
+    /// Map the code addresses to the storages they run on, and warn about the targets running on
+    /// the storage of another
+    fn detect_storage_aliases(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        let codes = deployed_contracts
+            .iter()
+            .filter_map(|(_, address)| {
+                let code = self.executor.host.code.get(address)?;
+                Some((*address, code.deref().clone().unlock().original_bytes().to_vec()))
+            })
+            .collect::<HashMap<_, _>>();
+        detect_aliases(&self.executor.host.evmstate, &codes, deployed_contracts);
+        warn_double_snapshots(deployed_contracts);
+    }
+
+    /// Detect the token interfaces of the contracts with ERC-165 and the functions they have
+    fn detect_interfaces(
+        &mut self,
+        deployed_contracts: &Vec<(String, EVMAddress)>,
+        known_abis: &HashMap<EVMAddress, Vec<ABIConfig>>,
+    ) {
+        let vm_state = self.executor.host.evmstate.clone();
+        for (name, address) in deployed_contracts {
+            let selectors = known_abis
+                .get(address)
+                .map(|abis| abis.iter().map(|abi| abi.function).collect::<HashSet<_>>())
+                .unwrap_or_default();
+            let mut interfaces = probe_interfaces(self.executor, self.state, &vm_state, *address);
+            for interface in detect_by_selectors(&selectors) {
+                if !interfaces.contains(&interface) {
+                    interfaces.push(interface);
+                }
+            }
+            if !interfaces.is_empty() {
+                info!(
+                    "{} implements {}, enabling the conformance oracles",
+                    name,
+                    interfaces.iter().map(|i| i.name()).join(", ")
+                );
+                self.interfaces.insert(*address, interfaces);
+            }
+        }
+        // probing calls overwrite the host state
+        self.executor.host.evmstate = vm_state;
+    }
+
+    /// Find the balance mapping of the tokens among the targets, for amounts to be mutated
+    /// relative to the live balances (see [`crate::evm::token_flow`])
+    fn register_token_balances(&mut self, known_abis: &HashMap<EVMAddress, Vec<ABIConfig>>) {
+        let vm_state = self.executor.host.evmstate.clone();
+        for (address, abis) in known_abis {
+            if balance_slot(address).is_some() || !abis.iter().any(|abi| abi.function == BALANCE_OF_SELECTOR) {
+                continue;
+            }
+            if let Some(slot) = find_balance_slot(self.executor, self.state, &vm_state, *address) {
+                debug!("Balance mapping of {:?} at slot {:?}", address, slot);
+                register_balance_slot(*address, slot);
+            }
+        }
+        // probing calls overwrite the host state
+        self.executor.host.evmstate = vm_state;
+    }
+
+    /// Compute the distances to the directed target in each matching contract
+    fn direct_to_target(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        let direct_to = match self.direct_to.clone() {
+            Some(direct_to) => direct_to,
+            None => return,
+        };
+        let mut reached = false;
+        for (name, address) in deployed_contracts {
+            if !direct_to.matches(name, address) {
+                continue;
+            }
+            let code = match self.executor.host.code.get(address) {
+                Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+                None => continue,
+            };
+            if let Some(distances) = compute_distances(&code, &direct_to.target) {
+                info!("[directed] {}: {} blocks reach {:?}", name, distances.len(), direct_to.target);
+                register_distances(*address, distances);
+                reached = true;
+            }
+        }
+        if !reached {
+            warn!("[directed] {:?} not found in any contract, fuzzing undirected", direct_to);
+        }
+    }
+
+    fn direct_to_goals(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        for (name, address) in deployed_contracts {
+            let slots = self
+                .goals
+                .iter()
+                .filter(|goal| goal.matches(name, address))
+                .filter_map(|goal| match goal {
+                    Goal::Slot { slot, .. } => Some(*slot),
+                    Goal::Balance { .. } => None,
+                })
+                .collect::<Vec<_>>();
+            if slots.is_empty() {
+                continue;
+            }
+            let code = match self.executor.host.code.get(address) {
+                Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+                None => continue,
+            };
+            match goal_distances(&code, &slots) {
+                Some(distances) => {
+                    info!("[goal] {}: {} blocks reach the writes of the goal slots", name, distances.len());
+                    merge_distances(*address, distances);
+                }
+                None => warn!("[goal] {}: no constant write of the goal slots found, fuzzing undirected", name),
+            }
+        }
+    }
+
+    /// Register the CFG of each contract, refined during fuzzing with the jumps observed
+    fn register_cfgs(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        if unsafe { !REFINE_CFG } {
+            return;
+        }
+        for (name, address) in deployed_contracts {
+            let code = match self.executor.host.code.get(address) {
+                Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+                None => continue,
+            };
+            let target = self
+                .direct_to
+                .as_ref()
+                .filter(|direct_to| direct_to.matches(name, address))
+                .map(|direct_to| &direct_to.target);
+            register_cfg(*address, code, target);
+        }
+    }
+
+    /// Static pass of the withdrawal sanitizer: the functions of each contract reaching a `CALL`
+    /// without a caller check, confirmed during fuzzing by the oracle
+    fn scan_withdrawals(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        if unsafe { !WITHDRAWAL_CHECK } {
+            return;
+        }
+        for (name, address) in deployed_contracts {
+            let code = match self.executor.host.code.get(address) {
+                Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+                None => continue,
+            };
+            match scan_withdrawals(&code) {
+                Some(candidates) => {
+                    info!("[withdrawal] {}: {} functions reach a call without caller check", name, candidates.len());
+                    register_candidates(*address, candidates);
+                }
+                None => warn!("[withdrawal] {}: decompilation failed, all functions are candidates", name),
+            }
+        }
+    }
+
+    /// Score the functions of each contract with the static pass of
+    /// [`crate::evm::bug_likelihood`] and give the seeds calling them extra rounds accordingly
+    fn seed_bug_priors(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        if unsafe { !BUG_PRIORS } {
+            return;
+        }
+        let mut scores = HashMap::new();
+        for (name, address) in deployed_contracts {
+            let code = match self.executor.host.code.get(address) {
+                Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+                None => continue,
+            };
+            match score_functions(&code) {
+                Some(features) => {
+                    let mut ranked = features.iter().map(|(selector, f)| (f.score(), *selector)).collect::<Vec<_>>();
+                    ranked.sort_by(|a, b| b.cmp(a));
+                    for (score, selector) in ranked.iter().take(5).filter(|(score, _)| *score > 0) {
+                        info!("[bug-priors] {}: 0x{} scores {}", name, hex::encode(selector), score);
+                    }
+                    record_scores(&features);
+                    scores.insert(*address, features);
+                }
+                None => warn!("[bug-priors] {}: decompilation failed, no priors", name),
+            }
+        }
+        for idx in 0..self.state.corpus().count() {
+            let (contract, selector) = {
+                let mut testcase = self.state.corpus().get(idx).unwrap().borrow_mut();
+                let input = testcase.load_input().expect("failed to load input");
+                let selector = match &input.data {
+                    Some(data) => data.function,
+                    None => match input.direct_data.get(..4) {
+                        Some(prefix) => prefix.try_into().unwrap(),
+                        None => continue,
+                    },
+                };
+                (input.contract, selector)
+            };
+            let score = scores
+                .get(&contract)
+                .and_then(|features| features.get(&selector))
+                .map_or(0, |features| features.score());
+            if score > 0 {
+                set_prior(self.state, idx, prior_energy(score));
+            }
+        }
+    }
+
+    /// Register the storage layouts of the deployed contracts, by contract name
+    fn register_storage_layouts(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        if self.storage_layouts.is_empty() {
+            return;
+        }
+        for (name, address) in deployed_contracts {
+            if let Some(layout) = self.storage_layouts.get(name) {
+                info!("Registered storage layout of {} ({} variables)", name, layout.variables.len());
+                register_storage_layout(*address, layout.clone());
+            }
+        }
+        if unsafe { DUMP_CORPUS } {
+            save_storage_layouts();
+        }
+    }
+
+    /// Force-set the token balances of the callers and let every deployed contract spend them
+    fn mint_tokens(&mut self, deployed_contracts: &Vec<(String, EVMAddress)>) {
+        let mut vm_state = self.executor.host.evmstate.clone();
+        let callers = self.state.callers_pool.clone();
+        let spenders = deployed_contracts.iter().map(|(_, addr)| *addr).collect();
+        for token_mint in self.token_mints.clone() {
+            let token = match token_mint.resolve(deployed_contracts) {
+                Some(token) => token,
+                None => {
+                    warn!("Cannot mint {}: no such contract", token_mint.token);
+                    continue;
+                }
+            };
+            match find_balance_slot(self.executor, self.state, &vm_state, token) {
+                Some(slot) => {
+                    info!("Minting {} of {} for {} callers (slot {:?})", token_mint.amount, token_mint.token, callers.len(), slot);
+                    mint(&mut vm_state, token, &slot, &callers, token_mint.amount);
+                    register_balance_slot(token, slot);
+                }
+                None => warn!("Cannot mint {}: balance slot not found", token_mint.token),
+            }
+            match find_allowance_slot(self.executor, self.state, &vm_state, token) {
+                Some(slot) => approve(&mut vm_state, token, &slot, &callers, &spenders),
+                None => warn!("Cannot approve {}: allowance slot not found", token_mint.token),
+            }
+        }
+        // probing calls overwrite the host state
+        self.executor.host.evmstate = vm_state;
+    }
+
+    pub fn setup_default_callers(&mut self) {
+        for (idx, caller) in DEFAULT_CALLERS.map(fixed_address).into_iter().enumerate() {
+            register_caller_alias(caller, &format!("attacker{}", idx + 1));
+            register_caller_role(caller, CallerRole::Attacker);
+            if sender_allowed(&caller) {
+                self.state.add_caller(&caller);
+            }
         }
     }
 
     pub fn setup_contract_callers(&mut self) {
-        let contract_callers = HashSet::from([
-            fixed_address("e1A425f1AC34A8a441566f93c82dD730639c8510"),
-            fixed_address("68Dd4F5AC792eAaa5e36f4f4e0474E0625dc9024"),
-            // fixed_address("aF97EE5eef1B02E12B650B8127D8E8a6cD722bD2"),
-        ]);
-        for caller in contract_callers {
-            self.state.add_caller(&caller);
+        for (idx, caller) in CONTRACT_CALLERS.map(fixed_address).into_iter().enumerate() {
+            register_caller_alias(caller, &format!("attacker_contract{}", idx + 1));
+            register_caller_role(caller, CallerRole::Attacker);
+            if sender_allowed(&caller) {
+                self.state.add_caller(&caller);
+            }
             self.executor
                 .host
                 .set_code(caller, Bytecode::new_raw(Bytes::from(vec![0xfd, 0x00])), self.state);
         }
     }
 
+    /// Name the callers of the config over the built-in ones and add the allowed senders to the
+    /// caller pool, see [`crate::evm::callers`]
+    pub fn setup_named_callers(&mut self) -> MauResult<()> {
+        apply_named_callers();
+        for caller in named_callers() {
+            let address = caller.address();
+            self.state.add_address(&address);
+            if sender_allowed(&address) {
+                self.state.add_caller(&address);
+            }
+        }
+        if self.state.callers_pool.is_empty() {
+            return Err(MauError::NoSenders);
+        }
+        Ok(())
+    }
+
+    /// Add inputs for the declared entry points of the contract, or, if the contract has no ABI,
+    /// for the entry points detected by heuristics (e.g., first-byte routing)
+    fn add_entry_points(&mut self, name: &String, deployed_address: EVMAddress, has_abi: bool) {
+        let code = match self.executor.host.code.get(&deployed_address) {
+            Some(code) => code.deref().clone().unlock().original_bytes().to_vec(),
+            None => return,
+        };
+        let declared = self
+            .entry_points
+            .get(name)
+            .or_else(|| self.entry_points.get(&format!("{:?}", deployed_address)))
+            .cloned();
+        let prefixes = match declared {
+            Some(entry_points) => resolve_prefixes(&code, &entry_points),
+            None if !has_abi => detect_prefixes(&code),
+            None => return,
+        };
+        for prefix in prefixes {
+            info!("Adding entry point 0x{} of {}", hex::encode(&prefix), name);
+            let abi = ABIConfig {
+                abi: ENTRY_POINT_ARGS.to_string(),
+                function: prefix_to_function(&prefix),
+                function_name: format!("entry_{}", hex::encode(&prefix)),
+                is_static: false,
+                is_payable: true,
+                is_constructor: false,
+            };
+            self.add_abi(&abi, self.scheduler, deployed_address);
+        }
+    }
+
+    /// If the contract is a diamond, add the functions of its facets, called on the diamond
+    fn add_diamond_facets(&mut self, name: &String, diamond: EVMAddress, known_abis: &HashMap<EVMAddress, Vec<ABIConfig>>) {
+        match self.executor.host.code.get(&diamond) {
+            Some(code) if is_diamond(code.deref().clone().unlock().original_bytes().as_ref()) => {}
+            _ => return,
+        };
+        let vm_state = self.executor.host.evmstate.clone();
+        let output = self.executor.fast_static_call(
+            &vec![(diamond, Bytes::from(FACETS_SELECTOR.to_vec()))],
+            &vm_state,
+            self.state,
+        );
+        self.executor.host.evmstate = vm_state;
+        let facets = match decode_facets(&output[0]) {
+            Some(facets) if !facets.is_empty() => facets,
+            _ => facets_from_storage(&self.executor.host.evmstate, diamond),
+        };
+        info!("{} is a diamond with {} facets", name, facets.len());
+        register_facets(diamond, &facets);
+
+        let own_functions = known_abis
+            .get(&diamond)
+            .map(|abis| abis.iter().map(|abi| abi.function).collect::<HashSet<_>>())
+            .unwrap_or_default();
+        for (facet, functions) in facets {
+            let facet_abis = known_abis.get(&facet).cloned().unwrap_or_default();
+            for function in functions {
+                if own_functions.contains(&function) {
+                    continue;
+                }
+                // args are unknown if the facet is not among the targets
+                let abi = facet_abis
+                    .iter()
+                    .find(|abi| abi.function == function)
+                    .cloned()
+                    .unwrap_or(ABIConfig {
+                        abi: ENTRY_POINT_ARGS.to_string(),
+                        function,
+                        function_name: format!("facet_{}", hex::encode(function)),
+                        is_static: false,
+                        is_payable: true,
+                        is_constructor: false,
+                    });
+                self.add_abi(&abi, self.scheduler, diamond);
+            }
+        }
+    }
+
     fn add_abi(
         &mut self,
         abi: &ABIConfig,
@@ -253,14 +1000,22 @@ impl<'a> EVMCorpusInitializer<'a> {
                     .insert(abi.function.clone(), HashSet::from([deployed_address]));
             }
         }
-        #[cfg(not(feature = "fuzz_static"))]
-        if abi.is_static {
-            return;
+        if unsafe { DIFFERENTIAL } {
+            // only the functions without side effects are checked against revm
+            if !abi.is_static {
+                return;
+            }
+        } else {
+            #[cfg(not(feature = "fuzz_static"))]
+            if abi.is_static {
+                return;
+            }
         }
         let mut abi_instance = get_abi_type_boxed(&abi.abi);
         abi_instance.set_func_with_name(abi.function, abi.function_name.clone());
         let input = EVMInput {
-            caller: self.state.get_rand_caller(),
+            // flashloan receivers are called back by the lender
+            caller: callback_caller(abi.function).unwrap_or_else(|| self.state.get_rand_caller()),
             contract: deployed_address,
             data: Some(abi_instance),
             sstate: StagedVMState::new_uninitialized(),
@@ -283,8 +1038,59 @@ impl<'a> EVMCorpusInitializer<'a> {
             repeat: 1,
             cu_data: vec![],
             is_cuda: false,
+            scenario: None,
+            adversary: None,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
         };
         add_input_to_corpus!(self.state, scheduler, input.clone());
+        register_payability(deployed_address, abi.function, abi.is_payable);
+        if is_amount_function(&abi.function_name) {
+            register_amount_function(abi.function);
+        }
+        if self.prepare_attacker_states && !abi.is_static {
+            self.preparation_inputs.push(input.clone());
+        }
+        if is_initializer_function(&abi.function_name) {
+            register_initializer(abi.function);
+            self.init_inputs.push(input.clone());
+        }
+
+        // pass the constant guards of the function right away
+        let constants = self
+            .state
+            .metadata()
+            .get::<SelectorConstantsMetadata>()
+            .and_then(|meta| meta.constants.get(&abi.function).cloned())
+            .unwrap_or_default();
+        for seed in synthesize_seeds(input.data.as_ref().unwrap(), &constants) {
+            let mut seed_input = input.clone();
+            seed_input.data = Some(seed);
+            add_input_to_corpus!(self.state, scheduler, seed_input);
+        }
+
+        for pack in &self.packs {
+            let seeds = pack.seeds_for(&abi.function, &abi.abi);
+            if seeds.is_empty() {
+                continue;
+            }
+            // calls without arguments are already seeded
+            for args in seeds.into_iter().filter(|args| !args.is_empty()) {
+                let mut seed_input = input.clone();
+                seed_input.data.as_mut().unwrap().b.set_bytes(args);
+                add_input_to_corpus!(self.state, scheduler, seed_input);
+            }
+            add_dictionary_to_state(&pack.dictionary, self.state);
+        }
+
+        // seed the same call under every scenario preset
+        for idx in 0..scenario_count() {
+            let mut scenario_input = input.clone();
+            scenario_input.scenario = Some(idx);
+            add_input_to_corpus!(self.state, scheduler, scenario_input);
+        }
 
         #[cfg(feature = "use_presets")]
         {
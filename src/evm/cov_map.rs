@@ -0,0 +1,225 @@
+/// Coverage map of a run (`cli cov map <work_dir>`)
+///
+/// Renders the disassembly of each contract of `stats/coverage.json` as HTML, each instruction
+/// colored by its number of executions, the basic blocks never reached greyed out and the
+/// comparisons guarding the branches taken only one way highlighted. These comparisons are the
+/// `require()` conditions the fuzzer could not flip, i.e., where seeds (`--dictionary-dir`,
+/// constants) help. The guarded branches are also listed in the report.
+use crate::evm::cov_merge::{load_run, ContractCoverage};
+use crate::evm::entry_points::disassemble;
+use revm_interpreter::OPCODE_JUMPMAP;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+
+const JUMPI: u8 = 0x57;
+const JUMPDEST: u8 = 0x5b;
+/// LT, GT, SLT, SGT, EQ
+const COMPARISONS: [u8; 5] = [0x10, 0x11, 0x12, 0x13, 0x14];
+const ISZERO: u8 = 0x15;
+
+fn ends_block(op: u8) -> bool {
+    matches!(op, 0x00 | 0x56 | 0x57 | 0xf3 | 0xfd | 0xfe | 0xff)
+}
+
+fn op_name(op: u8) -> String {
+    match OPCODE_JUMPMAP[op as usize] {
+        Some(name) => name.to_string(),
+        None => format!("UNKNOWN({:#04x})", op),
+    }
+}
+
+/// Basic blocks, as ranges of indices of the disassembly
+pub fn basic_blocks(ops: &Vec<(usize, u8, Vec<u8>)>) -> Vec<(usize, usize)> {
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (i, (_, op, _)) in ops.iter().enumerate() {
+        if *op == JUMPDEST && i > start {
+            blocks.push((start, i));
+            start = i;
+        }
+        if ends_block(*op) {
+            blocks.push((start, i + 1));
+            start = i + 1;
+        }
+    }
+    if start < ops.len() {
+        blocks.push((start, ops.len()));
+    }
+    blocks
+}
+
+/// A branch taken only one way
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardedBranch {
+    pub jumpi: usize,
+    /// Whether the jump was taken, otherwise it only fell through
+    pub jumped: bool,
+    /// PC and name of the comparison computing the condition, if in the same block
+    pub comparison: Option<(usize, String)>,
+}
+
+/// Branches of the code executed but taken only one way, with their guarding comparison
+pub fn guarded_branches(ops: &Vec<(usize, u8, Vec<u8>)>, coverage: &ContractCoverage) -> Vec<GuardedBranch> {
+    let mut guarded = vec![];
+    for (start, end) in basic_blocks(ops) {
+        let (pc, op, _) = &ops[end - 1];
+        if *op != JUMPI || !coverage.pcs.contains(pc) {
+            continue;
+        }
+        let taken = coverage
+            .branches
+            .range((*pc, 0)..=(*pc, usize::MAX))
+            .map(|(_, dest)| *dest)
+            .collect::<HashSet<_>>();
+        if taken.len() != 1 {
+            continue;
+        }
+        let block = &ops[start..end - 1];
+        let comparison = block
+            .iter()
+            .rev()
+            .find(|(_, op, _)| COMPARISONS.contains(op))
+            .or_else(|| block.iter().rev().find(|(_, op, _)| *op == ISZERO))
+            .map(|(pc, op, _)| (*pc, op_name(*op)));
+        guarded.push(GuardedBranch {
+            jumpi: *pc,
+            jumped: !taken.contains(&(pc + 1)),
+            comparison,
+        });
+    }
+    guarded
+}
+
+/// Background color of an instruction executed `hits` times, `max_hits` being the hottest
+fn heat(hits: u64, max_hits: u64) -> String {
+    if hits == 0 {
+        return "#ffffff".to_string();
+    }
+    let ratio = (hits as f64).ln_1p() / (max_hits as f64).ln_1p();
+    // from light yellow to red
+    let green = 240.0 - 200.0 * ratio;
+    format!("#ff{:02x}{:02x}", green as u8, (160.0 * (1.0 - ratio)) as u8)
+}
+
+fn render_contract(hash: &str, coverage: &ContractCoverage, code: &[u8], html: &mut String) -> usize {
+    let ops = disassemble(code);
+    let guarded = guarded_branches(&ops, coverage);
+    let guards = guarded
+        .iter()
+        .flat_map(|g| g.comparison.iter().map(|(pc, _)| *pc).chain(std::iter::once(g.jumpi)))
+        .collect::<HashSet<_>>();
+    let max_hits = coverage.hits.values().cloned().max().unwrap_or(1);
+    let blocks = basic_blocks(&ops);
+    let reached = blocks
+        .iter()
+        .filter(|(start, end)| ops[*start..*end].iter().any(|(pc, _, _)| coverage.pcs.contains(pc)))
+        .count();
+    html.push_str(&format!(
+        "<h2>{} ({})</h2>\n<p>{} / {} basic blocks reached, {} branches taken one way only</p>\n<table>\n",
+        coverage.address,
+        &hash[..hash.len().min(8)],
+        reached,
+        blocks.len(),
+        guarded.len()
+    ));
+    for (start, end) in blocks {
+        let unreached = !ops[start..end].iter().any(|(pc, _, _)| coverage.pcs.contains(pc));
+        for (pc, op, imm) in &ops[start..end] {
+            let hits = coverage.hits.get(pc).cloned().unwrap_or(0);
+            let class = if guards.contains(pc) {
+                " class=\"guard\""
+            } else if unreached {
+                " class=\"unreached\""
+            } else {
+                ""
+            };
+            html.push_str(&format!(
+                "<tr{} style=\"background:{}\"><td>{:#06x}</td><td>{}</td><td>{}{}</td></tr>\n",
+                class,
+                heat(hits, max_hits),
+                pc,
+                hits,
+                op_name(*op),
+                if imm.is_empty() { String::new() } else { format!(" 0x{}", hex::encode(imm)) }
+            ));
+        }
+        html.push_str("<tr class=\"sep\"><td colspan=\"3\"></td></tr>\n");
+    }
+    html.push_str("</table>\n");
+    guarded.len()
+}
+
+/// Render the coverage of a run (work directory or coverage file) to the HTML file `output`
+/// and report the branches taken one way only
+pub fn render_cov_map(path: &str, output: &str) -> Result<String, String> {
+    let run = load_run(path)?;
+    let mut html = String::from(
+        "<html><head><style>\
+         body { font-family: monospace; } td { padding: 0 8px; } \
+         .unreached { color: #aaa; background: #f4f4f4 !important; } \
+         .guard { outline: 2px solid #06c; font-weight: bold; } .sep td { height: 6px; }\
+         </style></head><body>\n",
+    );
+    let mut report = String::new();
+    for (hash, coverage) in &run.contracts {
+        let code = match hex::decode(&coverage.code) {
+            Ok(code) if !code.is_empty() => code,
+            _ => {
+                report.push_str(&format!("{}: no code in the coverage file, replay the corpus again\n", coverage.address));
+                continue;
+            }
+        };
+        render_contract(hash, coverage, &code, &mut html);
+        let ops = disassemble(&code);
+        let guarded = guarded_branches(&ops, coverage);
+        report.push_str(&format!("{}: {} branches taken one way only\n", coverage.address, guarded.len()));
+        for branch in guarded {
+            report.push_str(&format!(
+                "  JUMPI {:#x} always {}{}\n",
+                branch.jumpi,
+                if branch.jumped { "jumps" } else { "falls through" },
+                match branch.comparison {
+                    Some((pc, name)) => format!(", guarded by {} at {:#x}", name, pc),
+                    None => String::new(),
+                }
+            ));
+        }
+    }
+    html.push_str("</body></html>\n");
+    let mut file = File::create(output).map_err(|e| format!("failed to create {}: {}", output, e))?;
+    file.write_all(html.as_bytes())
+        .map_err(|e| format!("failed to write {}: {}", output, e))?;
+    report.push_str(&format!("Coverage map written to {}\n", output));
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_guarded_branches() {
+        // CALLVALUE, PUSH1 0, EQ, PUSH1 0x0b, JUMPI (6), PUSH1 0, DUP1, REVERT, JUMPDEST (0x0b), STOP
+        let code = hex::decode("34600014600b57600080fd5b00").unwrap();
+        let ops = disassemble(&code);
+        assert_eq!(basic_blocks(&ops).len(), 3);
+        let coverage = ContractCoverage {
+            pcs: BTreeSet::from([0, 1, 3, 4, 6, 11, 12]),
+            branches: BTreeSet::from([(6, 11)]),
+            ..Default::default()
+        };
+        let guarded = guarded_branches(&ops, &coverage);
+        assert_eq!(
+            guarded,
+            vec![GuardedBranch {
+                jumpi: 6,
+                jumped: true,
+                comparison: Some((3, "EQ".to_string())),
+            }]
+        );
+        let mut html = String::new();
+        assert_eq!(render_contract("abcd", &coverage, &code, &mut html), 1);
+        assert!(html.contains("class=\"unreached\""));
+    }
+}
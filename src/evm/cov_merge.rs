@@ -0,0 +1,202 @@
+/// Merge of the coverage of several runs (`cli cov merge run1/ run2/ -o merged/`)
+///
+/// The coverage maps of the fuzzer are indexed by hashes modulo the map size, which differs
+/// between builds (e.g., the GPU nodes and the CPU nodes), so they cannot be compared across
+/// runs. Instead, replaying a corpus writes `stats/coverage.json` in a canonical form: the PCs
+/// and the branches (`JUMPI` PC, destination) covered in each contract, keyed by the hash of its
+/// code. Merging unions these and reports the branches that only one run hit.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub const COVERAGE_FILE: &str = "coverage.json";
+pub const UNIQUE_BRANCHES_FILE: &str = "unique_branches.json";
+
+/// Coverage of a contract, PC indexed
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractCoverage {
+    /// Address of the contract in the run
+    pub address: String,
+    pub pcs: BTreeSet<usize>,
+    /// (PC of the `JUMPI`, PC executed next)
+    pub branches: BTreeSet<(usize, usize)>,
+    /// Executions of each PC
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hits: BTreeMap<usize, u64>,
+    /// Runtime code (hex), rendered by `cli cov map`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub code: String,
+}
+
+/// Coverage of a run, keyed by the hash of the code of the contracts
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalCoverage {
+    pub contracts: BTreeMap<String, ContractCoverage>,
+}
+
+impl CanonicalCoverage {
+    pub fn branch_count(&self) -> usize {
+        self.contracts.values().map(|c| c.branches.len()).sum()
+    }
+
+    pub fn pc_count(&self) -> usize {
+        self.contracts.values().map(|c| c.pcs.len()).sum()
+    }
+
+    /// Add the coverage of another run
+    pub fn union(&mut self, other: &CanonicalCoverage) {
+        for (hash, coverage) in &other.contracts {
+            let merged = self.contracts.entry(hash.clone()).or_insert_with(|| ContractCoverage {
+                address: coverage.address.clone(),
+                code: coverage.code.clone(),
+                ..Default::default()
+            });
+            merged.pcs.extend(coverage.pcs.iter().cloned());
+            merged.branches.extend(coverage.branches.iter().cloned());
+            for (pc, hits) in &coverage.hits {
+                *merged.hits.entry(*pc).or_default() += hits;
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self).unwrap())
+    }
+}
+
+/// Load the canonical coverage of a run, given its work directory or the coverage file itself
+pub fn load_run(path: &str) -> Result<CanonicalCoverage, String> {
+    let candidates = [
+        Path::new(path).join("stats").join(COVERAGE_FILE),
+        Path::new(path).join(COVERAGE_FILE),
+        Path::new(path).to_path_buf(),
+    ];
+    let file = candidates
+        .iter()
+        .find(|p| p.is_file())
+        .ok_or(format!("no {} found in {}, replay the corpus of the run first", COVERAGE_FILE, path))?;
+    let mut data = String::new();
+    File::open(file)
+        .and_then(|mut f| f.read_to_string(&mut data))
+        .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+    serde_json::from_str(&data).map_err(|e| format!("malformed coverage file {}: {}", file.display(), e))
+}
+
+/// Branches hit by each run and by none of the others
+pub fn unique_branches(runs: &Vec<CanonicalCoverage>) -> Vec<CanonicalCoverage> {
+    runs.iter()
+        .enumerate()
+        .map(|(i, run)| {
+            let mut unique = CanonicalCoverage::default();
+            for (hash, coverage) in &run.contracts {
+                let branches = coverage
+                    .branches
+                    .iter()
+                    .filter(|branch| {
+                        runs.iter().enumerate().all(|(j, other)| {
+                            j == i
+                                || !other
+                                    .contracts
+                                    .get(hash)
+                                    .map_or(false, |c| c.branches.contains(branch))
+                        })
+                    })
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                if !branches.is_empty() {
+                    unique.contracts.insert(
+                        hash.clone(),
+                        ContractCoverage {
+                            address: coverage.address.clone(),
+                            branches,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            unique
+        })
+        .collect()
+}
+
+/// Merge the coverage of the runs into `<output>/coverage.json`, write the branches unique to
+/// each run to `<output>/unique_branches.json` and return a report
+pub fn merge_runs(paths: &Vec<String>, output: &str) -> Result<String, String> {
+    let runs = paths.iter().map(|p| load_run(p)).collect::<Result<Vec<_>, _>>()?;
+    let mut merged = CanonicalCoverage::default();
+    for run in &runs {
+        merged.union(run);
+    }
+    let unique = unique_branches(&runs);
+
+    fs::create_dir_all(output).map_err(|e| format!("failed to create {}: {}", output, e))?;
+    merged
+        .save(&Path::new(output).join(COVERAGE_FILE).to_string_lossy())
+        .map_err(|e| format!("failed to write {}: {}", COVERAGE_FILE, e))?;
+    let by_run = paths.iter().cloned().zip(unique.iter().cloned()).collect::<BTreeMap<_, _>>();
+    let mut file = File::create(Path::new(output).join(UNIQUE_BRANCHES_FILE))
+        .map_err(|e| format!("failed to create {}: {}", UNIQUE_BRANCHES_FILE, e))?;
+    file.write_all(serde_json::to_string_pretty(&by_run).unwrap().as_bytes())
+        .map_err(|e| format!("failed to write {}: {}", UNIQUE_BRANCHES_FILE, e))?;
+
+    let mut report = format!(
+        "Merged {} runs: {} instructions, {} branches\n",
+        runs.len(),
+        merged.pc_count(),
+        merged.branch_count()
+    );
+    for ((path, run), unique) in paths.iter().zip(runs.iter()).zip(unique.iter()) {
+        report.push_str(&format!(
+            "{}: {} branches, {} only hit in this run\n",
+            path,
+            run.branch_count(),
+            unique.branch_count()
+        ));
+        for (hash, coverage) in &unique.contracts {
+            report.push_str(&format!(
+                "  {} ({}): {}\n",
+                coverage.address,
+                &hash[..hash.len().min(8)],
+                coverage
+                    .branches
+                    .iter()
+                    .map(|(pc, dest)| format!("{:#x}->{:#x}", pc, dest))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_coverage() {
+        let run = |branches: Vec<(usize, usize)>| {
+            let mut coverage = CanonicalCoverage::default();
+            coverage.contracts.insert(
+                "abcd".to_string(),
+                ContractCoverage {
+                    address: "0x1".to_string(),
+                    pcs: branches.iter().map(|b| b.0).collect(),
+                    branches: branches.into_iter().collect(),
+                    ..Default::default()
+                },
+            );
+            coverage
+        };
+        let runs = vec![run(vec![(10, 11), (20, 30)]), run(vec![(10, 11), (10, 40)])];
+        let mut merged = CanonicalCoverage::default();
+        runs.iter().for_each(|r| merged.union(r));
+        assert_eq!(merged.branch_count(), 3);
+        assert_eq!(merged.pc_count(), 2);
+
+        let unique = unique_branches(&runs);
+        assert_eq!(unique[0].contracts["abcd"].branches, BTreeSet::from([(20, 30)]));
+        assert_eq!(unique[1].contracts["abcd"].branches, BTreeSet::from([(10, 40)]));
+    }
+}
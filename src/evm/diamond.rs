@@ -0,0 +1,181 @@
+/// Support for diamond (EIP-2535) contracts
+///
+/// A diamond routes each selector to a facet and DELEGATECALLs into it, so the functions of the
+/// diamond are the ones of its facets, executed in the storage context of the diamond. We
+/// enumerate the facets via the loupe (`facets()`), or, when the loupe is missing, via the
+/// storage layout of the reference implementation, and keep a selector -> facet table per
+/// diamond.
+///
+/// revm follows the DELEGATECALL by itself. The GPU kernel is translated from a single bytecode,
+/// so a call routed to a facet only runs in GPU if the kernel is bound to that facet (with the
+/// storage of the diamond loaded, see [`crate::gpu_stage`]).
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha3::Sha3;
+use crate::evm::config::GPU_KERNEL_ADDRESS;
+use crate::evm::storage_alias::kernel_runs_on;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use std::collections::HashMap;
+
+/// `facets()` of the diamond loupe
+pub const FACETS_SELECTOR: [u8; 4] = [0x7a, 0x0e, 0xd6, 0x27];
+
+/// Storage position of `DiamondStorage` in the reference implementation
+pub const DIAMOND_STORAGE_POSITION: &str = "diamond.standard.diamond.storage";
+
+/// Selector -> facet table of each diamond
+pub static mut DIAMONDS: Option<HashMap<EVMAddress, HashMap<[u8; 4], EVMAddress>>> = None;
+
+/// Whether the code looks like a diamond, i.e., implements the loupe or uses the reference
+/// diamond storage
+pub fn is_diamond(code: &[u8]) -> bool {
+    let loupe = [[0x63].as_slice(), FACETS_SELECTOR.as_slice()].concat();
+    let storage = [[0x7f].as_slice(), &diamond_storage_position().to_be_bytes::<32>()].concat();
+    code.windows(loupe.len()).any(|w| w == loupe.as_slice())
+        || code.windows(storage.len()).any(|w| w == storage.as_slice())
+}
+
+fn keccak(data: &[u8]) -> EVMU256 {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    let mut out = [0u8; 32];
+    hasher.result(&mut out);
+    EVMU256::from_be_bytes(out)
+}
+
+fn diamond_storage_position() -> EVMU256 {
+    keccak(DIAMOND_STORAGE_POSITION.as_bytes())
+}
+
+fn word(data: &[u8], offset: usize) -> Option<EVMU256> {
+    data.get(offset..offset + 32).map(|w| EVMU256::try_from_be_slice(w).unwrap())
+}
+
+fn as_offset(value: EVMU256) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+/// Decode the return data of `facets()`, i.e., `(address facetAddress, bytes4[] functionSelectors)[]`
+pub fn decode_facets(output: &[u8]) -> Option<Vec<(EVMAddress, Vec<[u8; 4]>)>> {
+    let array = as_offset(word(output, 0)?)?;
+    let len = as_offset(word(output, array)?)?;
+    let head = array + 32;
+    let mut facets = vec![];
+    for i in 0..len {
+        let tuple = head + as_offset(word(output, head + i * 32)?)?;
+        let facet = EVMAddress::from_slice(&output.get(tuple + 12..tuple + 32)?);
+        let selectors = tuple + as_offset(word(output, tuple + 32)?)?;
+        let count = as_offset(word(output, selectors)?)?;
+        let mut functions = vec![];
+        for j in 0..count {
+            let offset = selectors + 32 + j * 32;
+            functions.push(output.get(offset..offset + 4)?.try_into().unwrap());
+        }
+        facets.push((facet, functions));
+    }
+    Some(facets)
+}
+
+/// Enumerate the facets from the storage of the reference implementation:
+/// ```text
+/// struct DiamondStorage {
+///     mapping(bytes4 => FacetAddressAndPosition) selectorToFacetAndPosition;  // position
+///     mapping(address => FacetFunctionSelectors) facetFunctionSelectors;      // position + 1
+///     address[] facetAddresses;                                               // position + 2
+///     ...
+/// }
+/// struct FacetFunctionSelectors { bytes4[] functionSelectors; uint256 facetAddressPosition; }
+/// ```
+pub fn facets_from_storage(vm_state: &EVMState, diamond: EVMAddress) -> Vec<(EVMAddress, Vec<[u8; 4]>)> {
+    let storage = match vm_state.get(&diamond) {
+        Some(storage) => storage,
+        None => return vec![],
+    };
+    let sload = |slot: EVMU256| storage.get(&slot).cloned().unwrap_or(EVMU256::ZERO);
+    let position = diamond_storage_position();
+
+    let addresses_slot = position + EVMU256::from(2);
+    let addresses_data = keccak(&addresses_slot.to_be_bytes::<32>());
+    let facet_count = as_offset(sload(addresses_slot)).unwrap_or(0);
+    let mut facets = vec![];
+    for i in 0..facet_count {
+        let facet_word: [u8; 32] = sload(addresses_data + EVMU256::from(i)).to_be_bytes();
+        let facet = EVMAddress::from_slice(&facet_word[12..]);
+
+        let selectors_slot = keccak(
+            &[
+                [[0u8; 12].as_slice(), facet.0.as_slice()].concat(),
+                (position + EVMU256::from(1)).to_be_bytes::<32>().to_vec(),
+            ]
+            .concat(),
+        );
+        let selectors_data = keccak(&selectors_slot.to_be_bytes::<32>());
+        let count = as_offset(sload(selectors_slot)).unwrap_or(0);
+        // bytes4 are packed 8 per slot, starting from the lower-order bytes
+        let functions = (0..count)
+            .map(|j| {
+                let packed: [u8; 32] = sload(selectors_data + EVMU256::from(j / 8)).to_be_bytes();
+                let end = 32 - (j % 8) * 4;
+                packed[end - 4..end].try_into().unwrap()
+            })
+            .collect();
+        facets.push((facet, functions));
+    }
+    facets
+}
+
+/// Register the selector -> facet table of a diamond
+pub fn register_facets(diamond: EVMAddress, facets: &Vec<(EVMAddress, Vec<[u8; 4]>)>) {
+    let table = unsafe { DIAMONDS.get_or_insert_with(HashMap::new) }
+        .entry(diamond)
+        .or_default();
+    for (facet, functions) in facets {
+        for function in functions {
+            table.insert(*function, *facet);
+        }
+    }
+}
+
+/// Facet handling the selector, if the contract is a diamond
+pub fn facet_of(contract: &EVMAddress, calldata: &[u8]) -> Option<EVMAddress> {
+    let selector: [u8; 4] = calldata.get(..4)?.try_into().unwrap();
+    unsafe { DIAMONDS.as_ref()?.get(contract)?.get(&selector).cloned() }
+}
+
+/// Whether the GPU kernel runs the code handling the call, on the storage of the contract called
+/// (see [`crate::evm::storage_alias`])
+pub fn gpu_can_execute(contract: &EVMAddress, calldata: &[u8]) -> bool {
+    match facet_of(contract, calldata) {
+        Some(facet) => unsafe { GPU_KERNEL_ADDRESS == Some(facet) },
+        None => unsafe { GPU_KERNEL_ADDRESS }.map_or(true, |kernel| kernel_runs_on(&kernel, contract)),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_facets() {
+        // [(0x..01, [0x11111111, 0x22222222])]
+        let output = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "1111111100000000000000000000000000000000000000000000000000000000",
+            "2222222200000000000000000000000000000000000000000000000000000000",
+        ))
+        .unwrap();
+        let facets = decode_facets(&output).unwrap();
+        let mut facet = [0u8; 20];
+        facet[19] = 1;
+        assert_eq!(
+            facets,
+            vec![(EVMAddress::from_slice(&facet), vec![[0x11; 4], [0x22; 4]])]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+/// Dictionaries learned during a campaign, persisted across campaigns on the same targets
+///
+/// The host records the operands of comparisons, the preimages hashed by `SHA3` and the magic
+/// values matched by `EQ` while executing. They are saved to `<dir>/<targets hash>.json`
+/// (`--dictionary-dir`) and added to the constant pool of the mutator when a campaign on the
+/// same targets starts, so that a restarted run does not relearn them from scratch.
+use crate::evm::mutation_utils::ConstantPoolMetadata;
+use crate::evm::types::EVMU256;
+use crate::workdir::hash_bytes;
+use libafl::prelude::HasMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Maximum number of entries of each dictionary
+pub const MAX_DICTIONARY_SIZE: usize = 4096;
+/// Largest preimage recorded, i.e., a mapping key and its slot
+pub const MAX_PREIMAGE_SIZE: usize = 64;
+/// Save the dictionaries every this many executions
+pub const DICTIONARY_SAVE_INTERVAL: usize = 100000;
+
+/// Dictionaries of a campaign, values are hex encoded
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LearnedDictionary {
+    pub cmp_operands: BTreeSet<String>,
+    pub keccak_preimages: BTreeSet<String>,
+    pub magic_values: BTreeSet<String>,
+}
+
+/// Dictionaries being learned, `None` if they are not persisted
+pub static mut LEARNED_DICTIONARY: Option<LearnedDictionary> = None;
+/// File the dictionaries are persisted to
+pub static mut DICTIONARY_PATH: Option<String> = None;
+
+fn insert(set: &mut BTreeSet<String>, value: &[u8]) {
+    if set.len() < MAX_DICTIONARY_SIZE {
+        set.insert(hex::encode(value));
+    }
+}
+
+/// Big endian bytes without the leading zeros
+fn trimmed_bytes(v: EVMU256) -> Vec<u8> {
+    let bytes: [u8; 32] = v.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(31);
+    bytes[start..].to_vec()
+}
+
+/// Record the operands of a comparison
+pub fn record_cmp(v1: EVMU256, v2: EVMU256, is_eq: bool) {
+    let dict = match unsafe { LEARNED_DICTIONARY.as_mut() } {
+        Some(dict) => dict,
+        None => return,
+    };
+    // small values are already covered by the interesting values of the mutator
+    for v in [v1, v2] {
+        if v > EVMU256::from(255) {
+            insert(&mut dict.cmp_operands, &trimmed_bytes(v));
+        }
+    }
+    if is_eq && v1 == v2 && v1 > EVMU256::from(255) {
+        insert(&mut dict.magic_values, &trimmed_bytes(v1));
+    }
+}
+
+/// Whether the value (without its leading zeros) is a comparison operand learned so far
+pub fn is_learned_operand(value: &[u8]) -> bool {
+    let start = value.iter().position(|b| *b != 0).unwrap_or(value.len().saturating_sub(1));
+    let value = hex::encode(&value[start..]);
+    match unsafe { LEARNED_DICTIONARY.as_ref() } {
+        Some(dict) => dict.cmp_operands.contains(&value) || dict.magic_values.contains(&value),
+        None => false,
+    }
+}
+
+/// Record the preimage of a `SHA3`
+pub fn record_preimage(preimage: &[u8]) {
+    if let Some(dict) = unsafe { LEARNED_DICTIONARY.as_mut() } {
+        if !preimage.is_empty() && preimage.len() <= MAX_PREIMAGE_SIZE {
+            insert(&mut dict.keccak_preimages, preimage);
+        }
+    }
+}
+
+/// Hash identifying the targets of a campaign
+pub fn targets_hash(codes: &Vec<&[u8]>) -> String {
+    let mut hashes = codes.iter().map(|code| hash_bytes(code)).collect::<Vec<_>>();
+    hashes.sort();
+    hash_bytes(hashes.concat().as_bytes())
+}
+
+pub fn load_dictionary(path: &str) -> LearnedDictionary {
+    let mut data = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut data)) {
+        Ok(_) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Ignoring malformed dictionary {}: {}", path, e);
+            LearnedDictionary::default()
+        }),
+        Err(_) => LearnedDictionary::default(),
+    }
+}
+
+/// Load the dictionaries of the targets and start learning on top of them
+pub fn init_dictionary(dir: &str, codes: &Vec<&[u8]>) -> LearnedDictionary {
+    let path = Path::new(dir)
+        .join(format!("{}.json", targets_hash(codes)))
+        .to_string_lossy()
+        .to_string();
+    let dict = load_dictionary(&path);
+    info!(
+        "Loaded dictionary {}: {} comparison operands, {} keccak preimages, {} magic values",
+        path,
+        dict.cmp_operands.len(),
+        dict.keccak_preimages.len(),
+        dict.magic_values.len()
+    );
+    unsafe {
+        LEARNED_DICTIONARY = Some(dict.clone());
+        DICTIONARY_PATH = Some(path);
+    }
+    dict
+}
+
+/// Add the dictionaries to the constant pool of the mutator
+pub fn add_dictionary_to_state<S: HasMetadata>(dict: &LearnedDictionary, state: &mut S) {
+    let mut constants = vec![];
+    for value in dict.magic_values.iter().chain(dict.cmp_operands.iter()) {
+        constants.extend(hex::decode(value).ok());
+    }
+    // the words of a preimage are mapping keys and slots
+    for preimage in &dict.keccak_preimages {
+        if let Ok(bytes) = hex::decode(preimage) {
+            constants.extend(bytes.chunks(32).map(|word| word.to_vec()));
+        }
+    }
+    if constants.is_empty() {
+        return;
+    }
+    match state.metadata_mut().get_mut::<ConstantPoolMetadata>() {
+        Some(meta) => {
+            for constant in constants {
+                if !meta.constants.contains(&constant) {
+                    meta.constants.push(constant);
+                }
+            }
+        }
+        None => {
+            state.metadata_mut().insert(ConstantPoolMetadata { constants });
+        }
+    }
+}
+
+/// Save the dictionaries learned so far
+pub fn save_dictionary() {
+    let (dict, path) = match unsafe { (LEARNED_DICTIONARY.as_ref(), DICTIONARY_PATH.as_ref()) } {
+        (Some(dict), Some(path)) => (dict, path),
+        _ => return,
+    };
+    let written = match Path::new(path).parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::write(path, serde_json::to_string_pretty(dict).unwrap()));
+    if let Err(e) = written {
+        warn!("failed to write the dictionary {}: {}", path, e);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trimmed_bytes() {
+        assert_eq!(trimmed_bytes(EVMU256::from(0xdeadbeefu64)), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(trimmed_bytes(EVMU256::ZERO), vec![0]);
+        let a: &[u8] = &[1, 2];
+        let b: &[u8] = &[3];
+        assert_eq!(targets_hash(&vec![a, b]), targets_hash(&vec![b, a]));
+    }
+}
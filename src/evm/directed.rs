@@ -0,0 +1,257 @@
+/// Directed fuzzing toward a target function or PC (`--direct-to`)
+///
+/// The distance of a basic block is the number of CFG edges on the shortest path from the block
+/// to the target. The CFG is built from the bytecode (fallthroughs and `PUSH dest, JUMP(I)`)
+/// and completed with the jumps taken by heimdall's symbolic execution of each selector, which
+/// resolves the dynamic jumps returning from internal functions.
+///
+/// During execution, the host records the smallest distance among the jump destinations hit.
+/// An execution getting closer to the target than all the previous ones is kept in the corpus
+/// and its state is voted for, so that the fuzzer concentrates on the inputs nearing the target.
+use crate::evm::entry_points::disassemble;
+use crate::evm::hex_utils::Selector;
+use crate::evm::onchain::abi_decompiler::fetch_jump_edges_heimdall;
+use crate::evm::types::EVMAddress;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+
+/// What to direct the fuzzer toward
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DirectTarget {
+    /// Entry point of a function
+    Selector([u8; 4]),
+    Pc(usize),
+}
+
+/// `--direct-to` argument: `[contract:]<selector or pc>`, e.g., `Vault:0x2e1a7d4d` or `1234`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirectedTarget {
+    /// Contract name or address, all contracts if `None`
+    pub contract: Option<String>,
+    pub target: DirectTarget,
+}
+
+impl FromStr for DirectedTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (contract, target) = match s.rsplit_once(':') {
+            Some((contract, target)) => (Some(contract.to_string()), target),
+            None => (None, s),
+        };
+        let target = match target.strip_prefix("0x") {
+            // 4 bytes hex is a selector
+            Some(hex_str) if hex_str.len() == 8 => DirectTarget::Selector(
+                Selector::from_str(hex_str).map_err(|e| format!("Invalid selector {}: {}", target, e))?.0,
+            ),
+            Some(hex_str) => DirectTarget::Pc(
+                usize::from_str_radix(hex_str, 16).map_err(|e| format!("Invalid pc {}: {}", target, e))?,
+            ),
+            None => DirectTarget::Pc(usize::from_str(target).map_err(|e| format!("Invalid pc {}: {}", target, e))?),
+        };
+        Ok(DirectedTarget { contract, target })
+    }
+}
+
+impl DirectedTarget {
+    /// Whether the target applies to the contract
+    pub fn matches(&self, name: &String, address: &EVMAddress) -> bool {
+        match &self.contract {
+            Some(contract) => {
+                contract == name || contract.to_lowercase() == format!("{:?}", address).to_lowercase()
+            }
+            None => true,
+        }
+    }
+}
+
+/// Start PCs of the basic blocks
+pub fn block_starts(code: &[u8]) -> BTreeSet<usize> {
+    let mut starts = BTreeSet::from([0]);
+    for (pc, op, imm) in disassemble(code) {
+        match op {
+            // JUMPDEST
+            0x5b => {
+                starts.insert(pc);
+            }
+            // STOP, JUMP, JUMPI, RETURN, REVERT, INVALID, SELFDESTRUCT
+            0x00 | 0x56 | 0x57 | 0xf3 | 0xfd | 0xfe | 0xff if pc + 1 + imm.len() < code.len() => {
+                starts.insert(pc + 1 + imm.len());
+            }
+            _ => {}
+        }
+    }
+    starts
+}
+
+/// Start PC of the block containing the PC
+pub fn block_of(starts: &BTreeSet<usize>, pc: usize) -> usize {
+    *starts.range(..=pc).next_back().unwrap_or(&0)
+}
+
+/// Successors of each block: fallthroughs, static jumps and the jumps resolved by heimdall
+pub fn build_cfg(code: &[u8], jump_edges: &Vec<(usize, usize)>) -> HashMap<usize, HashSet<usize>> {
+    let starts = block_starts(code);
+    let mut cfg: HashMap<usize, HashSet<usize>> = HashMap::new();
+    let mut block = 0;
+    let mut prev: Option<(u8, Vec<u8>)> = None;
+    for (pc, op, imm) in disassemble(code) {
+        if pc != block && starts.contains(&pc) {
+            let falls_through = match &prev {
+                Some((prev_op, _)) => !matches!(prev_op, 0x00 | 0x56 | 0xf3 | 0xfd | 0xfe | 0xff),
+                None => true,
+            };
+            if falls_through {
+                cfg.entry(block).or_default().insert(pc);
+            }
+            block = pc;
+        }
+        if let (0x56 | 0x57, Some((0x60..=0x7f, dest))) = (op, &prev) {
+            if let Ok(dest) = usize::try_from(dest.iter().fold(0u128, |acc, b| acc << 8 | *b as u128)) {
+                cfg.entry(block).or_default().insert(dest);
+            }
+        }
+        prev = Some((op, imm));
+    }
+    for (from, to) in jump_edges {
+        cfg.entry(block_of(&starts, *from)).or_default().insert(*to);
+    }
+    cfg
+}
+
+/// Distance of each block reaching the targets, by BFS on the reversed CFG
+pub fn distances(cfg: &HashMap<usize, HashSet<usize>>, targets: &HashSet<usize>) -> HashMap<usize, u32> {
+    let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (from, tos) in cfg {
+        for to in tos {
+            predecessors.entry(*to).or_default().push(*from);
+        }
+    }
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for target in targets {
+        distances.insert(*target, 0);
+        queue.push_back(*target);
+    }
+    while let Some(block) = queue.pop_front() {
+        let distance = distances[&block] + 1;
+        for pred in predecessors.get(&block).cloned().unwrap_or_default() {
+            if !distances.contains_key(&pred) {
+                distances.insert(pred, distance);
+                queue.push_back(pred);
+            }
+        }
+    }
+    distances
+}
+
+/// Distances to the target in the code, `None` if the target is not in the code or the code
+/// could not be decompiled
+pub fn compute_distances(code: &[u8], target: &DirectTarget) -> Option<HashMap<usize, u32>> {
+    let (entry_points, jump_edges) = fetch_jump_edges_heimdall(hex::encode(code)).ok()?;
+    let target_block = target_block(code, &entry_points, target)?;
+    let cfg = build_cfg(code, &jump_edges);
+    Some(distances(&cfg, &HashSet::from([target_block])))
+}
+
+/// Block of the target in the code, `None` if it is not in the code
+pub fn target_block(code: &[u8], entry_points: &HashMap<[u8; 4], usize>, target: &DirectTarget) -> Option<usize> {
+    let starts = block_starts(code);
+    match target {
+        DirectTarget::Selector(selector) => Some(block_of(&starts, *entry_points.get(selector)?)),
+        DirectTarget::Pc(pc) if *pc < code.len() => Some(block_of(&starts, *pc)),
+        DirectTarget::Pc(_) => None,
+    }
+}
+
+/// Distances of the blocks of each directed contract
+pub static mut DIRECTED_DISTANCES: Option<HashMap<EVMAddress, HashMap<usize, u32>>> = None;
+/// Smallest distance reached by the current execution
+pub static mut DIRECTED_DISTANCE: u32 = u32::MAX;
+/// Smallest distance reached so far
+pub static mut BEST_DIRECTED_DISTANCE: u32 = u32::MAX;
+/// Whether the last execution got closer to the target
+pub static mut DIRECTED_INTERESTING: bool = false;
+
+pub fn register_distances(address: EVMAddress, distances: HashMap<usize, u32>) {
+    unsafe {
+        DIRECTED_DISTANCES
+            .get_or_insert_with(HashMap::new)
+            .insert(address, distances);
+    }
+}
+
+/// Add the distances to other targets, keeping the smallest distance of each block
+pub fn merge_distances(address: EVMAddress, distances: HashMap<usize, u32>) {
+    unsafe {
+        let registered = DIRECTED_DISTANCES
+            .get_or_insert_with(HashMap::new)
+            .entry(address)
+            .or_default();
+        for (block, distance) in distances {
+            registered
+                .entry(block)
+                .and_modify(|d| *d = (*d).min(distance))
+                .or_insert(distance);
+        }
+    }
+}
+
+/// Record a jump of the current execution
+pub fn record_directed_jump(address: &EVMAddress, dest: usize) {
+    unsafe {
+        if let Some(distance) = DIRECTED_DISTANCES
+            .as_ref()
+            .and_then(|d| d.get(address))
+            .and_then(|d| d.get(&dest))
+        {
+            if *distance < DIRECTED_DISTANCE {
+                DIRECTED_DISTANCE = *distance;
+            }
+        }
+    }
+}
+
+/// Whether the current execution got closer to the target than the previous ones.
+/// Resets the distance of the current execution.
+pub fn take_directed_progress() -> bool {
+    unsafe {
+        let distance = DIRECTED_DISTANCE;
+        DIRECTED_DISTANCE = u32::MAX;
+        if distance < BEST_DIRECTED_DISTANCE {
+            BEST_DIRECTED_DISTANCE = distance;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        // CALLDATASIZE, PUSH1 0x08, JUMPI, PUSH1 0x00, DUP1, REVERT, JUMPDEST (8), STOP
+        let code = hex::decode("36600857600080fd5b00").unwrap();
+        let cfg = build_cfg(&code, &vec![]);
+        let starts = block_starts(&code);
+        assert_eq!(starts, BTreeSet::from([0, 4, 8]));
+        let d = distances(&cfg, &HashSet::from([8]));
+        assert_eq!(d.get(&0), Some(&1));
+        assert_eq!(d.get(&4), None);
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            DirectedTarget::from_str("Vault:0x2e1a7d4d").unwrap(),
+            DirectedTarget {
+                contract: Some("Vault".to_string()),
+                target: DirectTarget::Selector([0x2e, 0x1a, 0x7d, 0x4d])
+            }
+        );
+        assert_eq!(DirectedTarget::from_str("1234").unwrap().target, DirectTarget::Pc(1234));
+        assert_eq!(DirectedTarget::from_str("0x4d2").unwrap().target, DirectTarget::Pc(1234));
+    }
+}
@@ -0,0 +1,240 @@
+/// Coverage-preserving corpus distillation (`cli distill <work_dir> -o <output>`)
+///
+/// Produces a minimal and anonymized corpus of a run, to publish alongside a paper or a bug
+/// report:
+/// 1. each replayable entry of the corpus is replayed alone, with the arguments of the run (from
+///    its manifest), for its canonical coverage (see [`crate::evm::cov_merge`])
+/// 2. entries are picked greedily, the one adding the most branches (then PCs) first, until they
+///    cover everything the corpus covers
+/// 3. the callers that are not the fuzzer's own (default and contract callers, deployer), e.g.
+///    holders of an on-chain fork, are replaced by placeholders (`0x…ca11e70001`), in the
+///    arguments as well
+/// 4. the distilled corpus is replayed as a whole and must reach the same coverage, otherwise
+///    the branches lost are reported
+///
+/// The distilled corpus is written to `<output>/corpus`, with the manifest of the run.
+use crate::evm::contract_utils::FIX_DEPLOYER;
+use crate::evm::corpus_format::{write_corpus_file, CorpusFile, CorpusTxn, TxnKind};
+use crate::evm::corpus_initializer::{CONTRACT_CALLERS, DEFAULT_CALLERS};
+use crate::evm::cov_merge::{load_run, CanonicalCoverage};
+use crate::evm::shell::{campaign_args, load_entries, replay_files};
+use crate::evm::types::{fixed_address, EVMAddress};
+use crate::workdir::{CORPUS_DIR, MANIFEST_FILE};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Low bits of the placeholders of the callers
+const PLACEHOLDER_BASE: u64 = 0xca11e7_0000;
+
+/// Log level of the replays
+const REPLAY_LOG: &str = "error";
+
+/// The `i`th placeholder of a caller, from 1
+pub fn placeholder(i: usize) -> EVMAddress {
+    let mut address = EVMAddress::zero();
+    address.0[12..].copy_from_slice(&(PLACEHOLDER_BASE + i as u64).to_be_bytes());
+    address
+}
+
+/// Branches and PCs covered, keyed by the hash of the code
+fn covered(coverage: &CanonicalCoverage) -> (BTreeSet<(String, usize, usize)>, BTreeSet<(String, usize)>) {
+    let mut branches = BTreeSet::new();
+    let mut pcs = BTreeSet::new();
+    for (hash, contract) in &coverage.contracts {
+        branches.extend(contract.branches.iter().map(|(from, to)| (hash.clone(), *from, *to)));
+        pcs.extend(contract.pcs.iter().map(|pc| (hash.clone(), *pc)));
+    }
+    (branches, pcs)
+}
+
+/// Indices of the entries covering together what all of them cover, picked greedily
+pub fn greedy_cover(coverages: &Vec<CanonicalCoverage>) -> Vec<usize> {
+    let items = coverages.iter().map(covered).collect::<Vec<_>>();
+    let mut branches = BTreeSet::new();
+    let mut pcs = BTreeSet::new();
+    let mut picked = vec![];
+    loop {
+        let best = items
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !picked.contains(i))
+            .map(|(i, (b, p))| (i, b.difference(&branches).count(), p.difference(&pcs).count()))
+            .filter(|(_, new_branches, new_pcs)| new_branches + new_pcs > 0)
+            .max_by(|a, b| (a.1, a.2).cmp(&(b.1, b.2)).then(b.0.cmp(&a.0)));
+        match best {
+            Some((i, _, _)) => {
+                branches.extend(items[i].0.iter().cloned());
+                pcs.extend(items[i].1.iter().cloned());
+                picked.push(i);
+            }
+            None => return picked,
+        }
+    }
+}
+
+/// Replace the callers not in `keep` by placeholders, and the arguments holding them
+pub fn anonymize(
+    txns: &mut Vec<CorpusTxn>,
+    keep: &HashSet<EVMAddress>,
+    placeholders: &mut HashMap<EVMAddress, EVMAddress>,
+) {
+    for txn in txns.iter_mut() {
+        if !keep.contains(&txn.caller) {
+            let next = placeholders.len() + 1;
+            txn.caller = *placeholders.entry(txn.caller).or_insert_with(|| placeholder(next));
+        }
+    }
+    for txn in txns.iter_mut().filter(|txn| txn.kind == TxnKind::Abi) {
+        let mut data = match hex::decode(&txn.data) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        for word in data.get_mut(4..).unwrap_or_default().chunks_mut(32).filter(|w| w.len() == 32) {
+            if word[..12].iter().any(|b| *b != 0) {
+                continue;
+            }
+            if let Some(replaced) = placeholders.get(&EVMAddress::from_slice(&word[12..])) {
+                word[12..].copy_from_slice(&replaced.0);
+            }
+        }
+        txn.data = hex::encode(data);
+    }
+}
+
+/// Branches and PCs of `expected` that `actual` misses
+fn missing(expected: &CanonicalCoverage, actual: &CanonicalCoverage) -> (usize, usize) {
+    let (expected_branches, expected_pcs) = covered(expected);
+    let (branches, pcs) = covered(actual);
+    (
+        expected_branches.difference(&branches).count(),
+        expected_pcs.difference(&pcs).count(),
+    )
+}
+
+/// Distill the corpus of the run into `<output>/corpus`, returns a report
+pub fn distill(run: &str, output: &str) -> Result<String, String> {
+    let args = campaign_args(run)?;
+    let entries = load_entries(run)?;
+    if entries.is_empty() {
+        return Err(format!("no replayable corpus entry in {}", run));
+    }
+    let replay_dir = Path::new(output).join("replay");
+    let mut coverages = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        let dir = replay_dir.join(i.to_string());
+        fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        let file = dir.join("entry.json").to_string_lossy().to_string();
+        write_corpus_file(&file, &CorpusFile::V1 { txns: entry.txns.clone() })?;
+        replay_files(&args, &dir, &file, REPLAY_LOG)?;
+        coverages.push(load_run(&dir.to_string_lossy())?);
+    }
+    let mut total = CanonicalCoverage::default();
+    coverages.iter().for_each(|coverage| total.union(coverage));
+    let picked = greedy_cover(&coverages);
+
+    let mut keep = DEFAULT_CALLERS
+        .iter()
+        .chain(CONTRACT_CALLERS.iter())
+        .chain([FIX_DEPLOYER].iter())
+        .map(|caller| fixed_address(caller))
+        .collect::<HashSet<_>>();
+    keep.extend(entries.iter().flat_map(|entry| entry.txns.iter().map(|txn| txn.contract)));
+    let mut placeholders = HashMap::new();
+    let corpus_dir = Path::new(output).join(CORPUS_DIR);
+    fs::create_dir_all(&corpus_dir).map_err(|e| format!("failed to create {}: {}", corpus_dir.display(), e))?;
+    for (n, i) in picked.iter().enumerate() {
+        let mut txns = entries[*i].txns.clone();
+        anonymize(&mut txns, &keep, &mut placeholders);
+        let file = corpus_dir.join(format!("{:06}_replayable", n));
+        write_corpus_file(&file.to_string_lossy(), &CorpusFile::V1 { txns })?;
+    }
+    fs::copy(Path::new(run).join(MANIFEST_FILE), Path::new(output).join(MANIFEST_FILE))
+        .map_err(|e| format!("failed to copy the manifest: {}", e))?;
+
+    // the distilled corpus must replay to the same coverage
+    let verify_dir = Path::new(output).join("verify");
+    fs::create_dir_all(&verify_dir).map_err(|e| format!("failed to create {}: {}", verify_dir.display(), e))?;
+    let files = corpus_dir.join("*_replayable").to_string_lossy().to_string();
+    replay_files(&args, &verify_dir, &files, REPLAY_LOG)?;
+    let distilled = load_run(&verify_dir.to_string_lossy())?;
+    let report = format!(
+        "Distilled {} of {} entries into {} ({} branches, {} PCs), {} callers anonymized\n",
+        picked.len(),
+        entries.len(),
+        corpus_dir.display(),
+        total.branch_count(),
+        total.pc_count(),
+        placeholders.len()
+    );
+    match missing(&total, &distilled) {
+        (0, 0) => Ok(format!("{}Verified: the distilled corpus replays to the same coverage\n", report)),
+        (branches, pcs) => Err(format!(
+            "{}The distilled corpus misses {} branches and {} PCs of the corpus when replayed, \
+             the anonymized callers may not be equivalent",
+            report, branches, pcs
+        )),
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::cov_merge::ContractCoverage;
+    use crate::evm::types::EVMU256;
+
+    #[test]
+    fn test_distill() {
+        let coverage = |branches: Vec<(usize, usize)>| {
+            let mut coverage = CanonicalCoverage::default();
+            coverage.contracts.insert(
+                "code".to_string(),
+                ContractCoverage {
+                    pcs: branches.iter().map(|(from, _)| *from).collect(),
+                    branches: branches.into_iter().collect(),
+                    ..Default::default()
+                },
+            );
+            coverage
+        };
+        let coverages = vec![
+            coverage(vec![(1, 2)]),
+            coverage(vec![(1, 2), (3, 4)]),
+            coverage(vec![(5, 6)]),
+            coverage(vec![(3, 4)]),
+        ];
+        assert_eq!(greedy_cover(&coverages), vec![1, 2]);
+
+        let holder = EVMAddress::from_slice(&[0x77; 20]);
+        let attacker = fixed_address(DEFAULT_CALLERS[0]);
+        let txn = |caller: EVMAddress, data: String| CorpusTxn {
+            kind: TxnKind::Abi,
+            caller,
+            contract: EVMAddress::from_slice(&[0x11; 20]),
+            data,
+            value: EVMU256::ZERO,
+            liquidation_percent: 0,
+            warp_to: 0,
+            repeat: 1,
+            reentrancy_limit: 0,
+            step: false,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
+            function: None,
+            meta: None,
+        };
+        // transfer(holder, 1) by the holder, then by the attacker
+        let data = format!("a9059cbb{:0>64}{:064x}", hex::encode(holder.0), 1);
+        let mut txns = vec![txn(holder, data.clone()), txn(attacker, data)];
+        let keep = HashSet::from([attacker]);
+        let mut placeholders = HashMap::new();
+        anonymize(&mut txns, &keep, &mut placeholders);
+        assert_eq!(txns[0].caller, placeholder(1));
+        assert_eq!(txns[1].caller, attacker);
+        assert_eq!(
+            txns[1].data,
+            format!("a9059cbb{:0>64}{:064x}", hex::encode(placeholder(1).0), 1)
+        );
+    }
+}
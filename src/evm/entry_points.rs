@@ -0,0 +1,190 @@
+/// Entry points of contracts that do not use the standard selector dispatcher
+///
+/// Handwritten assembly, minimal proxies and router contracts often route calls on something
+/// else than a 4 bytes selector (e.g., the first byte of calldata). Without an ABI, such
+/// contracts would only be fuzzed with plain transfers. Entry points can be declared manually
+/// (calldata prefixes or PCs reached by the dispatcher) or detected by heuristics.
+use crate::evm::hex_utils::parse_hex;
+use serde::{Deserialize, Serialize};
+use crate::error::{load_json, MauResult};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// A manually declared entry point
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryPoint {
+    /// PC of the code handling the call, resolved to the calldata prefix the dispatcher compares
+    /// against before jumping there
+    Pc(usize),
+    /// Calldata prefix (hex) selecting the entry point, up to 4 bytes
+    Prefix(String),
+}
+
+/// Load entry points from a JSON file mapping contract names or addresses to entry points, e.g.,
+/// `{"Router": [{"prefix": "0x01"}, {"pc": 1234}]}`
+pub fn load_entry_points(path: &str) -> MauResult<HashMap<String, Vec<EntryPoint>>> {
+    load_json(path)
+}
+
+/// A comparison of calldata against a constant followed by a conditional jump,
+/// i.e., `PUSHn constant, (DUPn), EQ, PUSHm dest, JUMPI`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DispatchBranch {
+    pub constant: Vec<u8>,
+    pub dest: usize,
+}
+
+/// Disassemble into (pc, opcode, immediate) tuples
+pub(crate) fn disassemble(code: &[u8]) -> Vec<(usize, u8, Vec<u8>)> {
+    let mut ops = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        match op {
+            0x60..=0x7f => {
+                let size = op as usize - 0x5f;
+                let end = std::cmp::min(pc + 1 + size, code.len());
+                ops.push((pc, op, code[pc + 1..end].to_vec()));
+                pc = end;
+            }
+            _ => {
+                ops.push((pc, op, vec![]));
+                pc += 1;
+            }
+        }
+    }
+    ops
+}
+
+fn as_usize(bytes: &Vec<u8>) -> usize {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize)
+}
+
+/// Find all `constant == x ? jump dest` branches in the code
+pub fn find_dispatch_branches(code: &[u8]) -> Vec<DispatchBranch> {
+    let ops = disassemble(code);
+    let mut branches = vec![];
+    for i in 0..ops.len() {
+        if ops[i].1 != 0x14 {
+            // EQ
+            continue;
+        }
+        // constant is pushed right before EQ, or right before a DUP preceding EQ
+        let constant = match (i.checked_sub(1).map(|j| &ops[j]), i.checked_sub(2).map(|j| &ops[j])) {
+            (Some((_, 0x60..=0x7f, c)), _) => c.clone(),
+            (Some((_, 0x80..=0x8f, _)), Some((_, 0x60..=0x7f, c))) => c.clone(),
+            _ => continue,
+        };
+        match (ops.get(i + 1), ops.get(i + 2)) {
+            (Some((_, 0x60..=0x7f, dest)), Some((_, 0x57, _))) => branches.push(DispatchBranch {
+                constant,
+                dest: as_usize(dest),
+            }),
+            _ => {}
+        }
+    }
+    branches
+}
+
+/// Whether the code has a standard (solc / vyper) selector dispatcher,
+/// i.e., extracts the first 4 bytes of calldata with `SHR 0xe0` or `DIV 2**224`
+pub fn has_standard_dispatcher(code: &[u8]) -> bool {
+    let ops = disassemble(code);
+    ops.windows(2).any(|w| match (&w[0], &w[1]) {
+        ((_, 0x60, c), (_, 0x1c, _)) => c == &vec![0xe0],
+        ((_, 0x7c, c), (_, 0x04, _)) | ((_, 0x7c, c), (_, 0x90, _)) => {
+            c.len() == 29 && c[0] == 0x01 && c[1..].iter().all(|b| *b == 0)
+        }
+        _ => false,
+    })
+}
+
+/// Whether the code routes calls on the first byte of calldata,
+/// i.e., `PUSH1 0x00 CALLDATALOAD PUSH1 0xf8 SHR` or `PUSH1 0x00 CALLDATALOAD PUSH1 0x00 BYTE`
+pub fn has_first_byte_routing(code: &[u8]) -> bool {
+    let ops = disassemble(code);
+    ops.windows(4).any(|w| match (&w[0], &w[1], &w[2], &w[3]) {
+        ((_, 0x60, a), (_, 0x35, _), (_, 0x60, b), (_, 0x1c, _)) => a == &vec![0] && b == &vec![0xf8],
+        ((_, 0x60, a), (_, 0x35, _), (_, 0x60, b), (_, 0x1a, _)) => a == &vec![0] && b == &vec![0],
+        _ => false,
+    })
+}
+
+/// Heuristically detect calldata prefixes of non-standard dispatchers
+pub fn detect_prefixes(code: &[u8]) -> Vec<Vec<u8>> {
+    if has_standard_dispatcher(code) || !has_first_byte_routing(code) {
+        return vec![];
+    }
+    let mut prefixes: Vec<Vec<u8>> = find_dispatch_branches(code)
+        .into_iter()
+        .filter(|b| b.constant.len() == 1)
+        .map(|b| b.constant)
+        .collect();
+    prefixes.sort();
+    prefixes.dedup();
+    prefixes
+}
+
+/// Resolve declared entry points to calldata prefixes
+pub fn resolve_prefixes(code: &[u8], entry_points: &Vec<EntryPoint>) -> Vec<Vec<u8>> {
+    let branches = find_dispatch_branches(code);
+    let mut prefixes = vec![];
+    for entry_point in entry_points {
+        match entry_point {
+            EntryPoint::Prefix(prefix) => {
+                match parse_hex(prefix) {
+                    Ok(prefix) if prefix.len() <= 4 => prefixes.push(prefix),
+                    Ok(_) => warn!("entry point prefix {} is longer than 4 bytes, ignored", prefix),
+                    Err(e) => warn!("entry point prefix ignored: {}", e),
+                }
+            }
+            EntryPoint::Pc(pc) => {
+                let found: Vec<Vec<u8>> = branches
+                    .iter()
+                    .filter(|b| b.dest == *pc && b.constant.len() <= 4)
+                    .map(|b| b.constant.clone())
+                    .collect();
+                if found.is_empty() {
+                    warn!("No dispatcher branch leads to entry point pc {:#x}", pc);
+                }
+                prefixes.extend(found);
+            }
+        }
+    }
+    prefixes
+}
+
+/// Pad a prefix to be used as a function hash
+pub fn prefix_to_function(prefix: &Vec<u8>) -> [u8; 4] {
+    let mut function = [0u8; 4];
+    function[..prefix.len()].copy_from_slice(prefix);
+    function
+}
+
+mod tests {
+    use super::*;
+
+    // PUSH1 0x00 CALLDATALOAD PUSH1 0xf8 SHR
+    // DUP1 PUSH1 0x01 EQ PUSH1 0x15 JUMPI
+    // DUP1 PUSH1 0x02 EQ PUSH1 0x17 JUMPI
+    // STOP JUMPDEST STOP JUMPDEST STOP
+    const ROUTER: &str = "60003560f81c8060011460155780600214601757005b005b00";
+
+    #[test]
+    fn test_detect_prefixes() {
+        let code = hex::decode(ROUTER).unwrap();
+        assert!(!has_standard_dispatcher(&code));
+        assert_eq!(detect_prefixes(&code), vec![vec![0x01], vec![0x02]]);
+    }
+
+    #[test]
+    fn test_resolve_pc() {
+        let code = hex::decode(ROUTER).unwrap();
+        let entry_points = vec![EntryPoint::Pc(0x17), EntryPoint::Prefix("0xaabb".to_string())];
+        assert_eq!(
+            resolve_prefixes(&code, &entry_points),
+            vec![vec![0x02], vec![0xaa, 0xbb]]
+        );
+    }
+}
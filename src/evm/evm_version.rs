@@ -0,0 +1,123 @@
+/// EVM version (hardfork) the targets are executed under
+///
+/// Pre-merge contracts rely on DIFFICULTY semantics, post-Cancun contracts use MCOPY, so the
+/// version is pinned for the campaign: revm runs the matching spec and the GPU kernel the
+/// matching opcode table. PTX kernels are translated for a version, embedded in their header when
+/// signed (`--sign-ptx --evm-version`, see [`crate::evm::ptx_binding`]), and rejected when it
+/// does not match.
+use std::str::FromStr;
+
+/// Prefix of the PTX comment holding the EVM version of the kernel
+pub const PTX_EVM_VERSION_PREFIX: &str = "// mau evm version: ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EVMVersion {
+    London,
+    Shanghai,
+    Cancun,
+    /// Latest spec supported by revm (default)
+    Latest,
+}
+
+impl FromStr for EVMVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "london" => Ok(EVMVersion::London),
+            "shanghai" => Ok(EVMVersion::Shanghai),
+            "cancun" => Ok(EVMVersion::Cancun),
+            "latest" => Ok(EVMVersion::Latest),
+            _ => Err(format!("Unknown EVM version: {}", s)),
+        }
+    }
+}
+
+impl EVMVersion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EVMVersion::London => "london",
+            EVMVersion::Shanghai => "shanghai",
+            EVMVersion::Cancun => "cancun",
+            EVMVersion::Latest => "latest",
+        }
+    }
+
+    /// Id of the opcode table of the GPU kernel
+    pub fn gpu_id(&self) -> u8 {
+        match self {
+            EVMVersion::London => 12,
+            EVMVersion::Shanghai => 16,
+            EVMVersion::Cancun => 17,
+            EVMVersion::Latest => 18,
+        }
+    }
+}
+
+pub static mut EVM_VERSION: EVMVersion = EVMVersion::Latest;
+
+/// Run the interpreter under the spec of [`EVM_VERSION`]
+#[macro_export]
+macro_rules! run_inspect_with_version {
+    ($interp: expr, $S: ty, $H: ty, $host: expr, $state: expr) => {
+        match unsafe { $crate::evm::evm_version::EVM_VERSION } {
+            $crate::evm::evm_version::EVMVersion::London => {
+                $interp.run_inspect::<$S, $H, revm_primitives::LondonSpec>($host, $state)
+            }
+            $crate::evm::evm_version::EVMVersion::Shanghai => {
+                $interp.run_inspect::<$S, $H, revm_primitives::ShanghaiSpec>($host, $state)
+            }
+            $crate::evm::evm_version::EVMVersion::Cancun => {
+                $interp.run_inspect::<$S, $H, revm_primitives::CancunSpec>($host, $state)
+            }
+            $crate::evm::evm_version::EVMVersion::Latest => {
+                $interp.run_inspect::<$S, $H, revm_primitives::LatestSpec>($host, $state)
+            }
+        }
+    };
+}
+
+/// PTX comment declaring the EVM version of the kernel
+pub fn evm_version_header(version: EVMVersion) -> String {
+    format!("{}{}", PTX_EVM_VERSION_PREFIX, version.name())
+}
+
+/// EVM version declared in the PTX source, `None` if the kernel has no such header
+pub fn parse_ptx_version(ptx: &str) -> Option<Result<EVMVersion, String>> {
+    ptx.lines()
+        .find_map(|line| line.strip_prefix(PTX_EVM_VERSION_PREFIX))
+        .map(|name| EVMVersion::from_str(name.trim()))
+}
+
+/// Check that the PTX kernel is translated for the EVM version
+pub fn check_ptx_version(ptx: &str, version: EVMVersion) -> Result<(), String> {
+    match parse_ptx_version(ptx) {
+        Some(Ok(declared)) if declared == version => Ok(()),
+        Some(Ok(declared)) => Err(format!(
+            "PTX kernel is translated for EVM version {}, not {}",
+            declared.name(),
+            version.name()
+        )),
+        Some(Err(e)) => Err(e),
+        None => Err(format!(
+            "PTX kernel declares no EVM version, sign it with --sign-ptx <bin> --evm-version {}",
+            version.name()
+        )),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evm_version() {
+        assert_eq!(EVMVersion::from_str("Cancun"), Ok(EVMVersion::Cancun));
+        assert_eq!(EVMVersion::from_str("london").unwrap().name(), "london");
+        assert!(EVMVersion::from_str("paris").is_err());
+
+        let ptx = format!("{}\n.version 8.0\n", evm_version_header(EVMVersion::Cancun));
+        assert!(check_ptx_version(&ptx, EVMVersion::Cancun).is_ok());
+        assert!(check_ptx_version(&ptx, EVMVersion::London).is_err());
+        assert!(check_ptx_version(".version 8.0\n", EVMVersion::Latest).is_err());
+    }
+}
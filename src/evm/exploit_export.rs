@@ -0,0 +1,369 @@
+/// Export of a solution as an exploit scenario for a fork (`cli export-exploit`)
+///
+/// A solution is only written once an oracle confirmed it on revm, but replaying it needs the
+/// fuzzer. `cli export-exploit <solution> -o <dir>` turns its replayable sequence into files that
+/// run against a fork of the chain with the tools users already have:
+/// - `--format anvil`: `anvil.json`, a JSON-RPC batch, and `anvil.sh`, the same requests sent
+///   one by one with `cast rpc`. The senders are impersonated and funded, the blocks are mined
+///   as the sequence advances them (`anvil_mine`), then the transactions are sent in order. A
+///   transaction warped to a block sets the timestamp of its block (`evm_setNextBlockTimestamp`)
+///   and, when the block of the fork is given, mines up to it.
+/// - `--format tenderly`: `tenderly.json`, the payload of a Tenderly bundle simulation, the
+///   senders funded through the state overrides of the first simulation. The block advances
+///   become block overrides when the block of the fork is given (`--fork-block`,
+///   `--fork-timestamp`), the transactions warped to a block override it in any case.
+///
+/// What only exists in the model of the fuzzer is not exported and is listed in the report: the
+/// flashloans of its borrow transactions, the prices of its mock feeds, the ether it force-feeds
+/// and the transactions resuming a control leak.
+use crate::evm::block_advance::BlockAdvance;
+use crate::evm::config::DEFAULT_BLOCK_GAS_LIMIT;
+use crate::evm::corpus_format::{parse_corpus_file, CorpusTxn, TxnKind};
+use crate::evm::types::{EVMAddress, EVMU256};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Anvil,
+    Tenderly,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anvil" => Ok(ExportFormat::Anvil),
+            "tenderly" => Ok(ExportFormat::Tenderly),
+            _ => Err(format!("unknown export format {} (anvil, tenderly)", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Network of the Tenderly simulations
+    pub network_id: String,
+    /// Balance each sender is funded with, on top of the value it sends
+    pub balance: EVMU256,
+    /// Block number and timestamp of the fork, for the block overrides of Tenderly
+    pub fork_block: Option<u64>,
+    pub fork_timestamp: Option<u64>,
+}
+
+/// A solution file, its oracle output and its sequence
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub oracle: String,
+    pub txns: Vec<CorpusTxn>,
+}
+
+/// Parse a solution written by the fuzzer (its `Replayable:` section) or a corpus file
+pub fn parse_scenario(content: &str) -> Result<Scenario, String> {
+    let oracle = content
+        .lines()
+        .find_map(|line| line.strip_prefix("Oracle: "))
+        .unwrap_or_default()
+        .to_string();
+    let replayable = match content.split_once("Replayable:\n") {
+        Some((_, rest)) => rest.split("\nProvenance:").next().unwrap_or_default(),
+        None => content,
+    };
+    let (_, file) = parse_corpus_file(replayable)?;
+    Ok(Scenario { oracle, txns: file.txns() })
+}
+
+fn hex_u64(n: u64) -> String {
+    format!("{:#x}", n)
+}
+
+fn hex_u256(n: EVMU256) -> String {
+    format!("{:#x}", n)
+}
+
+/// Calldata of a transaction, `00` standing for none in the corpus files
+fn calldata(txn: &CorpusTxn) -> String {
+    match txn.data.as_str() {
+        "" | "00" => "0x".to_string(),
+        data => format!("0x{}", data),
+    }
+}
+
+/// Block number and timestamp a transaction is warped to, as the fuzzer runs it
+fn warp(txn: &CorpusTxn) -> Option<(u64, u64)> {
+    (txn.warp_to > 0).then(|| (txn.warp_to, txn.warp_to * 1000))
+}
+
+/// Senders in the order of their first transaction, with the value they send in total
+fn senders(txns: &Vec<&CorpusTxn>) -> Vec<(EVMAddress, EVMU256)> {
+    let mut senders: Vec<(EVMAddress, EVMU256)> = vec![];
+    for txn in txns {
+        let sent = txn.value.saturating_mul(EVMU256::from(txn.repeat.max(1)));
+        match senders.iter_mut().find(|(sender, _)| *sender == txn.caller) {
+            Some((_, total)) => *total = total.saturating_add(sent),
+            None => senders.push((txn.caller, sent)),
+        }
+    }
+    senders
+}
+
+/// Transactions that can run on a fork, and the notes on the ones or the parts that cannot
+pub fn exportable(scenario: &Scenario) -> (Vec<&CorpusTxn>, Vec<String>) {
+    let mut txns = vec![];
+    let mut notes = vec![];
+    for (idx, txn) in scenario.txns.iter().enumerate() {
+        if txn.kind == TxnKind::Borrow {
+            notes.push(format!("transaction {}: flashloan borrow of {:?} skipped, fund the sender instead", idx, txn.contract));
+            continue;
+        }
+        if txn.step {
+            notes.push(format!("transaction {}: resumes a control leak of the fuzzer, skipped", idx));
+            continue;
+        }
+        if !txn.feed_prices.is_empty() {
+            notes.push(format!("transaction {}: the prices of the mock feeds are not set on the fork", idx));
+        }
+        if let Some(value) = txn.force_feed {
+            notes.push(format!("transaction {}: {} wei force-fed to {:?} before it, not exported", idx, value, txn.contract));
+        }
+        txns.push(txn);
+    }
+    (txns, notes)
+}
+
+fn rpc(method: &str, params: Value) -> Value {
+    json!({"method": method, "params": params})
+}
+
+/// JSON-RPC requests replaying the sequence on an anvil fork
+pub fn anvil_requests(txns: &Vec<&CorpusTxn>, options: &ExportOptions) -> Vec<Value> {
+    let senders = senders(txns);
+    let mut requests = vec![];
+    for (sender, sent) in &senders {
+        requests.push(rpc("anvil_impersonateAccount", json!([format!("{:?}", sender)])));
+        requests.push(rpc(
+            "anvil_setBalance",
+            json!([format!("{:?}", sender), hex_u256(options.balance.saturating_add(*sent))]),
+        ));
+    }
+    // block of the fork last mined, each transaction being mined in its own block
+    let mut block = options.fork_block;
+    for txn in txns {
+        let (blocks, seconds) = txn.block_advance.map_or((0, 0), |advance| advance.delta());
+        if blocks > 0 {
+            requests.push(rpc("anvil_mine", json!([hex_u64(blocks), hex_u64(seconds / blocks)])));
+            block = block.map(|block| block + blocks);
+        }
+        if let Some((number, timestamp)) = warp(txn) {
+            if let Some(last) = block.filter(|last| number > last + 1) {
+                requests.push(rpc("anvil_mine", json!([hex_u64(number - last - 1)])));
+                block = Some(number - 1);
+            }
+            requests.push(rpc("evm_setNextBlockTimestamp", json!([hex_u64(timestamp)])));
+        }
+        let mut tx = json!({
+            "from": format!("{:?}", txn.caller),
+            "to": format!("{:?}", txn.contract),
+            "data": calldata(txn),
+            "value": hex_u256(txn.value),
+        });
+        if let Some(limit) = txn.gas_limit {
+            tx["gas"] = json!(hex_u64(limit));
+        }
+        for _ in 0..txn.repeat.max(1) {
+            requests.push(rpc("eth_sendTransaction", json!([tx.clone()])));
+        }
+        block = block.map(|block| block + txn.repeat.max(1) as u64);
+    }
+    for (sender, _) in &senders {
+        requests.push(rpc("anvil_stopImpersonatingAccount", json!([format!("{:?}", sender)])));
+    }
+    requests
+        .into_iter()
+        .enumerate()
+        .map(|(id, mut request)| {
+            request["jsonrpc"] = json!("2.0");
+            request["id"] = json!(id + 1);
+            request
+        })
+        .collect()
+}
+
+/// Shell script sending the requests one by one with `cast rpc`
+pub fn anvil_script(scenario: &Scenario, requests: &Vec<Value>) -> String {
+    let mut script = String::from("#!/usr/bin/env bash\n");
+    if !scenario.oracle.is_empty() {
+        script.push_str(&format!("# {}\n", scenario.oracle));
+    }
+    script.push_str("# Run against a fork: anvil --fork-url <rpc> [--fork-block-number <block>]\n");
+    script.push_str("set -euo pipefail\nRPC=\"${RPC:-http://127.0.0.1:8545}\"\n\n");
+    for request in requests {
+        script.push_str(&format!(
+            "cast rpc --rpc-url \"$RPC\" --raw {} '{}'\n",
+            request["method"].as_str().unwrap_or_default(),
+            request["params"]
+        ));
+    }
+    script
+}
+
+/// Payload of a Tenderly bundle simulation of the sequence
+pub fn tenderly_payload(txns: &Vec<&CorpusTxn>, options: &ExportOptions) -> Value {
+    let mut state_objects = json!({});
+    for (sender, sent) in senders(txns) {
+        state_objects[format!("{:?}", sender)] = json!({"balance": options.balance.saturating_add(sent).to_string()});
+    }
+    let (mut blocks, mut seconds) = (0, 0);
+    let mut simulations = vec![];
+    for txn in txns {
+        let (advance_blocks, advance_seconds) = txn.block_advance.map_or((0, 0), |advance: BlockAdvance| advance.delta());
+        blocks += advance_blocks;
+        seconds += advance_seconds;
+        for _ in 0..txn.repeat.max(1) {
+            let mut simulation = json!({
+                "network_id": options.network_id,
+                "from": format!("{:?}", txn.caller),
+                "to": format!("{:?}", txn.contract),
+                "input": calldata(txn),
+                "value": txn.value.to_string(),
+                "gas": txn.gas_limit.unwrap_or(DEFAULT_BLOCK_GAS_LIMIT),
+                "gas_price": "0",
+                "save": true,
+                "save_if_fails": true,
+                "simulation_type": "full",
+            });
+            if simulations.is_empty() {
+                simulation["state_objects"] = state_objects.clone();
+            }
+            if let Some((number, timestamp)) = warp(txn) {
+                simulation["block_number"] = json!(number);
+                simulation["block_header"] = json!({"number": hex_u64(number), "timestamp": hex_u64(timestamp)});
+            } else if let Some(fork_block) = options.fork_block {
+                simulation["block_number"] = json!(fork_block + blocks);
+                let mut header = json!({"number": hex_u64(fork_block + blocks)});
+                if let Some(fork_timestamp) = options.fork_timestamp {
+                    header["timestamp"] = json!(hex_u64(fork_timestamp + seconds));
+                }
+                simulation["block_header"] = header;
+            }
+            simulations.push(simulation);
+        }
+    }
+    json!({ "simulations": simulations })
+}
+
+/// Export the solution to the output directory, returns the report
+pub fn export_exploit(solution: &str, output: &str, options: &ExportOptions) -> Result<String, String> {
+    let content = fs::read_to_string(solution).map_err(|e| format!("failed to read {}: {}", solution, e))?;
+    let scenario = parse_scenario(&content).map_err(|e| format!("{}: {}", solution, e))?;
+    let (txns, mut notes) = exportable(&scenario);
+    if txns.is_empty() {
+        return Err(format!("{}: no transaction can run on a fork", solution));
+    }
+    if options.format == ExportFormat::Tenderly
+        && options.fork_block.is_none()
+        && txns.iter().any(|txn| txn.block_advance.map_or(false, |advance| advance != BlockAdvance::Same))
+    {
+        notes.push("the block advances are dropped without --fork-block".to_string());
+    }
+    fs::create_dir_all(output).map_err(|e| format!("failed to create {}: {}", output, e))?;
+    let write = |name: &str, data: String| -> Result<String, String> {
+        let path = Path::new(output).join(name);
+        fs::write(&path, data).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    };
+    let files = match options.format {
+        ExportFormat::Anvil => {
+            let requests = anvil_requests(&txns, options);
+            vec![
+                write("anvil.json", serde_json::to_string_pretty(&requests).unwrap())?,
+                write("anvil.sh", anvil_script(&scenario, &requests))?,
+            ]
+        }
+        ExportFormat::Tenderly => vec![write(
+            "tenderly.json",
+            serde_json::to_string_pretty(&tenderly_payload(&txns, options)).unwrap(),
+        )?],
+    };
+    let mut report = format!(
+        "Exported {} of {} transactions ({} senders) to {}\n",
+        txns.len(),
+        scenario.txns.len(),
+        senders(&txns).len(),
+        files.join(", ")
+    );
+    for note in notes {
+        report.push_str(&format!("- {}\n", note));
+    }
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_exploit() {
+        let solution = "Oracle: [bug] bug() hit at contract 0x0\nTrace: ...\nReplayable:\n\
+            abi 0x1111111111111111111111111111111111111111 0x3333333333333333333333333333333333333333 d0e30db0 5 0 0 1 0 false\n\
+            borrow 0x1111111111111111111111111111111111111111 0x4444444444444444444444444444444444444444 00 0 0 0\n\
+            abi 0x2222222222222222222222222222222222222222 0x3333333333333333333333333333333333333333 3ccfd60b 0 0 0 2 0 false - - day 80000\n\
+            abi 0x1111111111111111111111111111111111111111 0x3333333333333333333333333333333333333333 00 0 0 9000 1 0 false\n\
+            \nProvenance:\n";
+        let scenario = parse_scenario(solution).unwrap();
+        assert_eq!(scenario.oracle, "[bug] bug() hit at contract 0x0");
+        let (txns, notes) = exportable(&scenario);
+        assert_eq!(txns.len(), 3);
+        assert_eq!(notes.len(), 1);
+
+        let options = ExportOptions {
+            format: ExportFormat::Anvil,
+            network_id: "1".to_string(),
+            balance: EVMU256::from(100),
+            fork_block: Some(1000),
+            fork_timestamp: Some(5000),
+        };
+        let requests = anvil_requests(&txns, &options);
+        let methods = requests.iter().map(|r| r["method"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            methods,
+            vec![
+                "anvil_impersonateAccount",
+                "anvil_setBalance",
+                "anvil_impersonateAccount",
+                "anvil_setBalance",
+                "eth_sendTransaction",
+                "anvil_mine",
+                "eth_sendTransaction",
+                "eth_sendTransaction",
+                "anvil_mine",
+                "evm_setNextBlockTimestamp",
+                "eth_sendTransaction",
+                "anvil_stopImpersonatingAccount",
+                "anvil_stopImpersonatingAccount",
+            ]
+        );
+        // funded with the value it sends
+        assert_eq!(requests[1]["params"][1], "0x69");
+        assert_eq!(requests[5]["params"], json!(["0x1c20", "0xc"]));
+        assert_eq!(requests[6]["params"][0]["gas"], "0x13880");
+        // warped to block 9000: mined up to it from block 8203 of the fork, without calldata
+        assert_eq!(requests[8]["params"], json!(["0x31c"]));
+        assert_eq!(requests[9]["params"], json!([hex_u64(9000 * 1000)]));
+        assert_eq!(requests[10]["params"][0]["data"], "0x");
+        assert!(anvil_script(&scenario, &requests).contains("cast rpc --rpc-url \"$RPC\" --raw anvil_mine '[\"0x1c20\",\"0xc\"]'"));
+
+        let payload = tenderly_payload(&txns, &options);
+        let simulations = payload["simulations"].as_array().unwrap();
+        assert_eq!(simulations.len(), 4);
+        assert_eq!(simulations[0]["block_number"], 1000);
+        assert_eq!(simulations[2]["block_header"]["timestamp"], hex_u64(5000 + 86400));
+        assert!(simulations[1].get("state_objects").is_none());
+        assert_eq!(simulations[3]["block_number"], 9000);
+        assert_eq!(simulations[3]["block_header"]["timestamp"], hex_u64(9000 * 1000));
+        assert_eq!(simulations[3]["input"], "0x");
+    }
+}
@@ -0,0 +1,225 @@
+/// Flashloan providers (`--flashloan-provider <provider>[:<lender>]`)
+///
+/// A borrow input models ether borrowed from nowhere and owed back as is, while a real attack
+/// borrows from a provider, which decides who calls back whom and how much is repaid. The
+/// providers compiled in are:
+/// - `aave-v3`: the pool calls `executeOperation` on the receiver, which must return `true` and
+///   approve the pool to pull the amount plus a 0.05% premium
+/// - `balancer`: the vault calls `receiveFlashLoan` on the recipient, which transfers the amount
+///   back (no fee) before returning
+/// - `erc3156`: the lender calls `onFlashLoan` on the receiver, which must return the
+///   `ERC3156FlashBorrower.onFlashLoan` hash and approve the lender to pull the amount plus the
+///   fee of the lender (in basis points, `erc3156:<lender>:<fee bps>`)
+/// - `uniswap-v2`: the pair calls `uniswapV2Call` on the recipient of the flash swap, which
+///   transfers back the amount plus the 0.3% swap fee before returning
+///
+/// With a provider, the ether of the borrow inputs is borrowed from it, so the fee of the
+/// provider is owed along with the amount. The functions of the targets that are the callback
+/// of the provider are flashloan receivers, and their seeds are called by the lender, as the
+/// provider would.
+use crate::evm::contract_utils::set_hash;
+use crate::evm::types::{EVMAddress, EVMU256};
+use std::fmt::Debug;
+use std::str::FromStr;
+
+/// Aave v3 pool on mainnet
+pub const AAVE_V3_POOL: &str = "87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2";
+/// Balancer vault, at the same address on all chains
+pub const BALANCER_VAULT: &str = "BA12222222228d8Ba445958a75a0704d566BF2C8";
+
+/// Provider of the flashloans, set with `--flashloan-provider`
+pub static mut FLASHLOAN_PROVIDER: Option<Box<dyn FlashloanProvider>> = None;
+
+/// Callback and repayment rules of a flashloan provider
+pub trait FlashloanProvider: Debug {
+    fn name(&self) -> &'static str;
+
+    /// Address lending the tokens and calling back the receiver
+    fn lender(&self) -> EVMAddress;
+
+    /// Signature of the callback the lender calls on the receiver
+    fn callback(&self) -> &'static str;
+
+    /// Fee owed on top of the borrowed amount
+    fn fee(&self, amount: EVMU256) -> EVMU256;
+
+    fn callback_selector(&self) -> [u8; 4] {
+        let mut selector = [0u8; 4];
+        set_hash(self.callback(), &mut selector);
+        selector
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AaveV3 {
+    pub pool: EVMAddress,
+}
+
+impl FlashloanProvider for AaveV3 {
+    fn name(&self) -> &'static str {
+        "aave-v3"
+    }
+
+    fn lender(&self) -> EVMAddress {
+        self.pool
+    }
+
+    fn callback(&self) -> &'static str {
+        "executeOperation(address,uint256,uint256,address,bytes)"
+    }
+
+    /// `FLASHLOAN_PREMIUM_TOTAL` of 5 basis points, rounded half up
+    fn fee(&self, amount: EVMU256) -> EVMU256 {
+        (amount.saturating_mul(EVMU256::from(5)) + EVMU256::from(5000)) / EVMU256::from(10000)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Balancer {
+    pub vault: EVMAddress,
+}
+
+impl FlashloanProvider for Balancer {
+    fn name(&self) -> &'static str {
+        "balancer"
+    }
+
+    fn lender(&self) -> EVMAddress {
+        self.vault
+    }
+
+    fn callback(&self) -> &'static str {
+        "receiveFlashLoan(address[],uint256[],uint256[],bytes)"
+    }
+
+    fn fee(&self, _amount: EVMU256) -> EVMU256 {
+        EVMU256::ZERO
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Erc3156 {
+    pub lender: EVMAddress,
+    pub fee_bps: u64,
+}
+
+impl FlashloanProvider for Erc3156 {
+    fn name(&self) -> &'static str {
+        "erc3156"
+    }
+
+    fn lender(&self) -> EVMAddress {
+        self.lender
+    }
+
+    fn callback(&self) -> &'static str {
+        "onFlashLoan(address,address,uint256,uint256,bytes)"
+    }
+
+    fn fee(&self, amount: EVMU256) -> EVMU256 {
+        amount.saturating_mul(EVMU256::from(self.fee_bps)) / EVMU256::from(10000)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UniswapV2 {
+    pub pair: EVMAddress,
+}
+
+impl FlashloanProvider for UniswapV2 {
+    fn name(&self) -> &'static str {
+        "uniswap-v2"
+    }
+
+    fn lender(&self) -> EVMAddress {
+        self.pair
+    }
+
+    fn callback(&self) -> &'static str {
+        "uniswapV2Call(address,uint256,uint256,bytes)"
+    }
+
+    /// The pair checks its invariant on the balances net of the 0.3% fee, so `amount * 1000 / 997`
+    /// (rounded up) is repaid
+    fn fee(&self, amount: EVMU256) -> EVMU256 {
+        (amount.saturating_mul(EVMU256::from(3)) + EVMU256::from(996)) / EVMU256::from(997)
+    }
+}
+
+fn parse_address(address: Option<&str>, default: Option<&str>) -> Result<EVMAddress, String> {
+    let address = address
+        .or(default)
+        .ok_or("the provider needs the address of the lender".to_string())?;
+    EVMAddress::from_str(address.trim_start_matches("0x")).map_err(|e| format!("invalid lender {}: {}", address, e))
+}
+
+/// Parse `<provider>[:<lender>]` (`erc3156:<lender>[:<fee bps>]` for ERC-3156)
+pub fn parse_provider(spec: &str) -> Result<Box<dyn FlashloanProvider>, String> {
+    let mut parts = spec.trim().split(':');
+    let name = parts.next().unwrap_or_default();
+    let lender = parts.next();
+    match name {
+        "aave-v3" => Ok(Box::new(AaveV3 {
+            pool: parse_address(lender, Some(AAVE_V3_POOL))?,
+        })),
+        "balancer" => Ok(Box::new(Balancer {
+            vault: parse_address(lender, Some(BALANCER_VAULT))?,
+        })),
+        "erc3156" => Ok(Box::new(Erc3156 {
+            lender: parse_address(lender, None)?,
+            fee_bps: match parts.next() {
+                Some(fee) => fee.parse().map_err(|_| format!("invalid fee {}", fee))?,
+                None => 0,
+            },
+        })),
+        "uniswap-v2" => Ok(Box::new(UniswapV2 {
+            pair: parse_address(lender, None)?,
+        })),
+        other => Err(format!(
+            "unknown flashloan provider {} (aave-v3, balancer, erc3156, uniswap-v2)",
+            other
+        )),
+    }
+}
+
+pub fn set_flashloan_provider(provider: Option<Box<dyn FlashloanProvider>>) {
+    unsafe {
+        FLASHLOAN_PROVIDER = provider;
+    }
+}
+
+pub fn flashloan_provider() -> Option<&'static dyn FlashloanProvider> {
+    unsafe { FLASHLOAN_PROVIDER.as_deref() }
+}
+
+/// Lender calling a function of a target, when it is the callback of the provider
+pub fn callback_caller(selector: [u8; 4]) -> Option<EVMAddress> {
+    flashloan_provider()
+        .filter(|provider| provider.callback_selector() == selector)
+        .map(|provider| provider.lender())
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flashloan_provider() {
+        let aave = parse_provider("aave-v3").unwrap();
+        assert_eq!(aave.lender(), EVMAddress::from_str(AAVE_V3_POOL).unwrap());
+        assert_eq!(aave.callback_selector(), [0x1b, 0x11, 0xd0, 0xff]);
+        assert_eq!(aave.fee(EVMU256::from(10000)), EVMU256::from(5));
+
+        let pair = parse_provider("uniswap-v2:0x1111111111111111111111111111111111111111").unwrap();
+        assert_eq!(pair.callback_selector(), [0x10, 0xd1, 0xe8, 0x5c]);
+        // 997 borrowed, 1000 repaid
+        assert_eq!(pair.fee(EVMU256::from(997)), EVMU256::from(3));
+
+        let lender = parse_provider("erc3156:1111111111111111111111111111111111111111:9").unwrap();
+        assert_eq!(lender.callback_selector(), [0x23, 0xe3, 0x0c, 0x8b]);
+        assert_eq!(lender.fee(EVMU256::from(10000)), EVMU256::from(9));
+
+        assert_eq!(parse_provider("balancer").unwrap().fee(EVMU256::from(10000)), EVMU256::ZERO);
+        assert!(parse_provider("erc3156").is_err());
+        assert!(parse_provider("dydx").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+/// Force-fed ether (`--force-feed`)
+///
+/// The host answers `BALANCE` / `SELFBALANCE` with an infinite balance, so a contract assuming
+/// `address(this).balance == totalDeposits` can never be caught out. With `--force-feed`, the
+/// targets of the transactions get a modeled balance, kept in [`EVMState::balances`]: it is
+/// credited with the value of the calls they receive and debited with the value they send
+/// (a call sending more than the modeled balance fails with `OutOfFund`), and `SELFDESTRUCT`
+/// moves it to the beneficiary. Accounts outside of the ledger (callers, other contracts) keep
+/// an infinite balance.
+///
+/// The input carries ether force-fed to its target right before it is executed
+/// ([`EVMInput::force_feed`]), the way an attacker would, by self-destructing a contract with
+/// the target as beneficiary or by naming it as the coinbase of a block: no code of the target
+/// runs, its balance just grows. The mutator sets it with [`mutate_force_feed`], and the
+/// amount is recorded in the reproducer.
+use crate::evm::types::{EVMAddress, EVMU256};
+use libafl::prelude::{HasRand, Rand};
+use std::collections::HashMap;
+
+/// Whether balances are modeled, on with `--force-feed`
+pub static mut FORCE_FEED: bool = false;
+
+/// Probability (in percent) that a mutation changes the ether force-fed before the input
+pub const FORCE_FEED_MUTATION_PROBABILITY: u64 = 5;
+
+/// 1 ether in wei
+const ETHER: u64 = 1_000_000_000_000_000_000;
+
+/// Credit an account, registering it in the ledger
+pub fn credit(balances: &mut HashMap<EVMAddress, EVMU256>, address: EVMAddress, value: EVMU256) {
+    let balance = balances.entry(address).or_insert(EVMU256::ZERO);
+    *balance = balance.saturating_add(value);
+}
+
+/// Modeled balance, `None` for an account outside of the ledger
+pub fn balance_of(balances: &HashMap<EVMAddress, EVMU256>, address: &EVMAddress) -> Option<EVMU256> {
+    balances.get(address).cloned()
+}
+
+/// Move value between two accounts, returns `false` (and moves nothing) when the sender is in
+/// the ledger and cannot afford it
+pub fn transfer(balances: &mut HashMap<EVMAddress, EVMU256>, from: EVMAddress, to: EVMAddress, value: EVMU256) -> bool {
+    if value == EVMU256::ZERO {
+        return true;
+    }
+    if let Some(balance) = balances.get_mut(&from) {
+        if *balance < value {
+            return false;
+        }
+        *balance -= value;
+    }
+    if balances.contains_key(&to) {
+        credit(balances, to, value);
+    }
+    true
+}
+
+/// Move the whole balance of a self-destructed contract to the beneficiary
+pub fn self_destruct(balances: &mut HashMap<EVMAddress, EVMU256>, address: EVMAddress, beneficiary: EVMAddress) {
+    if let Some(balance) = balances.insert(address, EVMU256::ZERO) {
+        if beneficiary != address && balances.contains_key(&beneficiary) {
+            credit(balances, beneficiary, balance);
+        }
+    }
+}
+
+/// A dust amount (1 wei), a random amount of at most 100 ether, twice the current amount, or
+/// no force-feed at all
+pub fn mutate_force_feed<S: HasRand>(force_feed: Option<EVMU256>, state: &mut S) -> Option<EVMU256> {
+    match state.rand_mut().below(4) {
+        0 => None,
+        1 => Some(EVMU256::from(1)),
+        2 => Some(EVMU256::from(state.rand_mut().below(100 * ETHER) + 1)),
+        _ => Some(force_feed.unwrap_or(EVMU256::from(ETHER)).saturating_mul(EVMU256::from(2))),
+    }
+}
+
+/// Prefix of the pretty transaction of an input force-feeding ether to its target
+pub fn force_feed_prefix(force_feed: Option<EVMU256>) -> String {
+    match force_feed {
+        Some(value) => format!("(force-feed {} wei) ", value),
+        None => String::new(),
+    }
+}
+
+/// Ether force-fed by a pretty transaction, see [`force_feed_prefix`]
+pub fn parse_force_feed_prefix(pretty_txn: &str) -> Option<EVMU256> {
+    pretty_txn
+        .split("(force-feed ")
+        .nth(1)?
+        .split(" wei)")
+        .next()
+        .and_then(|value| EVMU256::from_str_radix(value, 10).ok())
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_force_feed() {
+        let (target, attacker, outsider) = (
+            EVMAddress::from_slice(&[0x11; 20]),
+            EVMAddress::from_slice(&[0x22; 20]),
+            EVMAddress::from_slice(&[0x33; 20]),
+        );
+        let mut balances = HashMap::new();
+        credit(&mut balances, target, EVMU256::from(10));
+        credit(&mut balances, attacker, EVMU256::ZERO);
+
+        assert!(!transfer(&mut balances, target, attacker, EVMU256::from(11)));
+        assert!(transfer(&mut balances, target, attacker, EVMU256::from(4)));
+        assert_eq!(balance_of(&balances, &attacker), Some(EVMU256::from(4)));
+        // outside of the ledger, the balance is infinite
+        assert!(transfer(&mut balances, outsider, target, EVMU256::from(100)));
+        assert_eq!(balance_of(&balances, &outsider), None);
+        self_destruct(&mut balances, target, attacker);
+        assert_eq!(balance_of(&balances, &target), Some(EVMU256::ZERO));
+        assert_eq!(balance_of(&balances, &attacker), Some(EVMU256::from(110)));
+
+        let pretty = format!("{}(prices 1) ABI with None ETH", force_feed_prefix(Some(EVMU256::from(7))));
+        assert_eq!(parse_force_feed_prefix(&pretty), Some(EVMU256::from(7)));
+        assert_eq!(parse_force_feed_prefix("ABI with None ETH"), None);
+    }
+}
@@ -0,0 +1,104 @@
+/// Custom harnesses fuzzed instead of (or along with) the targets given with `-t`
+///
+/// A campaign normally deploys each target and calls its functions directly. Some code is only
+/// meaningful behind a harness: a sequencing contract replaying the transactions it is given,
+/// a router wrapping the calls to the pools, a test contract setting up a protocol. A
+/// [`FuzzTarget`] provides the contracts to deploy (the harness bytecode and what it drives) and
+/// hooks around each input, e.g., to seed the storage of the harness with the input or to reset
+/// a part of the state afterwards. Targets are passed in
+/// [`crate::evm::config::Config`]`::targets` or registered with
+/// [`crate::evm::host::FuzzHost::add_fuzz_target`].
+use crate::evm::contract_utils::ContractInfo;
+use crate::evm::types::EVMAddress;
+use crate::evm::vm::EVMState;
+use revm_primitives::Env;
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::rc::Rc;
+
+pub trait FuzzTarget {
+    /// Contracts deployed with the targets of the campaign
+    fn contracts(&self) -> Vec<ContractInfo>;
+
+    /// Before an input is executed, on the state and environment it is executed on
+    fn setup(&mut self, _caller: EVMAddress, _contract: EVMAddress, _data: &[u8], _state: &mut EVMState, _env: &mut Env) {}
+
+    /// After an input is executed, on the state it leaves
+    fn teardown(&mut self, _reverted: bool, _state: &mut EVMState) {}
+}
+
+/// Targets registered on a host
+#[derive(Default, Clone)]
+pub struct FuzzTargets {
+    targets: Vec<Rc<RefCell<dyn FuzzTarget>>>,
+}
+
+impl Debug for FuzzTargets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FuzzTargets({})", self.targets.len())
+    }
+}
+
+impl FuzzTargets {
+    pub fn add(&mut self, target: Rc<RefCell<dyn FuzzTarget>>) {
+        self.targets.push(target);
+    }
+
+    pub fn setup(&self, caller: EVMAddress, contract: EVMAddress, data: &[u8], state: &mut EVMState, env: &mut Env) {
+        for target in &self.targets {
+            target.deref().borrow_mut().setup(caller, contract, data, state, env);
+        }
+    }
+
+    /// In reverse order of the setups
+    pub fn teardown(&self, reverted: bool, state: &mut EVMState) {
+        for target in self.targets.iter().rev() {
+            target.deref().borrow_mut().teardown(reverted, state);
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::types::EVMU256;
+    use std::collections::HashMap;
+
+    /// Harness counting the inputs in its slot 0, cleared after the reverted ones
+    struct Sequencer {
+        address: EVMAddress,
+    }
+
+    impl FuzzTarget for Sequencer {
+        fn contracts(&self) -> Vec<ContractInfo> {
+            vec![]
+        }
+
+        fn setup(&mut self, _caller: EVMAddress, _contract: EVMAddress, _data: &[u8], state: &mut EVMState, _env: &mut Env) {
+            let mut storage = state.get(&self.address).cloned().unwrap_or_default();
+            *storage.entry(EVMU256::ZERO).or_default() += EVMU256::from(1);
+            state.insert(self.address, storage);
+        }
+
+        fn teardown(&mut self, reverted: bool, state: &mut EVMState) {
+            if reverted {
+                state.insert(self.address, HashMap::new());
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_targets() {
+        let address = EVMAddress::from_slice(&[0x11; 20]);
+        let mut targets = FuzzTargets::default();
+        targets.add(Rc::new(RefCell::new(Sequencer { address })));
+        let (mut state, mut env) = (EVMState::default(), Env::default());
+        for _ in 0..2 {
+            targets.setup(EVMAddress::zero(), address, &[], &mut state, &mut env);
+            targets.teardown(false, &mut state);
+        }
+        assert_eq!(state.get(&address).unwrap()[&EVMU256::ZERO], EVMU256::from(2));
+        targets.teardown(true, &mut state);
+        assert!(state.get(&address).unwrap().is_empty());
+    }
+}
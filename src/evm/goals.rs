@@ -0,0 +1,191 @@
+/// Attacker goals, "capture the flag" conditions (`--goals`)
+///
+/// Rather than looking for any bug, the fuzzer can be given the state an exploit must reach,
+/// declared in a JSON file:
+/// ```json
+/// [
+///   {"kind": "slot", "contract": "Vault", "slot": "0x5", "value": "attacker"},
+///   {"kind": "balance", "token": "0x6b175474e89094c44da98b954eedeac495271d0f", "increase": "1e18"}
+/// ]
+/// ```
+/// - `slot`: the storage slot of the contract (name or address) holds the value after a
+///   transaction, an integer or `attacker` for the address of the caller
+/// - `balance`: the `balanceOf` the caller in the token increases by at least `increase`
+///   (1 by default) during a transaction
+///
+/// A goal is reached when a transaction makes it hold, which [`crate::evm::oracles::goal`]
+/// reports as a solution. The fuzzer is also directed (see [`crate::evm::directed`]) toward the
+/// blocks writing the slots of the goals, i.e., the blocks pushing the slot as a constant and
+/// executing an `SSTORE`.
+use crate::evm::directed::{block_of, block_starts, build_cfg, distances};
+use crate::evm::entry_points::disassemble;
+use crate::evm::onchain::abi_decompiler::fetch_jump_edges_heimdall;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::view_invariants::parse_number;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+const SSTORE: u8 = 0x55;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GoalValue {
+    /// Address of the caller of the transaction
+    Attacker,
+    Value(EVMU256),
+}
+
+impl GoalValue {
+    pub fn matches(&self, value: EVMU256, attacker: &EVMAddress) -> bool {
+        match self {
+            GoalValue::Attacker => value == EVMU256::from_be_slice(&attacker.0),
+            GoalValue::Value(expected) => value == *expected,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// The slot of the contract holds the value
+    Slot {
+        contract: String,
+        slot: EVMU256,
+        value: GoalValue,
+    },
+    /// The balance of the caller in the token increases by at least `increase`
+    Balance { token: String, increase: EVMU256 },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum GoalConfig {
+    Slot {
+        contract: String,
+        slot: String,
+        value: String,
+    },
+    Balance {
+        token: String,
+        #[serde(default)]
+        increase: Option<String>,
+    },
+}
+
+fn parse_goal(config: GoalConfig) -> Result<Goal, String> {
+    match config {
+        GoalConfig::Slot { contract, slot, value } => Ok(Goal::Slot {
+            contract,
+            slot: parse_number(&slot)?,
+            value: match value.as_str() {
+                "attacker" => GoalValue::Attacker,
+                number => GoalValue::Value(parse_number(number)?),
+            },
+        }),
+        GoalConfig::Balance { token, increase } => Ok(Goal::Balance {
+            token,
+            increase: match increase {
+                Some(increase) => parse_number(&increase)?,
+                None => EVMU256::from(1),
+            },
+        }),
+    }
+}
+
+pub fn load_goals(path: &str) -> Vec<Goal> {
+    let mut data = String::new();
+    File::open(path)
+        .expect("failed to open goals file")
+        .read_to_string(&mut data)
+        .expect("failed to read goals file");
+    let configs: Vec<GoalConfig> = serde_json::from_str(&data).expect("failed to parse goals file");
+    configs
+        .into_iter()
+        .map(|c| parse_goal(c).unwrap_or_else(|e| panic!("invalid goal: {}", e)))
+        .collect()
+}
+
+impl Goal {
+    /// Contract (name or address) the goal is about
+    pub fn contract(&self) -> &String {
+        match self {
+            Goal::Slot { contract, .. } => contract,
+            Goal::Balance { token, .. } => token,
+        }
+    }
+
+    /// Whether the goal is about the contract `name` deployed at `address`
+    pub fn matches(&self, name: &str, address: &EVMAddress) -> bool {
+        let contract = self.contract();
+        contract == name || contract.to_lowercase() == format!("{:?}", address)
+    }
+}
+
+/// Blocks pushing the slot as a constant and executing an `SSTORE`
+pub fn slot_writing_blocks(code: &[u8], slot: EVMU256) -> HashSet<usize> {
+    let starts = block_starts(code);
+    let mut pushes = HashSet::new();
+    let mut writes = HashSet::new();
+    for (pc, op, imm) in disassemble(code) {
+        let block = block_of(&starts, pc);
+        match op {
+            PUSH1..=PUSH32 if EVMU256::try_from_be_slice(&imm) == Some(slot) => {
+                pushes.insert(block);
+            }
+            SSTORE => {
+                writes.insert(block);
+            }
+            _ => {}
+        }
+    }
+    pushes.intersection(&writes).cloned().collect()
+}
+
+/// Distances to the blocks writing the slots, `None` if no block writes them
+pub fn goal_distances(code: &[u8], slots: &Vec<EVMU256>) -> Option<HashMap<usize, u32>> {
+    let targets = slots
+        .iter()
+        .flat_map(|slot| slot_writing_blocks(code, *slot))
+        .collect::<HashSet<_>>();
+    if targets.is_empty() {
+        return None;
+    }
+    let jump_edges = fetch_jump_edges_heimdall(hex::encode(code))
+        .map(|(_, edges)| edges)
+        .unwrap_or_default();
+    Some(distances(&build_cfg(code, &jump_edges), &targets))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goals() {
+        let configs: Vec<GoalConfig> = serde_json::from_str(
+            r#"[{"kind": "slot", "contract": "Vault", "slot": "0x5", "value": "attacker"},
+                {"kind": "balance", "token": "Token"}]"#,
+        )
+        .unwrap();
+        let goals = configs.into_iter().map(|c| parse_goal(c).unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            goals[0],
+            Goal::Slot {
+                contract: "Vault".to_string(),
+                slot: EVMU256::from(5),
+                value: GoalValue::Attacker
+            }
+        );
+        assert_eq!(goals[1], Goal::Balance { token: "Token".to_string(), increase: EVMU256::from(1) });
+
+        let attacker = EVMAddress::from_slice(&[0x11; 20]);
+        assert!(GoalValue::Attacker.matches(EVMU256::from_be_slice(&[0x11; 20]), &attacker));
+        assert!(!GoalValue::Attacker.matches(EVMU256::from(1), &attacker));
+
+        // 0: CALLVALUE PUSH1 0x07 JUMPI STOP STOP STOP  7: JUMPDEST CALLER PUSH1 0x05 SSTORE STOP
+        let code = hex::decode("346007570000005b3360055500").unwrap();
+        assert_eq!(slot_writing_blocks(&code, EVMU256::from(5)), HashSet::from([7]));
+        assert!(slot_writing_blocks(&code, EVMU256::from(6)).is_empty());
+    }
+}
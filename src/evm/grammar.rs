@@ -0,0 +1,172 @@
+/// Calldata grammar shared with other fuzzers
+///
+/// The grammar is the inferred input structure of each function (given, fetched or decompiled
+/// ABIs), written as a JSON object mapping contract names to a Solidity JSON ABI, so that it can
+/// be fed as-is to Echidna / Medusa campaigns on the same target. Each function also carries its
+/// `selector`, since decompiled functions have no name to hash. The same format is accepted with
+/// `--grammar` to import the structural knowledge of another campaign.
+use crate::evm::contract_utils::{ABIConfig, ContractLoader};
+use crate::evm::hex_utils::Selector;
+use crate::evm::types::EVMAddress;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Name of the grammar file in the artifacts directory
+pub const GRAMMAR_FILE: &str = "grammar.json";
+
+/// Split the components of a tuple type, e.g., `(uint256,(address,bool)[])`
+pub fn split_components(ty: &str) -> Vec<String> {
+    let inner = &ty[1..ty.rfind(')').unwrap_or(ty.len())];
+    let mut components = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                components.push(inner[start..i].to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !inner[start..].is_empty() {
+        components.push(inner[start..].to_string());
+    }
+    components
+}
+
+/// JSON ABI of a parameter
+pub fn param_to_json(name: String, ty: &str) -> Value {
+    if ty.starts_with('(') {
+        let close = ty.rfind(')').unwrap();
+        let components = split_components(ty)
+            .iter()
+            .enumerate()
+            .map(|(i, c)| param_to_json(format!("field{}", i), c))
+            .collect::<Vec<_>>();
+        json!({
+            "name": name,
+            "type": format!("tuple{}", &ty[close + 1..]),
+            "components": components,
+        })
+    } else {
+        // args of unknown structure (e.g., decompiled `bytes`) are raw bytes to other fuzzers
+        let ty = if ty == "unknown" { "bytes" } else { ty };
+        json!({ "name": name, "type": ty })
+    }
+}
+
+/// JSON ABI of a function
+pub fn function_to_json(abi: &ABIConfig) -> Value {
+    let inputs = split_components(&abi.abi)
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| param_to_json(format!("arg{}", i), ty))
+        .collect::<Vec<_>>();
+    let state_mutability = if abi.is_static {
+        "view"
+    } else if abi.is_payable {
+        "payable"
+    } else {
+        "nonpayable"
+    };
+    json!({
+        "type": "function",
+        "name": abi.function_name,
+        "selector": format!("0x{}", hex::encode(abi.function)),
+        "inputs": inputs,
+        "outputs": [],
+        "stateMutability": state_mutability,
+    })
+}
+
+/// Write the grammar of the contracts (name, address, functions)
+pub fn export_grammar(path: &Path, contracts: &Vec<(String, EVMAddress, Vec<ABIConfig>)>) {
+    let mut grammar = Map::new();
+    for (name, address, abis) in contracts {
+        let functions = abis
+            .iter()
+            .filter(|abi| !abi.is_constructor)
+            .map(function_to_json)
+            .collect::<Vec<_>>();
+        grammar.insert(
+            name.clone(),
+            json!({ "address": format!("{:?}", address), "abi": functions }),
+        );
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("failed to create artifacts directory");
+    }
+    let mut file = File::create(path).expect("failed to create grammar file");
+    file.write_all(serde_json::to_string_pretty(&Value::Object(grammar)).unwrap().as_bytes())
+        .expect("failed to write grammar file");
+}
+
+/// Load a grammar, keyed by contract name and address
+pub fn load_grammar(path: &str) -> HashMap<String, Vec<ABIConfig>> {
+    let mut data = String::new();
+    File::open(path)
+        .expect("failed to open grammar file")
+        .read_to_string(&mut data)
+        .expect("failed to read grammar file");
+    let grammar: Map<String, Value> = serde_json::from_str(&data).expect("failed to parse grammar file");
+
+    let mut result = HashMap::new();
+    for (name, contract) in grammar {
+        // also accept a bare JSON ABI per contract
+        let functions = match contract.get("abi") {
+            Some(abi) => abi.as_array().cloned().unwrap_or_default(),
+            None => contract.as_array().cloned().unwrap_or_default(),
+        }
+        .into_iter()
+        .filter(|f| f["type"] == "function")
+        .map(|mut f| {
+            if f.get("stateMutability").is_none() {
+                f["stateMutability"] = json!("nonpayable");
+            }
+            f
+        })
+        .collect::<Vec<_>>();
+        let mut abis = ContractLoader::parse_abi_str(&Value::Array(functions.clone()).to_string());
+        // decompiled functions are named after their selector
+        for (abi, function) in abis.iter_mut().zip(functions.iter()) {
+            if let Some(Ok(selector)) = function["selector"].as_str().map(Selector::from_str) {
+                abi.function = selector.0;
+            }
+        }
+        if let Some(address) = contract["address"].as_str() {
+            result.insert(address.to_lowercase(), abis.clone());
+        }
+        result.insert(name, abis);
+    }
+    result
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_to_json() {
+        let abi = ABIConfig {
+            abi: "(uint256,(address,bool)[])".to_string(),
+            function: [0x12, 0x34, 0x56, 0x78],
+            function_name: "f".to_string(),
+            is_static: false,
+            is_payable: true,
+            is_constructor: false,
+        };
+        let json = function_to_json(&abi);
+        assert_eq!(json["selector"], "0x12345678");
+        assert_eq!(json["inputs"][0]["type"], "uint256");
+        assert_eq!(json["inputs"][1]["type"], "tuple[]");
+        assert_eq!(json["inputs"][1]["components"][1]["type"], "bool");
+        assert_eq!(json["stateMutability"], "payable");
+        assert_eq!(split_components("()"), Vec::<String>::new());
+    }
+}
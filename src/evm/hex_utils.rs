@@ -0,0 +1,186 @@
+/// Typed hex values of the artifacts: selectors, event topics, addresses and bytecode
+///
+/// Selectors, topics and bytecode come as hex strings from many places (decompiler output,
+/// signature database, `.bin` / `.address` files, combined JSON, RPC responses, the CLI) and used
+/// to be decoded and sliced in place, panicking on a short or malformed value. They are parsed
+/// here instead, with or without `0x` and surrounding whitespace, into [`Selector`],
+/// [`EventTopic`] and [`HexBytecode`], whose constructors check the size and return a
+/// [`HexError`] the caller can report or skip.
+use crate::evm::types::EVMAddress;
+use revm_primitives::B256;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HexError {
+    /// Not hex, or an odd number of digits
+    Invalid { value: String, msg: String },
+    /// Hex of the wrong size
+    Length { what: &'static str, expected: usize, got: usize },
+    /// Placeholder (`__$...$__`) of a library left in a bytecode
+    Unlinked,
+}
+
+impl Display for HexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexError::Invalid { value, msg } => write!(f, "invalid hex {}: {}", value, msg),
+            HexError::Length { what, expected, got } => {
+                write!(f, "{} of {} bytes, expected {}", what, got, expected)
+            }
+            HexError::Unlinked => write!(f, "unlinked library placeholder in the bytecode"),
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+/// Value for the error messages, the start of a long one
+fn excerpt(s: &str) -> String {
+    match s.char_indices().nth(24) {
+        Some((idx, _)) => format!("{}...", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+/// Bytes of a hex string, with or without `0x`
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, HexError> {
+    let s = s.trim();
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|e| HexError::Invalid {
+        value: excerpt(s),
+        msg: e.to_string(),
+    })
+}
+
+fn fixed<const N: usize>(bytes: &[u8], what: &'static str) -> Result<[u8; N], HexError> {
+    bytes.try_into().map_err(|_| HexError::Length {
+        what,
+        expected: N,
+        got: bytes.len(),
+    })
+}
+
+/// Address of a hex string
+pub fn parse_address(s: &str) -> Result<EVMAddress, HexError> {
+    Ok(EVMAddress::from(fixed::<20>(&parse_hex(s)?, "address")?))
+}
+
+/// Selector of a function or a custom error
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Selector(pub [u8; 4]);
+
+impl Selector {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, HexError> {
+        Ok(Self(fixed(bytes, "selector")?))
+    }
+
+    /// Selector of a call, `None` if the calldata is shorter
+    pub fn of_calldata(calldata: &[u8]) -> Option<Self> {
+        calldata.get(..4).map(|bytes| Self(bytes.try_into().unwrap()))
+    }
+}
+
+impl FromStr for Selector {
+    type Err = HexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_slice(&parse_hex(s)?)
+    }
+}
+
+/// In hex without `0x`, as in the signature database
+impl Display for Selector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+/// First topic of an event, the hash of its signature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventTopic(pub [u8; 32]);
+
+impl EventTopic {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, HexError> {
+        Ok(Self(fixed(bytes, "event topic")?))
+    }
+}
+
+impl From<B256> for EventTopic {
+    fn from(topic: B256) -> Self {
+        Self(topic.0)
+    }
+}
+
+impl FromStr for EventTopic {
+    type Err = HexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_slice(&parse_hex(s)?)
+    }
+}
+
+/// In hex without `0x`, as in the signature database
+impl Display for EventTopic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+/// Bytecode of an artifact, linked (no library placeholder left)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HexBytecode(Vec<u8>);
+
+impl HexBytecode {
+    pub fn from_hex(s: &str) -> Result<Self, HexError> {
+        if s.contains("__") {
+            return Err(HexError::Unlinked);
+        }
+        Ok(Self(parse_hex(s)?))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl FromStr for HexBytecode {
+    type Err = HexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_utils() {
+        let selector = Selector::from_str("0xa9059cbb").unwrap();
+        assert_eq!(selector.0, [0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(selector.to_string(), "a9059cbb");
+        assert_eq!(Selector::of_calldata(&[0xa9, 0x05]), None);
+        assert_eq!(
+            Selector::from_str("a9059c"),
+            Err(HexError::Length { what: "selector", expected: 4, got: 3 })
+        );
+        assert!(matches!(Selector::from_str("a9059cb"), Err(HexError::Invalid { .. })));
+
+        let topic = "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+        assert_eq!(EventTopic::from_str(topic).unwrap().to_string(), topic);
+        // a truncated topic is an error, not a panic
+        assert!(EventTopic::from_str(&topic[..60]).is_err());
+
+        assert_eq!(parse_address(" 0x1111111111111111111111111111111111111111\n"), Ok(EVMAddress::from_slice(&[0x11; 20])));
+        assert!(parse_address("0x12").is_err());
+        assert_eq!(HexBytecode::from_hex("0x6080\n").unwrap().as_slice(), &[0x60, 0x80]);
+        assert_eq!(
+            HexBytecode::from_hex("73__$3f2a$__6080"),
+            Err(HexError::Unlinked)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+/// Hooks observing the executions of the fuzzer
+///
+/// Custom analyses (gas golfing, MEV detection, ...) implement [`ExecutorHook`] and pass it in
+/// [`crate::evm::config::Config`]`::hooks`, or register it with
+/// [`crate::evm::host::FuzzHost::add_hook`]. Hooks only observe: they see the transactions
+/// executed by the fuzzer (not the static calls of the oracles) and cannot change their outcome.
+/// All methods default to doing nothing.
+use crate::evm::types::{EVMAddress, EVMU256};
+use revm_primitives::B256;
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::rc::Rc;
+
+pub trait ExecutorHook {
+    /// A transaction is about to be executed
+    fn on_tx_start(&mut self, _caller: EVMAddress, _contract: EVMAddress, _data: &[u8], _value: EVMU256) {}
+
+    /// A contract calls another one, `depth` being the number of call frames active
+    fn on_call(&mut self, _caller: EVMAddress, _callee: EVMAddress, _data: &[u8], _value: EVMU256, _depth: usize) {}
+
+    fn on_sstore(&mut self, _contract: EVMAddress, _slot: EVMU256, _value: EVMU256) {}
+
+    fn on_log(&mut self, _contract: EVMAddress, _topics: &[B256], _data: &[u8]) {}
+
+    /// The transaction is done
+    fn on_end(&mut self, _reverted: bool, _output: &[u8]) {}
+}
+
+/// Hooks registered on a host, notified during the transactions only
+#[derive(Default)]
+pub struct ExecutorHooks {
+    hooks: Vec<Rc<RefCell<dyn ExecutorHook>>>,
+    in_tx: bool,
+}
+
+impl Debug for ExecutorHooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExecutorHooks({})", self.hooks.len())
+    }
+}
+
+impl ExecutorHooks {
+    pub fn add(&mut self, hook: Rc<RefCell<dyn ExecutorHook>>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    fn notify(&self, mut f: impl FnMut(&mut dyn ExecutorHook)) {
+        if !self.in_tx {
+            return;
+        }
+        for hook in &self.hooks {
+            f(&mut *hook.deref().borrow_mut());
+        }
+    }
+
+    pub fn tx_start(&mut self, caller: EVMAddress, contract: EVMAddress, data: &[u8], value: EVMU256) {
+        self.in_tx = true;
+        self.notify(|hook| hook.on_tx_start(caller, contract, data, value));
+    }
+
+    pub fn call(&self, caller: EVMAddress, callee: EVMAddress, data: &[u8], value: EVMU256, depth: usize) {
+        self.notify(|hook| hook.on_call(caller, callee, data, value, depth));
+    }
+
+    pub fn sstore(&self, contract: EVMAddress, slot: EVMU256, value: EVMU256) {
+        self.notify(|hook| hook.on_sstore(contract, slot, value));
+    }
+
+    pub fn log(&self, contract: EVMAddress, topics: &[B256], data: &[u8]) {
+        self.notify(|hook| hook.on_log(contract, topics, data));
+    }
+
+    pub fn tx_end(&mut self, reverted: bool, output: &[u8]) {
+        self.notify(|hook| hook.on_end(reverted, output));
+        self.in_tx = false;
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter {
+        events: Vec<&'static str>,
+    }
+
+    impl ExecutorHook for Counter {
+        fn on_tx_start(&mut self, _caller: EVMAddress, _contract: EVMAddress, _data: &[u8], _value: EVMU256) {
+            self.events.push("start");
+        }
+
+        fn on_sstore(&mut self, _contract: EVMAddress, _slot: EVMU256, _value: EVMU256) {
+            self.events.push("sstore");
+        }
+
+        fn on_end(&mut self, _reverted: bool, _output: &[u8]) {
+            self.events.push("end");
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let counter = Rc::new(RefCell::new(Counter::default()));
+        let mut hooks = ExecutorHooks::default();
+        hooks.add(counter.clone());
+        let zero = EVMAddress::zero();
+        // outside of a transaction (static calls of the oracles)
+        hooks.sstore(zero, EVMU256::from(0), EVMU256::from(1));
+        hooks.tx_start(zero, zero, &[], EVMU256::from(0));
+        hooks.sstore(zero, EVMU256::from(0), EVMU256::from(1));
+        hooks.log(zero, &[], &[]);
+        hooks.tx_end(false, &[]);
+        hooks.sstore(zero, EVMU256::from(0), EVMU256::from(1));
+        assert_eq!(counter.borrow().events, vec!["start", "sstore", "end"]);
+    }
+}
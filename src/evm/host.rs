@@ -1,8 +1,20 @@
 use crate::evm::bytecode_analyzer;
+use crate::evm::dictionary::{record_cmp, record_preimage, MAX_PREIMAGE_SIZE};
+use crate::evm::branch_coverage::record_branch;
+use crate::evm::directed::record_directed_jump;
+use crate::evm::cfg_refinement::{record_observed_jump, REFINE_CFG};
+use crate::evm::opcode_stats::{record_cpu_step, OPCODE_STATS};
 use crate::evm::input::{EVMInput, EVMInputT, EVMInputTy};
+use crate::evm::fuzz_target::{FuzzTarget, FuzzTargets};
+use crate::evm::hooks::{ExecutorHook, ExecutorHooks};
 use crate::evm::middlewares::middleware::{CallMiddlewareReturn, Middleware, MiddlewareType};
 use crate::evm::mutator::AccessPattern;
 use crate::evm::onchain::flashloan::{Flashloan, FlashloanData};
+use crate::evm::amm::amm_call;
+use crate::evm::weird_tokens::weird_token_call;
+use crate::evm::price_feeds::price_feed_call;
+use crate::evm::force_feed::{balance_of, self_destruct, transfer, FORCE_FEED};
+use crate::evm::revert_stats::INSTRUCTIONS_EXECUTED;
 use bytes::Bytes;
 use itertools::Itertools;
 use libafl::prelude::{HasCorpus, Scheduler, HasRand};
@@ -30,11 +42,17 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use hex::FromHex;
 use revm_interpreter::{BytecodeLocked, CallContext, CallInputs, CallScheme, Contract, CreateInputs, Gas, Host, InstructionResult, Interpreter, SelfDestructResult};
 use revm_interpreter::analysis::to_analysed;
-use revm_primitives::{B256, Bytecode, Env, LatestSpec, Spec};
+use revm_primitives::{B256, Bytecode, Env, Spec};
+use crate::run_inspect_with_version;
 use crate::evm::types::{as_u64, EVMAddress, EVMU256, generate_random_address, is_zero};
 
 use crate::evm::uniswap::{generate_uniswap_router_call, TokenContext};
-use crate::evm::vm::EVMState;
+use crate::evm::vm::{EVMState, EVMStorage, StateCheckpoint};
+use crate::evm::call_graph::{enter_call, exit_call};
+use crate::evm::storage_alias::on_delegate_call;
+use crate::evm::storage_write::{check_storage_write, STORAGE_WRITE_CHECK};
+use crate::evm::withdrawal::{record_value_transfer, WITHDRAWAL_CHECK};
+use crate::evm::tx_gas::{charge, enter_gas_frame, exit_gas_frame, gas_left, metering, opcode_cost};
 use crate::generic_vm::vm_executor::{ExecutionResult, GenericVM, MAP_SIZE};
 use crate::generic_vm::vm_state::VMStateT;
 use crate::input::VMInputT;
@@ -49,6 +67,12 @@ pub static mut EXPLORED_EDGE: usize = 0;
 
 pub static mut JMP_MAP: [u8; MAP_SIZE] = [0; MAP_SIZE];
 
+/// Number of times each back-edge (i.e., loop iteration) is taken in the current execution
+pub static mut LOOP_MAP: [u8; MAP_SIZE] = [0; MAP_SIZE];
+/// Hit counts of back-edges in `JMP_MAP` saturate at this bound, so that inputs only adding
+/// iterations to the same loop are not considered new coverage. 0 disables the bound.
+pub static mut LOOP_BOUND: u8 = 2;
+
 // dataflow
 pub static mut READ_MAP: [bool; MAP_SIZE] = [false; MAP_SIZE];
 pub static mut WRITE_MAP: [u8; MAP_SIZE] = [0; MAP_SIZE];
@@ -62,6 +86,22 @@ pub static mut BRANCH_DISTANCE_CHANGED: bool = false;
 pub static mut ABI_MAX_SIZE: [usize; MAP_SIZE] = [0; MAP_SIZE];
 pub static mut STATE_CHANGE: bool = false;
 
+// sanitizers
+/// Contracts of the active call frames of the current execution
+pub static mut CALL_STACK: Vec<EVMAddress> = Vec::new();
+/// Contract writing its storage while re-entered during the current execution
+pub static mut REENTRANCY_HIT: Option<EVMAddress> = None;
+/// Contract self-destructed during the current execution
+pub static mut SELFDESTRUCT_HIT: Option<EVMAddress> = None;
+/// Maximum depth of the call frames, a call beyond it fails
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+/// Depth the transactions are executed at, raised to emulate a call-depth attack
+pub static mut CALL_DEPTH_OFFSET: usize = 0;
+/// Calls failed for exceeding [`CALL_DEPTH_LIMIT`] during the current execution
+pub static mut CALL_DEPTH_REFUSED: usize = 0;
+/// Whether a target called another contract during the current execution
+pub static mut EXTERNAL_CALL_MADE: bool = false;
+
 pub const RW_SKIPPER_PERCT_IDX: usize = 100;
 pub const RW_SKIPPER_AMT: usize = MAP_SIZE - RW_SKIPPER_PERCT_IDX;
 
@@ -121,6 +161,10 @@ where
     // for hash colision
     pub edge_hashmap: AHashMap<u64, usize>,
     pub ins_hashmap:  HashSet<u64>,
+
+    pub hooks: ExecutorHooks,
+    /// Harnesses set up and torn down around each input
+    pub fuzz_targets: FuzzTargets,
 }
 
 impl<VS, I, S> Debug for FuzzHost<VS, I, S>
@@ -184,6 +228,8 @@ where
             setcode_data:self.setcode_data.clone(),
             edge_hashmap: self.edge_hashmap.clone(),
             ins_hashmap: self.ins_hashmap.clone(),
+            hooks: ExecutorHooks::default(),
+            fuzz_targets: self.fuzz_targets.clone(),
         }
     }
 }
@@ -192,6 +238,24 @@ where
 // this return type is never used as we disabled gas
 pub(crate) const ControlLeak: InstructionResult = InstructionResult::FatalExternalError;
 pub static mut ACTIVE_MATCH_EXT_CALL: bool = false;
+
+/// Bump the hit count of an edge in `JMP_MAP`.
+/// Back-edges (jumping backward, i.e., a loop iteration) are also counted in `LOOP_MAP`
+/// and only contribute up to `LOOP_BOUND` hits to the coverage.
+#[inline]
+unsafe fn record_edge_hit(idx: usize, is_back_edge: bool) {
+    let bound = if is_back_edge {
+        if LOOP_MAP[idx] < 255 {
+            LOOP_MAP[idx] += 1;
+        }
+        if LOOP_BOUND == 0 { 255 } else { LOOP_BOUND }
+    } else {
+        255
+    };
+    if JMP_MAP[idx] < bound {
+        JMP_MAP[idx] += 1;
+    }
+}
 const CONTROL_LEAK_DETECTION: bool = true;
 const UNBOUND_CALL_THRESHOLD: usize = 3;
 
@@ -232,11 +296,25 @@ where
             setcode_data:HashMap::new(),
             edge_hashmap:AHashMap::new(),
             ins_hashmap: Default::default(),
+            hooks: ExecutorHooks::default(),
+            fuzz_targets: FuzzTargets::default(),
         };
         // ret.env.block.timestamp = EVMU256::max_value();
         ret
     }
 
+    /// Revert the state to the checkpoint taken at the beginning of a call that failed. A
+    /// control leak keeps the writes, the execution is resumed from them.
+    fn finish_call(&mut self, ret: InstructionResult, checkpoint: StateCheckpoint) {
+        let succeeded = matches!(
+            ret,
+            Continue | InstructionResult::Stop | Return | InstructionResult::SelfDestruct
+        );
+        if !succeeded && ret != ControlLeak {
+            self.evmstate.revert_to(checkpoint);
+        }
+    }
+
     pub fn remove_all_middlewares(&mut self) {
         self.middlewares_enabled = false;
         self.middlewares.deref().borrow_mut().clear();
@@ -251,6 +329,14 @@ where
             .insert(ty, middlewares);
     }
 
+    pub fn add_hook(&mut self, hook: Rc<RefCell<dyn ExecutorHook>>) {
+        self.hooks.add(hook);
+    }
+
+    pub fn add_fuzz_target(&mut self, target: Rc<RefCell<dyn FuzzTarget>>) {
+        self.fuzz_targets.add(target);
+    }
+
     pub fn add_flashloan_middleware(&mut self, middlware: Flashloan<VS, I, S>) {
         self.flashloan_middleware = Some(Rc::new(RefCell::new(middlware)));
     }
@@ -402,6 +488,11 @@ macro_rules! u256_to_u8 {
 
 pub static mut ARBITRARY_CALL: bool = false;
 
+/// Whether a call made with `frames` call frames active goes beyond [`CALL_DEPTH_LIMIT`]
+pub fn exceeds_call_depth(frames: usize) -> bool {
+    frames + unsafe { CALL_DEPTH_OFFSET } > CALL_DEPTH_LIMIT
+}
+
 impl<VS, I, S> Host<S> for FuzzHost<VS, I, S>
 where
     S: State +HasRand + HasCaller<EVMAddress> + Debug + Clone + HasCorpus<I> +  'static,
@@ -410,6 +501,7 @@ where
 {
     fn step(&mut self, interp: &mut Interpreter, state: &mut S) -> InstructionResult {
         unsafe {
+            INSTRUCTIONS_EXECUTED += 1;
             if self.middlewares_enabled {
                 match self.flashloan_middleware.clone() {
                     Some(m) => {
@@ -435,6 +527,26 @@ where
             if self.edge_hashmap.len() > MAP_SIZE {
                 println!("======================= Hash collison !!!! =======================");
             } 
+            if STORAGE_WRITE_CHECK {
+                check_storage_write(interp);
+            }
+            if metering() {
+                let op = *interp.instruction_pointer;
+                let storage = self.evmstate.get(&interp.contract.address);
+                let cost = opcode_cost(op, interp.stack.data(), |slot| {
+                    storage.and_then(|s| s.get(&slot)).map_or(true, |v| *v == EVMU256::ZERO)
+                });
+                if !charge(cost, interp.memory.len()) {
+                    return InstructionResult::OutOfGas;
+                }
+                // `GAS` and the calls read the metered gas left
+                if matches!(op, 0x5a | 0xf0 | 0xf1 | 0xf2 | 0xf4 | 0xf5 | 0xfa) {
+                    interp.gas = Gas::new(gas_left());
+                }
+            }
+            if OPCODE_STATS > 0 {
+                record_cpu_step(interp.contract.address, interp.program_counter(), *interp.instruction_pointer);
+            }
             macro_rules! fast_peek {
                 ($idx:expr) => {
                     interp.stack.data()[interp.stack.len() - 1 - $idx]
@@ -470,8 +582,10 @@ where
                     if JMP_MAP[idx] == 0 {
                         self.coverage_changed = true;
                     }
-                    if JMP_MAP[idx] < 255 {
-                        JMP_MAP[idx] += 1;
+                    record_edge_hit(idx, jump_dest <= interp.program_counter() as u64);
+                    record_directed_jump(&interp.contract.address, jump_dest as usize);
+                    if REFINE_CFG {
+                        record_observed_jump(&interp.contract.address, interp.program_counter(), jump_dest as usize);
                     }
                 }
                 0x57 => {
@@ -499,9 +613,12 @@ where
                     if JMP_MAP[idx] == 0 {
                         self.coverage_changed = true;
                     }
-                    if JMP_MAP[idx] < 255 {
-                        JMP_MAP[idx] += 1;
+                    record_edge_hit(idx, jump_dest <= interp.program_counter() as u64);
+                    record_directed_jump(&interp.contract.address, jump_dest as usize);
+                    if REFINE_CFG {
+                        record_observed_jump(&interp.contract.address, interp.program_counter(), jump_dest as usize);
                     }
+                    record_branch(&interp.contract.address, interp.program_counter(), !is_zero(br));
 
                     #[cfg(feature = "cmp")]
                     {
@@ -557,6 +674,7 @@ where
                     // LT, SLT
                     let v1 = fast_peek!(0);
                     let v2 = fast_peek!(1);
+                    record_cmp(v1, v2, false);
                     let abs_diff = if v1 >= v2 {
                         if v1 - v2 != EVMU256::ZERO {
                             v1 - v2
@@ -582,6 +700,7 @@ where
                     // GT, SGT
                     let v1 = fast_peek!(0);
                     let v2 = fast_peek!(1);
+                    record_cmp(v1, v2, false);
                     let abs_diff = if v1 <= v2 {
                         if v2 - v1 != EVMU256::ZERO {
                             v2 - v1
@@ -607,6 +726,7 @@ where
                     // EQ
                     let v1 = fast_peek!(0);
                     let v2 = fast_peek!(1);
+                    record_cmp(v1, v2, true);
                     let abs_diff = if v1 < v2 {
                         (v2 - v1) % (EVMU256::MAX - EVMU256::from(1)) + EVMU256::from(1)
                     } else {
@@ -624,6 +744,15 @@ where
                     }
                 }
 
+                0x20 => {
+                    // SHA3, the memory is already expanded if the preimage was written
+                    let offset = as_u64(fast_peek!(0)) as usize;
+                    let size = as_u64(fast_peek!(1)) as usize;
+                    if size <= MAX_PREIMAGE_SIZE && offset.saturating_add(size) <= interp.memory.len() {
+                        record_preimage(interp.memory.get_slice(offset, size));
+                    }
+                }
+
                 0xf1 | 0xf2 | 0xf4 | 0xfa => {
                     let offset_of_ret_size: usize = match *interp.instruction_pointer {
                         0xf1 | 0xf2 => 6,
@@ -671,9 +800,13 @@ where
         )
     }
 
-    fn balance(&mut self, _address: EVMAddress) -> Option<(EVMU256, bool)> {
+    fn balance(&mut self, address: EVMAddress) -> Option<(EVMU256, bool)> {
         // println!("balance");
-
+        if unsafe { FORCE_FEED } {
+            if let Some(balance) = balance_of(&self.evmstate.balances, &address) {
+                return Some((balance, true));
+            }
+        }
         Some((EVMU256::MAX, true))
     }
 
@@ -714,14 +847,18 @@ where
         index: EVMU256,
         value: EVMU256,
     ) -> Option<(EVMU256, EVMU256, EVMU256, bool)> {
+        unsafe {
+            if CALL_STACK.iter().filter(|a| **a == address).count() > 1 {
+                REENTRANCY_HIT = Some(address);
+            }
+        }
+        self.hooks.sstore(address, index, value);
         match self.evmstate.get_mut(&address) {
             Some(account) => {
                 account.insert(index, value);
             }
             None => {
-                let mut account = HashMap::new();
-                account.insert(index, value);
-                self.evmstate.insert(address, account);
+                self.evmstate.insert(address, EVMStorage::unit(index, value));
             }
         };
 
@@ -729,6 +866,7 @@ where
     }
 
     fn log(&mut self, _address: EVMAddress, _topics: Vec<B256>, _data: Bytes) {
+        self.hooks.log(_address, &_topics, &_data);
         if _topics.len() == 1 && (*_topics.last().unwrap()).0[31] == 0x37 {
             if unsafe {PANIC_ON_BUG} {
                 panic!("target hit");
@@ -759,6 +897,12 @@ where
     }
 
     fn selfdestruct(&mut self, _address: EVMAddress, _target: EVMAddress) -> Option<SelfDestructResult> {
+        unsafe {
+            SELFDESTRUCT_HIT = Some(_address);
+            if FORCE_FEED {
+                self_destruct(&mut self.evmstate.balances, _address, _target);
+            }
+        }
         return Some(SelfDestructResult::default());
     }
 
@@ -785,7 +929,7 @@ where
                 1e10 as u64,
                 false
             );
-            let ret = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(self, state);
+            let ret = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, self, state);
             if ret == InstructionResult::Continue {
                 self.set_code(
                     r_addr,
@@ -814,6 +958,30 @@ where
         if self.call_count >= unsafe {CALL_UNTIL} {
             return (ControlLeak, Gas::new(0), Bytes::new());
         }
+        // the writes of the call (and of the calls it makes) are undone if it fails
+        let checkpoint = self.evmstate.checkpoint();
+        unsafe {
+            EXTERNAL_CALL_MADE = true;
+            if exceeds_call_depth(CALL_STACK.len()) {
+                CALL_DEPTH_REFUSED += 1;
+                return (Revert, Gas::new(0), Bytes::new());
+            }
+            if FORCE_FEED
+                && !transfer(&mut self.evmstate.balances, input.transfer.source, input.transfer.target, input.transfer.value)
+            {
+                return (InstructionResult::OutOfFund, Gas::new(0), Bytes::new());
+            }
+            if WITHDRAWAL_CHECK {
+                record_value_transfer(input.transfer.source, input.transfer.target, input.transfer.value);
+            }
+        }
+        self.hooks.call(
+            input.context.caller,
+            input.contract,
+            &input.input,
+            input.context.apparent_value,
+            unsafe { CALL_STACK.len() },
+        );
 
         let mut hash = input.input.to_vec();
         hash.resize(4, 0);
@@ -851,6 +1019,27 @@ where
             return middleware_result.unwrap();
         }
 
+        // price feeds report the price of the input instead of being executed
+        if let Some(ret) = price_feed_call(input.contract, &input.input, self.env.block.timestamp) {
+            return (Continue, Gas::new(0), ret);
+        }
+
+        // simulated AMM pools are answered by their model
+        if let Some((res, ret)) = amm_call(input.contract, &input.input, &mut self.evmstate, self.env.block.timestamp) {
+            return (if res == Revert { Revert } else { Continue }, Gas::new(0), ret);
+        }
+
+        // simulated weird ERC-20 tokens are answered by their model
+        if let Some((res, ret)) = weird_token_call(
+            input.contract,
+            input.context.caller,
+            &input.input,
+            &mut self.evmstate,
+            self.env.block.timestamp,
+        ) {
+            return (if res == Revert { Revert } else { Continue }, Gas::new(0), ret);
+        }
+
         // if calling sender, then definitely control leak
         if self.origin == input.contract {
             record_func_hash!();
@@ -861,16 +1050,12 @@ where
         let mut input_seq = input.input.to_vec();
 
         // check whether the whole CALLDATAVALUE can be arbitrary
-        if !self.pc_to_call_hash.contains_key(&self._pc) {
-            self.pc_to_call_hash.insert(self._pc, HashSet::new());
-        }
-        self.pc_to_call_hash
-            .get_mut(&self._pc)
-            .unwrap()
-            .insert(hash.to_vec());
-        if self.pc_to_call_hash.get(&self._pc).unwrap().len() > UNBOUND_CALL_THRESHOLD
-            && input_seq.len() >= 4
-        {
+        let call_hashes = self.pc_to_call_hash.entry(self._pc).or_default();
+        // more hashes than the threshold are not needed to tell that the call is unbound
+        if call_hashes.len() <= UNBOUND_CALL_THRESHOLD {
+            call_hashes.insert(hash.to_vec());
+        }
+        if call_hashes.len() > UNBOUND_CALL_THRESHOLD && input_seq.len() >= 4 {
             unsafe {
                 ARBITRARY_CALL = true;
             }
@@ -909,12 +1094,14 @@ where
             old_call_context = GLOBAL_CALL_CONTEXT.clone();
             GLOBAL_CALL_CONTEXT = Some(input.context.clone());
         }
+        enter_call(input.context.caller, input.contract, &input.input);
 
         macro_rules! ret_back_ctx {
             () => {
                 unsafe {
                     GLOBAL_CALL_CONTEXT = old_call_context;
                 }
+                exit_call();
             };
         }
 
@@ -940,7 +1127,12 @@ where
                     false
                 );
 
-                let ret = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(self, state);
+                unsafe { CALL_STACK.push(input.context.address) };
+                enter_gas_frame(input.gas_limit, input.transfer.value != EVMU256::ZERO, input.context.address);
+                let ret = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, self, state);
+                exit_gas_frame(ret);
+                unsafe { CALL_STACK.pop() };
+                self.finish_call(ret, checkpoint);
                 ret_back_ctx!();
                 return (ret, Gas::new(0), interp.return_value());
             }
@@ -948,6 +1140,9 @@ where
 
         // if there is code, then call the code
         if let Some(code) = self.code.get(&input.context.code_address) {
+            if input.context.address != input.context.code_address {
+                on_delegate_call(input.context.address, input.context.code_address);
+            }
             let mut interp = Interpreter::new(
                 Contract::new_with_context_analyzed(
                     input_bytes.clone(),
@@ -957,7 +1152,12 @@ where
                 1e10 as u64,
                 false
             );
-            let ret = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(self, state);
+            unsafe { CALL_STACK.push(input.context.address) };
+            enter_gas_frame(input.gas_limit, input.transfer.value != EVMU256::ZERO, input.context.address);
+            let ret = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, self, state);
+            exit_gas_frame(ret);
+            unsafe { CALL_STACK.pop() };
+            self.finish_call(ret, checkpoint);
             ret_back_ctx!();
             return (ret, Gas::new(0), interp.return_value());
         }
@@ -968,6 +1168,7 @@ where
             return (Continue, Gas::new(0), Bytes::new());
         }
 
+        self.evmstate.revert_to(checkpoint);
         ret_back_ctx!();
         return (Revert, Gas::new(0), Bytes::new());
     }
@@ -0,0 +1,135 @@
+/// Support for reusing a translated PTX kernel across deployments that only differ in
+/// solc immutables.
+///
+/// Solc compiles immutables as `PUSH32 0x00..00` placeholders in the runtime code and fills
+/// them in the constructor. The PTX kernel is translated from the creation code, so it only
+/// knows about the placeholders. Instead of re-running ptxsema for each constructor argument
+/// variation, we detect the placeholders, patch their values on device at load time, and
+/// check the kernel against the bytecode hash embedded in its header (see
+/// [`crate::evm::ptx_binding`]) so that re-translation is only needed when the code itself changes.
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha3::Sha3;
+#[cfg(feature = "cuda")]
+use crate::runner_sys::cuPatchImmutable;
+
+/// An immutable value filled by the constructor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImmutableRef {
+    /// Offset of the 32 bytes value in the runtime code (right after the PUSH32)
+    pub offset: usize,
+    /// Value of the immutable, in the same (big endian) order as in the code
+    pub value: [u8; 32],
+}
+
+/// Find the runtime code template (with zeroed immutable placeholders) embedded in the
+/// creation code, given the runtime code returned by the constructor
+pub fn find_runtime_template<'a>(creation: &'a [u8], runtime: &[u8]) -> Option<&'a [u8]> {
+    if runtime.len() == 0 || creation.len() < runtime.len() {
+        return None;
+    }
+    for start in 0..=creation.len() - runtime.len() {
+        let candidate = &creation[start..start + runtime.len()];
+        if find_immutables(candidate, runtime).is_some() {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Compare the runtime code template with the deployed runtime code.
+///
+/// Returns None if they differ outside of immutable placeholders (i.e., the kernel needs to be
+/// re-translated), otherwise returns the immutables to patch.
+pub fn find_immutables(template: &[u8], runtime: &[u8]) -> Option<Vec<ImmutableRef>> {
+    if template.len() != runtime.len() {
+        return None;
+    }
+    let mut refs = vec![];
+    let mut idx = 0;
+    while idx < template.len() {
+        let op = template[idx];
+        if op != runtime[idx] {
+            return None;
+        }
+        match op {
+            // hook all PUSH instruction
+            0x60..=0x7f => {
+                let size = op as usize - 0x5f;
+                let end = std::cmp::min(idx + 1 + size, template.len());
+                let (imm, deployed_imm) = (&template[idx + 1..end], &runtime[idx + 1..end]);
+                if imm != deployed_imm {
+                    // only PUSH32 with zero placeholder can be an immutable
+                    if op != 0x7f || end - idx - 1 != 32 || imm.iter().any(|b| *b != 0) {
+                        return None;
+                    }
+                    let mut value = [0u8; 32];
+                    value.copy_from_slice(deployed_imm);
+                    refs.push(ImmutableRef {
+                        offset: idx + 1,
+                        value,
+                    });
+                }
+                idx = end;
+            }
+            _ => {
+                idx += 1;
+            }
+        }
+    }
+    Some(refs)
+}
+
+/// Signature of the runtime code template that a PTX kernel is translated from
+pub fn template_signature(template: &[u8]) -> String {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(template);
+    hasher.result_str()
+}
+
+/// Patch the immutables of the kernel on device
+#[cfg(feature = "cuda")]
+pub fn cu_patch_immutables(refs: &Vec<ImmutableRef>) {
+    for r in refs {
+        unsafe {
+            cuPatchImmutable(r.offset as u32, r.value.as_ptr());
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_immutables() {
+        // PUSH32 <immutable> PUSH1 0x00 SSTORE
+        let mut template = vec![0x7f];
+        template.extend([0u8; 32]);
+        template.extend([0x60, 0x00, 0x55]);
+        let mut runtime = template.clone();
+        runtime[32] = 0x2a;
+
+        let refs = find_immutables(&template, &runtime).unwrap();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].offset, 1);
+        assert_eq!(refs[0].value[31], 0x2a);
+
+        // code changes outside of the placeholders require re-translation
+        runtime[34] = 0x01;
+        assert!(find_immutables(&template, &runtime).is_none());
+    }
+
+    #[test]
+    fn test_find_runtime_template() {
+        let mut template = vec![0x7f];
+        template.extend([0u8; 32]);
+        template.extend([0x60, 0x00, 0x55]);
+        let mut creation = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+        creation.extend(template.clone());
+        let mut runtime = template.clone();
+        runtime[1] = 0xff;
+
+        assert_eq!(find_runtime_template(&creation, &runtime), Some(template.as_slice()));
+    }
+}
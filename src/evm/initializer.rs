@@ -0,0 +1,63 @@
+/// Harness for the initializer functions of upgradeable contracts
+///
+/// Implementations behind proxies are set up by an `initialize(...)`-style function instead of a
+/// constructor. The fuzzer detects these functions, runs the init phase explicitly before
+/// fuzzing (see [`crate::evm::corpus_initializer::EVMCorpusInitializer::initialize_contracts`]):
+/// - an initializer that a non-deployer can call on the initial state means the contract is
+///   left uninitialized ("anyone can initialize"), which the `initializer` sanitizer flags
+/// - the state initialized by the deployer is added to the infant states, so that normal
+///   fuzzing continues from the initialized contract
+use crate::evm::types::EVMAddress;
+
+/// Whether the function is an initializer (`initialize`, `init`, `__Foo_init`, `reinitialize`, ...)
+pub fn is_initializer_function(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name == "init"
+        || name.starts_with("initialize")
+        || name.starts_with("reinitialize")
+        || (name.starts_with("__") && name.ends_with("_init"))
+}
+
+/// Selectors of the initializer functions of the targets
+pub static mut INITIALIZER_SELECTORS: Vec<[u8; 4]> = Vec::new();
+
+/// Contracts whose initializer could be called by anyone on the initial state
+pub static mut UNINITIALIZED_CONTRACTS: Vec<EVMAddress> = Vec::new();
+
+pub fn register_initializer(selector: [u8; 4]) {
+    unsafe {
+        if !INITIALIZER_SELECTORS.contains(&selector) {
+            INITIALIZER_SELECTORS.push(selector);
+        }
+    }
+}
+
+pub fn is_initializer(calldata: &[u8]) -> bool {
+    calldata.len() >= 4 && unsafe { INITIALIZER_SELECTORS.iter().any(|s| s[..] == calldata[..4]) }
+}
+
+pub fn record_uninitialized(contract: EVMAddress) {
+    unsafe {
+        if !UNINITIALIZED_CONTRACTS.contains(&contract) {
+            UNINITIALIZED_CONTRACTS.push(contract);
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initializer_function() {
+        assert!(is_initializer_function("initialize"));
+        assert!(is_initializer_function("initializeV2"));
+        assert!(is_initializer_function("__Ownable_init"));
+        assert!(is_initializer_function("init"));
+        assert!(!is_initializer_function("initiateWithdrawal"));
+        assert!(!is_initializer_function("transfer"));
+
+        register_initializer([1, 2, 3, 4]);
+        assert!(is_initializer(&[1, 2, 3, 4, 0]));
+        assert!(!is_initializer(&[1, 2, 3]));
+    }
+}
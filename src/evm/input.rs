@@ -1,12 +1,28 @@
-use crate::evm::abi::{AEmpty, AUnknown, BoxedABI, BasicVarType};
+use crate::evm::abi::{format_address, format_uint, AEmpty, AUnknown, BoxedABI, BasicVarType};
+use crate::evm::address_pool::set_current_target;
+use crate::evm::call_value::{constrain_value, is_payable, VIOLATION_RATE};
+use crate::evm::adversary::adversary_prefix;
+use crate::evm::price_feeds::price_prefix;
+use crate::evm::force_feed::force_feed_prefix;
+#[cfg(feature = "flashloan_v2")]
+use crate::evm::flashloan_provider::flashloan_provider;
+use crate::evm::block_advance::{block_advance_prefix, BlockAdvance};
 use crate::evm::input;
-use crate::evm::mutation_utils::byte_mutator;
+use crate::evm::mutation_utils::{byte_mutator, set_mutated_selector};
+use crate::evm::token_flow::set_live_balances;
+use crate::evm::tx_gas::gas_limit_prefix;
+use crate::evm::bytes_mutation::set_call_args;
+use crate::evm::value_hints::mutate_hinted_arg;
 use crate::evm::mutator::AccessPattern;
-use crate::evm::types::{EVMAddress, EVMStagedVMState, EVMU256, EVMU512};
+use crate::evm::scenario::scenario;
+use crate::evm::types::{as_u64, EVMAddress, EVMStagedVMState, EVMU256, EVMU512};
 use crate::evm::vm::EVMState;
 use crate::input::VMInputT;
 use crate::state::{HasCaller, HasItyState};
 use crate::state_input::StagedVMState;
+use crate::runner_sys::{
+    cuClearContinuation, cuLoadContinuation, cuLoadSeed, cuLoadStorage, record_device_storage, setEVMEnv,
+};
 
 use libafl::bolts::HasLen;
 use libafl::inputs::Input;
@@ -22,7 +38,8 @@ use std::fmt::Debug;
 use std::ops::Deref;
 use std::rc::Rc;
 use std::ptr;
-use crate::evm::config::{SEED_SIZE};
+use crate::evm::config::{BLOCK_GAS_LIMIT, EXPLORE_PATHOLOGICAL_ENV};
+use crate::ffi_marshal::{marshal_continuation, marshal_storage, seed_len, MarshalError};
 
 /// EVM Input Types
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
@@ -86,17 +103,67 @@ pub trait EVMInputT {
 
     fn get_calldata(&self) -> Vec<u8>;
 
+    /// Replace the calldata with raw bytes, dropping the ABI
+    fn set_direct_data(&mut self, data: Vec<u8>);
+
     fn cu_load_evm_env(&self);
 
-    fn cu_load_input(&self, tid: u32);
+    /// Env of the input as handed to the runner: `to`, timestamp and block number, reversed /
+    /// little endian
+    fn cu_evm_env(&self) -> ([u8; 20], [u8; 32], [u8; 32]);
+
+    /// Copy the seed to the buffer of the GPU thread, executed on the storage loaded at
+    /// `state_idx` (see [`Self::cu_load_storage`]), fails when it does not fit
+    fn cu_load_input(&self, state_idx: u32, tid: u32) -> Result<(), MarshalError>;
 
-    fn cu_load_storage(&self, tid: u32);
+    /// Copy the storage of the target to the GPU thread, fails when it does not fit
+    fn cu_load_storage(&self, tid: u32) -> Result<(), MarshalError>;
+
+    /// Set the frame the GPU threads resume from for a stepped input (the last post execution
+    /// context of its state), and clear it for the others
+    fn cu_load_continuation(&self) -> Result<(), MarshalError>;
 
     fn get_distance(&self) -> usize;
     fn set_distance(&mut self, distance:usize);
 
     fn set_cuda_input(&mut self, status:bool);
 
+    /// Get the scenario preset of the input
+    fn get_scenario(&self) -> Option<usize>;
+
+    /// Set the scenario preset of the input
+    fn set_scenario(&mut self, scenario: Option<usize>);
+
+    /// Get the adversary transaction executed before the input, `None` when resuming a control
+    /// leak (the adversary cannot land in the middle of a transaction)
+    fn get_adversary(&self) -> Option<usize>;
+
+    /// Set the adversary transaction executed before the input
+    fn set_adversary(&mut self, adversary: Option<usize>);
+
+    /// Get the prices reported by the price feeds during the execution
+    fn get_feed_prices(&self) -> &Vec<EVMU256>;
+
+    /// Get the prices reported by the price feeds during the execution mutably
+    fn get_feed_prices_mut(&mut self) -> &mut Vec<EVMU256>;
+
+    /// Get the ether force-fed to the target before the input is executed
+    fn get_force_feed(&self) -> Option<EVMU256>;
+
+    /// Set the ether force-fed to the target before the input is executed
+    fn set_force_feed(&mut self, force_feed: Option<EVMU256>);
+
+    /// Get how the block advances from the previous transaction
+    fn get_block_advance(&self) -> Option<BlockAdvance>;
+
+    /// Set how the block advances from the previous transaction
+    fn set_block_advance(&mut self, advance: Option<BlockAdvance>);
+
+    /// Get the gas limit of the transaction, the block gas limit if unset
+    fn get_gas_limit(&self) -> Option<u64>;
+
+    /// Set the gas limit of the transaction
+    fn set_gas_limit(&mut self, gas_limit: Option<u64>);
 }
 
 
@@ -132,6 +199,7 @@ pub struct EVMInput {
     pub env: Env,
 
     /// Access pattern
+    #[serde(default)]
     pub access_pattern: Rc<RefCell<AccessPattern>>,
 
     /// Percentage of the token amount in all callers' account to liquidate
@@ -142,16 +210,47 @@ pub struct EVMInput {
     pub direct_data: Bytes,
 
     /// Additional random bytes for mutator
+    #[serde(default)]
     pub randomness: Vec<u8>,
 
     /// Execute the transaction multiple times
     pub repeat: usize,
 
     /// cuda input
+    #[serde(default)]
     pub cu_data: Vec<u8>,
 
+    /// Whether the input was found by the GPU
+    #[serde(default)]
     pub is_cuda: bool,
+    /// Branch distance recorded for the input
+    #[serde(default)]
     pub branch_distance: usize,
+
+    /// Index of the scenario preset applied before execution, `None` for the base environment
+    #[serde(default)]
+    pub scenario: Option<usize>,
+
+    /// Index of the adversary transaction executed right before the input, see [`crate::evm::adversary`]
+    #[serde(default)]
+    pub adversary: Option<usize>,
+
+    /// Prices reported by the price feeds, see [`crate::evm::price_feeds`]
+    #[serde(default)]
+    pub feed_prices: Vec<EVMU256>,
+
+    /// Ether force-fed to the target right before the input, see [`crate::evm::force_feed`]
+    #[serde(default)]
+    pub force_feed: Option<EVMU256>,
+
+    /// Block the input is executed in, relative to the previous transaction, see
+    /// [`crate::evm::block_advance`]
+    #[serde(default)]
+    pub block_advance: Option<BlockAdvance>,
+
+    /// Gas limit of the transaction, the block gas limit if unset, see [`crate::evm::tx_gas`]
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
 }
 
 impl HasLen for EVMInput {
@@ -265,19 +364,13 @@ impl EVMInputT for EVMInput {
         }
     }
 
-    fn cu_load_evm_env(&self) {
-        let block = &self.env.block;
-        let timestamp: [u8; 32] = block.timestamp.to_le_bytes();
-        let blocknum: [u8; 32] = block.number.to_le_bytes();
-        // println!("timestamp = {:?}", timestamp);
-        let mut to: [u8; 20]  = self.get_contract().to_fixed_bytes();
-        to.reverse();
-   
+    fn set_direct_data(&mut self, data: Vec<u8>) {
+        self.data = None;
+        self.direct_data = Bytes::from(data);
+    }
 
-        #[link(name = "runner")]
-        extern "C" {
-            fn setEVMEnv(To: *const u8, Timestamp: *const u8, Blocknum: *const u8) -> bool;
-        }
+    fn cu_load_evm_env(&self) {
+        let (to, timestamp, blocknum) = self.cu_evm_env();
         unsafe {
             setEVMEnv(to.as_ptr(),
                       timestamp.as_ptr(), 
@@ -285,23 +378,25 @@ impl EVMInputT for EVMInput {
         }
     }
 
-    fn cu_load_input(&self, tid: u32) {
-        #[link(name = "runner")]
-        extern "C" {
-            fn cuLoadSeed(caller_ptr: *const u8, value_ptr: *const u8, data_ptr: *const u8, data_size: u32, state_idx: u32, thread: u32);
-            fn cuGetStoragePos(s_idx: u32) -> u32;
+    fn cu_evm_env(&self) -> ([u8; 20], [u8; 32], [u8; 32]) {
+        // the scenario preset applies on the GPU as it does on revm
+        let mut block = self.env.block.clone();
+        if let Some(scenario) = scenario(self.scenario) {
+            scenario.apply_block(&mut block);
         }
+        let mut to: [u8; 20] = self.get_contract().to_fixed_bytes();
+        to.reverse();
+        (to, block.timestamp.to_le_bytes(), block.number.to_le_bytes())
+    }
+
+    fn cu_load_input(&self, state_idx: u32, tid: u32) -> Result<(), MarshalError> {
         let mut caller =  self.get_caller().to_fixed_bytes();
         caller.reverse();
         let callvalue: [u8; 32] = self.get_txn_value().unwrap_or(EVMU256::ZERO).to_le_bytes();
 
         let calldata = self.get_calldata();
         
-        let calldatasize = calldata.len();
-
-        if 68 + calldatasize > SEED_SIZE {
-            println!("[-] Increate the SEED_SIZE. calldatasize({:?}) > {:?}.", 68 + calldatasize, SEED_SIZE);
-        }
+        let calldatasize = seed_len(calldata.len())?;
         // println!("state ectracting idx = {:?}",  self.get_state_idx());
 
         // let state_idx;
@@ -323,33 +418,61 @@ impl EVMInputT for EVMInput {
                 caller.as_ptr(), 
                 callvalue.as_ptr(), 
                 calldata.as_ptr(), 
-                calldatasize as u32, 
-                0,
+                calldatasize, 
+                state_idx,
                 tid,
             );
         }
+        Ok(())
     }
 
-    fn cu_load_storage(&self, state_id: u32) {
-        #[link(name = "runner")]
-        extern "C" {
-            fn cuLoadStorage(src: *const u8, slotCnt: u32, state_id: u32);
+    fn cu_load_storage(&self, state_id: u32) -> Result<(), MarshalError> {
+        // load initial storage one by one (heavy mode), with the overrides of the scenario preset
+        let mut storage = self.get_state().get(&self.get_contract()).cloned();
+        if let Some(scenario) = scenario(self.scenario) {
+            scenario.apply_storage(&self.contract, storage.get_or_insert_with(Default::default));
         }
-        // load initial storage one by one (heavy mode)
-        if let Some(storage) = self.get_state().get(&self.get_contract()) {
-            let mut bytes = Vec::new();
-            // println!("storage content before executing input=> {:?}", storage);
-            for (key, value) in storage {
-                // for (key, value) in storage {
-                //     println!("{:#x}: {:#x}", key, value);
-                // }
-                let slot = [key.as_le_bytes(), value.as_le_bytes()].concat();
-                bytes.extend(slot);
-            }
-            unsafe{ cuLoadStorage(bytes.as_ptr(), storage.len() as u32, state_id as u32); }
+        if let Some(storage) = storage.filter(|storage| !storage.is_empty()) {
+            let (bytes, count) = marshal_storage(&storage)?;
+            unsafe{ cuLoadStorage(bytes.as_ptr(), count, state_id); }
         } else {
-            unsafe{ cuLoadStorage(ptr::null(), 0, state_id as u32); }
+            unsafe{ cuLoadStorage(ptr::null(), 0, state_id); }
+        }
+        record_device_storage(state_id, self.sstate_idx);
+        Ok(())
+    }
+
+    fn cu_load_continuation(&self) -> Result<(), MarshalError> {
+        let ctx = match self.get_state().post_execution.last() {
+            Some(ctx) if self.step => ctx,
+            _ => {
+                unsafe { cuClearContinuation(); }
+                return Ok(());
+            }
+        };
+        let continuation = marshal_continuation(ctx)?;
+        let mut caller = ctx.caller.to_fixed_bytes();
+        caller.reverse();
+        let value: [u8; 32] = ctx.apparent_value.to_le_bytes();
+        let loaded = unsafe {
+            cuLoadContinuation(
+                continuation.pc,
+                continuation.stack.as_ptr(),
+                continuation.stack_len,
+                continuation.memory.as_ptr(),
+                continuation.memory_len,
+                continuation.call_data.as_ptr(),
+                continuation.call_data_len,
+                continuation.output_offset,
+                continuation.output_len,
+                caller.as_ptr(),
+                value.as_ptr(),
+            )
+        };
+        if !loaded {
+            return Err(MarshalError::ContinuationRejected { pc: ctx.pc });
         }
+        Ok(())
     }
 
     fn get_distance(&self) -> usize {
@@ -363,6 +486,58 @@ impl EVMInputT for EVMInput {
     fn set_cuda_input(&mut self, status:bool) {
         self.is_cuda = status;
     }
+
+    fn get_scenario(&self) -> Option<usize> {
+        self.scenario
+    }
+
+    fn set_scenario(&mut self, scenario: Option<usize>) {
+        self.scenario = scenario;
+    }
+
+    fn get_adversary(&self) -> Option<usize> {
+        if self.step {
+            None
+        } else {
+            self.adversary
+        }
+    }
+
+    fn set_adversary(&mut self, adversary: Option<usize>) {
+        self.adversary = adversary;
+    }
+
+    fn get_feed_prices(&self) -> &Vec<EVMU256> {
+        &self.feed_prices
+    }
+
+    fn get_feed_prices_mut(&mut self) -> &mut Vec<EVMU256> {
+        &mut self.feed_prices
+    }
+
+    fn get_force_feed(&self) -> Option<EVMU256> {
+        self.force_feed
+    }
+
+    fn set_force_feed(&mut self, force_feed: Option<EVMU256>) {
+        self.force_feed = force_feed;
+    }
+
+    fn get_block_advance(&self) -> Option<BlockAdvance> {
+        self.block_advance
+    }
+
+    fn set_block_advance(&mut self, advance: Option<BlockAdvance>) {
+        self.block_advance = advance;
+    }
+
+    fn get_gas_limit(&self) -> Option<u64> {
+        self.gas_limit
+    }
+
+    fn set_gas_limit(&mut self, gas_limit: Option<u64>) {
+        self.gas_limit = gas_limit;
+    }
 }
 
 
@@ -461,13 +636,63 @@ impl<'a> HasBytesVec for MutatorInput<'a> {
 }
 
 impl EVMInput {
+    /// Human-friendly call of the input: caller alias, decoded args, value, encoded calldata and
+    /// the staged state it starts from, e.g.,
+    /// `attacker1 -> transfer(to=owner, amount=1e18) with 0 wei (a9059cbb...) [state #3 <- #1]`
+    fn pretty_call(&self, d: &BoxedABI) -> String {
+        format!(
+            "{} -> {} with {} wei ({}){}",
+            format_address(&self.caller),
+            d.to_decoded_string(),
+            format_uint(self.txn_value.unwrap_or(EVMU256::ZERO)),
+            hex::encode(d.get_bytes()),
+            self.pretty_ancestry()
+        )
+    }
+
+    /// Staged state the input starts from and the state it was derived from
+    fn pretty_ancestry(&self) -> String {
+        if self.sstate_idx == 0 {
+            return String::new();
+        }
+        match self.sstate.trace.from_idx {
+            Some(parent) => format!(" [state #{} <- #{}]", self.sstate_idx, parent),
+            None => format!(" [state #{}]", self.sstate_idx),
+        }
+    }
+
     impl_env_mutator_u256!(basefee, block);
     impl_env_mutator_u256!(timestamp, block);
     impl_env_mutator_h160!(coinbase, block);
-    impl_env_mutator_u256!(gas_limit, block);
     impl_env_mutator_u256!(number, block);
     impl_env_mutator_u256!(chain_id, cfg);
 
+    pub fn gas_limit<S>(input: &mut EVMInput, state_: &mut S) -> MutationResult
+    where
+        S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
+    {
+        let vm_slots = if let Some(s) = input.get_state().get(&input.get_contract()) {
+            Some(s.clone())
+        } else {
+            None
+        };
+        let mut input_by: [u8; 32] = input.get_vm_env().block.gas_limit.to_be_bytes();
+        let mut input_vec = input_by.to_vec();
+        let mut wrapper = MutatorInput::new(&mut input_vec);
+        let res = byte_mutator(state_, &mut wrapper, vm_slots);
+        if res == MutationResult::Skipped {
+            return res;
+        }
+        let mut gas_limit = EVMU256::try_from_be_slice(&input_vec.as_slice()).unwrap();
+        // keep the gas limit within the block gas limit of the chain profile, pathological
+        // values are only explored out-of-band (10% of the time) when explicitly enabled
+        if !unsafe { EXPLORE_PATHOLOGICAL_ENV } || state_.rand_mut().below(100) >= 10 {
+            gas_limit = gas_limit % EVMU256::from(unsafe { BLOCK_GAS_LIMIT } + 1);
+        }
+        input.get_vm_env_mut().block.gas_limit = gas_limit;
+        res
+    }
+
     pub fn prevrandao<S>(_input: &mut EVMInput, _state_: &mut S) -> MutationResult
     where
         S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
@@ -531,7 +756,18 @@ impl EVMInput {
         for i in 0..16 {
             input_vec[i] = 0;
         }
-        input.set_txn_value(EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap());
+        // follow the payability of the function and the balance of the caller, but not always
+        let payable = input
+            .data
+            .as_ref()
+            .and_then(|abi| is_payable(&input.contract, &abi.function));
+        let violation = state_.rand_mut().below(VIOLATION_RATE) == 0;
+        let value = EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap();
+        let value = constrain_value(value, payable, violation);
+        if Some(value) == input.get_txn_value() || (value == EVMU256::ZERO && input.get_txn_value().is_none()) {
+            return MutationResult::Skipped;
+        }
+        input.set_txn_value(value);
         res
     }
 
@@ -600,6 +836,13 @@ impl VMInputT<EVMState, EVMAddress, EVMAddress> for EVMInput {
         };
         match self.data {
             Some(ref mut data) => {
+                set_mutated_selector(state, data.function);
+                set_current_target(self.contract);
+                set_live_balances(&self.sstate.state, self.caller, self.contract, data.function);
+                set_call_args(data.get_bytes());
+                if let Some(result) = mutate_hinted_arg(state, data) {
+                    return result;
+                }
                 // println!("type before => {:?}", data.get_type());
                 let a = data.mutate_with_vm_slots(state, vm_slots);
                 // println!("type=> after {:?}", data.get_type());
@@ -626,26 +869,8 @@ impl VMInputT<EVMState, EVMAddress, EVMAddress> for EVMInput {
     }
 
     fn set_evm_env(&self) -> &Env {
-        let block = &self.env.block;
-        let timestamp: [u8; 32] = block.timestamp.to_le_bytes();
-        let blocknum: [u8; 32] = block.number.to_le_bytes();
-
-        let to: [u8; 20]  = self.get_contract().to_fixed_bytes();
-        let caller: [u8; 20] = self.get_caller().to_fixed_bytes();
-        let callvalue: [u8; 32] = self.get_txn_value().unwrap_or(EVMU256::ZERO).to_le_bytes();
-   
-
-        #[link(name = "runner")]
-        extern "C" {
-            fn setEVMEnv(From: *const u8, To: *const u8, Timestamp: *const u8, Blocknum: *const u8) -> bool;
-        }
-        unsafe {
-            setEVMEnv(caller.as_ptr(),
-                      to.as_ptr(),
-                      timestamp.as_ptr(), 
-                      blocknum.as_ptr());
-        }
-
+        // the caller is loaded along with the seed
+        self.cu_load_evm_env();
         &self.env
     }
 
@@ -693,39 +918,51 @@ impl VMInputT<EVMState, EVMAddress, EVMAddress> for EVMInput {
     #[cfg(feature = "flashloan_v2")]
     fn pretty_txn(&self) -> Option<String> {
         let liq = self.liquidation_percent;
-        match self.data {
-            Some(ref d) => Some(format!(
-                "{} with {:?} ETH ({}), liq percent: {}",
-                d.to_string(),
-                self.txn_value,
-                hex::encode(d.get_bytes()),
-                liq
-            )),
+        let txn = match self.data {
+            Some(ref d) => Some(format!("{}, liq percent: {}", self.pretty_call(d), liq)),
             None => match self.input_type {
                 EVMInputTy::ABI => Some(format!(
                     "ABI with {:?} ETH, liq percent: {}",
                     self.txn_value, liq
                 )),
                 EVMInputTy::Borrow => Some(format!(
-                    "Borrow with {:?} ETH, liq percent: {}",
+                    "Borrow {}with {:?} ETH, liq percent: {}",
+                    flashloan_provider()
+                        .map(|provider| format!("from {} ", provider.name()))
+                        .unwrap_or_default(),
                     self.txn_value, liq
                 )),
                 EVMInputTy::Liquidate => None,
             },
-        }
+        };
+        txn.map(|txn| {
+            format!(
+                "{}{}{}{}{}{}",
+                adversary_prefix(self.get_adversary()),
+                block_advance_prefix(self.block_advance),
+                gas_limit_prefix(self.gas_limit),
+                force_feed_prefix(self.force_feed),
+                price_prefix(&self.feed_prices),
+                txn
+            )
+        })
     }
 
     #[cfg(not(feature = "flashloan_v2"))]
     fn pretty_txn(&self) -> Option<String> {
-        match self.data {
-            Some(ref d) => Some(format!(
-                "{} with {:?} ETH ({})",
-                d.to_string(),
-                self.txn_value,
-                hex::encode(d.get_bytes())
-            )),
-            None => Some(format!("ABI with {:?} ETH", self.txn_value)),
-        }
+        let txn = match self.data {
+            Some(ref d) => self.pretty_call(d),
+            None => format!("ABI with {:?} ETH", self.txn_value),
+        };
+        Some(format!(
+            "{}{}{}{}{}{}",
+            adversary_prefix(self.get_adversary()),
+            block_advance_prefix(self.block_advance),
+            gas_limit_prefix(self.gas_limit),
+            force_feed_prefix(self.force_feed),
+            price_prefix(&self.feed_prices),
+            txn
+        ))
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -770,6 +1007,12 @@ impl VMInputT<EVMState, EVMAddress, EVMAddress> for EVMInput {
         self.txn_value
     }
 
+    #[cfg(feature = "evm")]
+    fn get_warp_to(&self) -> u64 {
+        // a corpus transaction warped to block n runs at timestamp n * 1000
+        as_u64(self.env.block.timestamp / EVMU256::from(1000))
+    }
+
     #[cfg(feature = "evm")]
     fn get_cuda_input(&self) -> Vec<u8> {
         self.cu_data.clone()
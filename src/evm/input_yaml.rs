@@ -0,0 +1,528 @@
+/// Human-editable YAML form of the inputs (`cli input decode / encode`)
+///
+/// The inputs of the corpus are saved by LibAFL as binary files (`input-NNNNNN.bin`), which
+/// cannot be read nor tweaked by hand. An input is written here as a YAML document with its
+/// decoded ABI tree, the caller and contract by alias (e.g., `attacker1`) when they have one,
+/// amounts in decimal and the block environment when it differs from the default:
+/// ```yaml
+/// caller: attacker1
+/// contract: '0x1000000000000000000000000000000000000000'
+/// value: 1e18
+/// function: transfer
+/// selector: a9059cbb
+/// args:
+///   type: array
+///   items:
+///   - type: address
+///     value: attacker2
+///   - type: uint
+///     value: '1000'
+///     size: 32
+/// block:
+///   number: '42'
+/// ```
+/// Decoding the document gives back the same input, except for what the fuzzer recomputes
+/// (access pattern, GPU data, branch distance). The staged VM state is not written, the input
+/// refers to the infant state by `state_idx`; `encode --base <input.bin>` carries over the state
+/// of an existing input.
+use crate::evm::abi::{format_uint, function_sig_name, AArray, ADynamic, AEmpty, AUnknown, BoxedABI, A256, ABI};
+use crate::evm::block_advance::BlockAdvance;
+use crate::evm::callers::apply_named_callers;
+use crate::evm::contract_utils::FIX_DEPLOYER;
+use crate::evm::corpus_initializer::{CONTRACT_CALLERS, DEFAULT_CALLERS};
+use crate::evm::input::EVMInput;
+#[cfg(feature = "flashloan_v2")]
+use crate::evm::input::EVMInputTy;
+use crate::evm::mutator::AccessPattern;
+use crate::evm::types::{alias_address, caller_alias, fixed_address, register_caller_alias, EVMAddress, EVMU256};
+use crate::evm::view_invariants::parse_number;
+use crate::state_input::StagedVMState;
+use bytes::Bytes;
+use libafl::inputs::Input;
+use revm_primitives::{Env, B256};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::ops::DerefMut;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Size of the data of `bytes` / `string` args is rounded up to a multiple of
+const DEFAULT_MULTIPLIER: usize = 32;
+
+fn default_multiplier() -> usize {
+    DEFAULT_MULTIPLIER
+}
+
+fn is_default_multiplier(multiplier: &usize) -> bool {
+    *multiplier == DEFAULT_MULTIPLIER
+}
+
+/// Arg of the ABI tree of an input
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AbiValue {
+    Empty,
+    /// Address, by alias or in hex
+    Address {
+        value: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        frozen: bool,
+    },
+    /// Integer of `size` bytes, in decimal (e.g., `1e18`) or hex
+    Uint {
+        value: String,
+        size: usize,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        frozen: bool,
+    },
+    /// `bytesN`, in hex
+    FixedBytes {
+        value: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        frozen: bool,
+    },
+    /// `bytes` in hex, or a `string` that is not valid UTF-8
+    Bytes {
+        value: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        string: bool,
+        #[serde(default = "default_multiplier", skip_serializing_if = "is_default_multiplier")]
+        multiplier: usize,
+    },
+    String {
+        value: String,
+        #[serde(default = "default_multiplier", skip_serializing_if = "is_default_multiplier")]
+        multiplier: usize,
+    },
+    /// Tuple or array, `dynamic` for an array of dynamic size
+    Array {
+        items: Vec<AbiValue>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        dynamic: bool,
+    },
+    /// Arg of unknown type, with its current concrete value
+    Unknown { size: usize, value: Box<AbiValue> },
+}
+
+/// Block environment of an input, the fields that differ from the default
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basefee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    /// In hex, `none` before the merge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prevrandao: Option<String>,
+}
+
+/// YAML document of an input
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InputDoc {
+    #[cfg(feature = "flashloan_v2")]
+    #[serde(default = "default_input_type")]
+    pub kind: EVMInputTy,
+    pub caller: String,
+    pub contract: String,
+    /// Value sent, in wei
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Name of the function called, only informative (the selector is the one called)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// Selector of the function, in hex, `00000000` to resume from a control leak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<AbiValue>,
+    /// Raw calldata in hex, used when the input has no ABI
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub calldata: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub step: bool,
+    #[serde(default)]
+    pub repeat: usize,
+    #[serde(default)]
+    pub state_idx: usize,
+    #[serde(default, skip_serializing_if = "is_default_block")]
+    pub block: BlockOverrides,
+    #[cfg(feature = "flashloan_v2")]
+    #[serde(default)]
+    pub liquidation_percent: u8,
+    /// Random bytes of the mutator (e.g., the amount of a borrow), in hex
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub randomness: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adversary: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feed_prices: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_feed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_advance: Option<BlockAdvance>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<u64>,
+}
+
+#[cfg(feature = "flashloan_v2")]
+fn default_input_type() -> EVMInputTy {
+    EVMInputTy::ABI
+}
+
+fn is_default_block(block: &BlockOverrides) -> bool {
+    *block == BlockOverrides::default()
+}
+
+/// Register the aliases of the deployer and the default callers, which are fixed addresses, and
+/// the names of the callers named in the config over them
+pub fn register_known_aliases() {
+    register_caller_alias(fixed_address(FIX_DEPLOYER), "owner");
+    for (idx, caller) in DEFAULT_CALLERS.iter().enumerate() {
+        register_caller_alias(fixed_address(caller), &format!("attacker{}", idx + 1));
+    }
+    for (idx, caller) in CONTRACT_CALLERS.iter().enumerate() {
+        register_caller_alias(fixed_address(caller), &format!("attacker_contract{}", idx + 1));
+    }
+    apply_named_callers();
+}
+
+fn encode_address(address: &EVMAddress) -> String {
+    caller_alias(address).unwrap_or_else(|| format!("{:?}", address))
+}
+
+fn decode_address(s: &str) -> Result<EVMAddress, String> {
+    match alias_address(s) {
+        Some(address) => Ok(address),
+        None => EVMAddress::from_str(s).map_err(|_| format!("invalid address {}", s)),
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|e| format!("invalid hex {}: {}", s, e))
+}
+
+/// Big endian bytes of the number, on `size` bytes
+fn number_bytes(s: &str, size: usize) -> Result<Vec<u8>, String> {
+    let value = parse_number(s)?;
+    let bytes = value.to_be_bytes::<32>();
+    if size > 32 || bytes[..32 - size].iter().any(|b| *b != 0) {
+        return Err(format!("{} does not fit in {} bytes", s, size));
+    }
+    Ok(bytes[32 - size..].to_vec())
+}
+
+pub fn encode_abi(abi: &BoxedABI) -> AbiValue {
+    let mut abi = abi.clone();
+    let any = abi.b.deref_mut().as_any();
+    if let Some(a256) = any.downcast_mut::<A256>() {
+        return if a256.is_address && a256.data.len() == 20 {
+            AbiValue::Address {
+                value: encode_address(&EVMAddress::from_slice(&a256.data)),
+                frozen: a256.dont_mutate,
+            }
+        } else if a256.is_bytes {
+            AbiValue::FixedBytes {
+                value: hex::encode(&a256.data),
+                frozen: a256.dont_mutate,
+            }
+        } else {
+            AbiValue::Uint {
+                value: format_uint(EVMU256::try_from_be_slice(&a256.data).unwrap_or_default()),
+                size: a256.data.len(),
+                frozen: a256.dont_mutate,
+            }
+        };
+    }
+    if let Some(adyn) = any.downcast_mut::<ADynamic>() {
+        return match String::from_utf8(adyn.data.clone()) {
+            Ok(value) if adyn.is_string => AbiValue::String {
+                value,
+                multiplier: adyn.multiplier,
+            },
+            _ => AbiValue::Bytes {
+                value: hex::encode(&adyn.data),
+                string: adyn.is_string,
+                multiplier: adyn.multiplier,
+            },
+        };
+    }
+    if let Some(aarray) = any.downcast_mut::<AArray>() {
+        return AbiValue::Array {
+            items: aarray.data.iter().map(encode_abi).collect(),
+            dynamic: aarray.dynamic_size,
+        };
+    }
+    if let Some(aunknown) = any.downcast_mut::<AUnknown>() {
+        return AbiValue::Unknown {
+            size: aunknown.size,
+            value: Box::new(encode_abi(&aunknown.concrete)),
+        };
+    }
+    AbiValue::Empty
+}
+
+pub fn decode_abi(value: &AbiValue) -> Result<BoxedABI, String> {
+    let b: Box<dyn ABI> = match value {
+        AbiValue::Empty => Box::new(AEmpty {}),
+        AbiValue::Address { value, frozen } => Box::new(A256 {
+            data: decode_address(value)?.0.to_vec(),
+            is_address: true,
+            dont_mutate: *frozen,
+            is_bytes: false,
+            provenance: Default::default(),
+        }),
+        AbiValue::Uint { value, size, frozen } => Box::new(A256 {
+            data: number_bytes(value, *size)?,
+            is_address: false,
+            dont_mutate: *frozen,
+            is_bytes: false,
+            provenance: Default::default(),
+        }),
+        AbiValue::FixedBytes { value, frozen } => Box::new(A256 {
+            data: decode_hex(value)?,
+            is_address: false,
+            dont_mutate: *frozen,
+            is_bytes: true,
+            provenance: Default::default(),
+        }),
+        AbiValue::Bytes { value, string, multiplier } => Box::new(ADynamic {
+            data: decode_hex(value)?,
+            multiplier: *multiplier,
+            is_string: *string,
+            provenance: Default::default(),
+        }),
+        AbiValue::String { value, multiplier } => Box::new(ADynamic {
+            data: value.as_bytes().to_vec(),
+            multiplier: *multiplier,
+            is_string: true,
+            provenance: Default::default(),
+        }),
+        AbiValue::Array { items, dynamic } => Box::new(AArray {
+            data: items.iter().map(decode_abi).collect::<Result<Vec<_>, _>>()?,
+            dynamic_size: *dynamic,
+        }),
+        AbiValue::Unknown { size, value } => Box::new(AUnknown {
+            concrete: decode_abi(value)?,
+            size: *size,
+        }),
+    };
+    Ok(BoxedABI::new(b))
+}
+
+fn encode_block(env: &Env) -> BlockOverrides {
+    let (block, default) = (&env.block, Env::default().block);
+    let changed = |v: EVMU256, d: EVMU256| if v == d { None } else { Some(format_uint(v)) };
+    BlockOverrides {
+        number: changed(block.number, default.number),
+        timestamp: changed(block.timestamp, default.timestamp),
+        coinbase: (block.coinbase != default.coinbase).then(|| encode_address(&block.coinbase)),
+        gas_limit: changed(block.gas_limit, default.gas_limit),
+        basefee: changed(block.basefee, default.basefee),
+        difficulty: changed(block.difficulty, default.difficulty),
+        prevrandao: (block.prevrandao != default.prevrandao).then(|| match block.prevrandao {
+            Some(prevrandao) => hex::encode(prevrandao.0),
+            None => "none".to_string(),
+        }),
+    }
+}
+
+fn decode_block(overrides: &BlockOverrides) -> Result<Env, String> {
+    let mut env = Env::default();
+    let number = |v: &Option<String>, d: EVMU256| v.as_deref().map_or(Ok(d), parse_number);
+    env.block.number = number(&overrides.number, env.block.number)?;
+    env.block.timestamp = number(&overrides.timestamp, env.block.timestamp)?;
+    env.block.gas_limit = number(&overrides.gas_limit, env.block.gas_limit)?;
+    env.block.basefee = number(&overrides.basefee, env.block.basefee)?;
+    env.block.difficulty = number(&overrides.difficulty, env.block.difficulty)?;
+    if let Some(coinbase) = &overrides.coinbase {
+        env.block.coinbase = decode_address(coinbase)?;
+    }
+    match overrides.prevrandao.as_deref() {
+        Some("none") => env.block.prevrandao = None,
+        Some(prevrandao) => {
+            let bytes = decode_hex(prevrandao)?;
+            if bytes.len() != 32 {
+                return Err(format!("invalid prevrandao {}", prevrandao));
+            }
+            env.block.prevrandao = Some(B256::from_slice(&bytes));
+        }
+        None => {}
+    }
+    Ok(env)
+}
+
+pub fn input_to_doc(input: &EVMInput) -> InputDoc {
+    let selector = input.data.as_ref().map(|data| data.function);
+    InputDoc {
+        #[cfg(feature = "flashloan_v2")]
+        kind: input.input_type.clone(),
+        caller: encode_address(&input.caller),
+        contract: encode_address(&input.contract),
+        value: input.txn_value.map(format_uint),
+        function: selector
+            .map(|selector| function_sig_name(&selector))
+            .filter(|name| Some(name.as_str()) != selector.map(hex::encode).as_deref()),
+        selector: selector.map(hex::encode),
+        args: input.data.as_ref().map(encode_abi),
+        calldata: hex::encode(&input.direct_data),
+        step: input.step,
+        repeat: input.repeat,
+        state_idx: input.sstate_idx,
+        block: encode_block(&input.env),
+        #[cfg(feature = "flashloan_v2")]
+        liquidation_percent: input.liquidation_percent,
+        randomness: hex::encode(&input.randomness),
+        scenario: input.scenario,
+        adversary: input.adversary,
+        feed_prices: input.feed_prices.iter().map(|price| format_uint(*price)).collect(),
+        force_feed: input.force_feed.map(format_uint),
+        block_advance: input.block_advance,
+        gas_limit: input.gas_limit,
+    }
+}
+
+/// Input of the document, on an uninitialized staged state
+pub fn doc_to_input(doc: &InputDoc) -> Result<EVMInput, String> {
+    let data = match (&doc.args, &doc.selector) {
+        (Some(args), selector) => {
+            let mut data = decode_abi(args)?;
+            if let Some(selector) = selector {
+                let selector = decode_hex(selector)?;
+                data.function = selector
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| format!("invalid selector {}", hex::encode(&selector)))?;
+            }
+            Some(data)
+        }
+        (None, Some(_)) => return Err("a selector without args, add `args: {type: empty}`".to_string()),
+        (None, None) => None,
+    };
+    Ok(EVMInput {
+        #[cfg(feature = "flashloan_v2")]
+        input_type: doc.kind.clone(),
+        caller: decode_address(&doc.caller)?,
+        contract: decode_address(&doc.contract)?,
+        data,
+        sstate: StagedVMState::new_uninitialized(),
+        sstate_idx: doc.state_idx,
+        txn_value: doc.value.as_deref().map(parse_number).transpose()?,
+        step: doc.step,
+        env: decode_block(&doc.block)?,
+        access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+        #[cfg(feature = "flashloan_v2")]
+        liquidation_percent: doc.liquidation_percent,
+        direct_data: Bytes::from(decode_hex(&doc.calldata)?),
+        randomness: decode_hex(&doc.randomness)?,
+        repeat: doc.repeat,
+        cu_data: vec![],
+        is_cuda: false,
+        branch_distance: 0,
+        scenario: doc.scenario,
+        adversary: doc.adversary,
+        feed_prices: doc
+            .feed_prices
+            .iter()
+            .map(|price| parse_number(price))
+            .collect::<Result<Vec<_>, _>>()?,
+        force_feed: doc.force_feed.as_deref().map(parse_number).transpose()?,
+        block_advance: doc.block_advance,
+        gas_limit: doc.gas_limit,
+    })
+}
+
+pub fn input_to_yaml(input: &EVMInput) -> String {
+    serde_yaml::to_string(&input_to_doc(input)).expect("failed to serialize input")
+}
+
+pub fn input_from_yaml(yaml: &str) -> Result<EVMInput, String> {
+    let doc: InputDoc = serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+    doc_to_input(&doc)
+}
+
+/// YAML document of a binary input of the corpus
+pub fn decode_input_file(path: &str) -> Result<String, String> {
+    register_known_aliases();
+    let input = EVMInput::from_file(path).map_err(|e| format!("failed to read {}: {:?}", path, e))?;
+    Ok(input_to_yaml(&input))
+}
+
+/// Write the input of a YAML document as a binary input, with the staged state of `base` if any
+pub fn encode_input_file(yaml_path: &str, output: &str, base: Option<&str>) -> Result<(), String> {
+    register_known_aliases();
+    let yaml = fs::read_to_string(yaml_path).map_err(|e| format!("failed to read {}: {}", yaml_path, e))?;
+    let mut input = input_from_yaml(&yaml).map_err(|e| format!("{}: {}", yaml_path, e))?;
+    if let Some(base) = base {
+        let base = EVMInput::from_file(base).map_err(|e| format!("failed to read {}: {:?}", base, e))?;
+        input.sstate = base.sstate;
+    }
+    input
+        .to_file(output)
+        .map_err(|e| format!("failed to write {}: {:?}", output, e))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_yaml_roundtrip() {
+        register_known_aliases();
+        let args = AbiValue::Array {
+            items: vec![
+                AbiValue::Address {
+                    value: "attacker2".to_string(),
+                    frozen: false,
+                },
+                AbiValue::Uint {
+                    value: "1e18".to_string(),
+                    size: 32,
+                    frozen: true,
+                },
+                AbiValue::String {
+                    value: "hello".to_string(),
+                    multiplier: 32,
+                },
+                AbiValue::Array {
+                    items: vec![AbiValue::FixedBytes {
+                        value: "cafe".to_string(),
+                        frozen: false,
+                    }],
+                    dynamic: true,
+                },
+            ],
+            dynamic: false,
+        };
+        let yaml = format!(
+            "caller: attacker1\ncontract: '0x1000000000000000000000000000000000000000'\nvalue: 1e18\n\
+             selector: a9059cbb\nargs: {}\nblock:\n  number: '42'\n",
+            serde_json::to_string(&args).unwrap()
+        );
+        let input = input_from_yaml(&yaml).unwrap();
+        assert_eq!(caller_alias(&input.caller), Some("attacker1".to_string()));
+        assert_eq!(input.txn_value, Some(EVMU256::from(10).pow(EVMU256::from(18))));
+        assert_eq!(input.env.block.number, EVMU256::from(42));
+        assert_eq!(input.data.as_ref().unwrap().function, [0xa9, 0x05, 0x9c, 0xbb]);
+
+        let doc = input_to_doc(&input);
+        assert_eq!(doc.args, Some(args));
+        assert_eq!(doc.block.number, Some("42".to_string()));
+        assert_eq!(doc.block.timestamp, None);
+        let again = input_from_yaml(&input_to_yaml(&input)).unwrap();
+        assert_eq!(input_to_doc(&again), doc);
+        assert_eq!(again.data.unwrap().get_bytes(), input.data.unwrap().get_bytes());
+
+        assert!(number_bytes("256", 1).is_err());
+        assert!(input_from_yaml("caller: nobody\ncontract: attacker1\n").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+/// Detection of the token interfaces implemented by the targets
+///
+/// A target implements an interface if it answers `supportsInterface(id)` with true (ERC-165,
+/// only meaningful for ERC-721 / ERC-1155) or if it has all the functions of the interface. The
+/// detected interfaces enable the conformance oracles (see
+/// [`crate::evm::oracles::conformance`]) without manual flags.
+use crate::evm::input::EVMInput;
+use crate::evm::token_minting::address_word;
+use crate::evm::types::{EVMAddress, EVMFuzzState};
+use crate::evm::vm::{EVMExecutor, EVMState};
+use crate::generic_vm::vm_executor::GenericVM;
+use bytes::Bytes;
+use std::collections::HashSet;
+
+pub const SUPPORTS_INTERFACE: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenInterface {
+    ERC20,
+    ERC721,
+    ERC1155,
+    ERC4626,
+}
+
+impl TokenInterface {
+    pub fn all() -> [TokenInterface; 4] {
+        [Self::ERC20, Self::ERC721, Self::ERC1155, Self::ERC4626]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ERC20 => "ERC-20",
+            Self::ERC721 => "ERC-721",
+            Self::ERC1155 => "ERC-1155",
+            Self::ERC4626 => "ERC-4626",
+        }
+    }
+
+    /// ERC-165 interface id
+    pub fn interface_id(&self) -> Option<[u8; 4]> {
+        match self {
+            Self::ERC721 => Some([0x80, 0xac, 0x58, 0xcd]),
+            Self::ERC1155 => Some([0xd9, 0xb6, 0x7a, 0x26]),
+            _ => None,
+        }
+    }
+
+    /// Functions identifying the interface
+    pub fn selectors(&self) -> Vec<[u8; 4]> {
+        match self {
+            // totalSupply, balanceOf, transfer, transferFrom, approve, allowance
+            Self::ERC20 => vec![
+                [0x18, 0x16, 0x0d, 0xdd],
+                [0x70, 0xa0, 0x82, 0x31],
+                [0xa9, 0x05, 0x9c, 0xbb],
+                [0x23, 0xb8, 0x72, 0xdd],
+                [0x09, 0x5e, 0xa7, 0xb3],
+                [0xdd, 0x62, 0xed, 0x3e],
+            ],
+            // ownerOf, balanceOf, safeTransferFrom, transferFrom, setApprovalForAll, getApproved
+            Self::ERC721 => vec![
+                [0x63, 0x52, 0x21, 0x1e],
+                [0x70, 0xa0, 0x82, 0x31],
+                [0x42, 0x84, 0x2e, 0x0e],
+                [0x23, 0xb8, 0x72, 0xdd],
+                [0xa2, 0x2c, 0xb4, 0x65],
+                [0x08, 0x18, 0x12, 0xfc],
+            ],
+            // balanceOf, balanceOfBatch, safeTransferFrom, safeBatchTransferFrom
+            Self::ERC1155 => vec![
+                [0x00, 0xfd, 0xd5, 0x8e],
+                [0x4e, 0x12, 0x73, 0xf4],
+                [0xf2, 0x42, 0x43, 0x2a],
+                [0x2e, 0xb2, 0xc2, 0xd6],
+            ],
+            // asset, totalAssets, convertToShares, convertToAssets, deposit, redeem
+            Self::ERC4626 => vec![
+                [0x38, 0xd5, 0x2e, 0x0f],
+                [0x01, 0xe1, 0xd1, 0x14],
+                [0xc6, 0xe6, 0xf5, 0x92],
+                [0x07, 0xa2, 0xd1, 0x3a],
+                [0x6e, 0x55, 0x3f, 0x65],
+                [0xba, 0x08, 0x76, 0x52],
+            ],
+        }
+    }
+}
+
+/// Interfaces whose functions are all present
+pub fn detect_by_selectors(selectors: &HashSet<[u8; 4]>) -> Vec<TokenInterface> {
+    TokenInterface::all()
+        .into_iter()
+        .filter(|interface| interface.selectors().iter().all(|s| selectors.contains(s)))
+        .collect()
+}
+
+fn supports_interface_call(id: [u8; 4]) -> Bytes {
+    let mut word = [0u8; 32];
+    word[..4].copy_from_slice(&id);
+    Bytes::from([SUPPORTS_INTERFACE.to_vec(), word.to_vec()].concat())
+}
+
+/// Whether the call returned true
+fn returns_true(ret: &Vec<u8>) -> bool {
+    ret.len() >= 32 && ret[..31].iter().all(|b| *b == 0) && ret[31] == 1
+}
+
+/// Interfaces declared with ERC-165
+pub fn probe_interfaces(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    address: EVMAddress,
+) -> Vec<TokenInterface> {
+    if executor.host.code.get(&address).is_none() {
+        return vec![];
+    }
+    // ERC-165 requires supportsInterface(0xffffffff) to be false, which rules out fallbacks
+    // returning true for anything
+    let res = executor.fast_static_call(
+        &vec![
+            (address, supports_interface_call(SUPPORTS_INTERFACE)),
+            (address, supports_interface_call([0xff; 4])),
+        ],
+        vm_state,
+        state,
+    );
+    if !returns_true(&res[0]) || returns_true(&res[1]) {
+        return vec![];
+    }
+    TokenInterface::all()
+        .into_iter()
+        .filter(|interface| match interface.interface_id() {
+            Some(id) => {
+                let res = executor.fast_static_call(&vec![(address, supports_interface_call(id))], vm_state, state);
+                returns_true(&res[0])
+            }
+            None => false,
+        })
+        .collect()
+}
+
+/// Word `i` of the arguments of a calldata
+pub fn arg_word(calldata: &[u8], i: usize) -> Option<[u8; 32]> {
+    let start = 4 + 32 * i;
+    let mut word = [0u8; 32];
+    word.copy_from_slice(calldata.get(start..start + 32)?);
+    Some(word)
+}
+
+/// Address argument `i` of a calldata
+pub fn arg_address(calldata: &[u8], i: usize) -> Option<EVMAddress> {
+    arg_word(calldata, i).map(|word| EVMAddress::from_slice(&word[12..]))
+}
+
+/// Calldata of a call with address and word arguments
+pub fn encode_call(selector: [u8; 4], addresses: &[EVMAddress], words: &[[u8; 32]]) -> Bytes {
+    let mut data = selector.to_vec();
+    for address in addresses {
+        data.extend(address_word(address));
+    }
+    for word in words {
+        data.extend(word);
+    }
+    Bytes::from(data)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_by_selectors() {
+        let mut selectors = TokenInterface::ERC20.selectors().into_iter().collect::<HashSet<_>>();
+        assert_eq!(detect_by_selectors(&selectors), vec![TokenInterface::ERC20]);
+        selectors.extend(TokenInterface::ERC4626.selectors());
+        assert_eq!(
+            detect_by_selectors(&selectors),
+            vec![TokenInterface::ERC20, TokenInterface::ERC4626]
+        );
+        assert!(returns_true(&[vec![0; 31], vec![1]].concat()));
+        assert!(!returns_true(&vec![]));
+    }
+}
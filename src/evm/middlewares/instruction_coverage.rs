@@ -10,12 +10,15 @@ use libafl::inputs::Input;
 use libafl::prelude::{HasCorpus, HasMetadata, State};
 use revm_interpreter::Interpreter;
 use revm_primitives::Bytecode;
+use tracing::warn;
+use crate::evm::cov_merge::{CanonicalCoverage, ContractCoverage, COVERAGE_FILE};
 use crate::evm::host::FuzzHost;
 use crate::evm::input::EVMInputT;
 use crate::evm::middlewares::middleware::{Middleware, MiddlewareType};
 use crate::generic_vm::vm_state::VMStateT;
 use crate::input::VMInputT;
 use crate::state::{HasCaller, HasCurrentInputIdx, HasItyState};
+use crate::workdir::hash_bytes;
 // use crate::evm::types::EVMAddress;
 use crate::evm::types::{as_u64, EVMAddress, EVMU256, is_zero};
 
@@ -41,7 +44,13 @@ pub struct InstructionCoverage {
     pub pc_coverage: HashMap<EVMAddress, HashSet<usize>>,
     pub total_instr: HashMap<EVMAddress, usize>,
     pub total_instr_set: HashMap<EVMAddress, HashSet<usize>>,
+    /// Branches taken, `JUMPI` PC << 32 | PC executed next
     pub edge_coverage: HashMap<EVMAddress, HashSet<usize>>,
+    /// Hash of the code of each contract, identifying it across runs
+    pub code_hashes: HashMap<EVMAddress, String>,
+    /// Executions of each PC
+    pub pc_hits: HashMap<EVMAddress, HashMap<usize, u64>>,
+    pub codes: HashMap<EVMAddress, Vec<u8>>,
     pub cov_path: String,
 }
 
@@ -53,10 +62,38 @@ impl InstructionCoverage {
             total_instr: HashMap::new(),
             total_instr_set: HashMap::new(),
             edge_coverage: HashMap::new(),
+            code_hashes: HashMap::new(),
+            pc_hits: HashMap::new(),
+            codes: HashMap::new(),
             cov_path: cov_path,
         }
     }
 
+    /// PC indexed coverage, comparable across runs (see [`crate::evm::cov_merge`])
+    pub fn canonical_coverage(&self) -> CanonicalCoverage {
+        let mut coverage = CanonicalCoverage::default();
+        for (addr, hash) in &self.code_hashes {
+            coverage.contracts.insert(
+                hash.clone(),
+                ContractCoverage {
+                    address: format!("{:?}", addr),
+                    pcs: self.pc_coverage.get(addr).cloned().unwrap_or_default().into_iter().collect(),
+                    branches: self
+                        .edge_coverage
+                        .get(addr)
+                        .cloned()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|edge| (edge >> 32, edge & 0xffffffff))
+                        .collect(),
+                    hits: self.pc_hits.get(addr).cloned().unwrap_or_default().into_iter().collect(),
+                    code: self.codes.get(addr).map(hex::encode).unwrap_or_default(),
+                },
+            );
+        }
+        coverage
+    }
+
     pub fn record_instruction_coverage(&mut self) {
         // println!("total_instr: {:?}", self.total_instr);
         // println!("total_instr_set: {:?}", self.total_instr_set);
@@ -119,6 +156,11 @@ impl InstructionCoverage {
             .open(self.cov_path.clone())
             .unwrap();
         file.write_all(data.as_bytes()).unwrap();
+
+        let canonical_path = Path::new(&self.cov_path).with_file_name(COVERAGE_FILE);
+        if let Err(e) = self.canonical_coverage().save(&canonical_path.to_string_lossy()) {
+            warn!("failed to write {}: {}", canonical_path.display(), e);
+        }
     }
 }
 
@@ -145,38 +187,30 @@ impl<I, VS, S> Middleware<VS, I, S> for InstructionCoverage
         let address = interp.contract.address;
         let pc = interp.program_counter().clone();
         self.pc_coverage.entry(address).or_default().insert(pc);
+        *self.pc_hits.entry(address).or_default().entry(pc).or_default() += 1;
 
-        // let pc = interp.program_counter().clone() as u64;
-        // macro_rules! fast_peek {
-        //     ($idx:expr) => {
-        //         interp.stack.data()[interp.stack.len() - 1 - $idx]
-        //     };
-        // }
-        // match *interp.instruction_pointer {
-        //     0x56 => { // JUMP
-        //         // println!("fd {} @ {:?}", interp.program_counter(), interp.contract.address);
-        //         let jump_dest = as_u64(fast_peek!(0));
-        //         let edge = pc << 32 | jump_dest;
-        //         self.pc_coverage.entry(address).or_default().insert(edge as usize);
-        //     }
-        //     0x57 => { // JUMPI
-        //         let br = fast_peek!(1);
-        //         let jump_dest = if is_zero(br) {
-        //             pc + 1
-        //         } else {
-        //             as_u64(fast_peek!(0))
-        //         };
-        //         let edge = pc << 32 | jump_dest;
-        //         self.pc_coverage.entry(address).or_default().insert(edge as usize);
-        //     }
-        //     _ => {}
-        // }
+        macro_rules! fast_peek {
+            ($idx:expr) => {
+                interp.stack.data()[interp.stack.len() - 1 - $idx]
+            };
+        }
+        if *interp.instruction_pointer == 0x57 && interp.stack.len() >= 2 {
+            // JUMPI
+            let jump_dest = if is_zero(fast_peek!(1)) {
+                pc + 1
+            } else {
+                as_u64(fast_peek!(0)) as usize
+            };
+            self.edge_coverage.entry(address).or_default().insert(pc << 32 | jump_dest);
+        }
     }
 
     unsafe fn on_insert(&mut self, bytecode: &mut Bytecode, address: EVMAddress, host: &mut FuzzHost<VS, I, S>, state: &mut S) {
         let pcs = instructions_pc(&bytecode.clone());
         self.total_instr.insert(address, pcs.len());
         self.total_instr_set.insert(address, pcs);
+        self.code_hashes.insert(address, hash_bytes(bytecode.bytes()));
+        self.codes.insert(address, bytecode.bytes().to_vec());
     }
 
     fn get_type(&self) -> MiddlewareType {
@@ -1,20 +1,82 @@
 pub mod abi;
+pub mod address_pool;
+pub mod block_advance;
+pub mod adversary;
+pub mod amm;
+pub mod branch_coverage;
+pub mod bug_likelihood;
 pub mod bytecode_analyzer;
+pub mod bytes_mutation;
+pub mod call_graph;
+pub mod callers;
+pub mod call_value;
+pub mod cfg_refinement;
 pub mod concolic;
 pub mod config;
+pub mod contract_metadata;
 pub mod contract_utils;
+pub mod control;
+pub mod corpus_format;
 pub mod corpus_initializer;
+pub mod cov_map;
+pub mod cov_merge;
+pub mod diamond;
+pub mod dictionary;
+pub mod directed;
+pub mod distill;
+pub mod entry_points;
+pub mod evm_version;
+pub mod exploit_export;
+pub mod flashloan_provider;
+pub mod force_feed;
+pub mod grammar;
+pub mod fuzz_target;
+pub mod goals;
+pub mod hex_utils;
+pub mod hooks;
 pub mod host;
+pub mod immutables;
+pub mod initializer;
 pub mod input;
+pub mod input_yaml;
+pub mod interfaces;
 pub mod middlewares;
 pub mod mutation_utils;
 pub mod mutator;
 pub mod onchain;
+pub mod opcode_stats;
 pub mod oracle;
 pub mod oracles;
+pub mod packs;
+pub mod pausable;
 pub mod presets;
+pub mod price_feeds;
 pub mod producers;
+pub mod provenance;
+pub mod project;
+pub mod ptx_binding;
+pub mod revert_stats;
+pub mod sanitizers;
+pub mod scenario;
+pub mod seed_synthesis;
+pub mod shell;
+pub mod solution_dedup;
 pub mod srcmap;
+pub mod state_diff;
+pub mod storage_alias;
+pub mod storage_layout;
+pub mod storage_write;
+pub mod summary;
+pub mod target_spec;
+pub mod token_flow;
+pub mod token_minting;
+pub mod triage;
+pub mod tx_gas;
 pub mod types;
 pub mod uniswap;
+pub mod value_hints;
+pub mod view_invariants;
 pub mod vm;
+pub mod warm_start;
+pub mod weird_tokens;
+pub mod withdrawal;
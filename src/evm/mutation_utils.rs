@@ -15,6 +15,11 @@ use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 use crate::evm::types::EVMU256;
+use crate::evm::vm::EVMStorage;
+use crate::evm::address_pool::CURRENT_TARGET;
+use crate::evm::storage_layout::storage_layout;
+use crate::evm::dictionary::is_learned_operand;
+use crate::evm::provenance::{record_hint, ArgProvenance};
 
 /// Constants in the contracts
 ///
@@ -27,6 +32,33 @@ pub struct ConstantPoolMetadata {
 
 impl_serdeany!(ConstantPoolMetadata);
 
+/// Constants guarding the branches of each function, e.g., the magic number checked by
+/// `unlock(uint256 code)`, and the function whose args are being mutated
+///
+/// This is metadata attached to the global fuzz state
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SelectorConstantsMetadata {
+    /// Function hash -> constants
+    pub constants: HashMap<[u8; 4], Vec<Vec<u8>>>,
+    /// Function hash of the input being mutated
+    pub current: Option<[u8; 4]>,
+}
+
+impl_serdeany!(SelectorConstantsMetadata);
+
+/// Percentage of constant hints taken from the dictionary of the function being mutated
+pub const SELECTOR_CONSTANT_PROB: u64 = 80;
+
+/// Set the function whose args are going to be mutated
+pub fn set_mutated_selector<S>(state: &mut S, function: [u8; 4])
+where
+    S: HasMetadata,
+{
+    if let Some(meta) = state.metadata_mut().get_mut::<SelectorConstantsMetadata>() {
+        meta.current = Some(function);
+    }
+}
+
 /// [`ConstantHintedMutator`] is a mutator that mutates the input to a constant in the contract
 ///
 /// We discover that sometimes directly setting the bytes to the constants allow us to increase
@@ -58,9 +90,26 @@ where
         _stage_idx: i32,
     ) -> Result<MutationResult, Error> {
         let idx = state.rand_mut().next() as usize;
-        let constant = match state.metadata().get::<ConstantPoolMetadata>() {
-            Some(meta) => unsafe { meta.constants.get_unchecked(idx % meta.constants.len()) },
-            None => return Ok(MutationResult::Skipped),
+        let use_selector_constants = state.rand_mut().below(100) < SELECTOR_CONSTANT_PROB;
+        let selector_constants = match state.metadata().get::<SelectorConstantsMetadata>() {
+            Some(meta) if use_selector_constants => meta
+                .current
+                .and_then(|function| meta.constants.get(&function))
+                .filter(|constants| !constants.is_empty()),
+            _ => None,
+        };
+        let constant = match selector_constants {
+            Some(constants) => unsafe { constants.get_unchecked(idx % constants.len()) },
+            None => match state.metadata().get::<ConstantPoolMetadata>() {
+                Some(meta) => unsafe { meta.constants.get_unchecked(idx % meta.constants.len()) },
+                None => return Ok(MutationResult::Skipped),
+            },
+        };
+        // the constants guarding the branches of the function are comparison operands as well
+        let provenance = if selector_constants.is_some() || is_learned_operand(constant) {
+            ArgProvenance::Comparison
+        } else {
+            ArgProvenance::Dictionary
         };
 
         let input_bytes = input.bytes_mut();
@@ -73,6 +122,7 @@ where
             input_bytes
                 .copy_from_slice(&[vec![0; input_len - constant_len], constant.clone()].concat());
         }
+        record_hint(provenance, input.bytes());
         Ok(MutationResult::Mutated)
     }
 }
@@ -82,7 +132,7 @@ where
 /// Similar to [`ConstantHintedMutator`], we discover that sometimes directly setting the bytes to
 /// the values in the VM state allow us to increase test coverage.
 pub struct VMStateHintedMutator<'a> {
-    pub vm_slots: &'a HashMap<EVMU256, EVMU256>,
+    pub vm_slots: &'a EVMStorage,
 }
 
 impl Named for VMStateHintedMutator<'_> {
@@ -92,25 +142,34 @@ impl Named for VMStateHintedMutator<'_> {
 }
 
 impl<'a> VMStateHintedMutator<'a> {
-    pub fn new(vm_slots: &'a HashMap<EVMU256, EVMU256>) -> Self {
+    pub fn new(vm_slots: &'a EVMStorage) -> Self {
         Self { vm_slots }
     }
 }
 
-/// Mutate the input to a value in the VM state
+/// Mutate the input to a value in the VM state, returns the slot sampled and the value taken
+/// from it (its content or the slot itself)
 pub fn mutate_with_vm_slot<S: State + HasRand>(
-    vm_slots: &HashMap<EVMU256, EVMU256>,
+    vm_slots: &EVMStorage,
     state: &mut S,
-) -> EVMU256 {
+) -> (EVMU256, EVMU256) {
     // sample a key from the vm_state.state
     let idx = state.rand_mut().below(vm_slots.len() as u64) as usize;
     let key = vm_slots.keys().nth(idx).unwrap();
-    if state.rand_mut().below(100) < 90 {
+    let value = if state.rand_mut().below(100) < 90 {
         let value = vm_slots.get(key).unwrap();
-        value.clone()
+        // a slot packing several variables yields one of them
+        match unsafe { CURRENT_TARGET }.and_then(|target| storage_layout(&target)) {
+            Some(layout) => {
+                let values = layout.unpack(key, *value);
+                values[state.rand_mut().below(values.len() as u64) as usize]
+            }
+            None => value.clone(),
+        }
     } else {
         key.clone()
-    }
+    };
+    (*key, value)
 }
 
 impl<'a, I, S> Mutator<I, S> for VMStateHintedMutator<'a>
@@ -129,11 +188,12 @@ where
         if input_len < 8 {
             return Ok(MutationResult::Skipped);
         }
-        let new_val = mutate_with_vm_slot(self.vm_slots, state);
+        let (slot, new_val) = mutate_with_vm_slot(self.vm_slots, state);
 
         let mut data: [u8; 32] = new_val.to_be_bytes();
 
         input.bytes_mut().copy_from_slice(&data[(32 - input_len)..]);
+        record_hint(ArgProvenance::Storage(slot), input.bytes());
         Ok(MutationResult::Mutated)
     }
 }
@@ -144,7 +204,7 @@ where
 pub fn byte_mutator<I, S>(
     state: &mut S,
     input: &mut I,
-    vm_slots: Option<HashMap<EVMU256, EVMU256>>,
+    vm_slots: Option<EVMStorage>,
 ) -> MutationResult
 where
     S: State + HasRand + HasMetadata,
@@ -185,7 +245,7 @@ where
 pub fn byte_mutator_with_expansion<I, S>(
     state: &mut S,
     input: &mut I,
-    vm_slots: Option<HashMap<EVMU256, EVMU256>>,
+    vm_slots: Option<EVMStorage>,
 ) -> MutationResult
 where
     S: State + HasRand + HasMaxSize,
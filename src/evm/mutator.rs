@@ -17,17 +17,31 @@ use crate::evm::input::EVMInputTy::Borrow;
 use std::fmt::Debug;
 use revm_interpreter::Interpreter;
 use crate::evm::types::{convert_u256_to_h160, EVMAddress};
+use crate::evm::adversary::{adversary_count, ADVERSARY_MUTATION_PROBABILITY};
+use crate::evm::price_feeds::{mutate_feed_prices, price_feed_count, PRICE_MUTATION_PROBABILITY};
+use crate::evm::force_feed::{mutate_force_feed, FORCE_FEED, FORCE_FEED_MUTATION_PROBABILITY};
+use crate::evm::tx_gas::{mutate_gas_limit, GAS_LIMIT_MUTATION_PROBABILITY, GAS_METERING};
+use crate::evm::block_advance::{block_advance_enabled, mutate_block_advance, BLOCK_ADVANCE_MUTATION_PROBABILITY};
+use crate::evm::scenario::{scenario_count, scheduled_scenario};
 
 use crate::state::HasItyState;
 use crate::state_input::StagedVMState;
 
+/// Env opcodes executed by a GPU thread, as reported by the runner (`cuReadEnvAccess`)
+pub const GPU_ENV_TIMESTAMP: u32 = 1;
+pub const GPU_ENV_NUMBER: u32 = 1 << 1;
+pub const GPU_ENV_CALLER: u32 = 1 << 2;
+pub const GPU_ENV_ORIGIN: u32 = 1 << 3;
+pub const GPU_ENV_BASEFEE: u32 = 1 << 4;
+
 /// [`AccessPattern`] records the access pattern of the input during execution. This helps
 /// to determine what is needed to be fuzzed. For instance, we don't need to mutate caller
 /// if the execution never uses it.
 ///
 /// Each mutant should report to its parent's access pattern
 /// if a new corpus item is added, it should inherit the access pattern of its source
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AccessPattern {
     pub caller: bool,       // or origin
     pub balance: Vec<EVMAddress>, // balance queried for accounts
@@ -60,6 +74,16 @@ impl AccessPattern {
         }
     }
 
+    /// Record the env opcodes executed by a GPU thread, reported by the runner as a bitmask of
+    /// [`GPU_ENV_TIMESTAMP`], [`GPU_ENV_NUMBER`], [`GPU_ENV_CALLER`], [`GPU_ENV_ORIGIN`] and
+    /// [`GPU_ENV_BASEFEE`]
+    pub fn merge_gpu_env_access(&mut self, mask: u32) {
+        self.timestamp |= mask & GPU_ENV_TIMESTAMP != 0;
+        self.number |= mask & GPU_ENV_NUMBER != 0;
+        self.caller |= mask & (GPU_ENV_CALLER | GPU_ENV_ORIGIN) != 0;
+        self.basefee |= mask & GPU_ENV_BASEFEE != 0;
+    }
+
     /// Record access pattern of current opcode executed by the interpreter
     pub fn decode_instruction(&mut self, interp: &Interpreter) {
         match unsafe { *interp.instruction_pointer } {
@@ -138,6 +162,13 @@ where
             input.set_staged_state(concrete.1, concrete.0);
         }
 
+        // the scenario presets are cycled per scheduled corpus entry, its mutants run under the
+        // scenario handed out to it (a control leak resumes under the scenario it leaked in)
+        if scenario_count() > 0 && !input.is_step() {
+            let scenario = scheduled_scenario(state);
+            input.set_scenario(scenario);
+        }
+
         // determine whether we should conduct havoc
         // (a sequence of mutations in batch vs single mutation)
         let should_havoc = state.rand_mut().below(100) < 60;
@@ -199,6 +230,53 @@ where
                 }
             }
 
+            // inject or change the adversary transaction landing before the input
+            if adversary_count() > 0 && !input.is_step() && state.rand_mut().below(100) < ADVERSARY_MUTATION_PROBABILITY {
+                let count = adversary_count() as u64;
+                let adversary = match state.rand_mut().below(count + 1) {
+                    idx if idx == count => None,
+                    idx => Some(idx as usize),
+                };
+                if adversary != input.get_adversary() {
+                    input.set_adversary(adversary);
+                    return MutationResult::Mutated;
+                }
+            }
+
+            // manipulate the price reported by a price feed
+            if price_feed_count() > 0 && state.rand_mut().below(100) < PRICE_MUTATION_PROBABILITY {
+                if mutate_feed_prices(input.get_feed_prices_mut(), state) {
+                    return MutationResult::Mutated;
+                }
+            }
+
+            // execute the input in the same block, the next one or a day after the previous one
+            if block_advance_enabled() && !input.is_step() && state.rand_mut().below(100) < BLOCK_ADVANCE_MUTATION_PROBABILITY {
+                let advance = mutate_block_advance(state);
+                if advance != input.get_block_advance() {
+                    input.set_block_advance(advance);
+                    return MutationResult::Mutated;
+                }
+            }
+
+            // force-feed ether to the target before the input
+            if unsafe { FORCE_FEED } && !input.is_step() && state.rand_mut().below(100) < FORCE_FEED_MUTATION_PROBABILITY {
+                let force_feed = mutate_force_feed(input.get_force_feed(), state);
+                if force_feed != input.get_force_feed() {
+                    input.set_force_feed(force_feed);
+                    return MutationResult::Mutated;
+                }
+            }
+
+            // lower the gas limit of the transaction, starving its inner calls
+            if unsafe { GAS_METERING } && !input.is_step() && state.rand_mut().below(100) < GAS_LIMIT_MUTATION_PROBABILITY {
+                let gas_limit = mutate_gas_limit(input.get_gas_limit(), state);
+                if gas_limit != input.get_gas_limit() {
+                    input.set_gas_limit(gas_limit);
+                    return MutationResult::Mutated;
+                }
+            }
+
             // mutate the bytes or VM state or liquidation percent (percentage of token to liquidate)
             // by default
             match state.rand_mut().below(100) {
@@ -1,17 +1,81 @@
-use crate::evm::contract_utils::ABIConfig;
-use heimdall::decompile::decompile_with_bytecode;
+use crate::evm::contract_metadata::strip_metadata_hex;
+use crate::evm::abi::register_function_args;
+use crate::evm::onchain::signature_workers::request_function;
+use crate::evm::contract_utils::{set_hash, ABIConfig};
+use crate::evm::ptx_binding::bytecode_hash;
+use crate::evm::hex_utils::Selector;
+use crate::error::{MauError, MauResult};
+use crate::workdir::artifacts_dir;
+use heimdall::decompile::{decompile_jump_edges, decompile_selector_constants, decompile_with_bytecode};
 use heimdall::decompile::output::ABIStructure;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::warn;
 
-pub fn fetch_abi_heimdall(bytecode: String) -> Vec<ABIConfig> {
-    let output_dir = "/tmp/heimdall";
+/// Analyses of each bytecode, shared by the campaigns started from the same directory and
+/// filled ahead of them by the workers of [`crate::campaign`]
+pub const HEIMDALL_CACHE_DIR: &str = "cache/heimdall";
 
-    if !Path::new(output_dir).exists() {
-        fs::create_dir(output_dir).unwrap();
+/// Text signatures of the selectors resolved so far, so that later runs name the same functions
+/// offline
+pub const SIGNATURES_FILE: &str = "cache/signatures.json";
+
+/// JSON ABI of the decompiled functions whose names are resolved, in the artifacts of the run
+pub const DECOMPILED_ABI_FILE: &str = "decompiled.abi";
+
+/// Whether the selectors not resolved yet are looked up in the signature database
+/// (`--resolve-signatures`)
+pub static mut RESOLVE_SIGNATURES: bool = false;
+
+static OUTPUT_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static OUTPUT_DIR: usize = OUTPUT_DIRS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Directory heimdall writes its disassembly to, one per thread as the workers of a campaign
+/// analyse targets concurrently
+fn heimdall_output_dir() -> MauResult<String> {
+    let output_dir = format!("/tmp/heimdall/{}-{}", std::process::id(), OUTPUT_DIR.with(|idx| *idx));
+
+    if !Path::new(&output_dir).exists() {
+        fs::create_dir_all(&output_dir).map_err(|e| MauError::io(&output_dir, e))?;
+    }
+    Ok(output_dir)
+}
+
+/// Result of the analysis of the bytecode, from the cache or stored in it
+fn cached<T, F>(kind: &str, bytecode: &str, analysis: F) -> MauResult<T>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> MauResult<T>,
+{
+    let path = Path::new(HEIMDALL_CACHE_DIR).join(format!("{}-{}.json", kind, bytecode_hash(bytecode.as_bytes())));
+    if let Some(value) = fs::read_to_string(&path).ok().and_then(|json| serde_json::from_str(&json).ok()) {
+        return Ok(value);
     }
+    let value = analysis()?;
+    // written aside then renamed, concurrent campaigns never read a partial file
+    let tmp = path.with_extension(format!("{}-{}", std::process::id(), OUTPUT_DIR.with(|idx| *idx)));
+    let stored = fs::create_dir_all(HEIMDALL_CACHE_DIR)
+        .and_then(|_| fs::write(&tmp, serde_json::to_string(&value).unwrap()))
+        .and_then(|_| fs::rename(&tmp, &path));
+    if let Err(e) = stored {
+        return Err(MauError::io(&path.to_string_lossy(), e));
+    }
+    Ok(value)
+}
 
-    let heimdall_result = decompile_with_bytecode(bytecode, output_dir.to_string());
+pub fn fetch_abi_heimdall(bytecode: String) -> MauResult<Vec<ABIConfig>> {
+    let output_dir = heimdall_output_dir()?;
+
+    let heimdall_result = decompile_with_bytecode(strip_metadata_hex(&bytecode), output_dir);
 
     let mut result = vec![];
     for heimdall_abi in heimdall_result {
@@ -28,17 +92,22 @@ pub fn fetch_abi_heimdall(bytecode: String) -> Vec<ABIConfig> {
                 }
 
                 let name = func.name.replace("Unresolved_", "");
-                let mut abi_config = ABIConfig {
+                // the other functions of the contract are still fuzzed
+                let selector = match Selector::from_str(&name) {
+                    Ok(selector) => selector,
+                    Err(e) => {
+                        warn!("decompiler: skipping the function {}, not named after its selector: {}", func.name, e);
+                        continue;
+                    }
+                };
+                let abi_config = ABIConfig {
                     abi: format!("({})", inputs.join(",")),
-                    function: [0; 4],
+                    function: selector.0,
                     function_name: name.clone(),
-                    is_static: func.state_mutability == "view",
+                    is_static: matches!(func.state_mutability.as_str(), "view" | "pure"),
                     is_payable: func.state_mutability == "payable",
                     is_constructor: false,
                 };
-                abi_config
-                    .function
-                    .copy_from_slice(hex::decode(name).unwrap().as_slice());
                 result.push(abi_config)
             }
             _ => {
@@ -47,16 +116,256 @@ pub fn fetch_abi_heimdall(bytecode: String) -> Vec<ABIConfig> {
         }
     }
 
-    result
+    resolve_function_selectors(&mut result);
+    Ok(result)
+}
+
+/// Number of arguments of `(type,...)`, tuples counting as one
+pub(crate) fn arg_count(args: &str) -> usize {
+    let inner = args.trim_start_matches('(').trim_end_matches(')');
+    if inner.is_empty() {
+        return 0;
+    }
+    let mut depth = 0;
+    let mut count = 1;
+    for c in inner.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Signature among the candidates hashing to the selector, preferring the ones taking as many
+/// arguments as the decompiled function
+pub(crate) fn pick_signature(selector: &[u8; 4], args: usize, candidates: Vec<String>) -> Option<String> {
+    let matching = candidates
+        .into_iter()
+        .filter(|signature| {
+            let mut hash = [0u8; 4];
+            set_hash(signature, &mut hash);
+            hash == *selector
+        })
+        .collect::<Vec<_>>();
+    matching
+        .iter()
+        .find(|signature| signature.find('(').map_or(false, |i| arg_count(&signature[i..]) == args))
+        .or(matching.first())
+        .cloned()
+}
+
+fn load_signatures() -> BTreeMap<String, String> {
+    fs::read_to_string(SIGNATURES_FILE)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Persist the signature of a selector in [`SIGNATURES_FILE`]
+pub(crate) fn store_signature(selector: &str, signature: &str) {
+    let mut signatures = load_signatures();
+    signatures.insert(selector.to_string(), signature.to_string());
+    let stored = Path::new(SIGNATURES_FILE)
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(SIGNATURES_FILE, serde_json::to_string_pretty(&signatures).unwrap()));
+    if let Err(e) = stored {
+        warn!("failed to write {}: {}", SIGNATURES_FILE, e);
+    }
+}
+
+/// Name the function after its signature, and register its args to decode its calls
+pub(crate) fn apply_signature(abi: &mut ABIConfig, signature: &str) {
+    let (name, types) = parse_signature(signature);
+    abi.function_name = name;
+    abi.abi = format!("({})", types.join(","));
+    register_function_args(abi.function, types.into_iter().map(|ty| (String::new(), ty)).collect());
+}
+
+/// Add the resolved functions to the ABI emitted in the artifacts of the run
+pub(crate) fn emit_abi(abis: &Vec<ABIConfig>) {
+    let path = Path::new(&artifacts_dir()).join(DECOMPILED_ABI_FILE);
+    let mut entries: Vec<Value> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    for abi in abis {
+        let name = abi.function_name.as_str();
+        if entries.iter().any(|e| e["name"] == name && e["selector"] == hex::encode(abi.function)) {
+            continue;
+        }
+        let (_, types) = parse_signature(&format!("{}{}", name, abi.abi));
+        entries.push(json!({
+            "type": "function",
+            "name": name,
+            "selector": hex::encode(abi.function),
+            "inputs": types.iter().map(|ty| json!({"name": "", "type": ty})).collect::<Vec<_>>(),
+            "outputs": [],
+            "stateMutability": if abi.is_static { "view" } else if abi.is_payable { "payable" } else { "nonpayable" },
+        }));
+    }
+    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&entries).unwrap()) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+/// Name and argument types of a text signature
+pub fn parse_signature(signature: &str) -> (String, Vec<String>) {
+    let start = signature.find('(').unwrap_or(signature.len());
+    let args = signature[start..].trim_start_matches('(').trim_end_matches(')');
+    let mut types = vec![];
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in args.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        types.push(current);
+    }
+    (signature[..start].to_string(), types)
+}
+
+/// Name the decompiled functions (named after their selectors) from the signatures resolved
+/// before. With `--resolve-signatures`, the others are looked up in the signature database by the
+/// background workers (see [`crate::evm::onchain::signature_workers`]) and named when the answer
+/// arrives, the analysis never waiting on the network. The names are persisted in
+/// [`SIGNATURES_FILE`] and in the ABI emitted in the artifacts of the run. Returns the number of
+/// functions named from the cache.
+pub fn resolve_function_selectors(abis: &mut Vec<ABIConfig>) -> usize {
+    let signatures = load_signatures();
+    let mut resolved = vec![];
+    for abi in abis.iter_mut() {
+        let selector = hex::encode(abi.function);
+        if abi.function_name != selector {
+            continue;
+        }
+        match signatures.get(&selector) {
+            Some(signature) => {
+                apply_signature(abi, signature);
+                resolved.push(abi.clone());
+            }
+            None if unsafe { RESOLVE_SIGNATURES } => request_function(abi.clone()),
+            None => {}
+        }
+    }
+    if !resolved.is_empty() {
+        emit_abi(&resolved);
+    }
+    resolved.len()
+}
+
+/// Constants guarding the branches of each function, in big-endian without leading zeros
+pub fn fetch_selector_constants_heimdall(bytecode: String) -> MauResult<HashMap<[u8; 4], Vec<Vec<u8>>>> {
+    let bytecode = strip_metadata_hex(&bytecode);
+    let constants: Vec<([u8; 4], Vec<Vec<u8>>)> = cached("constants", &bytecode, || {
+        let mut result = vec![];
+        for (selector, constants) in decompile_selector_constants(bytecode.clone(), heimdall_output_dir()?) {
+            let function = match Selector::from_str(&selector) {
+                Ok(selector) => selector.0,
+                Err(_) => continue,
+            };
+            let constants = constants
+                .iter()
+                .map(|constant| {
+                    let mut data = [0u8; 32];
+                    constant.to_big_endian(&mut data);
+                    let start = data.iter().position(|b| *b != 0).unwrap_or(31);
+                    data[start..].to_vec()
+                })
+                .collect();
+            result.push((function, constants));
+        }
+        Ok(result)
+    })?;
+    Ok(constants.into_iter().collect())
+}
+
+/// Entry point of each function and the (JUMP/JUMPI pc, destination) pairs resolved by heimdall,
+/// completed with the jumps observed at runtime that it missed (see
+/// [`crate::evm::cfg_refinement`])
+pub fn fetch_jump_edges_heimdall(bytecode: String) -> MauResult<(HashMap<[u8; 4], usize>, Vec<(usize, usize)>)> {
+    let bytecode = strip_metadata_hex(&bytecode);
+    let (entry_points, mut edges): (Vec<([u8; 4], usize)>, Vec<(usize, usize)>) = cached("jumps", &bytecode, || {
+        let (entry_points, edges) = decompile_jump_edges(bytecode.clone(), heimdall_output_dir()?);
+        Ok(jump_edges(entry_points, edges))
+    })?;
+    edges.extend(load_observed_jump_edges(&bytecode));
+    edges.sort();
+    edges.dedup();
+    Ok((entry_points.into_iter().collect(), edges))
+}
+
+fn observed_jump_edges_path(bytecode: &str) -> PathBuf {
+    Path::new(HEIMDALL_CACHE_DIR).join(format!("observed-{}.json", bytecode_hash(bytecode.as_bytes())))
+}
+
+fn load_observed_jump_edges(bytecode: &str) -> Vec<(usize, usize)> {
+    fs::read_to_string(observed_jump_edges_path(bytecode))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Add jumps observed at runtime to the ones of the bytecode, for the analyses of this run and
+/// of the later ones
+pub fn store_observed_jump_edges(bytecode: String, edges: &Vec<(usize, usize)>) -> MauResult<()> {
+    let bytecode = strip_metadata_hex(&bytecode);
+    let path = observed_jump_edges_path(&bytecode);
+    let mut observed = load_observed_jump_edges(&bytecode);
+    observed.extend(edges.iter().cloned());
+    observed.sort();
+    observed.dedup();
+    fs::create_dir_all(HEIMDALL_CACHE_DIR)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&observed).unwrap()))
+        .map_err(|e| MauError::io(&path.to_string_lossy(), e))
+}
+
+fn jump_edges(entry_points: HashMap<String, u64>, edges: Vec<(u128, u128)>) -> (Vec<([u8; 4], usize)>, Vec<(usize, usize)>) {
+    let entry_points = entry_points
+        .into_iter()
+        .filter_map(|(selector, entry_point)| {
+            Some((Selector::from_str(&selector).ok()?.0, entry_point as usize))
+        })
+        .collect();
+    let edges = edges
+        .into_iter()
+        .map(|(from, to)| (from as usize, to as usize))
+        .collect();
+    (entry_points, edges)
 }
 
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pick_signature() {
+        let transfer = [0xa9, 0x05, 0x9c, 0xbb];
+        let candidates = vec!["transfer(address,uint256)".to_string(), "approve(address,uint256)".to_string()];
+        assert_eq!(pick_signature(&transfer, 2, candidates), Some("transfer(address,uint256)".to_string()));
+        assert_eq!(arg_count("((uint256,address),bytes)"), 2);
+        assert_eq!(arg_count("()"), 0);
+        assert_eq!(
+            parse_signature("swap((uint256,address),bytes)"),
+            ("swap".to_string(), vec!["(uint256,address)".to_string(), "bytes".to_string()])
+        );
+    }
+
     #[test]
     fn test_heimdall() {
         println!("{:?}", fetch_abi_heimdall(
             "0x6080604052600436106101395760003560e01c8063715018a6116100ab578063b6fccf8a1161006f578063b6fccf8a1461035e578063db006a751461037e578063dd62ed3e1461039e578063eb37acfc146103d6578063f2fde38b146103f6578063fec362351461041657600080fd5b8063715018a6146102e15780638da5cb5b146102f657806395d89b4114610314578063a9059cbb14610329578063b69ef8a81461034957600080fd5b80632f34d282116100fd5780632f34d282146101f057806330d5baea14610210578063313ce567146102305780634d95cad91461025c57806351cff8d91461029457806370a08231146102b457600080fd5b806306fdde031461014e578063095ea7b31461017957806318160ddd146101a95780631d9053e0146101c857806323b872dd146101d057600080fd5b366101495761014733610436565b005b600080fd5b34801561015a57600080fd5b506101636104e2565b60405161017091906115d2565b60405180910390f35b34801561018557600080fd5b5061019961019436600461161a565b610570565b6040519015158152602001610170565b3480156101b557600080fd5b506005545b604051908152602001610170565b6101476105dc565b3480156101dc57600080fd5b506101996101eb366004611646565b6105f9565b3480156101fc57600080fd5b5061014761020b366004611687565b61077d565b34801561021c57600080fd5b5061014761022b3660046116a4565b6107f5565b34801561023c57600080fd5b5060045461024a9060ff1681565b60405160ff9091168152602001610170565b34801561026857600080fd5b5060075461027c906001600160a01b031681565b6040516001600160a01b039091168152602001610170565b3480156102a057600080fd5b506101476102af366004611687565b6108cd565b3480156102c057600080fd5b506101ba6102cf366004611687565b60096020526000908152604090205481565b3480156102ed57600080fd5b506101476109a9565b34801561030257600080fd5b506001546001600160a01b031661027c565b34801561032057600080fd5b506101636109bb565b34801561033557600080fd5b5061019961034436600461161a565b6109c8565b34801561035557600080fd5b506006546101ba565b34801561036a57600080fd5b5060085461027c906001600160a01b031681565b34801561038a57600080fd5b506101476103993660046116a4565b6109dc565b3480156103aa57600080fd5b506101ba6103b93660046116bd565b600a60209081526000928352604080842090915290825290205481565b3480156103e257600080fd5b506101476103f13660046116a4565b610b04565b34801561040257600080fd5b50610147610411366004611687565b610f3e565b34801561042257600080fd5b506101476104313660046116a4565b610fb4565b6007546001600160a01b03828116911614610479576001600160a01b0381166000908152600960205260408120805434929061047390849061170c565b90915550505b346005600082825461048b919061170c565b9091555061049c9050346001610fcf565b806001600160a01b03167fe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c346040516104d791815260200190565b60405180910390a250565b600280546104ef90611724565b80601f016020809104026020016040519081016040528092919081815260200182805461051b90611724565b80156105685780601f1061053d57610100808354040283529160200191610568565b820191906000526020600020905b81548152906001019060200180831161054b57829003601f168201915b505050505081565b336000818152600a602090815260408083206001600160a01b038716808552925280832085905551919290917f8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925906105cb9086815260200190565b60405180910390a350600192915050565b6105e4611011565b6105ed33610436565b6105f76001600055565b565b6001600160a01b03831660009081526009602052604081205482111561061e57600080fd5b6001600160a01b038416331480159061065c57506001600160a01b0384166000908152600a6020908152604080832033845290915290205460001914155b156106ca576001600160a01b0384166000908152600a6020908152604080832033845290915290205482111561069157600080fd5b6001600160a01b0384166000908152600a60209081526040808320338452909152812080548492906106c490849061175f565b90915550505b6001600160a01b038416600090815260096020526040812080548492906106f290849061175f565b90915550506001600160a01b0383166000908152600960205260408120805484929061071f90849061170c565b92505081905550826001600160a01b0316846001600160a01b03167fddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef8460405161076b91815260200190565b60405180910390a35060019392505050565b61078561106b565b6001600160a01b0381166107d35760405162461bcd60e51b815260206004820152601060248201526f4e4f4e205a45524f204144445245535360801b60448201526064015b60405180910390fd5b600880546001600160a01b0319166001600160a01b0392909216919091179055565b6107fd611011565b3360009081526009602052604090205481111561082c5760405162461bcd60e51b81526004016107ca90611776565b336000908152600960205260408120805483929061084b90849061175f565b925050819055508060056000828254610864919061175f565b9091555050600754610880906001600160a01b031633836110c5565b61088b816000610fcf565b60405181815233907f7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b659060200160405180910390a26108ca6001600055565b50565b6108d561106b565b63637a874042101561091c5760405162461bcd60e51b815260206004820152601060248201526f1413d3d3081393d5081156141254915160821b60448201526064016107ca565b6001600160a01b038116610934576108ca33476111f6565b6040516370a0823160e01b81523060048201526108ca90829033906001600160a01b038316906370a0823190602401602060405180830381865afa158015610980573d6000803e3d6000fd5b505050506040513d601f19601f820116820180604052508101906109a491906117a1565b6110c5565b6109b161106b565b6105f760006112d5565b600380546104ef90611724565b60006109d53384846105f9565b9392505050565b6109e4611011565b33600090815260096020526040902054811115610a135760405162461bcd60e51b81526004016107ca90611776565b3360009081526009602052604081208054839290610a3290849061175f565b925050819055508060056000828254610a4b919061175f565b9091555050604051600090339083908381818185875af1925050503d8060008114610a92576040519150601f19603f3d011682016040523d82523d6000602084013e610a97565b606091505b5050905080610ab957604051633204506f60e01b815260040160405180910390fd5b610ac4826000610fcf565b60405182815233907f7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b659060200160405180910390a2506108ca6001600055565b610b0c611011565b6008546001600160a01b0316610b4d5760405162461bcd60e51b81526020600482015260066024820152650534554204c560d41b60448201526064016107ca565b6008546040516370a0823160e01b815233600482015282916001600160a01b0316906370a0823190602401602060405180830381865afa158015610b95573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610bb991906117a1565b1015610bd75760405162461bcd60e51b81526004016107ca90611776565b6008546040516370a0823160e01b81523060048201526000916001600160a01b0316906370a0823190602401602060405180830381865afa158015610c20573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610c4491906117a1565b6008546040516323b872dd60e01b8152336004820152306024820152604481018590529192506001600160a01b0316906323b872dd906064016020604051808303816000875af1158015610c9c573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610cc091906117ba565b506008546040516370a0823160e01b81523060048201526000916001600160a01b0316906370a0823190602401602060405180830381865afa158015610d0a573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610d2e91906117a1565b905082610d3b838361175f565b1015610d7f5760405162461bcd60e51b81526020600482015260136024820152720a8a4829ca68c8aa4409c9ea8408a9c9eaa8e9606b1b60448201526064016107ca565b6008546040516323b872dd60e01b81523360048201526001600160a01b039091166024820181905260448201859052906323b872dd906064016020604051808303816000875af1158015610dd7573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610dfb91906117ba565b5060085460405163226bf2d160e21b815233600482015260009182916001600160a01b03909116906389afcb449060240160408051808303816000875af1158015610e4a573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610e6e91906117dc565b91509150306001600160a01b0316600860009054906101000a90046001600160a01b03166001600160a01b0316630dfe16816040518163ffffffff1660e01b8152600401602060405180830381865afa158015610ecf573d6000803e3d6000fd5b505050506040513d601f19601f82011682018060405250810190610ef39190611800565b6001600160a01b03161415610f1b57610f0b81611327565b610f1533836109c8565b50610f30565b610f2482611327565b610f2e33826109c8565b505b505050506108ca6001600055565b610f4661106b565b6001600160a01b038116610fab5760405162461bcd60e51b815260206004820152602660248201527f4f776e61626c653a206e6577206f776e657220697320746865207a65726f206160448201526564647265737360d01b60648201526084016107ca565b6108ca816112d5565b610fbc611011565b610fc581611327565b6108ca6001600055565b80610ff1578160066000828254610fe6919061175f565b92505081905561100a565b8160066000828254611003919061170c565b9250508190555b6006555050565b600260005414156110645760405162461bcd60e51b815260206004820152601f60248201527f5265656e7472616e637947756172643a207265656e7472616e742063616c6c0060448201526064016107ca565b6002600055565b6001546001600160a01b031633146105f75760405162461bcd60e51b815260206004820181905260248201527f4f776e61626c653a2063616c6c6572206973206e6f7420746865206f776e657260448201526064016107ca565b604080516001600160a01b038481166024830152604480830185905283518084039091018152606490920183526020820180516001600160e01b031663a9059cbb60e01b1790529151600092839290871691611121919061181d565b6000604051808303816000865af19150503d806000811461115e576040519150601f19603f3d011682016040523d82523d6000602084013e611163565b606091505b509150915081801561118d57508051158061118d57508080602001905181019061118d91906117ba565b6111ef5760405162461bcd60e51b815260206004820152602d60248201527f5472616e7366657248656c7065723a3a736166655472616e736665723a20747260448201526c185b9cd9995c8819985a5b1959609a1b60648201526084016107ca565b5050505050565b604080516000808252602082019092526001600160a01b038416908390604051611220919061181d565b60006040518083038185875af1925050503d806000811461125d576040519150601f19603f3d011682016040523d82523d6000602084013e611262565b606091505b50509050806112d05760405162461bcd60e51b815260206004820152603460248201527f5472616e7366657248656c7065723a3a736166655472616e736665724554483a60448201527308115512081d1c985b9cd9995c8819985a5b195960621b60648201526084016107ca565b505050565b600180546001600160a01b038381166001600160a01b0319831681179093556040519116919082907f8be0079c531659141344cd1fd0a4f28419497f9722a3daafe3b4186f6b6457e090600090a35050565b6007546040516370a0823160e01b815233600482015282916001600160a01b0316906370a0823190602401602060405180830381865afa15801561136f573d6000803e3d6000fd5b505050506040513d601f19601f8201168201806040525081019061139391906117a1565b10156113b15760405162461bcd60e51b81526004016107ca90611776565b6007546040516370a0823160e01b81523060048201526000916001600160a01b0316906370a0823190602401602060405180830381865afa1580156113fa573d6000803e3d6000fd5b505050506040513d601f19601f8201168201806040525081019061141e91906117a1565b6007546040516323b872dd60e01b8152336004820152306024820152604481018590529192506001600160a01b0316906323b872dd906064016020604051808303816000875af1158015611476573d6000803e3d6000fd5b505050506040513d601f19601f8201168201806040525081019061149a91906117ba565b506007546040516370a0823160e01b81523060048201526000916001600160a01b0316906370a0823190602401602060405180830381865afa1580156114e4573d6000803e3d6000fd5b505050506040513d601f19601f8201168201806040525081019061150891906117a1565b905082611515838361175f565b10156115595760405162461bcd60e51b81526020600482015260136024820152720a8a4829ca68c8aa4409c9ea8408a9c9eaa8e9606b1b60448201526064016107ca565b336000908152600960205260408120805485929061157890849061170c565b925050819055508260056000828254611591919061170c565b909155506112d09050836001610fcf565b60005b838110156115bd5781810151838201526020016115a5565b838111156115cc576000848401525b50505050565b60208152600082518060208401526115f18160408501602087016115a2565b601f01601f19169190910160400192915050565b6001600160a01b03811681146108ca57600080fd5b6000806040838503121561162d57600080fd5b823561163881611605565b946020939093013593505050565b60008060006060848603121561165b57600080fd5b833561166681611605565b9250602084013561167681611605565b929592945050506040919091013590565b60006020828403121561169957600080fd5b81356109d581611605565b6000602082840312156116b657600080fd5b5035919050565b600080604083850312156116d057600080fd5b82356116db81611605565b915060208301356116eb81611605565b809150509250929050565b634e487b7160e01b600052601160045260246000fd5b6000821982111561171f5761171f6116f6565b500190565b600181811c9082168061173857607f821691505b6020821081141561175957634e487b7160e01b600052602260045260246000fd5b50919050565b600082821015611771576117716116f6565b500390565b6020808252601190820152704e4f20454e4f5547482042414c414e434560781b604082015260600190565b6000602082840312156117b357600080fd5b5051919050565b6000602082840312156117cc57600080fd5b815180151581146109d557600080fd5b600080604083850312156117ef57600080fd5b505080516020909101519092909150565b60006020828403121561181257600080fd5b81516109d581611605565b6000825161182f8184602087016115a2565b919091019291505056fea2646970667358221220b9216a219819fff3398750dcd8547a3d24f16c386122922294dc501a8b0d549864736f6c634300080a0033".to_string(),
-        ))
+        ).unwrap())
     }
 }
@@ -19,10 +19,13 @@ use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use tracing::{debug, warn};
 use revm_interpreter::analysis::to_analysed;
 use revm_primitives::bitvec::macros::internal::funty::Integral;
 use revm_primitives::{Bytecode, LatestSpec};
+use crate::evm::hex_utils::{parse_hex, HexBytecode};
 use crate::evm::types::{EVMAddress, EVMU256};
+use crate::host_mem::{account, should_shrink};
 
 const MAX_HOPS: u32 = 5; // Assuming the value of MAX_HOPS
 
@@ -63,6 +66,17 @@ impl Chain {
         }
     }
 
+    /// Realistic block gas limit of the chain, used to bound env mutation
+    pub fn get_block_gas_limit(&self) -> u64 {
+        match self {
+            Chain::ETH => 30_000_000,
+            Chain::BSC => 140_000_000,
+            Chain::POLYGON => 30_000_000,
+            Chain::MUMBAI => 20_000_000,
+            Chain::LOCAL => 30_000_000,
+        }
+    }
+
     pub fn to_lowercase(&self) -> String {
         match self {
             Chain::ETH => "eth",
@@ -243,13 +257,13 @@ impl OnChainConfig {
                             return OperationResult::Ok(t);
                         }
                         Err(e) => {
-                            println!("{:?}", e);
+                            warn!("{:?}", e);
                             return OperationResult::Retry("failed to parse response".to_string());
                         }
                     }
                 }
                 Err(e) => {
-                    println!("Error: {}", e);
+                    warn!("{}", e);
                     return OperationResult::Retry("failed to send request".to_string());
                 }
             }
@@ -262,7 +276,7 @@ impl OnChainConfig {
                 Some(t)
             }
             Err(e) => {
-                println!("Error: {}", e);
+                warn!("{}", e);
                 None
             }
         }
@@ -298,13 +312,13 @@ impl OnChainConfig {
                             return OperationResult::Ok(t);
                         }
                         Err(e) => {
-                            println!("{:?}", e);
+                            warn!("{:?}", e);
                             return OperationResult::Retry("failed to parse response".to_string());
                         }
                     }
                 }
                 Err(e) => {
-                    println!("Error: {}", e);
+                    warn!("{}", e);
                     return OperationResult::Retry("failed to send request".to_string());
                 }
             }
@@ -316,7 +330,7 @@ impl OnChainConfig {
                 Some(t)
             }
             Err(e) => {
-                println!("Error: {}", e);
+                warn!("{}", e);
                 None
             }
         }
@@ -451,7 +465,7 @@ impl OnChainConfig {
                 "".to_string()
             }
         );
-        println!("fetching abi from {}", endpoint);
+        debug!("fetching abi from {}", endpoint);
         match self.get(endpoint.clone()) {
             Some(resp) => {
                 let json = serde_json::from_str::<Value>(&resp);
@@ -473,7 +487,7 @@ impl OnChainConfig {
                 }
             }
             None => {
-                println!("failed to fetch abi from {}", endpoint);
+                warn!("failed to fetch abi from {}", endpoint);
                 return None;
             }
         }
@@ -488,6 +502,18 @@ impl OnChainConfig {
         abi
     }
 
+    /// Return data of a call to the contract at the block
+    pub fn eth_call(&self, to: EVMAddress, data: &[u8]) -> Option<Vec<u8>> {
+        let params = json!([
+            {"to": format!("{:?}", to), "data": format!("0x{}", hex::encode(data))},
+            self.block_number
+        ]);
+        match self._request("eth_call".to_string(), params.to_string()) {
+            Some(Value::String(ret)) => hex::decode(ret.trim_start_matches("0x")).ok(),
+            _ => None,
+        }
+    }
+
     fn _request(&self, method: String, params: String) -> Option<Value> {
         let data = format!(
             "{{\"jsonrpc\":\"2.0\", \"method\": \"{}\", \"params\": {}, \"id\": {}}}",
@@ -503,14 +529,14 @@ impl OnChainConfig {
                         return json.get("result").cloned();
                     }
                     Err(e) => {
-                        println!("{:?}", e);
+                        warn!("{:?}", e);
                         return None;
                     }
                 }
             }
 
             None => {
-                println!("failed to fetch from {}", self.endpoint_url);
+                warn!("failed to fetch from {}", self.endpoint_url);
                 return None;
             }
         }
@@ -531,19 +557,41 @@ impl OnChainConfig {
                         return json.get("result").cloned();
                     }
                     Err(e) => {
-                        println!("{:?}", e);
+                        warn!("{:?}", e);
                         return None;
                     }
                 }
             }
 
             None => {
-                println!("failed to fetch from {}", self.endpoint_url);
+                warn!("failed to fetch from {}", self.endpoint_url);
                 return None;
             }
         }
     }
 
+    /// Give the in-memory caches back when memory runs short (see [`crate::host_mem`]), the
+    /// responses are read again from the cache on disk. Returns whether they were dropped.
+    pub fn shrink_caches(&mut self) -> bool {
+        account("onchain slot cache", self.slot_cache.len());
+        account("onchain code cache", self.code_cache.len());
+        account("onchain storage cache", self.storage_all_cache.len() + self.storage_dump_cache.len());
+        // with force_cache, what is missing from the cache reads as empty
+        if cfg!(feature = "force_cache") || !should_shrink("onchain caches") {
+            return false;
+        }
+        warn!(
+            "[host-mem] memory running short, dropping the onchain caches ({} slots, {} codes)",
+            self.slot_cache.len(),
+            self.code_cache.len()
+        );
+        self.slot_cache = Default::default();
+        self.code_cache = Default::default();
+        self.storage_all_cache = Default::default();
+        self.storage_dump_cache = Default::default();
+        true
+    }
+
     pub fn get_contract_code(&mut self, address: EVMAddress, force_cache: bool) -> Bytecode {
         if self.code_cache.contains_key(&address) {
             return self.code_cache[&address].clone();
@@ -552,7 +600,7 @@ impl OnChainConfig {
             return Bytecode::default();
         }
 
-        println!("fetching code from {}", hex::encode(address));
+        debug!("fetching code from {}", hex::encode(address));
 
         let resp_string = {
             let mut params = String::from("[");
@@ -573,7 +621,13 @@ impl OnChainConfig {
             self.code_cache.insert(address, Bytecode::new());
             return Bytecode::new();
         }
-        let code = hex::decode(code).unwrap();
+        let code = match HexBytecode::from_hex(code) {
+            Ok(code) => code.into_bytes(),
+            Err(e) => {
+                warn!("code of {:?}: {}", address, e);
+                vec![]
+            }
+        };
         let bytes = to_analysed(Bytecode::new_raw(Bytes::from(code)));
         self.code_cache.insert(address, bytes.clone());
         return bytes;
@@ -609,7 +663,10 @@ impl OnChainConfig {
             self.slot_cache.insert((address, slot), EVMU256::ZERO);
             return EVMU256::ZERO;
         }
-        let slot_value = EVMU256::try_from_be_slice(&hex::decode(slot_suffix).unwrap()).unwrap();
+        let slot_value = parse_hex(slot_suffix)
+            .ok()
+            .and_then(|bytes| EVMU256::try_from_be_slice(&bytes))
+            .unwrap_or_default();
         self.slot_cache.insert((address, slot), slot_value);
         return slot_value;
     }
@@ -31,6 +31,8 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 
 use crate::evm::contract_utils::ABIConfig;
+#[cfg(feature = "flashloan_v2")]
+use crate::evm::flashloan_provider::flashloan_provider;
 use crate::evm::onchain::onchain::OnChain;
 use crate::evm::oracles::erc20::IERC20OracleFlashloan;
 use crate::get_token_ctx;
@@ -131,6 +133,12 @@ where
                 repeat: 1,
                 cu_data: vec![],
                 is_cuda: false,
+                scenario: None,
+                adversary: None,
+                feed_prices: vec![],
+                force_feed: None,
+                block_advance: None,
+                gas_limit: None,
             }
         }
         .as_any()
@@ -288,6 +296,11 @@ where
         if input.get_txn_value().is_some() {
             flashloan_data.owed += EVMU512::from(input.get_txn_value().unwrap()) * scale!();
         }
+        // the ether of a borrow is lent by the provider, which charges its fee
+        #[cfg(feature = "flashloan_v2")]
+        if let (EVMInputTy::Borrow, Some(provider)) = (input.get_input_type(), flashloan_provider()) {
+            flashloan_data.owed += EVMU512::from(provider.fee(input.get_txn_value().unwrap_or_default())) * scale!();
+        }
         let addr = input.get_contract();
         // dont care if the call target is not erc20
         if self.erc20_address.contains(&addr) {
@@ -2,3 +2,4 @@ pub mod abi_decompiler;
 pub mod endpoints;
 pub mod flashloan;
 pub mod onchain;
+pub mod signature_workers;
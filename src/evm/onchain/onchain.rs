@@ -1,4 +1,6 @@
 use crate::evm::abi::get_abi_type_boxed;
+use crate::evm::address_pool::register_contract_address;
+use crate::evm::call_value::register_payability;
 use crate::evm::bytecode_analyzer;
 use crate::evm::config::StorageFetchingMode;
 use crate::evm::contract_utils::{ABIConfig, ContractLoader};
@@ -32,6 +34,7 @@ use crate::evm::onchain::flashloan::register_borrow_txn;
 use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::Arc;
+use tracing::{debug, warn};
 use revm_interpreter::Interpreter;
 use revm_primitives::Bytecode;
 use crate::evm::types::{convert_u256_to_h160, EVMAddress, EVMU256};
@@ -128,6 +131,14 @@ where
         }
         self.blacklist.insert(address);
     }
+
+    /// Drop the fetched storages with the caches of the endpoint when memory runs short
+    fn shrink_caches(&mut self) {
+        if self.endpoint.shrink_caches() {
+            self.storage_all.clear();
+            self.storage_dump.clear();
+        }
+    }
 }
 
 pub fn keccak_hex(data: EVMU256) -> String {
@@ -187,6 +198,7 @@ where
 
         match *interp.instruction_pointer {
             0x54 => {
+                self.shrink_caches();
                 let address = interp.contract.address;
                 let slot_idx = interp.stack.peek(0).unwrap();
 
@@ -246,6 +258,7 @@ where
             }
 
             0xf1 | 0xf2 | 0xf4 | 0xfa | 0x3b | 0x3c => {
+                self.shrink_caches();
                 let caller = interp.contract.address;
                 let address = match *interp.instruction_pointer {
                     0xf1 | 0xf2 | 0xf4 | 0xfa => interp.stack.peek(1).unwrap(),
@@ -282,12 +295,15 @@ where
                     _ => false,
                 };
 
-                println!("fetching abi {:?}", address_h160);
+                debug!("fetching abi {:?}", address_h160);
                 let abi = self.endpoint.fetch_abi(address_h160);
 
                 let parsed_abi = match abi {
                     Some(ref abi_ins) => ContractLoader::parse_abi_str(abi_ins),
-                    None => fetch_abi_heimdall(hex::encode(contract_code.bytes())),
+                    None => fetch_abi_heimdall(hex::encode(contract_code.bytes())).unwrap_or_else(|e| {
+                        warn!("failed to decompile {:?}: {}", address_h160, e);
+                        vec![]
+                    }),
                 };
 
                 // set up host
@@ -306,7 +322,7 @@ where
                             host.add_one_hashes(caller, hash);
                         }
                     }
-                    println!("Propagating hashes {:?} for proxy {:?}",
+                    debug!("Propagating hashes {:?} for proxy {:?}",
                              abi_hashes_to_add
                                  .iter()
                                 .map(|x| hex::encode(x))
@@ -326,6 +342,7 @@ where
                     address_h160
                 };
                 state.add_address(&target);
+                register_contract_address(target);
 
                 // notify flashloan and blacklisting flashloan addresses
                 #[cfg(feature = "flashloan_v2")]
@@ -350,6 +367,7 @@ where
                         let mut abi_instance = get_abi_type_boxed(&abi.abi);
                         abi_instance
                             .set_func_with_name(abi.function, abi.function_name.clone());
+                        register_payability(target, abi.function, abi.is_payable);
                         let input = EVMInput {
                             caller: state.get_rand_caller(),
                             contract: target,
@@ -375,6 +393,12 @@ where
                             repeat: 1,
                             cu_data: vec![],
                             is_cuda: false,
+                            scenario: None,
+                            adversary: None,
+                            feed_prices: vec![],
+                            force_feed: None,
+                            block_advance: None,
+                            gas_limit: None,
                         };
                         add_corpus(host, state, &input);
                     });
@@ -0,0 +1,221 @@
+/// Background resolution of function, event and error signatures (`--resolve-signatures`)
+///
+/// Looking a selector up in the signature database is an HTTP request, which used to stall the
+/// analysis of every decompiled contract at startup. The lookups are now queued to a pool of
+/// worker threads (`--signature-workers`), which send the signatures found back over a channel.
+/// The fuzz loop drains the channel at each stage run ([`drain_resolved`]) without ever waiting:
+/// the functions are then named (so that calls printed from then on carry their names, the
+/// inputs already generated keeping the decompiled argument types), their signatures persisted
+/// in the cache and the ABI of the run. Events and errors are requested the same way
+/// ([`request`]), their names read back with [`event_name`] and [`error_name`] once resolved. A
+/// selector is looked up at most once per run, unless memory runs short (see [`crate::host_mem`]):
+/// the names of the events and errors are then dropped, and looked up again when next requested.
+use crate::evm::abi::register_function_name;
+use crate::evm::contract_utils::ABIConfig;
+use crate::evm::hex_utils::{EventTopic, Selector};
+use crate::evm::onchain::abi_decompiler::{
+    apply_signature, arg_count, emit_abi, pick_signature, store_signature,
+};
+use crate::host_mem::{account, should_shrink};
+use heimdall_common::ether::signatures::{
+    resolve_error_signature, resolve_event_signature, resolve_function_signature,
+};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{debug, info};
+
+/// Number of lookups run concurrently (`--signature-workers`)
+pub static mut SIGNATURE_WORKERS: usize = 4;
+
+#[derive(Clone, Debug)]
+pub enum SignatureRequest {
+    /// A decompiled function, named after its selector
+    Function(ABIConfig),
+    /// The topic of an event
+    Event(EventTopic),
+    /// The selector of a custom error
+    Error(Selector),
+}
+
+impl SignatureRequest {
+    fn key(&self) -> String {
+        match self {
+            SignatureRequest::Function(abi) => format!("function:{}", hex::encode(abi.function)),
+            SignatureRequest::Event(topic) => format!("event:{}", topic),
+            SignatureRequest::Error(selector) => format!("error:{}", selector),
+        }
+    }
+}
+
+struct Workers {
+    requests: Sender<SignatureRequest>,
+    resolved: Receiver<(SignatureRequest, String)>,
+}
+
+static WORKERS: Lazy<Mutex<Option<Workers>>> = Lazy::new(|| Mutex::new(None));
+
+/// Keys of the requests sent, so that a selector is looked up once
+static REQUESTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+static EVENT_NAMES: Lazy<Mutex<HashMap<EventTopic, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static ERROR_NAMES: Lazy<Mutex<HashMap<Selector, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Signature answering the request, from the signature database
+fn lookup(request: &SignatureRequest) -> Option<String> {
+    match request {
+        SignatureRequest::Function(abi) => {
+            let candidates = resolve_function_signature(&hex::encode(abi.function))?
+                .into_iter()
+                .map(|f| f.signature)
+                .collect();
+            pick_signature(&abi.function, arg_count(&abi.abi), candidates)
+        }
+        SignatureRequest::Event(topic) => resolve_event_signature(&topic.to_string())?
+            .into_iter()
+            .next()
+            .map(|e| e.signature),
+        SignatureRequest::Error(selector) => {
+            let candidates = resolve_error_signature(&selector.to_string())?
+                .into_iter()
+                .map(|e| e.signature)
+                .collect();
+            // the arity of an error is unknown, any candidate hashing to the selector
+            pick_signature(&selector.0, usize::MAX, candidates)
+        }
+    }
+}
+
+fn start_workers() -> Workers {
+    let (requests, queue) = channel::<SignatureRequest>();
+    let (answers, resolved) = channel();
+    let queue = Arc::new(Mutex::new(queue));
+    let count = unsafe { SIGNATURE_WORKERS }.max(1);
+    for _ in 0..count {
+        let queue = queue.clone();
+        let answers = answers.clone();
+        thread::spawn(move || loop {
+            let request = match queue.lock().unwrap().recv() {
+                Ok(request) => request,
+                Err(_) => return,
+            };
+            match lookup(&request) {
+                Some(signature) => {
+                    if answers.send((request, signature)).is_err() {
+                        return;
+                    }
+                }
+                None => debug!("[signatures] no signature for {}", request.key()),
+            }
+        });
+    }
+    info!("[signatures] {} resolution workers started", count);
+    Workers { requests, resolved }
+}
+
+/// Queue a lookup, the workers being started by the first one
+pub fn request(request: SignatureRequest) {
+    if !REQUESTED.lock().unwrap().insert(request.key()) {
+        return;
+    }
+    let mut workers = WORKERS.lock().unwrap();
+    let _ = workers.get_or_insert_with(start_workers).requests.send(request);
+}
+
+/// Queue the lookup of a decompiled function
+pub fn request_function(abi: ABIConfig) {
+    request(SignatureRequest::Function(abi));
+}
+
+/// Record a signature found by the workers
+fn apply(request: SignatureRequest, signature: String) {
+    match request {
+        SignatureRequest::Function(mut abi) => {
+            let selector = hex::encode(abi.function);
+            apply_signature(&mut abi, &signature);
+            register_function_name(abi.function, abi.function_name.clone());
+            store_signature(&selector, &signature);
+            emit_abi(&vec![abi]);
+        }
+        SignatureRequest::Event(topic) => {
+            EVENT_NAMES.lock().unwrap().insert(topic, signature);
+        }
+        SignatureRequest::Error(selector) => {
+            ERROR_NAMES.lock().unwrap().insert(selector, signature);
+        }
+    }
+}
+
+/// Apply the signatures found since the last call, never blocking. Returns how many.
+pub fn drain_resolved() -> usize {
+    let resolved = match WORKERS.lock().unwrap().as_ref() {
+        Some(workers) => workers.resolved.try_iter().collect::<Vec<_>>(),
+        None => return 0,
+    };
+    let count = resolved.len();
+    for (request, signature) in resolved {
+        info!("[signatures] {} resolved to {}", request.key(), signature);
+        apply(request, signature);
+    }
+    shrink_names();
+    count
+}
+
+/// Drop the names of the events and errors when memory runs short. The functions keep theirs,
+/// which are in the ABI of the run.
+fn shrink_names() {
+    let mut events = EVENT_NAMES.lock().unwrap();
+    let mut errors = ERROR_NAMES.lock().unwrap();
+    account("signature cache", events.len() + errors.len());
+    if !should_shrink("signature cache") {
+        return;
+    }
+    info!("[signatures] memory running short, dropping {} event and error names", events.len() + errors.len());
+    *events = HashMap::new();
+    *errors = HashMap::new();
+    REQUESTED
+        .lock()
+        .unwrap()
+        .retain(|key| !key.starts_with("event:") && !key.starts_with("error:"));
+}
+
+/// Signature of an event, if resolved
+pub fn event_name(topic: &EventTopic) -> Option<String> {
+    EVENT_NAMES.lock().unwrap().get(topic).cloned()
+}
+
+/// Signature of a custom error, if resolved
+pub fn error_name(selector: &Selector) -> Option<String> {
+    ERROR_NAMES.lock().unwrap().get(selector).cloned()
+}
+
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_apply_resolved() {
+        let abi = ABIConfig {
+            abi: "(address,uint256)".to_string(),
+            function: [0xa9, 0x05, 0x9c, 0xbb],
+            function_name: "a9059cbb".to_string(),
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+        };
+        assert_eq!(SignatureRequest::Function(abi.clone()).key(), "function:a9059cbb");
+        // looked up once per run: a selector already requested starts no worker
+        REQUESTED.lock().unwrap().insert("function:a9059cbb".to_string());
+        request_function(abi);
+        assert!(WORKERS.lock().unwrap().is_none());
+        assert_eq!(drain_resolved(), 0);
+
+        let signature = "ERC20InsufficientBalance(address,uint256,uint256)".to_string();
+        let selector = Selector::from_str("e450d38c").unwrap();
+        apply(SignatureRequest::Error(selector), signature.clone());
+        assert_eq!(error_name(&selector), Some(signature));
+        assert_eq!(event_name(&EventTopic::default()), None);
+    }
+}
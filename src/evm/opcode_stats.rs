@@ -0,0 +1,192 @@
+/// Opcode execution statistics per target and per backend (`--opcode-stats <n>`)
+///
+/// The time of a GPU batch goes to the opcodes its threads execute, and a few expensive ones
+/// often dominate: a contract hashing its mapping keys spends most of its instructions in
+/// `KECCAK256`, which is costly on the device. With the statistics on, the host counts the
+/// opcodes and PCs executed on revm, and the runner those executed by the threads of each batch
+/// (`cuEnableOpcodeStats`, read back with `cuReadOpcodeStats`). The `n` hottest opcodes and PCs
+/// of each target are printed periodically for both backends and written to
+/// `stats/opcodes.json`.
+use crate::evm::types::EVMAddress;
+use crate::workdir::stats_dir;
+use revm_interpreter::OPCODE_JUMPMAP;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+pub const OPCODE_STATS_FILE: &str = "opcodes.json";
+
+/// Print and save the statistics every this many executions
+pub const OPCODE_STATS_INTERVAL: usize = 50000;
+
+/// PCs counted by the runner, the maximum size of a contract
+pub const GPU_OPCODE_PCS: usize = 0x6000;
+
+const KECCAK256: u8 = 0x20;
+
+/// Share of the instructions above which a target is reported as dominated by `KECCAK256`
+pub const KECCAK_DOMINATED: f64 = 0.2;
+
+/// Number of hottest opcodes and PCs reported per target, 0 when the statistics are off
+pub static mut OPCODE_STATS: usize = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Cpu,
+    Gpu,
+}
+
+#[derive(Clone, Debug)]
+pub struct OpcodeHistogram {
+    pub ops: [u64; 256],
+    pub pcs: HashMap<usize, u64>,
+}
+
+impl Default for OpcodeHistogram {
+    fn default() -> Self {
+        Self {
+            ops: [0; 256],
+            pcs: HashMap::new(),
+        }
+    }
+}
+
+impl OpcodeHistogram {
+    pub fn total(&self) -> u64 {
+        self.ops.iter().sum()
+    }
+
+    /// The `n` most executed opcodes, with their counts
+    pub fn top_ops(&self, n: usize) -> Vec<(u8, u64)> {
+        let mut ops = (0..=255u8)
+            .map(|op| (op, self.ops[op as usize]))
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        ops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ops.truncate(n);
+        ops
+    }
+
+    /// The `n` most executed PCs, with their counts
+    pub fn top_pcs(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut pcs = self.pcs.iter().map(|(pc, count)| (*pc, *count)).collect::<Vec<_>>();
+        pcs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        pcs.truncate(n);
+        pcs
+    }
+
+    /// Share of the instructions that are `KECCAK256`
+    pub fn keccak_share(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.ops[KECCAK256 as usize] as f64 / total as f64,
+        }
+    }
+}
+
+static mut OPCODE_HISTOGRAMS: Option<HashMap<(EVMAddress, Backend), OpcodeHistogram>> = None;
+
+fn histogram(address: EVMAddress, backend: Backend) -> &'static mut OpcodeHistogram {
+    unsafe {
+        OPCODE_HISTOGRAMS
+            .get_or_insert_with(HashMap::new)
+            .entry((address, backend))
+            .or_default()
+    }
+}
+
+/// Called by the host on each instruction executed on revm
+pub fn record_cpu_step(address: EVMAddress, pc: usize, op: u8) {
+    let histogram = histogram(address, Backend::Cpu);
+    histogram.ops[op as usize] += 1;
+    *histogram.pcs.entry(pc).or_insert(0) += 1;
+}
+
+/// Add the counts read back from the runner for the kernel of the target
+pub fn merge_gpu_counts(address: EVMAddress, ops: &[u64], pcs: &[u64]) {
+    let histogram = histogram(address, Backend::Gpu);
+    for (op, count) in ops.iter().enumerate().take(256) {
+        histogram.ops[op] += count;
+    }
+    for (pc, count) in pcs.iter().enumerate().filter(|(_, count)| **count > 0) {
+        *histogram.pcs.entry(pc).or_insert(0) += count;
+    }
+}
+
+fn op_name(op: u8) -> String {
+    match OPCODE_JUMPMAP[op as usize] {
+        Some(name) => name.to_string(),
+        None => format!("UNKNOWN({:#04x})", op),
+    }
+}
+
+/// Print the hottest opcodes and PCs of each target and save them
+pub fn report_opcode_stats() {
+    let n = unsafe { OPCODE_STATS };
+    let histograms = match unsafe { OPCODE_HISTOGRAMS.as_ref() } {
+        Some(histograms) if n > 0 => histograms,
+        _ => return,
+    };
+    let mut keys = histograms.keys().cloned().collect::<Vec<_>>();
+    keys.sort_by_key(|(address, backend)| (format!("{:?}", address), format!("{:?}", backend)));
+    let mut report = vec![];
+    for key in keys {
+        let (address, backend) = key;
+        let histogram = &histograms[&key];
+        let total = histogram.total();
+        let ops = histogram
+            .top_ops(n)
+            .iter()
+            .map(|(op, count)| format!("{} {:.1}%", op_name(*op), *count as f64 * 100.0 / total as f64))
+            .collect::<Vec<_>>();
+        let pcs = histogram
+            .top_pcs(n)
+            .iter()
+            .map(|(pc, count)| format!("{:#x} {:.1}%", pc, *count as f64 * 100.0 / total as f64))
+            .collect::<Vec<_>>();
+        info!("[opcodes] {:?} on {:?}: {} instructions, hottest opcodes {}", address, backend, total, ops.join(", "));
+        info!("[opcodes] {:?} on {:?}: hottest pcs {}", address, backend, pcs.join(", "));
+        if backend == Backend::Gpu && histogram.keccak_share() >= KECCAK_DOMINATED {
+            info!(
+                "[opcodes] {:?}: {:.1}% of the GPU instructions are KECCAK256, the kernel would benefit from device-side crypto",
+                address,
+                histogram.keccak_share() * 100.0
+            );
+        }
+        report.push(serde_json::json!({
+            "contract": format!("{:?}", address),
+            "backend": backend,
+            "instructions": total,
+            "opcodes": histogram.top_ops(n).iter().map(|(op, count)| serde_json::json!({"opcode": op_name(*op), "count": count})).collect::<Vec<_>>(),
+            "pcs": histogram.top_pcs(n).iter().map(|(pc, count)| serde_json::json!({"pc": pc, "count": count})).collect::<Vec<_>>(),
+        }));
+    }
+    let path = Path::new(&stats_dir()).join(OPCODE_STATS_FILE);
+    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&report).unwrap()) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_histogram() {
+        let target = EVMAddress::from_slice(&[0x42; 20]);
+        for (pc, op) in [(0, 0x60), (2, KECCAK256), (3, KECCAK256), (2, KECCAK256)] {
+            record_cpu_step(target, pc, op);
+        }
+        let cpu = histogram(target, Backend::Cpu);
+        assert_eq!(cpu.top_ops(1), vec![(KECCAK256, 3)]);
+        assert_eq!(cpu.top_pcs(2), vec![(2, 2), (0, 1)]);
+        assert_eq!(cpu.keccak_share(), 0.75);
+
+        merge_gpu_counts(target, &[1, 0, 4], &[0, 5]);
+        let gpu = histogram(target, Backend::Gpu);
+        assert_eq!(gpu.top_ops(5), vec![(0x02, 4), (0x00, 1)]);
+        assert_eq!(gpu.top_pcs(5), vec![(1, 5)]);
+    }
+}
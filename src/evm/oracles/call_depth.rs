@@ -0,0 +1,140 @@
+/// Logic breaking at the call depth limit (`--sanitize call-depth`)
+///
+/// An attacker can call a target from deep enough in its own call stack that every call the
+/// target makes goes beyond [`CALL_DEPTH_LIMIT`] and fails (the call-depth attack; the calls
+/// starved by the 63/64 forwarding rule are covered by `--sanitize oog-griefing`). Each
+/// transaction making external calls is executed again at the depth limit: if it still succeeds
+/// although some of its calls failed, and still writes storage, the target ignores the failure of
+/// its calls (e.g., marks a payout as sent while the `send` failed).
+use crate::evm::host::{
+    CALL_DEPTH_LIMIT, CALL_DEPTH_OFFSET, CALL_DEPTH_REFUSED, EXTERNAL_CALL_MADE, REENTRANCY_HIT, SELFDESTRUCT_HIT,
+    STATE_CHANGE,
+};
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::generic_vm::vm_state::VMStateT;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+use std::ops::Deref;
+
+pub struct CallDepthOracle;
+
+impl CallDepthOracle {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Slots whose value differs between the two states
+pub fn changed_slots(pre: &EVMState, post: &EVMState) -> Vec<(EVMAddress, EVMU256)> {
+    let mut changed = vec![];
+    for (address, storage) in &post.state {
+        let before = pre.get(address);
+        for (slot, value) in storage {
+            let old = before.and_then(|s| s.get(slot)).cloned().unwrap_or_default();
+            if old != *value {
+                changed.push((*address, *slot));
+            }
+        }
+    }
+    changed.sort();
+    changed
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for CallDepthOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        if !unsafe { EXTERNAL_CALL_MADE } || ctx.post_state.has_post_execution() {
+            return false;
+        }
+        // the execution at the depth limit must leave the coverage and the flags of the
+        // transaction under evaluation untouched
+        let vm = ctx.executor.deref().borrow();
+        let (jmp, read, write) = (*vm.get_jmp(), *vm.get_read(), *vm.get_write());
+        drop(vm);
+        let flags = unsafe { (STATE_CHANGE, REENTRANCY_HIT, SELFDESTRUCT_HIT) };
+
+        unsafe {
+            CALL_DEPTH_OFFSET = CALL_DEPTH_LIMIT;
+        }
+        let res = ctx.executor.deref().borrow_mut().execute(ctx.input, ctx.fuzz_state);
+        let refused = unsafe { CALL_DEPTH_REFUSED };
+        unsafe {
+            CALL_DEPTH_OFFSET = 0;
+        }
+
+        let vm = ctx.executor.deref().borrow();
+        *vm.get_jmp() = jmp;
+        *vm.get_read() = read;
+        *vm.get_write() = write;
+        drop(vm);
+        unsafe {
+            STATE_CHANGE = flags.0;
+            REENTRANCY_HIT = flags.1;
+            SELFDESTRUCT_HIT = flags.2;
+            EXTERNAL_CALL_MADE = true;
+        }
+
+        if res.reverted || refused == 0 || res.new_state.state.has_post_execution() {
+            return false;
+        }
+        let written = changed_slots(ctx.pre_state, &res.new_state.state);
+        if written.is_empty() {
+            return false;
+        }
+        let normal = changed_slots(ctx.pre_state, &ctx.post_state);
+        let slots = written
+            .iter()
+            .map(|(address, slot)| format!("{:?}[{}]", address, slot))
+            .collect::<Vec<_>>();
+        unsafe {
+            ORACLE_OUTPUT = format!(
+                "[call-depth] succeeds at call depth {} with {} failed call(s), still writing {}{}",
+                CALL_DEPTH_LIMIT,
+                refused,
+                slots.join(", "),
+                if written == normal { "" } else { " (storage differs from the normal execution)" }
+            );
+        }
+        true
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::host::exceeds_call_depth;
+    use crate::evm::vm::EVMStorage;
+
+    #[test]
+    fn test_call_depth() {
+        assert!(!exceeds_call_depth(1));
+        unsafe {
+            CALL_DEPTH_OFFSET = CALL_DEPTH_LIMIT;
+        }
+        // the first call of the transaction already fails
+        assert!(exceeds_call_depth(1));
+        unsafe {
+            CALL_DEPTH_OFFSET = 0;
+        }
+
+        let target = EVMAddress::zero();
+        let mut pre = EVMState::default();
+        pre.state.insert(target, EVMStorage::unit(EVMU256::from(0), EVMU256::from(1)));
+        let mut post = pre.clone();
+        assert!(changed_slots(&pre, &post).is_empty());
+        post.state.get_mut(&target).unwrap().insert(EVMU256::from(0), EVMU256::from(0));
+        post.state.get_mut(&target).unwrap().insert(EVMU256::from(2), EVMU256::from(5));
+        assert_eq!(
+            changed_slots(&pre, &post),
+            vec![(target, EVMU256::from(0)), (target, EVMU256::from(2))]
+        );
+    }
+}
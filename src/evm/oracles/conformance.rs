@@ -0,0 +1,167 @@
+/// Conformance oracles of the token interfaces detected at startup (see [`crate::evm::interfaces`])
+///
+/// After each transaction to a detected token, the effect of the transaction is checked against
+/// the standard with static calls on the pre / post states:
+/// - ERC-20: `transfer(to, amount)` credits `to` with exactly `amount`, and the caller does not
+///   hold more than the total supply
+/// - ERC-721: `(safe)transferFrom(from, to, id)` makes `to` the owner of `id`
+/// - ERC-1155: `safeTransferFrom(from, to, id, amount, data)` credits `to` with exactly `amount`
+/// - ERC-4626: converting assets to shares and back does not yield more assets
+use crate::evm::input::{EVMInput, EVMInputT};
+use crate::evm::interfaces::{arg_address, arg_word, encode_call, TokenInterface};
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::input::VMInputT;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+use std::collections::HashMap;
+
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const TOTAL_SUPPLY: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+const OWNER_OF: [u8; 4] = [0x63, 0x52, 0x21, 0x1e];
+const TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+const SAFE_TRANSFER_FROM: [u8; 4] = [0x42, 0x84, 0x2e, 0x0e];
+const SAFE_TRANSFER_FROM_WITH_DATA: [u8; 4] = [0xb8, 0x8d, 0x4f, 0xde];
+const ERC1155_BALANCE_OF: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+const ERC1155_SAFE_TRANSFER_FROM: [u8; 4] = [0xf2, 0x42, 0x43, 0x2a];
+const CONVERT_TO_SHARES: [u8; 4] = [0xc6, 0xe6, 0xf5, 0x92];
+const CONVERT_TO_ASSETS: [u8; 4] = [0x07, 0xa2, 0xd1, 0x3a];
+
+/// Assets converted back and forth by the ERC-4626 check (1e18)
+const ROUND_TRIP_ASSETS: u64 = 1_000_000_000_000_000_000;
+
+fn word_of(ret: &Vec<u8>) -> Option<EVMU256> {
+    if ret.len() < 32 {
+        return None;
+    }
+    EVMU256::try_from_be_slice(&ret[..32])
+}
+
+pub struct InterfaceConformanceOracle {
+    pub targets: HashMap<EVMAddress, Vec<TokenInterface>>,
+}
+
+impl InterfaceConformanceOracle {
+    pub fn new(targets: HashMap<EVMAddress, Vec<TokenInterface>>) -> Self {
+        Self { targets }
+    }
+
+    /// Amount credited to the holder of the token by the transaction, using the given calldata
+    /// of the balance query
+    fn credited(ctx: &mut EVMOracleCtx<'_>, token: EVMAddress, query: Bytes) -> Option<EVMU256> {
+        let pre = word_of(&ctx.call_pre_batch(&vec![(token, query.clone())])[0])?;
+        let post = word_of(&ctx.call_post_batch(&vec![(token, query)])[0])?;
+        Some(post.wrapping_sub(pre))
+    }
+
+    fn check(ctx: &mut EVMOracleCtx<'_>, token: EVMAddress, interface: TokenInterface) -> Option<String> {
+        let calldata = ctx.input.to_bytes();
+        let selector: [u8; 4] = calldata.get(..4)?.try_into().ok()?;
+        let caller = ctx.input.get_caller();
+        match interface {
+            TokenInterface::ERC20 => {
+                if selector == TRANSFER {
+                    let to = arg_address(&calldata, 0)?;
+                    let amount = EVMU256::from_be_bytes(arg_word(&calldata, 1)?);
+                    if to != caller {
+                        let credited = Self::credited(ctx, token, encode_call(BALANCE_OF, &[to], &[]))?;
+                        if credited != amount {
+                            return Some(format!(
+                                "transfer of {} to {:?} credited {}",
+                                amount, to, credited
+                            ));
+                        }
+                    }
+                }
+                let res = ctx.call_post_batch(&vec![
+                    (token, encode_call(TOTAL_SUPPLY, &[], &[])),
+                    (token, encode_call(BALANCE_OF, &[caller], &[])),
+                ]);
+                let (supply, balance) = (word_of(&res[0])?, word_of(&res[1])?);
+                if balance > supply {
+                    return Some(format!("balance {} of {:?} exceeds the total supply {}", balance, caller, supply));
+                }
+            }
+            TokenInterface::ERC721 => {
+                if [TRANSFER_FROM, SAFE_TRANSFER_FROM, SAFE_TRANSFER_FROM_WITH_DATA].contains(&selector) {
+                    let to = arg_address(&calldata, 1)?;
+                    let id = arg_word(&calldata, 2)?;
+                    let res = ctx.call_post_batch(&vec![(token, encode_call(OWNER_OF, &[], &[id]))]);
+                    let owner = EVMAddress::from_slice(res[0].get(12..32)?);
+                    if owner != to {
+                        return Some(format!(
+                            "transfer of token {} to {:?} left it to {:?}",
+                            EVMU256::from_be_bytes(id),
+                            to,
+                            owner
+                        ));
+                    }
+                }
+            }
+            TokenInterface::ERC1155 => {
+                if selector == ERC1155_SAFE_TRANSFER_FROM {
+                    let from = arg_address(&calldata, 0)?;
+                    let to = arg_address(&calldata, 1)?;
+                    let id = arg_word(&calldata, 2)?;
+                    let amount = EVMU256::from_be_bytes(arg_word(&calldata, 3)?);
+                    if from != to {
+                        let credited = Self::credited(ctx, token, encode_call(ERC1155_BALANCE_OF, &[to], &[id]))?;
+                        if credited != amount {
+                            return Some(format!(
+                                "transfer of {} of token {} to {:?} credited {}",
+                                amount,
+                                EVMU256::from_be_bytes(id),
+                                to,
+                                credited
+                            ));
+                        }
+                    }
+                }
+            }
+            TokenInterface::ERC4626 => {
+                let assets = EVMU256::from(ROUND_TRIP_ASSETS);
+                let shares = word_of(
+                    &ctx.call_post_batch(&vec![(token, encode_call(CONVERT_TO_SHARES, &[], &[assets.to_be_bytes()]))])[0],
+                )?;
+                let back = word_of(
+                    &ctx.call_post_batch(&vec![(token, encode_call(CONVERT_TO_ASSETS, &[], &[shares.to_be_bytes()]))])[0],
+                )?;
+                if back > assets {
+                    return Some(format!(
+                        "{} assets converted to {} shares convert back to {} assets",
+                        assets, shares, back
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for InterfaceConformanceOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        let token = ctx.input.get_contract();
+        let interfaces = match self.targets.get(&token) {
+            Some(interfaces) => interfaces.clone(),
+            None => return false,
+        };
+        for interface in interfaces {
+            if let Some(violation) = Self::check(ctx, token, interface) {
+                unsafe {
+                    ORACLE_OUTPUT = format!("[conformance] {} {:?}: {}", interface.name(), token, violation);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
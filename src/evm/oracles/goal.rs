@@ -0,0 +1,88 @@
+/// Reports a transaction reaching an attacker goal (`--goals`), see [`crate::evm::goals`]
+///
+/// A goal counts when it holds after the transaction and did not before, so that a goal
+/// already holding on the initial state is not reported on every transaction.
+use crate::evm::goals::Goal;
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::input::VMInputT;
+use crate::oracle::Oracle;
+use crate::state::HasExecutionResult;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// `balanceOf(address)`
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+pub struct GoalOracle {
+    pub goals: Vec<(EVMAddress, Goal)>,
+}
+
+impl GoalOracle {
+    pub fn new(goals: Vec<(EVMAddress, Goal)>) -> Self {
+        Self { goals }
+    }
+
+    fn slot_value(state: &EVMState, contract: &EVMAddress, slot: &EVMU256) -> EVMU256 {
+        state
+            .get(contract)
+            .and_then(|storage| storage.get(slot))
+            .cloned()
+            .unwrap_or(EVMU256::ZERO)
+    }
+
+    fn balance(returns: &Vec<Vec<u8>>) -> Option<EVMU256> {
+        returns
+            .first()
+            .filter(|ret| ret.len() >= 32)
+            .and_then(|ret| EVMU256::try_from_be_slice(&ret[..32]))
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for GoalOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        if ctx.fuzz_state.get_execution_result().reverted {
+            return false;
+        }
+        let attacker = ctx.input.get_caller();
+        for (contract, goal) in &self.goals {
+            let reached = match goal {
+                Goal::Slot { slot, value, .. } => {
+                    let after = Self::slot_value(&ctx.post_state, contract, slot);
+                    let before = Self::slot_value(ctx.pre_state, contract, slot);
+                    (value.matches(after, &attacker) && !value.matches(before, &attacker))
+                        .then(|| format!("slot {:#x} of {:?} set to {:#x}", slot, contract, after))
+                }
+                Goal::Balance { increase, .. } => {
+                    let calldata = [BALANCE_OF.to_vec(), vec![0u8; 12], attacker.0.to_vec()].concat();
+                    let batch = vec![(*contract, Bytes::from(calldata))];
+                    match (
+                        Self::balance(&ctx.call_pre_batch(&batch)),
+                        Self::balance(&ctx.call_post_batch(&batch)),
+                    ) {
+                        (Some(before), Some(after)) if after >= before.saturating_add(*increase) => Some(format!(
+                            "balance of {:?} in {:?} increased from {} to {}",
+                            attacker, contract, before, after
+                        )),
+                        _ => None,
+                    }
+                }
+            };
+            if let Some(description) = reached {
+                unsafe {
+                    ORACLE_OUTPUT = format!("[goal] {} reached: {}", goal.contract(), description);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,55 @@
+use crate::evm::contract_utils::FIX_DEPLOYER;
+use crate::evm::initializer::is_initializer;
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{fixed_address, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::input::VMInputT;
+use crate::oracle::Oracle;
+use crate::state::HasExecutionResult;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// Flags an initializer of a target successfully called by someone else than the deployer,
+/// i.e., a contract anyone can initialize (`--sanitize initializer`)
+pub struct InitializerOracle {
+    deployer: EVMAddress,
+}
+
+impl InitializerOracle {
+    pub fn new() -> Self {
+        Self {
+            deployer: fixed_address(FIX_DEPLOYER),
+        }
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for InitializerOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        let input = ctx.input;
+        if input.get_caller() == self.deployer || !is_initializer(&input.to_bytes()) {
+            return false;
+        }
+        if ctx.fuzz_state.get_execution_result().reverted {
+            return false;
+        }
+        let contract = input.get_contract();
+        if ctx.pre_state.get(&contract) == ctx.post_state.get(&contract) {
+            return false;
+        }
+        unsafe {
+            ORACLE_OUTPUT = format!(
+                "[initializer] {:?} initialized {:?}, anyone can initialize it",
+                input.get_caller(),
+                contract
+            );
+        }
+        true
+    }
+}
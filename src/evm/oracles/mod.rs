@@ -1,4 +1,14 @@
+pub mod conformance;
 pub mod erc20;
 pub mod function;
+pub mod goal;
+pub mod initializer;
+pub mod reentrancy;
+pub mod selfdestruct;
+pub mod storage_write;
 pub mod v2_pair;
-pub mod bug;
\ No newline at end of file
+pub mod view_consistency;
+pub mod withdrawal;
+pub mod bug;
+pub mod call_depth;
+pub mod oog_griefing;
\ No newline at end of file
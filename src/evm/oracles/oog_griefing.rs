@@ -0,0 +1,94 @@
+/// State written around inner calls that ran out of gas (`--sanitize oog-griefing`)
+///
+/// The sender of a transaction chooses its gas limit. When a target calls a callback (a hook of
+/// a token, the receiver of a payout, a relayed call) and carries on after the callback failed,
+/// the sender can forward just enough gas for the target and not for the callback: the callback
+/// runs out of gas and the target records the operation as done (gas griefing). With the
+/// oracle, the host meters gas ([`crate::evm::tx_gas`]) and the mutator lowers the gas limit of
+/// the transactions; a transaction succeeding although one of its inner calls ran out of gas,
+/// while the contract making that call wrote storage, is reported.
+use crate::evm::input::{EVMInput, EVMInputT};
+use crate::evm::oracles::call_depth::changed_slots;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::tx_gas::OOG_CALLS;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::generic_vm::vm_state::VMStateT;
+use crate::oracle::Oracle;
+use crate::state::HasExecutionResult;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+pub struct OogGriefingOracle;
+
+impl OogGriefingOracle {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Slots written by the callers of the calls that ran out of gas
+fn griefed_writes(
+    oog_calls: &[(EVMAddress, EVMAddress)],
+    pre: &EVMState,
+    post: &EVMState,
+) -> Vec<(EVMAddress, EVMU256)> {
+    changed_slots(pre, post)
+        .into_iter()
+        .filter(|(address, _)| oog_calls.iter().any(|(caller, _)| caller == address))
+        .collect()
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for OogGriefingOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        let oog_calls = unsafe { OOG_CALLS.clone() };
+        if oog_calls.is_empty() || ctx.post_state.has_post_execution() {
+            return false;
+        }
+        if ctx.fuzz_state.get_execution_result().reverted {
+            return false;
+        }
+        let written = griefed_writes(&oog_calls, ctx.pre_state, &ctx.post_state);
+        if written.is_empty() {
+            return false;
+        }
+        let (caller, callee) = oog_calls[0];
+        unsafe {
+            ORACLE_OUTPUT = format!(
+                "[oog-griefing] succeeds with a gas limit of {} although the call of {:?} to {:?} ran out of gas, still writing {}",
+                ctx.input.get_gas_limit().map_or("the block".to_string(), |limit| limit.to_string()),
+                caller,
+                callee,
+                written
+                    .iter()
+                    .map(|(address, slot)| format!("{:?}[{}]", address, slot))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        true
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::vm::EVMStorage;
+
+    #[test]
+    fn test_griefed_writes() {
+        let (target, callback) = (EVMAddress::from_slice(&[0x11; 20]), EVMAddress::from_slice(&[0x22; 20]));
+        let pre = EVMState::default();
+        let mut post = pre.clone();
+        post.state.insert(target, EVMStorage::unit(EVMU256::from(3), EVMU256::from(1)));
+        post.state.insert(callback, EVMStorage::unit(EVMU256::from(0), EVMU256::from(1)));
+        // only the writes of the contract whose call ran out of gas
+        assert_eq!(griefed_writes(&[(target, callback)], &pre, &post), vec![(target, EVMU256::from(3))]);
+        assert!(griefed_writes(&[], &pre, &post).is_empty());
+    }
+}
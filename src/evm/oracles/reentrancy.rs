@@ -0,0 +1,38 @@
+use crate::evm::host::REENTRANCY_HIT;
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// Flags a contract writing its storage while it is re-entered, i.e., called again before its
+/// outer call frame returns (`--sanitize reentrancy`)
+pub struct ReentrancyOracle;
+
+impl ReentrancyOracle {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for ReentrancyOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        match unsafe { REENTRANCY_HIT } {
+            Some(contract) => {
+                unsafe {
+                    ORACLE_OUTPUT = format!("[reentrancy] storage of {:?} written while re-entered", contract);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::evm::host::SELFDESTRUCT_HIT;
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// Flags a `SELFDESTRUCT` executed by a target (`--sanitize selfdestruct`)
+pub struct SelfdestructOracle;
+
+impl SelfdestructOracle {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for SelfdestructOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        match unsafe { SELFDESTRUCT_HIT } {
+            Some(contract) => {
+                unsafe {
+                    ORACLE_OUTPUT = format!("[selfdestruct] {:?} self-destructed", contract);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::storage_layout::describe_slot;
+use crate::evm::storage_write::STORAGE_WRITE_HIT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// Flags an SSTORE whose slot is controlled by calldata (`--sanitize storage-write`), see
+/// [`crate::evm::storage_write`]
+pub struct StorageWriteOracle;
+
+impl StorageWriteOracle {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for StorageWriteOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        match unsafe { STORAGE_WRITE_HIT.clone() } {
+            Some(hit) => {
+                unsafe {
+                    ORACLE_OUTPUT = format!(
+                        "[storage-write] {:?} writes slot {} at pc {} controlled by calldata bytes {}..{}{}",
+                        hit.contract,
+                        match describe_slot(&hit.contract, &hit.slot) {
+                            Some(name) => format!("{:#x} ({})", hit.slot, name),
+                            None => format!("{:#x}", hit.slot),
+                        },
+                        hit.pc,
+                        hit.calldata_range.0,
+                        hit.calldata_range.1,
+                        if hit.via_offset { " (unbounded array offset)" } else { "" }
+                    );
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
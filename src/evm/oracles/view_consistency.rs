@@ -0,0 +1,82 @@
+/// Consistency of view functions under state churn (`--view-invariants`), see
+/// [`crate::evm::view_invariants`]
+///
+/// After each transaction, the view functions of each invariant are called on the pre and post
+/// states. A transaction turning an invariant that held into one that does not is reported, so
+/// the solution is the first sequence breaking it.
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::view_invariants::ViewInvariant;
+use crate::evm::vm::EVMState;
+use crate::oracle::Oracle;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+use std::collections::HashMap;
+
+pub struct ViewConsistencyOracle {
+    pub invariants: Vec<(EVMAddress, ViewInvariant)>,
+}
+
+impl ViewConsistencyOracle {
+    pub fn new(invariants: Vec<(EVMAddress, ViewInvariant)>) -> Self {
+        Self { invariants }
+    }
+
+    /// Results of the view functions, failed calls are left out
+    fn results(returns: &Vec<Vec<u8>>, calls: &Vec<[u8; 4]>) -> HashMap<[u8; 4], EVMU256> {
+        calls
+            .iter()
+            .zip(returns.iter())
+            .filter(|(_, ret)| ret.len() >= 32)
+            .filter_map(|(selector, ret)| Some((*selector, EVMU256::try_from_be_slice(&ret[..32])?)))
+            .collect()
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for ViewConsistencyOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        for (target, invariant) in &self.invariants {
+            let calls = invariant.calls();
+            let batch = calls
+                .iter()
+                .map(|selector| (*target, Bytes::from(selector.to_vec())))
+                .collect::<Vec<_>>();
+            let post = Self::results(&ctx.call_post_batch(&batch), &calls);
+            if invariant.holds(&post) != Some(false) {
+                continue;
+            }
+            let pre = Self::results(&ctx.call_pre_batch(&batch), &calls);
+            if invariant.holds(&pre) != Some(true) {
+                continue;
+            }
+            let values = calls
+                .iter()
+                .filter_map(|selector| Some((selector, pre.get(selector)?, post.get(selector)?)))
+                .map(|(selector, before, after)| {
+                    let name = match invariant.name_of(selector) {
+                        Some(name) => name,
+                        None => hex::encode(selector),
+                    };
+                    format!("{}() {} -> {}", name, before, after)
+                })
+                .collect::<Vec<_>>();
+            unsafe {
+                ORACLE_OUTPUT = format!(
+                    "[view-consistency] {:?}: {} broken ({})",
+                    target,
+                    invariant.source,
+                    values.join(", ")
+                );
+            }
+            return true;
+        }
+        false
+    }
+}
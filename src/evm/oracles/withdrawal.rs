@@ -0,0 +1,56 @@
+use crate::evm::contract_utils::FIX_DEPLOYER;
+use crate::evm::input::EVMInput;
+use crate::evm::oracles::erc20::ORACLE_OUTPUT;
+use crate::evm::types::{fixed_address, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256};
+use crate::evm::vm::EVMState;
+use crate::evm::withdrawal::{ether_sent, is_candidate};
+use crate::input::VMInputT;
+use crate::oracle::Oracle;
+use crate::state::HasExecutionResult;
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+/// Confirms an unprotected withdrawal found by the static pass: a candidate function called
+/// successfully by someone else than the deployer sends ether of the target to the caller
+/// (`--sanitize withdrawal`)
+pub struct WithdrawalOracle {
+    deployer: EVMAddress,
+}
+
+impl WithdrawalOracle {
+    pub fn new() -> Self {
+        Self {
+            deployer: fixed_address(FIX_DEPLOYER),
+        }
+    }
+}
+
+impl Oracle<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>
+    for WithdrawalOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> bool {
+        let input = ctx.input;
+        let (caller, contract) = (input.get_caller(), input.get_contract());
+        if caller == self.deployer || !is_candidate(&contract, &input.to_bytes()) {
+            return false;
+        }
+        if ctx.fuzz_state.get_execution_result().reverted {
+            return false;
+        }
+        let sent = ether_sent(&contract, &caller);
+        if sent == EVMU256::ZERO {
+            return false;
+        }
+        unsafe {
+            ORACLE_OUTPUT = format!(
+                "[withdrawal] {:?} withdrew ether of {:?} without a caller check, net earned = {} wei",
+                caller, contract, sent
+            );
+        }
+        true
+    }
+}
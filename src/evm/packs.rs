@@ -0,0 +1,166 @@
+/// Public seed corpora and dictionaries of common protocol families
+///
+/// `cli fetch-pack erc20` downloads a curated pack (`packs/<name>.json` of the repository, or
+/// `<url>/<name>.json` with `--url`) into [`PACK_DIR`]. Every pack of the directory is merged
+/// into new campaigns (`--pack-dir`, `--no-packs` to disable): a seed is a call given by the
+/// signature of the function and its arguments, added to the initial corpus of each target
+/// function with the same signature, and the dictionary of a pack whose seeds match a target is
+/// added to the constant pool of the mutator.
+use crate::evm::contract_utils::set_hash;
+use crate::evm::dictionary::LearnedDictionary;
+use crate::evm::shell::{encode_word, is_elementary};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Local cache of the packs fetched
+pub const PACK_DIR: &str = "cache/packs";
+
+/// Where `fetch-pack` downloads the packs from by default
+pub const DEFAULT_PACK_URL: &str = "https://raw.githubusercontent.com/Kenun99/mau/main/packs";
+
+/// Packs curated in the repository
+pub const PACKS: [&str; 3] = ["erc20", "uniswap", "openzeppelin"];
+
+/// Call of a function of any target with this signature
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackSeed {
+    /// e.g., `transfer(address,uint256)`
+    pub signature: String,
+    /// Values of the arguments, as accepted by `shell`'s `set`, e.g., `1e18`
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl PackSeed {
+    /// Selector and types of the arguments, e.g., `(address,uint256)`
+    pub fn function(&self) -> Result<([u8; 4], String), String> {
+        let start = self.signature.find('(').ok_or(format!("invalid signature {}", self.signature))?;
+        if !self.signature.ends_with(')') {
+            return Err(format!("invalid signature {}", self.signature));
+        }
+        let mut selector = [0; 4];
+        set_hash(&self.signature, &mut selector);
+        Ok((selector, self.signature[start..].to_string()))
+    }
+
+    /// ABI encoded arguments (without the selector)
+    pub fn encode_args(&self) -> Result<Vec<u8>, String> {
+        let (_, types) = self.function()?;
+        let types = types[1..types.len() - 1]
+            .split(',')
+            .filter(|ty| !ty.is_empty())
+            .collect::<Vec<_>>();
+        if types.len() != self.args.len() {
+            return Err(format!("{} takes {} arguments, {} given", self.signature, types.len(), self.args.len()));
+        }
+        let mut data = vec![];
+        for (ty, value) in types.iter().zip(self.args.iter()) {
+            if !is_elementary(ty) {
+                return Err(format!("{}: arguments of type {} are not supported", self.signature, ty));
+            }
+            data.extend_from_slice(&encode_word(ty, value)?);
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CorpusPack {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub seeds: Vec<PackSeed>,
+    #[serde(default)]
+    pub dictionary: LearnedDictionary,
+}
+
+impl CorpusPack {
+    /// Encoded arguments of the seeds of the function, whose arguments have the types given
+    pub fn seeds_for(&self, function: &[u8; 4], types: &str) -> Vec<Vec<u8>> {
+        self.seeds
+            .iter()
+            .filter(|seed| matches!(seed.function(), Ok((selector, ref t)) if selector == *function && t == types))
+            .filter_map(|seed| seed.encode_args().ok())
+            .collect()
+    }
+}
+
+/// Parse a pack, checking that all its seeds can be encoded
+pub fn parse_pack(json: &str) -> Result<CorpusPack, String> {
+    let pack: CorpusPack = serde_json::from_str(json).map_err(|e| format!("invalid pack: {}", e))?;
+    for seed in &pack.seeds {
+        seed.encode_args()?;
+    }
+    Ok(pack)
+}
+
+/// Packs fetched into the directory
+pub fn load_packs(dir: &str) -> Vec<CorpusPack> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut packs = vec![];
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().map_or(true, |ext| ext != "json") {
+            continue;
+        }
+        match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|json| parse_pack(&json)) {
+            Ok(pack) => {
+                info!("Loaded pack {}: {} seeds", pack.name, pack.seeds.len());
+                packs.push(pack);
+            }
+            Err(e) => warn!("Ignoring pack {}: {}", path.display(), e),
+        }
+    }
+    packs
+}
+
+/// Download the pack into [`PACK_DIR`]
+pub fn fetch_pack(name: &str, url: &str) -> Result<String, String> {
+    let source = format!("{}/{}.json", url.trim_end_matches('/'), name);
+    let response = reqwest::blocking::get(&source).map_err(|e| format!("failed to download {}: {}", source, e))?;
+    if !response.status().is_success() {
+        return Err(format!("failed to download {}: {}", source, response.status()));
+    }
+    let json = response.text().map_err(|e| format!("failed to download {}: {}", source, e))?;
+    let pack = parse_pack(&json)?;
+    fs::create_dir_all(PACK_DIR).map_err(|e| format!("failed to create {}: {}", PACK_DIR, e))?;
+    let path = Path::new(PACK_DIR).join(format!("{}.json", name));
+    fs::write(&path, json).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(format!(
+        "Fetched pack {} ({} seeds, {} dictionary values) into {}\n",
+        pack.name,
+        pack.seeds.len(),
+        pack.dictionary.cmp_operands.len() + pack.dictionary.magic_values.len() + pack.dictionary.keccak_preimages.len(),
+        path.display()
+    ))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_seeds() {
+        let pack = parse_pack(
+            r#"{"name": "erc20", "seeds": [
+                {"signature": "transfer(address,uint256)", "args": ["0x0000000000000000000000000000000000000001", "1e18"]},
+                {"signature": "totalSupply()"}
+            ], "dictionary": {"cmp_operands": [], "keccak_preimages": [], "magic_values": ["0de0b6b3a7640000"]}}"#,
+        )
+        .unwrap();
+        let seeds = pack.seeds_for(&[0xa9, 0x05, 0x9c, 0xbb], "(address,uint256)");
+        assert_eq!(seeds.len(), 1);
+        assert_eq!(seeds[0].len(), 64);
+        assert_eq!(seeds[0][31], 1);
+        assert_eq!(hex::encode(&seeds[0][56..]), "0de0b6b3a7640000");
+        // same selector, arguments decompiled with other types
+        assert!(pack.seeds_for(&[0xa9, 0x05, 0x9c, 0xbb], "(unknown,unknown)").is_empty());
+        assert_eq!(pack.seeds_for(&[0x18, 0x16, 0x0d, 0xdd], "()"), vec![Vec::<u8>::new()]);
+
+        assert!(parse_pack(r#"{"name": "x", "seeds": [{"signature": "f(uint256)", "args": []}]}"#).is_err());
+        assert!(parse_pack(r#"{"name": "x", "seeds": [{"signature": "f(string)", "args": ["a"]}]}"#).is_err());
+    }
+}
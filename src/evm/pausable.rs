@@ -0,0 +1,144 @@
+/// Contracts paused in the initial state (OpenZeppelin `Pausable`, `--paused`)
+///
+/// A contract deployed (or forked) paused reverts every call guarded by `whenNotPaused`, and a
+/// campaign on it burns its budget on reverting calls. After deploying a target, the fuzzer calls
+/// its `paused()`; when it returns true, the flag is looked for in the storage of the target by
+/// clearing, one at a time, the bytes of its slots holding 1 (a `bool`, often packed with the
+/// owner, or in the ERC-7201 namespace of `PausableUpgradeable`) until `paused()` returns false.
+/// Then, depending on `--paused`:
+/// - `halt` (default): report the paused targets and stop
+/// - `explore`: fuzz both variants, the paused initial state as the base and an `unpaused`
+///   scenario clearing the flags (see [`crate::evm::scenario`]), their coverage and bugs being
+///   attributed separately
+/// - `ignore`: only warn, and fuzz the paused state
+use crate::evm::input::EVMInput;
+use crate::evm::types::{EVMAddress, EVMFuzzState, EVMU256};
+use crate::evm::vm::{EVMExecutor, EVMState};
+use crate::generic_vm::vm_executor::GenericVM;
+use bytes::Bytes;
+use std::str::FromStr;
+
+/// `paused()`
+pub const PAUSED_SELECTOR: [u8; 4] = [0x5c, 0x97, 0x5a, 0xbb];
+
+/// Name of the scenario clearing the flags of the paused targets
+pub const UNPAUSED_SCENARIO: &str = "unpaused";
+
+/// Slots of a target probed for the flag
+const MAX_PROBED_SLOTS: usize = 256;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PausedMode {
+    /// Report the paused targets and stop
+    #[default]
+    Halt,
+    /// Fuzz the paused and unpaused variants as separate scenarios
+    Explore,
+    /// Only warn
+    Ignore,
+}
+
+impl FromStr for PausedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" => Ok(PausedMode::Halt),
+            "explore" => Ok(PausedMode::Explore),
+            "ignore" => Ok(PausedMode::Ignore),
+            _ => Err(format!("unknown paused mode {} (halt, explore, ignore)", s)),
+        }
+    }
+}
+
+/// Decode the return of `paused()`, `None` if it is not a bool
+pub fn decode_paused(ret: &[u8]) -> Option<bool> {
+    if ret.len() != 32 || ret[..31].iter().any(|b| *b != 0) {
+        return None;
+    }
+    match ret[31] {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+/// The value with one of its bytes holding 1 cleared, for each such byte
+pub fn cleared_flag_candidates(value: EVMU256) -> Vec<EVMU256> {
+    let bytes: [u8; 32] = value.to_be_bytes();
+    (0..32)
+        .filter(|i| bytes[*i] == 1)
+        .map(|i| {
+            let mut cleared = bytes;
+            cleared[i] = 0;
+            EVMU256::from_be_slice(&cleared)
+        })
+        .collect()
+}
+
+/// Whether the contract is paused in the VM state, `None` if it has no `paused()`
+pub fn is_paused(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    address: EVMAddress,
+) -> Option<bool> {
+    let res = executor.fast_static_call(
+        &vec![(address, Bytes::from(PAUSED_SELECTOR.to_vec()))],
+        vm_state,
+        state,
+    );
+    decode_paused(&res[0])
+}
+
+/// Slot holding the flag of the paused contract, and its value once the flag is cleared
+pub fn find_paused_flag(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    address: EVMAddress,
+) -> Option<(EVMU256, EVMU256)> {
+    let storage = vm_state.get(&address)?.clone();
+    for (slot, value) in storage
+        .iter()
+        .filter(|(_, value)| **value != EVMU256::ZERO)
+        .take(MAX_PROBED_SLOTS)
+    {
+        for candidate in cleared_flag_candidates(*value) {
+            let mut probed = vm_state.clone();
+            probed.get_mut(&address).unwrap().insert(*slot, candidate);
+            if is_paused(executor, state, &probed, address) == Some(false) {
+                return Some((*slot, candidate));
+            }
+        }
+    }
+    None
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_flag() {
+        let mut ret = vec![0; 32];
+        assert_eq!(decode_paused(&ret), Some(false));
+        ret[31] = 1;
+        assert_eq!(decode_paused(&ret), Some(true));
+        ret[0] = 1;
+        assert_eq!(decode_paused(&ret), None);
+        assert_eq!(decode_paused(&[]), None);
+
+        // `_paused` packed after a 20-byte owner: 0x01 || owner
+        let mut packed = [0u8; 32];
+        packed[11] = 1;
+        packed[12..].copy_from_slice(&[0x01; 20]);
+        let candidates = cleared_flag_candidates(EVMU256::from_be_slice(&packed));
+        assert_eq!(candidates.len(), 21);
+        let mut unpaused = packed;
+        unpaused[11] = 0;
+        assert_eq!(candidates[0], EVMU256::from_be_slice(&unpaused));
+
+        assert_eq!(PausedMode::from_str("explore"), Ok(PausedMode::Explore));
+        assert!(PausedMode::from_str("resume").is_err());
+    }
+}
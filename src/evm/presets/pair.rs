@@ -39,6 +39,7 @@ where
                         is_address: true,
                         dont_mutate: true,
                         is_bytes: false,
+                        provenance: Default::default(),
                     }),
                     function: [0xbc, 0x25, 0xcf, 0x77],
                 });
@@ -0,0 +1,197 @@
+/// Manipulable price oracle stubs
+///
+/// The addresses declared as price feeds in `--price-feeds` are not executed: calls to them are
+/// answered by the host with the price carried by the input ([`EVMInput::feed_prices`]), which
+/// the mutator sets within the bounds of the feed. Price manipulation exploits thus become
+/// directly searchable without modelling the AMM pools the real feed would read from.
+///
+/// Chainlink aggregators (`latestRoundData()`, `latestAnswer()`, `decimals()`) are answered in
+/// their format, any other call to a feed returns the price as a single word (e.g.,
+/// `getPrice(asset)` of a custom oracle).
+use crate::evm::types::{EVMAddress, EVMU256};
+use bytes::Bytes;
+use libafl::prelude::{HasRand, Rand};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use tracing::info;
+
+const LATEST_ROUND_DATA: [u8; 4] = [0xfe, 0xaf, 0x96, 0x8c];
+const DECIMALS: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// A price feed and the bounds of the prices it can report
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PriceFeed {
+    pub name: String,
+    pub address: EVMAddress,
+    /// Price reported when the input does not set one
+    pub price: EVMU256,
+    pub min: EVMU256,
+    pub max: EVMU256,
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+}
+
+fn default_decimals() -> u8 {
+    8
+}
+
+impl PriceFeed {
+    /// Bring a mutated price within the bounds of the feed
+    pub fn clamp(&self, price: EVMU256) -> EVMU256 {
+        if self.max <= self.min {
+            return self.min;
+        }
+        if price >= self.min && price <= self.max {
+            return price;
+        }
+        let span = self.max - self.min;
+        if span == EVMU256::MAX {
+            return price;
+        }
+        self.min + price % (span + EVMU256::from(1))
+    }
+
+    /// Return data of a call to the feed reporting the price
+    pub fn response(&self, calldata: &[u8], price: EVMU256, timestamp: EVMU256) -> Bytes {
+        let word = |v: EVMU256| v.to_be_bytes::<32>().to_vec();
+        let selector = calldata.get(..4);
+        if selector == Some(&LATEST_ROUND_DATA[..]) {
+            // roundId, answer, startedAt, updatedAt, answeredInRound
+            let round = EVMU256::from(1);
+            Bytes::from([word(round), word(price), word(timestamp), word(timestamp), word(round)].concat())
+        } else if selector == Some(&DECIMALS[..]) {
+            Bytes::from(word(EVMU256::from(self.decimals)))
+        } else {
+            // latestAnswer() and custom getters
+            Bytes::from(word(price))
+        }
+    }
+}
+
+/// Price feeds declared in the config, indexed like [`EVMInput::feed_prices`]
+pub static mut PRICE_FEEDS: Vec<PriceFeed> = Vec::new();
+/// Prices reported by the feeds during the current execution, set from the input
+pub static mut FEED_PRICES: Vec<EVMU256> = Vec::new();
+
+/// Probability (in percent) that a mutation changes the price of a feed
+pub const PRICE_MUTATION_PROBABILITY: u64 = 10;
+
+/// Load price feeds from a JSON file containing an array of [`PriceFeed`]
+pub fn load_price_feeds(path: &str) -> Vec<PriceFeed> {
+    let mut file = File::open(path).expect("failed to open price feed file");
+    let mut data = String::new();
+    file.read_to_string(&mut data)
+        .expect("failed to read price feed file");
+    serde_json::from_str(&data).expect("failed to parse price feed file")
+}
+
+pub fn register_price_feeds(feeds: Vec<PriceFeed>) {
+    for feed in &feeds {
+        info!("Registered price feed {} at {:?}", feed.name, feed.address);
+    }
+    unsafe {
+        PRICE_FEEDS = feeds;
+    }
+}
+
+pub fn price_feed_count() -> usize {
+    unsafe { PRICE_FEEDS.len() }
+}
+
+/// Set the prices reported during the execution of an input
+pub fn set_feed_prices(prices: &Vec<EVMU256>) {
+    unsafe {
+        FEED_PRICES = prices.clone();
+    }
+}
+
+/// Answer a call to a price feed, `None` if the address is not a feed
+pub fn price_feed_call(address: EVMAddress, calldata: &[u8], timestamp: EVMU256) -> Option<Bytes> {
+    let feeds = unsafe { &PRICE_FEEDS };
+    let idx = feeds.iter().position(|feed| feed.address == address)?;
+    let feed = &feeds[idx];
+    let price = unsafe { FEED_PRICES.get(idx) }
+        .map(|price| feed.clamp(*price))
+        .unwrap_or(feed.price);
+    Some(feed.response(calldata, price, timestamp))
+}
+
+/// Set the price of a random feed to one of its bounds, a random price within them or a
+/// multiple / fraction of its current price, returns whether the prices changed
+pub fn mutate_feed_prices<S: HasRand>(prices: &mut Vec<EVMU256>, state: &mut S) -> bool {
+    let feeds = unsafe { &PRICE_FEEDS };
+    if feeds.is_empty() {
+        return false;
+    }
+    while prices.len() < feeds.len() {
+        prices.push(feeds[prices.len()].price);
+    }
+    let idx = state.rand_mut().below(feeds.len() as u64) as usize;
+    let feed = &feeds[idx];
+    let current = prices[idx];
+    let price = match state.rand_mut().below(5) {
+        0 => feed.min,
+        1 => feed.max,
+        2 => feed.clamp(EVMU256::from(state.rand_mut().next())),
+        3 => feed.clamp(current.saturating_mul(EVMU256::from(2))),
+        _ => feed.clamp(current / EVMU256::from(2)),
+    };
+    prices[idx] = price;
+    price != current
+}
+
+/// Prefix of the pretty transaction of an input setting the feed prices
+pub fn price_prefix(prices: &Vec<EVMU256>) -> String {
+    if prices.is_empty() {
+        return String::new();
+    }
+    format!(
+        "(prices {}) ",
+        prices.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",")
+    )
+}
+
+/// Feed prices of a pretty transaction, see [`price_prefix`]
+pub fn parse_price_prefix(pretty_txn: &str) -> Vec<EVMU256> {
+    pretty_txn
+        .split("(prices ")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .map(|list| parse_price_list(list))
+        .unwrap_or_default()
+}
+
+/// Parse a comma separated list of prices
+pub fn parse_price_list(list: &str) -> Vec<EVMU256> {
+    list.split(',')
+        .filter_map(|p| EVMU256::from_str_radix(p.trim(), 10).ok())
+        .collect()
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_feed() {
+        let feed = PriceFeed {
+            name: "ETH/USD".to_string(),
+            address: EVMAddress::zero(),
+            price: EVMU256::from(2000),
+            min: EVMU256::from(1000),
+            max: EVMU256::from(3000),
+            decimals: 8,
+        };
+        assert_eq!(feed.clamp(EVMU256::from(1500)), EVMU256::from(1500));
+        assert!(feed.clamp(EVMU256::from(5000)) <= feed.max);
+        assert!(feed.clamp(EVMU256::from(5)) >= feed.min);
+
+        let round = feed.response(&LATEST_ROUND_DATA, EVMU256::from(1500), EVMU256::from(7));
+        assert_eq!(round.len(), 160);
+        assert_eq!(EVMU256::try_from_be_slice(&round[32..64]).unwrap(), EVMU256::from(1500));
+        assert_eq!(feed.response(&DECIMALS, EVMU256::ZERO, EVMU256::ZERO)[31], 8);
+
+        let prices = vec![EVMU256::from(1), EVMU256::from(2)];
+        assert_eq!(parse_price_prefix(&format!("{}ABI with None ETH", price_prefix(&prices))), prices);
+    }
+}
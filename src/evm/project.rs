@@ -0,0 +1,348 @@
+/// Contracts of a Foundry or Hardhat project, from their build artifacts
+///
+/// `-t <project root>` (or `--target-type project`) loads every artifact of `out/**/*.json`
+/// (Foundry) or `artifacts/**/*.json` (Hardhat, storage layouts read from
+/// `artifacts/build-info`), with their ABI, creation bytecode, link references and storage
+/// layout. Targets are picked by contract name with `--contracts Vault,Token` (`<source>:<name>`
+/// when the name is ambiguous). Without it, the deployable contracts of the project are fuzzed,
+/// leaving out the dependencies, tests and scripts. The libraries the targets link are deployed
+/// first and their addresses are written over the placeholders of the bytecode.
+use crate::evm::hex_utils::HexBytecode;
+use crate::evm::storage_layout::{parse_storage_layout, StorageLayout};
+use crate::evm::types::EVMAddress;
+use glob::glob;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Offsets of the placeholders of a library in a bytecode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkReference {
+    pub source: String,
+    pub library: String,
+    pub offsets: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProjectContract {
+    pub name: String,
+    /// Source file, relative to the project root
+    pub source: String,
+    /// ABI, as JSON
+    pub abi: String,
+    /// Creation bytecode, hex, with the placeholders of the libraries
+    pub bytecode: String,
+    pub link_references: Vec<LinkReference>,
+    pub storage_layout: Option<StorageLayout>,
+}
+
+impl ProjectContract {
+    pub fn qualified_name(&self) -> String {
+        format!("{}:{}", self.source, self.name)
+    }
+
+    /// Interfaces and abstract contracts have no bytecode
+    pub fn is_deployable(&self) -> bool {
+        !self.bytecode.is_empty()
+    }
+
+    /// Dependencies, tests and scripts, not fuzzed unless asked for
+    pub fn is_auxiliary(&self) -> bool {
+        let source = self.source.as_str();
+        ["lib/", "node_modules/", "test/", "script/", "hardhat/", "forge-std/"]
+            .iter()
+            .any(|dir| source.starts_with(dir))
+            || source.starts_with('@')
+            || source.ends_with(".t.sol")
+            || source.ends_with(".s.sol")
+    }
+}
+
+fn parse_link_references(json: &Value) -> Vec<LinkReference> {
+    let mut references = vec![];
+    for (source, libraries) in json.as_object().into_iter().flatten() {
+        for (library, offsets) in libraries.as_object().into_iter().flatten() {
+            references.push(LinkReference {
+                source: source.clone(),
+                library: library.clone(),
+                offsets: offsets
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|offset| offset["start"].as_u64())
+                    .map(|start| start as usize)
+                    .collect(),
+            });
+        }
+    }
+    references
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let mut data = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut data))
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&data).map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+}
+
+fn strip_hex(code: &str) -> String {
+    code.trim().trim_start_matches("0x").to_string()
+}
+
+/// Contract of a Foundry artifact `out/<file>.sol/<name>.json`
+pub fn parse_foundry_artifact(json: &Value, path: &Path) -> Result<ProjectContract, String> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.split('.').next())
+        .ok_or(format!("invalid artifact name {}", path.display()))?
+        .to_string();
+    // the artifacts are grouped by source file name, the full path is in the metadata
+    let source = json["metadata"]["settings"]["compilationTarget"]
+        .as_object()
+        .and_then(|target| target.keys().next().cloned())
+        .or_else(|| json["ast"]["absolutePath"].as_str().map(|s| s.to_string()))
+        .or_else(|| path.parent()?.file_name()?.to_str().map(|s| s.to_string()))
+        .unwrap_or_default();
+    let bytecode = &json["bytecode"];
+    Ok(ProjectContract {
+        name,
+        source,
+        abi: json["abi"].to_string(),
+        bytecode: strip_hex(bytecode["object"].as_str().ok_or("artifact without bytecode")?),
+        link_references: parse_link_references(&bytecode["linkReferences"]),
+        storage_layout: match json.get("storageLayout") {
+            Some(layout) if !layout.is_null() => Some(parse_storage_layout(layout)?),
+            _ => None,
+        },
+    })
+}
+
+/// Contract of a Hardhat artifact `artifacts/<source>/<name>.json`
+pub fn parse_hardhat_artifact(json: &Value) -> Result<ProjectContract, String> {
+    let field = |name: &str| json[name].as_str().ok_or(format!("artifact without {}", name));
+    Ok(ProjectContract {
+        name: field("contractName")?.to_string(),
+        source: field("sourceName")?.to_string(),
+        abi: json["abi"].to_string(),
+        bytecode: strip_hex(field("bytecode")?),
+        link_references: parse_link_references(&json["linkReferences"]),
+        storage_layout: None,
+    })
+}
+
+/// Replace the placeholders of the libraries with their addresses, by (source, name)
+pub fn link(
+    bytecode: &str,
+    references: &Vec<LinkReference>,
+    addresses: &HashMap<(String, String), EVMAddress>,
+) -> Result<Vec<u8>, String> {
+    let mut code = bytecode.to_string();
+    for reference in references {
+        let address = addresses
+            .get(&(reference.source.clone(), reference.library.clone()))
+            .ok_or(format!("library {}:{} is not deployed", reference.source, reference.library))?;
+        for offset in &reference.offsets {
+            let start = offset * 2;
+            if start + 40 > code.len() {
+                return Err(format!("link reference of {} out of the bytecode", reference.library));
+            }
+            code.replace_range(start..start + 40, &hex::encode(address.0));
+        }
+    }
+    HexBytecode::from_hex(&code)
+        .map(HexBytecode::into_bytes)
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Project {
+    pub contracts: Vec<ProjectContract>,
+}
+
+impl Project {
+    /// Whether the path is the root of a Foundry or Hardhat project
+    pub fn is_project(path: &str) -> bool {
+        let root = Path::new(path);
+        root.is_dir()
+            && ["foundry.toml", "hardhat.config.js", "hardhat.config.ts", "out", "artifacts"]
+                .iter()
+                .any(|file| root.join(file).exists())
+    }
+
+    pub fn load(root: &str) -> Result<Self, String> {
+        let root = Path::new(root);
+        let mut project = Project::default();
+        if root.join("out").is_dir() {
+            project.load_foundry(&root.join("out"))?;
+        } else if root.join("artifacts").is_dir() {
+            project.load_hardhat(&root.join("artifacts"))?;
+        } else {
+            return Err(format!("no out/ or artifacts/ in {}, build the project first", root.display()));
+        }
+        Ok(project)
+    }
+
+    fn artifacts(dir: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+        let pattern = dir.join("**").join("*.json");
+        Ok(glob(&pattern.to_string_lossy())
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .collect())
+    }
+
+    fn load_foundry(&mut self, out: &Path) -> Result<(), String> {
+        for path in Self::artifacts(out)? {
+            if path.components().any(|c| c.as_os_str() == "build-info") {
+                continue;
+            }
+            let json = read_json(&path)?;
+            if json.get("bytecode").is_none() {
+                continue;
+            }
+            self.contracts.push(parse_foundry_artifact(&json, &path)?);
+        }
+        Ok(())
+    }
+
+    fn load_hardhat(&mut self, artifacts: &Path) -> Result<(), String> {
+        let mut layouts = HashMap::new();
+        for path in Self::artifacts(&artifacts.join("build-info"))? {
+            let json = read_json(&path)?;
+            for (source, contracts) in json["output"]["contracts"].as_object().into_iter().flatten() {
+                for (name, contract) in contracts.as_object().into_iter().flatten() {
+                    if let Some(layout) = contract.get("storageLayout") {
+                        layouts.insert((source.clone(), name.clone()), parse_storage_layout(layout)?);
+                    }
+                }
+            }
+        }
+        for path in Self::artifacts(artifacts)? {
+            let file = path.to_string_lossy();
+            if file.ends_with(".dbg.json") || path.components().any(|c| c.as_os_str() == "build-info") {
+                continue;
+            }
+            let json = read_json(&path)?;
+            if json.get("contractName").is_none() {
+                continue;
+            }
+            let mut contract = parse_hardhat_artifact(&json)?;
+            contract.storage_layout = layouts.remove(&(contract.source.clone(), contract.name.clone()));
+            self.contracts.push(contract);
+        }
+        Ok(())
+    }
+
+    /// Index of the contract `name` or `<source>:<name>`
+    pub fn find(&self, name: &str) -> Result<usize, String> {
+        let matches = self
+            .contracts
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.name == name || c.qualified_name() == name)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        match matches.as_slice() {
+            [idx] => Ok(*idx),
+            [] => Err(format!("no contract {} in the project", name)),
+            _ => Err(format!(
+                "contract {} is ambiguous, use one of {}",
+                name,
+                matches
+                    .iter()
+                    .map(|i| self.contracts[*i].qualified_name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Contracts fuzzed, the deployable non-auxiliary ones when no name is given
+    pub fn targets(&self, names: &Vec<String>) -> Result<Vec<usize>, String> {
+        if names.is_empty() {
+            return Ok((0..self.contracts.len())
+                .filter(|i| self.contracts[*i].is_deployable() && !self.contracts[*i].is_auxiliary())
+                .collect());
+        }
+        names.iter().map(|name| self.find(name)).collect()
+    }
+
+    /// The targets and the libraries they link, each library before the contracts linking it
+    pub fn deployment_order(&self, targets: &Vec<usize>) -> Result<Vec<usize>, String> {
+        fn visit(project: &Project, idx: usize, visiting: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<(), String> {
+            if order.contains(&idx) {
+                return Ok(());
+            }
+            if visiting.contains(&idx) {
+                return Err(format!("cyclic library links through {}", project.contracts[idx].name));
+            }
+            visiting.push(idx);
+            for reference in &project.contracts[idx].link_references {
+                let library = project.find(&format!("{}:{}", reference.source, reference.library))?;
+                visit(project, library, visiting, order)?;
+            }
+            visiting.pop();
+            order.push(idx);
+            Ok(())
+        }
+        let mut order = vec![];
+        for idx in targets {
+            if !self.contracts[*idx].is_deployable() {
+                return Err(format!("{} has no bytecode (interface or abstract contract)", self.contracts[*idx].name));
+            }
+            visit(self, *idx, &mut vec![], &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Storage layouts of the contracts, by name
+    pub fn storage_layouts(&self) -> HashMap<String, StorageLayout> {
+        self.contracts
+            .iter()
+            .filter_map(|c| Some((c.name.clone(), c.storage_layout.clone()?)))
+            .collect()
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_foundry_artifact() {
+        let placeholder = "__$8d2bbd2b8d86e93d67fe1f5ec4c29cab50$__";
+        let json: Value = serde_json::from_str(&format!(
+            r#"{{"abi": [], "bytecode": {{"object": "0x73{}5b", "linkReferences": {{"src/Math.sol": {{"Math": [{{"start": 1, "length": 20}}]}}}}}},
+                "metadata": {{"settings": {{"compilationTarget": {{"src/Vault.sol": "Vault"}}}}}}}}"#,
+            placeholder
+        ))
+        .unwrap();
+        let vault = parse_foundry_artifact(&json, Path::new("out/Vault.sol/Vault.0.8.19.json")).unwrap();
+        assert_eq!((vault.name.as_str(), vault.source.as_str()), ("Vault", "src/Vault.sol"));
+        assert!(!vault.is_auxiliary());
+
+        let math = ProjectContract {
+            name: "Math".to_string(),
+            source: "src/Math.sol".to_string(),
+            abi: "[]".to_string(),
+            bytecode: "00".to_string(),
+            link_references: vec![],
+            storage_layout: None,
+        };
+        let project = Project {
+            contracts: vec![vault.clone(), math],
+        };
+        assert_eq!(project.deployment_order(&vec![0]).unwrap(), vec![1, 0]);
+        assert_eq!(project.targets(&vec!["src/Math.sol:Math".to_string()]).unwrap(), vec![1]);
+
+        let address = EVMAddress::from_str("0x00000000000000000000000000000000000000aa").unwrap();
+        let addresses = HashMap::from([(("src/Math.sol".to_string(), "Math".to_string()), address)]);
+        let code = link(&vault.bytecode, &vault.link_references, &addresses).unwrap();
+        assert_eq!(code.len(), 22);
+        assert_eq!(code[20], 0xaa);
+        assert!(link(&vault.bytecode, &vault.link_references, &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+/// Provenance of the arguments of the solutions (why this value?)
+///
+/// Each `A256` and `ADynamic` arg records where its current value comes from, set by the
+/// mutation that produced it:
+/// - `seed`: the value of the initial corpus, never mutated
+/// - `dictionary`: a constant of the contracts (constant pool)
+/// - `comparison`: an operand of a comparison, either guarding a branch of the function being
+///   mutated ([`crate::evm::mutation_utils::SelectorConstantsMetadata`]) or learned during
+///   execution ([`crate::evm::dictionary`]), the input-to-state substitution of Redqueen
+/// - `storage slot <slot>`: the value (or the key) of a slot of the target, copied from the VM
+///   state
+/// - `address`: an address of the pool
+/// - `amount`: an amount relative to a live token balance ([`crate::evm::token_flow`])
+/// - `structured`: a structured value of a dynamic arg ([`crate::evm::bytes_mutation`])
+/// - `random`: the byte-level mutations
+///
+/// The hint mutators (constant and storage) record the bytes they wrote; the arg keeps their
+/// provenance only if the byte mutations stacked after them left those bytes unchanged. The
+/// provenance of the args of the transactions of a solution is written to its solution file and
+/// to the summary.
+use crate::evm::abi::{AArray, ADynamic, AUnknown, BoxedABI, A256};
+use crate::evm::types::EVMU256;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::ops::DerefMut;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArgProvenance {
+    Seed,
+    Dictionary,
+    Comparison,
+    Storage(EVMU256),
+    Address,
+    Amount,
+    Structured,
+    Random,
+}
+
+impl Default for ArgProvenance {
+    fn default() -> Self {
+        ArgProvenance::Seed
+    }
+}
+
+impl Display for ArgProvenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgProvenance::Seed => write!(f, "seed"),
+            ArgProvenance::Dictionary => write!(f, "dictionary"),
+            ArgProvenance::Comparison => write!(f, "comparison"),
+            ArgProvenance::Storage(slot) => write!(f, "storage slot {:#x}", slot),
+            ArgProvenance::Address => write!(f, "address"),
+            ArgProvenance::Amount => write!(f, "amount"),
+            ArgProvenance::Structured => write!(f, "structured"),
+            ArgProvenance::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// Provenance and bytes written by the last hint mutator
+static mut HINT: Option<(ArgProvenance, Vec<u8>)> = None;
+
+/// Called by the hint mutators with the bytes they wrote
+pub fn record_hint(provenance: ArgProvenance, bytes: &[u8]) {
+    unsafe {
+        HINT = Some((provenance, bytes.to_vec()));
+    }
+}
+
+/// Forget the hint, before mutating an arg
+pub fn clear_hint() {
+    unsafe {
+        HINT = None;
+    }
+}
+
+/// Provenance of an arg mutated into `bytes`: the hint if the bytes are still the ones it wrote
+pub fn mutated_provenance(bytes: &[u8]) -> ArgProvenance {
+    match unsafe { HINT.take() } {
+        Some((provenance, hinted)) if hinted == bytes => provenance,
+        _ => ArgProvenance::Random,
+    }
+}
+
+fn hex_value(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn collect(abi: &BoxedABI, args: &mut Vec<(String, ArgProvenance)>) {
+    let mut abi = abi.clone();
+    let any = abi.b.deref_mut().as_any();
+    if let Some(a256) = any.downcast_mut::<A256>() {
+        args.push((hex_value(&a256.data), a256.provenance.clone()));
+    } else if let Some(adyn) = any.downcast_mut::<ADynamic>() {
+        args.push((hex_value(&adyn.data), adyn.provenance.clone()));
+    } else if let Some(aarray) = any.downcast_mut::<AArray>() {
+        for item in &aarray.data {
+            collect(item, args);
+        }
+    } else if let Some(unknown) = any.downcast_mut::<AUnknown>() {
+        collect(&unknown.concrete, args);
+    }
+}
+
+/// Value and provenance of each leaf arg of the ABI tree, in order
+pub fn arg_provenances(abi: &BoxedABI) -> Vec<(String, ArgProvenance)> {
+    let mut args = vec![];
+    collect(abi, &mut args);
+    args
+}
+
+/// One line per transaction: `function(value (provenance), ...)`
+pub fn provenance_line(function: &str, abi: &BoxedABI) -> String {
+    let args = arg_provenances(abi)
+        .iter()
+        .map(|(value, provenance)| format!("{} ({})", value, provenance))
+        .collect::<Vec<_>>();
+    format!("{}({})", function, args.join(", "))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arg_provenance() {
+        clear_hint();
+        record_hint(ArgProvenance::Storage(EVMU256::from(3)), &[0, 7]);
+        assert_eq!(mutated_provenance(&[0, 7]), ArgProvenance::Storage(EVMU256::from(3)));
+        // the hint is consumed, and bytes mutated after it are random
+        assert_eq!(mutated_provenance(&[0, 7]), ArgProvenance::Random);
+        record_hint(ArgProvenance::Comparison, &[1]);
+        assert_eq!(mutated_provenance(&[2]), ArgProvenance::Random);
+
+        let mut amount = A256 {
+            data: vec![0x10],
+            is_address: false,
+            dont_mutate: false,
+            is_bytes: false,
+            provenance: ArgProvenance::Dictionary,
+        };
+        let constant = BoxedABI::new(Box::new(amount.clone()));
+        amount.provenance = ArgProvenance::Storage(EVMU256::from(1));
+        let abi = BoxedABI::new(Box::new(AArray {
+            data: vec![constant, BoxedABI::new(Box::new(amount))],
+            dynamic_size: false,
+        }));
+        assert_eq!(
+            provenance_line("f", &abi),
+            "f(0x10 (dictionary), 0x10 (storage slot 0x1))"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+/// Binding of a PTX kernel to the bytecode it is translated from
+///
+/// A stale kernel (translated from another `.bin`) runs without complaint and yields garbage
+/// coverage and solutions. The keccak256 of the source bytecode is embedded as a comment in the
+/// PTX once it is translated (`--sign-ptx`), and checked against the targets before the kernel is
+/// loaded by the runner. A mismatch aborts the campaign unless `--force` is given. The header also
+/// declares the instrumentation (see [`crate::evm::sanitizers`]) and the EVM version (see
+/// [`crate::evm::evm_version`]) of the kernel.
+use crate::error::{MauError, MauResult};
+use crate::evm::evm_version::{evm_version_header, EVMVersion, PTX_EVM_VERSION_PREFIX};
+use crate::evm::hex_utils::HexBytecode;
+use crate::evm::immutables::template_signature;
+use crate::evm::sanitizers::{sanitizers_header, Sanitizer, PTX_SANITIZERS_PREFIX};
+use std::fs::{self, File};
+use std::io::Read;
+
+/// Prefix of the PTX comment holding the bytecode hash
+pub const PTX_HASH_PREFIX: &str = "// mau bytecode keccak256: ";
+
+/// Hash of the bytecode a kernel is translated from
+pub fn bytecode_hash(code: &[u8]) -> String {
+    template_signature(code)
+}
+
+/// Bytecode hash embedded in the PTX source
+pub fn parse_bytecode_hash(ptx: &str) -> Option<String> {
+    ptx.lines()
+        .find_map(|line| line.strip_prefix(PTX_HASH_PREFIX))
+        .map(|hash| hash.trim().to_string())
+}
+
+pub(crate) fn read_ptx(ptx_path: &str) -> String {
+    let mut ptx = String::new();
+    File::open(ptx_path)
+        .expect("failed to open PTX kernel")
+        .read_to_string(&mut ptx)
+        .expect("failed to read PTX kernel");
+    ptx
+}
+
+/// Embed the hash of the source bytecode (hex, as given to ptxsema), the instrumentation and the
+/// EVM version the kernel is built with in the PTX kernel, replacing the previous ones
+pub fn sign_ptx(ptx_path: &str, bin_path: &str, sanitizers: &Vec<Sanitizer>, version: EVMVersion) -> MauResult<String> {
+    let bin = fs::read_to_string(bin_path).map_err(|e| MauError::io(bin_path, e))?;
+    let code = HexBytecode::from_hex(&bin)
+        .map_err(|e| MauError::Config { path: bin_path.to_string(), msg: e.to_string() })?;
+    let hash = bytecode_hash(code.as_slice());
+
+    let ptx = fs::read_to_string(ptx_path)
+        .map_err(|e| MauError::io(ptx_path, e))?
+        .lines()
+        .filter(|line| {
+            !line.starts_with(PTX_HASH_PREFIX)
+                && !line.starts_with(PTX_SANITIZERS_PREFIX)
+                && !line.starts_with(PTX_EVM_VERSION_PREFIX)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let header = format!(
+        "{}{}\n{}\n{}",
+        PTX_HASH_PREFIX,
+        hash,
+        sanitizers_header(sanitizers),
+        evm_version_header(version)
+    );
+    fs::write(ptx_path, format!("{}\n{}\n", header, ptx)).map_err(|e| MauError::io(ptx_path, e))?;
+    Ok(hash)
+}
+
+/// Whether the PTX kernel is translated from the bytecode, a kernel carrying no hash (only loaded
+/// with `--force`) is trusted
+pub fn ptx_translated_from(ptx_path: &str, code: &[u8]) -> bool {
+    parse_bytecode_hash(&read_ptx(ptx_path)).map_or(true, |hash| hash == bytecode_hash(code))
+}
+
+/// Check that the PTX kernel is translated from one of the bytecodes
+pub fn check_ptx_binding(ptx_path: &str, codes: &Vec<&[u8]>) -> Result<(), String> {
+    let hash = match parse_bytecode_hash(&read_ptx(ptx_path)) {
+        Some(hash) => hash,
+        None => {
+            return Err(format!(
+                "PTX kernel {} carries no bytecode hash, sign it with --sign-ptx <bin>",
+                ptx_path
+            ))
+        }
+    };
+    if codes.iter().any(|code| bytecode_hash(code) == hash) {
+        Ok(())
+    } else {
+        Err(format!(
+            "PTX kernel {} is translated from bytecode {}, which matches none of the targets",
+            ptx_path, hash
+        ))
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bytecode_hash() {
+        let ptx = format!("{}abcd\n.version 8.0\n.target sm_86\n", PTX_HASH_PREFIX);
+        assert_eq!(parse_bytecode_hash(&ptx), Some("abcd".to_string()));
+        assert_eq!(parse_bytecode_hash(".version 8.0\n"), None);
+    }
+}
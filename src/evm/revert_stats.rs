@@ -0,0 +1,147 @@
+/// Per function revert rate statistics
+///
+/// A function whose executions almost always revert within the first few instructions (the
+/// guard phase: `require(msg.sender == owner)`, `whenNotPaused`, ...) is dead for the fuzzer:
+/// mutating its arguments is wasted until the state or the caller allows passing the guard. The
+/// fraction of the executions of each selector that revert in the guard phase is printed
+/// periodically and written to `stats/revert_rates.json`, so that users can see where manual
+/// seeds or state preparation (e.g., `--scenario-file`) are needed.
+use crate::evm::abi::function_sig_name;
+use crate::workdir::stats_dir;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use tracing::info;
+
+pub const REVERT_STATS_FILE: &str = "revert_rates.json";
+
+/// Print and save the revert rates every this many executions
+pub const REVERT_STATS_INTERVAL: usize = 50000;
+
+/// Number of instructions of the guard phase, reverts after that are not counted as guard reverts
+pub static mut GUARD_PHASE_INSTRUCTIONS: usize = 64;
+
+/// Instructions executed by the current transaction, incremented by the host
+pub static mut INSTRUCTIONS_EXECUTED: usize = 0;
+
+/// A selector is reported as dead above this guard revert rate
+pub const DEAD_RATE: f64 = 0.95;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SelectorStats {
+    pub executions: usize,
+    pub reverts: usize,
+    /// Reverts within the first [`GUARD_PHASE_INSTRUCTIONS`] instructions
+    pub guard_reverts: usize,
+}
+
+impl SelectorStats {
+    pub fn guard_revert_rate(&self) -> f64 {
+        if self.executions == 0 {
+            return 0.0;
+        }
+        self.guard_reverts as f64 / self.executions as f64
+    }
+}
+
+static mut REVERT_STATS: Option<HashMap<[u8; 4], SelectorStats>> = None;
+
+fn revert_stats() -> &'static mut HashMap<[u8; 4], SelectorStats> {
+    unsafe { REVERT_STATS.get_or_insert_with(Default::default) }
+}
+
+/// Record the execution of a transaction with the given calldata
+pub fn record_execution(calldata: &[u8], reverted: bool) {
+    if calldata.len() < 4 {
+        return;
+    }
+    let stats = revert_stats()
+        .entry(calldata[..4].try_into().unwrap())
+        .or_default();
+    stats.executions += 1;
+    if reverted {
+        stats.reverts += 1;
+        if unsafe { INSTRUCTIONS_EXECUTED <= GUARD_PHASE_INSTRUCTIONS } {
+            stats.guard_reverts += 1;
+        }
+    }
+}
+
+/// Selectors sorted by decreasing guard revert rate
+fn sorted_stats() -> Vec<(String, SelectorStats)> {
+    let mut stats = revert_stats()
+        .iter()
+        .map(|(selector, stats)| (function_sig_name(selector), stats.clone()))
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| {
+        b.1.guard_revert_rate()
+            .partial_cmp(&a.1.guard_revert_rate())
+            .unwrap()
+            .then(a.0.cmp(&b.0))
+    });
+    stats
+}
+
+/// Selectors whose guard revert rate is above [`DEAD_RATE`], with their rate
+pub fn dead_selectors() -> Vec<([u8; 4], f64)> {
+    let mut dead = revert_stats()
+        .iter()
+        .map(|(selector, stats)| (*selector, stats.guard_revert_rate()))
+        .filter(|(_, rate)| *rate >= DEAD_RATE)
+        .collect::<Vec<_>>();
+    dead.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    dead
+}
+
+/// Print the functions the fuzzer cannot get past and save all the revert rates
+pub fn report_revert_stats() {
+    let stats = sorted_stats();
+    for (name, stats) in stats.iter().filter(|(_, s)| s.guard_revert_rate() >= DEAD_RATE) {
+        info!(
+            "[revert] {}: {:.1}% of {} execs revert in the first {} instructions, consider seeding it or preparing the state",
+            name,
+            stats.guard_revert_rate() * 100.0,
+            stats.executions,
+            unsafe { GUARD_PHASE_INSTRUCTIONS }
+        );
+    }
+    let path = Path::new(&stats_dir()).join(REVERT_STATS_FILE);
+    if let Ok(mut file) = File::create(path) {
+        let rates = stats
+            .iter()
+            .map(|(name, stats)| {
+                serde_json::json!({
+                    "function": name,
+                    "executions": stats.executions,
+                    "reverts": stats.reverts,
+                    "guard_reverts": stats.guard_reverts,
+                    "guard_revert_rate": stats.guard_revert_rate(),
+                })
+            })
+            .collect::<Vec<_>>();
+        file.write_all(serde_json::to_string_pretty(&rates).unwrap().as_bytes())
+            .expect("failed to write revert rates");
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_revert_rate() {
+        let stats = SelectorStats {
+            executions: 4,
+            reverts: 3,
+            guard_reverts: 2,
+        };
+        assert_eq!(stats.guard_revert_rate(), 0.5);
+        assert_eq!(SelectorStats::default().guard_revert_rate(), 0.0);
+
+        record_execution(&[0xde, 0xad, 0xbe, 0xef, 0x00], true);
+        record_execution(&[0xde, 0xad], true);
+        let recorded = &revert_stats()[&[0xde, 0xad, 0xbe, 0xef]];
+        assert_eq!((recorded.executions, recorded.reverts), (1, 1));
+    }
+}
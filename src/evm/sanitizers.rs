@@ -0,0 +1,156 @@
+/// Sanitizers selected with `--sanitize`
+///
+/// A sanitizer is a class of bugs to detect. Host-side sanitizers are oracles checked on revm,
+/// the others need instrumentation built into the PTX kernel by ptxsema. The kernel declares the
+/// instrumentation it carries in its header (written by `--sign-ptx` along with the bytecode
+/// hash, see [`crate::evm::ptx_binding`]) so that a campaign asking for a sanitizer the kernel
+/// lacks fails at startup instead of silently missing bugs.
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Prefix of the PTX comment listing the instrumentation of the kernel
+pub const PTX_SANITIZERS_PREFIX: &str = "// mau sanitizers: ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sanitizer {
+    /// Integer overflow / underflow
+    Ibsan,
+    /// Reads of uninitialized memory and out-of-bounds copies
+    Msan,
+    /// `bug()` hits
+    Bug,
+    /// Storage written by a contract re-entered during its own execution
+    Reentrancy,
+    /// `SELFDESTRUCT` of a target
+    Selfdestruct,
+    /// Initializer of an upgradeable target called by someone else than the deployer
+    Initializer,
+    /// SSTORE to a slot controlled by calldata
+    StorageWrite,
+    /// Transaction succeeding with its calls failed at the call depth limit
+    CallDepth,
+    /// Transaction succeeding with an inner call out of gas, its caller writing storage
+    OogGriefing,
+    /// Ether sent to a non-deployer caller by a function without caller check
+    Withdrawal,
+}
+
+impl FromStr for Sanitizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ibsan" => Ok(Sanitizer::Ibsan),
+            "msan" => Ok(Sanitizer::Msan),
+            "bug" => Ok(Sanitizer::Bug),
+            "reentrancy" => Ok(Sanitizer::Reentrancy),
+            "selfdestruct" => Ok(Sanitizer::Selfdestruct),
+            "initializer" => Ok(Sanitizer::Initializer),
+            "storage-write" => Ok(Sanitizer::StorageWrite),
+            "call-depth" => Ok(Sanitizer::CallDepth),
+            "oog-griefing" => Ok(Sanitizer::OogGriefing),
+            "withdrawal" => Ok(Sanitizer::Withdrawal),
+            _ => Err(format!(
+                "Unknown sanitizer: {} (expected ibsan, msan, bug, reentrancy, selfdestruct, initializer, storage-write, call-depth, oog-griefing or withdrawal)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Sanitizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Sanitizer::Ibsan => "ibsan",
+            Sanitizer::Msan => "msan",
+            Sanitizer::Bug => "bug",
+            Sanitizer::Reentrancy => "reentrancy",
+            Sanitizer::Selfdestruct => "selfdestruct",
+            Sanitizer::Initializer => "initializer",
+            Sanitizer::StorageWrite => "storage-write",
+            Sanitizer::CallDepth => "call-depth",
+            Sanitizer::OogGriefing => "oog-griefing",
+            Sanitizer::Withdrawal => "withdrawal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Sanitizer {
+    /// Whether the kernel has to be instrumented for the sanitizer to work in GPU
+    pub fn needs_instrumentation(&self) -> bool {
+        matches!(self, Sanitizer::Ibsan | Sanitizer::Msan | Sanitizer::Bug)
+    }
+}
+
+/// Parse a comma separated list of sanitizers
+pub fn parse_sanitizers(s: &str) -> Result<Vec<Sanitizer>, String> {
+    let mut sanitizers = vec![];
+    for name in s.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        let sanitizer = Sanitizer::from_str(name)?;
+        if !sanitizers.contains(&sanitizer) {
+            sanitizers.push(sanitizer);
+        }
+    }
+    Ok(sanitizers)
+}
+
+/// PTX comment declaring the instrumentation of the kernel
+pub fn sanitizers_header(sanitizers: &Vec<Sanitizer>) -> String {
+    format!(
+        "{}{}",
+        PTX_SANITIZERS_PREFIX,
+        sanitizers.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(",")
+    )
+}
+
+/// Instrumentation declared in the PTX source, `None` if the kernel has no such header
+pub fn parse_ptx_sanitizers(ptx: &str) -> Option<Vec<Sanitizer>> {
+    ptx.lines()
+        .find_map(|line| line.strip_prefix(PTX_SANITIZERS_PREFIX))
+        .map(|list| parse_sanitizers(list.trim()).unwrap_or_default())
+}
+
+/// Check that the kernel carries the instrumentation of the requested sanitizers
+pub fn check_ptx_sanitizers(ptx: &str, requested: &Vec<Sanitizer>) -> Result<(), String> {
+    let needed = requested
+        .iter()
+        .filter(|s| s.needs_instrumentation())
+        .cloned()
+        .collect::<Vec<_>>();
+    if needed.is_empty() {
+        return Ok(());
+    }
+    let present = parse_ptx_sanitizers(ptx).ok_or(format!(
+        "PTX kernel declares no instrumentation, sign it with --sign-ptx <bin> --sanitize {}",
+        needed.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(",")
+    ))?;
+    let missing = needed
+        .iter()
+        .filter(|s| !present.contains(s))
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "PTX kernel is not instrumented for {}, rebuild it with ptxsema",
+            missing.join(",")
+        ))
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_ptx_sanitizers() {
+        let requested = parse_sanitizers("ibsan,reentrancy,bug").unwrap();
+        let ptx = format!("{}\n.version 8.0\n", sanitizers_header(&vec![Sanitizer::Bug, Sanitizer::Ibsan]));
+        assert!(check_ptx_sanitizers(&ptx, &requested).is_ok());
+        assert!(check_ptx_sanitizers(".version 8.0\n", &requested).is_err());
+        // host-side sanitizers need no instrumentation
+        assert!(check_ptx_sanitizers(".version 8.0\n", &vec![Sanitizer::Reentrancy]).is_ok());
+        assert!(parse_sanitizers("asan").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+/// Named execution environment presets ("scenarios") that seeds can carry
+///
+/// A scenario describes a mode the protocol can be in (e.g., bull-market prices,
+/// post-upgrade state, paused contract). It is applied on top of the VM state and
+/// block environment of the input right before execution, so the same seed can be
+/// explored under radically different protocol behaviors.
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::{EVMState, EVMStorage};
+use libafl::impl_serdeany;
+use libafl::prelude::HasMetadata;
+use revm_primitives::{BlockEnv, Env};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use tracing::info;
+
+/// A scenario preset loaded from the scenario config file
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Scenario {
+    /// Name of the scenario, used when attributing coverage / bugs
+    pub name: String,
+    /// Block timestamp to use in this scenario
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Block number to use in this scenario
+    #[serde(default)]
+    pub number: Option<u64>,
+    /// Storage slots to override, mapping of contract address to slot to value
+    #[serde(default)]
+    pub storage: HashMap<EVMAddress, HashMap<EVMU256, EVMU256>>,
+}
+
+impl Scenario {
+    /// Apply the scenario on the block environment and VM state
+    pub fn apply(&self, env: &mut Env, vm_state: &mut EVMState) {
+        self.apply_block(&mut env.block);
+        for (address, slots) in &self.storage {
+            match vm_state.get_mut(address) {
+                Some(storage) => storage.extend(slots.clone()),
+                None => vm_state.insert(*address, slots.clone().into_iter().collect()),
+            }
+        }
+    }
+
+    /// Apply the timestamp and number of the scenario on a block
+    pub fn apply_block(&self, block: &mut BlockEnv) {
+        if let Some(timestamp) = self.timestamp {
+            block.timestamp = EVMU256::from(timestamp);
+        }
+        if let Some(number) = self.number {
+            block.number = EVMU256::from(number);
+        }
+    }
+
+    /// Apply the storage overrides of the scenario on the storage of a contract
+    pub fn apply_storage(&self, address: &EVMAddress, storage: &mut EVMStorage) {
+        if let Some(slots) = self.storage.get(address) {
+            storage.extend(slots.iter().map(|(slot, value)| (*slot, *value)));
+        }
+    }
+}
+
+/// All scenarios defined in the config, indexed by [`EVMInput::scenario`]
+pub static mut SCENARIOS: Vec<Scenario> = Vec::new();
+
+/// Load scenarios from a JSON file containing an array of [`Scenario`]
+pub fn load_scenarios(path: &str) -> Vec<Scenario> {
+    let mut file = File::open(path).expect("failed to open scenario file");
+    let mut data = String::new();
+    file.read_to_string(&mut data)
+        .expect("failed to read scenario file");
+    serde_json::from_str(&data).expect("failed to parse scenario file")
+}
+
+/// Register the scenarios so that inputs can refer to them by index
+pub fn register_scenarios(scenarios: Vec<Scenario>) {
+    for scenario in &scenarios {
+        info!("Registered scenario: {}", scenario.name);
+    }
+    unsafe {
+        SCENARIOS = scenarios;
+    }
+}
+
+/// Get the scenario named `name`, registering an empty one if there is none
+pub fn scenario_mut(name: &str) -> &'static mut Scenario {
+    unsafe {
+        let idx = match SCENARIOS.iter().position(|scenario| scenario.name == name) {
+            Some(idx) => idx,
+            None => {
+                info!("Registered scenario: {}", name);
+                SCENARIOS.push(Scenario {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                SCENARIOS.len() - 1
+            }
+        };
+        &mut SCENARIOS[idx]
+    }
+}
+
+/// Number of registered scenarios
+pub fn scenario_count() -> usize {
+    unsafe { SCENARIOS.len() }
+}
+
+/// Get a scenario, `None` for inputs without scenario or referring to a scenario that is not
+/// registered (e.g., a corpus written with another scenario file)
+pub fn scenario(idx: Option<usize>) -> Option<&'static Scenario> {
+    unsafe { SCENARIOS.get(idx?) }
+}
+
+/// Get the name of a scenario, `base` is used for inputs without scenario
+pub fn scenario_name(idx: Option<usize>) -> String {
+    match scenario(idx) {
+        Some(scenario) => scenario.name.clone(),
+        None => "base".to_string(),
+    }
+}
+
+/// Per-scenario fuzzing statistics
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ScenarioStats {
+    /// Number of executions under this scenario
+    pub executions: usize,
+    /// Number of inputs added to the corpus under this scenario
+    pub corpus: usize,
+    /// Number of solutions found under this scenario
+    pub bugs: usize,
+}
+
+/// Metadata attached to the global fuzz state, attributing coverage and bugs
+/// to scenarios and keeping track of the scenario cycle
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ScenarioMetadata {
+    /// Next scenario to hand out, scenarios are cycled in a round-robin fashion
+    pub cursor: usize,
+    /// Scenario of the corpus entry being fuzzed, handed out when the scheduler picked it
+    pub scheduled: Option<usize>,
+    /// Statistics for each scenario, keyed by scenario name
+    pub stats: HashMap<String, ScenarioStats>,
+}
+
+impl_serdeany!(ScenarioMetadata);
+
+impl ScenarioMetadata {
+    /// Get the stats of a scenario mutably
+    pub fn stats_mut(&mut self, idx: Option<usize>) -> &mut ScenarioStats {
+        self.stats.entry(scenario_name(idx)).or_default()
+    }
+}
+
+/// Get the [`ScenarioMetadata`] from the state, inserting it if it does not exist yet
+pub fn scenario_metadata_mut<S>(state: &mut S) -> &mut ScenarioMetadata
+where
+    S: HasMetadata,
+{
+    if !state.metadata().contains::<ScenarioMetadata>() {
+        state.metadata_mut().insert(ScenarioMetadata::default());
+    }
+    state.metadata_mut().get_mut::<ScenarioMetadata>().unwrap()
+}
+
+/// Pick the next scenario for an input, cycling through base and all registered scenarios
+pub fn next_scenario<S>(state: &mut S) -> Option<usize>
+where
+    S: HasMetadata,
+{
+    let count = scenario_count();
+    if count == 0 {
+        return None;
+    }
+    let meta = scenario_metadata_mut(state);
+    let cursor = meta.cursor;
+    meta.cursor = (cursor + 1) % (count + 1);
+    if cursor == count {
+        None
+    } else {
+        Some(cursor)
+    }
+}
+
+/// Hand the next scenario out to the corpus entry the scheduler just picked, its mutants run
+/// under it (see [`scheduled_scenario`])
+pub fn schedule_scenario<S>(state: &mut S)
+where
+    S: HasMetadata,
+{
+    if scenario_count() > 0 {
+        let scenario = next_scenario(state);
+        scenario_metadata_mut(state).scheduled = scenario;
+    }
+}
+
+/// Scenario of the corpus entry being fuzzed
+pub fn scheduled_scenario<S>(state: &mut S) -> Option<usize>
+where
+    S: HasMetadata,
+{
+    scenario_metadata_mut(state).scheduled
+}
+
+/// Print a summary of the per-scenario statistics
+pub fn print_scenario_stats<S>(state: &mut S)
+where
+    S: HasMetadata,
+{
+    if scenario_count() == 0 {
+        return;
+    }
+    let meta = scenario_metadata_mut(state);
+    for (name, stats) in &meta.stats {
+        info!(
+            "Scenario {}: {} execs, {} corpus, {} bugs",
+            name, stats.executions, stats.corpus, stats.bugs
+        );
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_apply_scenario() {
+        let scenarios: Vec<Scenario> = serde_json::from_str(
+            r#"[{"name": "paused", "timestamp": 1000, "storage": {"0x0000000000000000000000000000000000000001": {"0x0": "0x1"}}}]"#,
+        )
+        .unwrap();
+        let mut env = Env::default();
+        let mut vm_state = EVMState::default();
+        scenarios[0].apply(&mut env, &mut vm_state);
+
+        assert_eq!(env.block.timestamp, EVMU256::from(1000));
+        let address = EVMAddress::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        assert_eq!(
+            vm_state.get(&address).unwrap().get(&EVMU256::ZERO),
+            Some(&EVMU256::from(1))
+        );
+
+        // the storage of the GPU seeds gets the same overrides
+        let mut storage = EVMStorage::new();
+        storage.insert(EVMU256::from(2), EVMU256::from(3));
+        scenarios[0].apply_storage(&address, &mut storage);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.get(&EVMU256::ZERO), Some(&EVMU256::from(1)));
+        scenarios[0].apply_storage(&EVMAddress::zero(), &mut storage);
+        assert_eq!(storage.len(), 2);
+        assert!(scenario(Some(scenario_count())).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+/// Seeds synthesized from the constants guarding each function
+///
+/// A guard such as `require(code == 0xdead)` is found by heimdall (see
+/// [`crate::evm::onchain::abi_decompiler::fetch_selector_constants_heimdall`]), and havoc rarely
+/// hits the constant by itself. Before fuzzing starts, each word-sized argument of the function is
+/// set to each of its guard constants, so that the initial corpus already passes the guard.
+use crate::evm::abi::{AArray, AUnknown, BoxedABI, A256};
+
+/// Seeds synthesized per function at most, the guards of a large function are still hinted
+/// by the [`crate::evm::mutation_utils::ConstantHintedMutator`]
+pub const MAX_SYNTHESIZED_SEEDS: usize = 32;
+
+/// Value of the argument equal to the constant (big endian without leading zeros), `None` if
+/// the constant does not fit the argument
+fn fit_constant(arg: &A256, constant: &[u8]) -> Option<Vec<u8>> {
+    let width = arg.data.len();
+    if arg.dont_mutate || constant.is_empty() || constant.len() > width {
+        return None;
+    }
+    // bytesN are compared left-aligned, only take the constants of their exact size
+    if arg.is_bytes && constant.len() != width {
+        return None;
+    }
+    let mut data = vec![0; width - constant.len()];
+    data.extend_from_slice(constant);
+    Some(data)
+}
+
+/// Word-sized argument, or the current concrete value of an argument of unknown type (of the
+/// functions decompiled by heimdall)
+fn word_arg(arg: &mut BoxedABI) -> Option<&mut A256> {
+    if arg.b.as_any().is::<AUnknown>() {
+        let unknown = arg.b.as_any().downcast_mut::<AUnknown>().unwrap();
+        return unknown.concrete.b.as_any().downcast_mut::<A256>();
+    }
+    arg.b.as_any().downcast_mut::<A256>()
+}
+
+/// Copies of the call with one argument set to one of the constants
+pub fn synthesize_seeds(abi: &BoxedABI, constants: &Vec<Vec<u8>>) -> Vec<BoxedABI> {
+    let mut template = abi.clone();
+    let args = match template.b.as_any().downcast_mut::<AArray>() {
+        Some(args) => args.data.len(),
+        None => return vec![],
+    };
+    let mut seeds = vec![];
+    for idx in 0..args {
+        for constant in constants {
+            let mut seed = template.clone();
+            let args = seed.b.as_any().downcast_mut::<AArray>().unwrap();
+            let arg = match word_arg(&mut args.data[idx]) {
+                Some(arg) => arg,
+                None => break,
+            };
+            let data = match fit_constant(arg, constant) {
+                Some(data) if data != arg.data => data,
+                _ => continue,
+            };
+            arg.data = data;
+            seeds.push(seed);
+            if seeds.len() >= MAX_SYNTHESIZED_SEEDS {
+                return seeds;
+            }
+        }
+    }
+    seeds
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::abi::get_abi_type_boxed;
+
+    #[test]
+    fn test_synthesize_seeds() {
+        let abi = get_abi_type_boxed(&"(uint256,uint8,bytes4,string)".to_string());
+        let constants = vec![vec![0xde, 0xad], vec![0x01], vec![0xca, 0xfe, 0xba, 0xbe]];
+        let seeds = synthesize_seeds(&abi, &constants);
+        // uint256 takes the three constants, uint8 only 0x01, bytes4 only 0xcafebabe
+        assert_eq!(seeds.len(), 5);
+        let first = seeds[0].get_bytes_vec();
+        assert_eq!(&first[30..32], &[0xde, 0xad]);
+        assert!(first[..30].iter().all(|b| *b == 0));
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert!(seeds.iter().any(|seed| seed.get_bytes_vec()[32..64] == one));
+
+        assert!(synthesize_seeds(&get_abi_type_boxed(&"()".to_string()), &constants).is_empty());
+    }
+}
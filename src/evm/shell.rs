@@ -0,0 +1,353 @@
+/// Interactive explorer of the corpus of a run (`cli shell <work_dir>`)
+///
+/// Lists the replayable corpus files of the run, prints their transactions with the calldata
+/// decoded by the ABIs of the targets (found from the arguments in `manifest.json`), replays one
+/// with debug logs by running the campaign again with `--replay-file` in `<work_dir>/shell`, edits
+/// a transaction (caller, value, block or an argument) and pushes the edited sequence to the
+/// campaign still running in the work directory through its control socket
+/// (see [`crate::evm::control`]).
+use crate::evm::abi::{format_address, function_args, function_sig_name, get_abi_type_boxed, register_function_name};
+use crate::evm::contract_utils::ContractLoader;
+use crate::evm::control::push_to_campaign;
+use crate::evm::onchain::abi_decompiler::DECOMPILED_ABI_FILE;
+use crate::evm::corpus_format::{load_corpus_file, write_corpus_file, CorpusFile, CorpusTxn, TxnKind};
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::view_invariants::parse_number;
+use crate::workdir::{ARTIFACTS_DIR, CORPUS_DIR, MANIFEST_FILE};
+use glob::glob;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+pub const SHELL_DIR: &str = "shell";
+
+const HELP: &str = "\
+ls                            list the corpus entries
+show <n>                      print the transactions of an entry
+set <n> <txn> <field> <value> set the caller, contract, value, warp, repeat or an argument (by name or index) of a transaction
+replay <n>                    replay an entry with debug logs
+push <n>                      push an entry to the running campaign
+quit";
+
+#[derive(Clone, Debug)]
+pub struct CorpusEntry {
+    pub name: String,
+    pub txns: Vec<CorpusTxn>,
+    pub edited: bool,
+}
+
+/// Replayable corpus files of the run, sorted by name
+pub fn load_entries(run: &str) -> Result<Vec<CorpusEntry>, String> {
+    let dir = Path::new(run).join(CORPUS_DIR);
+    let mut paths = fs::read_dir(&dir)
+        .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.to_string_lossy().ends_with("_replayable"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths
+        .iter()
+        .filter_map(|path| {
+            let txns = load_corpus_file(&path.to_string_lossy()).ok()?;
+            txns.iter().for_each(|txn| txn.register_function());
+            Some(CorpusEntry {
+                name: path.file_name()?.to_string_lossy().to_string(),
+                txns,
+                edited: false,
+            })
+        })
+        .collect())
+}
+
+/// Arguments of the campaign, from the manifest
+pub(crate) fn campaign_args(run: &str) -> Result<Vec<String>, String> {
+    let path = Path::new(run).join(MANIFEST_FILE);
+    let content = fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let manifest: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    Ok(manifest["args"]
+        .as_array()
+        .ok_or("manifest without args")?
+        .iter()
+        .filter_map(|arg| arg.as_str().map(|s| s.to_string()))
+        .collect())
+}
+
+/// Register the ABIs of the targets of a glob target and the decompiled functions named during
+/// the run, to decode the calldata
+fn load_abis(run: &str, args: &Vec<String>) {
+    let decompiled = Path::new(run).join(ARTIFACTS_DIR).join(DECOMPILED_ABI_FILE);
+    if let Ok(abi) = fs::read_to_string(&decompiled) {
+        for abi in ContractLoader::parse_abi_str(&abi) {
+            register_function_name(abi.function, abi.function_name);
+        }
+    }
+    let target = args
+        .iter()
+        .position(|arg| arg == "-t" || arg == "--target")
+        .and_then(|i| args.get(i + 1));
+    if let Some(target) = target {
+        for path in glob(target).into_iter().flatten().flatten() {
+            if path.extension().map_or(false, |ext| ext == "abi") {
+                if let Ok(abi) = fs::read_to_string(&path) {
+                    ContractLoader::parse_abi_str(&abi);
+                }
+            }
+        }
+    }
+}
+
+fn decode_calldata(data: &[u8]) -> String {
+    if data.len() < 4 {
+        return format!("fallback(0x{})", hex::encode(data));
+    }
+    let selector: [u8; 4] = data[..4].try_into().unwrap();
+    match function_args(&selector) {
+        Some(args) => {
+            let types = args.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>().join(",");
+            let mut abi = get_abi_type_boxed(&format!("({})", types));
+            abi.set_bytes(data.to_vec());
+            abi.set_func(selector);
+            abi.to_decoded_string()
+        }
+        None => format!("{}(0x{})", function_sig_name(&selector), hex::encode(&data[4..])),
+    }
+}
+
+fn call_name(txn: &CorpusTxn) -> String {
+    let data = hex::decode(&txn.data).unwrap_or_default();
+    match txn.kind {
+        TxnKind::Borrow => "borrow".to_string(),
+        TxnKind::Abi if data.len() < 4 => "fallback".to_string(),
+        TxnKind::Abi => function_sig_name(&data[..4].try_into().unwrap()),
+    }
+}
+
+pub fn describe_txn(txn: &CorpusTxn) -> String {
+    let call = match txn.kind {
+        TxnKind::Borrow => "borrow".to_string(),
+        TxnKind::Abi => decode_calldata(&hex::decode(&txn.data).unwrap_or_default()),
+    };
+    let mut line = format!("{} -> {}: {}", format_address(&txn.caller), format_address(&txn.contract), call);
+    if txn.value != EVMU256::ZERO {
+        line.push_str(&format!(" value={}", txn.value));
+    }
+    line.push_str(&format!(" block={}", txn.warp_to));
+    if txn.repeat > 1 {
+        line.push_str(&format!(" repeat={}", txn.repeat));
+    }
+    line
+}
+
+/// ABI word of a value of an elementary type
+pub(crate) fn encode_word(ty: &str, value: &str) -> Result<[u8; 32], String> {
+    if ty == "address" {
+        let address = EVMAddress::from_str(value).map_err(|_| format!("invalid address {}", value))?;
+        let mut word = [0; 32];
+        word[12..].copy_from_slice(&address.0);
+        return Ok(word);
+    }
+    if ty == "bool" {
+        return match value {
+            "true" => Ok(EVMU256::from(1).to_be_bytes()),
+            "false" => Ok([0; 32]),
+            _ => Err(format!("invalid bool {}", value)),
+        };
+    }
+    if ty.starts_with("bytes") {
+        let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|_| format!("invalid bytes {}", value))?;
+        if bytes.len() > 32 {
+            return Err(format!("{} is longer than 32 bytes", value));
+        }
+        let mut word = [0; 32];
+        word[..bytes.len()].copy_from_slice(&bytes);
+        return Ok(word);
+    }
+    match value.strip_prefix('-') {
+        Some(abs) if ty.starts_with("int") => Ok(EVMU256::ZERO.wrapping_sub(parse_number(abs)?).to_be_bytes()),
+        _ => Ok(parse_number(value)?.to_be_bytes()),
+    }
+}
+
+/// Whether an argument is encoded in a single word of the head
+pub(crate) fn is_elementary(ty: &str) -> bool {
+    !ty.contains('(') && !ty.contains('[') && ty != "string" && ty != "bytes"
+}
+
+/// Set a field or an argument of a transaction
+pub fn set_field(txn: &mut CorpusTxn, field: &str, value: &str) -> Result<(), String> {
+    match field {
+        "caller" => txn.caller = EVMAddress::from_str(value).map_err(|_| format!("invalid address {}", value))?,
+        "contract" => txn.contract = EVMAddress::from_str(value).map_err(|_| format!("invalid address {}", value))?,
+        "value" => txn.value = parse_number(value)?,
+        "warp" => txn.warp_to = value.parse().map_err(|_| format!("invalid block {}", value))?,
+        "repeat" => txn.repeat = value.parse().map_err(|_| format!("invalid repeat {}", value))?,
+        arg => {
+            let mut data = hex::decode(&txn.data).map_err(|_| "invalid calldata")?;
+            if txn.kind != TxnKind::Abi || data.len() < 4 {
+                return Err("the transaction has no arguments".to_string());
+            }
+            let selector: [u8; 4] = data[..4].try_into().unwrap();
+            let args = function_args(&selector).ok_or("unknown ABI of the function")?;
+            let idx = match arg.parse::<usize>() {
+                Ok(idx) => idx,
+                Err(_) => args
+                    .iter()
+                    .position(|(name, _)| name == arg)
+                    .ok_or(format!("no argument {}", arg))?,
+            };
+            let ty = &args.get(idx).ok_or(format!("no argument {}", idx))?.1;
+            // the head of a dynamic argument is an offset, but static tuples and arrays take more words
+            if !is_elementary(ty) || args[..idx].iter().any(|(_, ty)| !is_elementary(ty) && !ty.ends_with("[]") && ty != "string" && ty != "bytes") {
+                return Err(format!("cannot set argument {} of type {}", arg, ty));
+            }
+            let start = 4 + 32 * idx;
+            if data.len() < start + 32 {
+                return Err("calldata shorter than its arguments".to_string());
+            }
+            data[start..start + 32].copy_from_slice(&encode_word(ty, value)?);
+            txn.data = hex::encode(data);
+        }
+    }
+    Ok(())
+}
+
+fn entry<'a>(entries: &'a mut Vec<CorpusEntry>, idx: Option<&str>) -> Result<&'a mut CorpusEntry, String> {
+    let idx: usize = idx.ok_or("missing entry")?.parse().map_err(|_| "invalid entry")?;
+    let count = entries.len();
+    entries.get_mut(idx).ok_or(format!("no entry {} ({} entries)", idx, count))
+}
+
+/// Replay an entry by running the campaign again in `<run>/shell` with `--replay-file`
+fn replay(run: &str, args: &Vec<String>, entry: &CorpusEntry) -> Result<(), String> {
+    let dir = Path::new(run).join(SHELL_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let file = dir.join("replay.json").to_string_lossy().to_string();
+    write_corpus_file(&file, &CorpusFile::V1 { txns: entry.txns.clone() })?;
+    replay_files(args, &dir, &file, "debug")
+}
+
+/// Run the campaign of the arguments again in `dir`, replaying the corpus files matching `files`
+pub(crate) fn replay_files(args: &Vec<String>, dir: &Path, files: &str, log: &str) -> Result<(), String> {
+    let mut replay_args = vec![];
+    let mut skip = false;
+    for arg in args.iter().skip(1) {
+        if skip {
+            skip = false;
+            continue;
+        }
+        if ["--work-dir", "--replay-file", "--log"].contains(&arg.as_str()) {
+            skip = true;
+            continue;
+        }
+        replay_args.push(arg.clone());
+    }
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let status = Command::new(exe)
+        .args(replay_args)
+        .args(["--work-dir", &dir.to_string_lossy(), "--replay-file", files, "--log", log])
+        .status()
+        .map_err(|e| format!("failed to replay: {}", e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("replay exited with {}", status)),
+    }
+}
+
+fn command(run: &str, args: &Vec<String>, entries: &mut Vec<CorpusEntry>, line: &str) -> Result<bool, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        [] => {}
+        ["ls"] => {
+            for (i, entry) in entries.iter().enumerate() {
+                let calls = entry.txns.iter().map(call_name).collect::<Vec<_>>();
+                println!("{:>4} {}{} [{}]", i, entry.name, if entry.edited { "*" } else { "" }, calls.join(", "));
+            }
+        }
+        ["show", idx] => {
+            for (i, txn) in entry(entries, Some(idx))?.txns.iter().enumerate() {
+                println!("{:>3}: {}", i, describe_txn(txn));
+            }
+        }
+        ["set", idx, txn, field, value] => {
+            let entry = entry(entries, Some(idx))?;
+            let txn_idx: usize = txn.parse().map_err(|_| "invalid transaction")?;
+            let txn = entry.txns.get_mut(txn_idx).ok_or(format!("no transaction {}", txn_idx))?;
+            set_field(txn, field, value)?;
+            println!("{:>3}: {}", txn_idx, describe_txn(txn));
+            entry.edited = true;
+        }
+        ["replay", idx] => replay(run, args, entry(entries, Some(idx))?)?,
+        ["push", idx] => {
+            let entry = entry(entries, Some(idx))?;
+            println!("{}", push_to_campaign(run, &CorpusFile::V1 { txns: entry.txns.clone() })?);
+        }
+        ["help"] => println!("{}", HELP),
+        ["quit"] | ["exit"] => return Ok(false),
+        _ => return Err(format!("unknown command {}, see help", line.trim())),
+    }
+    Ok(true)
+}
+
+pub fn run_shell(run: &str) -> Result<(), String> {
+    let args = campaign_args(run)?;
+    load_abis(run, &args);
+    let mut entries = load_entries(run)?;
+    println!("{} corpus entries in {}, type help for the commands", entries.len(), run);
+    let stdin = io::stdin();
+    loop {
+        print!("mau> ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(());
+        }
+        match command(run, &args, &mut entries, &line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::abi::register_function_args;
+
+    #[test]
+    fn test_set_field() {
+        // transfer(address,uint256)
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        register_function_args(selector, vec![("to".to_string(), "address".to_string()), ("amount".to_string(), "uint256".to_string())]);
+        let mut txn = CorpusTxn {
+            kind: TxnKind::Abi,
+            caller: EVMAddress::zero(),
+            contract: EVMAddress::zero(),
+            data: format!("a9059cbb{}", "00".repeat(64)),
+            value: EVMU256::ZERO,
+            liquidation_percent: 0,
+            warp_to: 0,
+            repeat: 1,
+            reentrancy_limit: 0,
+            step: false,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
+            function: None,
+            meta: None,
+        };
+        set_field(&mut txn, "amount", "1e18").unwrap();
+        set_field(&mut txn, "0", "0x00000000000000000000000000000000000000aa").unwrap();
+        set_field(&mut txn, "value", "5").unwrap();
+        let data = hex::decode(&txn.data).unwrap();
+        assert_eq!(data[35], 0xaa);
+        assert_eq!(EVMU256::try_from_be_slice(&data[36..68]).unwrap(), EVMU256::from(10).pow(EVMU256::from(18)));
+        assert_eq!(txn.value, EVMU256::from(5));
+        assert!(set_field(&mut txn, "spender", "1").is_err());
+        assert_eq!(encode_word("int256", "-1").unwrap(), [0xff; 32]);
+    }
+}
@@ -0,0 +1,145 @@
+/// Deduplication of solutions
+///
+/// A solution is a duplicate if it has the same call sequence (syntactic) or the same effect on
+/// the state (semantic) as an earlier one, e.g., two sequences draining the same funds. The
+/// semantic fingerprint is the set of storage slots changed since the initial state, with the
+/// direction of the change. Slots absent from the initial state (e.g., the balance of the
+/// attacker in a mapping) are only identified by their contract, so that sequences performed by
+/// different callers still collapse.
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use libafl::impl_serdeany;
+use libafl::prelude::HasMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// Fingerprints of the solutions found so far, attached to the fuzz state
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SolutionFingerprints {
+    /// State of the VM after the initialization, the base of the state diffs
+    pub initial_state: EVMState,
+    pub syntactic: HashSet<u64>,
+    pub semantic: HashSet<u64>,
+}
+
+impl_serdeany!(SolutionFingerprints);
+
+/// Direction of the change of a slot
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    Increase,
+    Decrease,
+}
+
+fn fingerprints_mut<S>(state: &mut S) -> &mut SolutionFingerprints
+where
+    S: HasMetadata,
+{
+    if !state.metadata().contains::<SolutionFingerprints>() {
+        state.metadata_mut().insert(SolutionFingerprints::default());
+    }
+    state.metadata_mut().get_mut::<SolutionFingerprints>().unwrap()
+}
+
+/// Set the state the semantic fingerprints are computed against
+pub fn set_initial_state<S>(state: &mut S, initial_state: EVMState)
+where
+    S: HasMetadata,
+{
+    fingerprints_mut(state).initial_state = initial_state;
+}
+
+/// Changed slots of the post state, `None` for slots absent from the initial state
+pub fn state_diff(initial: &EVMState, post: &EVMState) -> Vec<(EVMAddress, Option<EVMU256>, Change)> {
+    let mut diff = vec![];
+    for (address, storage) in &post.state {
+        let initial_storage = initial.state.get(address);
+        for (slot, value) in storage {
+            let old = initial_storage.and_then(|s| s.get(slot));
+            let old_value = old.cloned().unwrap_or(EVMU256::ZERO);
+            if *value == old_value {
+                continue;
+            }
+            let change = if *value > old_value {
+                Change::Increase
+            } else {
+                Change::Decrease
+            };
+            diff.push((*address, old.map(|_| *slot), change));
+        }
+    }
+    diff.sort();
+    diff.dedup();
+    diff
+}
+
+pub fn semantic_fingerprint(initial: &EVMState, post: &EVMState) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state_diff(initial, post).hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn syntactic_fingerprint(trace: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    trace.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Check whether the solution duplicates an earlier one and record its fingerprints otherwise.
+/// Returns the kind of duplicate.
+pub fn is_duplicate_solution<S>(state: &mut S, trace: &str, post: &EVMState) -> Option<&'static str>
+where
+    S: HasMetadata,
+{
+    let fingerprints = fingerprints_mut(state);
+    let syntactic = syntactic_fingerprint(trace);
+    if fingerprints.syntactic.contains(&syntactic) {
+        return Some("syntactic");
+    }
+    let semantic = semantic_fingerprint(&fingerprints.initial_state, post);
+    if fingerprints.semantic.contains(&semantic) {
+        return Some("semantic");
+    }
+    fingerprints.syntactic.insert(syntactic);
+    fingerprints.semantic.insert(semantic);
+    None
+}
+
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn state_with(address: EVMAddress, slots: Vec<(u64, u64)>) -> EVMState {
+        let mut state = EVMState::default();
+        state.insert(
+            address,
+            slots
+                .into_iter()
+                .map(|(k, v)| (EVMU256::from(k), EVMU256::from(v)))
+                .collect::<HashMap<_, _>>(),
+        );
+        state
+    }
+
+    #[test]
+    fn test_semantic_fingerprint() {
+        let token = EVMAddress::zero();
+        let initial = state_with(token, vec![(1, 100)]);
+        // the pool is drained to two different attackers
+        let drained_a = state_with(token, vec![(1, 0), (2, 100)]);
+        let drained_b = state_with(token, vec![(1, 0), (3, 100)]);
+        // the pool receives funds instead
+        let deposited = state_with(token, vec![(1, 200), (2, 100)]);
+
+        assert_eq!(
+            semantic_fingerprint(&initial, &drained_a),
+            semantic_fingerprint(&initial, &drained_b)
+        );
+        assert_ne!(
+            semantic_fingerprint(&initial, &drained_a),
+            semantic_fingerprint(&initial, &deposited)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+/// Structured diff of the storage of two infant states
+///
+/// With `--dump-corpus`, the storage of each infant state added to the corpus is saved to
+/// `<work_dir>/states/<idx>.json` along with the index of the state it was reached from.
+/// `cli state-diff <parent_idx> <child_idx>` then prints the slots changed between the two
+/// states, which shows what a multi-transaction sequence achieved. Slot names are inferred:
+/// - EIP-1967 proxy slots (implementation, admin, beacon)
+/// - `slot[N]` for the slots of the static variables
+/// - `mapping@N[key]` for `keccak256(key . N)` where the key is an address seen in the states
+///   or a small integer
+/// - `array@N[i]` for `keccak256(N) + i`
+///
+/// With `--storage-layout`, the variable names of the contracts take precedence, see
+/// [`crate::evm::storage_layout`].
+use crate::corpus_journal::persist_file;
+use crate::evm::abi::format_address;
+use crate::evm::storage_layout::{load_saved_storage_layouts, StorageLayout};
+use crate::evm::token_minting::{address_word, keccak};
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMStorage;
+use crate::workdir::{states_dir, STATES_DIR};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+pub type Storage = HashMap<EVMAddress, EVMStorage>;
+
+/// Storage indices of the static variables, candidates for the base slot of mappings / arrays
+pub const MAX_BASE_SLOT: u64 = 64;
+
+/// Highest array index / integer mapping key named
+pub const MAX_INDEX: u64 = 256;
+
+const EIP1967_SLOTS: [(&str, &str); 3] = [
+    ("eip1967.implementation", "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc"),
+    ("eip1967.admin", "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103"),
+    ("eip1967.beacon", "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50"),
+];
+
+/// Storage of an infant state saved to the work directory
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StateSnapshot {
+    /// Index of the infant state the state was reached from
+    pub parent: Option<usize>,
+    /// `(contract, [(slot, value)])`, sorted
+    pub storage: Vec<(EVMAddress, Vec<(EVMU256, EVMU256)>)>,
+}
+
+impl StateSnapshot {
+    pub fn new(parent: Option<usize>, storage: &Storage) -> Self {
+        let mut contracts = storage
+            .iter()
+            .map(|(addr, slots)| {
+                let mut slots = slots.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+                slots.sort();
+                (*addr, slots)
+            })
+            .collect::<Vec<_>>();
+        contracts.sort_by(|a, b| a.0.cmp(&b.0));
+        Self {
+            parent,
+            storage: contracts,
+        }
+    }
+
+    pub fn to_storage(&self) -> Storage {
+        self.storage
+            .iter()
+            .map(|(addr, slots)| (*addr, slots.iter().cloned().collect()))
+            .collect()
+    }
+}
+
+/// Save the storage of the infant state `idx` to the work directory
+pub fn save_snapshot(idx: usize, parent: Option<usize>, storage: &Storage) {
+    let dir = states_dir();
+    let snapshot = StateSnapshot::new(parent, storage);
+    let path = Path::new(&dir).join(format!("{}.json", idx)).to_string_lossy().to_string();
+    if let Err(e) = persist_file(&path, serde_json::to_string(&snapshot).unwrap().into_bytes()) {
+        warn!("failed to save the state snapshot {}: {}", idx, e);
+    }
+}
+
+/// Load the storage of the infant state `idx` saved in the work directory `work_dir`
+pub fn load_snapshot(work_dir: &str, idx: usize) -> Result<StateSnapshot, String> {
+    let path = Path::new(work_dir).join(STATES_DIR).join(format!("{}.json", idx));
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {} (was the campaign run with --dump-corpus?)", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("malformed state snapshot {}: {}", path.display(), e))
+}
+
+/// A slot changed between two states, `None` when the slot is unset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlotChange {
+    pub contract: EVMAddress,
+    pub slot: EVMU256,
+    pub name: String,
+    pub before: Option<EVMU256>,
+    pub after: Option<EVMU256>,
+}
+
+/// Names of the hashed slots of mappings and dynamic arrays
+pub struct SlotNamer {
+    names: HashMap<EVMU256, String>,
+}
+
+impl SlotNamer {
+    /// Build the names of the entries keyed by the given addresses and small integers
+    pub fn new(keys: &HashSet<EVMAddress>) -> Self {
+        let mut names = HashMap::new();
+        for (name, hex_slot) in EIP1967_SLOTS {
+            names.insert(EVMU256::from_str_radix(hex_slot, 16).unwrap(), name.to_string());
+        }
+        for base in 0..MAX_BASE_SLOT {
+            let base_word: [u8; 32] = EVMU256::from(base).to_be_bytes();
+            let array_start = keccak(&base_word);
+            for i in 0..MAX_INDEX {
+                names.insert(array_start.wrapping_add(EVMU256::from(i)), format!("array@{}[{}]", base, i));
+                let key: [u8; 32] = EVMU256::from(i).to_be_bytes();
+                names
+                    .entry(keccak(&[key, base_word].concat()))
+                    .or_insert(format!("mapping@{}[{}]", base, i));
+            }
+            for key in keys {
+                names.insert(
+                    keccak(&[address_word(key), base_word].concat()),
+                    format!("mapping@{}[{}]", base, format_address(key)),
+                );
+            }
+        }
+        Self { names }
+    }
+
+    pub fn name(&self, slot: &EVMU256) -> String {
+        if let Some(name) = self.names.get(slot) {
+            return name.clone();
+        }
+        if *slot < EVMU256::from(1u64 << 16) {
+            return format!("slot[{}]", slot);
+        }
+        format!("{:#x}", slot)
+    }
+}
+
+/// Addresses appearing in the states: the contracts and the values that look like addresses
+fn known_addresses(states: &[&Storage]) -> HashSet<EVMAddress> {
+    let mut addresses = HashSet::new();
+    for storage in states {
+        for (contract, slots) in storage.iter() {
+            addresses.insert(*contract);
+            for value in slots.values() {
+                let bytes: [u8; 32] = value.to_be_bytes();
+                if bytes[..12].iter().all(|b| *b == 0) && bytes[12..16].iter().any(|b| *b != 0) {
+                    addresses.insert(EVMAddress::from_slice(&bytes[12..]));
+                }
+            }
+        }
+    }
+    addresses
+}
+
+/// Slots changed between the parent and the child storage, sorted by contract and slot
+pub fn diff_states(
+    parent: &Storage,
+    child: &Storage,
+    layouts: &HashMap<EVMAddress, StorageLayout>,
+) -> Vec<SlotChange> {
+    let addresses = known_addresses(&[parent, child]);
+    let namer = SlotNamer::new(&addresses);
+    let layout_names = layouts
+        .iter()
+        .map(|(contract, layout)| (*contract, layout.slot_names(&addresses)))
+        .collect::<HashMap<_, _>>();
+    let empty = EVMStorage::new();
+    let contracts = parent.keys().chain(child.keys()).cloned().collect::<HashSet<_>>();
+    let mut changes = vec![];
+    for contract in contracts {
+        let before = parent.get(&contract).unwrap_or(&empty);
+        let after = child.get(&contract).unwrap_or(&empty);
+        let slots = before.keys().chain(after.keys()).cloned().collect::<HashSet<_>>();
+        for slot in slots {
+            let (b, a) = (before.get(&slot).cloned(), after.get(&slot).cloned());
+            if b != a {
+                changes.push(SlotChange {
+                    contract,
+                    slot,
+                    name: match layout_names.get(&contract).and_then(|names| names.get(&slot)) {
+                        Some(name) => name.clone(),
+                        None => namer.name(&slot),
+                    },
+                    before: b,
+                    after: a,
+                });
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.contract.cmp(&b.contract).then(a.slot.cmp(&b.slot)));
+    changes
+}
+
+fn format_value(value: &Option<EVMU256>) -> String {
+    match value {
+        Some(v) => format!("{:#x}", v),
+        None => "unset".to_string(),
+    }
+}
+
+/// Report of the slots changed between the infant states `parent` and `child` of a campaign
+pub fn state_diff_report(work_dir: &str, parent: usize, child: usize) -> Result<String, String> {
+    let parent_state = load_snapshot(work_dir, parent)?;
+    let child_state = load_snapshot(work_dir, child)?;
+    let changes = diff_states(
+        &parent_state.to_storage(),
+        &child_state.to_storage(),
+        &load_saved_storage_layouts(work_dir),
+    );
+    let mut report = format!("state #{} -> #{}: {} slots changed\n", parent, child, changes.len());
+    let mut current = None;
+    for change in changes {
+        if current != Some(change.contract) {
+            report.push_str(&format!("{}\n", format_address(&change.contract)));
+            current = Some(change.contract);
+        }
+        report.push_str(&format!(
+            "  {}: {} -> {}\n",
+            change.name,
+            format_value(&change.before),
+            format_value(&change.after)
+        ));
+    }
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_diff_states() {
+        let contract = EVMAddress::from_str("0x0000000000000000000000000000000000001234").unwrap();
+        let holder = EVMAddress::from_str("0x7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap();
+        let balance_slot = keccak(&[address_word(&holder), EVMU256::from(3).to_be_bytes::<32>()].concat());
+
+        let parent = Storage::from([(contract, EVMStorage::from(vec![(EVMU256::from(0), EVMU256::from(1))]))]);
+        let child = Storage::from([(
+            contract,
+            EVMStorage::from(vec![
+                (EVMU256::from(0), EVMU256::from(2)),
+                (EVMU256::from(1), EVMU256::from_be_slice(&address_word(&holder))),
+                (balance_slot, EVMU256::from(100)),
+            ]),
+        )]);
+        let changes = diff_states(&parent, &child, &HashMap::new());
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].name, "slot[0]");
+        assert_eq!(changes[0].before, Some(EVMU256::from(1)));
+        assert!(changes.iter().any(|c| c.name == format!("mapping@3[{}]", format_address(&holder))));
+        assert!(diff_states(&child, &child, &HashMap::new()).is_empty());
+    }
+}
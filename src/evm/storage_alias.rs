@@ -0,0 +1,176 @@
+/// Storage shared between the contracts of a composed target through `DELEGATECALL`
+///
+/// A proxy runs the code of its implementation on its own storage, and so do a router and its
+/// modules, or a contract and its libraries: several code addresses then share the storage of a
+/// single contract. The fuzzer maps the code addresses to the storages they run on:
+/// - statically, after the deployment: EIP-1167 minimal proxies (the implementation is in their
+///   code) and EIP-1967 proxies (the implementation is in their storage)
+/// - at runtime, the host records the code address and the storage of every `DELEGATECALL` /
+///   `CALLCODE`, warning about the pairs the static pass missed
+///
+/// The device snapshot of an input is the storage of the contract it calls, on which the kernel
+/// runs its code: an input goes to the GPU only if it calls the kernel's contract or a contract
+/// sharing its storage with the kernel's code (a proxy of it), the others running on revm rather
+/// than the kernel's code on an unrelated storage. A target that only runs on the storage of
+/// another target (an implementation fuzzed along with its proxy) is reported, since snapshotting
+/// both would snapshot the storage the calls of the implementation never use.
+use crate::evm::hex_utils::parse_address;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+pub const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+const MINIMAL_PROXY_PREFIX: &str = "363d3d373d3d3d363d73";
+const MINIMAL_PROXY_SUFFIX: &str = "5af43d82803e903d91602b57fd5bf3";
+
+/// Code address -> storages it runs on, besides its own
+static mut STORAGE_ALIASES: Option<HashMap<EVMAddress, HashSet<EVMAddress>>> = None;
+
+/// Implementation of an EIP-1167 minimal proxy
+pub fn minimal_proxy_target(code: &[u8]) -> Option<EVMAddress> {
+    let code = hex::encode(code);
+    let target = code.strip_prefix(MINIMAL_PROXY_PREFIX)?.strip_suffix(MINIMAL_PROXY_SUFFIX)?;
+    match target.len() {
+        40 => parse_address(target).ok(),
+        _ => None,
+    }
+}
+
+/// Implementation of an EIP-1967 proxy, from its storage
+pub fn eip1967_implementation(vm_state: &EVMState, proxy: &EVMAddress) -> Option<EVMAddress> {
+    let slot = EVMU256::from_str(EIP1967_IMPLEMENTATION_SLOT).unwrap();
+    let value: [u8; 32] = vm_state.get(proxy)?.get(&slot)?.to_be_bytes();
+    match value {
+        v if v == [0; 32] => None,
+        v => Some(EVMAddress::from_slice(&v[12..])),
+    }
+}
+
+/// Record that the code at `code` runs on the storage of `storage`, returns whether it is new
+pub fn record_alias(storage: EVMAddress, code: EVMAddress) -> bool {
+    if storage == code {
+        return false;
+    }
+    unsafe { STORAGE_ALIASES.get_or_insert_with(HashMap::new) }
+        .entry(code)
+        .or_default()
+        .insert(storage)
+}
+
+/// Called by the host on a `DELEGATECALL` / `CALLCODE`
+pub fn on_delegate_call(storage: EVMAddress, code: EVMAddress) {
+    if record_alias(storage, code) {
+        warn!("[storage-alias] {:?} runs on the storage of {:?} (found at runtime)", code, storage);
+    }
+}
+
+/// Whether the code at `code` runs on the storage of `storage`
+pub fn shares_storage(storage: &EVMAddress, code: &EVMAddress) -> bool {
+    storage == code
+        || unsafe { STORAGE_ALIASES.as_ref() }
+            .and_then(|aliases| aliases.get(code))
+            .map_or(false, |storages| storages.contains(storage))
+}
+
+/// Whether the device snapshot of an input calling `contract` is the storage the kernel's code
+/// runs on
+pub fn kernel_runs_on(kernel: &EVMAddress, contract: &EVMAddress) -> bool {
+    shares_storage(contract, kernel)
+}
+
+/// Map the proxies among the deployed contracts to their implementations
+pub fn detect_aliases(
+    vm_state: &EVMState,
+    codes: &HashMap<EVMAddress, Vec<u8>>,
+    deployed_contracts: &Vec<(String, EVMAddress)>,
+) {
+    for (name, address) in deployed_contracts {
+        let implementation = codes
+            .get(address)
+            .and_then(|code| minimal_proxy_target(code))
+            .or_else(|| eip1967_implementation(vm_state, address));
+        if let Some(implementation) = implementation {
+            if record_alias(*address, implementation) {
+                info!("[storage-alias] {} ({:?}) runs {:?} on its storage", name, address, implementation);
+            }
+        }
+    }
+}
+
+/// Targets running on the storage of another target, with the names of both
+pub fn aliased_targets(deployed_contracts: &Vec<(String, EVMAddress)>) -> Vec<(String, String)> {
+    let names = deployed_contracts.iter().map(|(name, address)| (*address, name)).collect::<HashMap<_, _>>();
+    let mut aliased = vec![];
+    for (name, address) in deployed_contracts {
+        for (storage, storage_name) in &names {
+            if storage != address && shares_storage(storage, address) {
+                aliased.push((name.clone(), (*storage_name).clone()));
+            }
+        }
+    }
+    aliased.sort();
+    aliased
+}
+
+/// Warn about the targets whose storage would be snapshotted although they run on another's
+pub fn warn_double_snapshots(deployed_contracts: &Vec<(String, EVMAddress)>) {
+    for (code, storage) in aliased_targets(deployed_contracts) {
+        warn!(
+            "[storage-alias] {} runs on the storage of {}: fuzzing both snapshots the storage of {} \
+             separately although its calls through {} use the storage of {}, consider fuzzing {} only",
+            code, storage, code, storage, storage, storage
+        );
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_aliases() {
+        let implementation = EVMAddress::from_slice(&[0xaa; 20]);
+        let clone = EVMAddress::from_slice(&[0xbb; 20]);
+        let proxy = EVMAddress::from_slice(&[0xcc; 20]);
+        let code = hex::decode(format!(
+            "{}{}{}",
+            MINIMAL_PROXY_PREFIX,
+            "aa".repeat(20),
+            MINIMAL_PROXY_SUFFIX
+        ))
+        .unwrap();
+        assert_eq!(minimal_proxy_target(&code), Some(implementation));
+        assert_eq!(minimal_proxy_target(&code[1..]), None);
+
+        let mut vm_state = EVMState::default();
+        let mut storage = crate::evm::vm::EVMStorage::new();
+        let mut value = [0u8; 32];
+        value[12..].copy_from_slice(&[0xaa; 20]);
+        storage.insert(EVMU256::from_str(EIP1967_IMPLEMENTATION_SLOT).unwrap(), EVMU256::from_be_slice(&value));
+        vm_state.insert(proxy, storage);
+        assert_eq!(eip1967_implementation(&vm_state, &proxy), Some(implementation));
+
+        let contracts = vec![
+            ("Impl".to_string(), implementation),
+            ("Clone".to_string(), clone),
+            ("Proxy".to_string(), proxy),
+        ];
+        detect_aliases(&vm_state, &HashMap::from([(clone, code)]), &contracts);
+        assert!(kernel_runs_on(&implementation, &clone));
+        assert!(kernel_runs_on(&implementation, &proxy));
+        assert!(!kernel_runs_on(&clone, &proxy));
+        assert_eq!(
+            aliased_targets(&contracts),
+            vec![
+                ("Impl".to_string(), "Clone".to_string()),
+                ("Impl".to_string(), "Proxy".to_string())
+            ]
+        );
+        // already known, not reported again at runtime
+        assert!(!record_alias(proxy, implementation));
+    }
+}
@@ -0,0 +1,341 @@
+/// Storage layouts of the targets, from solc's `storageLayout` output
+///
+/// With `--storage-layout`, slots are named after the variables of the contracts instead of
+/// being printed as raw hex, in the state diffs (see [`crate::evm::state_diff`]) and in the
+/// oracle reports. Packed variables are also unpacked when storage values are used as mutation
+/// hints, and [`resolve_slot`] computes the slot of an expression such as `balances[msg.sender]`.
+///
+/// Both the standard JSON output (`contracts.<file>.<name>.storageLayout`) and the combined JSON
+/// output (`solc --combined-json storage-layout`, `contracts.<file>:<name>.storage-layout`) are
+/// accepted.
+use crate::evm::abi::format_address;
+use crate::evm::address_pool::CONTRACT_ADDRESSES;
+use crate::evm::token_minting::{address_word, keccak};
+use crate::evm::types::{EVMAddress, EVMU256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use crate::workdir::{states_dir, STATES_DIR};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+pub const LAYOUTS_FILE: &str = "layouts.json";
+
+/// Highest index of the dynamic array elements named
+pub const MAX_NAMED_INDEX: u64 = 64;
+
+/// A state variable, or a member of a struct
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StorageVariable {
+    pub label: String,
+    pub slot: EVMU256,
+    /// Offset in bytes of a variable packed in the slot
+    pub offset: usize,
+    pub ty: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageType {
+    /// `inplace`, `mapping`, `dynamic_array` or `bytes`
+    pub encoding: String,
+    pub label: String,
+    pub number_of_bytes: usize,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub base: Option<String>,
+    pub members: Vec<StorageVariable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageLayout {
+    pub variables: Vec<StorageVariable>,
+    pub types: HashMap<String, StorageType>,
+}
+
+fn parse_variable(v: &Value) -> Result<StorageVariable, String> {
+    let field = |name: &str| v[name].as_str().ok_or(format!("storage variable without {}", name));
+    Ok(StorageVariable {
+        label: field("label")?.to_string(),
+        slot: EVMU256::from_str(field("slot")?).map_err(|e| format!("invalid slot: {}", e))?,
+        offset: v["offset"].as_u64().unwrap_or(0) as usize,
+        ty: field("type")?.to_string(),
+    })
+}
+
+/// Parse a `{"storage": [...], "types": {...}}` layout
+pub fn parse_storage_layout(json: &Value) -> Result<StorageLayout, String> {
+    let variables = json["storage"]
+        .as_array()
+        .ok_or("storage layout without storage")?
+        .iter()
+        .map(parse_variable)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut types = HashMap::new();
+    if let Some(entries) = json["types"].as_object() {
+        for (name, ty) in entries {
+            let members = match ty["members"].as_array() {
+                Some(members) => members.iter().map(parse_variable).collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+            let string = |field: &str| ty[field].as_str().map(|s| s.to_string());
+            types.insert(
+                name.clone(),
+                StorageType {
+                    encoding: string("encoding").unwrap_or_default(),
+                    label: string("label").unwrap_or_default(),
+                    number_of_bytes: string("numberOfBytes").and_then(|n| n.parse().ok()).unwrap_or(32),
+                    key: string("key"),
+                    value: string("value"),
+                    base: string("base"),
+                    members,
+                },
+            );
+        }
+    }
+    Ok(StorageLayout { variables, types })
+}
+
+/// Load the layouts of a solc output file, by contract name
+pub fn load_storage_layouts(path: &str) -> HashMap<String, StorageLayout> {
+    let mut data = String::new();
+    File::open(path)
+        .expect("failed to open storage layout file")
+        .read_to_string(&mut data)
+        .expect("failed to read storage layout file");
+    let json: Value = serde_json::from_str(&data).expect("failed to parse storage layout file");
+    let contracts = json["contracts"]
+        .as_object()
+        .expect("no contracts in the storage layout file");
+    let parse = |layout: &Value| parse_storage_layout(layout).expect("invalid storage layout");
+    let mut layouts = HashMap::new();
+    for (key, value) in contracts {
+        // combined JSON: "<file>:<name>" => {"storage-layout": ...}
+        if let Some(layout) = value.get("storage-layout") {
+            let name = key.rsplit(':').next().unwrap_or(key);
+            layouts.insert(name.to_string(), parse(layout));
+            continue;
+        }
+        // standard JSON: "<file>" => {"<name>": {"storageLayout": ...}}
+        if let Some(file_contracts) = value.as_object() {
+            for (name, contract) in file_contracts {
+                if let Some(layout) = contract.get("storageLayout") {
+                    layouts.insert(name.clone(), parse(layout));
+                }
+            }
+        }
+    }
+    layouts
+}
+
+static mut STORAGE_LAYOUTS: Option<HashMap<EVMAddress, StorageLayout>> = None;
+
+pub fn register_storage_layout(address: EVMAddress, layout: StorageLayout) {
+    unsafe {
+        STORAGE_LAYOUTS.get_or_insert_with(HashMap::new).insert(address, layout);
+    }
+}
+
+pub fn storage_layout(address: &EVMAddress) -> Option<&'static StorageLayout> {
+    unsafe { STORAGE_LAYOUTS.as_ref().and_then(|l| l.get(address)) }
+}
+
+pub fn storage_layouts() -> HashMap<EVMAddress, StorageLayout> {
+    unsafe { STORAGE_LAYOUTS.clone().unwrap_or_default() }
+}
+
+/// Save the registered layouts next to the state snapshots, for `cli state-diff`
+pub fn save_storage_layouts() {
+    let dir = states_dir();
+    if !Path::new(&dir).exists() {
+        fs::create_dir_all(&dir).expect("failed to create states directory");
+    }
+    let layouts = storage_layouts().into_iter().collect::<Vec<_>>();
+    let mut file = File::create(Path::new(&dir).join(LAYOUTS_FILE)).expect("failed to create storage layouts file");
+    file.write_all(serde_json::to_string(&layouts).unwrap().as_bytes())
+        .expect("failed to write storage layouts");
+}
+
+/// Load the layouts saved in the work directory `work_dir`, empty if the campaign had none
+pub fn load_saved_storage_layouts(work_dir: &str) -> HashMap<EVMAddress, StorageLayout> {
+    let path = Path::new(work_dir).join(STATES_DIR).join(LAYOUTS_FILE);
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Vec<(EVMAddress, StorageLayout)>>(&content).ok())
+        .map(|layouts| layouts.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Variable name of a slot of a registered contract, with the deployed contracts as mapping keys
+pub fn describe_slot(contract: &EVMAddress, slot: &EVMU256) -> Option<String> {
+    let mut keys = unsafe { CONTRACT_ADDRESSES.iter().cloned().collect::<HashSet<_>>() };
+    keys.insert(*contract);
+    storage_layout(contract)?.slot_names(&keys).remove(slot)
+}
+
+fn mapping_slot(key: [u8; 32], slot: EVMU256) -> EVMU256 {
+    keccak(&[key, slot.to_be_bytes::<32>()].concat())
+}
+
+impl StorageLayout {
+    fn number_of_slots(&self, ty: &str) -> usize {
+        let bytes = self.types.get(ty).map_or(32, |t| t.number_of_bytes);
+        ((bytes + 31) / 32).max(1)
+    }
+
+    /// Name the slots of a variable, recursing into structs, mappings and arrays
+    fn name_variable(
+        &self,
+        label: String,
+        slot: EVMU256,
+        ty: &str,
+        keys: &Vec<(String, [u8; 32])>,
+        depth: usize,
+        names: &mut HashMap<EVMU256, String>,
+    ) {
+        let ty_info = self.types.get(ty).cloned().unwrap_or_default();
+        match ty_info.encoding.as_str() {
+            "mapping" if depth < 2 => {
+                let value_ty = ty_info.value.clone().unwrap_or_default();
+                for (key_name, key) in keys {
+                    let entry = mapping_slot(*key, slot);
+                    self.name_variable(format!("{}[{}]", label, key_name), entry, &value_ty, keys, depth + 1, names);
+                }
+            }
+            "dynamic_array" => {
+                names.entry(slot).or_insert(format!("{}.length", label));
+                let base = ty_info.base.clone().unwrap_or_default();
+                let element_slots = self.number_of_slots(&base) as u64;
+                let start = keccak(&slot.to_be_bytes::<32>());
+                for i in 0..MAX_NAMED_INDEX {
+                    let element = start.wrapping_add(EVMU256::from(i * element_slots));
+                    self.name_variable(format!("{}[{}]", label, i), element, &base, keys, depth + 1, names);
+                }
+            }
+            "inplace" if !ty_info.members.is_empty() => {
+                for member in &ty_info.members {
+                    self.name_variable(
+                        format!("{}.{}", label, member.label),
+                        slot.wrapping_add(member.slot),
+                        &member.ty,
+                        keys,
+                        depth,
+                        names,
+                    );
+                }
+            }
+            "inplace" if ty_info.base.is_some() => {
+                // static array
+                let base = ty_info.base.clone().unwrap();
+                let element_slots = self.number_of_slots(&base);
+                for i in 0..self.number_of_slots(ty) / element_slots {
+                    let element = slot.wrapping_add(EVMU256::from(i * element_slots));
+                    self.name_variable(format!("{}[{}]", label, i), element, &base, keys, depth, names);
+                }
+            }
+            _ => {
+                names
+                    .entry(slot)
+                    .and_modify(|name| *name = format!("{}|{}", name, label))
+                    .or_insert(label);
+            }
+        }
+    }
+
+    /// Names of the slots of the variables, with the given addresses and small integers as
+    /// mapping keys
+    pub fn slot_names(&self, addresses: &HashSet<EVMAddress>) -> HashMap<EVMU256, String> {
+        let mut keys = addresses
+            .iter()
+            .map(|a| (format_address(a), address_word(a)))
+            .collect::<Vec<_>>();
+        keys.extend((0..8u64).map(|i| (i.to_string(), EVMU256::from(i).to_be_bytes::<32>())));
+        let mut names = HashMap::new();
+        for variable in &self.variables {
+            self.name_variable(variable.label.clone(), variable.slot, &variable.ty, &keys, 0, &mut names);
+        }
+        names
+    }
+
+    /// Values of the variables packed in a slot, each as a separate value
+    pub fn unpack(&self, slot: &EVMU256, value: EVMU256) -> Vec<EVMU256> {
+        let packed = self
+            .variables
+            .iter()
+            .filter(|v| v.slot == *slot)
+            .map(|v| (v.offset, self.types.get(&v.ty).map_or(32, |t| t.number_of_bytes)))
+            .collect::<Vec<_>>();
+        if packed.len() < 2 {
+            return vec![value];
+        }
+        packed
+            .iter()
+            .map(|(offset, size)| {
+                let shifted = value >> (offset * 8);
+                if *size >= 32 {
+                    shifted
+                } else {
+                    shifted & ((EVMU256::from(1) << (size * 8)) - EVMU256::from(1))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Slot of an expression such as `owner`, `balances[msg.sender]` or `allowance[0x..][0x..]`
+pub fn resolve_slot(layout: &StorageLayout, expr: &str, sender: &EVMAddress) -> Option<EVMU256> {
+    let mut parts = expr.trim().split('[');
+    let label = parts.next()?.trim();
+    let variable = layout.variables.iter().find(|v| v.label == label)?;
+    let mut slot = variable.slot;
+    for key in parts {
+        let key = key.trim().strip_suffix(']')?.trim();
+        let word = if key == "msg.sender" {
+            address_word(sender)
+        } else if key.starts_with("0x") && key.len() == 42 {
+            address_word(&EVMAddress::from_str(key).ok()?)
+        } else {
+            EVMU256::from_str(key).ok()?.to_be_bytes::<32>()
+        };
+        slot = mapping_slot(word, slot);
+    }
+    Some(slot)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_layout() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "storage": [
+                    {"label": "owner", "offset": 0, "slot": "0", "type": "t_address"},
+                    {"label": "paused", "offset": 20, "slot": "0", "type": "t_bool"},
+                    {"label": "balances", "offset": 0, "slot": "1", "type": "t_mapping(t_address,t_uint256)"},
+                    {"label": "holders", "offset": 0, "slot": "2", "type": "t_array(t_address)dyn_storage"}
+                ],
+                "types": {
+                    "t_address": {"encoding": "inplace", "label": "address", "numberOfBytes": "20"},
+                    "t_bool": {"encoding": "inplace", "label": "bool", "numberOfBytes": "1"},
+                    "t_uint256": {"encoding": "inplace", "label": "uint256", "numberOfBytes": "32"},
+                    "t_mapping(t_address,t_uint256)": {"encoding": "mapping", "key": "t_address", "label": "mapping(address => uint256)", "numberOfBytes": "32", "value": "t_uint256"},
+                    "t_array(t_address)dyn_storage": {"encoding": "dynamic_array", "base": "t_address", "label": "address[]", "numberOfBytes": "32"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let layout = parse_storage_layout(&json).unwrap();
+        let sender = EVMAddress::from_slice(&[0x11; 20]);
+        let names = layout.slot_names(&HashSet::from([sender]));
+        assert_eq!(names[&EVMU256::ZERO], "owner|paused");
+        let balance = resolve_slot(&layout, "balances[msg.sender]", &sender).unwrap();
+        assert_eq!(names[&balance], format!("balances[{}]", format_address(&sender)));
+        assert_eq!(names[&EVMU256::from(2)], "holders.length");
+        assert_eq!(names[&keccak(&EVMU256::from(2).to_be_bytes::<32>())], "holders[0]");
+
+        let value = (EVMU256::from(1) << 160) | EVMU256::from(0xbeef);
+        assert_eq!(layout.unpack(&EVMU256::ZERO, value), vec![EVMU256::from(0xbeef), EVMU256::from(1)]);
+    }
+}
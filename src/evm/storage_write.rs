@@ -0,0 +1,128 @@
+/// Detection of arbitrary storage writes ("storage pointer" bugs)
+///
+/// A slot of SSTORE is expected to be a constant (static variables) or derived with keccak
+/// (mappings, dynamic arrays), optionally plus a bounded offset (array elements, struct
+/// members). A slot that is a calldata word itself, or a keccak output plus an unbounded offset
+/// read from calldata (array length manipulation, e.g., `array.length--` in Solidity < 0.6 and
+/// then `array[huge] = v`), lets the caller overwrite any slot, e.g., the owner.
+///
+/// Instead of a full taint analysis, the slots are matched against the calldata words of the
+/// frame and the keccak outputs of the transaction, which covers the classic patterns.
+use crate::evm::types::{EVMAddress, EVMU256};
+use revm_interpreter::Interpreter;
+
+/// Slots below are static variables, equal calldata words are coincidences
+pub const STATIC_SLOT_LIMIT: u64 = 1 << 16;
+
+/// Offsets from a keccak output above are not bounded array / struct offsets
+pub const BOUNDED_OFFSET_LIMIT: u64 = u64::MAX;
+
+/// Whether SSTOREs are checked (`--sanitize storage-write`)
+pub static mut STORAGE_WRITE_CHECK: bool = false;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageWriteHit {
+    pub contract: EVMAddress,
+    pub pc: usize,
+    pub slot: EVMU256,
+    /// Range of the calldata bytes controlling the slot
+    pub calldata_range: (usize, usize),
+    /// Whether the slot is a keccak output plus the calldata word
+    pub via_offset: bool,
+}
+
+/// First arbitrary storage write of the current transaction
+pub static mut STORAGE_WRITE_HIT: Option<StorageWriteHit> = None;
+
+/// Keccak outputs of the current transaction
+static mut KECCAK_OUTPUTS: Vec<EVMU256> = Vec::new();
+/// Whether the previous instruction was SHA3, its output is then on top of the stack
+static mut PENDING_KECCAK: bool = false;
+
+pub fn reset_storage_write() {
+    unsafe {
+        STORAGE_WRITE_HIT = None;
+        KECCAK_OUTPUTS.clear();
+        PENDING_KECCAK = false;
+    }
+}
+
+/// Offsets of the calldata words after the selector, with their values
+fn calldata_words(calldata: &[u8]) -> impl Iterator<Item = (usize, EVMU256)> + '_ {
+    (4..calldata.len().saturating_sub(31))
+        .step_by(32)
+        .map(move |offset| (offset, EVMU256::from_be_slice(&calldata[offset..offset + 32])))
+}
+
+/// Range of the calldata bytes making `slot` an arbitrary slot, and whether it is via an offset
+pub fn controlling_bytes(slot: EVMU256, calldata: &[u8], keccak_outputs: &[EVMU256]) -> Option<((usize, usize), bool)> {
+    if slot < EVMU256::from(STATIC_SLOT_LIMIT) || keccak_outputs.contains(&slot) {
+        return None;
+    }
+    for (offset, word) in calldata_words(calldata) {
+        if word == slot {
+            return Some(((offset, offset + 32), false));
+        }
+        if word <= EVMU256::from(BOUNDED_OFFSET_LIMIT) {
+            continue;
+        }
+        if keccak_outputs.iter().any(|hash| hash.wrapping_add(word) == slot) {
+            return Some(((offset, offset + 32), true));
+        }
+    }
+    None
+}
+
+/// Called by the host before each instruction when [`STORAGE_WRITE_CHECK`] is on
+pub fn check_storage_write(interp: &Interpreter) {
+    unsafe {
+        let stack = interp.stack.data();
+        if PENDING_KECCAK {
+            PENDING_KECCAK = false;
+            if let Some(hash) = stack.last() {
+                KECCAK_OUTPUTS.push(*hash);
+            }
+        }
+        match *interp.instruction_pointer {
+            0x20 => PENDING_KECCAK = true,
+            0x55 if STORAGE_WRITE_HIT.is_none() && !stack.is_empty() => {
+                let slot = stack[stack.len() - 1];
+                if let Some((calldata_range, via_offset)) =
+                    controlling_bytes(slot, &interp.contract.input, &KECCAK_OUTPUTS)
+                {
+                    STORAGE_WRITE_HIT = Some(StorageWriteHit {
+                        contract: interp.contract.address,
+                        pc: interp.program_counter(),
+                        slot,
+                        calldata_range,
+                        via_offset,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controlling_bytes() {
+        let slot = EVMU256::from_str_radix("deadbeef00000000000000000000000000", 16).unwrap();
+        let mut calldata = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        calldata.extend(EVMU256::from(7).to_be_bytes::<32>());
+        calldata.extend(slot.to_be_bytes::<32>());
+        assert_eq!(controlling_bytes(slot, &calldata, &[]), Some(((36, 68), false)));
+        // static slot
+        assert_eq!(controlling_bytes(EVMU256::from(7), &calldata, &[]), None);
+        // array element with a huge index
+        let hash = EVMU256::from(1u64 << 40);
+        assert_eq!(
+            controlling_bytes(hash.wrapping_add(slot), &calldata, &[hash]),
+            Some(((36, 68), true))
+        );
+        // array element with a bounded index
+        assert_eq!(controlling_bytes(hash + EVMU256::from(7), &calldata, &[hash]), None);
+    }
+}
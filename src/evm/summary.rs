@@ -0,0 +1,375 @@
+/// Campaign summary, grouping findings by bug class (SWC / DASP category derived from the oracle)
+///
+/// The summary is rewritten to `summary.md` / `summary.json` in the work directory every time
+/// a finding is recorded and printed when the campaign exits, along with the triage report (see
+/// [`crate::evm::triage`]). Findings are sorted by their exploitability score so that triage
+/// starts with the most actionable bugs.
+use crate::evm::abi::BoxedABI;
+use crate::evm::contract_utils::FIX_DEPLOYER;
+use crate::evm::dictionary::save_dictionary;
+use crate::evm::triage::save_triage;
+use crate::workdir::{oracle_name, work_path};
+use crate::ffi_marshal::marshal_errors;
+use libafl::impl_serdeany;
+use libafl::prelude::HasMetadata;
+use revm_primitives::Env;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Bug class of a finding
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BugClass {
+    /// SWC registry id, `-` if there is no matching entry
+    pub swc: String,
+    /// DASP top 10 category
+    pub dasp: String,
+    pub title: String,
+}
+
+impl BugClass {
+    fn new(swc: &str, dasp: &str, title: &str) -> Self {
+        Self {
+            swc: swc.to_string(),
+            dasp: dasp.to_string(),
+            title: title.to_string(),
+        }
+    }
+}
+
+/// Derive the bug class from the name of the oracle that triggered (see [`oracle_name`])
+pub fn classify(oracle: &str) -> BugClass {
+    match oracle {
+        "bug" => BugClass::new("SWC-110", "DASP-10 Unknown Unknowns", "Assert violation"),
+        "flashloan" => BugClass::new("-", "DASP-10 Unknown Unknowns", "Profitable flashloan attack"),
+        "imbalanced_pair" => BugClass::new("-", "DASP-10 Unknown Unknowns", "Imbalanced pair reserves"),
+        "msan" => BugClass::new("-", "DASP-10 Unknown Unknowns", "EVM memory safety violation"),
+        "integer" | "overflow" => BugClass::new("SWC-101", "DASP-3 Arithmetic", "Integer overflow and underflow"),
+        "withdrawal" => BugClass::new("SWC-105", "DASP-2 Access Control", "Unprotected ether withdrawal"),
+        _ => BugClass::new("-", "-", oracle),
+    }
+}
+
+/// A finding of the campaign
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Finding {
+    pub oracle: String,
+    pub class: BugClass,
+    /// Output of the oracle
+    pub output: String,
+    /// Function of the transaction triggering the oracle
+    pub function: String,
+    /// Replayable trace leading to the finding
+    pub reproducer: String,
+    /// Provenance of the args of its transactions, see [`crate::evm::provenance`]
+    #[serde(default)]
+    pub provenance: String,
+    /// Whether the finding needs block environment values (timestamp, number, ...) set by the fuzzer
+    #[serde(default)]
+    pub env_dependent: bool,
+    /// Exploitability score in 0..=100, see [`exploitability`]
+    #[serde(default)]
+    pub score: u32,
+    /// Unix time of the finding, in seconds
+    #[serde(default)]
+    pub found_at: u64,
+}
+
+/// Penalty of a finding requiring the deployer (i.e., a privileged caller)
+const PRIVILEGED_PENALTY: i64 = 40;
+/// Penalty per transaction beyond the first one
+const TXN_PENALTY: i64 = 5;
+const MAX_TXN_PENALTY: i64 = 30;
+/// Penalty of a finding requiring specific block environment values
+const ENV_PENALTY: i64 = 15;
+/// Bonus per order of magnitude of the profit in wei, capped at [`MAX_PROFIT_BONUS`]
+const PROFIT_BONUS_PER_DIGIT: i64 = 2;
+const MAX_PROFIT_BONUS: i64 = 30;
+
+/// Transactions of a reproducer, one per `abi` / `borrow` line (see [`crate::tracer::TxnTrace::to_file_str`])
+fn reproducer_txns(reproducer: &str) -> Vec<&str> {
+    reproducer
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("abi ") || line.starts_with("borrow "))
+        .collect()
+}
+
+/// Whether one of the transactions is sent by the deployer
+fn needs_privileged_caller(txns: &Vec<&str>) -> bool {
+    txns.iter().any(|txn| {
+        txn.split_whitespace()
+            .nth(1)
+            .map(|caller| caller.trim_start_matches("0x").to_lowercase() == FIX_DEPLOYER)
+            .unwrap_or(false)
+    })
+}
+
+/// Profit in wei reported by the oracle, i.e., the net earned or earned minus owed of a flashloan
+fn profit(output: &str) -> Option<String> {
+    if let Some(rest) = output.split("net earned = ").nth(1) {
+        return Some(rest.split("wei").next()?.trim().to_string());
+    }
+    let earned = output.split("Earned ").nth(1)?;
+    let mut words = earned.split_whitespace();
+    let earned = words.next()?;
+    let owed = words.nth(3)?.trim_end_matches(',');
+    let (earned, owed) = (earned.parse::<u128>().ok()?, owed.parse::<u128>().ok()?);
+    Some(earned.saturating_sub(owed).to_string())
+}
+
+/// Exploitability score of a finding in 0..=100, higher is more actionable
+///
+/// A finding loses points if it needs a privileged caller, many transactions or specific block
+/// environment values, and gains points with the magnitude of its profit.
+pub fn exploitability(finding: &Finding) -> u32 {
+    let txns = reproducer_txns(&finding.reproducer);
+    let mut score: i64 = 70;
+    if needs_privileged_caller(&txns) {
+        score -= PRIVILEGED_PENALTY;
+    }
+    score -= (TXN_PENALTY * (txns.len().max(1) as i64 - 1)).min(MAX_TXN_PENALTY);
+    if finding.env_dependent {
+        score -= ENV_PENALTY;
+    }
+    if let Some(profit) = profit(&finding.output) {
+        let digits = profit.trim_start_matches('0').len() as i64;
+        score += (PROFIT_BONUS_PER_DIGIT * digits).min(MAX_PROFIT_BONUS);
+    }
+    score.clamp(0, 100) as u32
+}
+
+/// Whether the fuzzer changed the block environment of the transaction
+pub fn env_dependent(env: &Env) -> bool {
+    env.block != Env::default().block
+}
+
+/// Findings and coverage per function, attached to the fuzz state
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SummaryMetadata {
+    pub findings: Vec<Finding>,
+    /// Edges (indices in the coverage map) covered by the corpus, per function
+    pub function_coverage: HashMap<String, HashSet<usize>>,
+    /// Unix time of the start of the campaign, in seconds
+    #[serde(default)]
+    pub started_at: u64,
+    /// Unix time of the last input covering new edges, in seconds
+    #[serde(default)]
+    pub last_coverage_at: u64,
+}
+
+/// Unix time in seconds
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl_serdeany!(SummaryMetadata);
+
+/// Get the [`SummaryMetadata`] from the state, inserting it if it does not exist yet
+pub fn summary_metadata_mut<S>(state: &mut S) -> &mut SummaryMetadata
+where
+    S: HasMetadata,
+{
+    if !state.metadata().contains::<SummaryMetadata>() {
+        state.metadata_mut().insert(SummaryMetadata {
+            started_at: now_secs(),
+            ..Default::default()
+        });
+    }
+    state.metadata_mut().get_mut::<SummaryMetadata>().unwrap()
+}
+
+/// Name of the function called by the transaction, `fallback` for plain transfers
+pub fn function_name(abi: &Option<BoxedABI>) -> String {
+    match abi {
+        Some(abi) if abi.get_func_name().len() > 0 => abi.get_func_name(),
+        Some(_) => "(control leak)".to_string(),
+        None => "fallback".to_string(),
+    }
+}
+
+/// Attribute the edges covered by an input added to the corpus to its function
+pub fn record_coverage<S>(state: &mut S, function: String, jmp_map: &[u8])
+where
+    S: HasMetadata,
+{
+    let meta = summary_metadata_mut(state);
+    let covered = meta.function_coverage.entry(function).or_default();
+    let mut grew = false;
+    for (idx, hits) in jmp_map.iter().enumerate() {
+        if *hits > 0 {
+            grew |= covered.insert(idx);
+        }
+    }
+    if grew {
+        meta.last_coverage_at = now_secs();
+    }
+}
+
+/// Record a finding and refresh the summary in the work directory
+pub fn record_finding<S>(
+    state: &mut S,
+    oracle_output: &str,
+    function: String,
+    reproducer: String,
+    provenance: String,
+    env_dependent: bool,
+) where
+    S: HasMetadata,
+{
+    let oracle = oracle_name(oracle_output);
+    let mut finding = Finding {
+        class: classify(&oracle),
+        oracle,
+        output: oracle_output.to_string(),
+        function,
+        reproducer,
+        provenance,
+        env_dependent,
+        score: 0,
+        found_at: now_secs(),
+    };
+    finding.score = exploitability(&finding);
+    let meta = summary_metadata_mut(state);
+    meta.findings.push(finding);
+    // stable, so findings with the same score stay in discovery order
+    meta.findings.sort_by(|a, b| b.score.cmp(&a.score));
+    meta.save();
+}
+
+impl SummaryMetadata {
+    /// Render the summary as markdown, bug classes are sorted by their most exploitable finding
+    pub fn render(&self) -> String {
+        let mut by_class: BTreeMap<String, Vec<&Finding>> = BTreeMap::new();
+        for finding in &self.findings {
+            let key = format!("{} | {} | {}", finding.class.swc, finding.class.dasp, finding.class.title);
+            by_class.entry(key).or_default().push(finding);
+        }
+        let mut by_class = by_class
+            .into_iter()
+            .map(|(class, mut findings)| {
+                findings.sort_by(|a, b| b.score.cmp(&a.score));
+                (class, findings)
+            })
+            .collect::<Vec<_>>();
+        by_class.sort_by(|a, b| b.1[0].score.cmp(&a.1[0].score));
+
+        let mut out = String::from("# Campaign summary\n\n## Findings\n\n");
+        if by_class.is_empty() {
+            out.push_str("No findings.\n");
+        }
+        for (class, findings) in &by_class {
+            let functions = findings
+                .iter()
+                .map(|f| f.function.clone())
+                .collect::<HashSet<String>>()
+                .into_iter()
+                .collect::<Vec<String>>()
+                .join(", ");
+            out.push_str(&format!(
+                "### {}\n\n- Exploitability: {}/100\n- Count: {}\n- Affected functions: {}\n- Oracle: {}\n- Reproducer:\n\n```\n{}\n```\n\n",
+                class,
+                findings[0].score,
+                findings.len(),
+                functions,
+                findings[0].output,
+                findings[0].reproducer.trim()
+            ));
+            if !findings[0].provenance.is_empty() {
+                out.push_str(&format!(
+                    "- Argument provenance:\n\n```\n{}\n```\n\n",
+                    findings[0].provenance.trim()
+                ));
+            }
+        }
+
+        out.push_str("## Coverage per function\n\n| Function | Edges |\n|---|---|\n");
+        let mut coverage = self.function_coverage.iter().collect::<Vec<_>>();
+        coverage.sort_by(|a, b| a.0.cmp(b.0));
+        for (function, edges) in coverage {
+            out.push_str(&format!("| {} | {} |\n", function, edges.len()));
+        }
+        out
+    }
+
+    /// Write `summary.md` and `summary.json` to the work directory
+    pub fn save(&self) {
+        let mut file = File::create(work_path("summary.md")).expect("failed to create summary");
+        file.write_all(self.render().as_bytes())
+            .expect("failed to write summary");
+        let mut file = File::create(work_path("summary.json")).expect("failed to create summary");
+        file.write_all(serde_json::to_string_pretty(&self.findings).unwrap().as_bytes())
+            .expect("failed to write summary");
+    }
+}
+
+/// Print and save the summary and the triage report, called when the campaign exits
+pub fn print_summary<S>(state: &mut S)
+where
+    S: HasMetadata,
+{
+    let meta = summary_metadata_mut(state);
+    meta.save();
+    save_triage(meta);
+    save_dictionary();
+    info!("\n{}", meta.render());
+    for (kind, count) in marshal_errors() {
+        warn!("{} inputs did not fit the GPU runner ({}) and ran on revm", count, kind);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_summary() {
+        let mut meta = SummaryMetadata::default();
+        for _ in 0..2 {
+            meta.findings.push(Finding {
+                oracle: "bug".to_string(),
+                class: classify("bug"),
+                output: "[bug] bug() hit at contract 0x0".to_string(),
+                function: "withdraw".to_string(),
+                reproducer: "withdraw()".to_string(),
+                provenance: "withdraw(0x10 (storage slot 0x3))\n".to_string(),
+                env_dependent: false,
+                score: 0,
+                found_at: 0,
+            });
+        }
+        let mut flashloan = Finding {
+            oracle: "flashloan".to_string(),
+            class: classify("flashloan"),
+            output: "[Flashloan] Earned 1000000000000000000 more than owed 0".to_string(),
+            function: "swap".to_string(),
+            reproducer: "abi 0x35c9dfd76bf02107ff4f7128bd69716612d31ddb 0x0 00 0 0 0 1 0 false\n".to_string(),
+            provenance: String::new(),
+            env_dependent: false,
+            score: 0,
+            found_at: 0,
+        };
+        flashloan.score = exploitability(&flashloan);
+        meta.findings.push(flashloan.clone());
+        meta.function_coverage
+            .insert("withdraw".to_string(), HashSet::from([1, 2, 3]));
+
+        let summary = meta.render();
+        assert!(summary.contains("### SWC-110 | DASP-10 Unknown Unknowns | Assert violation"));
+        assert!(summary.contains("- Count: 2"));
+        assert!(summary.contains("| withdraw | 3 |"));
+        assert!(summary.contains("withdraw(0x10 (storage slot 0x3))"));
+        // the profitable finding comes first
+        assert!(summary.find("Profitable flashloan").unwrap() < summary.find("Assert violation").unwrap());
+
+        let privileged = Finding {
+            reproducer: format!("abi 0x{} 0x0 00 0 0 0 1 0 false\nabi 0x{} 0x0 00 0 0 0 1 0 false\n", FIX_DEPLOYER, FIX_DEPLOYER),
+            env_dependent: true,
+            ..flashloan.clone()
+        };
+        assert_eq!(exploitability(&flashloan), 100);
+        assert!(exploitability(&privileged) < exploitability(&flashloan));
+    }
+}
@@ -0,0 +1,266 @@
+/// Parsing of the targets given with `-t`
+///
+/// A target is one of (comma separated for several on-chain contracts):
+/// - `0x<address>`, optionally bound to a chain as `0x<address>@bsc` or `bsc:0x<address>`
+///   (EIP-3770). Mixed-case addresses must carry a valid EIP-55 checksum.
+/// - an ENS name, `vault.eth[@chain]` or `ens:<name>[@chain]`, resolved through the ENS registry
+///   of mainnet (`--ens-registry`, `--ens-rpc`); on other chains the address of the chain is
+///   read from the resolver (ENSIP-11).
+/// - `bytecode:0x<creation code>` or `runtime:0x<runtime code>`, fuzzed with the ABI recovered
+///   by the decompiler.
+/// - a Foundry / Hardhat project, or else a glob of `.abi` / `.bin` files.
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha3::Sha3;
+use crate::evm::hex_utils::HexBytecode;
+use crate::evm::onchain::endpoints::{Chain, OnChainConfig};
+use crate::evm::project::Project;
+use crate::evm::types::EVMAddress;
+use std::str::FromStr;
+
+/// ENS registry, at the same address on mainnet and the testnets
+pub const DEFAULT_ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+
+/// `resolver(bytes32)`, `addr(bytes32)` and `addr(bytes32,uint256)`
+const RESOLVER: [u8; 4] = [0x01, 0x78, 0xb8, 0xbf];
+const ADDR: [u8; 4] = [0x3b, 0x3b, 0x57, 0xde];
+const ADDR_COIN_TYPE: [u8; 4] = [0xf1, 0xcb, 0x7e, 0x06];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetSpec {
+    Address { address: EVMAddress, chain: Option<Chain> },
+    Ens { name: String, chain: Option<Chain> },
+    /// Creation code, or runtime code set at a fresh address when `deployed`
+    Bytecode { code: Vec<u8>, deployed: bool },
+    Project(String),
+    Glob(String),
+}
+
+fn keccak(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    let mut out = [0u8; 32];
+    hasher.result(&mut out);
+    out
+}
+
+/// Chain of an EIP-3770 short name or of a `--chain-type` name
+pub fn parse_chain(name: &str) -> Result<Chain, String> {
+    match name.to_lowercase().as_str() {
+        "bnb" => Ok(Chain::BSC),
+        "matic" => Ok(Chain::POLYGON),
+        lower => Chain::from_str(&lower.to_string()).ok_or(format!("unknown chain {}", name)),
+    }
+}
+
+/// EIP-55 checksummed form of the address
+pub fn to_checksum(address: &EVMAddress) -> String {
+    let lower = hex::encode(address.0);
+    let hash = keccak(lower.as_bytes());
+    let checksummed = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0xf;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+/// Address, checking the checksum of a mixed-case one
+pub fn parse_address(s: &str) -> Result<EVMAddress, String> {
+    let digits = s.strip_prefix("0x").ok_or(format!("{}: an address starts with 0x", s))?;
+    if digits.len() != 40 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("{}: an address has 40 hex digits", s));
+    }
+    let address = EVMAddress::from_str(digits).map_err(|e| format!("{}: {}", s, e))?;
+    let mixed = digits.chars().any(|c| c.is_ascii_lowercase()) && digits.chars().any(|c| c.is_ascii_uppercase());
+    if mixed && to_checksum(&address) != s {
+        return Err(format!("{}: invalid checksum, expected {}", s, to_checksum(&address)));
+    }
+    Ok(address)
+}
+
+/// ENS namehash of the name
+pub fn namehash(name: &str) -> [u8; 32] {
+    let mut node = [0u8; 32];
+    for label in name.rsplit('.').filter(|label| !label.is_empty()) {
+        node = keccak(&[node, keccak(label.to_lowercase().as_bytes())].concat());
+    }
+    node
+}
+
+/// Target name and chain of `target@chain` or `chain:target`
+fn split_chain(s: &str) -> Result<(&str, Option<Chain>), String> {
+    if let Some((target, chain)) = s.rsplit_once('@') {
+        return Ok((target, Some(parse_chain(chain)?)));
+    }
+    match s.split_once(':') {
+        Some((chain, target)) if target.starts_with("0x") => Ok((target, Some(parse_chain(chain)?))),
+        _ => Ok((s, None)),
+    }
+}
+
+fn parse_code(s: &str) -> Result<Vec<u8>, String> {
+    let code = HexBytecode::from_hex(s)
+        .map(HexBytecode::into_bytes)
+        .map_err(|e| format!("invalid bytecode: {}", e))?;
+    if code.is_empty() {
+        return Err("empty bytecode".to_string());
+    }
+    Ok(code)
+}
+
+pub fn parse_target(s: &str) -> Result<TargetSpec, String> {
+    let s = s.trim();
+    if let Some(code) = s.strip_prefix("bytecode:") {
+        return Ok(TargetSpec::Bytecode { code: parse_code(code)?, deployed: false });
+    }
+    if let Some(code) = s.strip_prefix("runtime:") {
+        return Ok(TargetSpec::Bytecode { code: parse_code(code)?, deployed: true });
+    }
+    if let Some(name) = s.strip_prefix("ens:") {
+        let (name, chain) = split_chain(name)?;
+        return Ok(TargetSpec::Ens { name: name.to_string(), chain });
+    }
+    if s.starts_with("0x") || s.contains(":0x") || (s.contains('@') && !s.contains('/')) {
+        let (target, chain) = split_chain(s)?;
+        if target.ends_with(".eth") {
+            return Ok(TargetSpec::Ens { name: target.to_string(), chain });
+        }
+        return Ok(TargetSpec::Address { address: parse_address(target)?, chain });
+    }
+    if s.ends_with(".eth") && !s.contains('/') && !s.contains('*') {
+        return Ok(TargetSpec::Ens { name: s.to_string(), chain: None });
+    }
+    if Project::is_project(s) {
+        return Ok(TargetSpec::Project(s.to_string()));
+    }
+    Ok(TargetSpec::Glob(s.to_string()))
+}
+
+/// Comma separated targets, either on-chain contracts (sharing one chain), bytecodes, or a
+/// single project / glob
+pub fn parse_targets(s: &str) -> Result<Vec<TargetSpec>, String> {
+    let targets = s
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(parse_target)
+        .collect::<Result<Vec<_>, _>>()?;
+    let onchain = targets
+        .iter()
+        .filter(|t| matches!(t, TargetSpec::Address { .. } | TargetSpec::Ens { .. }))
+        .count();
+    let bytecodes = targets.iter().filter(|t| matches!(t, TargetSpec::Bytecode { .. })).count();
+    if targets.is_empty() {
+        return Err("no target".to_string());
+    }
+    if onchain != targets.len() && bytecodes != targets.len() && targets.len() != 1 {
+        return Err(format!("{}: cannot mix on-chain targets, bytecodes, projects and globs", s));
+    }
+    target_chain(&targets)?;
+    Ok(targets)
+}
+
+/// Chain the on-chain targets are bound to
+pub fn target_chain(targets: &Vec<TargetSpec>) -> Result<Option<Chain>, String> {
+    let mut chain = None;
+    for target in targets {
+        let bound = match target {
+            TargetSpec::Address { chain, .. } | TargetSpec::Ens { chain, .. } => *chain,
+            _ => None,
+        };
+        match (chain, bound) {
+            (Some(c), Some(b)) if c != b => {
+                return Err(format!("targets on both {} and {}", c.to_lowercase(), b.to_lowercase()))
+            }
+            (None, Some(b)) => chain = Some(b),
+            _ => {}
+        }
+    }
+    Ok(chain)
+}
+
+/// Address of the ENS name on the chain, resolved through the registry
+pub fn resolve_ens(resolver_rpc: &OnChainConfig, registry: EVMAddress, name: &str, chain: Chain) -> Result<EVMAddress, String> {
+    let node = namehash(name);
+    let ret = resolver_rpc
+        .eth_call(registry, &[RESOLVER.to_vec(), node.to_vec()].concat())
+        .ok_or(format!("{}: failed to query the ENS registry", name))?;
+    if ret.len() < 32 || ret[12..32].iter().all(|b| *b == 0) {
+        return Err(format!("{}: no resolver", name));
+    }
+    let resolver = EVMAddress::from_slice(&ret[12..32]);
+    let call = match chain {
+        Chain::ETH => [ADDR.to_vec(), node.to_vec()].concat(),
+        _ => {
+            // ENSIP-11 coin type of an EVM chain
+            let mut coin_type = [0u8; 32];
+            coin_type[28..].copy_from_slice(&(0x8000_0000 | chain.get_chain_id()).to_be_bytes());
+            [ADDR_COIN_TYPE.to_vec(), node.to_vec(), coin_type.to_vec()].concat()
+        }
+    };
+    let ret = resolver_rpc
+        .eth_call(resolver, &call)
+        .ok_or(format!("{}: failed to query the resolver", name))?;
+    // `addr(bytes32)` returns an address, `addr(bytes32,uint256)` bytes
+    let address = match chain {
+        Chain::ETH if ret.len() >= 32 => ret[12..32].to_vec(),
+        _ if ret.len() >= 84 => ret[64..84].to_vec(),
+        _ => vec![],
+    };
+    if address.len() != 20 || address.iter().all(|b| *b == 0) {
+        return Err(format!("{}: no address on {}", name, chain.to_lowercase()));
+    }
+    Ok(EVMAddress::from_slice(&address))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let address = parse_address(checksummed).unwrap();
+        assert_eq!(to_checksum(&address), checksummed);
+        assert!(parse_address(&checksummed.to_lowercase()).is_ok());
+        assert!(parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+
+        assert_eq!(
+            parse_target(&format!("{}@bsc", checksummed)).unwrap(),
+            TargetSpec::Address { address, chain: Some(Chain::BSC) }
+        );
+        assert_eq!(
+            parse_target(&format!("bnb:{}", checksummed)).unwrap(),
+            TargetSpec::Address { address, chain: Some(Chain::BSC) }
+        );
+        assert_eq!(
+            parse_target("vault.eth@polygon").unwrap(),
+            TargetSpec::Ens { name: "vault.eth".to_string(), chain: Some(Chain::POLYGON) }
+        );
+        assert_eq!(
+            parse_target("runtime:0x6080").unwrap(),
+            TargetSpec::Bytecode { code: vec![0x60, 0x80], deployed: true }
+        );
+        assert_eq!(parse_target("tests/evm/*").unwrap(), TargetSpec::Glob("tests/evm/*".to_string()));
+
+        assert!(parse_targets(&format!("{}@bsc,{}@eth", checksummed, checksummed)).is_err());
+        assert!(parse_targets(&format!("{},tests/evm/*", checksummed)).is_err());
+        assert_eq!(parse_targets(&format!("{}@bsc,vault.eth", checksummed)).unwrap().len(), 2);
+
+        assert_eq!(
+            hex::encode(namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+        assert_eq!(
+            hex::encode(namehash("foo.eth")),
+            "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+        );
+    }
+}
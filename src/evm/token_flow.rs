@@ -0,0 +1,142 @@
+/// Mutation of token amounts relative to the live balances
+///
+/// Random 32-byte amounts almost always exceed the balance of the caller, and a transfer /
+/// withdrawal reverts right away. The balance mapping of each token among the targets is found
+/// once (see [`crate::evm::token_minting::find_balance_slot`]), and the uint256 arguments of the
+/// functions moving tokens (by their name: `transfer`, `withdraw`, `swap`, ...) are set to
+/// `{0, 1, balance - 1, balance, balance + 1, type(uint256).max}` of the balance of the caller or
+/// of the target, read from the state the input is executed on. The uint256 arguments of the
+/// other functions (e.g., decompiled ones, whose names are unknown) get these values less often.
+use crate::evm::token_minting::MappingSlot;
+use crate::evm::types::{EVMAddress, EVMU256};
+use crate::evm::vm::EVMState;
+use libafl::prelude::Rand;
+use libafl::state::HasRand;
+use std::collections::HashSet;
+
+/// `balanceOf(address)`, tokens are the targets having it
+pub const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Words in the names of the functions taking token amounts
+pub const AMOUNT_KEYWORDS: [&str; 16] = [
+    "amount", "transfer", "deposit", "withdraw", "mint", "burn", "swap", "stake", "redeem", "borrow", "repay",
+    "approve", "liquidat", "claim", "supply", "flash",
+];
+
+/// Percentage of the mutations of an argument of a function taking amounts relative to a balance
+pub const AMOUNT_MUTATION_PROB: u64 = 40;
+
+/// Percentage for the uint256 arguments of the other functions
+pub const OTHER_MUTATION_PROB: u64 = 5;
+
+static mut BALANCE_SLOTS: Vec<(EVMAddress, MappingSlot)> = Vec::new();
+static mut AMOUNT_FUNCTIONS: Option<HashSet<[u8; 4]>> = None;
+
+/// Balances of the input being mutated, and whether its function takes amounts
+static mut LIVE_BALANCES: Vec<EVMU256> = Vec::new();
+static mut AMOUNT_FUNCTION: bool = false;
+
+pub fn register_balance_slot(token: EVMAddress, slot: MappingSlot) {
+    unsafe {
+        if !BALANCE_SLOTS.iter().any(|(t, _)| *t == token) {
+            BALANCE_SLOTS.push((token, slot));
+        }
+    }
+}
+
+pub fn balance_slot(token: &EVMAddress) -> Option<MappingSlot> {
+    unsafe { BALANCE_SLOTS.iter().find(|(t, _)| t == token).map(|(_, slot)| *slot) }
+}
+
+/// Whether the function plausibly takes a token amount
+pub fn is_amount_function(name: &str) -> bool {
+    let name = name.to_lowercase();
+    AMOUNT_KEYWORDS.iter().any(|k| name.contains(k))
+}
+
+pub fn register_amount_function(selector: [u8; 4]) {
+    unsafe {
+        AMOUNT_FUNCTIONS.get_or_insert_with(HashSet::new).insert(selector);
+    }
+}
+
+/// Non-zero balances of the holders in the tokens whose balance mapping is known
+pub fn live_balances(vm_state: &EVMState, holders: &[EVMAddress]) -> Vec<EVMU256> {
+    let mut balances = vec![];
+    for (token, slot) in unsafe { BALANCE_SLOTS.iter() } {
+        let storage = match vm_state.get(token) {
+            Some(storage) => storage,
+            None => continue,
+        };
+        for holder in holders {
+            match storage.get(&slot.key(holder)) {
+                Some(balance) if *balance != EVMU256::ZERO && !balances.contains(balance) => balances.push(*balance),
+                _ => {}
+            }
+        }
+    }
+    balances
+}
+
+/// Set the balances the amounts of the input being mutated are relative to
+pub fn set_live_balances(vm_state: &EVMState, caller: EVMAddress, contract: EVMAddress, selector: [u8; 4]) {
+    unsafe {
+        if BALANCE_SLOTS.is_empty() {
+            return;
+        }
+        LIVE_BALANCES = live_balances(vm_state, &[caller, contract]);
+        AMOUNT_FUNCTION = AMOUNT_FUNCTIONS.as_ref().map_or(false, |f| f.contains(&selector));
+    }
+}
+
+/// Amounts around the balance
+pub fn amount_candidates(balance: EVMU256) -> [EVMU256; 6] {
+    [
+        EVMU256::ZERO,
+        EVMU256::from(1),
+        balance.saturating_sub(EVMU256::from(1)),
+        balance,
+        balance.saturating_add(EVMU256::from(1)),
+        EVMU256::MAX,
+    ]
+}
+
+/// Amount relative to a live balance, `None` to leave the argument to the other mutations
+pub fn mutate_amount<S: HasRand>(state: &mut S) -> Option<EVMU256> {
+    let (balances, prob) = unsafe {
+        (
+            &LIVE_BALANCES,
+            if AMOUNT_FUNCTION { AMOUNT_MUTATION_PROB } else { OTHER_MUTATION_PROB },
+        )
+    };
+    if balances.is_empty() || state.rand_mut().below(100) >= prob {
+        return None;
+    }
+    let balance = balances[state.rand_mut().below(balances.len() as u64) as usize];
+    let candidates = amount_candidates(balance);
+    Some(candidates[state.rand_mut().below(candidates.len() as u64) as usize])
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::token_minting::{mint, MappingLayout};
+
+    #[test]
+    fn test_live_balances() {
+        assert!(is_amount_function("transferFrom"));
+        assert!(is_amount_function("emergencyWithdraw"));
+        assert!(!is_amount_function("setOwner"));
+
+        let token = EVMAddress::from_slice(&[0x11; 20]);
+        let caller = EVMAddress::from_slice(&[0x22; 20]);
+        let slot = MappingSlot { index: EVMU256::from(3), layout: MappingLayout::Solidity };
+        register_balance_slot(token, slot);
+        let mut vm_state = EVMState::default();
+        mint(&mut vm_state, token, &slot, &vec![caller], EVMU256::from(1000));
+        assert_eq!(live_balances(&vm_state, &[caller, token]), vec![EVMU256::from(1000)]);
+
+        let candidates = amount_candidates(EVMU256::from(1000));
+        assert!(candidates.contains(&EVMU256::from(999)) && candidates.contains(&EVMU256::from(1001)));
+        assert_eq!(amount_candidates(EVMU256::MAX)[4], EVMU256::MAX);
+    }
+}
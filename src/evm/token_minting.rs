@@ -0,0 +1,257 @@
+/// Force-set ERC-20 balances and allowances of the fuzzer's callers
+///
+/// Many targets only become interesting once the caller holds some token (e.g., a vault that
+/// takes deposits). Instead of hoping for the fuzzer to find a way to acquire them, we write the
+/// balance / allowance mapping slots of the token directly. The mapping slot is discovered with
+/// probing calls: a marker value is written to a candidate slot and `balanceOf` / `allowance` is
+/// called to check whether the token reads it back.
+///
+/// The minted storage is part of the initial VM state, so both revm and the device snapshot
+/// (loaded from the staged VM state of each batch, see [`crate::gpu_stage`]) start with it.
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha3::Sha3;
+use crate::evm::hex_utils::parse_address;
+use crate::evm::input::EVMInput;
+use crate::evm::types::{fixed_address, EVMAddress, EVMFuzzState, EVMU256};
+use crate::evm::vm::{EVMExecutor, EVMState};
+use crate::generic_vm::vm_executor::GenericVM;
+use bytes::Bytes;
+use std::str::FromStr;
+
+/// Amount minted when none is given (1e24, i.e., 1M tokens with 18 decimals)
+pub const DEFAULT_MINT_AMOUNT: &str = "1000000000000000000000000";
+
+/// Number of storage indices probed for the balance / allowance mapping
+pub const MAX_PROBED_SLOTS: u64 = 64;
+
+/// Holder / spender used by the probing calls
+const PROBE_HOLDER: &str = "00000000000000000000000000000000000c0ffe";
+const PROBE_SPENDER: &str = "000000000000000000000000000000000000beef";
+
+/// Marker written to the candidate slots
+const PROBE_VALUE: u64 = 0x1337_c0ffee;
+
+const BALANCE_OF: &str = "70a08231";
+const ALLOWANCE: &str = "dd62ed3e";
+
+/// A token to mint for the callers, e.g., `USDT:1000` or `0xdac1...:1000`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenMint {
+    /// Contract name or address of the token
+    pub token: String,
+    pub amount: EVMU256,
+}
+
+/// Parse a comma separated list of `token[:amount]`
+pub fn parse_token_mints(s: &str) -> Vec<TokenMint> {
+    s.split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let (token, amount) = match t.split_once(':') {
+                Some((token, amount)) => (token, amount),
+                None => (t, DEFAULT_MINT_AMOUNT),
+            };
+            TokenMint {
+                token: token.to_string(),
+                amount: EVMU256::from_str(amount).expect("invalid mint amount"),
+            }
+        })
+        .collect()
+}
+
+impl TokenMint {
+    /// Address of the token among the deployed contracts (name, address)
+    pub fn resolve(&self, deployed: &Vec<(String, EVMAddress)>) -> Option<EVMAddress> {
+        if self.token.starts_with("0x") {
+            return parse_address(&self.token).ok();
+        }
+        deployed
+            .iter()
+            .find(|(name, _)| name == &self.token)
+            .map(|(_, addr)| *addr)
+    }
+}
+
+/// How the compiler computes the storage key of a mapping entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MappingLayout {
+    /// `keccak256(key . index)`
+    Solidity,
+    /// `keccak256(index . key)`
+    Vyper,
+}
+
+/// Storage index and layout of a mapping
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MappingSlot {
+    pub index: EVMU256,
+    pub layout: MappingLayout,
+}
+
+pub(crate) fn keccak(data: &[u8]) -> EVMU256 {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    let mut out = [0u8; 32];
+    hasher.result(&mut out);
+    EVMU256::from_be_bytes(out)
+}
+
+pub(crate) fn address_word(addr: &EVMAddress) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(addr.0.as_slice());
+    word
+}
+
+impl MappingSlot {
+    fn entry(&self, key: [u8; 32], index: EVMU256) -> EVMU256 {
+        let index: [u8; 32] = index.to_be_bytes();
+        match self.layout {
+            MappingLayout::Solidity => keccak(&[key, index].concat()),
+            MappingLayout::Vyper => keccak(&[index, key].concat()),
+        }
+    }
+
+    /// Storage key of `mapping[key]`
+    pub fn key(&self, key: &EVMAddress) -> EVMU256 {
+        self.entry(address_word(key), self.index)
+    }
+
+    /// Storage key of `mapping[key][inner]`
+    pub fn nested_key(&self, key: &EVMAddress, inner: &EVMAddress) -> EVMU256 {
+        self.entry(address_word(inner), self.key(key))
+    }
+}
+
+/// Candidate mapping slots, in the order they are probed
+fn candidates() -> Vec<MappingSlot> {
+    let mut slots = vec![];
+    for index in 0..MAX_PROBED_SLOTS {
+        for layout in [MappingLayout::Solidity, MappingLayout::Vyper] {
+            slots.push(MappingSlot {
+                index: EVMU256::from(index),
+                layout,
+            });
+        }
+    }
+    slots
+}
+
+/// Find the mapping slot such that writing the marker to `storage_key(slot)` makes the call
+/// return the marker
+fn probe_slot<F>(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    token: EVMAddress,
+    call_data: Bytes,
+    storage_key: F,
+) -> Option<MappingSlot>
+where
+    F: Fn(&MappingSlot) -> EVMU256,
+{
+    if executor.host.code.get(&token).is_none() {
+        return None;
+    }
+    let marker = EVMU256::from(PROBE_VALUE);
+    candidates().into_iter().find(|slot| {
+        let mut probed = vm_state.clone();
+        probed
+            .state
+            .entry(token)
+            .or_default()
+            .insert(storage_key(slot), marker);
+        let res = executor.fast_static_call(&vec![(token, call_data.clone())], &probed, state);
+        EVMU256::try_from_be_slice(res[0].as_slice()) == Some(marker)
+    })
+}
+
+/// Discover the slot of the `balanceOf` mapping of the token
+pub fn find_balance_slot(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    token: EVMAddress,
+) -> Option<MappingSlot> {
+    let holder = fixed_address(PROBE_HOLDER);
+    let call_data = Bytes::from([hex::decode(BALANCE_OF).unwrap(), address_word(&holder).to_vec()].concat());
+    probe_slot(executor, state, vm_state, token, call_data, |slot| slot.key(&holder))
+}
+
+/// Discover the slot of the `allowance` mapping of the token
+pub fn find_allowance_slot(
+    executor: &mut EVMExecutor<EVMInput, EVMFuzzState, EVMState>,
+    state: &mut EVMFuzzState,
+    vm_state: &EVMState,
+    token: EVMAddress,
+) -> Option<MappingSlot> {
+    let owner = fixed_address(PROBE_HOLDER);
+    let spender = fixed_address(PROBE_SPENDER);
+    let call_data = Bytes::from(
+        [
+            hex::decode(ALLOWANCE).unwrap(),
+            address_word(&owner).to_vec(),
+            address_word(&spender).to_vec(),
+        ]
+        .concat(),
+    );
+    probe_slot(executor, state, vm_state, token, call_data, |slot| {
+        slot.nested_key(&owner, &spender)
+    })
+}
+
+/// Set the balance of each holder. The total supply is left untouched, so the token balances
+/// of the other accounts are preserved.
+pub fn mint(vm_state: &mut EVMState, token: EVMAddress, slot: &MappingSlot, holders: &Vec<EVMAddress>, amount: EVMU256) {
+    let storage = vm_state.state.entry(token).or_default();
+    for holder in holders {
+        storage.insert(slot.key(holder), amount);
+    }
+}
+
+/// Give each spender an unlimited allowance over the tokens of each owner
+pub fn approve(
+    vm_state: &mut EVMState,
+    token: EVMAddress,
+    slot: &MappingSlot,
+    owners: &Vec<EVMAddress>,
+    spenders: &Vec<EVMAddress>,
+) {
+    let storage = vm_state.state.entry(token).or_default();
+    for owner in owners {
+        for spender in spenders {
+            storage.insert(slot.nested_key(owner, spender), EVMU256::MAX);
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token_mints() {
+        let mints = parse_token_mints("USDT:1000, 0x000000000000000000000000000000000000beef");
+        assert_eq!(mints.len(), 2);
+        assert_eq!(mints[0].token, "USDT");
+        assert_eq!(mints[0].amount, EVMU256::from(1000));
+        assert_eq!(mints[1].amount, EVMU256::from_str(DEFAULT_MINT_AMOUNT).unwrap());
+        assert_eq!(
+            mints[1].resolve(&vec![]),
+            Some(fixed_address(PROBE_SPENDER))
+        );
+    }
+
+    #[test]
+    fn test_mapping_key() {
+        // balances[0x..beef] at index 0, i.e., keccak256(pad(0x..beef) . pad(0))
+        let slot = MappingSlot {
+            index: EVMU256::ZERO,
+            layout: MappingLayout::Solidity,
+        };
+        let mut data = address_word(&fixed_address(PROBE_SPENDER)).to_vec();
+        data.extend([0u8; 32]);
+        assert_eq!(slot.key(&fixed_address(PROBE_SPENDER)), keccak(&data));
+    }
+}
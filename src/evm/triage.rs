@@ -0,0 +1,313 @@
+/// Post-campaign triage report (`TRIAGE.md` in the work directory)
+///
+/// The summary says what the campaign found, the triage report says what to do next, for
+/// engineers who are not fuzzing experts. It is written with the summary when the campaign exits
+/// and has three parts:
+/// - the findings grouped by bug class, the most exploitable first, with where their
+///   reproducers are
+/// - the critical functions left uncovered: the functions the static pass of `--bug-priors`
+///   scores at least [`CRITICAL_SCORE`] (see [`crate::evm::bug_likelihood`]) without a single
+///   edge covered, and the functions whose executions revert in the guard phase (see
+///   [`crate::evm::revert_stats`])
+/// - the call-to-action items synthesized from the telemetry of the campaign ([`triage_actions`]):
+///   seed a selector, provide the storage layout, raise the time or memory budget, run the static
+///   pass
+use crate::evm::abi::function_sig_name;
+use crate::evm::bug_likelihood::function_scores;
+use crate::evm::config::TIME_BUDGET;
+use crate::evm::revert_stats::dead_selectors;
+use crate::evm::storage_layout::storage_layouts;
+use crate::evm::summary::{now_secs, Finding, SummaryMetadata};
+use crate::host_mem;
+use crate::workdir::{work_path, SOLUTIONS_DIR};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use tracing::info;
+
+pub const TRIAGE_FILE: &str = "TRIAGE.md";
+
+/// Score of the static pass from which a function is critical (a value call or worse)
+pub const CRITICAL_SCORE: usize = 4;
+
+/// The campaign was still finding coverage if its last new edge came in the last this percent
+/// of its time
+pub const STILL_GROWING_PERCENT: u64 = 20;
+
+/// What the campaign knows besides its findings and coverage, gathered when it exits
+#[derive(Clone, Debug, Default)]
+pub struct CampaignTelemetry {
+    /// Score of the static pass by selector, `None` without `--bug-priors`
+    pub scores: Option<HashMap<[u8; 4], usize>>,
+    /// Selectors reverting in the guard phase, with their guard revert rate
+    pub dead: Vec<([u8; 4], f64)>,
+    pub has_storage_layouts: bool,
+    /// `--time-budget` in seconds, 0 for none
+    pub time_budget: u64,
+    /// Seconds since the start of the campaign
+    pub elapsed: u64,
+    /// Seconds since the last input covering new edges
+    pub since_last_coverage: u64,
+    pub memory_exhausted: bool,
+}
+
+impl CampaignTelemetry {
+    pub fn collect(meta: &SummaryMetadata) -> Self {
+        let now = now_secs();
+        Self {
+            scores: function_scores(),
+            dead: dead_selectors(),
+            has_storage_layouts: !storage_layouts().is_empty(),
+            time_budget: unsafe { TIME_BUDGET },
+            elapsed: now.saturating_sub(meta.started_at),
+            since_last_coverage: now.saturating_sub(meta.last_coverage_at.max(meta.started_at)),
+            memory_exhausted: host_mem::exhausted(),
+        }
+    }
+}
+
+/// A critical function without coverage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UncoveredFunction {
+    pub selector: [u8; 4],
+    pub name: String,
+    /// Why it is critical
+    pub reason: String,
+}
+
+/// Something to do after the campaign
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Replay and fix the findings of a bug class
+    Confirm { class: String, oracle: String, count: usize, score: u32 },
+    /// Write a seed calling the function, or prepare the state it needs
+    Seed { selector: [u8; 4], name: String },
+    /// Name the slots of the targets with `--storage-layout`
+    StorageLayout,
+    /// Score the functions with `--bug-priors` to find the critical ones
+    BugPriors,
+    /// The campaign stopped while still finding coverage
+    TimeBudget { suggested: u64 },
+    /// The campaign stopped on its memory budget
+    MemoryBudget,
+}
+
+impl Action {
+    pub fn describe(&self) -> String {
+        match self {
+            Action::Confirm { class, oracle, count, score } => format!(
+                "Confirm and fix **{}** ({} finding{}, exploitability {}/100): replay the reproducers in `{}/{}/`, or run them on a fork with `cli export-exploit`",
+                class,
+                count,
+                if *count > 1 { "s" } else { "" },
+                score,
+                SOLUTIONS_DIR,
+                oracle
+            ),
+            Action::Seed { selector, name } => format!(
+                "Add a seed for selector `0x{}` (`{}`): write an input calling it as YAML and encode it into the corpus (`cli input encode seed.yaml -o corpus/input-{}.bin`), or prepare the state it needs with `--scenario-file`",
+                hex::encode(selector),
+                name,
+                hex::encode(selector)
+            ),
+            Action::StorageLayout => "Provide the storage layout of the targets (`--storage-layout`, solc `storage-layout` output): the slots get named in the state diffs and a `--scenario-file` can set the guarded slots by name".to_string(),
+            Action::BugPriors => "Run with `--bug-priors` to score the functions by a static pass, so that the uncovered critical functions are listed here and fuzzed first".to_string(),
+            Action::TimeBudget { suggested } => format!(
+                "Raise the time budget (e.g. `--time-budget {}`): the campaign was still covering new edges when it stopped",
+                suggested
+            ),
+            Action::MemoryBudget => "Raise the memory budget (`--max-host-mem`) or lower `--max-states`: the campaign stopped on its memory budget".to_string(),
+        }
+    }
+}
+
+/// Findings grouped by bug class title, the most exploitable class first
+pub fn group_findings(findings: &Vec<Finding>) -> Vec<(String, Vec<&Finding>)> {
+    let mut by_class: BTreeMap<String, Vec<&Finding>> = BTreeMap::new();
+    for finding in findings {
+        by_class.entry(finding.class.title.clone()).or_default().push(finding);
+    }
+    let mut groups = by_class.into_iter().collect::<Vec<_>>();
+    for (_, findings) in groups.iter_mut() {
+        findings.sort_by(|a, b| b.score.cmp(&a.score));
+    }
+    groups.sort_by(|a, b| b.1[0].score.cmp(&a.1[0].score));
+    groups
+}
+
+/// Critical functions without any edge covered, then the ones stuck in their guard phase
+pub fn uncovered_critical(meta: &SummaryMetadata, telemetry: &CampaignTelemetry) -> Vec<UncoveredFunction> {
+    let covered = |name: &String| meta.function_coverage.get(name).map_or(false, |edges| !edges.is_empty());
+    let mut scored = telemetry
+        .scores
+        .iter()
+        .flatten()
+        .filter(|(_, score)| **score >= CRITICAL_SCORE)
+        .map(|(selector, score)| (*selector, *score))
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut uncovered = vec![];
+    for (selector, score) in scored {
+        let name = function_sig_name(&selector);
+        if !covered(&name) {
+            uncovered.push(UncoveredFunction {
+                selector,
+                name,
+                reason: format!("risk score {} of the static pass, no edge covered", score),
+            });
+        }
+    }
+    for (selector, rate) in &telemetry.dead {
+        if uncovered.iter().all(|function| function.selector != *selector) {
+            uncovered.push(UncoveredFunction {
+                selector: *selector,
+                name: function_sig_name(selector),
+                reason: format!("{:.1}% of its executions revert in the guard phase", rate * 100.0),
+            });
+        }
+    }
+    uncovered
+}
+
+/// Call-to-action items, the findings to confirm first
+pub fn triage_actions(meta: &SummaryMetadata, telemetry: &CampaignTelemetry) -> Vec<Action> {
+    let mut actions = group_findings(&meta.findings)
+        .into_iter()
+        .map(|(class, findings)| Action::Confirm {
+            class,
+            oracle: findings[0].oracle.clone(),
+            count: findings.len(),
+            score: findings[0].score,
+        })
+        .collect::<Vec<_>>();
+    let uncovered = uncovered_critical(meta, telemetry);
+    actions.extend(uncovered.iter().map(|function| Action::Seed {
+        selector: function.selector,
+        name: function.name.clone(),
+    }));
+    if !uncovered.is_empty() && !telemetry.has_storage_layouts {
+        actions.push(Action::StorageLayout);
+    }
+    if telemetry.scores.is_none() {
+        actions.push(Action::BugPriors);
+    }
+    let budget = telemetry.time_budget;
+    if budget > 0
+        && telemetry.elapsed >= budget
+        && telemetry.since_last_coverage * 100 < telemetry.elapsed * STILL_GROWING_PERCENT
+    {
+        actions.push(Action::TimeBudget { suggested: budget * 2 });
+    }
+    if telemetry.memory_exhausted {
+        actions.push(Action::MemoryBudget);
+    }
+    actions
+}
+
+pub fn render_triage(meta: &SummaryMetadata, telemetry: &CampaignTelemetry) -> String {
+    let mut out = String::from("# Triage\n\n## Findings\n\n");
+    let groups = group_findings(&meta.findings);
+    if groups.is_empty() {
+        out.push_str("No findings.\n");
+    } else {
+        out.push_str("| Bug class | Count | Exploitability | Functions |\n|---|---|---|---|\n");
+    }
+    for (class, findings) in &groups {
+        let mut functions = findings.iter().map(|f| f.function.clone()).collect::<Vec<_>>();
+        functions.sort();
+        functions.dedup();
+        out.push_str(&format!(
+            "| {} | {} | {}/100 | {} |\n",
+            class,
+            findings.len(),
+            findings[0].score,
+            functions.join(", ")
+        ));
+    }
+
+    out.push_str("\n## Uncovered critical functions\n\n");
+    let uncovered = uncovered_critical(meta, telemetry);
+    if uncovered.is_empty() {
+        out.push_str("None.\n");
+    }
+    for function in &uncovered {
+        out.push_str(&format!(
+            "- `{}` (`0x{}`): {}\n",
+            function.name,
+            hex::encode(function.selector),
+            function.reason
+        ));
+    }
+
+    out.push_str("\n## Next steps\n\n");
+    let actions = triage_actions(meta, telemetry);
+    if actions.is_empty() {
+        out.push_str("Nothing to do: no findings, and the campaign covered the critical functions.\n");
+    }
+    for (idx, action) in actions.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n", idx + 1, action.describe()));
+    }
+    out
+}
+
+/// Write `TRIAGE.md` to the work directory
+pub fn save_triage(meta: &SummaryMetadata) {
+    let telemetry = CampaignTelemetry::collect(meta);
+    let mut file = File::create(work_path(TRIAGE_FILE)).expect("failed to create triage report");
+    file.write_all(render_triage(meta, &telemetry).as_bytes())
+        .expect("failed to write triage report");
+    info!("Triage report written to {}", work_path(TRIAGE_FILE));
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::summary::classify;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_triage_actions() {
+        let mut meta = SummaryMetadata::default();
+        meta.findings.push(Finding {
+            oracle: "bug".to_string(),
+            class: classify("bug"),
+            output: "[bug] bug() hit at contract 0x0".to_string(),
+            function: "withdraw".to_string(),
+            reproducer: "withdraw()".to_string(),
+            provenance: String::new(),
+            env_dependent: false,
+            score: 70,
+            found_at: 0,
+        });
+        meta.function_coverage.insert("withdraw".to_string(), HashSet::from([1, 2]));
+        let (covered, critical, dead) = ([0x11; 4], [0x22; 4], [0x33; 4]);
+        crate::evm::abi::register_function_name(covered, "withdraw".to_string());
+        let telemetry = CampaignTelemetry {
+            scores: Some(HashMap::from([(covered, 8), (critical, 6), ([0x44; 4], 2)])),
+            dead: vec![(dead, 0.99)],
+            time_budget: 600,
+            elapsed: 600,
+            since_last_coverage: 30,
+            ..Default::default()
+        };
+
+        let uncovered = uncovered_critical(&meta, &telemetry);
+        assert_eq!(uncovered.iter().map(|f| f.selector).collect::<Vec<_>>(), vec![critical, dead]);
+        let actions = triage_actions(&meta, &telemetry);
+        assert!(matches!(&actions[0], Action::Confirm { oracle, count: 1, .. } if oracle == "bug"));
+        assert!(actions.contains(&Action::Seed { selector: critical, name: hex::encode(critical) }));
+        assert!(actions.contains(&Action::StorageLayout));
+        assert!(actions.contains(&Action::TimeBudget { suggested: 1200 }));
+        assert!(!actions.contains(&Action::BugPriors));
+
+        let report = render_triage(&meta, &telemetry);
+        assert!(report.contains("| Assert violation | 1 | 70/100 | withdraw |"));
+        assert!(report.contains("Add a seed for selector `0x22222222`"));
+
+        // no coverage for the last 5 minutes of a 10 minutes campaign, no static pass
+        let stalled = CampaignTelemetry { scores: None, dead: vec![], since_last_coverage: 300, ..telemetry };
+        assert_eq!(
+            triage_actions(&SummaryMetadata::default(), &stalled),
+            vec![Action::BugPriors]
+        );
+    }
+}
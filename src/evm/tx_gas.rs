@@ -0,0 +1,232 @@
+/// Gas limit of the transactions (`--tx-gas`) and gas metering of the host
+///
+/// The interpreter is built without gas measuring, so a transaction never runs out of gas and
+/// a callback-based protocol never sees its callbacks starved. With `--tx-gas` (or the
+/// `oog-griefing` sanitizer), the host meters the gas of the transactions with the static cost
+/// of each opcode ([`opcode_cost`]: the EIP-2929 cold costs, `SSTORE` of an unset slot, the
+/// copies, hashes and logs by size) and the cost of the memory expansion. Each call frame gets a
+/// budget, following the 63/64 rule (EIP-150) and the stipend of the calls sending value, and a
+/// frame exceeding it stops with `OutOfGas`. `GAS` and the gas forwarded by the calls read the
+/// metered gas left.
+///
+/// The gas limit of a transaction is part of the input ([`EVMInput::gas_limit`], the block gas
+/// limit when unset): the mutator sets it with [`mutate_gas_limit`], and the limit is recorded
+/// in the reproducer. The costs are an approximation (no refunds, warm accounts and slots are
+/// charged as cold), meant to find the limits at which the inner calls fail, not to estimate
+/// the gas of a transaction.
+use crate::evm::config::BLOCK_GAS_LIMIT;
+use crate::evm::types::{as_u64, EVMAddress, EVMU256};
+use libafl::prelude::{HasRand, Rand};
+use revm_interpreter::InstructionResult;
+
+/// Whether the host meters gas, on with `--tx-gas` or `--sanitize oog-griefing`
+pub static mut GAS_METERING: bool = false;
+
+/// Probability (in percent) that a mutation changes the gas limit of the input
+pub const GAS_LIMIT_MUTATION_PROBABILITY: u64 = 5;
+
+/// Gas of any transaction before its execution
+pub const TX_BASE_GAS: u64 = 21000;
+/// Gas given to a call sending value on top of the gas forwarded
+pub const CALL_STIPEND: u64 = 2300;
+
+/// Gas limits tried by the mutator besides random ones
+const TYPICAL_GAS_LIMITS: [u64; 5] = [50_000, 100_000, 300_000, 1_000_000, 3_000_000];
+
+#[derive(Clone, Copy, Debug, Default)]
+struct GasFrame {
+    limit: u64,
+    used: u64,
+    /// Memory size paid for, in words
+    memory_words: u64,
+    /// Contract executing the frame
+    address: EVMAddress,
+}
+
+impl GasFrame {
+    fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+/// Frames of the transaction being executed, empty when gas is not metered
+static mut GAS_FRAMES: Vec<GasFrame> = Vec::new();
+
+/// Calls that ran out of gas in the current transaction: (caller, callee)
+pub static mut OOG_CALLS: Vec<(EVMAddress, EVMAddress)> = Vec::new();
+
+/// Gas of the calldata and of the transaction itself
+pub fn intrinsic_gas(calldata: &[u8]) -> u64 {
+    TX_BASE_GAS + calldata.iter().map(|b| if *b == 0 { 4 } else { 16 }).sum::<u64>()
+}
+
+/// Cost of a memory of `words` words
+pub fn memory_cost(words: u64) -> u64 {
+    words.saturating_mul(3).saturating_add(words.saturating_mul(words) / 512)
+}
+
+fn words(size: EVMU256) -> u64 {
+    as_u64(size).saturating_add(31) / 32
+}
+
+/// Cost of the opcode about to be executed, without the memory expansion. `stack` is the stack
+/// of the frame (its top last) and `unset` tells whether a slot of the contract holds zero.
+pub fn opcode_cost(op: u8, stack: &[EVMU256], unset: impl Fn(EVMU256) -> bool) -> u64 {
+    let arg = |idx: usize| stack.len().checked_sub(idx + 1).map(|i| stack[i]).unwrap_or_default();
+    match op {
+        0x00 | 0xf3 | 0xfd | 0xfe => 0,
+        // ADDRESS, ORIGIN, CALLER, CALLVALUE, CALLDATASIZE, CODESIZE, GASPRICE, RETURNDATASIZE,
+        // block info, POP, PC, MSIZE, GAS, PUSH0
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3a | 0x3d | 0x41..=0x46 | 0x48 | 0x50 | 0x58..=0x5a | 0x5f => 2,
+        0x01 | 0x03 | 0x10..=0x1d | 0x35 | 0x51..=0x53 | 0x60..=0x9f => 3,
+        0x02 | 0x04..=0x07 | 0x0b | 0x47 => 5,
+        0x08 | 0x09 | 0x56 => 8,
+        0x57 => 10,
+        0x0a => 10 + 50 * ((256 - arg(1).leading_zeros() as u64 + 7) / 8),
+        0x20 => 30 + 6 * words(arg(1)),
+        0x37 | 0x39 | 0x3e => 3 + 3 * words(arg(2)),
+        0x3c => 2600 + 3 * words(arg(3)),
+        0x31 | 0x3b | 0x3f => 2600,
+        0x40 => 20,
+        0x54 => 2100,
+        0x55 if arg(1) != EVMU256::ZERO && unset(arg(0)) => 22100,
+        0x55 => 5000,
+        0x5b => 1,
+        0xa0..=0xa4 => 375 * (1 + (op - 0xa0) as u64) + 8 * as_u64(arg(1)),
+        0xf0 => 32000,
+        0xf5 => 32000 + 6 * words(arg(2)),
+        0xf1 | 0xf2 if arg(2) != EVMU256::ZERO => 2600 + 9000,
+        0xf1 | 0xf2 | 0xf4 | 0xfa => 2600,
+        0xff => 5000,
+        _ => 3,
+    }
+}
+
+/// Whether the host meters the gas of the transaction being executed
+pub fn metering() -> bool {
+    unsafe { GAS_METERING && !GAS_FRAMES.is_empty() }
+}
+
+/// Start metering a transaction of the given gas limit (the block gas limit if unset)
+pub fn start_tx(gas_limit: Option<u64>, address: EVMAddress, calldata: &[u8]) {
+    unsafe {
+        OOG_CALLS.clear();
+        GAS_FRAMES.clear();
+        if GAS_METERING {
+            let limit = gas_limit.unwrap_or(BLOCK_GAS_LIMIT).saturating_sub(intrinsic_gas(calldata));
+            GAS_FRAMES.push(GasFrame { limit, address, ..Default::default() });
+        }
+    }
+}
+
+/// Stop metering, e.g., for the calls of the fuzzer that are not transactions
+pub fn end_tx() {
+    unsafe { GAS_FRAMES.clear() }
+}
+
+/// Charge the current frame, returns `false` if it ran out of gas
+pub fn charge(cost: u64, memory_size: usize) -> bool {
+    let frame = match unsafe { GAS_FRAMES.last_mut() } {
+        Some(frame) => frame,
+        None => return true,
+    };
+    let memory_words = (memory_size as u64 + 31) / 32;
+    if memory_words > frame.memory_words {
+        frame.used = frame.used.saturating_add(memory_cost(memory_words) - memory_cost(frame.memory_words));
+        frame.memory_words = memory_words;
+    }
+    frame.used = frame.used.saturating_add(cost);
+    frame.used <= frame.limit
+}
+
+/// Gas left in the current frame
+pub fn gas_left() -> u64 {
+    unsafe { GAS_FRAMES.last() }.map_or(u64::MAX, |frame| frame.remaining())
+}
+
+/// Enter a call forwarding `gas_limit` (the stipend included when it sends value): the callee
+/// gets 63/64 of the gas left at most
+pub fn enter_gas_frame(gas_limit: u64, sends_value: bool, address: EVMAddress) {
+    if metering() {
+        let stipend = if sends_value { CALL_STIPEND } else { 0 };
+        let available = gas_left();
+        let limit = gas_limit.saturating_sub(stipend).min(available - available / 64) + stipend;
+        unsafe { GAS_FRAMES.push(GasFrame { limit, address, ..Default::default() }) };
+    }
+}
+
+/// Leave a call, charging its caller with the gas it used
+pub fn exit_gas_frame(ret: InstructionResult) {
+    if !metering() || unsafe { GAS_FRAMES.len() } < 2 {
+        return;
+    }
+    unsafe {
+        let frame = GAS_FRAMES.pop().unwrap();
+        let caller = GAS_FRAMES.last_mut().unwrap();
+        caller.used = caller.used.saturating_add(frame.used.min(frame.limit));
+        if ret == InstructionResult::OutOfGas {
+            OOG_CALLS.push((caller.address, frame.address));
+        }
+    }
+}
+
+/// No limit (the block gas limit), a random limit, a typical one or half of the current one
+pub fn mutate_gas_limit<S: HasRand>(gas_limit: Option<u64>, state: &mut S) -> Option<u64> {
+    let block_limit = unsafe { BLOCK_GAS_LIMIT };
+    match state.rand_mut().below(4) {
+        0 => None,
+        1 => Some(TX_BASE_GAS + state.rand_mut().below(block_limit.saturating_sub(TX_BASE_GAS).max(1))),
+        2 => Some(TYPICAL_GAS_LIMITS[state.rand_mut().below(TYPICAL_GAS_LIMITS.len() as u64) as usize]),
+        _ => Some((gas_limit.unwrap_or(block_limit) / 2).max(TX_BASE_GAS)),
+    }
+}
+
+/// Prefix of the pretty transaction of an input with a gas limit
+pub fn gas_limit_prefix(gas_limit: Option<u64>) -> String {
+    match gas_limit {
+        Some(limit) => format!("(gas {}) ", limit),
+        None => String::new(),
+    }
+}
+
+/// Gas limit of a pretty transaction, see [`gas_limit_prefix`]
+pub fn parse_gas_limit_prefix(pretty_txn: &str) -> Option<u64> {
+    pretty_txn.split("(gas ").nth(1)?.split(')').next()?.parse().ok()
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tx_gas() {
+        let stack = vec![EVMU256::from(1), EVMU256::from(7)];
+        // SSTORE 1 to the slot 7, unset then set
+        assert_eq!(opcode_cost(0x55, &stack, |_| true), 22100);
+        assert_eq!(opcode_cost(0x55, &stack, |_| false), 5000);
+        assert_eq!(intrinsic_gas(&[0xa9, 0, 0, 1]), 21000 + 16 * 2 + 4 * 2);
+        assert_eq!(memory_cost(32), 96 + 2);
+
+        let (outer, inner) = (EVMAddress::from_slice(&[0x11; 20]), EVMAddress::from_slice(&[0x22; 20]));
+        unsafe {
+            GAS_METERING = true;
+        }
+        start_tx(Some(21000 + 16 + 64_000), outer, &[1]);
+        assert!(charge(22100, 0));
+        // the callee gets 63/64 of the gas left at most
+        enter_gas_frame(u64::MAX, false, inner);
+        assert_eq!(gas_left(), 64_000 - 22100 - (64_000 - 22100) / 64);
+        assert!(!charge(60_000, 0));
+        exit_gas_frame(InstructionResult::OutOfGas);
+        assert_eq!(unsafe { OOG_CALLS.clone() }, vec![(outer, inner)]);
+        // the failed call used the gas it was given
+        assert!(gas_left() < 2000);
+        end_tx();
+        assert!(!metering());
+        unsafe {
+            GAS_METERING = false;
+        }
+
+        assert_eq!(parse_gas_limit_prefix(&format!("{}ABI with None ETH", gas_limit_prefix(Some(50000)))), Some(50000));
+        assert_eq!(parse_gas_limit_prefix("(prices 1) ABI with None ETH"), None);
+    }
+}
@@ -13,6 +13,7 @@ use primitive_types::{H160, H256};
 use revm_primitives::{B160, Bytecode, U256};
 use libafl::prelude::Rand;
 use revm_primitives::ruint::aliases::U512;
+use std::collections::HashMap;
 
 pub type EVMAddress = B160;
 pub type EVMU256 = U256;
@@ -72,6 +73,32 @@ pub fn fixed_address(s: &str) -> EVMAddress {
     address
 }
 
+/// Names of well-known callers (e.g., `owner`, `attacker1`), used when printing transactions
+static mut CALLER_ALIASES: Option<HashMap<EVMAddress, String>> = None;
+
+pub fn register_caller_alias(address: EVMAddress, alias: &str) {
+    unsafe {
+        CALLER_ALIASES
+            .get_or_insert_with(Default::default)
+            .insert(address, alias.to_string());
+    }
+}
+
+pub fn caller_alias(address: &EVMAddress) -> Option<String> {
+    unsafe { CALLER_ALIASES.as_ref()?.get(address).cloned() }
+}
+
+/// Address of the caller with the alias
+pub fn alias_address(alias: &str) -> Option<EVMAddress> {
+    unsafe {
+        CALLER_ALIASES
+            .as_ref()?
+            .iter()
+            .find(|(_, name)| name.as_str() == alias)
+            .map(|(address, _)| *address)
+    }
+}
+
 /// Check is EVMU256 is zero
 pub fn is_zero(v: EVMU256) -> bool {
     v == EVMU256::ZERO
@@ -289,6 +289,7 @@ pub fn generate_uniswap_router_call(
                     is_address: false,
                     dont_mutate: false,
                     is_bytes: false,
+                    provenance: Default::default(),
                 })),
                 BoxedABI::new(Box::new(AArray {
                     data: path
@@ -299,6 +300,7 @@ pub fn generate_uniswap_router_call(
                                 is_address: true,
                                 dont_mutate: false,
                                 is_bytes: false,
+                                provenance: Default::default(),
                             }))
                         })
                         .collect(),
@@ -309,12 +311,14 @@ pub fn generate_uniswap_router_call(
                     is_address: true,
                     dont_mutate: false,
                     is_bytes: false,
+                    provenance: Default::default(),
                 })),
                 BoxedABI::new(Box::new(A256 {
                     data: vec![0xff; 32],
                     is_address: false,
                     dont_mutate: false,
                     is_bytes: false,
+                    provenance: Default::default(),
                 })),
             ],
             dynamic_size: false,
@@ -0,0 +1,522 @@
+/// Value ranges of the arguments, from revert strings and natspec (`--value-hints`)
+///
+/// Guards often explain themselves: `require(amount <= MAX_DEPOSIT, "amount must be <= 1e24")`,
+/// `/// @param fee The fee, at most 10000`. The constraints stated by the revert strings returned
+/// during the campaign, and by the natspec of the functions when the sources are available
+/// (Foundry / Hardhat projects), are turned into a range of values of the parameter they name,
+/// or of the only integer parameter of the function when they name none. The mutation of a
+/// function with ranges then sets one of its constrained arguments to a bound of its range, a
+/// value within it, or (one mutation out of [`OUTSIDE_RATE`]) a value just outside it.
+use crate::evm::abi::{format_uint, function_args, function_sig_name, AArray, BoxedABI, A256};
+use crate::evm::contract_utils::set_hash;
+use crate::evm::types::EVMU256;
+use libafl::mutators::MutationResult;
+use libafl::prelude::{HasRand, Rand};
+use std::collections::{HashMap, HashSet};
+use std::ops::DerefMut;
+use tracing::info;
+
+/// Percentage of the mutations of a function with ranges setting a constrained argument
+pub const HINT_MUTATION_PROB: u64 = 25;
+
+/// One hinted value out of this many is just outside the range
+pub const OUTSIDE_RATE: u64 = 4;
+
+/// Selector of `Error(string)`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+pub static mut VALUE_HINTS: bool = false;
+
+/// Range of each constrained parameter (by position) of each function
+static mut HINTS: Option<HashMap<[u8; 4], HashMap<usize, ValueRange>>> = None;
+
+/// Revert strings already parsed, by function
+static mut SEEN_REASONS: Option<HashSet<([u8; 4], String)>> = None;
+
+/// Inclusive range of values of a parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueRange {
+    pub min: EVMU256,
+    pub max: EVMU256,
+}
+
+impl ValueRange {
+    fn intersect(&self, other: &ValueRange) -> Option<ValueRange> {
+        let range = ValueRange { min: self.min.max(other.min), max: self.max.min(other.max) };
+        (range.min <= range.max).then_some(range)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl Comparison {
+    fn negate(self) -> Self {
+        match self {
+            Comparison::Lt => Comparison::Ge,
+            Comparison::Le => Comparison::Gt,
+            Comparison::Gt => Comparison::Le,
+            Comparison::Ge => Comparison::Lt,
+            Comparison::Eq => Comparison::Eq,
+        }
+    }
+
+    fn range(self, bound: EVMU256) -> Option<ValueRange> {
+        let (min, max) = match self {
+            Comparison::Lt if bound == EVMU256::ZERO => return None,
+            Comparison::Lt => (EVMU256::ZERO, bound - EVMU256::from(1)),
+            Comparison::Le => (EVMU256::ZERO, bound),
+            Comparison::Gt if bound == EVMU256::MAX => return None,
+            Comparison::Gt => (bound + EVMU256::from(1), EVMU256::MAX),
+            Comparison::Ge => (bound, EVMU256::MAX),
+            Comparison::Eq => (bound, bound),
+        };
+        Some(ValueRange { min, max })
+    }
+}
+
+/// Phrases stating a comparison, and whether a preceding "not" negates them. A revert string
+/// describes either the requirement ("must be at most") or its violation ("exceeds"), the
+/// violations are listed with the comparison they violate.
+const PHRASES: &[(&str, Comparison, bool)] = &[
+    ("<=", Comparison::Le, true),
+    ("=<", Comparison::Le, true),
+    ("at most", Comparison::Le, true),
+    ("up to", Comparison::Le, true),
+    ("max", Comparison::Le, false),
+    ("maximum", Comparison::Le, false),
+    ("exceed", Comparison::Le, false),
+    ("exceeds", Comparison::Le, false),
+    ("<", Comparison::Lt, true),
+    ("less than", Comparison::Lt, true),
+    ("lower than", Comparison::Lt, true),
+    ("smaller than", Comparison::Lt, true),
+    ("below", Comparison::Lt, true),
+    ("under", Comparison::Lt, true),
+    (">=", Comparison::Ge, true),
+    ("=>", Comparison::Ge, true),
+    ("at least", Comparison::Ge, true),
+    ("min", Comparison::Ge, false),
+    ("minimum", Comparison::Ge, false),
+    (">", Comparison::Gt, true),
+    ("greater than", Comparison::Gt, true),
+    ("more than", Comparison::Gt, true),
+    ("higher than", Comparison::Gt, true),
+    ("larger than", Comparison::Gt, true),
+    ("above", Comparison::Gt, true),
+    ("==", Comparison::Eq, false),
+    ("equal to", Comparison::Eq, true),
+];
+
+/// Integer literal: decimal, hex, `1e24`, `1.5e18`, `10**18`, with underscores and an optional
+/// ether unit
+fn parse_number(words: &[String]) -> Option<(EVMU256, usize)> {
+    let word = words.first()?.replace('_', "");
+    let mut used = 1;
+    let mut value = if let Some(hex) = word.strip_prefix("0x") {
+        EVMU256::from_str_radix(hex, 16).ok()?
+    } else if let Some((base, exp)) = word.split_once("**") {
+        EVMU256::from_str_radix(base, 10).ok()?.checked_pow(EVMU256::from_str_radix(exp, 10).ok()?)?
+    } else if let Some((mantissa, exp)) = word.split_once('e') {
+        let exp: usize = exp.parse().ok()?;
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if frac.len() > exp || int.is_empty() {
+            return None;
+        }
+        let digits = format!("{}{}{}", int, frac, "0".repeat(exp - frac.len()));
+        EVMU256::from_str_radix(&digits, 10).ok()?
+    } else if word.chars().all(|c| c.is_ascii_digit()) {
+        EVMU256::from_str_radix(&word, 10).ok()?
+    } else {
+        return None;
+    };
+    let unit = match words.get(1).map(|w| w.as_str()) {
+        Some("ether") => Some(18),
+        Some("gwei") => Some(9),
+        Some("wei") => Some(0),
+        _ => None,
+    };
+    if let Some(decimals) = unit {
+        value = value.checked_mul(EVMU256::from(10).pow(EVMU256::from(decimals)))?;
+        used += 1;
+    }
+    Some((value, used))
+}
+
+/// Words of a constraint, operators split from the names and numbers
+fn tokenize(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut chars = text.to_lowercase().chars().collect::<Vec<_>>().into_iter().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' || (c == '*' && chars.peek() == Some(&'*')) {
+            word.push(c);
+            if c == '*' {
+                word.push(chars.next().unwrap());
+            }
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(word.trim_end_matches('.').to_string());
+            word.clear();
+        }
+        if "<>=!".contains(c) {
+            let mut op = c.to_string();
+            if chars.peek().map_or(false, |next| "<>=".contains(*next)) {
+                op.push(chars.next().unwrap());
+            }
+            words.push(op);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word.trim_end_matches('.').to_string());
+    }
+    words
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_matches('_').to_lowercase()
+}
+
+/// Ranges stated by a text, by parameter position. `args` are the names and types of the
+/// parameters, `param` the parameter the text documents, if any.
+pub fn parse_constraints(text: &str, args: &[(String, String)], param: Option<&str>) -> Vec<(usize, ValueRange)> {
+    let integer_args = args
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, ty))| ty.starts_with("uint"))
+        .map(|(idx, (name, _))| (normalize_name(name), idx))
+        .collect::<Vec<_>>();
+    if integer_args.is_empty() {
+        return vec![];
+    }
+    let lookup = |name: &str| {
+        let name = normalize_name(name);
+        integer_args.iter().find(|(arg, _)| !arg.is_empty() && *arg == name).map(|(_, idx)| *idx)
+    };
+    let mut current = param.and_then(lookup);
+    if current.is_none() && param.is_none() && integer_args.len() == 1 {
+        current = Some(integer_args[0].1);
+    }
+
+    let mut constraints = vec![];
+    let mut comparison: Option<(Comparison, usize)> = None;
+    let words = tokenize(text);
+    let mut i = 0;
+    while i < words.len() {
+        if let Some(idx) = lookup(&words[i]) {
+            current = Some(idx);
+            comparison = None;
+            i += 1;
+            continue;
+        }
+        // "between 1 and 100"
+        if words[i] == "between" {
+            if let Some((low, used)) = parse_number(&words[i + 1..]) {
+                if words.get(i + 1 + used).map(|w| w.as_str()) == Some("and") {
+                    if let Some((high, more)) = parse_number(&words[i + 2 + used..]) {
+                        if let (Some(idx), true) = (current, low <= high) {
+                            constraints.push((idx, ValueRange { min: low, max: high }));
+                        }
+                        i += 2 + used + more;
+                        continue;
+                    }
+                }
+            }
+        }
+        // the longest phrase starting here
+        let phrase = PHRASES
+            .iter()
+            .filter_map(|(phrase, cmp, negatable)| {
+                let phrase_words = tokenize(phrase);
+                (words.len() >= i + phrase_words.len() && words[i..i + phrase_words.len()] == phrase_words[..])
+                    .then(|| (phrase_words.len(), *cmp, *negatable))
+            })
+            .max_by_key(|(len, _, _)| *len);
+        if let Some((len, cmp, negatable)) = phrase {
+            let negated = negatable && i > 0 && (words[i - 1] == "not" || (i > 1 && words[i - 2] == "not"));
+            comparison = Some((if negated { cmp.negate() } else { cmp }, i + len));
+            i += len;
+            continue;
+        }
+        if let Some((bound, used)) = parse_number(&words[i..]) {
+            if let (Some(idx), Some((cmp, _))) = (current, comparison.take()) {
+                if let Some(range) = cmp.range(bound) {
+                    constraints.push((idx, range));
+                }
+            }
+            i += used;
+            continue;
+        }
+        i += 1;
+    }
+    constraints
+}
+
+/// Restrict the range of a parameter of the function
+pub fn add_hint(function: [u8; 4], param: usize, range: ValueRange) {
+    let ranges = unsafe { HINTS.get_or_insert_with(HashMap::new) }.entry(function).or_default();
+    let range = match ranges.get(&param) {
+        // contradicting statements, the latest one wins
+        Some(known) => known.intersect(&range).unwrap_or(range),
+        None => range,
+    };
+    if ranges.insert(param, range) != Some(range) {
+        info!(
+            "[hints] argument {} of {} in [{}, {}]",
+            param,
+            function_sig_name(&function),
+            format_uint(range.min),
+            format_uint(range.max)
+        );
+    }
+}
+
+pub fn hints(function: &[u8; 4]) -> Option<&'static HashMap<usize, ValueRange>> {
+    unsafe { HINTS.as_ref()?.get(function) }
+}
+
+/// Reason of an `Error(string)` revert
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.get(..4)? != ERROR_SELECTOR {
+        return None;
+    }
+    let len = EVMU256::try_from_be_slice(output.get(36..68)?)?;
+    if len > EVMU256::from(output.len()) {
+        return None;
+    }
+    let len = len.as_limbs()[0] as usize;
+    String::from_utf8(output.get(68..68 + len)?.to_vec()).ok()
+}
+
+/// Called on each reverted transaction when [`VALUE_HINTS`] is on
+pub fn record_revert_reason(calldata: &[u8], output: &[u8]) {
+    let function: [u8; 4] = match calldata.get(..4).and_then(|s| s.try_into().ok()) {
+        Some(function) => function,
+        None => return,
+    };
+    let reason = match decode_revert_reason(output) {
+        Some(reason) => reason,
+        None => return,
+    };
+    if !unsafe { SEEN_REASONS.get_or_insert_with(HashSet::new) }.insert((function, reason.clone())) {
+        return;
+    }
+    let args = function_args(&function).unwrap_or_default();
+    for (param, range) in parse_constraints(&reason, &args, None) {
+        add_hint(function, param, range);
+    }
+}
+
+/// Canonical type of a parameter declared in a source, `None` for a struct or an enum
+fn canonical_type(ty: &str) -> Option<String> {
+    let (base, dims) = ty.split_at(ty.find('[').unwrap_or(ty.len()));
+    let base = match base {
+        "uint" => "uint256",
+        "int" => "int256",
+        "byte" => "bytes1",
+        b if ["uint", "int", "bytes", "address", "bool", "string"].iter().any(|t| b.starts_with(t)) => b,
+        _ => return None,
+    };
+    Some(format!("{}{}", base, dims))
+}
+
+/// Functions declared in a source with the natspec preceding them: selector, parameters and
+/// natspec lines
+fn documented_functions(source: &str) -> Vec<([u8; 4], Vec<(String, String)>, Vec<String>)> {
+    let mut functions = vec![];
+    let mut doc: Vec<String> = vec![];
+    let mut in_block = false;
+    let lines = source.lines().collect::<Vec<_>>();
+    for (n, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if in_block || line.starts_with("/**") {
+            in_block = !line.ends_with("*/");
+            let text = line.trim_start_matches("/**").trim_end_matches("*/").trim_start_matches('*').trim();
+            if !text.is_empty() {
+                doc.push(text.to_string());
+            }
+            continue;
+        }
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.trim().to_string());
+            continue;
+        }
+        if line.starts_with("function ") {
+            // the parameters may span several lines
+            let declaration = lines[n..].iter().take(16).map(|l| l.trim()).collect::<Vec<_>>().join(" ");
+            let name = declaration["function ".len()..].split('(').next().unwrap_or_default().trim().to_string();
+            let params = declaration
+                .split_once('(')
+                .and_then(|(_, params)| params.split_once(')'))
+                .map(|(params, _)| params.trim())
+                .unwrap_or_default();
+            let args = params
+                .split(',')
+                .filter(|param| !param.trim().is_empty())
+                .map(|param| {
+                    let words = param.split_whitespace().collect::<Vec<_>>();
+                    let ty = canonical_type(words.first().copied().unwrap_or_default());
+                    let name = match words.last() {
+                        Some(name) if words.len() > 1 && !["memory", "calldata", "storage", "payable"].contains(name) => {
+                            name.to_string()
+                        }
+                        _ => String::new(),
+                    };
+                    ty.map(|ty| (name, ty))
+                })
+                .collect::<Option<Vec<_>>>();
+            if let (Some(args), false, false) = (args, doc.is_empty(), name.is_empty()) {
+                let mut function = [0u8; 4];
+                let types = args.iter().map(|(_, ty)| ty.as_str()).collect::<Vec<_>>().join(",");
+                set_hash(&format!("{}({})", name, types), &mut function);
+                functions.push((function, args, doc.clone()));
+            }
+        }
+        doc.clear();
+    }
+    functions
+}
+
+/// Ranges stated by the natspec of the functions of a source
+pub fn natspec_hints(source: &str) -> Vec<([u8; 4], usize, ValueRange)> {
+    let mut hints = vec![];
+    for (function, args, doc) in documented_functions(source) {
+        for line in doc {
+            let constraints = match line.strip_prefix("@param ") {
+                Some(param) => {
+                    let (name, text) = param.split_once(char::is_whitespace).unwrap_or((param, ""));
+                    parse_constraints(text, &args, Some(name))
+                }
+                None => parse_constraints(&line, &args, None),
+            };
+            hints.extend(constraints.into_iter().map(|(param, range)| (function, param, range)));
+        }
+    }
+    hints
+}
+
+/// Register the ranges stated by the natspec of a source
+pub fn scan_natspec(source: &str) {
+    for (function, param, range) in natspec_hints(source) {
+        add_hint(function, param, range);
+    }
+}
+
+fn random_u256<S: HasRand>(state: &mut S) -> EVMU256 {
+    let rand = state.rand_mut();
+    EVMU256::from_limbs([rand.next(), rand.next(), rand.next(), rand.next()])
+}
+
+/// Value of a range: a bound, a value within it, or one just outside
+pub fn hinted_value<S: HasRand>(state: &mut S, range: &ValueRange, type_max: EVMU256) -> EVMU256 {
+    let max = range.max.min(type_max);
+    let min = range.min.min(max);
+    if state.rand_mut().below(OUTSIDE_RATE) == 0 {
+        let below = (min > EVMU256::ZERO).then(|| min - EVMU256::from(1));
+        let above = (max < type_max).then(|| max + EVMU256::from(1));
+        match (below, above) {
+            (Some(below), Some(above)) => return if state.rand_mut().below(2) == 0 { below } else { above },
+            (Some(value), None) | (None, Some(value)) => return value,
+            (None, None) => {}
+        }
+    }
+    match state.rand_mut().below(3) {
+        0 => min,
+        1 => max,
+        _ if max - min == EVMU256::MAX => random_u256(state),
+        _ => min + random_u256(state) % (max - min + EVMU256::from(1)),
+    }
+}
+
+/// Set a constrained argument of the function to a hinted value, `None` to leave the args to
+/// the other mutations
+pub fn mutate_hinted_arg<S: HasRand>(state: &mut S, abi: &mut BoxedABI) -> Option<MutationResult> {
+    if !unsafe { VALUE_HINTS } {
+        return None;
+    }
+    let ranges = hints(&abi.function)?;
+    if ranges.is_empty() || state.rand_mut().below(100) >= HINT_MUTATION_PROB {
+        return None;
+    }
+    let (param, range) = ranges.iter().nth(state.rand_mut().below(ranges.len() as u64) as usize)?;
+    let args = abi.b.deref_mut().as_any().downcast_mut::<AArray>()?;
+    let arg = args.data.get_mut(*param)?.b.deref_mut().as_any().downcast_mut::<A256>()?;
+    if arg.is_address || arg.is_bytes || arg.dont_mutate || arg.data.len() > 32 || arg.data.is_empty() {
+        return None;
+    }
+    let bits = arg.data.len() * 8;
+    let type_max = if bits == 256 { EVMU256::MAX } else { (EVMU256::from(1) << bits) - EVMU256::from(1) };
+    let value: [u8; 32] = hinted_value(state, range, type_max).to_be_bytes();
+    arg.data = value[32 - arg.data.len()..].to_vec();
+    Some(MutationResult::Mutated)
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::types::EVMFuzzState;
+    use crate::state::FuzzState;
+
+    #[test]
+    fn test_parse_constraints() {
+        let args = vec![
+            ("_amount".to_string(), "uint256".to_string()),
+            ("to".to_string(), "address".to_string()),
+            ("fee".to_string(), "uint16".to_string()),
+        ];
+        let e24 = EVMU256::from(10).pow(EVMU256::from(24));
+        assert_eq!(
+            parse_constraints("amount must be <= 1e24", &args, None),
+            vec![(0, ValueRange { min: EVMU256::ZERO, max: e24 })]
+        );
+        assert_eq!(
+            parse_constraints("Vault: amount exceeds max 1_000 ether", &args, None),
+            vec![(0, ValueRange { min: EVMU256::ZERO, max: EVMU256::from(10).pow(EVMU256::from(21)) })]
+        );
+        assert_eq!(
+            parse_constraints("amount must not be less than 5 and fee below 10000", &args, None),
+            vec![
+                (0, ValueRange { min: EVMU256::from(5), max: EVMU256::MAX }),
+                (2, ValueRange { min: EVMU256::ZERO, max: EVMU256::from(9999) }),
+            ]
+        );
+        assert_eq!(
+            parse_constraints("The fee, between 1 and 0x64", &args, Some("fee")),
+            vec![(2, ValueRange { min: EVMU256::from(1), max: EVMU256::from(100) })]
+        );
+        // two integer parameters, the constraint names neither
+        assert!(parse_constraints("must be > 0", &args, None).is_empty());
+        assert!(parse_constraints("insufficient balance", &args, None).is_empty());
+
+        let source = "
+            /// @notice Deposit into the vault
+            /// @param amount The amount, at most 10**18
+            function deposit(uint amount, address payable to) external {}
+        ";
+        let mut function = [0u8; 4];
+        set_hash("deposit(uint256,address)", &mut function);
+        assert_eq!(
+            natspec_hints(source),
+            vec![(function, 0, ValueRange { min: EVMU256::ZERO, max: EVMU256::from(10).pow(EVMU256::from(18)) })]
+        );
+
+        let mut output = ERROR_SELECTOR.to_vec();
+        output.extend_from_slice(&EVMU256::from(32).to_be_bytes::<32>());
+        output.extend_from_slice(&EVMU256::from(5).to_be_bytes::<32>());
+        output.extend_from_slice(b"hello");
+        assert_eq!(decode_revert_reason(&output), Some("hello".to_string()));
+
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let range = ValueRange { min: EVMU256::from(10), max: EVMU256::from(20) };
+        for _ in 0..100 {
+            let value = hinted_value(&mut state, &range, EVMU256::from(255));
+            assert!(value >= EVMU256::from(9) && value <= EVMU256::from(21));
+        }
+    }
+}
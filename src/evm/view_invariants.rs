@@ -0,0 +1,329 @@
+/// Consistency invariants between view functions of a target
+///
+/// `--view-invariants invariants.json` declares relations that the results of view functions
+/// must keep under any sequence of transactions, e.g., for a vault:
+/// ```json
+/// [{"contract": "Vault", "invariant": "totalAssets() >= totalSupply() * minPrice", "constants": {"minPrice": "1"}}]
+/// ```
+/// An invariant compares two arithmetic expressions (`+ - * /`, parentheses) over calls of view
+/// functions without arguments (`name()`), integers (`1000`, `0x3e8`, `1e18`) and the named
+/// constants. It is checked by [`crate::evm::oracles::view_consistency::ViewConsistencyOracle`]
+/// after each transaction.
+use crate::evm::token_minting::keccak;
+use crate::evm::types::{EVMAddress, EVMU256};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViewExpr {
+    Const(EVMU256),
+    /// Call of a view function without arguments
+    Call([u8; 4], String),
+    Add(Box<ViewExpr>, Box<ViewExpr>),
+    Sub(Box<ViewExpr>, Box<ViewExpr>),
+    Mul(Box<ViewExpr>, Box<ViewExpr>),
+    Div(Box<ViewExpr>, Box<ViewExpr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Eq,
+    Ne,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ViewInvariant {
+    /// Contract name or address of the target
+    pub contract: String,
+    /// The invariant as declared, for the reports
+    pub source: String,
+    pub lhs: ViewExpr,
+    pub cmp: Comparison,
+    pub rhs: ViewExpr,
+}
+
+#[derive(Deserialize)]
+struct ViewInvariantConfig {
+    contract: String,
+    invariant: String,
+    #[serde(default)]
+    constants: HashMap<String, String>,
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+    keccak(signature.as_bytes()).to_be_bytes::<32>()[..4].try_into().unwrap()
+}
+
+pub(crate) fn parse_number(s: &str) -> Result<EVMU256, String> {
+    let invalid = || format!("invalid number {}", s);
+    if let Some(hex) = s.strip_prefix("0x") {
+        return EVMU256::from_str_radix(hex, 16).map_err(|_| invalid());
+    }
+    match s.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let mantissa = EVMU256::from_str(mantissa).map_err(|_| invalid())?;
+            let exponent: usize = exponent.parse().map_err(|_| invalid())?;
+            mantissa
+                .checked_mul(EVMU256::from(10).pow(EVMU256::from(exponent)))
+                .ok_or_else(invalid)
+        }
+        None => EVMU256::from_str(s).map_err(|_| invalid()),
+    }
+}
+
+/// Recursive descent parser of the invariants
+struct Parser<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    constants: &'a HashMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn tokenize(s: &str) -> Vec<String> {
+        let mut tokens = vec![];
+        let chars = s.chars().collect::<Vec<_>>();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_alphanumeric() || c == '_' || c == '$' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                    i += 1;
+                }
+                tokens.push(chars[start..i].iter().collect());
+            } else if i + 1 < chars.len() && matches!((c, chars[i + 1]), ('>' | '<' | '=' | '!', '=')) {
+                tokens.push(chars[i..i + 2].iter().collect());
+                i += 2;
+            } else {
+                tokens.push(c.to_string());
+                i += 1;
+            }
+        }
+        tokens
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of invariant")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected {}, found {}", expected, token)),
+        }
+    }
+
+    fn atom(&mut self) -> Result<ViewExpr, String> {
+        let token = self.next()?;
+        if token == "(" {
+            let expr = self.sum()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(ViewExpr::Const(parse_number(&token)?));
+        }
+        if self.peek() == Some("(") {
+            self.expect("(")?;
+            self.expect(")")?;
+            return Ok(ViewExpr::Call(selector(&format!("{}()", token)), token));
+        }
+        match self.constants.get(&token) {
+            Some(value) => Ok(ViewExpr::Const(parse_number(value)?)),
+            None => Err(format!("unknown constant {}", token)),
+        }
+    }
+
+    fn product(&mut self) -> Result<ViewExpr, String> {
+        let mut expr = self.atom()?;
+        while let Some(op @ ("*" | "/")) = self.peek() {
+            let mul = op == "*";
+            self.pos += 1;
+            let rhs = Box::new(self.atom()?);
+            expr = if mul {
+                ViewExpr::Mul(Box::new(expr), rhs)
+            } else {
+                ViewExpr::Div(Box::new(expr), rhs)
+            };
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<ViewExpr, String> {
+        let mut expr = self.product()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let add = op == "+";
+            self.pos += 1;
+            let rhs = Box::new(self.product()?);
+            expr = if add {
+                ViewExpr::Add(Box::new(expr), rhs)
+            } else {
+                ViewExpr::Sub(Box::new(expr), rhs)
+            };
+        }
+        Ok(expr)
+    }
+}
+
+/// Parse an invariant of the target `contract`
+pub fn parse_view_invariant(
+    contract: &str,
+    invariant: &str,
+    constants: &HashMap<String, String>,
+) -> Result<ViewInvariant, String> {
+    let mut parser = Parser {
+        tokens: Parser::tokenize(invariant),
+        pos: 0,
+        constants,
+    };
+    let lhs = parser.sum()?;
+    let cmp = match parser.next()?.as_str() {
+        ">=" => Comparison::Ge,
+        ">" => Comparison::Gt,
+        "<=" => Comparison::Le,
+        "<" => Comparison::Lt,
+        "==" => Comparison::Eq,
+        "!=" => Comparison::Ne,
+        token => return Err(format!("expected a comparison, found {}", token)),
+    };
+    let rhs = parser.sum()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {} after the invariant", token));
+    }
+    Ok(ViewInvariant {
+        contract: contract.to_string(),
+        source: invariant.to_string(),
+        lhs,
+        cmp,
+        rhs,
+    })
+}
+
+pub fn load_view_invariants(path: &str) -> Vec<ViewInvariant> {
+    let mut data = String::new();
+    File::open(path)
+        .expect("failed to open view invariants file")
+        .read_to_string(&mut data)
+        .expect("failed to read view invariants file");
+    let configs: Vec<ViewInvariantConfig> = serde_json::from_str(&data).expect("failed to parse view invariants file");
+    configs
+        .iter()
+        .map(|c| {
+            parse_view_invariant(&c.contract, &c.invariant, &c.constants)
+                .unwrap_or_else(|e| panic!("invalid view invariant {}: {}", c.invariant, e))
+        })
+        .collect()
+}
+
+impl ViewExpr {
+    /// View functions called by the expression
+    pub fn calls(&self, calls: &mut Vec<[u8; 4]>) {
+        match self {
+            ViewExpr::Const(_) => {}
+            ViewExpr::Call(selector, _) => {
+                if !calls.contains(selector) {
+                    calls.push(*selector);
+                }
+            }
+            ViewExpr::Add(a, b) | ViewExpr::Sub(a, b) | ViewExpr::Mul(a, b) | ViewExpr::Div(a, b) => {
+                a.calls(calls);
+                b.calls(calls);
+            }
+        }
+    }
+
+    /// Name of the view function with the selector, if called by the expression
+    pub fn name_of(&self, selector: &[u8; 4]) -> Option<String> {
+        match self {
+            ViewExpr::Const(_) => None,
+            ViewExpr::Call(s, name) => (s == selector).then(|| name.clone()),
+            ViewExpr::Add(a, b) | ViewExpr::Sub(a, b) | ViewExpr::Mul(a, b) | ViewExpr::Div(a, b) => {
+                a.name_of(selector).or_else(|| b.name_of(selector))
+            }
+        }
+    }
+
+    /// Value of the expression given the results of the view functions, `None` if a call failed
+    /// or the arithmetic overflows
+    pub fn eval(&self, results: &HashMap<[u8; 4], EVMU256>) -> Option<EVMU256> {
+        match self {
+            ViewExpr::Const(v) => Some(*v),
+            ViewExpr::Call(selector, _) => results.get(selector).cloned(),
+            ViewExpr::Add(a, b) => a.eval(results)?.checked_add(b.eval(results)?),
+            ViewExpr::Sub(a, b) => a.eval(results)?.checked_sub(b.eval(results)?),
+            ViewExpr::Mul(a, b) => a.eval(results)?.checked_mul(b.eval(results)?),
+            ViewExpr::Div(a, b) => a.eval(results)?.checked_div(b.eval(results)?),
+        }
+    }
+}
+
+impl ViewInvariant {
+    pub fn calls(&self) -> Vec<[u8; 4]> {
+        let mut calls = vec![];
+        self.lhs.calls(&mut calls);
+        self.rhs.calls(&mut calls);
+        calls
+    }
+
+    pub fn name_of(&self, selector: &[u8; 4]) -> Option<String> {
+        self.lhs.name_of(selector).or_else(|| self.rhs.name_of(selector))
+    }
+
+    /// Whether the invariant holds, `None` if it cannot be evaluated
+    pub fn holds(&self, results: &HashMap<[u8; 4], EVMU256>) -> Option<bool> {
+        let (lhs, rhs) = (self.lhs.eval(results)?, self.rhs.eval(results)?);
+        Some(match self.cmp {
+            Comparison::Ge => lhs >= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+        })
+    }
+
+    /// Whether the target is the contract `name` deployed at `address`
+    pub fn matches(&self, name: &str, address: &EVMAddress) -> bool {
+        self.contract == name || self.contract.to_lowercase() == format!("{:?}", address)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_invariant() {
+        let constants = HashMap::from([("minPrice".to_string(), "2".to_string())]);
+        let invariant = parse_view_invariant("Vault", "totalAssets() >= totalSupply() * minPrice", &constants).unwrap();
+        let (assets, supply) = (selector("totalAssets()"), selector("totalSupply()"));
+        assert_eq!(assets, [0x01, 0xe1, 0xd1, 0x14]);
+        assert_eq!(invariant.calls(), vec![assets, supply]);
+
+        let mut results = HashMap::from([(assets, EVMU256::from(200)), (supply, EVMU256::from(100))]);
+        assert_eq!(invariant.holds(&results), Some(true));
+        results.insert(supply, EVMU256::from(101));
+        assert_eq!(invariant.holds(&results), Some(false));
+        results.remove(&supply);
+        assert_eq!(invariant.holds(&results), None);
+
+        let invariant = parse_view_invariant("Vault", "(a() + 1e18) / 0x2 == b()", &HashMap::new()).unwrap();
+        assert_eq!(invariant.cmp, Comparison::Eq);
+        assert!(parse_view_invariant("Vault", "a() >= minPrice", &HashMap::new()).is_err());
+        assert!(parse_view_invariant("Vault", "a() b()", &HashMap::new()).is_err());
+    }
+}
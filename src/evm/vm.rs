@@ -36,17 +36,31 @@ use rand::random;
 
 use revm::db::BenchmarkDB;
 use revm_interpreter::{CallContext, CallScheme, Contract, InstructionResult, Interpreter};
-use revm_primitives::{Bytecode, LatestSpec, Env};
+use revm_primitives::{Bytecode, Env};
+use crate::run_inspect_with_version;
 
 
+use crate::evm::storage_write::reset_storage_write;
+use crate::evm::tx_gas::{end_tx, start_tx};
+use crate::evm::withdrawal::ETHER_SENT;
 use crate::evm::bytecode_analyzer;
 use crate::evm::concolic::concolic_exe_host::ConcolicEVMExecutor;
 use crate::evm::host::{
     ControlLeak, FuzzHost, CMP_MAP, COVERAGE_NOT_CHANGED, GLOBAL_CALL_CONTEXT, JMP_MAP, READ_MAP,
-    RET_OFFSET, RET_SIZE, STATE_CHANGE, WRITE_MAP, BRANCH_DISTANCE,
+    RET_OFFSET, RET_SIZE, STATE_CHANGE, WRITE_MAP, BRANCH_DISTANCE, CALL_STACK, REENTRANCY_HIT,
+    SELFDESTRUCT_HIT, CALL_DEPTH_REFUSED, EXTERNAL_CALL_MADE,
 };
 use crate::evm::input::{EVMInputT, EVMInputTy};
 use crate::evm::middlewares::middleware::MiddlewareType;
+use crate::evm::adversary::adversary_txn;
+use crate::evm::price_feeds::set_feed_prices;
+use crate::evm::force_feed::{credit, FORCE_FEED};
+use crate::evm::cfg_refinement::{refine_pending_cfgs, REFINE_CFG};
+use crate::evm::block_advance::{advance_block, block_advance_enabled, BlockAdvance, BlockClock};
+use crate::evm::revert_stats::{record_execution, INSTRUCTIONS_EXECUTED};
+use crate::evm::value_hints::{record_revert_reason, VALUE_HINTS};
+use crate::backend_cost::{record_path_length, ROUTE_BY_COST};
+use crate::evm::scenario::scenario;
 use crate::evm::onchain::flashloan::FlashloanData;
 use crate::evm::uniswap::generate_uniswap_router_call;
 use crate::generic_vm::vm_executor::{ExecutionResult, GenericVM, MAP_SIZE};
@@ -118,10 +132,24 @@ impl PostExecutionCtx {
     }
 }
 
+/// Storage slots of a contract
+///
+/// A persistent map: a clone shares its structure with the original and a write only copies the
+/// path to the slot written, so cloning a state (for each staged state, each execution and each
+/// checkpoint of a call) costs O(contracts) rather than O(storage).
+pub type EVMStorage = im::HashMap<EVMU256, EVMU256>;
+
+/// Storage and balances of a state at the beginning of a call, restored if the call fails
+#[derive(Clone, Debug)]
+pub struct StateCheckpoint {
+    state: HashMap<EVMAddress, EVMStorage>,
+    balances: HashMap<EVMAddress, EVMU256>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EVMState {
     /// State of the EVM, which is mapping of EVMU256 slot to EVMU256 value for each contract
-    pub state: HashMap<EVMAddress, HashMap<EVMU256, EVMU256>>,
+    pub state: HashMap<EVMAddress, EVMStorage>,
 
     /// Post execution context
     /// If control leak happens, we add the post execution context to the VM state,
@@ -137,6 +165,14 @@ pub struct EVMState {
 
     /// Is bug() call in Solidity hit?
     pub bug_hit: bool,
+
+    /// Modeled ether balances of the targets, see [`crate::evm::force_feed`]
+    #[serde(default)]
+    pub balances: HashMap<EVMAddress, EVMU256>,
+
+    /// Block of the last transaction, see [`crate::evm::block_advance`]
+    #[serde(default)]
+    pub clock: Option<BlockClock>,
 }
 
 impl Default for EVMState {
@@ -148,6 +184,8 @@ impl Default for EVMState {
             post_execution: Vec::new(),
             flashloan_data: FlashloanData::new(),
             bug_hit: false,
+            balances: HashMap::new(),
+            clock: None,
         }
     }
 }
@@ -183,6 +221,10 @@ impl VMStateT for EVMState {
                 j.1.hash(&mut s);
             }
         }
+        for (address, balance) in self.balances.iter().sorted_by_key(|k| k.0) {
+            address.0.hash(&mut s);
+            balance.hash(&mut s);
+        }
         s.finish()
     }
 
@@ -233,23 +275,39 @@ impl EVMState {
             post_execution: vec![],
             flashloan_data: FlashloanData::new(),
             bug_hit: false,
+            balances: HashMap::new(),
+            clock: None,
         }
     }
 
     /// Get all storage slots of a specific contract
-    pub fn get(&self, address: &EVMAddress) -> Option<&HashMap<EVMU256, EVMU256>> {
+    pub fn get(&self, address: &EVMAddress) -> Option<&EVMStorage> {
         self.state.get(address)
     }
 
     /// Get all storage slots of a specific contract (mutable)
-    pub fn get_mut(&mut self, address: &EVMAddress) -> Option<&mut HashMap<EVMU256, EVMU256>> {
+    pub fn get_mut(&mut self, address: &EVMAddress) -> Option<&mut EVMStorage> {
         self.state.get_mut(address)
     }
 
     /// Insert all storage slots of a specific contract
-    pub fn insert(&mut self, address: EVMAddress, storage: HashMap<EVMU256, EVMU256>) {
+    pub fn insert(&mut self, address: EVMAddress, storage: EVMStorage) {
         self.state.insert(address, storage);
     }
+
+    /// Checkpoint of the storage and balances, sharing the storages with the state
+    pub fn checkpoint(&self) -> StateCheckpoint {
+        StateCheckpoint {
+            state: self.state.clone(),
+            balances: self.balances.clone(),
+        }
+    }
+
+    /// Undo the writes made since the checkpoint, including those of nested calls
+    pub fn revert_to(&mut self, checkpoint: StateCheckpoint) {
+        self.state = checkpoint.state;
+        self.balances = checkpoint.balances;
+    }
 }
 
 
@@ -342,9 +400,33 @@ where
         self.host.coverage_changed = false;
         self.host.evmstate = vm_state.clone();
         self.host.env = input.get_vm_env().clone();
+        // Apply the scenario preset (env + storage overrides) on top of the input
+        if let Some(scenario) = scenario(input.get_scenario()) {
+            scenario.apply(&mut self.host.env, &mut self.host.evmstate);
+        }
+        if block_advance_enabled() {
+            // resuming a control leak stays in the block of the transaction that leaked
+            let advance = match (&post_exec, &self.host.evmstate.clock) {
+                (Some(_), Some(_)) => Some(BlockAdvance::Same),
+                _ => input.get_block_advance(),
+            };
+            advance_block(&mut self.host.env, &mut self.host.evmstate.clock, advance);
+        }
         self.host.access_pattern = input.get_access_pattern().clone();
+        set_feed_prices(input.get_feed_prices());
         self.host.bug_hit = false;
         self.host.call_count = 0;
+        unsafe {
+            CALL_STACK = vec![call_ctx.address];
+            REENTRANCY_HIT = None;
+            SELFDESTRUCT_HIT = None;
+            ETHER_SENT.clear();
+            CALL_DEPTH_REFUSED = 0;
+            EXTERNAL_CALL_MADE = false;
+            INSTRUCTIONS_EXECUTED = 0;
+        }
+        reset_storage_write();
+        start_tx(input.get_gas_limit(), call_ctx.address, &data);
         let mut repeats = input.get_repeat();
         // Initially, there is no state change
         unsafe {
@@ -363,6 +445,11 @@ where
             .expect(&*format!("no code {:?}", call_ctx.code_address))
             .clone();
 
+        self.host
+            .fuzz_targets
+            .setup(call_ctx.caller, call_ctx.address, &data, &mut self.host.evmstate, &mut self.host.env);
+        self.host.hooks.tx_start(call_ctx.caller, call_ctx.address, &data, call_ctx.apparent_value);
+
         // Create the interpreter
         let mut interp = if let Some(ref post_exec_ctx) = post_exec {
             // If there is a post execution context, then we need to create the interpreter from
@@ -408,7 +495,7 @@ where
         let mut r = InstructionResult::Stop;
         for v in 0..repeats - 1 {
             // println!("repeat: {:?}", v);
-            r = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(&mut self.host, state);
+            r = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, &mut self.host, state);
             interp.stack.data.clear();
             interp.memory.data.clear();
             interp.instruction_pointer = interp.contract.bytecode.as_ptr();
@@ -418,8 +505,11 @@ where
             }
         }
         if r != InstructionResult::Revert {
-            r = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(&mut self.host, state);
+            r = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, &mut self.host, state);
         }
+        self.host.hooks.tx_end(r == InstructionResult::Revert, &interp.return_value());
+        self.host.fuzz_targets.teardown(r == InstructionResult::Revert, &mut self.host.evmstate);
+        end_tx();
 
         // Build the result
         let mut result = IntermediateExecutionResult {
@@ -502,7 +592,7 @@ where
                 .clone();
         }
         let mut interp = Interpreter::new(call, 1e10 as u64, false);
-        let ret = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(&mut self.host, state);
+        let ret = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, &mut self.host, state);
         unsafe {
             IS_FAST_CALL = false;
         }
@@ -557,6 +647,7 @@ where
         // };
         let value = input.get_txn_value().unwrap_or(EVMU256::ZERO);
         let contract_address = input.get_contract();
+        let selector = data[..data.len().min(4)].to_vec();
         
         // Execute the transaction
         let mut r = if is_step {
@@ -586,6 +677,19 @@ where
                 state,
             )
         } else {
+            // the adversary transaction lands right before the input
+            if let Some(txn) = input.get_adversary().and_then(adversary_txn) {
+                self.host.origin = txn.caller;
+                let res = self.fast_call(txn.contract, txn.calldata(), input.get_state(), state, txn.value, txn.caller);
+                if res.ret == InstructionResult::Return || res.ret == InstructionResult::Stop {
+                    vm_state = res.new_state;
+                }
+            }
+            // ether force-fed to the target and the value of the transaction land before it runs
+            if unsafe { FORCE_FEED } {
+                let fed = input.get_force_feed().unwrap_or(EVMU256::ZERO);
+                credit(&mut vm_state.balances, contract_address, fed.saturating_add(value));
+            }
             self.host.origin = caller;
             self.execute_from_pc(
                 &CallContext {
@@ -626,6 +730,25 @@ where
             _ => {}
         }
 
+        // the jumps to blocks the static CFG misses trigger its re-analysis
+        if unsafe { REFINE_CFG } {
+            refine_pending_cfgs();
+        }
+
+        // resuming from a control leak skips the guards of the function
+        if !is_step {
+            record_execution(
+                &selector,
+                r.ret != InstructionResult::Return && r.ret != InstructionResult::Stop && r.ret != ControlLeak,
+            );
+            if unsafe { ROUTE_BY_COST } {
+                record_path_length(&selector, unsafe { INSTRUCTIONS_EXECUTED });
+            }
+            if unsafe { VALUE_HINTS } && r.ret == InstructionResult::Revert {
+                record_revert_reason(&selector, &r.output);
+            }
+        }
+
         // println!("return value: {:?}", hex::encode(r.output.to_vec()));
         r.new_state.bug_hit = vm_state.bug_hit || self.host.bug_hit;
         unsafe {
@@ -686,7 +809,7 @@ where
         let mut interp = Interpreter::new(deployer, 1e10 as u64, false);
         self.host.middlewares_enabled = middleware_status;
         let mut dummy_state = S::default();
-        let r = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(&mut self.host, &mut dummy_state);
+        let r = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, &mut self.host, &mut dummy_state);
         #[cfg(feature = "evaluation")]
         {
             self.host.pc_coverage = Default::default();
@@ -818,7 +941,7 @@ where
                     &ctx,
                 );
                 let mut interp = Interpreter::new(call, 1e10 as u64, false);
-                let ret = interp.run_inspect::<S, FuzzHost<VS, I, S>, LatestSpec>(&mut self.host, state);
+                let ret = run_inspect_with_version!(interp, S, FuzzHost<VS, I, S>, &mut self.host, state);
                 if ret == InstructionResult::Revert {
                     vec![]
                 } else {
@@ -853,8 +976,8 @@ mod tests {
     use crate::evm::host::{FuzzHost, JMP_MAP};
     use crate::evm::input::{EVMInput, EVMInputTy};
     use crate::evm::mutator::AccessPattern;
-    use crate::evm::types::{EVMFuzzState, EVMU256, generate_random_address};
-    use crate::evm::vm::{EVMExecutor, EVMState};
+    use crate::evm::types::{EVMAddress, EVMFuzzState, EVMU256, generate_random_address};
+    use crate::evm::vm::{EVMExecutor, EVMState, EVMStorage};
     use crate::generic_vm::vm_executor::{GenericVM, MAP_SIZE};
     use crate::state::FuzzState;
     use crate::state_input::StagedVMState;
@@ -865,6 +988,28 @@ mod tests {
     use std::rc::Rc;
     use std::sync::Arc;
 
+    #[test]
+    fn test_state_checkpoint() {
+        let target = EVMAddress::zero();
+        let mut vm_state = EVMState::new();
+        vm_state.insert(target, EVMStorage::from(vec![(EVMU256::from(0), EVMU256::from(1))]));
+        let outer = vm_state.checkpoint();
+        vm_state.get_mut(&target).unwrap().insert(EVMU256::from(0), EVMU256::from(2));
+        let inner = vm_state.checkpoint();
+        vm_state.get_mut(&target).unwrap().insert(EVMU256::from(1), EVMU256::from(3));
+        vm_state.balances.insert(target, EVMU256::from(5));
+
+        // the inner call fails, the writes of the outer one are kept
+        vm_state.revert_to(inner);
+        assert_eq!(vm_state.get(&target).unwrap().get(&EVMU256::from(0)), Some(&EVMU256::from(2)));
+        assert_eq!(vm_state.get(&target).unwrap().get(&EVMU256::from(1)), None);
+        assert!(vm_state.balances.is_empty());
+
+        // the checkpoints do not see the later writes
+        vm_state.revert_to(outer);
+        assert_eq!(vm_state.get(&target).unwrap().get(&EVMU256::from(0)), Some(&EVMU256::from(1)));
+    }
+
     #[test]
     fn test_fuzz_executor() {
         let mut state: EVMFuzzState = FuzzState::new(0);
@@ -0,0 +1,229 @@
+/// Warm start of a campaign from the corpus of a run on a similar target (`--warm-start <dir>`)
+///
+/// Forks of a protocol keep most of its functions, so the corpus of a campaign on the original
+/// (`<dir>/corpus/*_replayable`) already reaches deep states of the fork. Each transaction is
+/// mapped onto the targets of the new campaign:
+/// - a target exposing the same selector receives the calldata as is
+/// - otherwise, a target exposing a function of the same name (from the signature recorded in
+///   the corpus file) receives the arguments re-encoded for its parameters: each parameter takes
+///   the argument of the same position and type, or the first unused one of the same type, and
+///   zero when there is none. Only functions with static parameters are re-encoded.
+/// - other transactions are dropped, the rest of the sequence is kept
+///
+/// The mapped sequences are evaluated before fuzzing, and the ones gaining coverage seed the
+/// corpus.
+use crate::evm::contract_utils::ContractInfo;
+use crate::evm::corpus_format::{load_corpus_file, CorpusTxn, TxnKind};
+use crate::evm::onchain::abi_decompiler::parse_signature;
+use crate::evm::types::EVMAddress;
+use crate::workdir::CORPUS_DIR;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Function of a target of the campaign
+#[derive(Clone, Debug)]
+struct TargetFunction {
+    contract: EVMAddress,
+    selector: [u8; 4],
+    types: Vec<String>,
+}
+
+/// Functions of the targets, by selector and by name
+#[derive(Clone, Debug, Default)]
+pub struct TargetFunctions {
+    by_selector: HashMap<[u8; 4], EVMAddress>,
+    by_name: HashMap<String, Vec<TargetFunction>>,
+}
+
+impl TargetFunctions {
+    pub fn new(contracts: &Vec<ContractInfo>) -> Self {
+        let mut functions = Self::default();
+        for contract in contracts {
+            for abi in contract.abi.iter().filter(|abi| !abi.is_constructor) {
+                functions.by_selector.entry(abi.function).or_insert(contract.deployed_address);
+                let (_, types) = parse_signature(&format!("{}{}", abi.function_name, abi.abi));
+                functions.by_name.entry(abi.function_name.clone()).or_default().push(TargetFunction {
+                    contract: contract.deployed_address,
+                    selector: abi.function,
+                    types,
+                });
+            }
+        }
+        functions
+    }
+}
+
+/// Elementary type, encoded in a single head word
+fn is_elementary(ty: &str) -> bool {
+    !ty.contains('[') && !ty.contains('(')
+}
+
+fn is_static(ty: &str) -> bool {
+    is_elementary(ty) && ty != "bytes" && ty != "string"
+}
+
+/// Calldata of the function with the arguments of the calldata of another one, `None` if it has
+/// dynamic parameters or the old arguments cannot be decoded
+pub fn reencode(calldata: &[u8], old_types: &[String], selector: [u8; 4], new_types: &[String]) -> Option<Vec<u8>> {
+    if !new_types.iter().all(|ty| is_static(ty)) || !old_types.iter().all(|ty| is_elementary(ty)) {
+        return None;
+    }
+    let args = calldata.get(4..)?;
+    let word = |idx: usize| args.get(idx * 32..(idx + 1) * 32);
+    let mut used = vec![false; old_types.len()];
+    let mut encoded = selector.to_vec();
+    for (idx, ty) in new_types.iter().enumerate() {
+        let same_position = (old_types.get(idx) == Some(ty) && !used[idx]).then_some(idx);
+        let source = same_position.or_else(|| (0..old_types.len()).find(|old| !used[*old] && old_types[*old] == *ty));
+        match source.and_then(|old| Some((old, word(old)?))) {
+            Some((old, value)) => {
+                used[old] = true;
+                encoded.extend_from_slice(value);
+            }
+            None => encoded.extend_from_slice(&[0u8; 32]),
+        }
+    }
+    Some(encoded)
+}
+
+/// Transaction mapped onto the targets, and whether its arguments were re-encoded
+pub fn map_txn(txn: &CorpusTxn, targets: &TargetFunctions) -> Option<(CorpusTxn, bool)> {
+    if txn.kind == TxnKind::Borrow {
+        return Some((txn.clone(), false));
+    }
+    let calldata = hex::decode(&txn.data).ok()?;
+    let selector: [u8; 4] = calldata.get(..4)?.try_into().ok()?;
+    let mut mapped = txn.clone();
+    if let Some(contract) = targets.by_selector.get(&selector) {
+        mapped.contract = *contract;
+        return Some((mapped, false));
+    }
+    let (name, old_types) = parse_signature(txn.function.as_ref()?);
+    let candidates = targets.by_name.get(&name)?;
+    // the overload with as many parameters if there is one
+    let target = candidates
+        .iter()
+        .find(|f| f.types.len() == old_types.len())
+        .unwrap_or(&candidates[0]);
+    mapped.data = hex::encode(reencode(&calldata, &old_types, target.selector, &target.types)?);
+    mapped.contract = target.contract;
+    mapped.function = None;
+    Some((mapped, true))
+}
+
+/// Sequences of the corpus of the run in `dir`, mapped onto the targets
+pub fn load_warm_start(dir: &str, contracts: &Vec<ContractInfo>) -> Vec<Vec<CorpusTxn>> {
+    let targets = TargetFunctions::new(contracts);
+    let corpus = Path::new(dir).join(CORPUS_DIR);
+    let entries = match fs::read_dir(&corpus) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("[warm-start] failed to read {}: {}", corpus.display(), e);
+            return vec![];
+        }
+    };
+    let (mut total, mut mapped, mut reencoded) = (0, 0, 0);
+    let mut sequences = vec![];
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if !path.to_string_lossy().ends_with("_replayable") {
+            continue;
+        }
+        let txns = match load_corpus_file(&path.to_string_lossy()) {
+            Ok(txns) => txns,
+            Err(e) => {
+                warn!("[warm-start] skipping corpus file {}", e);
+                continue;
+            }
+        };
+        total += txns.len();
+        let sequence = txns
+            .iter()
+            .filter_map(|txn| map_txn(txn, &targets))
+            .map(|(txn, was_reencoded)| {
+                reencoded += was_reencoded as usize;
+                txn
+            })
+            .collect::<Vec<_>>();
+        mapped += sequence.len();
+        if !sequence.is_empty() {
+            sequences.push(sequence);
+        }
+    }
+    info!(
+        "[warm-start] {} of {} transactions of {} mapped onto the targets ({} re-encoded)",
+        mapped, total, dir, reencoded
+    );
+    sequences
+}
+
+mod tests {
+    use super::*;
+    use crate::evm::contract_utils::{set_hash, ABIConfig};
+    use crate::evm::types::EVMU256;
+
+    fn abi(signature: &str) -> ABIConfig {
+        let (name, types) = parse_signature(signature);
+        let mut function = [0u8; 4];
+        set_hash(signature, &mut function);
+        ABIConfig {
+            abi: format!("({})", types.join(",")),
+            function,
+            function_name: name,
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+        }
+    }
+
+    #[test]
+    fn test_map_txn() {
+        let fork = EVMAddress::from_slice(&[0x22; 20]);
+        let contracts = vec![ContractInfo {
+            name: "Fork".to_string(),
+            abi: vec![abi("deposit(uint256,address)"), abi("withdraw(address,uint256,bool)")],
+            code: vec![],
+            is_code_deployed: false,
+            constructor_args: vec![],
+            deployed_address: fork,
+            source_map: None,
+        }];
+        let targets = TargetFunctions::new(&contracts);
+        let txn = |data: String, function: &str| CorpusTxn {
+            kind: TxnKind::Abi,
+            caller: EVMAddress::zero(),
+            contract: EVMAddress::from_slice(&[0x11; 20]),
+            data,
+            value: EVMU256::ZERO,
+            liquidation_percent: 0,
+            warp_to: 0,
+            repeat: 1,
+            reentrancy_limit: 0,
+            step: false,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
+            function: Some(function.to_string()),
+            meta: None,
+        };
+
+        // same signature
+        let deposit = abi("deposit(uint256,address)");
+        let data = format!("{}{:064x}{:064x}", hex::encode(deposit.function), 5, 0x33);
+        let (mapped, reencoded) = map_txn(&txn(data.clone(), "deposit(uint256,address)"), &targets).unwrap();
+        assert_eq!((mapped.contract, mapped.data, reencoded), (fork, data, false));
+
+        // withdraw(uint256 amount, address to) -> withdraw(address to, uint256 amount, bool)
+        let old = abi("withdraw(uint256,address)");
+        let data = format!("{}{:064x}{:064x}", hex::encode(old.function), 7, 0x44);
+        let (mapped, reencoded) = map_txn(&txn(data, "withdraw(uint256,address)"), &targets).unwrap();
+        let new = abi("withdraw(address,uint256,bool)");
+        assert!(reencoded);
+        assert_eq!(mapped.data, format!("{}{:064x}{:064x}{:064x}", hex::encode(new.function), 0x44, 7, 0));
+
+        let data = format!("{}{:064x}", hex::encode(abi("pause(uint256)").function), 1);
+        assert!(map_txn(&txn(data, "pause(uint256)"), &targets).is_none());
+    }
+}
@@ -0,0 +1,351 @@
+/// "Weird" ERC-20 tokens deployed into the initial state (`--weird-tokens`)
+///
+/// Most DeFi bugs only show with tokens that do not behave like the reference ERC-20: the
+/// recipient receives less than the amount sent, balances change without transfers, some
+/// accounts cannot receive, a failed transfer returns false. The tokens declared in
+/// `--weird-tokens` are deployed at their address with a placeholder code before the targets
+/// (which can then be constructed with them as the tokens of the protocol), and their calls are
+/// answered by the host with a native model, like the AMM pools (see [`crate::evm::amm`]):
+/// ```json
+/// [{"name": "USDT", "address": "0x...", "behaviors": ["fee_on_transfer", "return_false"],
+///   "fee_bps": 100, "balances": {"attacker1": "1000e18", "0x...": "1e24"}}]
+/// ```
+/// - `fee_on_transfer`: `fee_bps` of each transfer is burnt, the recipient receives the rest
+/// - `rebasing`: balances are shares scaled by an index growing by `rebase_bps` per day since
+///   the first call to the token
+/// - `blacklistable`: transfers from or to the `blacklist` accounts revert
+/// - `return_false`: failing transfers and approvals return false instead of reverting
+///
+/// The tokens answer `totalSupply()`, `balanceOf`, `transfer`, `transferFrom`, `approve`,
+/// `allowance` and `decimals()`. Their shares, allowances and the start of the rebase are kept in
+/// their storage, initialized at the first call, so that they are part of the VM state. Accounts
+/// are addresses or caller aliases (`attacker1`).
+use crate::evm::token_minting::{address_word, keccak};
+use crate::evm::types::{alias_address, EVMAddress, EVMU256};
+use crate::evm::view_invariants::parse_number;
+use crate::evm::vm::{EVMState, EVMStorage};
+use bytes::Bytes;
+use revm_interpreter::InstructionResult;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+use tracing::info;
+
+const TOTAL_SUPPLY: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+const APPROVE: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+const ALLOWANCE: [u8; 4] = [0xdd, 0x62, 0xed, 0x3e];
+const DECIMALS: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// Storage slots of the token state
+const SHARES_SLOT: u64 = 0;
+const ALLOWANCES_SLOT: u64 = 1;
+const TOTAL_SHARES_SLOT: u64 = 2;
+const REBASE_START_SLOT: u64 = 3;
+
+const BPS: u64 = 10000;
+const DAY: u64 = 86400;
+
+/// Code of the tokens, never executed (the host answers the calls), `INVALID` in case it is
+pub const TOKEN_CODE: [u8; 1] = [0xfe];
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenBehavior {
+    FeeOnTransfer,
+    Rebasing,
+    Blacklistable,
+    ReturnFalse,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeirdToken {
+    pub name: String,
+    pub address: EVMAddress,
+    #[serde(default)]
+    pub behaviors: Vec<TokenBehavior>,
+    /// Share of each transfer burnt by a fee-on-transfer token, in basis points
+    #[serde(default)]
+    pub fee_bps: u64,
+    /// Growth of the balances of a rebasing token per day, in basis points
+    #[serde(default)]
+    pub rebase_bps: u64,
+    /// Accounts a blacklistable token refuses to transfer from or to
+    #[serde(default)]
+    pub blacklist: Vec<String>,
+    /// Initial balances, account -> amount (`1e18`, `0x...`)
+    #[serde(default)]
+    pub balances: HashMap<String, String>,
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+}
+
+fn default_decimals() -> u8 {
+    18
+}
+
+/// Tokens declared in the config
+pub static mut WEIRD_TOKENS: Vec<WeirdToken> = Vec::new();
+
+pub fn load_weird_tokens(path: &str) -> Vec<WeirdToken> {
+    let mut file = File::open(path).expect("failed to open weird token file");
+    let mut data = String::new();
+    file.read_to_string(&mut data)
+        .expect("failed to read weird token file");
+    serde_json::from_str(&data).expect("failed to parse weird token file")
+}
+
+pub fn register_weird_tokens(tokens: Vec<WeirdToken>) {
+    for token in &tokens {
+        info!("Registered token {} ({:?}) at {:?}", token.name, token.behaviors, token.address);
+    }
+    unsafe {
+        WEIRD_TOKENS = tokens;
+    }
+}
+
+/// Address of an account, given as an address or a caller alias
+fn resolve_account(account: &str) -> Option<EVMAddress> {
+    alias_address(account).or_else(|| EVMAddress::from_str(account).ok())
+}
+
+fn mapping_slot(key: &EVMAddress, slot: EVMU256) -> EVMU256 {
+    let slot: [u8; 32] = slot.to_be_bytes();
+    keccak(&[address_word(key), slot].concat())
+}
+
+fn shares_slot(account: &EVMAddress) -> EVMU256 {
+    mapping_slot(account, EVMU256::from(SHARES_SLOT))
+}
+
+fn allowance_slot(owner: &EVMAddress, spender: &EVMAddress) -> EVMU256 {
+    mapping_slot(spender, mapping_slot(owner, EVMU256::from(ALLOWANCES_SLOT)))
+}
+
+fn get(storage: &EVMStorage, slot: EVMU256) -> EVMU256 {
+    storage.get(&slot).cloned().unwrap_or_default()
+}
+
+fn word(v: EVMU256) -> Vec<u8> {
+    v.to_be_bytes::<32>().to_vec()
+}
+
+fn arg(calldata: &[u8], i: usize) -> Option<EVMU256> {
+    calldata.get(4 + 32 * i..4 + 32 * (i + 1)).map(EVMU256::from_be_slice)
+}
+
+fn address_arg(calldata: &[u8], i: usize) -> Option<EVMAddress> {
+    calldata.get(4 + 32 * i + 12..4 + 32 * (i + 1)).map(EVMAddress::from_slice)
+}
+
+fn unit() -> EVMU256 {
+    EVMU256::from(10).pow(EVMU256::from(18))
+}
+
+impl WeirdToken {
+    pub fn has(&self, behavior: TokenBehavior) -> bool {
+        self.behaviors.contains(&behavior)
+    }
+
+    /// Initial storage of the token, the shares being the balances
+    pub fn initial_storage(&self, timestamp: EVMU256) -> EVMStorage {
+        let mut storage = EVMStorage::new();
+        let mut total = EVMU256::ZERO;
+        for (account, amount) in &self.balances {
+            let (address, amount) = match (resolve_account(account), parse_number(amount)) {
+                (Some(address), Ok(amount)) => (address, amount),
+                _ => panic!("invalid balance {}: {} of token {}", account, amount, self.name),
+            };
+            storage.insert(shares_slot(&address), amount);
+            total = total.saturating_add(amount);
+        }
+        storage.insert(EVMU256::from(TOTAL_SHARES_SLOT), total);
+        storage.insert(EVMU256::from(REBASE_START_SLOT), timestamp);
+        storage
+    }
+
+    /// Balance of a share, scaled by 1e18
+    pub fn index(&self, storage: &EVMStorage, timestamp: EVMU256) -> EVMU256 {
+        if !self.has(TokenBehavior::Rebasing) {
+            return unit();
+        }
+        let elapsed = timestamp.saturating_sub(get(storage, EVMU256::from(REBASE_START_SLOT)));
+        let growth = unit()
+            .saturating_mul(EVMU256::from(self.rebase_bps))
+            .saturating_mul(elapsed)
+            / EVMU256::from(BPS * DAY);
+        unit().saturating_add(growth)
+    }
+
+    fn blacklisted(&self, account: &EVMAddress) -> bool {
+        self.has(TokenBehavior::Blacklistable)
+            && self.blacklist.iter().any(|a| resolve_account(a).as_ref() == Some(account))
+    }
+
+    /// Move `amount` (in balance units) from `from` to `to`, `None` if the transfer fails
+    fn transfer(
+        &self,
+        storage: &mut EVMStorage,
+        from: &EVMAddress,
+        to: &EVMAddress,
+        amount: EVMU256,
+        index: EVMU256,
+    ) -> Option<()> {
+        if self.blacklisted(from) || self.blacklisted(to) {
+            return None;
+        }
+        let shares = amount.checked_mul(unit())? / index;
+        let from_shares = get(storage, shares_slot(from)).checked_sub(shares)?;
+        storage.insert(shares_slot(from), from_shares);
+        let fee = match self.has(TokenBehavior::FeeOnTransfer) {
+            true => shares.saturating_mul(EVMU256::from(self.fee_bps)) / EVMU256::from(BPS),
+            false => EVMU256::ZERO,
+        };
+        let to_shares = get(storage, shares_slot(to)).saturating_add(shares - fee);
+        storage.insert(shares_slot(to), to_shares);
+        let total = get(storage, EVMU256::from(TOTAL_SHARES_SLOT)).saturating_sub(fee);
+        storage.insert(EVMU256::from(TOTAL_SHARES_SLOT), total);
+        Some(())
+    }
+
+    /// Answer of a failing call: false or a revert
+    fn fail(&self) -> (InstructionResult, Bytes) {
+        match self.has(TokenBehavior::ReturnFalse) {
+            true => (InstructionResult::Return, Bytes::from(word(EVMU256::ZERO))),
+            false => (InstructionResult::Revert, Bytes::new()),
+        }
+    }
+}
+
+/// Answer a call to a token and update its state, `None` if the address is not a weird token
+pub fn weird_token_call(
+    address: EVMAddress,
+    caller: EVMAddress,
+    calldata: &[u8],
+    evmstate: &mut EVMState,
+    timestamp: EVMU256,
+) -> Option<(InstructionResult, Bytes)> {
+    let token = unsafe { WEIRD_TOKENS.iter().find(|token| token.address == address) }?;
+    if evmstate.get(&address).is_none() {
+        evmstate.insert(address, token.initial_storage(timestamp));
+    }
+    let storage = evmstate.get_mut(&address).unwrap();
+    let index = token.index(storage, timestamp);
+    let ok = |data: Vec<u8>| Some((InstructionResult::Return, Bytes::from(data)));
+    let success = word(EVMU256::from(1));
+    let selector: [u8; 4] = match calldata.get(..4).and_then(|s| s.try_into().ok()) {
+        Some(selector) => selector,
+        None => return Some((InstructionResult::Revert, Bytes::new())),
+    };
+    match selector {
+        TOTAL_SUPPLY => {
+            let total = get(storage, EVMU256::from(TOTAL_SHARES_SLOT));
+            ok(word(total.saturating_mul(index) / unit()))
+        }
+        BALANCE_OF => {
+            let account = address_arg(calldata, 0)?;
+            ok(word(get(storage, shares_slot(&account)).saturating_mul(index) / unit()))
+        }
+        DECIMALS => ok(word(EVMU256::from(token.decimals))),
+        ALLOWANCE => {
+            let (owner, spender) = (address_arg(calldata, 0)?, address_arg(calldata, 1)?);
+            ok(word(get(storage, allowance_slot(&owner, &spender))))
+        }
+        APPROVE => {
+            let (spender, amount) = (address_arg(calldata, 0)?, arg(calldata, 1)?);
+            if token.blacklisted(&caller) {
+                return Some(token.fail());
+            }
+            storage.insert(allowance_slot(&caller, &spender), amount);
+            ok(success)
+        }
+        TRANSFER => {
+            let (to, amount) = (address_arg(calldata, 0)?, arg(calldata, 1)?);
+            match token.transfer(storage, &caller, &to, amount, index) {
+                Some(()) => ok(success),
+                None => Some(token.fail()),
+            }
+        }
+        TRANSFER_FROM => {
+            let (from, to) = (address_arg(calldata, 0)?, address_arg(calldata, 1)?);
+            let amount = arg(calldata, 2)?;
+            let allowance = get(storage, allowance_slot(&from, &caller));
+            if caller != from && allowance < amount {
+                return Some(token.fail());
+            }
+            if token.transfer(storage, &from, &to, amount, index).is_none() {
+                return Some(token.fail());
+            }
+            // an infinite approval is never spent
+            if caller != from && allowance != EVMU256::MAX {
+                storage.insert(allowance_slot(&from, &caller), allowance - amount);
+            }
+            ok(success)
+        }
+        _ => Some((InstructionResult::Revert, Bytes::new())),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn call(
+        data: Vec<u8>,
+        caller: EVMAddress,
+        state: &mut EVMState,
+        timestamp: u64,
+    ) -> (InstructionResult, Vec<u8>) {
+        let timestamp = EVMU256::from(timestamp);
+        let (res, ret) = weird_token_call(fixed(0x70), caller, &data, state, timestamp).unwrap();
+        (res, ret.to_vec())
+    }
+
+    fn fixed(b: u8) -> EVMAddress {
+        EVMAddress::from_slice(&[b; 20])
+    }
+
+    fn calldata(selector: [u8; 4], args: Vec<[u8; 32]>) -> Vec<u8> {
+        [selector.to_vec(), args.concat()].concat()
+    }
+
+    #[test]
+    fn test_weird_tokens() {
+        let (alice, bob, eve) = (fixed(0xa1), fixed(0xb0), fixed(0xe5));
+        let tokens: Vec<WeirdToken> = serde_json::from_str(&format!(
+            r#"[{{"name": "FOT", "address": "{:?}", "behaviors": ["fee_on_transfer", "rebasing", "blacklistable", "return_false"],
+                 "fee_bps": 100, "rebase_bps": 100, "blacklist": ["{:?}"], "balances": {{"{:?}": "1000"}}}}]"#,
+            fixed(0x70),
+            eve,
+            alice
+        ))
+        .unwrap();
+        register_weird_tokens(tokens);
+        let mut state = EVMState::default();
+        let amount = |v: u64| word(EVMU256::from(v)).try_into().unwrap();
+
+        // 1% of the transfer is burnt
+        let (res, ret) = call(calldata(TRANSFER, vec![address_word(&bob), amount(100)]), alice, &mut state, 0);
+        assert_eq!((res, ret), (InstructionResult::Return, word(EVMU256::from(1))));
+        let (_, ret) = call(calldata(BALANCE_OF, vec![address_word(&bob)]), alice, &mut state, 0);
+        assert_eq!(ret, word(EVMU256::from(99)));
+        let (_, ret) = call(calldata(TOTAL_SUPPLY, vec![]), alice, &mut state, 0);
+        assert_eq!(ret, word(EVMU256::from(999)));
+
+        // a day later, the balances grew by 1%
+        let (_, ret) = call(calldata(BALANCE_OF, vec![address_word(&alice)]), alice, &mut state, DAY);
+        assert_eq!(ret, word(EVMU256::from(909)));
+
+        // failures return false: blacklisted recipient, insufficient balance or allowance
+        let failed = (InstructionResult::Return, word(EVMU256::ZERO));
+        assert_eq!(call(calldata(TRANSFER, vec![address_word(&eve), amount(1)]), alice, &mut state, 0), failed);
+        assert_eq!(call(calldata(TRANSFER, vec![address_word(&alice), amount(1000)]), bob, &mut state, 0), failed);
+        assert_eq!(
+            call(calldata(TRANSFER_FROM, vec![address_word(&alice), address_word(&bob), amount(1)]), bob, &mut state, 0),
+            failed
+        );
+    }
+}
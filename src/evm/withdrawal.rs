@@ -0,0 +1,140 @@
+/// Unprotected ether withdrawals (`--sanitize withdrawal`)
+///
+/// Static pass: on the CFG of each target (see [`crate::evm::directed::build_cfg`], completed
+/// with the jumps resolved by heimdall), a function is a candidate when a `CALL` is reachable
+/// from its entry point without going through a branch that depends on `CALLER` / `ORIGIN`
+/// (`require(msg.sender == owner)`, `onlyRole`, `balances[msg.sender] >= amount`, ...).
+///
+/// Dynamic pass: the host records the calls transferring value during an execution, and the
+/// `withdrawal` oracle confirms a candidate when a non-deployer calls it successfully and ether
+/// is sent from the target to the caller. The finding carries the reproducer of the transaction.
+use crate::evm::directed::{block_of, block_starts, build_cfg};
+use crate::evm::entry_points::disassemble;
+use crate::evm::onchain::abi_decompiler::fetch_jump_edges_heimdall;
+use crate::evm::types::{EVMAddress, EVMU256};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const CALLER: u8 = 0x33;
+const ORIGIN: u8 = 0x32;
+const JUMPI: u8 = 0x57;
+const CALL: u8 = 0xf1;
+
+/// Whether the host records the value transfers, on with `--sanitize withdrawal`
+pub static mut WITHDRAWAL_CHECK: bool = false;
+
+/// Candidate functions of each scanned target; all the functions of a target the decompiler
+/// failed on are candidates
+pub static mut WITHDRAWAL_CANDIDATES: Option<HashMap<EVMAddress, HashSet<[u8; 4]>>> = None;
+
+/// Calls transferring value during the current execution: sender, recipient, value
+pub static mut ETHER_SENT: Vec<(EVMAddress, EVMAddress, EVMU256)> = Vec::new();
+
+/// Whether a `CALL` is reachable from the entry point without a branch on the caller
+pub fn reaches_unguarded_call(code: &[u8], cfg: &HashMap<usize, HashSet<usize>>, entry: usize) -> bool {
+    let starts = block_starts(code);
+    let mut ops: HashMap<usize, Vec<u8>> = HashMap::new();
+    for (pc, op, _) in disassemble(code) {
+        ops.entry(block_of(&starts, pc)).or_default().push(op);
+    }
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(block_of(&starts, entry), false)]);
+    while let Some((block, mut caller_read)) = queue.pop_front() {
+        if !visited.insert((block, caller_read)) {
+            continue;
+        }
+        let mut guarded = false;
+        for op in ops.get(&block).map_or(&[][..], |ops| ops.as_slice()) {
+            match *op {
+                CALLER | ORIGIN => caller_read = true,
+                CALL => return true,
+                // a branch on the caller guards the rest of the function
+                JUMPI if caller_read => guarded = true,
+                _ => {}
+            }
+        }
+        if guarded {
+            continue;
+        }
+        for next in cfg.get(&block).into_iter().flatten() {
+            queue.push_back((*next, caller_read));
+        }
+    }
+    false
+}
+
+/// Find the candidate functions of the target, `None` if the code could not be decompiled
+pub fn scan_withdrawals(code: &[u8]) -> Option<HashSet<[u8; 4]>> {
+    let (entry_points, jump_edges) = fetch_jump_edges_heimdall(hex::encode(code)).ok()?;
+    let cfg = build_cfg(code, &jump_edges);
+    Some(
+        entry_points
+            .into_iter()
+            .filter(|(_, entry)| reaches_unguarded_call(code, &cfg, *entry))
+            .map(|(selector, _)| selector)
+            .collect(),
+    )
+}
+
+pub fn register_candidates(contract: EVMAddress, candidates: HashSet<[u8; 4]>) {
+    unsafe {
+        WITHDRAWAL_CANDIDATES
+            .get_or_insert_with(HashMap::new)
+            .insert(contract, candidates);
+    }
+}
+
+/// Whether the call is a candidate of the static pass
+pub fn is_candidate(contract: &EVMAddress, calldata: &[u8]) -> bool {
+    let selector: [u8; 4] = match calldata.get(..4).and_then(|s| s.try_into().ok()) {
+        Some(selector) => selector,
+        None => return false,
+    };
+    match unsafe { WITHDRAWAL_CANDIDATES.as_ref() }.and_then(|c| c.get(contract)) {
+        Some(candidates) => candidates.contains(&selector),
+        None => true,
+    }
+}
+
+/// Called by the host on each call when [`WITHDRAWAL_CHECK`] is on
+pub fn record_value_transfer(from: EVMAddress, to: EVMAddress, value: EVMU256) {
+    if value > EVMU256::ZERO {
+        unsafe {
+            ETHER_SENT.push((from, to, value));
+        }
+    }
+}
+
+/// Ether sent from the contract to the recipient during the current execution
+pub fn ether_sent(contract: &EVMAddress, recipient: &EVMAddress) -> EVMU256 {
+    unsafe {
+        ETHER_SENT
+            .iter()
+            .filter(|(from, to, _)| from == contract && to == recipient)
+            .fold(EVMU256::ZERO, |sum, (_, _, value)| sum.saturating_add(*value))
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unguarded_call() {
+        // 0: CALLVALUE PUSH1 0x05 JUMPI STOP  5: JUMPDEST ... CALLER GAS CALL STOP
+        let unguarded = hex::decode("34600557005b6000808080335af100").unwrap();
+        let cfg = build_cfg(&unguarded, &vec![]);
+        assert!(reaches_unguarded_call(&unguarded, &cfg, 0));
+
+        // 0: CALLER PUSH1 0x05 JUMPI STOP  5: JUMPDEST ... CALLER GAS CALL STOP
+        let guarded = hex::decode("33600557005b6000808080335af100").unwrap();
+        let cfg = build_cfg(&guarded, &vec![]);
+        assert!(!reaches_unguarded_call(&guarded, &cfg, 0));
+
+        let (target, attacker) = (EVMAddress::from_slice(&[0x11; 20]), EVMAddress::from_slice(&[0x22; 20]));
+        register_candidates(target, HashSet::from([[1, 2, 3, 4]]));
+        assert!(is_candidate(&target, &[1, 2, 3, 4, 0]));
+        assert!(!is_candidate(&target, &[4, 3, 2, 1]));
+        record_value_transfer(target, attacker, EVMU256::from(5));
+        record_value_transfer(target, attacker, EVMU256::ZERO);
+        assert_eq!(ether_sent(&target, &attacker), EVMU256::from(5));
+    }
+}
@@ -0,0 +1,374 @@
+//! A/B experiments between two configurations of the fuzzer
+//! (`cli experiment <runs> '<arguments of A>' '<arguments of B>' [common arguments...]`)
+//!
+//! Each configuration is run `runs` times as a campaign of its own (a child process, as the state
+//! of the fuzzer is global) in `<work_dir>/{a,b}-<run>`, with the seeds `1..=runs` shared by the
+//! two configurations, for `--time-budget` seconds ([`QUICK_TIME_BUDGET`] unless given). The
+//! corpus of each run is then replayed to measure its branch coverage (see
+//! [`crate::evm::cov_merge`]), and the time to discovery of each bug class is read from its
+//! `summary.json`, a run missing a bug counting as the whole budget. The two samples of each
+//! metric are compared with the Mann-Whitney U test and the Vargha-Delaney A12 effect size, and
+//! the comparison is written to `<work_dir>/experiment.json`.
+//!
+//! `cli bench <runs> [common arguments...]` runs the same protocol over every power schedule
+//! (see [`crate::scheduler::PowerSchedule`]) in `<work_dir>/<schedule>-<run>`, compares each of
+//! them with `queue` and writes the results per schedule to `<work_dir>/schedules.json`.
+use crate::evm::cov_merge::load_run;
+use crate::evm::summary::Finding;
+use crate::quickstart::{has_flag, QUICK_TIME_BUDGET};
+use crate::scheduler::PowerSchedule;
+use crate::workdir::{CORPUS_DIR, MANIFEST_FILE};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tracing::info;
+
+/// First argument of an A/B experiment
+pub const EXPERIMENT_COMMAND: &str = "experiment";
+
+/// First argument of a comparison of the power schedules
+pub const BENCH_COMMAND: &str = "bench";
+
+/// Schedules compared by `cli bench`, the first one being the baseline
+pub const BENCH_SCHEDULES: [PowerSchedule; 5] = [
+    PowerSchedule::Queue,
+    PowerSchedule::Explore,
+    PowerSchedule::Fast,
+    PowerSchedule::Coe,
+    PowerSchedule::Quad,
+];
+
+/// Outcome of a run of a configuration
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RunResult {
+    pub seed: u64,
+    pub branches: usize,
+    pub instructions: usize,
+    /// Seconds from the start of the campaign to the first finding of each bug class
+    pub time_to_bug: BTreeMap<String, u64>,
+}
+
+/// Comparison of a metric between the two configurations
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub metric: String,
+    pub median_a: f64,
+    pub median_b: f64,
+    pub u: f64,
+    /// Two-sided p-value (normal approximation, corrected for ties)
+    pub p_value: f64,
+    /// Probability that a run of A scores higher than a run of B
+    pub a12: f64,
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+    }
+}
+
+/// Standard normal CDF, from the approximation of erf of Abramowitz and Stegun (7.1.26)
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / 2f64.sqrt();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if z >= 0.0 {
+        (1.0 + erf) / 2.0
+    } else {
+        (1.0 - erf) / 2.0
+    }
+}
+
+/// U statistic of `a`, two-sided p-value and Vargha-Delaney A12 of `a` over `b`
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> (f64, f64, f64) {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    if a.is_empty() || b.is_empty() {
+        return (0.0, 1.0, 0.5);
+    }
+    let mut all = a.iter().map(|v| (*v, true)).chain(b.iter().map(|v| (*v, false))).collect::<Vec<_>>();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+    // average ranks of the ties
+    let mut rank_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let j = (i..all.len()).find(|j| all[*j].0 != all[i].0).unwrap_or(all.len());
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_a += all[i..j].iter().filter(|(_, in_a)| *in_a).count() as f64 * rank;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+    let u = rank_a - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    let p_value = if variance <= 0.0 {
+        1.0
+    } else {
+        let z = (u - n1 * n2 / 2.0).abs() / variance.sqrt();
+        (2.0 * (1.0 - normal_cdf(z))).min(1.0)
+    };
+    (u, p_value, u / (n1 * n2))
+}
+
+/// Magnitude of an A12 effect size (Vargha and Delaney's thresholds)
+pub fn effect_size(a12: f64) -> &'static str {
+    match (a12 - 0.5).abs() {
+        d if d < 0.06 => "negligible",
+        d if d < 0.14 => "small",
+        d if d < 0.21 => "medium",
+        _ => "large",
+    }
+}
+
+pub fn compare(metric: &str, a: &[f64], b: &[f64]) -> Comparison {
+    let (u, p_value, a12) = mann_whitney(a, b);
+    Comparison {
+        metric: metric.to_string(),
+        median_a: median(a),
+        median_b: median(b),
+        u,
+        p_value,
+        a12,
+    }
+}
+
+/// Comparisons of the coverage and of the time to each bug class found by either configuration
+pub fn compare_runs(a: &[RunResult], b: &[RunResult], budget: u64) -> Vec<Comparison> {
+    let metric = |runs: &[RunResult], f: &dyn Fn(&RunResult) -> f64| runs.iter().map(f).collect::<Vec<_>>();
+    let mut comparisons = vec![
+        compare("branches", &metric(a, &|r| r.branches as f64), &metric(b, &|r| r.branches as f64)),
+        compare("instructions", &metric(a, &|r| r.instructions as f64), &metric(b, &|r| r.instructions as f64)),
+    ];
+    let bugs = a
+        .iter()
+        .chain(b.iter())
+        .flat_map(|r| r.time_to_bug.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    for bug in bugs {
+        let time = |r: &RunResult| *r.time_to_bug.get(&bug).unwrap_or(&budget) as f64;
+        comparisons.push(compare(&format!("time to {}", bug), &metric(a, &time), &metric(b, &time)));
+    }
+    comparisons
+}
+
+/// Coverage and time to the bugs of a finished run
+fn collect_run(dir: &Path, seed: u64) -> Result<RunResult, String> {
+    let coverage = load_run(&dir.join("replay").to_string_lossy())?;
+    let manifest: serde_json::Value = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .ok_or(format!("no manifest in {}", dir.display()))?;
+    let start = manifest["start_time"].as_u64().unwrap_or(0);
+    let findings: Vec<Finding> = fs::read_to_string(dir.join("summary.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let mut time_to_bug: BTreeMap<String, u64> = BTreeMap::new();
+    for finding in findings {
+        let elapsed = finding.found_at.saturating_sub(start);
+        let time = time_to_bug.entry(finding.class.title.clone()).or_insert(elapsed);
+        *time = (*time).min(elapsed);
+    }
+    Ok(RunResult {
+        seed,
+        branches: coverage.branch_count(),
+        instructions: coverage.pc_count(),
+        time_to_bug,
+    })
+}
+
+fn run_child(args: &[String]) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    match Command::new(&exe).args(args).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("exited with {}", status)),
+        Err(e) => Err(format!("failed to start: {}", e)),
+    }
+}
+
+fn render(comparisons: &[Comparison], runs: usize) -> String {
+    let mut report = format!("[experiment] {} runs per configuration\n", runs);
+    report.push_str("metric | median A | median B | U | p-value | A12\n");
+    for c in comparisons {
+        report.push_str(&format!(
+            "{} | {} | {} | {} | {:.4} | {:.3} ({})\n",
+            c.metric,
+            c.median_a,
+            c.median_b,
+            c.u,
+            c.p_value,
+            c.a12,
+            effect_size(c.a12)
+        ));
+    }
+    report
+}
+
+/// Common arguments without the work directory, the work directory and the time budget
+fn split_common(common: &[String], default_work_dir: &str) -> Result<(Vec<String>, String, u64), String> {
+    let mut common = common.to_vec();
+    let work_dir = match common.iter().position(|arg| arg == "--work-dir") {
+        Some(idx) if idx + 1 < common.len() => {
+            common.remove(idx);
+            common.remove(idx)
+        }
+        _ => default_work_dir.to_string(),
+    };
+    let budget = match common.iter().position(|arg| arg == "--time-budget") {
+        Some(idx) => common
+            .get(idx + 1)
+            .and_then(|budget| budget.parse::<u64>().ok())
+            .ok_or("invalid --time-budget")?,
+        None => {
+            common.extend(["--time-budget".to_string(), QUICK_TIME_BUDGET.to_string()]);
+            QUICK_TIME_BUDGET
+        }
+    };
+    if has_flag(&common, "--seed", None) || has_flag(&common, "--replay-file", None) {
+        return Err("--seed and --replay-file are set by the experiment".to_string());
+    }
+    Ok((common, work_dir, budget))
+}
+
+/// Run a configuration `runs` times in `<work_dir>/<name>-<run>` and collect the runs
+fn run_config(name: &str, config: &str, runs: usize, work_dir: &str, common: &[String]) -> Result<Vec<RunResult>, String> {
+    let mut samples = vec![];
+    for run in 1..=runs {
+        let seed = run as u64;
+        let dir = Path::new(work_dir).join(format!("{}-{}", name, run));
+        let mut args = common.to_vec();
+        args.extend(config.split_whitespace().map(|arg| arg.to_string()));
+        info!("[experiment] {} run {}/{}: {}", name.to_uppercase(), run, runs, dir.display());
+        let mut fuzz = args.clone();
+        fuzz.extend(["--seed".to_string(), seed.to_string(), "--work-dir".to_string(), dir.to_string_lossy().to_string()]);
+        run_child(&fuzz).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        // the coverage is measured by replaying the corpus
+        let mut replay = args;
+        replay.extend([
+            "--work-dir".to_string(),
+            dir.join("replay").to_string_lossy().to_string(),
+            "--replay-file".to_string(),
+            dir.join(CORPUS_DIR).join("*_replayable").to_string_lossy().to_string(),
+        ]);
+        run_child(&replay).map_err(|e| format!("{} replay: {}", dir.display(), e))?;
+        samples.push(collect_run(&dir, seed)?);
+    }
+    Ok(samples)
+}
+
+/// Run the experiment and return the comparison of the two configurations
+pub fn run_experiment(runs: usize, config_a: &str, config_b: &str, common: &[String]) -> Result<String, String> {
+    let (common, work_dir, budget) = split_common(common, "experiment")?;
+    let mut results = BTreeMap::new();
+    for (name, config) in [("a", config_a), ("b", config_b)] {
+        results.insert(name, run_config(name, config, runs, &work_dir, &common)?);
+    }
+
+    let comparisons = compare_runs(&results["a"], &results["b"], budget);
+    let json = serde_json::json!({
+        "a": config_a,
+        "b": config_b,
+        "runs": results,
+        "time_budget": budget,
+        "comparisons": comparisons,
+    });
+    let path = Path::new(&work_dir).join("experiment.json");
+    fs::write(&path, serde_json::to_string_pretty(&json).unwrap())
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(render(&comparisons, runs))
+}
+
+/// Medians of a schedule and its comparisons with the baseline
+fn render_schedules(results: &BTreeMap<String, (Vec<RunResult>, Vec<Comparison>)>, runs: usize) -> String {
+    let baseline = BENCH_SCHEDULES[0].to_string();
+    let mut report = format!("[bench] {} runs per schedule, compared with {}\n", runs, baseline);
+    report.push_str("schedule | median branches | median instructions | A12 branches\n");
+    for schedule in BENCH_SCHEDULES.iter().map(|s| s.to_string()) {
+        let (samples, comparisons) = match results.get(&schedule) {
+            Some(result) => result,
+            None => continue,
+        };
+        let branches = samples.iter().map(|r| r.branches as f64).collect::<Vec<_>>();
+        let instructions = samples.iter().map(|r| r.instructions as f64).collect::<Vec<_>>();
+        report.push_str(&format!(
+            "{} | {} | {} | {:.3} ({})\n",
+            schedule,
+            median(&branches),
+            median(&instructions),
+            comparisons[0].a12,
+            effect_size(comparisons[0].a12)
+        ));
+    }
+    report
+}
+
+/// Run every power schedule and return their comparison with the baseline
+pub fn run_schedule_bench(runs: usize, common: &[String]) -> Result<String, String> {
+    let (common, work_dir, budget) = split_common(common, "bench")?;
+    if has_flag(&common, "--power-schedule", None) {
+        return Err("--power-schedule is set by the bench".to_string());
+    }
+    let mut samples = BTreeMap::new();
+    for schedule in BENCH_SCHEDULES {
+        let name = schedule.to_string();
+        let config = format!("--power-schedule {}", name);
+        samples.insert(name.clone(), run_config(&name, &config, runs, &work_dir, &common)?);
+    }
+    let baseline = &samples[&BENCH_SCHEDULES[0].to_string()];
+    let results = samples
+        .iter()
+        .map(|(name, runs)| (name.clone(), (runs.clone(), compare_runs(runs, baseline, budget))))
+        .collect::<BTreeMap<_, _>>();
+    let json = serde_json::json!({
+        "baseline": BENCH_SCHEDULES[0].to_string(),
+        "time_budget": budget,
+        "schedules": results
+            .iter()
+            .map(|(name, (runs, comparisons))| (name.clone(), serde_json::json!({ "runs": runs, "comparisons": comparisons })))
+            .collect::<serde_json::Map<_, _>>(),
+    });
+    let path = Path::new(&work_dir).join("schedules.json");
+    fs::write(&path, serde_json::to_string_pretty(&json).unwrap())
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(render_schedules(&results, runs))
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mann_whitney() {
+        let (u, p_value, a12) = mann_whitney(&[1.0, 2.0, 3.0, 4.0, 5.0], &[6.0, 7.0, 8.0, 9.0, 10.0]);
+        assert_eq!(u, 0.0);
+        assert_eq!(a12, 0.0);
+        // exact p-value 0.0079, the normal approximation gives 0.009
+        assert!(p_value > 0.005 && p_value < 0.015);
+        assert_eq!(effect_size(a12), "large");
+
+        let (_, p_value, a12) = mann_whitney(&[3.0, 3.0, 3.0], &[3.0, 3.0, 3.0]);
+        assert_eq!((p_value, a12), (1.0, 0.5));
+
+        let a = vec![RunResult { branches: 10, ..Default::default() }];
+        let b = vec![RunResult {
+            branches: 8,
+            time_to_bug: BTreeMap::from([("Assert violation".to_string(), 30)]),
+            ..Default::default()
+        }];
+        let comparisons = compare_runs(&a, &b, 300);
+        assert_eq!(comparisons[0].a12, 1.0);
+        assert_eq!(comparisons[2].metric, "time to Assert violation");
+        assert_eq!((comparisons[2].median_a, comparisons[2].median_b), (300.0, 30.0));
+
+        let results = BTreeMap::from([("fast".to_string(), (a.clone(), compare_runs(&a, &b, 300)))]);
+        assert!(render_schedules(&results, 1).contains("fast | 10 | 0 | 1.000 (large)"));
+    }
+}
@@ -4,6 +4,8 @@ use crate::evm::types::EVMU256;
 
 use crate::generic_vm::vm_executor::{GenericVM, MAP_SIZE};
 use crate::evm::host::{BRANCH_DISTANCE_INTERESTING, BRANCH_DISTANCE_CHANGED};
+use crate::evm::directed::{take_directed_progress, DIRECTED_INTERESTING};
+use crate::evm::call_graph::{take_call_graph_progress, CALL_GRAPH_INTERESTING};
 use crate::generic_vm::vm_state::VMStateT;
 use crate::input::VMInputT;
 use crate::oracle::{Oracle, OracleCtx, Producer};
@@ -28,6 +30,7 @@ use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::rc::Rc;
+use tracing::debug;
 
 /// OracleFeedback is a wrapper around a set of oracles and producers.
 /// It executes the producers and then oracles after each successful execution. If any of the oracle
@@ -462,15 +465,33 @@ where
         if cmp_interesting {
             self.scheduler
                 .vote(state.get_infant_state_state(), input.get_state_idx());
-            println!("less distance in CPU");
+            debug!("less distance in CPU");
         }
         unsafe { BRANCH_DISTANCE_INTERESTING = cmp_interesting; }
 
+        // if the execution got closer to the directed target, vote for the state
+        let directed_interesting = take_directed_progress();
+        if directed_interesting {
+            self.scheduler
+                .vote(state.get_infant_state_state(), input.get_state_idx());
+            debug!("closer to the directed target in CPU");
+        }
+        unsafe { DIRECTED_INTERESTING = directed_interesting; }
+
+        // if the execution took a new inter-contract call edge, vote for the state
+        let call_graph_interesting = take_call_graph_progress();
+        if call_graph_interesting {
+            self.scheduler
+                .vote(state.get_infant_state_state(), input.get_state_idx());
+            debug!("new call edge in CPU");
+        }
+        unsafe { CALL_GRAPH_INTERESTING = call_graph_interesting; }
+
         // if coverage has increased, vote for the state
         if cov_interesting {
             self.scheduler
                 .vote(state.get_infant_state_state(), input.get_state_idx());
-            println!("new coverage in CPU");
+            debug!("new coverage in CPU");
         }
 
         // unsafe {
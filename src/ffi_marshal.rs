@@ -0,0 +1,212 @@
+//! Checked marshalling of the payloads handed to the GPU runner
+//!
+//! The runner takes lengths and counts as `u32` and copies seeds into buffers of [`SEED_SIZE`]
+//! bytes, so a `len as u32` on a huge storage snapshot or calldata silently truncates what the
+//! kernel executes, and its results no longer match the input they are attributed to. The
+//! payloads are checked here instead: an input that does not fit is executed on revm, and the
+//! rejections are reported by kind (the first one with a warning, then every
+//! [`MARSHAL_WARN_INTERVAL`]).
+use crate::evm::config::SEED_SIZE;
+use crate::evm::types::EVMU256;
+use crate::evm::vm::{EVMStorage, PostExecutionCtx};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use tracing::warn;
+
+/// Caller (32 bytes, padded) and call value preceding the calldata in a seed
+pub const SEED_HEADER_SIZE: usize = 68;
+
+/// Bytes of a storage slot handed to the runner, key then value (little endian)
+pub const SLOT_SIZE: usize = 64;
+
+pub const MARSHAL_WARN_INTERVAL: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarshalError {
+    /// The length of a payload does not fit the `u32` of the FFI call
+    LengthOverflow { what: &'static str, len: usize },
+    /// The calldata does not fit a seed buffer of the runner
+    SeedTooLarge { calldata: usize, max: usize },
+    /// The runner cannot resume the frame of a stepped input at this PC
+    ContinuationRejected { pc: usize },
+}
+
+impl Display for MarshalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarshalError::LengthOverflow { what, len } => {
+                write!(f, "{} of length {} exceed the u32 length of the runner", what, len)
+            }
+            MarshalError::SeedTooLarge { calldata, max } => write!(
+                f,
+                "calldata of {} bytes exceeds the seed buffer of the runner ({} bytes), increase SEED_SIZE",
+                calldata, max
+            ),
+            MarshalError::ContinuationRejected { pc } => {
+                write!(f, "the runner cannot resume a control leak at pc {:#x}", pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MarshalError {}
+
+impl MarshalError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarshalError::LengthOverflow { what, .. } => what,
+            MarshalError::SeedTooLarge { .. } => "seed",
+            MarshalError::ContinuationRejected { .. } => "continuation",
+        }
+    }
+}
+
+/// Length of a payload as passed to the runner
+pub fn ffi_len(what: &'static str, len: usize) -> Result<u32, MarshalError> {
+    u32::try_from(len).map_err(|_| MarshalError::LengthOverflow { what, len })
+}
+
+/// Size of the calldata of a seed, checked against the seed buffer
+pub fn seed_len(calldata: usize) -> Result<u32, MarshalError> {
+    let max = SEED_SIZE - SEED_HEADER_SIZE;
+    if calldata > max {
+        return Err(MarshalError::SeedTooLarge { calldata, max });
+    }
+    ffi_len("calldata", calldata)
+}
+
+/// Slots of a storage as handed to `cuLoadStorage`, and their count
+pub fn marshal_storage(storage: &EVMStorage) -> Result<(Vec<u8>, u32), MarshalError> {
+    let count = ffi_len("storage slots", storage.len())?;
+    ffi_len("storage bytes", storage.len().saturating_mul(SLOT_SIZE))?;
+    let mut bytes = Vec::with_capacity(storage.len() * SLOT_SIZE);
+    for (key, value) in storage {
+        bytes.extend_from_slice(key.as_le_bytes().as_ref());
+        bytes.extend_from_slice(value.as_le_bytes().as_ref());
+    }
+    Ok((bytes, count))
+}
+
+/// Frame resumed by the threads of a batch fuzzing a stepped input, as handed to
+/// `cuLoadContinuation`. The seed of the threads is the return buffer of the leaking call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Continuation {
+    pub pc: u32,
+    /// Words of the stack (little endian) from the bottom, the success of the leaking call on top
+    pub stack: Vec<u8>,
+    pub stack_len: u32,
+    /// Memory of the frame, large enough for the return buffer
+    pub memory: Vec<u8>,
+    pub memory_len: u32,
+    pub call_data: Vec<u8>,
+    pub call_data_len: u32,
+    pub output_offset: u32,
+    pub output_len: u32,
+}
+
+/// Frame of a post execution context, as resumed on revm by `execute_from_pc`
+pub fn marshal_continuation(ctx: &PostExecutionCtx) -> Result<Continuation, MarshalError> {
+    let pc = ffi_len("pc", ctx.pc)?;
+    let output_offset = ffi_len("output offset", ctx.output_offset)?;
+    let output_len = ffi_len("output length", ctx.output_len)?;
+    let memory_size = ctx.memory.len().max(ctx.output_offset.saturating_add(ctx.output_len));
+    let memory_len = ffi_len("memory", memory_size)?;
+    let call_data_len = ffi_len("call data", ctx.call_data.len())?;
+    let stack_len = ffi_len("stack", ctx.stack.len() + 1)?;
+
+    let mut stack = Vec::with_capacity((ctx.stack.len() + 1) * 32);
+    for word in ctx.stack.iter().chain(std::iter::once(&EVMU256::from(1))) {
+        stack.extend_from_slice(word.as_le_bytes().as_ref());
+    }
+    let mut memory = ctx.memory.clone();
+    memory.resize(memory_size, 0);
+    Ok(Continuation {
+        pc,
+        stack,
+        stack_len,
+        memory,
+        memory_len,
+        call_data: ctx.call_data.to_vec(),
+        call_data_len,
+        output_offset,
+        output_len,
+    })
+}
+
+static mut MARSHAL_ERRORS: Option<HashMap<&'static str, u64>> = None;
+
+/// Count a rejected input, warning on the first one of each kind and then periodically
+pub fn report_marshal_error(e: &MarshalError) {
+    let errors = unsafe { MARSHAL_ERRORS.get_or_insert_with(HashMap::new) };
+    let count = errors.entry(e.kind()).or_insert(0);
+    *count += 1;
+    if *count == 1 || *count % MARSHAL_WARN_INTERVAL == 0 {
+        warn!("[gpu] {} ({} inputs executed on revm instead)", e, count);
+    }
+}
+
+/// Inputs rejected so far, by kind
+pub fn marshal_errors() -> HashMap<&'static str, u64> {
+    unsafe { MARSHAL_ERRORS.clone().unwrap_or_default() }
+}
+
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_marshalling() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10000 {
+            // lengths around the limits
+            let len: usize = match rng.gen_range(0..3) {
+                0 => rng.gen_range(0..SEED_SIZE * 2),
+                1 => u32::MAX as usize - 100 + rng.gen_range(0..200),
+                _ => rng.gen(),
+            };
+            match ffi_len("calldata", len) {
+                Ok(l) => assert_eq!(l as usize, len),
+                Err(e) => {
+                    assert!(len > u32::MAX as usize);
+                    assert_eq!(e, MarshalError::LengthOverflow { what: "calldata", len });
+                }
+            }
+            match seed_len(len) {
+                Ok(l) => assert!(l as usize == len && len + SEED_HEADER_SIZE <= SEED_SIZE),
+                Err(MarshalError::SeedTooLarge { calldata, .. }) => assert!(calldata + SEED_HEADER_SIZE > SEED_SIZE),
+                Err(e) => panic!("unexpected {}", e),
+            }
+        }
+
+        let storage = (0..100u64)
+            .map(|i| (EVMU256::from(i), EVMU256::from(rand::random::<u64>())))
+            .collect::<EVMStorage>();
+        let (bytes, count) = marshal_storage(&storage).unwrap();
+        assert_eq!(count, 100);
+        assert_eq!(bytes.len(), 100 * SLOT_SIZE);
+        for slot in bytes.chunks(SLOT_SIZE) {
+            let key = EVMU256::from_le_slice(&slot[..32]);
+            assert_eq!(EVMU256::from_le_slice(&slot[32..]), storage[&key]);
+        }
+
+        let ctx = PostExecutionCtx {
+            stack: vec![EVMU256::from(7), EVMU256::from(0x44)],
+            memory: vec![0xaa; 0x40],
+            pc: 0x1234,
+            output_offset: 0x80,
+            output_len: 0x20,
+            call_data: Default::default(),
+            address: Default::default(),
+            caller: Default::default(),
+            code_address: Default::default(),
+            apparent_value: EVMU256::ZERO,
+        };
+        let continuation = marshal_continuation(&ctx).unwrap();
+        assert_eq!((continuation.pc, continuation.stack_len), (0x1234, 3));
+        // the leaking call succeeded
+        assert_eq!(EVMU256::from_le_slice(&continuation.stack[64..]), EVMU256::from(1));
+        assert_eq!(continuation.memory_len, 0xa0);
+        assert_eq!(continuation.memory[..0x40], ctx.memory[..]);
+        assert!(continuation.memory[0x40..].iter().all(|b| *b == 0));
+    }
+}
@@ -18,7 +18,7 @@ use core::{
 
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::Path;
-use std::process::exit;
+use tracing::{info, warn};
 use std::{marker::PhantomData, time::Duration};
 
 use crate::evm::vm::EVMState;
@@ -45,16 +45,33 @@ use nix::sys::stat;
 use revm_primitives::bitvec::macros::internal::funty::{Fundamental, Numeric};
 use serde_traitobject::Any;
 
-use crate::evm::host::{JMP_MAP, BRANCH_DISTANCE_INTERESTING, EXPLORED_INS, EXPLORED_EDGE,};
+use crate::evm::branch_coverage::branches_covered;
+use crate::evm::host::{JMP_MAP, BRANCH_DISTANCE_INTERESTING, EXPLORED_INS,};
+use crate::evm::directed::DIRECTED_INTERESTING;
+use crate::evm::call_graph::{save_call_graph, CALL_GRAPH_INTERESTING, CALL_GRAPH_SAVE_INTERVAL};
+use crate::scheduler::record_contribution;
+use crate::evm::dictionary::{save_dictionary, DICTIONARY_SAVE_INTERVAL};
 use crate::evm::types::EVMU256;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::hash::{Hash, Hasher};
 
+use crate::corpus_journal::persist_file;
+use crate::host_mem::{self, account, describe_host_mem, flush_host_mem_stats};
+use crate::evm::corpus_format::{CorpusFile, InputMeta};
+use crate::evm::state_diff::save_snapshot;
 use crate::evm::input::EVMInput;
+use crate::evm::solution_dedup::is_duplicate_solution;
+use crate::evm::summary::{env_dependent, function_name, print_summary, record_coverage, record_finding};
+use crate::evm::revert_stats::{report_revert_stats, REVERT_STATS_INTERVAL};
+use crate::evm::opcode_stats::{report_opcode_stats, OPCODE_STATS_INTERVAL};
+use crate::evm::scenario::{print_scenario_stats, scenario_count, scenario_metadata_mut, scenario_name};
 
 const STATS_TIMEOUT_DEFAULT: Duration = Duration::from_millis(4000);
-use crate::evm::config::{RUN_FOREVER, DUMP_CORPUS};
+use crate::evm::config::{RUN_FOREVER, DUMP_CORPUS, TIME_BUDGET};
+#[cfg(feature = "grpc")]
+use crate::remote;
+use crate::workdir::save_solution;
 
 /// A fuzzer that implements ItyFuzz logic using LibAFL's [`Fuzzer`] trait
 ///
@@ -192,6 +209,34 @@ where
     }
 }
 
+/// Drop the VM states the inputs of the corpus carry, returns how many. The mutator binds an
+/// infant state to an input without one when it is picked again. The inputs resuming a control
+/// leak and the one being fuzzed keep theirs.
+fn drop_staged_states<VS, Loc, Addr, I, S>(state: &mut S) -> Result<usize, Error>
+where
+    I: Input + VMInputT<VS, Loc, Addr>,
+    S: HasCorpus<I>,
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+{
+    let current = *state.corpus().current();
+    let mut dropped = 0;
+    for idx in 0..state.corpus().count() {
+        if Some(idx) == current {
+            continue;
+        }
+        let mut testcase = state.corpus().get(idx)?.borrow_mut();
+        if let Some(input) = testcase.input_mut() {
+            if !input.is_step() && input.get_staged_state().initialized {
+                input.set_staged_state(StagedVMState::new_uninitialized(), 0);
+                dropped += 1;
+            }
+        }
+    }
+    Ok(dropped)
+}
+
 /// Implement fuzzer trait for ItyFuzzer
 impl<'a, VS, Loc, Addr, Out, CS, IS, E, EM, F, IF, I, OF, S, ST, OT> Fuzzer<E, EM, I, S, ST>
     for ItyFuzzer<'a, VS, Loc, Addr, Out, CS, IS, F, IF, I, OF, S, OT>
@@ -255,22 +300,20 @@ where
         // TODO: if the idx input is a concolic input returned by the solver
         // we should not perform all stages.
 
-        stages
-            .perform_all(self, executor, state, manager, idx)
-            .expect("perform_all failed");
+        stages.perform_all(self, executor, state, manager, idx)?;
         manager.process(self, state, executor)?;
-        // println!("state.corpus() = {:?}", state.corpus().count());
+        // debug!("state.corpus() = {:?}", state.corpus().count());
         // for ttt in 0..state.corpus().count() {
         //     let input = state.corpus().get(ttt)?.clone().into_inner().input().as_ref().unwrap().clone();
         //     input.get_state().get_s(&input.get_evm_contract());
-        //     println!("input => {:?}", input.pretty_txn());
+        //     debug!("input => {:?}", input.pretty_txn());
         // }
-        // println!("----------------------------------------------------");
+        // debug!("----------------------------------------------------");
         // exit(0);
         Ok(idx)
     }
 
-    /// Fuzz loop
+    /// Fuzz loop, until a stop condition unwinds it with [`Error::ShuttingDown`]
     fn fuzz_loop(
         &mut self,
         stages: &mut ST,
@@ -281,9 +324,28 @@ where
         let mut last = current_time();
         // now report stats to manager every 0.1 sec
         let monitor_timeout = STATS_TIMEOUT_DEFAULT;
+        let start = current_time();
         loop {
             self.fuzz_one(stages, executor, state, manager)?;
             last = manager.maybe_report_progress(state, last, monitor_timeout)?;
+            let budget = unsafe { TIME_BUDGET };
+            if budget > 0 && (current_time() - start).as_secs() >= budget {
+                info!("Time budget of {}s exhausted", budget);
+                print_summary(state);
+                flush_host_mem_stats();
+                return Err(Error::ShuttingDown);
+            }
+            account("input corpus", state.corpus().count());
+            if host_mem::should_shrink("input corpus") {
+                let dropped = drop_staged_states::<VS, Loc, Addr, I, S>(state)?;
+                warn!("[host-mem] memory running short, dropped the VM states of {} corpus inputs", dropped);
+            }
+            if host_mem::exhausted() {
+                warn!("Host memory budget exhausted despite pruning, stopping: {}", describe_host_mem());
+                print_summary(state);
+                flush_host_mem_stats();
+                return Err(Error::ShuttingDown);
+            }
         }
     }
 
@@ -303,7 +365,7 @@ where
     //     let initial_corpus_size = state.corpus().count();
     //     // let mut vm_state = StagedVMState::new_with_state(EVMState::new());
     //     for idx in 0..initial_corpus_size {
-    //         println!("-------------------- idx = {:?}; size = {:?} --------------- ", idx, state.corpus().count());
+    //         debug!("-------------------- idx = {:?}; size = {:?} --------------- ", idx, state.corpus().count());
     //         state.set_current_input_idx(idx);
     //         let mut input = state.corpus().get(idx)?.clone().into_inner().input().as_ref().unwrap().clone();
     //         if !input.get_staged_state().initialized {
@@ -313,7 +375,7 @@ where
     //         let _ = self.evaluate_input_events(state, executor, manager, input, false);
     //         // input.get_state().get_s(&input.get_evm_contract());
     //     }
-    //     println!("===================================== GPU Enabled =======================================");
+    //     debug!("===================================== GPU Enabled =======================================");
     //     #[link(name = "runner")]
     //     extern "C" {
     //         fn cuLoadSeed(value_ptr: *const u8, data_ptr: *const u8, data_size: u32, thread: u32);
@@ -327,7 +389,7 @@ where
     //         let corpus_size = state.corpus().count();
     //         let wrap_count = min(corpus_size, NJOBS as usize);
     //         let slot_count = NJOBS as usize / wrap_count;
-    //         // println!("Corpus Size {:?}; wrap_count {:?}; slot_count {:?}", corpus_size, wrap_count, slot_count);
+    //         // debug!("Corpus Size {:?}; wrap_count {:?}; slot_count {:?}", corpus_size, wrap_count, slot_count);
 
     //         // Fuzz one batch of inputs
     //         let mut input_vec: Vec<EVMInput> = Vec::new();
@@ -336,7 +398,7 @@ where
     //         for wrap_i in 0..wrap_count {
     //             let corpus_idx = self.scheduler.next(state)?;
 
-    //             // println!("corpus_idx = {:?}; corpus_size = {:?}", corpus_idx, corpus_size);
+    //             // debug!("corpus_idx = {:?}; corpus_size = {:?}", corpus_idx, corpus_size);
     //             state.set_current_input_idx(corpus_idx);
     //             // let corpus_item = state.corpus().get(corpus_idx)?;
     //             // let testcase = corpus_item.clone().into_inner();
@@ -366,8 +428,8 @@ where
     //                 let concrete = state.get_infant_state(self.infant_scheduler).unwrap();
     //                 input.set_staged_state(concrete.1, concrete.0);
     //             }
-    //             // println!("\n\ncorpus[{:?}] initiated={:?}  = {:?}", cidx, input.get_staged_state().initialized, input);
-    //             // println!("===> current new get_data_abi => {:?}", input.get_data_abi());
+    //             // debug!("\n\ncorpus[{:?}] initiated={:?}  = {:?}", cidx, input.get_staged_state().initialized, input);
+    //             // debug!("===> current new get_data_abi => {:?}", input.get_data_abi());
     //             // input.get_state().get_s(&input.get_evm_contract());
 
     //             if wrap_i == 0 {
@@ -375,10 +437,10 @@ where
     //             }
     //             if let Some(storage) = input.as_any().downcast_ref::<EVMInput>().unwrap().get_state().get(&input.get_evm_contract()) {
     //                 let mut bytes = Vec::new();
-    //                 // println!("storage content before executing input=>");
+    //                 // debug!("storage content before executing input=>");
     //                 for (key, value) in storage {
     //                     // for (key, value) in storage {
-    //                     //     println!("{:#x}: {:#x}", key, value);
+    //                     //     debug!("{:#x}: {:#x}", key, value);
     //                     // }
     //                     let slot = [key.as_le_bytes(), value.as_le_bytes()].concat();
     //                     bytes.extend(slot);
@@ -401,7 +463,7 @@ where
     //                         Some(ref abi) => abi.get_bytes(), // function hash + encoded args
     //                     };
     //                 let calldatasize = calldata.len();
-    //                 // println!("tid#{:?} calldata:=> {:?}\n", tid, hex::encode(calldata.clone()));     
+    //                 // debug!("tid#{:?} calldata:=> {:?}\n", tid, hex::encode(calldata.clone()));     
     //                 input_vec.push(input.as_any().downcast_ref::<EVMInput>().unwrap().clone());
 
     //                 let callvalue: [u8; 32] = input.get_txn_value().unwrap_or(EVMU256::ZERO).to_le_bytes();
@@ -427,12 +489,12 @@ where
     //             let thread_input = tmp_in.as_any_mut().downcast_ref::<I>().unwrap().clone();
     //             let hnb : ExecuteCudaInputResult = unsafe { 
     //                 let r = isCudaInteresting(thread_id as u32);
-    //                 // println!("hnb[{:?}] = {:?}", thread_id, r);
+    //                 // debug!("hnb[{:?}] = {:?}", thread_id, r);
     //                 std::mem::transmute(r)
     //             };
     //             match hnb {
     //                 ExecuteCudaInputResult::EXECNONE => {
-    //                     // println!("============= EXEC NONE Seed =============");
+    //                     // debug!("============= EXEC NONE Seed =============");
     //                     let _ = self.evaluate_input_events(state, executor, manager, thread_input, true);
     //                     continue;
     //                 }
@@ -443,7 +505,7 @@ where
     //                     // unsafe{ cuDumpStorage(thread_id as u32); }
     //                     #[cfg(feature = "print_txn_corpus")]
     //                     {   
-    //                         println!("[bug] bug() hit at {:?}", thread_id);
+    //                         info!("[bug] bug() hit at {:?}", thread_id);
     //                         let txn = build_basic_txn(&thread_input, &state.get_execution_result());
     //                         state.get_execution_result_mut().new_state.trace.from_idx = Some(thread_input.get_state_idx());
     //                         state
@@ -455,9 +517,9 @@ where
     //                             unsafe {ORACLE_OUTPUT.clone()},
     //                         );
     //                         unsafe {
-    //                             println!("Oracle: {}", ORACLE_OUTPUT);
+    //                             info!("Oracle: {}", ORACLE_OUTPUT);
     //                         }
-    //                         println!(
+    //                         info!(
     //                             "Found a solution! trace: {}",
     //                             state
     //                                 .get_execution_result()
@@ -478,10 +540,10 @@ where
     //                 ExecuteCudaInputResult::EXECINTERESTING => {
     //                     // #[cfg(feature = "print_txn_corpus")]
     //                     // {
-    //                         // println!("======== New Corpus Item From GPU#{:?} ========", thread_id);
+    //                         // debug!("======== New Corpus Item From GPU#{:?} ========", thread_id);
     //                         // unsafe{ cuDumpStorage(thread_id as u32); }
     //                         // thread_input.get_state().get_s(&thread_input.get_evm_contract());
-    //                         // println!("==========================================");
+    //                         // debug!("==========================================");
     //                     // }
     //                     // add the cuda-varient to the corpus
     //                     // let mut cu_tc = Testcase::new(thread_input.clone()) as Testcase<I>;
@@ -516,6 +578,8 @@ pub enum ExecuteCudaInputResult {
     EXECBUGGY,
     /// mininer distance
     EXECLESSDISTANCE, 
+    /// Reached an external call whose target is loaded from the calldata (control leak)
+    EXECCONTROLLEAK,
 }
 
 // implement evaluator trait for ItyFuzzer
@@ -530,7 +594,7 @@ where
     E: Executor<EM, I, S, Self> + HasObservers<I, OT, S>,
     OT: ObserversTuple<I, S> + serde::Serialize + serde::de::DeserializeOwned,
     EM: EventManager<E, I, S, Self>,
-    I: VMInputT<VS, Loc, Addr>,
+    I: VMInputT<VS, Loc, Addr> + EVMInputT,
     OF: Feedback<I, S>,
     S: HasClientPerfMonitor
         + HasCorpus<I>
@@ -538,9 +602,10 @@ where
         + HasInfantStateState<Loc, Addr, VS>
         + HasItyState<Loc, Addr, VS>
         + HasExecutionResult<Loc, Addr, VS, Out>
-        + HasExecutions,
+        + HasExecutions
+        + HasMetadata,
     VS: Default + VMStateT,
-    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Addr: Serialize + DeserializeOwned + Debug + Clone + Into<EVMAddress>,
     Loc: Serialize + DeserializeOwned + Debug + Clone,
     Out: Default,
 {
@@ -554,7 +619,7 @@ where
         send_events: bool,
     ) -> Result<(ExecuteInputResult, Option<usize>), Error> {
         // {
-        //     println!("============= evaluating in EVM again =============");
+        //     debug!("============= evaluating in EVM again =============");
         //     let tx_trace = state
         //         .get_execution_result()
         //         .new_state
@@ -566,7 +631,7 @@ where
         //         state.get_execution_result().reverted,
         //         txn_text
         //     );
-        //     println!("{}", data);
+        //     debug!("{}", data);
         //     // input.get_state().get_s(&input.get_evm_contract());
         // }
         start_timer!(state);
@@ -585,6 +650,32 @@ where
             .post_exec_all(state, &input, &exitkind)?;
         mark_feature_time!(state, PerfFeature::PostExecObservers);
 
+        // attribute the execution to the scenario preset of the input
+        if scenario_count() > 0 {
+            scenario_metadata_mut(state).stats_mut(input.get_scenario()).executions += 1;
+        }
+
+        if *state.executions() % DICTIONARY_SAVE_INTERVAL == 0 {
+            save_dictionary();
+        }
+
+        if *state.executions() % REVERT_STATS_INTERVAL == 0 {
+            report_revert_stats();
+        }
+
+        if *state.executions() % OPCODE_STATS_INTERVAL == 0 {
+            report_opcode_stats();
+        }
+
+        if *state.executions() % CALL_GRAPH_SAVE_INTERVAL == 0 {
+            save_call_graph();
+        }
+
+        #[cfg(feature = "grpc")]
+        if *state.executions() % remote::STATS_INTERVAL == 0 {
+            remote::stream_stats(*state.executions(), state.corpus().count(), state.solutions().count());
+        }
+
         let observers = executor.observers();
         let reverted = state.get_execution_result().reverted;
 
@@ -592,13 +683,13 @@ where
         let is_infant_interesting = self
             .infant_feedback
             .is_interesting(state, manager, &input, observers, &exitkind)?;
-        // println!("is_infant_interesting => {:?}", is_infant_interesting);
+        // debug!("is_infant_interesting => {:?}", is_infant_interesting);
 
         let is_solution = self
             .objective
             .is_interesting(state, manager, &input, observers, &exitkind)?;
 
-        // println!("is_solution => {:?}", is_solution);
+        // debug!("is_solution => {:?}", is_solution);
         // add the trace of the new state
         #[cfg(any(feature = "print_infant_corpus", feature = "print_txn_corpus"))]
         {
@@ -617,13 +708,19 @@ where
                 &state.get_execution_result().new_state.clone(),
                 self.infant_scheduler,
             ).unwrap();
-            // println!("==========Interesting infant states #{:?} ==========", idx_infant_state);
+            if unsafe { DUMP_CORPUS } {
+                let new_state = &state.get_execution_result().new_state.state;
+                if let Some(evm_state) = new_state.as_any().downcast_ref::<EVMState>() {
+                    save_snapshot(idx_infant_state, Some(input.get_state_idx()), &evm_state.state);
+                }
+            }
+            // debug!("==========Interesting infant states #{:?} ==========", idx_infant_state);
             // load initial storage one by one (heavy mode)
             // #[cfg(feature = "cuda_snapshot_storage")] 
             // {   
-            //     // println!("changed => len = {:?}: {:?}", a.len(), a);
+            //     // debug!("changed => len = {:?}: {:?}", a.len(), a);
             //     // let k = state.get_execution_result().new_state.clone();
-            //     // println!("==========Interesting infant states ========== \n{:?}\n------\n{:?}\n==========", k.state.as_any().downcast_ref::<EVMState>().unwrap());
+            //     // debug!("==========Interesting infant states ========== \n{:?}\n------\n{:?}\n==========", k.state.as_any().downcast_ref::<EVMState>().unwrap());
             //     #[link(name = "runner")]
             //     extern "C" {
             //         fn cuLoadStorage(src: *const u8, slotCnt: u32, wrapi: u32);
@@ -641,7 +738,7 @@ where
             //         .unwrap();
             //         if let Some(storage) = executed_evm_state.get(&input.get_evm_contract()) {
             //             let mut bytes = Vec::new();
-            //             // println!("========== infant state ==========\n=> {:?}\n==========", storage);
+            //             // debug!("========== infant state ==========\n=> {:?}\n==========", storage);
             //             for (key, value) in storage {
             //                 let slot = [key.as_le_bytes(), value.as_le_bytes()].concat();
             //                 bytes.extend(slot);
@@ -658,11 +755,11 @@ where
         if is_solution && !reverted {
             res = ExecuteInputResult::Solution;
         } else {
-            // println!("is_interesting begin");
+            // debug!("is_interesting begin");
             let is_corpus = self
                 .feedback
                 .is_interesting(state, manager, &input, observers, &exitkind)?;
-            // println!("is_interesting end => {:?}", is_corpus);
+            // debug!("is_interesting end => {:?}", is_corpus);
             if is_corpus {
                 res = ExecuteInputResult::Corpus;
             }
@@ -672,6 +769,14 @@ where
             // add to the corpus
             res = ExecuteInputResult::Corpus;
         }
+        // directed mode
+        if res == ExecuteInputResult::None && unsafe { DIRECTED_INTERESTING } {
+            res = ExecuteInputResult::Corpus;
+        }
+        // new inter-contract call edge
+        if res == ExecuteInputResult::None && unsafe { CALL_GRAPH_INTERESTING } {
+            res = ExecuteInputResult::Corpus;
+        }
 
         if unsafe { DUMP_CORPUS } && res != ExecuteInputResult::None {
             // Debugging prints
@@ -688,16 +793,14 @@ where
                     .clone();
                 // let txn_text = tx_trace.to_string(state);
 
-                let txn_text_replayable = tx_trace.to_file_str(state);
-
                 // let data = format!(
                 //     "Reverted? {} \n Txn: {}",
                 //     state.get_execution_result().reverted,
                 //     txn_text
                 // );
-                // println!("============= New Corpus Item =============");
-                // println!("{}", data);
-                // println!("==========================================");
+                // debug!("============= New Corpus Item =============");
+                // debug!("{}", data);
+                // debug!("==========================================");
 
                 // write to file
                 let path = Path::new(self.corpus_path.as_str());
@@ -709,18 +812,28 @@ where
                 // file.write_all(data.as_bytes()).unwrap();
                 let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos();
 
-                let mut replayable_file =
-                    File::create(format!("{}/{}_{}_replayable", self.corpus_path.as_str(), unsafe { DUMP_FILE_COUNT }, timestamp)).unwrap();
-                replayable_file.write_all(txn_text_replayable.as_bytes()).unwrap();
+                let mut txns = tx_trace.to_corpus_txns(state);
+                txns.iter_mut().for_each(|txn| txn.annotate_function());
+                // scheduling metadata of the input, restored when the corpus is loaded again
+                if let (Some(txn), Some(evm_input)) = (txns.last_mut(), input.as_any().downcast_ref::<EVMInput>()) {
+                    txn.meta = Some(InputMeta::of(evm_input));
+                }
+                let corpus_file = CorpusFile::V1 { txns };
+                let corpus_path =
+                    format!("{}/{}_{}_replayable", self.corpus_path.as_str(), unsafe { DUMP_FILE_COUNT }, timestamp);
+                // a corpus file lost does not stop the campaign, the testcase stays in memory
+                if let Err(e) = persist_file(corpus_path.as_str(), corpus_file.to_json().into_bytes()) {
+                    warn!("failed to persist {}: {}", corpus_path, e);
+                }
                 
                 let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos();
 
                 // deployment/maze-10/maze-10(0x6b773032d99fb9aad6fc267651c446fa7f9301af): 85.13% (18040) Instruction Covered, 78.28% (1824) Branch Covered 1697701037735016082
 
-                println!(
+                info!(
                     "deployment/contract/contract(0x6b773032d99fb9aad6fc267651c446fa7f9301af): 0.00% ({}) Instruction Covered, 0.00% ({}) Branch Covered {}",
                     unsafe { EXPLORED_INS },
-                    unsafe { EXPLORED_EDGE },
+                    branches_covered(),
                     timestamp,
                 );
             }
@@ -739,11 +852,11 @@ where
             //     // execute inside the GPU
             //     #[cfg(feature = "cuda")]
             //     {   
-            //         // println!("[+] Cuda enabled");
+            //         // debug!("[+] Cuda enabled");
             //         let begin_cuda_time = current_time();
             //         // if *state.executions() > 200000 && res == ExecuteInputResult::None 
             //         if calldata.len() > 0 && *state.executions() > 0 {
-            //             // println!("Cuda is trying to solve constraints...");
+            //             // debug!("Cuda is trying to solve constraints...");
             //             *state.executions_mut() += NJOBS as usize;
             //             #[link(name = "runner")]
             //             extern "C" {
@@ -769,7 +882,7 @@ where
             //                 cuEvalTxn(calldatasize as u32);
             //             }
             //             let end_cuda_execute = current_time();
-            //             // println!("executed in CUDA costs {:?}", end_cuda_execute - begin_cuda_time);
+            //             // debug!("executed in CUDA costs {:?}", end_cuda_execute - begin_cuda_time);
 
             //             unsafe {
             //                 let mut new_cuda_cov : u64 = 0; // remove
@@ -795,13 +908,13 @@ where
             //                     }
             //                     ExecuteCudaInputResult::EXECBUGGY => {
             //                         // bug detected
-            //                         println!("========= Bug detected in thread {:?} ==========", tid);
+            //                         info!("========= Bug detected in thread {:?} ==========", tid);
             //                         // unsafe{ cuDumpStorage(tid as u32); }
             //                         #[cfg(any(feature = "print_cuda_corpus"))]
             //                         {   
             //                             let mut gpu_input = cuda_input.clone();
                                         
-            //                             // println!(
+            //                             // debug!(
             //                             //     "Debug a solution! trace: {}",
             //                             //     state
             //                             //         .get_execution_result()
@@ -819,7 +932,7 @@ where
             //                                     .set_bytes(tx_bytes.to_vec());
             //                             gpu_input.set_cuda_input(vec![]); // TODO
             //                             /*let after_bytes = gpu_input.get_data_abi().unwrap().get_bytes();
-            //                             println!(
+            //                             debug!(
             //                                 "calldata:=> {:?}\nabi-data:=> {:?}\nafttdata:=> {:?}",
             //                                 hex::encode(tx_bytes.to_vec()),
             //                                 hex::encode(original_data.clone()),
@@ -838,9 +951,9 @@ where
             //                                 unsafe {ORACLE_OUTPUT.clone()},
             //                             );
             //                             unsafe {
-            //                                 println!("Oracle: {}", ORACLE_OUTPUT);
+            //                                 info!("Oracle: {}", ORACLE_OUTPUT);
             //                             }
-            //                             println!(
+            //                             info!(
             //                                 "Found a solution! trace: {}",
             //                                 state
             //                                     .get_execution_result()
@@ -864,7 +977,7 @@ where
             //                         gpu_input.set_cuda_input(vec![]); // TODO
 
             //                         // let after_bytes = gpu_input.get_data_abi().unwrap().get_bytes();
-            //                         // println!(
+            //                         // debug!(
             //                         //     "calldata:=> {:?}\nabi-data:=> {:?}\nafttdata:=> {:?}",
             //                         //     hex::encode(tx_bytes.to_vec()),
             //                         //     hex::encode(original_data.clone()),
@@ -884,9 +997,9 @@ where
             //                                 state.get_execution_result().reverted,
             //                                 txn_text
             //                             );
-            //                             println!("======== New Corpus Item From GPU#{:?} ========", tid);
-            //                             println!("{}", data);
-            //                             println!("==========================================");
+            //                             debug!("======== New Corpus Item From GPU#{:?} ========", tid);
+            //                             debug!("{}", data);
+            //                             debug!("==========================================");
             //                         }
                                     
             //                         // add the cuda-varient to the corpus
@@ -899,7 +1012,7 @@ where
             //                 }
             //             } 
             //             let end_cuda_feedback = current_time();
-            //             // println!("feedback in CUDA costs  {:?}", end_cuda_feedback - end_cuda_execute);
+            //             // debug!("feedback in CUDA costs  {:?}", end_cuda_feedback - end_cuda_execute);
             //         }
             //     }
 
@@ -935,7 +1048,19 @@ where
                 self.feedback.append_metadata(state, &mut testcase)?;
                 let idx = state.corpus_mut().add(testcase)?;
                 self.scheduler.on_add(state, idx)?;
+                // credit the staged state the input is found on, see [`crate::scheduler::PruningPolicy`]
+                record_contribution(state.get_infant_state_state(), input.get_state_idx());
                 self.on_add_corpus(&input, unsafe { &JMP_MAP }, idx);
+                record_coverage(state, function_name(&input.get_data_abi()), unsafe { &JMP_MAP });
+                if scenario_count() > 0 {
+                    scenario_metadata_mut(state).stats_mut(input.get_scenario()).corpus += 1;
+                }
+                #[cfg(feature = "grpc")]
+                remote::stream_corpus(
+                    serde_json::to_vec(&input).unwrap(),
+                    input.pretty_txn().unwrap_or_default(),
+                    scenario_name(input.get_scenario()),
+                );
 
                 // Fire the event for CLI
                 if send_events {
@@ -963,20 +1088,71 @@ where
             // find the solution
             ExecuteInputResult::Solution => {
                 unsafe {
-                    println!("Oracle: {}", ORACLE_OUTPUT);
+                    info!("Oracle: {}", ORACLE_OUTPUT);
                 }
-                println!(
-                    "Found a solution! trace: {}",
-                    state
-                        .get_execution_result()
-                        .new_state
-                        .trace
-                        .clone()
-                        .to_string(state)
+                let trace = state
+                    .get_execution_result()
+                    .new_state
+                    .trace
+                    .clone()
+                    .to_string(state);
+                let provenance = state
+                    .get_execution_result()
+                    .new_state
+                    .trace
+                    .clone()
+                    .provenance_str(state);
+                let post_state = state.get_execution_result().new_state.state.clone();
+                if let Some(post_state) = post_state.as_any().downcast_ref::<EVMState>() {
+                    if let Some(kind) = is_duplicate_solution(state, trace.as_str(), post_state) {
+                        info!("Skipping solution, {} duplicate of an earlier one", kind);
+                        self.objective.discard_metadata(state, &input)?;
+                        return Ok((ExecuteInputResult::None, None));
+                    }
+                }
+                if scenario_count() > 0 {
+                    info!("Scenario: {}", scenario_name(input.get_scenario()));
+                    scenario_metadata_mut(state).stats_mut(input.get_scenario()).bugs += 1;
+                    print_scenario_stats(state);
+                }
+                info!("Found a solution! trace: {}", trace);
+                unsafe {
+                    save_solution(
+                        ORACLE_OUTPUT.as_str(),
+                        format!(
+                            "Oracle: {}\nTrace: {}\nReplayable:\n{}\nProvenance:\n{}",
+                            ORACLE_OUTPUT,
+                            trace,
+                            state.get_execution_result().new_state.trace.clone().to_file_str(state),
+                            provenance
+                        )
+                        .as_str(),
+                    );
+                    let reproducer = state.get_execution_result().new_state.trace.clone().to_file_str(state);
+                    record_finding(
+                        state,
+                        ORACLE_OUTPUT.as_str(),
+                        function_name(&input.get_data_abi()),
+                        reproducer,
+                        provenance,
+                        env_dependent(input.get_vm_env()),
+                    );
+                }
+
+                #[cfg(feature = "grpc")]
+                remote::stream_solution(
+                    serde_json::to_vec(&input).unwrap(),
+                    input.pretty_txn().unwrap_or_default(),
+                    scenario_name(input.get_scenario()),
+                    unsafe { ORACLE_OUTPUT.clone() },
+                    trace.clone(),
                 );
-                
+
                 if !unsafe { RUN_FOREVER } {
-                    exit(0);
+                    #[cfg(feature = "grpc")]
+                    remote::flush(Duration::from_secs(3));
+                    print_summary(state);
+                    return Err(Error::ShuttingDown);
                 }
 
                 // Not interesting
@@ -1064,9 +1240,9 @@ where
         //         state.get_execution_result().reverted,
         //         txn_text
         //     );
-        //     println!("============= New Corpus Item =============");
-        //     println!("{}", data);
-        //     println!("==========================================");
+        //     debug!("============= New Corpus Item =============");
+        //     debug!("{}", data);
+        //     debug!("==========================================");
 
         //     // write to file
         //     let path = Path::new(self.corpus_path.as_str());
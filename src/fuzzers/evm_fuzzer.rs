@@ -1,9 +1,6 @@
 use bytes::Bytes;
 use std::cell::RefCell;
-use std::fs::File;
-use std::io::Read;
 use std::rc::Rc;
-use std::str::FromStr;
 use std::sync::Arc;
 use std::ffi::CString;
 
@@ -13,39 +10,77 @@ use crate::{
 };
 use libafl::feedbacks::Feedback;
 use libafl::prelude::ShMemProvider;
-use libafl::prelude::{QueueScheduler, SimpleEventManager};
+use libafl::prelude::SimpleEventManager;
 use libafl::stages::{CalibrationStage, StdMutationalStage};
 use libafl::{
     prelude::{tuple_list, MaxMapFeedback, SimpleMonitor, StdMapObserver},
     Evaluator, Fuzzer,
 };
 use glob::glob;
+use tracing::{info, warn};
 
-use crate::evm::host::{ACTIVE_MATCH_EXT_CALL, CMP_MAP, JMP_MAP};
+use crate::corpus_journal::flush_corpus_journal;
+use crate::evm::branch_coverage::BranchCoverageObserver;
+use crate::evm::host::{ACTIVE_MATCH_EXT_CALL, CMP_MAP, JMP_MAP, LOOP_BOUND, LOOP_MAP};
 use crate::evm::host::{CALL_UNTIL};
+use crate::batch_composer::BATCH_MODE;
+use crate::cpu_pool::{register_code, CPU_WORKERS};
+use crate::evm::dictionary::{add_dictionary_to_state, init_dictionary};
+use crate::evm::oracles::conformance::InterfaceConformanceOracle;
+use crate::evm::oracles::view_consistency::ViewConsistencyOracle;
+use crate::evm::oracles::goal::GoalOracle;
+use crate::evm::evm_version::{check_ptx_version, EVM_VERSION};
+use crate::evm::ptx_binding::{check_ptx_binding, ptx_translated_from, read_ptx};
+use crate::evm::sanitizers::check_ptx_sanitizers;
 use crate::evm::vm::EVMState;
+use crate::error::{campaign_result, MauError, MauResult};
 use crate::feedback::{CmpFeedback, OracleFeedback};
 
-use crate::scheduler::SortedDroppingScheduler;
+use crate::evm::call_value::CALLER_BALANCE;
+use crate::perf::PERF_EXECUTIONS;
+use crate::batch_size::{init_batch_size, ADAPTIVE_BATCH};
+use crate::resident_pool::{GPU_RESIDENT_SEEDS, RESIDENT_LAUNCHES};
+use crate::scheduler::{PowerScheduler, SortedDroppingScheduler, POWER_SCHEDULE, PRUNING_POLICY, STATE_CORPUS_LIMIT};
 use crate::state::{FuzzState, HasCaller, HasExecutionResult};
 use crate::state_input::StagedVMState;
 
 use crate::evm::config::Config;
+use crate::evm::control::start_control_socket;
+use crate::evm::corpus_format::load_corpus_file;
 use crate::evm::corpus_initializer::EVMCorpusInitializer;
-use crate::evm::input::{EVMInput, EVMInputTy};
+use crate::evm::warm_start::load_warm_start;
+use crate::evm::input::EVMInput;
 
-use crate::evm::mutator::{AccessPattern, FuzzMutator};
+use crate::evm::mutator::FuzzMutator;
 use crate::evm::onchain::flashloan::Flashloan;
 use crate::evm::onchain::onchain::OnChain;
 use crate::evm::presets::pair::PairPreset;
-use crate::evm::types::{EVMAddress, EVMFuzzMutator, EVMFuzzState, EVMU256, fixed_address};
+use crate::evm::address_pool::ADDRESS_WEIGHTS;
+use crate::evm::adversary::register_adversary;
+use crate::evm::amm::{register_amm_pools, POOL_CODE};
+use crate::evm::weird_tokens::{register_weird_tokens, TOKEN_CODE};
+use crate::evm::callers::{register_caller_role, register_named_callers, CallerRole};
+use crate::evm::price_feeds::register_price_feeds;
+use crate::evm::revert_stats::GUARD_PHASE_INSTRUCTIONS;
+use crate::evm::scenario::register_scenarios;
+use crate::evm::types::{register_caller_alias, EVMAddress, EVMFuzzMutator, EVMFuzzState, EVMU256, fixed_address};
 use primitive_types::{H160, U256};
-use revm_primitives::{BlockEnv, Bytecode, Env};
+use revm_primitives::Bytecode;
 use revm_primitives::bitvec::view::BitViewSized;
 use crate::evm::middlewares::instruction_coverage::InstructionCoverage;
+#[cfg(feature = "cuda")]
+use crate::evm::opcode_stats::OPCODE_STATS;
+#[cfg(feature = "cuda")]
+use crate::runner_sys::{
+    cuEnableMemSanitizer, cuEnableOpcodeStats, cuMallocAll, cuMaxActiveThreads, cuSetBatchSize, cuSetEVMVersion, cuSetLoopBound,
+    cuSetPcTraceSize, load_runner, runner_exports, InitCudaCtx,
+};
+#[cfg(feature = "cuda")]
+use crate::evm::immutables::{cu_patch_immutables, find_immutables, find_runtime_template};
 
 use crate::gpu_stage::StdGPUMutationalStage;
-use crate::evm::config::{RUN_FOREVER, GPU_ENABLE, DUMP_CORPUS};
+use crate::host_mem::MAX_HOST_MEM;
+use crate::evm::config::{RUN_FOREVER, TIME_BUDGET, GPU_ENABLE, GPU_KERNEL_ADDRESS, DUMP_CORPUS, BLOCK_GAS_LIMIT, EXPLORE_PATHOLOGICAL_ENV, VERIFY_GPU_SOLUTIONS, PC_TRACE_SIZE};
 
 struct ABIConfig {
     abi: String,
@@ -58,25 +93,34 @@ struct ContractInfo {
 }
 
 pub fn evm_fuzzer(
-    config: Config<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>, state: &mut EVMFuzzState
-) {
+    mut config: Config<EVMState, EVMAddress, Bytecode, Bytes, EVMAddress, EVMU256, Vec<u8>, EVMInput, EVMFuzzState>, state: &mut EVMFuzzState
+) -> MauResult<()> {
+    // harness contracts are deployed along with the targets
+    for target in &config.targets {
+        config.contract_info.extend(target.borrow().contracts());
+    }
     let cov_middleware = Rc::new(RefCell::new(InstructionCoverage::new(config.cov_path)));
 
-    let monitor = SimpleMonitor::new(|s| println!("{}", s));
+    let monitor = SimpleMonitor::new(|s| info!("{}", s));
     let mut mgr = SimpleEventManager::new(monitor);
     let infant_scheduler = SortedDroppingScheduler::new();
 
     let jmps = unsafe { &mut JMP_MAP };
     let cmps = unsafe { &mut CMP_MAP };
     let jmp_observer = StdMapObserver::new("jmp_labels", jmps);
+    // iterations of loops, reset before each execution along with the coverage map
+    let loop_observer = StdMapObserver::new("loop_iterations", unsafe { &mut LOOP_MAP });
     let mut feedback = MaxMapFeedback::new(&jmp_observer);
     let calibration = CalibrationStage::new(&feedback);
 
-    let mut scheduler = QueueScheduler::new();
+    let mut scheduler = PowerScheduler::new();
 
     let mutator: EVMFuzzMutator<'_> = FuzzMutator::new(&infant_scheduler);
 
     let deployer = fixed_address(FIX_DEPLOYER);
+    register_caller_alias(deployer, "owner");
+    register_caller_role(deployer, CallerRole::Privileged);
+    register_named_callers(config.named_callers.clone());
     let mut fuzz_host = FuzzHost::new(Arc::new(scheduler.clone()));
 
     fuzz_host.set_concolic_enabled(config.concolic);
@@ -110,19 +154,90 @@ pub fn evm_fuzzer(
         }
     }
 
+    unsafe {
+        BLOCK_GAS_LIMIT = config.block_gas_limit;
+        EXPLORE_PATHOLOGICAL_ENV = config.explore_pathological_env;
+        VERIFY_GPU_SOLUTIONS = config.verify_gpu_solutions;
+        PC_TRACE_SIZE = config.pc_trace_size;
+        GUARD_PHASE_INSTRUCTIONS = config.guard_phase_instructions;
+        LOOP_BOUND = config.loop_bound;
+        BATCH_MODE = config.batch_mode;
+        GPU_RESIDENT_SEEDS = config.gpu_resident;
+        RESIDENT_LAUNCHES = config.resident_launches;
+        ADDRESS_WEIGHTS = config.address_weights;
+        EVM_VERSION = config.evm_version;
+        CPU_WORKERS = config.cpu_workers;
+        PRUNING_POLICY = config.state_pruning;
+        STATE_CORPUS_LIMIT = config.max_states;
+        POWER_SCHEDULE = config.power_schedule;
+        CALLER_BALANCE = config.caller_balance;
+        PERF_EXECUTIONS = config.perf_executions;
+        TIME_BUDGET = config.time_budget;
+        MAX_HOST_MEM = config.max_host_mem;
+    }
+
+    if let Some(endpoint) = config.collector.clone() {
+        #[cfg(feature = "grpc")]
+        crate::remote::start_streaming(endpoint, format!("ityfuzz-{}", std::process::id()));
+        #[cfg(not(feature = "grpc"))]
+        warn!("Ignoring collector {}, build with the `grpc` feature to enable streaming", endpoint);
+    }
+
     #[cfg(feature = "cuda")] 
     {   
         // initiate the CUDA environment
-        #[link(name = "runner")]
-        extern "C" {
-            fn InitCudaCtx(Dev: i32, pathToKernel: *const i8);
-            fn cuMallocAll();
-        }
         if config.ptx_path.len() > 0 {
+            load_runner(config.runner_lib.as_str())?;
+            // features whose entry points an older runner lacks
+            if !runner_exports("cuReadPcTrace") {
+                unsafe { PC_TRACE_SIZE = 0; }
+            }
+            if config.gpu_memory_sanitizer && !runner_exports("cuReadSanitizerReport") {
+                warn!("[-] the runner has no memory sanitizer, ignoring --gpu-memory-sanitizer");
+                config.gpu_memory_sanitizer = false;
+            }
+            let resident = ["cuPinResidentSeed", "cuSetResidentSeeds", "cuEvalResident", "cuReadResidentFinding"];
+            if unsafe { GPU_RESIDENT_SEEDS } > 0 && !resident.iter().all(|symbol| runner_exports(symbol)) {
+                warn!("[-] the runner has no resident seeds, ignoring --gpu-resident");
+                unsafe { GPU_RESIDENT_SEEDS = 0; }
+            }
+            let codes = config.contract_info.iter().map(|c| c.code.as_slice()).collect::<Vec<_>>();
+            if let Err(e) = check_ptx_binding(config.ptx_path.as_str(), &codes) {
+                if config.force_ptx {
+                    warn!("[-] {}, loading it anyway (--force)", e);
+                } else {
+                    return Err(MauError::PtxBinding(e));
+                }
+            }
+            check_ptx_sanitizers(&read_ptx(config.ptx_path.as_str()), &config.sanitizers)
+                .map_err(MauError::Sanitizers)?;
+        }
+        let version = if config.ptx_path.len() > 0 {
+            check_ptx_version(&read_ptx(config.ptx_path.as_str()), config.evm_version)
+        } else {
+            Ok(())
+        };
+        if let (Err(e), false) = (&version, config.force_ptx) {
+            warn!("[-] {} ({}), re-run ptxsema. GPU disabled.", e, config.ptx_path);
+        } else if config.ptx_path.len() > 0 {
+            if let Err(e) = version {
+                warn!("[-] {}, loading it anyway (--force)", e);
+            }
             unsafe { 
                 GPU_ENABLE = true;
-                InitCudaCtx(config.gpu_dev, CString::new(config.ptx_path).unwrap().into_raw()); 
+                InitCudaCtx(config.gpu_dev, CString::new(config.ptx_path.clone()).unwrap().into_raw()); 
+                // the ring buffer is allocated along with the other device buffers
+                cuSetPcTraceSize(PC_TRACE_SIZE as u32);
+                // same back-edge hit count saturation as the CPU executor
+                cuSetLoopBound(config.loop_bound);
+                cuEnableMemSanitizer(config.gpu_memory_sanitizer);
+                cuEnableOpcodeStats(OPCODE_STATS > 0);
+                cuSetEVMVersion(config.evm_version.gpu_id());
                 cuMallocAll();
+                if config.adaptive_batch {
+                    ADAPTIVE_BATCH = true;
+                    cuSetBatchSize(init_batch_size(cuMaxActiveThreads()));
+                }
             };
         }
     }
@@ -158,11 +273,37 @@ pub fn evm_fuzzer(
     let mut evm_executor: EVMExecutor<EVMInput, EVMFuzzState, EVMState> =
         EVMExecutor::new(fuzz_host, deployer);
 
+    for hook in &config.hooks {
+        evm_executor.host.add_hook(hook.clone());
+    }
+    for target in &config.targets {
+        evm_executor.host.add_fuzz_target(target.clone());
+    }
+
     if config.replay_file.is_some() {
         // add coverage middleware for replay
         evm_executor.host.add_middlewares(cov_middleware.clone());
     }
 
+    register_scenarios(config.scenarios);
+    register_adversary(config.adversary);
+    register_price_feeds(config.price_feeds);
+    // pools need code for the extcodesize checks of their callers, their storage is
+    // initialized at the first call
+    for pool in &config.amm_pools {
+        evm_executor
+            .host
+            .set_code(pool.address, Bytecode::new_raw(Bytes::from(POOL_CODE.to_vec())), state);
+    }
+    register_amm_pools(config.amm_pools);
+    // so do the tokens, deployed before the targets that may be constructed with them
+    for token in &config.weird_tokens {
+        evm_executor
+            .host
+            .set_code(token.address, Bytecode::new_raw(Bytes::from(TOKEN_CODE.to_vec())), state);
+    }
+    register_weird_tokens(config.weird_tokens);
+
     #[cfg(feature = "deployer_is_attacker")]
     state.add_caller(&deployer);
     let mut corpus_initializer = EVMCorpusInitializer::new(
@@ -174,10 +315,60 @@ pub fn evm_fuzzer(
 
     #[cfg(feature = "use_presets")]
     corpus_initializer.register_preset(&PairPreset {});
-
-    corpus_initializer.initialize(config.contract_info);
+    corpus_initializer.set_prepare_attacker_states(config.prepare_attacker_states);
+    corpus_initializer.set_entry_points(config.entry_points.clone());
+    corpus_initializer.set_token_mints(config.token_mints.clone());
+    corpus_initializer.set_direct_to(config.direct_to.clone());
+    corpus_initializer.set_goals(config.goals.clone());
+    corpus_initializer.set_grammar(config.grammar.clone());
+    corpus_initializer.set_packs(config.packs.clone());
+    corpus_initializer.set_paused_mode(config.paused_mode);
+    corpus_initializer.set_storage_layouts(config.storage_layouts.clone());
+
+    let contract_info = config.contract_info.clone();
+    corpus_initializer.initialize(config.contract_info)?;
+    let interfaces = corpus_initializer.interfaces();
+
+    // reuse the PTX kernel when deployments only differ in immutables, onchain targets are
+    // checked against the kernel header before it is loaded
+    #[cfg(feature = "cuda")]
+    if unsafe { GPU_ENABLE } && contract_info.iter().any(|c| !c.is_code_deployed) {
+        let mut kernel_bound = false;
+        for contract in contract_info.iter().filter(|c| !c.is_code_deployed) {
+            let runtime = match evm_executor.host.code.get(&contract.deployed_address) {
+                Some(code) => code.original_bytecode_slice().to_vec(),
+                None => continue,
+            };
+            if let Some(template) = find_runtime_template(&contract.code, &runtime) {
+                if ptx_translated_from(config.ptx_path.as_str(), &contract.code) {
+                    let refs = find_immutables(template, &runtime).unwrap();
+                    if !refs.is_empty() && !runner_exports("cuPatchImmutable") {
+                        warn!("[-] the runner cannot patch the immutables of {}", contract.name);
+                        continue;
+                    }
+                    info!("Reusing PTX kernel for {}, patching {} immutables", contract.name, refs.len());
+                    cu_patch_immutables(&refs);
+                    unsafe { GPU_KERNEL_ADDRESS = Some(contract.deployed_address); }
+                    kernel_bound = true;
+                    break;
+                }
+            }
+        }
+        if !kernel_bound {
+            warn!("[-] PTX kernel {} is translated from a different bytecode, re-run ptxsema. GPU disabled.", config.ptx_path);
+            unsafe { GPU_ENABLE = false; }
+        }
+    }
 
     evm_executor.host.initialize(state);
+    if let Some(dir) = &config.dictionary_dir {
+        let codes = contract_info.iter().map(|c| c.code.as_slice()).collect::<Vec<_>>();
+        let dictionary = init_dictionary(dir, &codes);
+        add_dictionary_to_state(&dictionary, state);
+    }
+    if config.cpu_workers > 1 {
+        register_code(evm_executor.host.code.clone());
+    }
 
     // now evm executor is ready, we can clone it
     let initial_evmstate: EVMState = evm_executor.host.evmstate.clone();
@@ -185,7 +376,11 @@ pub fn evm_fuzzer(
 
     let evm_executor_ref = Rc::new(RefCell::new(evm_executor));
 
-    let mut executor = FuzzExecutor::new(evm_executor_ref.clone(), tuple_list!(jmp_observer));
+    let branch_observer = BranchCoverageObserver::new("branches");
+    let mut executor = FuzzExecutor::new(
+        evm_executor_ref.clone(),
+        tuple_list!(jmp_observer, loop_observer, branch_observer),
+    );
 
     feedback
         .init_state(state)
@@ -193,6 +388,29 @@ pub fn evm_fuzzer(
     let infant_feedback = CmpFeedback::new(cmps, &infant_scheduler, evm_executor_ref.clone());
 
     let mut oracles = config.oracle;
+    if config.interface_oracles && !interfaces.is_empty() {
+        oracles.push(Rc::new(RefCell::new(InterfaceConformanceOracle::new(interfaces))));
+    }
+    if !config.view_invariants.is_empty() {
+        let mut invariants = vec![];
+        for invariant in &config.view_invariants {
+            match contract_info.iter().find(|c| invariant.matches(&c.name, &c.deployed_address)) {
+                Some(contract) => invariants.push((contract.deployed_address, invariant.clone())),
+                None => warn!("[view-consistency] {} not found in any contract, ignoring {}", invariant.contract, invariant.source),
+            }
+        }
+        oracles.push(Rc::new(RefCell::new(ViewConsistencyOracle::new(invariants))));
+    }
+    if !config.goals.is_empty() {
+        let mut goals = vec![];
+        for goal in &config.goals {
+            match contract_info.iter().find(|c| goal.matches(&c.name, &c.deployed_address)) {
+                Some(contract) => goals.push((contract.deployed_address, goal.clone())),
+                None => warn!("[goal] {} not found in any contract, ignoring {:?}", goal.contract(), goal),
+            }
+        }
+        oracles.push(Rc::new(RefCell::new(GoalOracle::new(goals))));
+    }
     let mut producers = config.producers;
 
     let objective = OracleFeedback::new(&mut oracles, &mut producers, evm_executor_ref.clone());
@@ -213,158 +431,75 @@ pub fn evm_fuzzer(
     match config.replay_file {
         None => {
             unsafe { DUMP_CORPUS = true; }
-            fuzzer
-                .fuzz_loop(&mut stages, &mut executor, state, &mut mgr)
-                .expect("Fuzzing failed");
+            if let Some(dir) = &config.warm_start {
+                for txns in load_warm_start(dir.as_str(), &contract_info) {
+                    let mut vm_state = vm_state.clone();
+                    for txn in txns {
+                        unsafe {
+                            CALL_UNTIL = txn.reentrancy_limit;
+                        }
+                        let inp = txn.to_input(vm_state.clone());
+                        match fuzzer.evaluate_input_events(state, &mut executor, &mut mgr, inp, false) {
+                            // the transferred sequence triggered a bug, its files are written
+                            // before returning
+                            Err(libafl::Error::ShuttingDown) => {
+                                flush_corpus_journal();
+                                return Ok(());
+                            }
+                            result => result?,
+                        };
+                        vm_state = state.get_execution_result().new_state.clone();
+                    }
+                }
+            }
+            start_control_socket();
+            let result = fuzzer.fuzz_loop(&mut stages, &mut executor, state, &mut mgr);
+            flush_corpus_journal();
+            campaign_result(result)?;
         }
         Some(files) => {
             unsafe { DUMP_CORPUS = false; }
-            for file in glob(files.as_str()).expect("Failed to read glob pattern") {
-                let mut f = File::open(file.expect("glob issue")).expect("Failed to open file");
-                let mut transactions = String::new();
-                f.read_to_string(&mut transactions)
-                    .expect("Failed to read file");
-
-                // let mut vm_state = StagedVMState::new_with_state(EVMState::new());
-                // let mut vm_state = StagedVMState::new_with_state(initial_evmstate.clone());
-                
-                // println!("state => {:?}", vm_state);
+            let paths = glob(files.as_str()).map_err(|e| MauError::Io { path: files.clone(), msg: e.to_string() })?;
+            for file in paths {
+                let file = file.expect("glob issue").to_string_lossy().to_string();
+                let txns = match load_corpus_file(file.as_str()) {
+                    Ok(txns) => txns,
+                    Err(e) => {
+                        warn!("Skipping corpus file {}", e);
+                        continue;
+                    }
+                };
+
                 let mut idx = 0;
 
-                for txn in transactions.split("\n") {
+                for txn in txns {
                     idx += 1;
-                    let splitter = txn.split(" ").collect::<Vec<&str>>();
-                    if splitter.len() < 4 {
-                        continue;
+                    unsafe {
+                        CALL_UNTIL = txn.reentrancy_limit;
                     }
+                    txn.register_function();
+                    let inp = txn.to_input(vm_state.clone());
 
-                    // [is_step] [caller] [target] [input] [value]
-                    unsafe {CALL_UNTIL = u32::MAX;}
-
-                    let inp = match splitter[0] {
-                        "abi" => {
-                            let caller = EVMAddress::from_str(splitter[1]).unwrap();
-                            let contract = EVMAddress::from_str(splitter[2]).unwrap();
-                            let input = hex::decode(splitter[3]).unwrap();
-                            let value = EVMU256::from_str_radix(splitter[4], 10).unwrap();
-                            let liquidation_percent = splitter[5].parse::<u8>().unwrap_or(0);
-                            let warp_to = splitter[6].parse::<u64>().unwrap_or(0);
-                            let repeat = splitter[7].parse::<usize>().unwrap_or(0);
-                            let reentrancy_call_limits = splitter[8].parse::<u32>().unwrap_or(u32::MAX);
-                            let is_step = splitter[9].parse::<bool>().unwrap_or(false);
-
-                            unsafe {CALL_UNTIL = reentrancy_call_limits;}
-                            EVMInput {
-                                caller,
-                                contract,
-                                data: None,
-                                sstate: vm_state.clone(),
-                                sstate_idx: 0,
-                                branch_distance: 0,
-                                txn_value: if value == EVMU256::ZERO {
-                                    None
-                                } else {
-                                    Some(value)
-                                },
-                                step: is_step,
-                                env: Env {
-                                    cfg: Default::default(),
-                                    block: BlockEnv {
-                                        number: EVMU256::from(warp_to),
-                                        coinbase: Default::default(),
-                                        timestamp: EVMU256::from(warp_to * 1000),
-                                        difficulty: Default::default(),
-                                        prevrandao: None,
-                                        basefee: Default::default(),
-                                        gas_limit: Default::default(),
-                                    },
-                                    tx: Default::default(),
-                                },
-                                access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
-                                #[cfg(feature = "flashloan_v2")]
-                                liquidation_percent,
-
-                                #[cfg(feature = "flashloan_v2")]
-                                input_type: EVMInputTy::ABI,
-                                direct_data: if input.len() == 1 && input[0] == 0 {
-                                    Bytes::new()
-                                } else {
-                                    Bytes::from(input.clone())
-                                },
-                                randomness: vec![],
-                                repeat,
-                                cu_data: vec![],
-                                is_cuda: false,
-                            }
-                        }
-                        "borrow" => {
-                            let caller = EVMAddress::from_str(splitter[1]).unwrap();
-                            let contract = EVMAddress::from_str(splitter[2]).unwrap();
-                            let randomness = hex::decode(splitter[3]).unwrap();
-                            let value = EVMU256::from_str(splitter[4]).unwrap();
-                            let _liquidation_percent = splitter[5].parse::<u8>().unwrap_or(0);
-                            let warp_to = splitter[6].parse::<u64>().unwrap_or(0);
-                            EVMInput {
-                                caller,
-                                contract,
-                                data: None,
-                                sstate: vm_state.clone(),
-                                sstate_idx: 0,
-                                branch_distance: 0,
-                                txn_value: if value == EVMU256::ZERO {
-                                    None
-                                } else {
-                                    Some(value)
-                                },
-                                step: false,
-                                env: Env {
-                                    cfg: Default::default(),
-                                    block: BlockEnv {
-                                        number: EVMU256::from(warp_to),
-                                        coinbase: Default::default(),
-                                        timestamp: EVMU256::from(warp_to * 1000),
-                                        difficulty: Default::default(),
-                                        prevrandao: None,
-                                        basefee: Default::default(),
-                                        gas_limit: Default::default(),
-                                    },
-                                    tx: Default::default(),
-                                },
-                                access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
-                                #[cfg(feature = "flashloan_v2")]
-                                liquidation_percent: 0,
-                                #[cfg(feature = "flashloan_v2")]
-                                input_type: EVMInputTy::Borrow,
-                                direct_data: Bytes::new(),
-                                randomness,
-                                repeat: 1,
-                                cu_data: vec![],
-                                is_cuda: false,
-                            }
-                        }
-                        _ => {
-                            unreachable!()
-                        }
+                    match fuzzer.evaluate_input_events(state, &mut executor, &mut mgr, inp, false) {
+                        // the replayed sequence triggered a bug
+                        Err(libafl::Error::ShuttingDown) => return Ok(()),
+                        result => result?,
                     };
 
-                    fuzzer
-                        .evaluate_input_events(state, &mut executor, &mut mgr, inp, false)
-                        .unwrap();
-
-                    println!("============ Execution result {} =============", idx);
-                    println!(
+                    info!("============ Execution result {} =============", idx);
+                    info!(
                         "reverted: {:?}",
                         state.get_execution_result().clone().reverted
                     );
-                    println!(
+                    info!(
                         "trace: {:?}",
                         state.get_execution_result().clone().new_state.trace
                     );
-                    println!(
+                    info!(
                         "output: {:?}",
                         hex::encode(state.get_execution_result().clone().output)
                     );
-                    println!("================================================");
+                    info!("================================================");
 
                     vm_state = state.get_execution_result().new_state.clone();
                 }
@@ -374,4 +509,5 @@ pub fn evm_fuzzer(
             cov_middleware.borrow_mut().record_instruction_coverage();
         }
     }
+    Ok(())
 }
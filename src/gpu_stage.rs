@@ -1,7 +1,7 @@
 //| The [`MutationalStage`] is the default stage used during fuzzing.
 //! For the current input, it will perform a range of random mutations, and then run them in the executor.
 
-use std::{fmt::Debug, borrow::BorrowMut};
+use std::{fmt::{Debug, Display, Formatter}, borrow::BorrowMut};
 use std::thread;
 
 use core::marker::PhantomData;
@@ -24,16 +24,36 @@ use libafl::{
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::ops::Deref;
 use std::time::Instant;
 
 use serde::Serialize;
 use serde_traitobject::Any;
-use std::ptr;
 use core::{
     cmp::{max, min},
 };
-use std::process::exit;
-use crate::evm::config::{NJOBS, SEED_SIZE, RUN_FOREVER, GPU_ENABLE, STATS_CPU_DEFAULT};
+use tracing::{debug, info, warn};
+use crate::evm::control::take_pushed;
+use crate::evm::onchain::signature_workers::drain_resolved;
+use crate::evm::host::CALL_UNTIL;
+use crate::evm::config::{NJOBS, SEED_SIZE, RUN_FOREVER, GPU_ENABLE, GPU_KERNEL_ADDRESS, STATS_CPU_DEFAULT, VERIFY_GPU_SOLUTIONS, PC_TRACE_SIZE};
+use crate::batch_composer::{compose, group_by_env, record_batch, warp_divergence, BatchMode, BATCH_MODE};
+use crate::evm::branch_coverage::{merge_gpu_branches, GPU_BRANCH_BITMAP_SIZE};
+use crate::backend_cost::{self, route, Backend, EXPLORE_PERCENT, ROUTE_BY_COST};
+use crate::batch_size::{batch_size, record_batch_size};
+use crate::ffi_marshal::{ffi_len, report_marshal_error, seed_len, SEED_HEADER_SIZE};
+use crate::nvtx::{batch_range, next_batch};
+use crate::cpu_pool::{execute_batch, PoolTxn, CPU_WORKERS, POOL_BATCH_PER_WORKER, POOL_ORACLE_SAMPLE};
+use crate::resident_pool::{
+    needs_sync, record_launches, record_sync, resident_enabled, select_resident, GPU_RESIDENT_SEEDS, RESIDENT_HAVOC,
+    RESIDENT_LAUNCHES,
+};
+use crate::scheduler::path_rounds;
+use crate::perf::{finish_perf, is_perf, perf_phase, perf_seed, record_gpu_batch, record_revm, PerfPhase};
+use crate::evm::diamond::gpu_can_execute;
+use crate::evm::summary::{env_dependent, function_name, print_summary, record_finding};
+use crate::workdir::{save_solution, write_artifact};
+use crate::differential::{diverges, minimize_calldata, read_gpu_outcome, record_divergence, Outcome, DIFFERENTIAL};
 use crate::evm::vm::EVMState;
 use crate::state::{HasCaller, HasExecutionResult};
 use crate::evm::input::EVMInput;
@@ -50,17 +70,244 @@ use serde::de::DeserializeOwned;
 
 use crate::tracer::build_basic_txn;
 use crate::fuzzer::ExecuteCudaInputResult;
+use crate::evm::opcode_stats::{merge_gpu_counts, GPU_OPCODE_PCS, OPCODE_STATS};
 use crate::evm::host::{CMP_MAP, BRANCH_DISTANCE, BRANCH_DISTANCE_INTERESTING, BRANCH_DISTANCE_CHANGED};
 use crate::generic_vm::vm_executor::{MAP_SIZE};
 
-#[link(name = "runner")]
-extern "C" {
-    fn cuLoadSeed(caller_ptr: *const u8, value_ptr: *const u8, data_ptr: *const u8, data_size: u32, thread: u32);
-    fn cuEvalTxn(nwrap: u32);
-    fn getCudaExecRes(pcov: *const u64, pbug: *const u64) -> bool;
-    fn isCudaInteresting(tid: u32) -> u8;
-    fn cuDumpStorage(threadId: u32);
-    fn cuLoadStorage(src: *const u8, slotCnt: u32, wrapi: u32);
+use crate::runner_sys::{
+    cuClearContinuation, cuEvalResident, cuEvalTxn, cuMutate, cuPinResidentSeed, cuPreMutate, cuReadBranchCoverage,
+    cuReadEnvAccess, cuReadOpcodeStats, cuReadControlLeak, cuReadPcTrace, cuReadResidentFinding, cuReadSanitizerReport,
+    cuSetBatchSize, cuSetResidentSeeds, gainCov, getCudaExecRes, isCudaInteresting,
+};
+
+/// Kind of violation flagged by the device sanitizers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SanitizerKind {
+    /// Integer overflow / underflow (integer sanitizer)
+    IntegerOverflow = 1,
+    /// MLOAD / MCOPY / RETURN of memory never written (memory sanitizer)
+    UninitMemoryRead,
+    /// CALLDATACOPY reading beyond the calldata (memory sanitizer)
+    CalldataCopyOutOfBounds,
+    /// CODECOPY reading beyond the code (memory sanitizer)
+    CodeCopyOutOfBounds,
+}
+
+impl SanitizerKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::IntegerOverflow),
+            2 => Some(Self::UninitMemoryRead),
+            3 => Some(Self::CalldataCopyOutOfBounds),
+            4 => Some(Self::CodeCopyOutOfBounds),
+            _ => None,
+        }
+    }
+
+    /// Whether the violation comes from the memory sanitizer, which has no counterpart on revm
+    pub fn is_memory(&self) -> bool {
+        *self != Self::IntegerOverflow
+    }
+}
+
+/// Violation reported by the device sanitizers for a GPU thread
+#[derive(Debug, Clone)]
+pub struct SanitizerReport {
+    pub kind: SanitizerKind,
+    /// PC of the violating instruction
+    pub pc: u32,
+    /// Operands of the violating instruction (e.g., destOffset, offset, size for CALLDATACOPY)
+    pub operands: [EVMU256; 3],
+}
+
+impl Display for SanitizerReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[sanitizer] {:?} at pc {:#x}, operands: {:#x} {:#x} {:#x}",
+            self.kind, self.pc, self.operands[0], self.operands[1], self.operands[2]
+        )
+    }
+}
+
+/// Read back the sanitizer report of a GPU thread, if any
+pub fn read_sanitizer_report(thread_id: u32) -> Option<SanitizerReport> {
+    let mut kind = 0u8;
+    let mut pc = 0u32;
+    let mut operands = [0u8; 96];
+    let found = unsafe { cuReadSanitizerReport(thread_id, &mut kind, &mut pc, operands.as_mut_ptr()) };
+    if !found {
+        return None;
+    }
+    Some(SanitizerReport {
+        kind: SanitizerKind::from_u8(kind)?,
+        pc,
+        operands: [
+            EVMU256::try_from_be_slice(&operands[0..32]).unwrap(),
+            EVMU256::try_from_be_slice(&operands[32..64]).unwrap(),
+            EVMU256::try_from_be_slice(&operands[64..96]).unwrap(),
+        ],
+    })
+}
+
+/// Bytes of the arguments of a leaking call read back
+pub const LEAK_ARGS_SIZE: usize = 1024;
+
+/// External call of a GPU thread whose target is loaded from the calldata, so that the attacker
+/// can make it call back into its own contract (a control leak, see [`crate::evm::host`])
+#[derive(Debug, Clone)]
+pub struct ControlLeakReport {
+    /// PC of the CALL
+    pub pc: u32,
+    /// Offset in the calldata (selector included) of the word the target is loaded from
+    pub target_offset: u32,
+    /// Memory passed as the arguments of the call, truncated to [`LEAK_ARGS_SIZE`]
+    pub args: Vec<u8>,
+}
+
+impl Display for ControlLeakReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[control-leak] CALL at pc {:#x} to the address at calldata offset {}, args: {}",
+            self.pc,
+            self.target_offset,
+            hex::encode(&self.args)
+        )
+    }
+}
+
+impl ControlLeakReport {
+    /// Calldata calling `caller` instead, so that revm reproduces the leak
+    pub fn redirect(&self, calldata: &[u8], caller: &EVMAddress) -> Option<Vec<u8>> {
+        let offset = self.target_offset as usize;
+        calldata.get(offset..offset + 32)?;
+        let mut calldata = calldata.to_vec();
+        calldata[offset..offset + 12].fill(0);
+        calldata[offset + 12..offset + 32].copy_from_slice(&caller.0);
+        Some(calldata)
+    }
+}
+
+/// Read back the leaking call of a GPU thread, if any
+pub fn read_control_leak(thread_id: u32) -> Option<ControlLeakReport> {
+    let mut pc = 0u32;
+    let mut target_offset = 0u32;
+    let mut args = vec![0u8; LEAK_ARGS_SIZE];
+    let mut len = 0u32;
+    let found = unsafe {
+        cuReadControlLeak(
+            thread_id,
+            &mut pc,
+            &mut target_offset,
+            args.as_mut_ptr(),
+            args.len() as u32,
+            &mut len,
+        )
+    };
+    if !found {
+        return None;
+    }
+    args.truncate(min(len as usize, LEAK_ARGS_SIZE));
+    Some(ControlLeakReport { pc, target_offset, args })
+}
+
+/// Read back the ring buffer of the last executed PCs / opcodes of a GPU thread,
+/// oldest first. Only called for threads violating an oracle.
+pub fn read_pc_trace(thread_id: u32) -> Vec<(u32, u8)> {
+    let size = unsafe { PC_TRACE_SIZE };
+    if size == 0 {
+        return vec![];
+    }
+    let mut pcs = vec![0u32; size];
+    let mut ops = vec![0u8; size];
+    let len = unsafe { cuReadPcTrace(thread_id, pcs.as_mut_ptr(), ops.as_mut_ptr(), size as u32) } as usize;
+    pcs.into_iter()
+        .zip(ops.into_iter())
+        .take(min(len, size))
+        .collect()
+}
+
+/// Merge the env opcodes (TIMESTAMP, NUMBER, CALLER, ORIGIN, BASEFEE) executed by a GPU thread
+/// into the access pattern of its input, which is shared with the seed it is mutated from, so
+/// that GPU-only campaigns also learn which env fields to mutate
+pub fn merge_gpu_env_access<I>(thread_id: u32, input: &I)
+where
+    I: EVMInputT,
+{
+    let mask = unsafe { cuReadEnvAccess(thread_id) };
+    if mask != 0 {
+        input.get_access_pattern().deref().borrow_mut().merge_gpu_env_access(mask);
+    }
+}
+
+/// Merge the branch directions taken by the threads of the last batch, which all executed the
+/// contract of the seed
+pub fn merge_gpu_branch_coverage<I>(input: &I)
+where
+    I: EVMInputT,
+{
+    let mut bitmap = vec![0u8; GPU_BRANCH_BITMAP_SIZE];
+    let len = unsafe { cuReadBranchCoverage(bitmap.as_mut_ptr(), bitmap.len() as u32) } as usize;
+    merge_gpu_branches(&input.get_contract(), &bitmap[..len.min(bitmap.len())]);
+}
+
+/// Merge the opcodes and PCs executed by the threads of the last batch into the statistics of the
+/// kernel's contract, see [`crate::evm::opcode_stats`]
+pub fn merge_gpu_opcode_stats<I>(input: &I)
+where
+    I: EVMInputT,
+{
+    if unsafe { OPCODE_STATS } == 0 {
+        return;
+    }
+    let mut ops = vec![0u64; 256];
+    let mut pcs = vec![0u64; GPU_OPCODE_PCS];
+    let len = unsafe { cuReadOpcodeStats(ops.as_mut_ptr(), pcs.as_mut_ptr(), pcs.len() as u32) } as usize;
+    let kernel = unsafe { GPU_KERNEL_ADDRESS }.unwrap_or(input.get_contract());
+    merge_gpu_counts(kernel, &ops, &pcs[..len.min(pcs.len())]);
+}
+
+/// Whether the kernel runs the frame a stepped input resumes: the kernel is compiled for a single
+/// contract, the leaking frame must execute its code
+pub fn gpu_can_resume(input: &EVMInput) -> bool {
+    let kernel = unsafe { GPU_KERNEL_ADDRESS }.unwrap_or(input.get_contract());
+    match input.get_state().post_execution.last() {
+        Some(ctx) => ctx.code_address == kernel,
+        None => false,
+    }
+}
+
+/// Format a PC trace as `pc:opcode` pairs for bug reports
+pub fn format_pc_trace(trace: &Vec<(u32, u8)>) -> String {
+    trace
+        .iter()
+        .map(|(pc, op)| format!("{:#x}:{:02x}", pc, op))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Set the caller, value and arguments of a mutant read back from the kernel (caller reversed at
+/// `[0..20]`, value at `[32..64]`, calldata length at `[64..68]`, then the calldata), returns
+/// `false` if the calldata does not fit the raw seed
+pub fn apply_raw_seed<I>(input: &mut I, raw: &mut [u8]) -> bool
+where
+    I: VMInputT<EVMState, EVMAddress, EVMAddress> + EVMInputT,
+{
+    let len = u32::from_le_bytes(raw[64..SEED_HEADER_SIZE].try_into().unwrap()) as usize;
+    if SEED_HEADER_SIZE + len > raw.len() {
+        return false;
+    }
+    let caller = &mut raw[..20];
+    caller.reverse();
+    input.set_caller(EVMAddress::from_slice(caller));
+    input.set_txn_value(EVMU256::try_from_be_slice(&raw[32..64]).unwrap());
+    match input.get_data_abi_mut().as_mut() {
+        Some(abi) => abi.set_bytes(raw[SEED_HEADER_SIZE..SEED_HEADER_SIZE + len].to_vec()),
+        None => return false,
+    }
+    true
 }
 
 
@@ -72,7 +319,7 @@ pub trait MutationalStage<E, EM, I, M, S, Z>: Stage<E, EM, S, Z>
 where
     M: Mutator<I, S>,
     I: Input + Serialize + EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>+ 'static,
-    S: HasClientPerfMonitor + HasCorpus<I> + HasExecutions + HasRand,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasExecutions + HasRand + HasMetadata,
     Z: Evaluator<E, EM, I, S>,
 {
     /// The mutator registered for this stage
@@ -111,7 +358,15 @@ where
             mark_feature_time!(state, PerfFeature::Mutate);
 
             // Time is measured directly the `evaluate_input` function
+            let calldata = if unsafe { ROUTE_BY_COST } { input.to_bytes() } else { vec![] };
+            let revm_start = Instant::now();
             let (_, corpus_idx) = fuzzer.evaluate_input(state, executor, manager, input)?;
+            if is_perf() {
+                record_revm(revm_start.elapsed());
+            }
+            if unsafe { ROUTE_BY_COST } {
+                backend_cost::record_cpu_execution(&calldata, revm_start.elapsed());
+            }
 
             start_timer!(state);
             self.mutator_mut().post_exec(state, i as i32, corpus_idx)?;
@@ -120,6 +375,132 @@ where
         Ok(())
     }
 
+    /// Runs this (mutational) stage on the CPU pool (see [`crate::cpu_pool`]), only the
+    /// interesting mutants are evaluated again on the executor
+    fn perform_parallel_mutational(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let workers = unsafe { CPU_WORKERS };
+        let mut input_vec: Vec<I> = Vec::with_capacity(workers * POOL_BATCH_PER_WORKER);
+        for i in 0..workers * POOL_BATCH_PER_WORKER {
+            let mut input = state.corpus().get(corpus_idx)?.borrow_mut().load_input()?.clone();
+            self.mutator_mut().mutate(state, &mut input, i as i32)?;
+            if input.is_step() {
+                // resuming a control leak needs the executor
+                let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+                continue;
+            }
+            input_vec.push(input);
+        }
+        let txns = input_vec.iter().map(PoolTxn::from_input).collect::<Vec<_>>();
+        let outcomes = execute_batch(txns, workers);
+        *state.executions_mut() += outcomes.len();
+        for (input, outcome) in input_vec.into_iter().zip(outcomes) {
+            // a sample of the others goes through the oracles and cmp / dataflow feedbacks
+            if outcome.is_interesting() || state.rand_mut().below(POOL_ORACLE_SAMPLE) == 0 {
+                let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replay on revm a GPU thread that reached an attacker-controllable CALL, the target
+    /// redirected to its caller, so that the leak state joins the corpus as on the CPU path
+    fn evaluate_gpu_leak(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        thread_id: u32,
+        mut thread_input: I,
+    ) -> Result<(), Error> {
+        let report = match read_control_leak(thread_id) {
+            Some(report) => report,
+            None => return Ok(()),
+        };
+        unsafe {
+            GPU_CONTROL_LEAKS += 1;
+        }
+        debug!("{} in GPU at thread#{:?}", report, thread_id);
+        let redirected = report.redirect(&thread_input.to_bytes(), &thread_input.get_caller());
+        if let (Some(calldata), Some(abi)) = (redirected, thread_input.get_data_abi_mut().as_mut()) {
+            abi.set_bytes(calldata);
+        }
+        let _ = fuzzer.evaluate_input(state, executor, manager, thread_input)?;
+        Ok(())
+    }
+
+    /// Two-phase triage of a bug found in GPU: replay it on revm with the same env/state and
+    /// only report it if the oracles confirm it there. Unconfirmed bugs are tagged separately
+    /// (see [`UNCONFIRMED_SOLUTIONS_PATH`]) for debugging the GPU backend.
+    fn triage_gpu_solution(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        thread_id: u32,
+        thread_input: I,
+    ) -> Result<(), Error> {
+        let trace = read_pc_trace(thread_id);
+        if trace.len() > 0 {
+            warn!("[bug] last {} PCs in GPU at thread#{:?}: {}", trace.len(), thread_id, format_pc_trace(&trace));
+        }
+        // revm has no memory sanitizer, so these violations cannot be re-verified on CPU
+        if let Some(report) = read_sanitizer_report(thread_id).filter(|r| r.kind.is_memory()) {
+            warn!("{} in GPU at thread#{:?}: {:?}", report, thread_id, thread_input.pretty_txn());
+            save_solution(
+                "[msan]",
+                format!(
+                    "{}\ntxn: {:?}\npc trace: {}\n",
+                    report,
+                    thread_input.pretty_txn(),
+                    format_pc_trace(&trace)
+                )
+                .as_str(),
+            );
+            record_finding(
+                state,
+                format!("[msan] {}", report).as_str(),
+                function_name(&thread_input.get_data_abi()),
+                thread_input.pretty_txn().unwrap_or_default(),
+                // mutated on the device, where the provenance of the args is not tracked
+                String::new(),
+                env_dependent(thread_input.get_vm_env()),
+            );
+            if !unsafe { RUN_FOREVER } {
+                print_summary(state);
+                return Err(Error::ShuttingDown);
+            }
+            return Ok(());
+        }
+        if !unsafe { VERIFY_GPU_SOLUTIONS } {
+            // report the GPU result as it is
+            warn!("[bug] bug() hit in GPU at thread#{:?}: {:?}", thread_id, thread_input.pretty_txn());
+            let _ = fuzzer.evaluate_input_events(state, executor, manager, thread_input, true)?;
+            if !unsafe { RUN_FOREVER } {
+                return Err(Error::ShuttingDown);
+            }
+            return Ok(());
+        }
+
+        #[cfg(feature = "print_txn_corpus")]
+        {
+            debug!("[bug] bug() hit at {:?}. Evaluating in CPU again:", thread_id);
+        }
+        let (res, _) = fuzzer.evaluate_input_events(state, executor, manager, thread_input.clone(), true)?;
+        if res != ExecuteInputResult::Solution {
+            record_unconfirmed_solution(thread_id, &thread_input, &trace);
+        }
+        Ok(())
+    }
+
     /// Runs this (mutational) stage in GPU for the given testcase
     #[allow(clippy::cast_possible_wrap)] // more than i32 stages on 32 bit system - highly unlikely...
     fn perform_one_mutational(
@@ -133,6 +514,8 @@ where
         let corpus_size = NJOBS as usize;//state.corpus().count();
         let wrap_count = min(corpus_size, NJOBS as usize);
         let slot_count = NJOBS as usize / wrap_count;
+        next_batch();
+        let upload = batch_range("upload");
 
         let mut input = state
             .corpus()
@@ -145,20 +528,10 @@ where
         input.cu_load_evm_env();
 
         // load the storage
-        if let Some(storage) = input.as_any().downcast_ref::<EVMInput>().unwrap().get_state().get(&input.get_evm_addr()) {
-            let mut bytes = Vec::new();
-            // println!("storage content before executing input=>");
-            for (key, value) in storage {
-                // for (key, value) in storage {
-                //     println!("{:#x}: {:#x}", key, value);
-                // }
-                let slot = [key.as_le_bytes(), value.as_le_bytes()].concat();
-                bytes.extend(slot);
-            }
-            unsafe{ cuLoadStorage(bytes.as_ptr(), storage.len() as u32, 0 as u32); }
-        } else {
-            // println!("Empty storage"); 
-            unsafe{ cuLoadStorage(ptr::null(), 0, 0 as u32); }
+        if let Err(e) = input.cu_load_storage(0) {
+            report_marshal_error(&e);
+            let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+            return Ok(());
         }
 
         let mut tid:u32 = 0;
@@ -167,14 +540,22 @@ where
         for i in 0..num {
             // mutate the arguments and transactions (in state)
             self.mutator_mut().mutate(state, &mut input, i as i32)?;
+            if let Err(e) = input.cu_load_input(0, tid) {
+                report_marshal_error(&e);
+                let _ = fuzzer.evaluate_input(state, executor, manager, input.clone())?;
+                continue;
+            }
             input_vec.push(input.clone());
-            input.cu_load_input(tid);
             tid += 1;
         }
+        drop(upload);
         // run
+        let execute = batch_range("execute");
         unsafe {
             cuEvalTxn(wrap_count as u32);
         }
+        drop(execute);
+        let _readback = batch_range("readback");
         *state.executions_mut() += tid as usize; 
         unsafe {
             let mut _cov : u64 = 0; // remove
@@ -188,6 +569,7 @@ where
                 // println!("hnb[{:?}] = {:?}", thread_id, r);
                 std::mem::transmute(r)
             };
+            merge_gpu_env_access(thread_id as u32, thread_input);
             match hnb {
                 ExecuteCudaInputResult::EXECNONE => {
                     // println!("============= EXEC NONE Seed =============");
@@ -199,18 +581,14 @@ where
                 }
                 ExecuteCudaInputResult::EXECBUGGY => {
                     // unsafe{ cuDumpStorage(thread_id as u32); }
-                    #[cfg(feature = "print_txn_corpus")]
-                    {       
-                        // println!("[bug] bug() hit at {:?}\n\t{:?}\n=>{:?}\n", thread_id, thread_input, thread_input.get_calldata());
-                        let _ = fuzzer.evaluate_input(state, executor, manager, thread_input.clone())?;
-                    }
-                    if !unsafe { RUN_FOREVER } {
-                        exit(0);
-                    }
+                    self.triage_gpu_solution(fuzzer, executor, state, manager, thread_id as u32, thread_input.clone())?;
                 }
                 ExecuteCudaInputResult::EXECINTERESTING => {
                     let _ = fuzzer.evaluate_input(state, executor, manager, thread_input.clone())?;
                 }
+                ExecuteCudaInputResult::EXECCONTROLLEAK => {
+                    self.evaluate_gpu_leak(fuzzer, executor, state, manager, thread_id as u32, thread_input.clone())?;
+                }
                 _ => {
                     unreachable!();
                 }
@@ -220,6 +598,127 @@ where
     }
 
 
+    /// Runs this (mutational) stage in GPU for a batch of seeds drawn from the whole corpus,
+    /// composed according to [`BATCH_MODE`] (see [`crate::batch_composer`])
+    fn perform_batch_mutational(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let mode = unsafe { BATCH_MODE };
+        let corpus_size = state.corpus().count();
+        next_batch();
+        let upload = batch_range("upload");
+        let mut input_vec: Vec<I> = Vec::with_capacity(batch_size() as usize);
+        for i in 0..batch_size() as usize {
+            // the scheduled testcase leads the batch
+            let idx = if i == 0 {
+                corpus_idx
+            } else {
+                state.rand_mut().below(corpus_size as u64) as usize
+            };
+            let mut input = state.corpus().get(idx)?.borrow_mut().load_input()?.clone();
+            self.mutator_mut().mutate(state, &mut input, i as i32)?;
+            // routed to a diamond facet the kernel is not translated from, or resuming a control
+            // leak, whose continuation cannot be shared by the seeds of a batch
+            if input.is_step() || !gpu_can_execute(&input.get_contract(), &input.get_calldata()) {
+                let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+                continue;
+            }
+            input_vec.push(input);
+        }
+        if input_vec.is_empty() {
+            return Ok(());
+        }
+        let composed = compose(input_vec, mode, state.rand_mut());
+        drop(upload);
+        // the env is shared by the threads of a launch, one launch per env
+        for group in group_by_env(composed) {
+            self.launch_batch(fuzzer, executor, state, manager, mode, group)?;
+        }
+        Ok(())
+    }
+
+    /// Launch seeds sharing an env, each thread on its own storage snapshot, and evaluate the
+    /// results of the threads
+    fn launch_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        mode: BatchMode,
+        inputs: Vec<I>,
+    ) -> Result<(), Error> {
+        let upload = batch_range("upload");
+        // inputs not fitting the runner run on revm
+        let mut input_vec: Vec<I> = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let tid = input_vec.len() as u32;
+            match input.cu_load_storage(tid).and_then(|_| input.cu_load_input(tid, tid)) {
+                Ok(()) => input_vec.push(input),
+                Err(e) => {
+                    report_marshal_error(&e);
+                    let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+                }
+            }
+        }
+        if input_vec.is_empty() {
+            return Ok(());
+        }
+        let divergence = warp_divergence(&input_vec);
+        input_vec[0].cu_load_evm_env();
+
+        drop(upload);
+        let execute = batch_range("execute");
+        let start_time = Instant::now();
+        unsafe {
+            // the seeds start from the entry point, whatever the previous launch resumed
+            cuClearContinuation();
+            cuEvalTxn(input_vec.len() as u32);
+        }
+        drop(execute);
+        let _readback = batch_range("readback");
+        record_batch(mode, input_vec.len(), divergence, start_time.elapsed());
+        if let Some(size) = record_batch_size(input_vec.len(), start_time.elapsed()) {
+            unsafe { cuSetBatchSize(size) };
+        }
+        *state.executions_mut() += input_vec.len();
+        unsafe {
+            let mut _cov : u64 = 0;
+            let mut _buggy : u64 = 0;
+            let _ = getCudaExecRes(&_cov, &_buggy);
+        }
+
+        for (thread_id, thread_input) in input_vec.into_iter().enumerate() {
+            let hnb : ExecuteCudaInputResult = unsafe {
+                std::mem::transmute(isCudaInteresting(thread_id as u32))
+            };
+            merge_gpu_env_access(thread_id as u32, &thread_input);
+            match hnb {
+                ExecuteCudaInputResult::EXECNONE | ExecuteCudaInputResult::EXECREVERTED => {
+                    continue;
+                }
+                ExecuteCudaInputResult::EXECBUGGY => {
+                    self.triage_gpu_solution(fuzzer, executor, state, manager, thread_id as u32, thread_input)?;
+                }
+                ExecuteCudaInputResult::EXECINTERESTING => {
+                    let _ = fuzzer.evaluate_input(state, executor, manager, thread_input)?;
+                }
+                ExecuteCudaInputResult::EXECCONTROLLEAK => {
+                    self.evaluate_gpu_leak(fuzzer, executor, state, manager, thread_id as u32, thread_input)?;
+                }
+                _ => {
+                    unreachable!();
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Runs this (mutational) stage in GPU for all testcase
     #[allow(clippy::cast_possible_wrap)] // more than i32 stages on 32 bit system - highly unlikely...
     fn perform_multiple_mutational(
@@ -230,16 +729,6 @@ where
         manager: &mut EM,
         corpus_idx: usize,
     ) -> Result<(), Error> {
-        #[link(name = "runner")]
-        extern "C" {
-            fn cuPreMutate(argTypes: *const u8, argTypesLen: u32);
-            fn cuMutate(calldasize: u32);
-            fn cuEvalTxn(nwrap: u32);
-            fn getCudaExecRes(pcov: *const u64, pbug: *const u64) -> bool;
-            fn isCudaInteresting(tid: u32) -> u8;
-            fn gainCov(tid: u32, RawSeed: *mut u8) -> u8;
-        }
-        
         let mut tx_bytes:[u8; SEED_SIZE] = [0; SEED_SIZE];
         for i in 0..self.iterations(state, corpus_idx)? {
 
@@ -254,15 +743,25 @@ where
                 .borrow_mut()
                 .load_input()?
                 .clone();
+            // the threads share the env of the seed, only its arguments are worth mutating
+            cpu_input.set_cuda_input(true);
             self.mutator_mut().mutate(state, &mut cpu_input, i as i32)?;
+            cpu_input.set_cuda_input(false);
             let cpu_calldata = cpu_input.to_bytes();
             let cpu_calldatasize = cpu_calldata.len();
 
             let (res, new_corpus_idx) = fuzzer.evaluate_input(state, executor, manager, cpu_input.clone())?;
             self.mutator_mut().post_exec(state, i as i32, new_corpus_idx)?;
 
-            if cpu_input.as_any().downcast_ref::<EVMInput>().unwrap().is_step()
-               || cpu_calldatasize <= 4 {
+            // a stepped input resumes a control leak, its seed is the return buffer of the call
+            let evm_input = cpu_input.as_any().downcast_ref::<EVMInput>().unwrap();
+            let is_step = evm_input.is_step();
+            let executable = if is_step {
+                gpu_can_resume(evm_input)
+            } else {
+                gpu_can_execute(&cpu_input.get_contract(), &cpu_calldata)
+            };
+            if cpu_calldatasize <= 4 || !executable {
                 continue;
             }
             if unsafe { !BRANCH_DISTANCE_INTERESTING } {
@@ -271,7 +770,7 @@ where
                 }
             }
             #[cfg(any(test, feature = "debug"))] 
-            println!("[-] time cost on CPU execution {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on CPU execution {:?} us", start_time.elapsed().as_micros()); 
             let start_time = Instant::now();
 
             // if cpu_calldatasize >= 4 {
@@ -286,27 +785,25 @@ where
             // Fuzz one batch of inputs in GPU
             
             // setup environmental parameters
+            let upload_start = Instant::now();
+            next_batch();
+            let upload_range = batch_range("upload");
             cpu_input.cu_load_evm_env();
             #[cfg(any(test, feature = "debug"))]
-            println!("[-] time cost on env loading {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on env loading {:?} us", start_time.elapsed().as_micros()); 
             let start_time = Instant::now();
 
-            // setup storage state
-            if let Some(storage) = cpu_input.as_any().downcast_ref::<EVMInput>().unwrap().get_state().get(&cpu_input.get_evm_addr()) {
-                let mut bytes = Vec::new();
-                // println!("CPU: storage content before executing input=> {:?} slots", storage.len());
-                for (key, value) in storage {
-                    // println!("{:#x}: {:#x}", key, value);
-                    let slot = [key.as_le_bytes(), value.as_le_bytes()].concat();
-                    bytes.extend(slot);
-                }
-                unsafe{ cuLoadStorage(bytes.as_ptr(), storage.len() as u32, 0 as u32); }
-            } else {
-                unsafe{ cuLoadStorage(ptr::null(), 0, 0 as u32); }
+            // setup storage state, the input already ran on revm when it does not fit the runner
+            if let Err(e) = cpu_input
+                .cu_load_storage(0)
+                .and_then(|_| cpu_input.cu_load_continuation())
+            {
+                report_marshal_error(&e);
+                continue;
             }
 
             #[cfg(any(test, feature = "debug"))] 
-            println!("[-] time cost on state loading {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on state loading {:?} us", start_time.elapsed().as_micros()); 
             let start_time = Instant::now();
 
             #[cfg(any(test, feature = "debug"))]
@@ -336,9 +833,19 @@ where
                 // println!("method => {:#x}{:#x}{:#x}{:#x}", data[0], data[1], data[2], data[3]); 
                 // println!("input_type_vec = {:?}", input_type_vec);
             }
-            cpu_input.cu_load_input(0);
+            let lens = cpu_input
+                .cu_load_input(0, 0)
+                .and_then(|_| ffi_len("argument types", input_type_vec.len()))
+                .and_then(|types_len| seed_len(cpu_calldatasize).map(|calldata_len| (types_len, calldata_len)));
+            let (types_len, calldata_len) = match lens {
+                Ok(lens) => lens,
+                Err(e) => {
+                    report_marshal_error(&e);
+                    continue;
+                }
+            };
             unsafe {
-                cuPreMutate(input_type_vec.as_ptr(), input_type_vec.len() as u32);
+                cuPreMutate(input_type_vec.as_ptr(), types_len);
             }
 
             let should_havoc = state.rand_mut().below(100) < 60;
@@ -349,35 +856,58 @@ where
             };
             for _ in 0..havoc_times {
                 unsafe {
-                    cuMutate(cpu_calldatasize as u32);
+                    cuMutate(calldata_len);
                 }
             }
             #[cfg(any(test, feature = "debug"))]
-            println!("[-] time cost on data copy {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on data copy {:?} us", start_time.elapsed().as_micros()); 
             let start_time = Instant::now();
 
             // run in GPU
+            let upload = upload_start.elapsed();
+            drop(upload_range);
+            let execute_range = batch_range("execute");
+            let kernel_start = Instant::now();
             unsafe {
                 cuEvalTxn(0);
+                // the other batches start from the entry point
+                if is_step {
+                    cuClearContinuation();
+                }
+            }
+            let kernel = kernel_start.elapsed();
+            drop(execute_range);
+            let _readback_range = batch_range("readback");
+            let threads = batch_size();
+            *state.executions_mut() += threads as usize; 
+            if let Some(size) = record_batch_size(threads as usize, kernel) {
+                unsafe { cuSetBatchSize(size) };
             }
-            *state.executions_mut() += NJOBS as usize; 
             
             #[cfg(any(test, feature = "debug"))]
-            println!("[-] time cost on SIMD execution {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on SIMD execution {:?} us", start_time.elapsed().as_micros()); 
             let start_time = Instant::now();
 
+            let readback_start = Instant::now();
             unsafe {
                 let mut _cov : u64 = 0; // remove
                 let mut _buggy : u64 = 0; // remove
                 let _ = getCudaExecRes(&_cov, &_buggy);
             }
-            for thread_id in 0..NJOBS {
+            merge_gpu_branch_coverage(&cpu_input);
+            merge_gpu_opcode_stats(&cpu_input);
+            let mut readback = readback_start.elapsed();
+            for thread_id in 0..threads {
+                let gain_start = Instant::now();
                 let hnb : ExecuteCudaInputResult = unsafe { 
                     let r = gainCov(thread_id as u32, tx_bytes.as_mut_ptr());
                     // let r = isCudaInteresting(thread_id as u32);
                     // println!("hnb[{:?}] = {:?}", thread_id, r);
                     std::mem::transmute(r)
                 };
+                readback += gain_start.elapsed();
+                // threads only mutate the arguments of the seed, their env accesses are the seed's
+                merge_gpu_env_access(thread_id as u32, &cpu_input);
                 match hnb {
                     ExecuteCudaInputResult::EXECNONE => {
                         continue;
@@ -396,7 +926,7 @@ where
                         // println!("now CPU caller=> {:?}", thread_input.get_caller());
 
                         thread_input.set_txn_value(EVMU256::try_from_be_slice(&tx_bytes[32..64]).unwrap());
-                        println!("current CPU data=> \ntypes:{:?}\ndata:{:?}", thread_input.get_types_vec(), hex::encode(thread_input.to_bytes()));
+                        debug!("current CPU data=> \ntypes:{:?}\ndata:{:?}", thread_input.get_types_vec(), hex::encode(thread_input.to_bytes()));
                         thread_input
                             .get_data_abi_mut()
                             .as_mut()
@@ -405,24 +935,19 @@ where
                         assert_eq!(thread_input.to_bytes(), tx_bytes[68..68+cpu_calldatasize].to_vec(), "set_bytes fails");
 
                         let calldata = hex::encode(thread_input.to_bytes().clone());
-                        #[cfg(feature = "print_txn_corpus")]
-                        {
-                            println!("[bug] bug() hit at {:?}. Evaluating in CPU again:", thread_id);
-                        }
-                        let (res, _) = fuzzer.evaluate_input_events(state, executor, manager, thread_input, true)?;
+                        self.triage_gpu_solution(fuzzer, executor, state, manager, thread_id, thread_input)?;
                         #[cfg(any(test, feature = "debug"))] {
-                            println!("cpu vector => {:?}", hex::encode(input_vector[thread_id as usize].to_bytes()));
+                            debug!("cpu vector => {:?}", hex::encode(input_vector[thread_id as usize].to_bytes()));
                         }
 
                         #[cfg(feature = "print_txn_corpus")]
                         { 
-                            println!("Found a {:?} in GPU at thread#{:?}", res, thread_id);
-                            println!("data changed from (CPU){:?}\n===> (GPU){:?}", hex::encode(cpu_input.to_bytes().clone()), calldata);
+                            debug!("data changed from (CPU){:?}\n===> (GPU){:?}", hex::encode(cpu_input.to_bytes().clone()), calldata);
                             // exit(0);
                         }
                     }
                     ExecuteCudaInputResult::EXECINTERESTING => {
-                        println!("Found an interesting from GPU #{:?}", thread_id);
+                        debug!("Found an interesting from GPU #{:?}", thread_id);
                         let mut thread_input = cpu_input.clone();
                         // println!("current CPU caller=> {:?}", thread_input.get_caller());
                         let new_caller = &mut tx_bytes[..20];
@@ -431,7 +956,7 @@ where
                         thread_input.set_caller(EVMAddress::from_slice(&new_caller));
                         // println!("now CPU caller=> {:?}", thread_input.get_caller());
                         thread_input.set_txn_value(EVMU256::try_from_be_slice(&tx_bytes[32..64]).unwrap());
-                        println!("current CPU data=> \ntypes:{:?}\ndata:{:?}", thread_input.get_types_vec(), hex::encode(thread_input.to_bytes()));
+                        debug!("current CPU data=> \ntypes:{:?}\ndata:{:?}", thread_input.get_types_vec(), hex::encode(thread_input.to_bytes()));
                         thread_input
                             .get_data_abi_mut()
                             .as_mut()
@@ -441,26 +966,46 @@ where
                         assert_eq!(thread_input.to_bytes(), tx_bytes[68..68+cpu_calldatasize].to_vec(), "set_bytes fails");
 
                         let calldata = hex::encode(thread_input.to_bytes());
-                        println!("input=>{:?}", calldata);
+                        debug!("input=>{:?}", calldata);
                         let (res, _) = fuzzer.evaluate_input_events(state, executor, manager, thread_input, true)?;
                         if res == ExecuteInputResult::None {
-                            println!("Unfortunately, uninteresting in CPU");
-                            println!("input=>{:?}", calldata);
+                            debug!("Unfortunately, uninteresting in CPU");
+                            debug!("input=>{:?}", calldata);
                             // exit(0);
                         } else {
-                            println!("It is indeed interesting in CPU as well!");
+                            debug!("It is indeed interesting in CPU as well!");
                         }
                     }
+                    ExecuteCudaInputResult::EXECCONTROLLEAK => {
+                        let mut thread_input = cpu_input.clone();
+                        let new_caller = &mut tx_bytes[..20];
+                        new_caller.reverse();
+                        thread_input.set_caller(EVMAddress::from_slice(&new_caller));
+                        thread_input.set_txn_value(EVMU256::try_from_be_slice(&tx_bytes[32..64]).unwrap());
+                        thread_input
+                            .get_data_abi_mut()
+                            .as_mut()
+                            .unwrap()
+                            .set_bytes(tx_bytes[68..68+cpu_calldatasize].to_vec());
+                        self.evaluate_gpu_leak(fuzzer, executor, state, manager, thread_id, thread_input)?;
+                    }
                     _ => {
                         unreachable!();
                     }
                 }
             }
             
+            if is_perf() {
+                record_gpu_batch(threads as u64, upload, kernel, readback);
+            }
+            if unsafe { ROUTE_BY_COST } {
+                backend_cost::record_gpu_batch(&cpu_calldata, threads, upload, kernel, readback);
+            }
+
             #[cfg(any(test, feature = "debug"))]
-            println!("[-] time cost on GPU feedback {:?} us", start_time.elapsed().as_micros()); 
+            debug!("[-] time cost on GPU feedback {:?} us", start_time.elapsed().as_micros()); 
             #[cfg(any(test, feature = "debug"))]
-            println!("[-] time cost on a round {:?} us\n", first_start_time.elapsed().as_micros());
+            debug!("[-] time cost on a round {:?} us\n", first_start_time.elapsed().as_micros());
         }
 
         // }
@@ -469,6 +1014,58 @@ where
     }
 }
 
+/// Whether the mutants of the seed are fuzzed on the GPU, the cheaper backend by predicted cost
+/// with `--route-by-cost` (see [`crate::backend_cost`])
+fn route_to_gpu<I, S>(state: &mut S, corpus_idx: usize) -> Result<bool, Error>
+where
+    I: Input + EVMInputT,
+    S: HasCorpus<I> + HasRand,
+{
+    if unsafe { !ROUTE_BY_COST } {
+        return Ok(true);
+    }
+    let calldata = state.corpus().get(corpus_idx)?.borrow_mut().load_input()?.to_bytes();
+    let explore = state.rand_mut().below(100) < EXPLORE_PERCENT;
+    Ok(route(&calldata, batch_size(), explore) == Backend::Gpu)
+}
+
+/// Directory (in the artifacts of the work directory) where GPU-found bugs that are not
+/// confirmed on revm are dumped
+pub const UNCONFIRMED_SOLUTIONS_PATH: &str = "unconfirmed_solutions";
+
+/// Number of GPU-found bugs that are not confirmed on revm
+pub static mut UNCONFIRMED_SOLUTIONS: usize = 0;
+
+/// Number of GPU threads that reached an attacker-controllable call, replayed on revm
+pub static mut GPU_CONTROL_LEAKS: usize = 0;
+
+/// Tag a GPU-found bug that cannot be reproduced on revm, i.e., a divergence of the GPU backend
+fn record_unconfirmed_solution<I>(thread_id: u32, input: &I, trace: &Vec<(u32, u8)>)
+where
+    I: EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress>,
+{
+    let count = unsafe {
+        UNCONFIRMED_SOLUTIONS += 1;
+        UNCONFIRMED_SOLUTIONS
+    };
+    warn!(
+        "[unconfirmed] bug() hit in GPU at thread#{:?} is not reproduced on revm ({} in total)",
+        thread_id, count
+    );
+
+    let data = format!(
+        "thread: {}\ncaller: {:?}\ncontract: {:?}\nvalue: {}\ncalldata: {}\ntxn: {:?}\npc trace: {}\n",
+        thread_id,
+        input.get_caller(),
+        input.get_contract(),
+        input.get_txn_value().unwrap_or(EVMU256::ZERO),
+        hex::encode(input.get_calldata()),
+        input.pretty_txn(),
+        format_pc_trace(trace),
+    );
+    write_artifact(UNCONFIRMED_SOLUTIONS_PATH, &count.to_string(), &data);
+}
+
 /// Default value, how many iterations each stage gets, as an upper bound.
 /// It may randomly continue earlier.
 pub static DEFAULT_MUTATIONAL_MAX_ITERATIONS: u64 = 128;
@@ -486,6 +1083,11 @@ where
     Z: Evaluator<E, EM, I, S>,
 {
     mutator: M,
+    /// Seeds pinned in device memory by slot, see [`crate::resident_pool`]
+    resident: Vec<I>,
+    /// Corpus indices of the seeds that can be resident, and the number of testcases checked
+    resident_candidates: Vec<usize>,
+    resident_scanned: usize,
     #[allow(clippy::type_complexity)]
     phantom: PhantomData<(E, EM, I, S, Z)>,
 }
@@ -498,7 +1100,8 @@ where
         + HasCorpus<I>
         + HasRand
         + HasStartTime
-        + HasExecutions,
+        + HasExecutions
+        + HasMetadata,
     Z: Evaluator<E, EM, I, S>,
 {
     /// The mutator, added to this stage
@@ -530,7 +1133,9 @@ where
         + HasCorpus<I>
         + HasRand
         + HasStartTime
-        + HasExecutions,
+        + HasExecutions
+        + HasMetadata
+        + HasExecutionResult<EVMAddress, EVMAddress, EVMState, Vec<u8>>,
     Z: Evaluator<E, EM, I, S>,
 {
     #[inline]
@@ -543,11 +1148,40 @@ where
         manager: &mut EM,
         corpus_idx: usize,
     ) -> Result<(), Error> {
+        // sequences pushed through the control socket
+        self.replay_pushed(fuzzer, executor, state, manager)?;
+        // signatures found by the background workers since the last run
+        drain_resolved();
+
+        // micro-benchmark: revm then GPU on the seeds of the initial corpus
+        if is_perf() {
+            let corpus_idx = perf_seed(state.corpus().count());
+            return match perf_phase(unsafe { GPU_ENABLE }) {
+                PerfPhase::Revm => self.perform_mutational(fuzzer, executor, state, manager, corpus_idx),
+                PerfPhase::Gpu => self.perform_multiple_mutational(fuzzer, executor, state, manager, corpus_idx),
+                PerfPhase::Done => {
+                    finish_perf();
+                    Err(Error::ShuttingDown)
+                }
+            };
+        }
+
+        if unsafe { DIFFERENTIAL && GPU_ENABLE } {
+            return self.perform_differential(fuzzer, executor, state, manager, corpus_idx);
+        }
+
         // run CPU mode at least five minutes
-        let ret = if unsafe { GPU_ENABLE } && current_time().checked_sub(*state.start_time()).unwrap().as_secs() > STATS_CPU_DEFAULT {
+        let gpu_phase = unsafe { GPU_ENABLE } && current_time().checked_sub(*state.start_time()).unwrap().as_secs() > STATS_CPU_DEFAULT;
+        let ret = if gpu_phase && route_to_gpu(state, corpus_idx)? {
         // let ret = if unsafe { GPU_ENABLE } {
             // println!("===================== GPU enabled ====================");
-            self.perform_multiple_mutational(fuzzer, executor, state, manager, corpus_idx)
+            match unsafe { BATCH_MODE } {
+                BatchMode::Seed if resident_enabled() => self.perform_resident(fuzzer, executor, state, manager, corpus_idx),
+                BatchMode::Seed => self.perform_multiple_mutational(fuzzer, executor, state, manager, corpus_idx),
+                _ => self.perform_batch_mutational(fuzzer, executor, state, manager, corpus_idx),
+            }
+        } else if unsafe { CPU_WORKERS } > 1 {
+            self.perform_parallel_mutational(fuzzer, executor, state, manager, corpus_idx)
         } else {
             // default 
             self.perform_mutational(fuzzer, executor, state, manager, corpus_idx)
@@ -573,6 +1207,255 @@ where
     }
 }
 
+impl<E, EM, I, M, S, Z> StdGPUMutationalStage<E, EM, I, M, S, Z>
+where
+    M: Mutator<I, S>,
+    I: Input + Serialize + EVMInputT + VMInputT<EVMState, EVMAddress, EVMAddress> + 'static,
+    S: HasClientPerfMonitor
+        + HasCorpus<I>
+        + HasRand
+        + HasStartTime
+        + HasExecutions
+        + HasMetadata
+        + HasExecutionResult<EVMAddress, EVMAddress, EVMState, Vec<u8>>,
+    Z: Evaluator<E, EM, I, S>,
+{
+    /// Replay the sequences pushed through the control socket from the initial state, each
+    /// transaction on the state left by the previous one
+    fn replay_pushed(&mut self, fuzzer: &mut Z, executor: &mut E, state: &mut S, manager: &mut EM) -> Result<(), Error> {
+        let pushed = take_pushed();
+        if pushed.is_empty() || state.corpus().count() == 0 {
+            return Ok(());
+        }
+        let initial = state.corpus().get(0)?.borrow_mut().load_input()?.get_staged_state().clone();
+        for txns in pushed {
+            let mut vm_state = initial.clone();
+            for txn in &txns {
+                unsafe {
+                    CALL_UNTIL = txn.reentrancy_limit;
+                }
+                let input = match (Box::new(txn.to_input(vm_state.clone())) as Box<dyn std::any::Any>).downcast::<I>() {
+                    Ok(input) => *input,
+                    Err(_) => return Ok(()),
+                };
+                let (res, corpus_idx) = fuzzer.evaluate_input(state, executor, manager, input)?;
+                debug!("[control] pushed transaction: {:?}, corpus entry {:?}", res, corpus_idx);
+                vm_state = state.get_execution_result().new_state.clone();
+            }
+            info!("[control] replayed a pushed sequence of {} transactions", txns.len());
+        }
+        Ok(())
+    }
+
+    /// Differential testing of the GPU backend (see [`crate::differential`]): the mutants of the
+    /// seed run on the GPU then on revm, and the ones whose outcomes differ are minimized and
+    /// dumped
+    fn perform_differential(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let mut mutants: Vec<I> = Vec::with_capacity(batch_size() as usize);
+        for i in 0..batch_size() as usize {
+            let mut input = state.corpus().get(corpus_idx)?.borrow_mut().load_input()?.clone();
+            self.mutator_mut().mutate(state, &mut input, i as i32)?;
+            // the kernel cannot run it: revm alone has nothing to be compared with
+            if input.is_step() || !gpu_can_execute(&input.get_contract(), &input.get_calldata()) {
+                let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+                continue;
+            }
+            mutants.push(input);
+        }
+        // each thread runs on its own storage, one launch per env (see `group_by_env`)
+        let mut input_vec: Vec<I> = Vec::with_capacity(mutants.len());
+        let mut gpu_outcomes = Vec::with_capacity(mutants.len());
+        for group in group_by_env(mutants) {
+            let mut launched: Vec<I> = Vec::with_capacity(group.len());
+            for input in group {
+                let tid = launched.len() as u32;
+                match input.cu_load_storage(tid).and_then(|_| input.cu_load_input(tid, tid)) {
+                    Ok(()) => launched.push(input),
+                    Err(e) => report_marshal_error(&e),
+                }
+            }
+            if launched.is_empty() {
+                continue;
+            }
+            launched[0].cu_load_evm_env();
+            unsafe {
+                cuEvalTxn(launched.len() as u32);
+            }
+            *state.executions_mut() += launched.len();
+            gpu_outcomes.extend((0..launched.len()).map(|tid| read_gpu_outcome(tid as u32)));
+            input_vec.extend(launched);
+        }
+
+        // a single input on the first thread, `None` when the kernel cannot run it or it does not
+        // fit the runner
+        let gpu = |input: &I| -> Option<Outcome> {
+            if input.is_step() || !gpu_can_execute(&input.get_contract(), &input.get_calldata()) {
+                return None;
+            }
+            input.cu_load_storage(0).and_then(|_| input.cu_load_input(0, 0)).ok()?;
+            input.cu_load_evm_env();
+            unsafe {
+                cuEvalTxn(1);
+            }
+            Some(read_gpu_outcome(0))
+        };
+        let mut revm = |input: I| -> Result<Outcome, Error> {
+            let _ = fuzzer.evaluate_input(state, executor, manager, input)?;
+            let result = state.get_execution_result();
+            Ok(Outcome {
+                reverted: result.reverted,
+                output: result.output.clone(),
+            })
+        };
+        for (input, gpu_outcome) in input_vec.into_iter().zip(gpu_outcomes) {
+            let cpu_outcome = revm(input.clone())?;
+            if !diverges(&gpu_outcome, &cpu_outcome) {
+                continue;
+            }
+            let mut outcomes = (gpu_outcome, cpu_outcome);
+            let minimized = minimize_calldata(&input.get_calldata(), |calldata| {
+                let mut candidate = input.clone();
+                candidate.set_direct_data(calldata.to_vec());
+                match (gpu(&candidate), revm(candidate)) {
+                    (Some(gpu_outcome), Ok(cpu_outcome)) if diverges(&gpu_outcome, &cpu_outcome) => {
+                        outcomes = (gpu_outcome, cpu_outcome);
+                        true
+                    }
+                    _ => false,
+                }
+            });
+            record_divergence(&input, &minimized, &outcomes.0, &outcomes.1);
+        }
+        Ok(())
+    }
+
+    /// Select the seeds of the resident pool around the scheduled one and pin them in device
+    /// memory, each with its storage, env, calldata and argument types
+    fn sync_resident(&mut self, state: &mut S, corpus_idx: usize) -> Result<(), Error> {
+        // only the testcases added since the last sync are checked
+        for idx in self.resident_scanned..state.corpus().count() {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            let input = testcase.load_input()?;
+            let calldata = input.to_bytes();
+            if !input.is_step() && calldata.len() > 4 && gpu_can_execute(&input.get_contract(), &calldata) {
+                self.resident_candidates.push(idx);
+            }
+        }
+        self.resident_scanned = state.corpus().count();
+        let candidates = self
+            .resident_candidates
+            .iter()
+            .map(|idx| (*idx, path_rounds(state, *idx)))
+            .collect::<Vec<_>>();
+        let k = unsafe { GPU_RESIDENT_SEEDS }.min(batch_size() as usize);
+        self.resident.clear();
+        unsafe {
+            cuSetResidentSeeds(0);
+        }
+        for idx in select_resident(corpus_idx, &candidates, k) {
+            let input = state.corpus().get(idx)?.borrow_mut().load_input()?.clone();
+            let slot = self.resident.len() as u32;
+            let types = input.get_types_vec();
+            let pinned = input
+                .cu_load_storage(slot)
+                .and_then(|_| input.cu_load_input(slot, slot))
+                .and_then(|_| ffi_len("argument types", types.len()));
+            match pinned {
+                Ok(types_len) => {
+                    let (to, timestamp, blocknum) = input.cu_evm_env();
+                    unsafe {
+                        cuPinResidentSeed(
+                            slot,
+                            to.as_ptr(),
+                            timestamp.as_ptr(),
+                            blocknum.as_ptr(),
+                            types.as_ptr(),
+                            types_len,
+                        );
+                    }
+                    self.resident.push(input);
+                }
+                Err(e) => report_marshal_error(&e),
+            }
+        }
+        unsafe {
+            cuClearContinuation();
+            cuSetResidentSeeds(self.resident.len() as u32);
+        }
+        record_sync(self.resident.len());
+        Ok(())
+    }
+
+    /// Launch the kernel [`RESIDENT_LAUNCHES`] times on the resident pool, then replay the
+    /// mutants it kept on revm: the per-thread reports only describe the last launch, so the
+    /// findings are confirmed by the oracles of the host
+    fn perform_resident_mutational(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let launches = unsafe { RESIDENT_LAUNCHES }.max(1);
+        let mut coverage = vec![0u64; launches as usize];
+        next_batch();
+        let execute_range = batch_range("execute");
+        let findings = unsafe { cuEvalResident(launches, RESIDENT_HAVOC, coverage.as_mut_ptr()) };
+        drop(execute_range);
+        let _readback_range = batch_range("readback");
+        let threads = batch_size();
+        *state.executions_mut() += (launches * threads) as usize;
+        merge_gpu_branch_coverage(&self.resident[0]);
+        merge_gpu_opcode_stats(&self.resident[0]);
+        record_launches(coverage, threads, findings as usize);
+
+        let mut raw = [0u8; SEED_SIZE];
+        for idx in 0..findings {
+            let mut slot = 0u32;
+            let hnb: ExecuteCudaInputResult =
+                unsafe { std::mem::transmute(cuReadResidentFinding(idx, raw.as_mut_ptr(), &mut slot)) };
+            if matches!(hnb, ExecuteCudaInputResult::EXECNONE | ExecuteCudaInputResult::EXECREVERTED) {
+                continue;
+            }
+            let mut mutant = match self.resident.get(slot as usize) {
+                Some(seed) => seed.clone(),
+                None => continue,
+            };
+            if apply_raw_seed(&mut mutant, &mut raw) {
+                fuzzer.evaluate_input(state, executor, manager, mutant)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Seed mode with a resident pool: a regular seed batch and a re-upload of the pool when it is
+    /// stale, then a run of launches on the pool
+    fn perform_resident(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        if needs_sync(self.resident.len()) {
+            self.perform_multiple_mutational(fuzzer, executor, state, manager, corpus_idx)?;
+            self.sync_resident(state, corpus_idx)?;
+            if self.resident.is_empty() {
+                return Ok(());
+            }
+        }
+        self.perform_resident_mutational(fuzzer, executor, state, manager)
+    }
+}
+
 impl<E, EM, I, M, S, Z> StdGPUMutationalStage<E, EM, I, M, S, Z>
 where
     M: Mutator<I, S>,
@@ -587,6 +1470,9 @@ where
     pub fn new(mutator: M) -> Self {
         Self {
             mutator,
+            resident: vec![],
+            resident_candidates: vec![],
+            resident_scanned: 0,
             phantom: PhantomData,
         }
     }
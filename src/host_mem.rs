@@ -0,0 +1,227 @@
+//! Memory budget of the host process (`--max-host-mem 16G`)
+//!
+//! The corpora, the staged states and the caches of a campaign grow with its coverage, and a
+//! long campaign used to be OOM-killed after hours, losing its last findings. With a budget, the
+//! resident set size of the process is sampled (at most every [`SAMPLE_PERIOD`]) and compared
+//! to it:
+//! - above [`HIGH_WATERMARK`] percent of the budget, the structures that can give memory back
+//!   shrink, each at most once per [`SHRINK_COOLDOWN`] so that the allocator has time to return
+//!   the freed pages: the state corpus lowers its limit (`--max-states`) to three quarters of its
+//!   size, down to [`MIN_STATE_CORPUS`] staged states, and prunes the excess; the inputs of the
+//!   input corpus drop the VM states they carry, bound again to an infant state when picked; the
+//!   onchain storage and code caches are emptied, read again from the RPC cache on disk (not
+//!   with `force_cache`); the resolved event and error names are dropped, looked up again
+//! - above the budget for [`EXHAUSTED_GRACE`], shrinking did not help: the campaign stops as if
+//!   its time budget were exhausted, printing its summary, instead of being killed
+//!
+//! The number of entries of the structures growing with the campaign (accounted with
+//! [`account`]), the shrinks and the sampled sizes are written to `stats/host_mem.json`.
+use crate::workdir::stats_dir;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub const HOST_MEM_FILE: &str = "host_mem.json";
+
+/// Budget of the resident set size in bytes, 0 for none (`--max-host-mem`)
+pub static mut MAX_HOST_MEM: usize = 0;
+
+/// Percentage of the budget above which the structures shrink
+pub const HIGH_WATERMARK: usize = 85;
+
+/// Smallest limit of the state corpus
+pub const MIN_STATE_CORPUS: usize = 32;
+
+pub const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+pub const SHRINK_COOLDOWN: Duration = Duration::from_secs(30);
+pub const EXHAUSTED_GRACE: Duration = Duration::from_secs(60);
+/// Write the stats every this many samples
+const STATS_SAMPLES: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemPressure {
+    Normal,
+    /// Above [`HIGH_WATERMARK`] percent of the budget
+    High,
+    /// Above the budget
+    Exhausted,
+}
+
+#[derive(Default)]
+struct HostMem {
+    rss: usize,
+    peak_rss: usize,
+    samples: usize,
+    sampled_at: Option<Instant>,
+    /// Since when the budget is exceeded
+    exhausted_since: Option<Instant>,
+    /// Structure -> number of entries
+    entries: BTreeMap<&'static str, usize>,
+    /// Structure -> number of shrinks, and when it last shrunk
+    shrinks: BTreeMap<&'static str, (usize, Instant)>,
+}
+
+static mut HOST_MEM: Option<HostMem> = None;
+
+fn host_mem() -> &'static mut HostMem {
+    unsafe { HOST_MEM.get_or_insert_with(Default::default) }
+}
+
+/// Parse a size in bytes, with an optional binary suffix (`512M`, `16G`, `1.5T`)
+pub fn parse_mem_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((idx, _)) => s.split_at(idx),
+        None => (s, ""),
+    };
+    let shift = match unit.to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("invalid memory size {} (e.g. 16G)", s)),
+    };
+    let number: f64 = number.trim().parse().map_err(|_| format!("invalid memory size {} (e.g. 16G)", s))?;
+    Ok((number * (1u64 << shift) as f64) as usize)
+}
+
+/// Resident set size of the process, from `/proc/self/status`
+pub fn resident_bytes() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+pub fn pressure_of(rss: usize, budget: usize) -> MemPressure {
+    if budget == 0 || rss < budget / 100 * HIGH_WATERMARK {
+        MemPressure::Normal
+    } else if rss < budget {
+        MemPressure::High
+    } else {
+        MemPressure::Exhausted
+    }
+}
+
+fn sample(mem: &mut HostMem, budget: usize) {
+    if mem.sampled_at.map_or(false, |at| at.elapsed() < SAMPLE_PERIOD) {
+        return;
+    }
+    mem.sampled_at = Some(Instant::now());
+    mem.rss = match resident_bytes() {
+        Some(rss) => rss,
+        None => return,
+    };
+    mem.peak_rss = mem.peak_rss.max(mem.rss);
+    mem.samples += 1;
+    match pressure_of(mem.rss, budget) {
+        MemPressure::Exhausted => {
+            if mem.exhausted_since.is_none() {
+                warn!("[host-mem] resident set of {} MiB over the budget of {} MiB", mem.rss >> 20, budget >> 20);
+                mem.exhausted_since = Some(Instant::now());
+            }
+        }
+        _ => mem.exhausted_since = None,
+    }
+    if mem.samples % STATS_SAMPLES == 1 {
+        save_host_mem_stats(mem, budget);
+    }
+}
+
+/// Current memory pressure, sampled at most every [`SAMPLE_PERIOD`]
+pub fn pressure() -> MemPressure {
+    let budget = unsafe { MAX_HOST_MEM };
+    if budget == 0 {
+        return MemPressure::Normal;
+    }
+    let mem = host_mem();
+    sample(mem, budget);
+    pressure_of(mem.rss, budget)
+}
+
+/// Whether `structure` should give memory back now, recording the shrink if so
+pub fn should_shrink(structure: &'static str) -> bool {
+    if pressure() < MemPressure::High {
+        return false;
+    }
+    let shrinks = &mut host_mem().shrinks;
+    if shrinks.get(structure).map_or(false, |(_, at)| at.elapsed() < SHRINK_COOLDOWN) {
+        return false;
+    }
+    let shrink = shrinks.entry(structure).or_insert((0, Instant::now()));
+    *shrink = (shrink.0 + 1, Instant::now());
+    true
+}
+
+/// Record the number of entries of a structure growing with the campaign
+pub fn account(structure: &'static str, entries: usize) {
+    if unsafe { MAX_HOST_MEM } > 0 {
+        host_mem().entries.insert(structure, entries);
+    }
+}
+
+/// Whether the budget has been exceeded for [`EXHAUSTED_GRACE`] despite the shrinks
+pub fn exhausted() -> bool {
+    pressure() == MemPressure::Exhausted
+        && host_mem().exhausted_since.map_or(false, |since| since.elapsed() >= EXHAUSTED_GRACE)
+}
+
+/// Summary of the memory use for the log
+pub fn describe_host_mem() -> String {
+    let mem = host_mem();
+    format!(
+        "resident set {} MiB (peak {} MiB) of a budget of {} MiB",
+        mem.rss >> 20,
+        mem.peak_rss >> 20,
+        unsafe { MAX_HOST_MEM } >> 20
+    )
+}
+
+fn save_host_mem_stats(mem: &HostMem, budget: usize) {
+    let stats = json!({
+        "budget": budget,
+        "rss": mem.rss,
+        "peak_rss": mem.peak_rss,
+        "pressure": format!("{:?}", pressure_of(mem.rss, budget)).to_lowercase(),
+        "entries": mem.entries,
+        "shrinks": mem.shrinks.iter().map(|(name, (count, _))| (*name, *count)).collect::<BTreeMap<_, _>>(),
+    });
+    let path = Path::new(&stats_dir()).join(HOST_MEM_FILE);
+    if let Ok(mut file) = File::create(path) {
+        let _ = file.write_all(serde_json::to_string_pretty(&stats).unwrap().as_bytes());
+    }
+}
+
+/// Write the stats a last time, e.g., when the campaign stops
+pub fn flush_host_mem_stats() {
+    let budget = unsafe { MAX_HOST_MEM };
+    if budget > 0 {
+        save_host_mem_stats(host_mem(), budget);
+        info!("[host-mem] {}", describe_host_mem());
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_mem() {
+        assert_eq!(parse_mem_size("16G"), Ok(16 << 30));
+        assert_eq!(parse_mem_size("512MiB"), Ok(512 << 20));
+        assert_eq!(parse_mem_size("1.5k"), Ok(1536));
+        assert_eq!(parse_mem_size("4096"), Ok(4096));
+        assert!(parse_mem_size("16Q").is_err());
+
+        let budget = 1000 << 20;
+        assert_eq!(pressure_of(100 << 20, budget), MemPressure::Normal);
+        assert_eq!(pressure_of(900 << 20, budget), MemPressure::High);
+        assert_eq!(pressure_of(budget, budget), MemPressure::Exhausted);
+        assert_eq!(pressure_of(usize::MAX, 0), MemPressure::Normal);
+        assert!(resident_bytes().map_or(true, |rss| rss > 0));
+    }
+}
@@ -95,6 +95,10 @@ where
     #[cfg(feature = "evm")]
     fn get_txn_value_temp(&self) -> Option<EVMU256>;
 
+    /// Get the block the transaction is warped to, 0 when it runs in the default block
+    #[cfg(feature = "evm")]
+    fn get_warp_to(&self) -> u64;
+
     // /// Get the value of the transaction
     #[cfg(feature = "evm")]
     fn get_cuda_input(&self) -> Vec<u8>;
@@ -1,21 +1,43 @@
 #![feature(downcast_unchecked)]
 extern crate core;
 
+pub mod backend_cost;
+pub mod batch_composer;
+pub mod batch_size;
+pub mod campaign;
+pub mod corpus_journal;
+pub mod cpu_pool;
 pub mod cache;
+pub mod differential;
+pub mod error;
 pub mod r#const;
 pub mod evm;
 pub mod executor;
+pub mod experiment;
 pub mod feedback;
+pub mod ffi_marshal;
 pub mod fuzzer;
 pub mod fuzzers;
 pub mod generic_vm;
 pub mod indexed_corpus;
 pub mod input;
+pub mod logging;
+#[cfg(feature = "mock_runner")]
+pub mod mock_runner;
 mod r#move;
+pub mod nvtx;
 pub mod oracle;
+pub mod perf;
+pub mod quickstart;
+#[cfg(feature = "grpc")]
+pub mod remote;
+pub mod resident_pool;
+pub mod runner_sys;
 pub mod scheduler;
 pub mod state;
 pub mod state_input;
 pub mod telemetry;
 pub mod tracer;
 pub mod gpu_stage;
+pub mod host_mem;
+pub mod workdir;
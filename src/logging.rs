@@ -0,0 +1,30 @@
+//! Logging setup, based on `tracing`
+//!
+//! Verbosity is controlled per module with a filter such as
+//! `ityfuzz::gpu_stage=debug,ityfuzz::fuzzer=info` (see [`EnvFilter`]), so that GPU debug output
+//! can be enabled or silenced independently from the oracle output.
+
+use tracing_subscriber::EnvFilter;
+
+/// Default filter, used when no filter is given
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// Initialize the global logger.
+///
+/// `filter` is a comma separated list of `target=level` directives (`RUST_LOG` is used when empty).
+/// When `json` is set, logs are emitted as JSON lines for machines, otherwise as colored text.
+/// The drivers running before the arguments are parsed (`cli campaign`, `cli quick`, ...) log
+/// with the default filter, a later initialization is then ignored.
+pub fn init_logging(filter: &str, json: bool) {
+    let filter = if filter.is_empty() {
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER))
+    } else {
+        EnvFilter::try_new(filter).expect("invalid log filter")
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        let _ = builder.json().try_init();
+    } else {
+        let _ = builder.with_ansi(true).try_init();
+    }
+}
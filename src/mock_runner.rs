@@ -0,0 +1,661 @@
+//! Deterministic mock of the GPU runner (`--features mock_runner`)
+//!
+//! Implements every entry point of [`crate::runner_sys`] in Rust, so that the glue around the
+//! runner (marshalling of seeds, storage and env, batches, read back of the results of the
+//! threads) can be tested in CI without a GPU, with `--runner-lib mock` or, in the tests,
+//! [`mock_runner`]. The mock does not execute the bytecode; the outcome of a thread is a
+//! fixed function of its seed:
+//! - a seed of less than 4 bytes of calldata reverts
+//! - each 4-byte window of the calldata is an edge of the coverage map, a seed reaching an edge
+//!   no seed reached before is interesting
+//! - a seed containing [`MOCK_BUG_MARKER`] is buggy
+//! - a seed whose argument word starts with [`MOCK_LEAK_MARKER`] leaks control: the word is the
+//!   target of the call, at the PC of its offset, with the calldata after the selector as the
+//!   arguments
+//! - the returned data is the calldata after the selector
+//! - with the opcode statistics on, each byte of the calldata counts as the opcode executed at
+//!   its offset
+//! - a thread of a resident launch runs the resident seed `(thread + launch) % count` (on its own
+//!   storage snapshot and env), mutated
+//!   once per havoc round, and the mutants neither reverting nor uninteresting are kept as
+//!   findings (at most [`MOCK_RESIDENT_FINDINGS`])
+//!
+//! The storage snapshots, the env, the continuation, the patched immutables and the pools are
+//! recorded as handed over, for the tests to check them ([`with_mock`]).
+#![allow(non_snake_case)]
+use crate::evm::config::{NJOBS, SEED_SIZE};
+use crate::ffi_marshal::{SEED_HEADER_SIZE, SLOT_SIZE};
+use crate::fuzzer::ExecuteCudaInputResult;
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_char;
+use std::slice;
+use std::sync::MutexGuard;
+
+/// Calldata bytes making a thread buggy
+pub const MOCK_BUG_MARKER: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+/// First bytes of an argument word making a thread leak control through it
+pub const MOCK_LEAK_MARKER: [u8; 4] = [0xca, 0x11, 0xba, 0xc4];
+
+/// Size of the coverage map of the mock
+pub const MOCK_MAP_SIZE: usize = 4096;
+
+/// Threads the mock reports as able to run at once
+pub const MOCK_ACTIVE_THREADS: u32 = 256;
+
+/// Findings kept by the resident launches
+pub const MOCK_RESIDENT_FINDINGS: usize = 1024;
+
+/// Seed loaded on a thread
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockSeed {
+    pub caller: [u8; 20],
+    pub value: [u8; 32],
+    pub calldata: Vec<u8>,
+    pub state_idx: u32,
+}
+
+/// Outcome of the last execution of a thread
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockOutcome {
+    pub result: u8,
+    pub output: Vec<u8>,
+    pub reverted: bool,
+    /// PC of the leaking call and calldata offset of its target
+    pub leak: Option<(u32, u32)>,
+}
+
+/// Everything handed to the runner, and the results of the last batch
+#[derive(Clone, Debug, Default)]
+pub struct MockRunner {
+    pub kernel: Option<String>,
+    pub device: i32,
+    pub batch_size: u32,
+    pub evm_version: u8,
+    pub loop_bound: u8,
+    pub mem_sanitizer: bool,
+    pub opcode_stats: bool,
+    /// Opcodes and PCs counted since the last read back
+    pub op_counts: HashMap<u8, u64>,
+    pub pc_counts: HashMap<u32, u64>,
+    pub pc_trace_size: u32,
+    pub callers: Vec<[u8; 20]>,
+    pub addresses: Vec<[u8; 20]>,
+    pub immutables: HashMap<u32, [u8; 32]>,
+    /// `to`, timestamp and block number
+    pub env: Option<([u8; 20], [u8; 32], [u8; 32])>,
+    pub continuation: Option<u32>,
+    pub seeds: HashMap<u32, MockSeed>,
+    /// Slots (key, value, little endian) by state
+    pub storages: HashMap<u32, Vec<([u8; 32], [u8; 32])>>,
+    pub coverage: HashSet<usize>,
+    pub bugs: u64,
+    pub outcomes: HashMap<u32, MockOutcome>,
+    pub batches: usize,
+    /// Resident seeds by slot, the first `resident_count` ones being sampled
+    pub resident: HashMap<u32, MockSeed>,
+    /// `to`, timestamp and block number of the resident seeds by slot
+    pub resident_envs: HashMap<u32, ([u8; 20], [u8; 32], [u8; 32])>,
+    pub resident_count: u32,
+    /// Slot, mutant and result of the findings of the last resident launches
+    pub findings: Vec<(u32, MockSeed, u8)>,
+}
+
+static mut MOCK: Option<MockRunner> = None;
+
+fn mock() -> &'static mut MockRunner {
+    unsafe { MOCK.get_or_insert_with(MockRunner::default) }
+}
+
+/// Inspect the state of the mock
+pub fn with_mock<T>(f: impl FnOnce(&MockRunner) -> T) -> T {
+    f(mock())
+}
+
+/// Install the mock as the runner, from a clean state
+pub fn load_mock_runner() {
+    unsafe {
+        MOCK = Some(MockRunner {
+            batch_size: NJOBS,
+            ..Default::default()
+        });
+    }
+    crate::runner_sys::install_mock_runner();
+}
+
+/// The mock installed as the runner until the guard drops, see [`mock_runner`]
+pub struct MockRunnerGuard {
+    _runner: MutexGuard<'static, ()>,
+}
+
+impl Drop for MockRunnerGuard {
+    fn drop(&mut self) {
+        crate::runner_sys::unload_runner();
+        unsafe {
+            MOCK = None;
+        }
+    }
+}
+
+/// Install the mock as the runner for a test: the tests using the runner are serialised on the
+/// guard, which unloads the mock when it drops
+pub fn mock_runner() -> MockRunnerGuard {
+    let runner = crate::runner_sys::lock_runner();
+    load_mock_runner();
+    MockRunnerGuard { _runner: runner }
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        return &[];
+    }
+    slice::from_raw_parts(ptr, len)
+}
+
+unsafe fn array<const N: usize>(ptr: *const u8) -> [u8; N] {
+    let mut array = [0u8; N];
+    if !ptr.is_null() {
+        array.copy_from_slice(bytes(ptr, N));
+    }
+    array
+}
+
+/// Edge of the coverage map reached by a window of the calldata
+fn edge(window: &[u8]) -> usize {
+    window
+        .iter()
+        .fold(2166136261u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(16777619)) as usize
+        % MOCK_MAP_SIZE
+}
+
+fn execute(seed: &MockSeed, coverage: &mut HashSet<usize>) -> MockOutcome {
+    if seed.calldata.len() < 4 {
+        return MockOutcome {
+            result: ExecuteCudaInputResult::EXECREVERTED as u8,
+            output: vec![],
+            reverted: true,
+            leak: None,
+        };
+    }
+    let mut new_coverage = false;
+    for window in seed.calldata.windows(4) {
+        new_coverage |= coverage.insert(edge(window));
+    }
+    let buggy = seed.calldata.windows(4).any(|window| window == MOCK_BUG_MARKER);
+    let leak = (4..seed.calldata.len())
+        .step_by(32)
+        .find(|offset| seed.calldata[*offset..].starts_with(&MOCK_LEAK_MARKER))
+        .map(|offset| (offset as u32, offset as u32));
+    let result = if buggy {
+        ExecuteCudaInputResult::EXECBUGGY
+    } else if leak.is_some() {
+        ExecuteCudaInputResult::EXECCONTROLLEAK
+    } else if new_coverage {
+        ExecuteCudaInputResult::EXECINTERESTING
+    } else {
+        ExecuteCudaInputResult::EXECNONE
+    };
+    MockOutcome {
+        result: result as u8,
+        output: seed.calldata[4..].to_vec(),
+        reverted: false,
+        leak,
+    }
+}
+
+/// Deterministic mutation of the arguments of a seed, depending on the thread
+fn mutate(calldata: &mut Vec<u8>, thread: u32) {
+    let mut x = 0x9e3779b97f4a7c15u64 ^ thread as u64;
+    for byte in calldata.iter_mut().skip(4) {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        if x % 4 == 0 {
+            *byte = x as u8;
+        }
+    }
+}
+
+pub unsafe extern "C" fn InitCudaCtx(dev: i32, path_to_kernel: *const c_char) {
+    let runner = mock();
+    runner.device = dev;
+    if !path_to_kernel.is_null() {
+        runner.kernel = Some(std::ffi::CStr::from_ptr(path_to_kernel).to_string_lossy().to_string());
+    }
+}
+
+pub unsafe extern "C" fn cuMallocAll() {}
+
+pub unsafe extern "C" fn cuSetPcTraceSize(size: u32) {
+    mock().pc_trace_size = size;
+}
+
+pub unsafe extern "C" fn cuSetLoopBound(bound: u8) {
+    mock().loop_bound = bound;
+}
+
+pub unsafe extern "C" fn cuEnableMemSanitizer(enable: bool) {
+    mock().mem_sanitizer = enable;
+}
+
+pub unsafe extern "C" fn cuEnableOpcodeStats(enable: bool) {
+    mock().opcode_stats = enable;
+}
+
+pub unsafe extern "C" fn cuSetEVMVersion(version: u8) {
+    mock().evm_version = version;
+}
+
+pub unsafe extern "C" fn cuMaxActiveThreads() -> u32 {
+    MOCK_ACTIVE_THREADS
+}
+
+pub unsafe extern "C" fn cuSetBatchSize(threads: u32) {
+    mock().batch_size = threads;
+}
+
+pub unsafe extern "C" fn cuPatchImmutable(offset: u32, value: *const u8) {
+    mock().immutables.insert(offset, array(value));
+}
+
+pub unsafe extern "C" fn cuAddCallerPool(added_caller: *const u8, pool_len: u32) {
+    let runner = mock();
+    runner.callers.push(array(added_caller));
+    runner.callers.truncate(pool_len as usize);
+}
+
+pub unsafe extern "C" fn cuAddAddressPool(added_addr: *const u8, pool_len: u32) {
+    let runner = mock();
+    runner.addresses.push(array(added_addr));
+    runner.addresses.truncate(pool_len as usize);
+}
+
+pub unsafe extern "C" fn setEVMEnv(to: *const u8, timestamp: *const u8, blocknum: *const u8) -> bool {
+    mock().env = Some((array(to), array(timestamp), array(blocknum)));
+    true
+}
+
+pub unsafe extern "C" fn cuLoadSeed(
+    caller_ptr: *const u8,
+    value_ptr: *const u8,
+    data_ptr: *const u8,
+    data_size: u32,
+    state_idx: u32,
+    thread: u32,
+) {
+    mock().seeds.insert(
+        thread,
+        MockSeed {
+            caller: array(caller_ptr),
+            value: array(value_ptr),
+            calldata: bytes(data_ptr, data_size as usize).to_vec(),
+            state_idx,
+        },
+    );
+}
+
+pub unsafe extern "C" fn cuGetStoragePos(s_idx: u32) -> u32 {
+    s_idx
+}
+
+pub unsafe extern "C" fn cuLoadStorage(src: *const u8, slot_cnt: u32, state_id: u32) {
+    let slots = bytes(src, slot_cnt as usize * SLOT_SIZE)
+        .chunks(SLOT_SIZE)
+        .map(|slot| (array(slot.as_ptr()), array(slot[32..].as_ptr())))
+        .collect();
+    mock().storages.insert(state_id, slots);
+}
+
+pub unsafe extern "C" fn cuFreeStorage(state_id: u32) {
+    mock().storages.remove(&state_id);
+}
+
+pub unsafe extern "C" fn cuLoadContinuation(
+    pc: u32,
+    _stack: *const u8,
+    _stack_len: u32,
+    _memory: *const u8,
+    _memory_len: u32,
+    _call_data: *const u8,
+    _call_data_len: u32,
+    _output_offset: u32,
+    _output_len: u32,
+    _caller: *const u8,
+    _value: *const u8,
+) -> bool {
+    mock().continuation = Some(pc);
+    true
+}
+
+pub unsafe extern "C" fn cuClearContinuation() {
+    mock().continuation = None;
+}
+
+pub unsafe extern "C" fn cuEvalTxn(nwrap: u32) {
+    let runner = mock();
+    runner.batches += 1;
+    for thread in 0..nwrap {
+        let seed = runner.seeds.get(&thread).cloned().unwrap_or_default();
+        let outcome = execute(&seed, &mut runner.coverage);
+        if runner.opcode_stats {
+            for (pc, op) in seed.calldata.iter().enumerate() {
+                *runner.op_counts.entry(*op).or_insert(0) += 1;
+                *runner.pc_counts.entry(pc as u32).or_insert(0) += 1;
+            }
+        }
+        if outcome.result == ExecuteCudaInputResult::EXECBUGGY as u8 {
+            runner.bugs += 1;
+        }
+        runner.outcomes.insert(thread, outcome);
+    }
+}
+
+pub unsafe extern "C" fn cuPreMutate(_arg_types: *const u8, _arg_types_len: u32) {}
+
+/// Mutates the seed of the first thread into the seeds of the batch, and runs them
+pub unsafe extern "C" fn cuMutate(_calldasize: u32) {
+    let runner = mock();
+    let seed = runner.seeds.get(&0).cloned().unwrap_or_default();
+    for thread in 1..runner.batch_size {
+        let mut mutated = seed.clone();
+        mutate(&mut mutated.calldata, thread);
+        runner.seeds.insert(thread, mutated);
+    }
+    let threads = runner.batch_size;
+    cuEvalTxn(threads);
+}
+
+/// Pins the seed loaded on the thread `slot`, with its env
+pub unsafe extern "C" fn cuPinResidentSeed(
+    slot: u32,
+    to: *const u8,
+    timestamp: *const u8,
+    blocknum: *const u8,
+    _arg_types: *const u8,
+    _arg_types_len: u32,
+) {
+    let runner = mock();
+    let seed = runner.seeds.get(&slot).cloned().unwrap_or_default();
+    runner.resident.insert(slot, seed);
+    runner.resident_envs.insert(slot, (array(to), array(timestamp), array(blocknum)));
+}
+
+pub unsafe extern "C" fn cuSetResidentSeeds(count: u32) {
+    mock().resident_count = count;
+}
+
+pub unsafe extern "C" fn cuEvalResident(launches: u32, havoc: u32, coverage: *mut u64) -> u32 {
+    let runner = mock();
+    runner.findings.clear();
+    if runner.resident_count == 0 {
+        return 0;
+    }
+    let coverage = slice::from_raw_parts_mut(coverage, launches as usize);
+    for launch in 0..launches {
+        runner.batches += 1;
+        for thread in 0..runner.batch_size {
+            let slot = (thread + launch) % runner.resident_count;
+            let mut seed = runner.resident.get(&slot).cloned().unwrap_or_default();
+            for round in 0..havoc.max(1) {
+                mutate(&mut seed.calldata, thread ^ (launch << 16) ^ (round << 24));
+            }
+            let outcome = execute(&seed, &mut runner.coverage);
+            let kept = outcome.result != ExecuteCudaInputResult::EXECNONE as u8
+                && outcome.result != ExecuteCudaInputResult::EXECREVERTED as u8;
+            if kept && runner.findings.len() < MOCK_RESIDENT_FINDINGS {
+                runner.findings.push((slot, seed, outcome.result));
+            }
+        }
+        coverage[launch as usize] = runner.coverage.len() as u64;
+    }
+    runner.findings.len() as u32
+}
+
+pub unsafe extern "C" fn cuReadResidentFinding(idx: u32, raw_seed: *mut u8, slot: *mut u32) -> u8 {
+    match mock().findings.get(idx as usize).cloned() {
+        Some((resident_slot, seed, result)) => {
+            write_raw_seed(&seed, raw_seed);
+            *slot = resident_slot;
+            result
+        }
+        None => ExecuteCudaInputResult::EXECNONE as u8,
+    }
+}
+
+pub unsafe extern "C" fn getCudaExecRes(pcov: *const u64, pbug: *const u64) -> bool {
+    let runner = mock();
+    *(pcov as *mut u64) = runner.coverage.len() as u64;
+    *(pbug as *mut u64) = runner.bugs;
+    runner.outcomes.values().any(|outcome| outcome.result != ExecuteCudaInputResult::EXECNONE as u8)
+}
+
+pub unsafe extern "C" fn isCudaInteresting(tid: u32) -> u8 {
+    mock().outcomes.get(&tid).map(|outcome| outcome.result).unwrap_or(0)
+}
+
+/// Copy a seed in the layout of the device buffers: caller, value, calldata size and calldata
+unsafe fn write_raw_seed(seed: &MockSeed, raw_seed: *mut u8) {
+    let raw = slice::from_raw_parts_mut(raw_seed, SEED_SIZE);
+    raw[..20].copy_from_slice(&seed.caller);
+    raw[32..64].copy_from_slice(&seed.value);
+    raw[64..68].copy_from_slice(&(seed.calldata.len() as u32).to_le_bytes());
+    let len = seed.calldata.len().min(SEED_SIZE - SEED_HEADER_SIZE);
+    raw[SEED_HEADER_SIZE..SEED_HEADER_SIZE + len].copy_from_slice(&seed.calldata[..len]);
+}
+
+pub unsafe extern "C" fn gainCov(tid: u32, raw_seed: *mut u8) -> u8 {
+    let seed = mock().seeds.get(&tid).cloned().unwrap_or_default();
+    write_raw_seed(&seed, raw_seed);
+    isCudaInteresting(tid)
+}
+
+pub unsafe extern "C" fn cuDumpStorage(_thread_id: u32) {}
+
+/// The trace of a thread is its calldata, one byte per pc
+pub unsafe extern "C" fn cuReadPcTrace(tid: u32, pcs: *mut u32, ops: *mut u8, len: u32) -> u32 {
+    let seed = mock().seeds.get(&tid).cloned().unwrap_or_default();
+    let count = seed.calldata.len().min(len as usize);
+    for (idx, op) in seed.calldata.iter().take(count).enumerate() {
+        *pcs.add(idx) = idx as u32;
+        *ops.add(idx) = *op;
+    }
+    count as u32
+}
+
+pub unsafe extern "C" fn cuReadSanitizerReport(_tid: u32, _kind: *mut u8, _pc: *mut u32, _operands: *mut u8) -> bool {
+    false
+}
+
+pub unsafe extern "C" fn cuReadEnvAccess(_tid: u32) -> u32 {
+    0
+}
+
+/// Edges of the coverage map, as the directions of the branches
+pub unsafe extern "C" fn cuReadBranchCoverage(bitmap: *mut u8, len: u32) -> u32 {
+    let bitmap = slice::from_raw_parts_mut(bitmap, len as usize);
+    for edge in mock().coverage.iter().filter(|edge| **edge < bitmap.len()) {
+        bitmap[*edge] = 1;
+    }
+    len.min(MOCK_MAP_SIZE as u32)
+}
+
+pub unsafe extern "C" fn cuReadReturnData(tid: u32, output: *mut u8, cap: u32, reverted: *mut bool) -> u32 {
+    let outcome = mock().outcomes.get(&tid).cloned().unwrap_or_default();
+    let len = outcome.output.len().min(cap as usize);
+    slice::from_raw_parts_mut(output, len).copy_from_slice(&outcome.output[..len]);
+    *reverted = outcome.reverted;
+    len as u32
+}
+
+/// Counts since the last read back, `ops` has 256 entries and `pcs` is indexed by PC
+pub unsafe extern "C" fn cuReadOpcodeStats(ops: *mut u64, pcs: *mut u64, pcs_len: u32) -> u32 {
+    let runner = mock();
+    let ops = slice::from_raw_parts_mut(ops, 256);
+    for (op, count) in runner.op_counts.drain() {
+        ops[op as usize] += count;
+    }
+    let pcs = slice::from_raw_parts_mut(pcs, pcs_len as usize);
+    let mut len = 0;
+    for (pc, count) in runner.pc_counts.drain().filter(|(pc, _)| (*pc as usize) < pcs.len()) {
+        pcs[pc as usize] += count;
+        len = len.max(pc + 1);
+    }
+    len
+}
+
+/// The arguments of the leaking call are the calldata after the selector
+pub unsafe extern "C" fn cuReadControlLeak(
+    tid: u32,
+    pc: *mut u32,
+    target_offset: *mut u32,
+    args: *mut u8,
+    args_cap: u32,
+    args_len: *mut u32,
+) -> bool {
+    let runner = mock();
+    let (leak_pc, offset) = match runner.outcomes.get(&tid).and_then(|outcome| outcome.leak) {
+        Some(leak) => leak,
+        None => return false,
+    };
+    let seed = runner.seeds.get(&tid).cloned().unwrap_or_default();
+    let len = (seed.calldata.len() - 4).min(args_cap as usize);
+    slice::from_raw_parts_mut(args, len).copy_from_slice(&seed.calldata[4..4 + len]);
+    *pc = leak_pc;
+    *target_offset = offset;
+    *args_len = len as u32;
+    true
+}
+
+mod tests {
+    use super::*;
+    use crate::differential::read_gpu_outcome;
+    #[cfg(feature = "flashloan_v2")]
+    use crate::evm::input::EVMInputTy;
+    use crate::evm::input::{EVMInput, EVMInputT};
+    use crate::evm::mutator::AccessPattern;
+    use crate::evm::types::{EVMAddress, EVMU256};
+    use crate::evm::vm::{EVMState, EVMStorage};
+    use crate::state_input::StagedVMState;
+    use bytes::Bytes;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_mock_runner() {
+        let _runner = mock_runner();
+        let contract = EVMAddress::from_slice(&[0x22; 20]);
+        let mut vm_state = EVMState::new();
+        vm_state.state.insert(contract, EVMStorage::unit(EVMU256::from(1), EVMU256::from(2)));
+        let input = EVMInput {
+            caller: EVMAddress::from_slice(&[0x11; 20]),
+            contract,
+            data: None,
+            sstate: StagedVMState::new_with_state(vm_state),
+            sstate_idx: 0,
+            branch_distance: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            #[cfg(feature = "flashloan_v2")]
+            liquidation_percent: 0,
+            #[cfg(feature = "flashloan_v2")]
+            input_type: EVMInputTy::ABI,
+            direct_data: Bytes::from([vec![0xaa; 4], vec![0x22; 32]].concat()),
+            randomness: vec![],
+            repeat: 1,
+            cu_data: vec![],
+            is_cuda: false,
+            scenario: None,
+            adversary: None,
+            feed_prices: vec![],
+            force_feed: None,
+            block_advance: None,
+            gas_limit: None,
+        };
+        input.cu_load_storage(0).unwrap();
+        input.cu_load_input(0, 0).unwrap();
+        input.cu_load_evm_env();
+        let mut buggy = input.clone();
+        buggy.set_direct_data([vec![0xaa; 4], MOCK_BUG_MARKER.to_vec()].concat());
+        buggy.cu_load_storage(1).unwrap();
+        buggy.cu_load_input(1, 1).unwrap();
+
+        unsafe { crate::runner_sys::cuEvalTxn(2) };
+        with_mock(|runner| {
+            // the caller is handed over in little endian
+            assert_eq!(runner.seeds[&0].caller, [0x11; 20]);
+            assert_eq!(runner.storages[&0].len(), 1);
+            // each thread runs on its own storage snapshot
+            assert_eq!((runner.seeds[&0].state_idx, runner.seeds[&1].state_idx), (0, 1));
+            assert!(runner.env.is_some());
+        });
+        assert_eq!(unsafe { crate::runner_sys::isCudaInteresting(0) }, ExecuteCudaInputResult::EXECINTERESTING as u8);
+        assert_eq!(unsafe { crate::runner_sys::isCudaInteresting(1) }, ExecuteCudaInputResult::EXECBUGGY as u8);
+        assert_eq!(read_gpu_outcome(0).output, vec![0x22; 32]);
+
+        // same seed, same edges: not interesting anymore
+        unsafe { crate::runner_sys::cuEvalTxn(1) };
+        assert_eq!(unsafe { crate::runner_sys::isCudaInteresting(0) }, ExecuteCudaInputResult::EXECNONE as u8);
+
+        // a call to the address of the first argument, replayed on revm as a call to the caller
+        let mut leaking = input.clone();
+        let calldata = [vec![0xbb; 4], MOCK_LEAK_MARKER.to_vec(), vec![0x33; 28]].concat();
+        leaking.set_direct_data(calldata.clone());
+        leaking.cu_load_input(0, 0).unwrap();
+        unsafe { crate::runner_sys::cuEvalTxn(1) };
+        assert_eq!(unsafe { crate::runner_sys::isCudaInteresting(0) }, ExecuteCudaInputResult::EXECCONTROLLEAK as u8);
+        let report = crate::gpu_stage::read_control_leak(0).unwrap();
+        assert_eq!((report.pc, report.target_offset), (4, 4));
+        assert_eq!(report.args, calldata[4..].to_vec());
+        let redirected = report.redirect(&calldata, &leaking.caller).unwrap();
+        assert_eq!(redirected[4..16], [0; 12]);
+        assert_eq!(redirected[16..36], [0x11; 20]);
+        assert!(crate::gpu_stage::read_control_leak(1).is_none());
+
+        // resident seeds: the threads sample the pinned seeds between launches
+        input.cu_load_input(0, 0).unwrap();
+        buggy.cu_load_input(1, 1).unwrap();
+        for (slot, seed) in [(0, &input), (1, &buggy)] {
+            let (to, timestamp, blocknum) = seed.cu_evm_env();
+            unsafe {
+                crate::runner_sys::cuPinResidentSeed(
+                    slot,
+                    to.as_ptr(),
+                    timestamp.as_ptr(),
+                    blocknum.as_ptr(),
+                    std::ptr::null(),
+                    0,
+                );
+            }
+        }
+        unsafe {
+            crate::runner_sys::cuSetResidentSeeds(2);
+        }
+        with_mock(|runner| {
+            assert_eq!(runner.resident[&1].state_idx, 1);
+            assert_eq!(runner.resident_envs[&1], buggy.cu_evm_env());
+        });
+        let mut coverage = vec![0u64; 3];
+        let findings = unsafe { crate::runner_sys::cuEvalResident(3, 1, coverage.as_mut_ptr()) };
+        assert!(findings > 0);
+        assert!(coverage[0] <= coverage[1] && coverage[1] <= coverage[2]);
+        let mut raw = [0u8; SEED_SIZE];
+        let mut slot = u32::MAX;
+        let result = unsafe { crate::runner_sys::cuReadResidentFinding(0, raw.as_mut_ptr(), &mut slot) };
+        assert_ne!(result, ExecuteCudaInputResult::EXECNONE as u8);
+        assert!(slot < 2);
+        assert_eq!(raw[..20], [0x11; 20]);
+
+        // pruning a VMState frees the snapshots taken from it, not the id equal to its index
+        let mut pruned = input.clone();
+        pruned.sstate_idx = 1;
+        pruned.cu_load_storage(0).unwrap();
+        assert_eq!(crate::runner_sys::free_device_storages(1), vec![0]);
+        with_mock(|runner| {
+            assert!(!runner.storages.contains_key(&0));
+            assert!(runner.storages.contains_key(&1));
+        });
+        assert!(crate::runner_sys::free_device_storages(1).is_empty());
+    }
+}
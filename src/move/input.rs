@@ -195,6 +195,11 @@ impl VMInputT<MoveVMState, ModuleId, AccountAddress> for MoveFunctionInput {
         unreachable!("MoveVM does not have an ABI")
     }
 
+    #[cfg(feature = "evm")]
+    fn get_warp_to(&self) -> u64 {
+        unreachable!("MoveVM does not have an EVM env")
+    }
+
     #[cfg(feature = "evm")]
     fn get_cuda_input(&self) -> Vec<u8> {
         todo!()
@@ -0,0 +1,80 @@
+//! NVTX ranges around the GPU batches (`--features nvtx`)
+//!
+//! Under Nsight Systems (`nsys profile --trace=cuda,nvtx ./cli ...`), each GPU batch shows up as
+//! `batch <n>: upload` (mutation and marshalling of the seeds, storage and env), `batch <n>:
+//! execute` (kernel) and `batch <n>: readback` (coverage and results of each thread, including
+//! the inputs re-executed on revm), so that a throughput problem can be traced to a phase and
+//! to the kernel launches and copies of the batch. Without the feature the ranges are no-ops.
+#[cfg(feature = "nvtx")]
+use std::ffi::CString;
+#[cfg(feature = "nvtx")]
+use std::os::raw::c_char;
+
+#[cfg(feature = "nvtx")]
+#[link(name = "nvToolsExt")]
+extern "C" {
+    fn nvtxRangePushA(message: *const c_char) -> i32;
+    fn nvtxRangePop() -> i32;
+}
+
+static mut BATCH: u64 = 0;
+
+/// Range open until dropped
+pub struct NvtxRange {
+    _private: (),
+}
+
+impl NvtxRange {
+    #[allow(unused_variables)]
+    pub fn new(name: &str) -> Self {
+        #[cfg(feature = "nvtx")]
+        if let Ok(message) = CString::new(name) {
+            unsafe {
+                nvtxRangePushA(message.as_ptr());
+            }
+        }
+        Self { _private: () }
+    }
+}
+
+impl Drop for NvtxRange {
+    fn drop(&mut self) {
+        #[cfg(feature = "nvtx")]
+        unsafe {
+            nvtxRangePop();
+        }
+    }
+}
+
+pub fn range_name(batch: u64, phase: &str) -> String {
+    format!("batch {}: {}", batch, phase)
+}
+
+/// Start the ranges of a new batch
+pub fn next_batch() {
+    unsafe {
+        BATCH += 1;
+    }
+}
+
+/// Range of a phase of the current batch
+#[allow(unused_variables)]
+pub fn batch_range(phase: &str) -> NvtxRange {
+    #[cfg(feature = "nvtx")]
+    return NvtxRange::new(&range_name(unsafe { BATCH }, phase));
+    #[cfg(not(feature = "nvtx"))]
+    NvtxRange { _private: () }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_name() {
+        assert_eq!(range_name(12, "upload"), "batch 12: upload");
+        // ranges are balanced whether or not NVTX is linked
+        let outer = batch_range("execute");
+        drop(NvtxRange::new("inner"));
+        drop(outer);
+    }
+}
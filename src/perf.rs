@@ -0,0 +1,197 @@
+//! Micro-benchmark of the GPU runner against revm (`cli perf`)
+//!
+//! The campaign runs with its usual arguments, but the mutational stage only picks the seeds of
+//! the initial corpus (round-robin) and the random seed is fixed, so that runs on different
+//! hardware execute the same mutants. The stage first executes [`PERF_EXECUTIONS`] mutants on
+//! revm, then as many on the GPU runner, and reports the executions per second of both along
+//! with the latency breakdown of a GPU batch (upload of the seed / env / storage and mutation,
+//! kernel, readback of the results) to `stats/perf.json`.
+use crate::workdir::stats_dir;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const PERF_FILE: &str = "perf.json";
+
+/// GPU phase stage runs per seed without a GPU batch before giving up on the GPU
+pub const IDLE_ROUNDS_PER_SEED: u64 = 4;
+
+/// Executions of each executor, when running `cli perf`
+pub static mut PERF_EXECUTIONS: Option<u64> = None;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfPhase {
+    Revm,
+    Gpu,
+    Done,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PerfCounters {
+    pub revm_executions: u64,
+    pub revm: Duration,
+    pub gpu_executions: u64,
+    pub gpu_batches: u64,
+    /// Stage runs of the GPU phase
+    pub gpu_rounds: u64,
+    pub upload: Duration,
+    pub kernel: Duration,
+    pub readback: Duration,
+}
+
+static mut PERF: Option<PerfCounters> = None;
+/// Size of the initial corpus, the seeds benchmarked
+static mut PERF_SEEDS: usize = 0;
+static mut PERF_NEXT_SEED: usize = 0;
+
+pub fn is_perf() -> bool {
+    unsafe { PERF_EXECUTIONS.is_some() }
+}
+
+fn counters() -> &'static mut PerfCounters {
+    unsafe { PERF.get_or_insert_with(PerfCounters::default) }
+}
+
+/// Seed benchmarked next, the seeds of the corpus of size `corpus_size` when first called
+pub fn perf_seed(corpus_size: usize) -> usize {
+    unsafe {
+        if PERF_SEEDS == 0 {
+            PERF_SEEDS = corpus_size.max(1);
+        }
+        let idx = PERF_NEXT_SEED % PERF_SEEDS;
+        PERF_NEXT_SEED += 1;
+        idx
+    }
+}
+
+/// Phase of the benchmark, given whether the GPU runner is available
+pub fn perf_phase(gpu: bool) -> PerfPhase {
+    let target = unsafe { PERF_EXECUTIONS.unwrap_or(0) };
+    let perf = counters();
+    if perf.revm_executions < target {
+        PerfPhase::Revm
+    } else if gpu && perf.gpu_executions < target {
+        // give up when no seed can be executed on the GPU
+        perf.gpu_rounds += 1;
+        if perf.gpu_batches == 0 && perf.gpu_rounds > IDLE_ROUNDS_PER_SEED * unsafe { PERF_SEEDS } as u64 {
+            return PerfPhase::Done;
+        }
+        PerfPhase::Gpu
+    } else {
+        PerfPhase::Done
+    }
+}
+
+pub fn record_revm(elapsed: Duration) {
+    let perf = counters();
+    perf.revm_executions += 1;
+    perf.revm += elapsed;
+}
+
+pub fn record_gpu_batch(executions: u64, upload: Duration, kernel: Duration, readback: Duration) {
+    let perf = counters();
+    perf.gpu_executions += executions;
+    perf.gpu_batches += 1;
+    perf.upload += upload;
+    perf.kernel += kernel;
+    perf.readback += readback;
+}
+
+fn per_second(executions: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+    executions as f64 / elapsed.as_secs_f64()
+}
+
+fn per_batch_us(elapsed: Duration, batches: u64) -> f64 {
+    if batches == 0 {
+        return 0.0;
+    }
+    elapsed.as_secs_f64() * 1e6 / batches as f64
+}
+
+impl PerfCounters {
+    pub fn to_json(&self) -> serde_json::Value {
+        let gpu = self.upload + self.kernel + self.readback;
+        serde_json::json!({
+            "seeds": unsafe { PERF_SEEDS },
+            "revm": {
+                "executions": self.revm_executions,
+                "execs_per_sec": per_second(self.revm_executions, self.revm),
+            },
+            "gpu": {
+                "executions": self.gpu_executions,
+                "batches": self.gpu_batches,
+                "execs_per_sec": per_second(self.gpu_executions, gpu),
+                "upload_us": per_batch_us(self.upload, self.gpu_batches),
+                "kernel_us": per_batch_us(self.kernel, self.gpu_batches),
+                "readback_us": per_batch_us(self.readback, self.gpu_batches),
+            },
+            "speedup": if self.revm_executions == 0 || self.revm.is_zero() {
+                0.0
+            } else {
+                per_second(self.gpu_executions, gpu) / per_second(self.revm_executions, self.revm)
+            },
+        })
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "revm: {} executions, {:.0} execs/sec\n",
+            self.revm_executions,
+            per_second(self.revm_executions, self.revm)
+        );
+        if self.gpu_batches == 0 {
+            report.push_str("gpu:  not run (GPU disabled or no seed executable on the GPU)\n");
+            return report;
+        }
+        let gpu = self.upload + self.kernel + self.readback;
+        report.push_str(&format!(
+            "gpu:  {} executions, {:.0} execs/sec ({:.1}x)\n",
+            self.gpu_executions,
+            per_second(self.gpu_executions, gpu),
+            per_second(self.gpu_executions, gpu) / per_second(self.revm_executions, self.revm).max(f64::MIN_POSITIVE)
+        ));
+        report.push_str(&format!(
+            "      per batch: upload {:.0} us, kernel {:.0} us, readback {:.0} us\n",
+            per_batch_us(self.upload, self.gpu_batches),
+            per_batch_us(self.kernel, self.gpu_batches),
+            per_batch_us(self.readback, self.gpu_batches)
+        ));
+        report
+    }
+}
+
+/// Log the results and write them to `stats/perf.json`
+pub fn finish_perf() {
+    let perf = counters();
+    info!("{}", perf.report().trim_end());
+    let path = Path::new(&stats_dir()).join(PERF_FILE);
+    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&perf.to_json()).unwrap()) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perf_counters() {
+        let mut perf = PerfCounters::default();
+        perf.revm_executions = 1000;
+        perf.revm = Duration::from_secs(1);
+        assert!(perf.report().contains("not run"));
+
+        perf.gpu_executions = 8192;
+        perf.gpu_batches = 2;
+        perf.upload = Duration::from_millis(100);
+        perf.kernel = Duration::from_millis(300);
+        perf.readback = Duration::from_millis(100);
+        let json = perf.to_json();
+        assert_eq!(json["gpu"]["execs_per_sec"], 16384.0);
+        assert_eq!(json["speedup"], 16.384);
+        assert_eq!(json["gpu"]["kernel_us"], 150000.0);
+    }
+}
@@ -0,0 +1,207 @@
+//! Config-free quickstart (`cli quick Token.sol`)
+//!
+//! Compiles the source with the solc release matching its pragma (managed by `svm`, falling
+//! back to the `solc` on the PATH) into `quick-<name>/`, translates the main contract to a PTX
+//! kernel when ptxsema is available (`PTXSEMA` and `PTXSEMA_RT` pointing to
+//! `standalone-ptxsema` and `rt.o.bc`, `llvm-link` / `llc` on the PATH or given by `LLVM_LINK`
+//! / `LLC`, `CUDA_ARCH` defaulting to `sm_86`), and returns the arguments of a campaign with
+//! the default sanitizers and a budget of [`QUICK_TIME_BUDGET`] seconds. Arguments after the
+//! source file are passed to the campaign and take precedence over the defaults.
+use crate::evm::evm_version::EVMVersion;
+use crate::evm::ptx_binding::sign_ptx;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+/// First argument of the quickstart
+pub const QUICK_COMMAND: &str = "quick";
+
+/// Seconds of fuzzing of a quickstart campaign
+pub const QUICK_TIME_BUDGET: u64 = 300;
+
+/// Bug classes detected without instrumentation of the kernel
+pub const QUICK_SANITIZERS: &str = "reentrancy,selfdestruct,initializer,storage-write";
+
+/// Version of the first `pragma solidity` of the source, e.g., `0.8.19` for `^0.8.19`
+pub fn pragma_version(source: &str) -> Option<String> {
+    let pragma = source
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with("pragma solidity"))?;
+    let version = pragma
+        .trim_start_matches("pragma solidity")
+        .trim_end_matches(';')
+        .split(|c: char| c.is_whitespace() || c == '<')
+        .map(|v| v.trim_start_matches(|c: char| !c.is_ascii_digit()))
+        .find(|v| !v.is_empty())?;
+    let parts = version.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 || parts.iter().any(|p| p.parse::<u32>().is_err()) {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+fn on_path(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+fn svm_solc(version: &str) -> Option<PathBuf> {
+    let home = env::var("HOME").ok()?;
+    let solc = Path::new(&home).join(".svm").join(version).join(format!("solc-{}", version));
+    solc.exists().then(|| solc)
+}
+
+/// solc binary of the version, installed with `svm` if missing
+pub fn find_solc(version: Option<&str>) -> Result<String, String> {
+    if let Some(version) = version {
+        if svm_solc(version).is_none() && on_path("svm") {
+            let status = Command::new("svm")
+                .args(["install", version])
+                .status()
+                .map_err(|e| format!("failed to run svm: {}", e))?;
+            if !status.success() {
+                warn!("[quick] svm failed to install solc {}", version);
+            }
+        }
+        if let Some(solc) = svm_solc(version) {
+            return Ok(solc.to_string_lossy().to_string());
+        }
+        warn!("[quick] solc {} is not installed with svm, using the solc on the PATH", version);
+    }
+    if on_path("solc") {
+        return Ok("solc".to_string());
+    }
+    Err("no solc found, install svm (cargo install svm-rs) or solc".to_string())
+}
+
+/// Compile the source to `<name>.abi` / `<name>.bin` files of each contract in `out_dir`
+pub fn compile(source: &str, out_dir: &str) -> Result<(), String> {
+    let code = fs::read_to_string(source).map_err(|e| format!("failed to read {}: {}", source, e))?;
+    let solc = find_solc(pragma_version(&code).as_deref())?;
+    let base = Path::new(source).parent().map_or(".".to_string(), |p| p.to_string_lossy().to_string());
+    let output = Command::new(&solc)
+        .args([source, "-o", out_dir, "--bin", "--abi", "--overwrite", "--base-path"])
+        .arg(if base.is_empty() { "." } else { base.as_str() })
+        .output()
+        .map_err(|e| format!("failed to run {}: {}", solc, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed:\n{}", solc, String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Bytecode translated to the kernel: the contract named after the file, else the largest one
+pub(crate) fn main_bin(out_dir: &str, name: &str) -> Option<PathBuf> {
+    let named = Path::new(out_dir).join(format!("{}.bin", name));
+    if named.exists() {
+        return Some(named);
+    }
+    fs::read_dir(out_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "bin"))
+        .max_by_key(|path| fs::metadata(path).map_or(0, |m| m.len()))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{} exited with {}", program, status)),
+    }
+}
+
+/// Translate the bytecode to a signed PTX kernel, `None` if ptxsema is not available
+pub fn build_kernel(bin: &Path, out_dir: &str) -> Result<Option<String>, String> {
+    let (ptxsema, rt) = match (env::var("PTXSEMA"), env::var("PTXSEMA_RT")) {
+        (Ok(ptxsema), Ok(rt)) => (ptxsema, rt),
+        _ => return Ok(None),
+    };
+    let llvm_link = env::var("LLVM_LINK").unwrap_or("llvm-link".to_string());
+    let llc = env::var("LLC").unwrap_or("llc".to_string());
+    let arch = env::var("CUDA_ARCH").unwrap_or("sm_86".to_string());
+    let path = |file: &str| Path::new(out_dir).join(file).to_string_lossy().to_string();
+    let (ll, bc, ptx) = (path("bytecode.ll"), path("kernel.bc"), path("kernel.ptx"));
+    let bin = bin.to_string_lossy().to_string();
+    run(&ptxsema, &[&bin, "-o", &ll, "--hex", "--dump"])?;
+    run(&llvm_link, &[&rt, &ll, "-o", &bc])?;
+    run(&llc, &[&format!("-mcpu={}", arch), &bc, "-o", &ptx])?;
+    sign_ptx(&ptx, &bin, &vec![], EVMVersion::Latest).map_err(|e| e.to_string())?;
+    Ok(Some(ptx))
+}
+
+/// Whether one of the arguments sets the flag
+pub(crate) fn has_flag(args: &[String], long: &str, short: Option<&str>) -> bool {
+    args.iter().any(|arg| {
+        arg == long || arg.starts_with(&format!("{}=", long)) || short.map_or(false, |short| arg == short)
+    })
+}
+
+/// Prepare the targets of the source and return the arguments of the campaign (without the
+/// program name), `extra` being the arguments given after the source
+pub fn quick_args(source: &str, extra: &[String]) -> Result<Vec<String>, String> {
+    let name = Path::new(source)
+        .file_stem()
+        .ok_or(format!("invalid source file {}", source))?
+        .to_string_lossy()
+        .to_string();
+    let out_dir = format!("quick-{}", name);
+    fs::create_dir_all(&out_dir).map_err(|e| format!("failed to create {}: {}", out_dir, e))?;
+    compile(source, &out_dir)?;
+    info!("[quick] compiled {} to {}/", source, out_dir);
+
+    let mut args = vec![];
+    if !has_flag(extra, "--target", Some("-t")) {
+        args.extend(["-t".to_string(), format!("{}/*", out_dir)]);
+    }
+    if !has_flag(extra, "--ptx-path", Some("-p")) {
+        let kernel = match main_bin(&out_dir, &name) {
+            Some(bin) => build_kernel(&bin, &out_dir).unwrap_or_else(|e| {
+                warn!("[quick] ptxsema failed, fuzzing on the CPU only: {}", e);
+                None
+            }),
+            None => None,
+        };
+        match kernel {
+            Some(ptx) => {
+                info!("[quick] translated the kernel to {}", ptx);
+                args.extend(["--ptx-path".to_string(), ptx]);
+            }
+            None => warn!("[quick] ptxsema not available (set PTXSEMA and PTXSEMA_RT), fuzzing on the CPU only"),
+        }
+    }
+    if !has_flag(extra, "--sanitize", None) {
+        args.extend(["--sanitize".to_string(), QUICK_SANITIZERS.to_string()]);
+    }
+    if !has_flag(extra, "--time-budget", None) {
+        args.extend(["--time-budget".to_string(), QUICK_TIME_BUDGET.to_string()]);
+    }
+    args.extend(extra.iter().cloned());
+    Ok(args)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quickstart_args() {
+        assert_eq!(pragma_version("// SPDX\npragma solidity ^0.8.19;\n"), Some("0.8.19".to_string()));
+        assert_eq!(pragma_version("pragma solidity >=0.6.0 <0.9.0;"), Some("0.6.0".to_string()));
+        assert_eq!(pragma_version("pragma solidity 0.8;"), None);
+        assert_eq!(pragma_version("contract A {}"), None);
+
+        let extra = vec!["-t".to_string(), "x/*".to_string(), "--time-budget=60".to_string()];
+        assert!(has_flag(&extra, "--target", Some("-t")));
+        assert!(has_flag(&extra, "--time-budget", None));
+        assert!(!has_flag(&extra, "--sanitize", None));
+    }
+}
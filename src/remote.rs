@@ -0,0 +1,164 @@
+//! Streaming new corpus entries, solutions and stats to a central collector over gRPC
+//!
+//! Events are queued in a bounded channel and sent by a background thread, so the
+//! fuzzing loop never blocks on the network. When the queue is full (the collector
+//! is slow or unreachable), events are dropped and counted instead.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+
+pub mod proto {
+    tonic::include_proto!("collector");
+}
+
+use proto::collector_client::CollectorClient;
+use proto::fuzz_event::Event;
+use proto::{CorpusEntry, FuzzEvent, Solution, Stats};
+
+/// Maximum number of events waiting to be sent
+const QUEUE_SIZE: usize = 4096;
+
+/// Delay before reconnecting to the collector
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Send stats every this many executions
+pub const STATS_INTERVAL: usize = 10000;
+
+/// Sending end of the event queue, None if streaming is disabled
+pub static mut REMOTE: Option<RemoteStreamer> = None;
+
+/// Number of events dropped because the queue is full
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+pub struct RemoteStreamer {
+    fuzzer_id: String,
+    sender: Sender<FuzzEvent>,
+}
+
+impl RemoteStreamer {
+    /// Queue an event without blocking, the event is dropped if the queue is full
+    fn send(&self, event: Event) {
+        let event = FuzzEvent {
+            fuzzer_id: self.fuzzer_id.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            event: Some(event),
+        };
+        match self.sender.try_send(event) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Start streaming to the collector at `endpoint` (e.g., http://collector:50051)
+pub fn start_streaming(endpoint: String, fuzzer_id: String) {
+    let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build tokio runtime");
+        runtime.block_on(stream_events(endpoint, receiver));
+    });
+    info!("Streaming corpus and stats as {}", fuzzer_id);
+    unsafe {
+        REMOTE = Some(RemoteStreamer { fuzzer_id, sender });
+    }
+}
+
+/// Forward queued events to the collector, reconnecting when the connection is lost
+async fn stream_events(endpoint: String, mut receiver: Receiver<FuzzEvent>) {
+    loop {
+        let mut client = match CollectorClient::connect(endpoint.clone()).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Failed to connect to collector {}: {}", endpoint, e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+
+        let (conn_sender, conn_receiver) = mpsc::channel(QUEUE_SIZE);
+        let call = tokio::spawn(async move {
+            client.stream(ReceiverStream::new(conn_receiver)).await
+        });
+
+        let mut closed = true;
+        while let Some(event) = receiver.recv().await {
+            if conn_sender.send(event).await.is_err() {
+                // the connection is broken, the event is lost
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+                closed = false;
+                break;
+            }
+        }
+        drop(conn_sender);
+        if let Ok(Err(status)) = call.await {
+            warn!("Collector stream closed: {}", status);
+        }
+        if closed {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+fn corpus_entry(input: Vec<u8>, txn: String, scenario: String) -> CorpusEntry {
+    CorpusEntry {
+        input,
+        txn,
+        scenario,
+    }
+}
+
+/// Stream an input added to the corpus
+pub fn stream_corpus(input: Vec<u8>, txn: String, scenario: String) {
+    if let Some(remote) = unsafe { REMOTE.as_ref() } {
+        remote.send(Event::Corpus(corpus_entry(input, txn, scenario)));
+    }
+}
+
+/// Stream a solution found by the oracles
+pub fn stream_solution(input: Vec<u8>, txn: String, scenario: String, oracle: String, trace: String) {
+    if let Some(remote) = unsafe { REMOTE.as_ref() } {
+        remote.send(Event::Solution(Solution {
+            input: Some(corpus_entry(input, txn, scenario)),
+            oracle,
+            trace,
+        }));
+    }
+}
+
+/// Stream the fuzzing stats
+pub fn stream_stats(executions: usize, corpus_size: usize, solutions: usize) {
+    if let Some(remote) = unsafe { REMOTE.as_ref() } {
+        remote.send(Event::Stats(Stats {
+            executions: executions as u64,
+            corpus_size: corpus_size as u64,
+            solutions: solutions as u64,
+            dropped: DROPPED.load(Ordering::Relaxed),
+        }));
+    }
+}
+
+/// Give the background thread some time to send the queued events before the process exits
+pub fn flush(timeout: Duration) {
+    if let Some(remote) = unsafe { REMOTE.as_ref() } {
+        let start = SystemTime::now();
+        while remote.sender.capacity() < QUEUE_SIZE
+            && start.elapsed().unwrap_or_default() < timeout
+        {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! GPU-resident corpus sampling (`--gpu-resident 64`)
+//!
+//! In the seed mode, every batch is a round trip: the host mutates a seed, uploads it with its
+//! storage and env, launches the kernel and reads every thread back. For a small contract the
+//! kernel runs in microseconds and the launch overhead dominates. With a resident pool, the
+//! top-K seeds of the corpus ([`select_resident`]: the seeds on the least fuzzed paths, the
+//! scheduled one first) are uploaded once into device memory, and the kernel runs
+//! [`RESIDENT_LAUNCHES`] launches in a row (`cuEvalResident`), each thread sampling a resident
+//! seed and havocking its arguments between launches. The kernel reports the coverage after each
+//! launch and keeps the interesting / buggy / leaking mutants in a device buffer the host reads
+//! once the launches are done.
+//!
+//! The host only syncs the pool (re-selecting and re-uploading the seeds, then running a regular
+//! seed batch so that the state and env mutations of the CPU still happen) every
+//! [`RESIDENT_SYNC_RUNS`] stage runs, or earlier once a run of launches adds no coverage or the
+//! storage snapshot of a resident seed is freed with its pruned VMState.
+//! Stepped inputs (resuming a control leak) and seeds the kernel cannot execute are never
+//! resident. Each resident seed is pinned with its own storage snapshot and env; the candidates
+//! are tracked as the corpus grows rather than rescanned at each sync.
+use std::collections::HashSet;
+use tracing::info;
+
+/// Number of seeds resident in device memory, 0 to disable (`--gpu-resident`)
+pub static mut GPU_RESIDENT_SEEDS: usize = 0;
+
+/// Launches run by the kernel between two reads of the host (`--resident-launches`)
+pub static mut RESIDENT_LAUNCHES: u32 = 8;
+
+/// Stage runs between two syncs of the pool
+pub const RESIDENT_SYNC_RUNS: usize = 16;
+
+/// Maximum number of havoc rounds applied by a thread to its seed
+pub const RESIDENT_HAVOC: u32 = 4;
+
+/// Print the stats of the pool every this many syncs
+const RESIDENT_STATS_INTERVAL: usize = 10;
+
+#[derive(Clone, Debug, Default)]
+pub struct ResidentStats {
+    pub syncs: usize,
+    /// Stage runs since the last sync
+    pub runs: usize,
+    pub launches: u64,
+    pub executions: u64,
+    pub findings: u64,
+    /// Coverage reported after each launch of the last run
+    pub coverage: Vec<u64>,
+}
+
+static mut RESIDENT_STATS: Option<ResidentStats> = None;
+
+pub fn resident_stats() -> &'static mut ResidentStats {
+    unsafe { RESIDENT_STATS.get_or_insert_with(Default::default) }
+}
+
+pub fn resident_enabled() -> bool {
+    unsafe { GPU_RESIDENT_SEEDS > 0 }
+}
+
+/// Whether the pool has to be re-uploaded before the next launches
+pub fn needs_sync(pool_len: usize) -> bool {
+    let stats = resident_stats();
+    pool_len == 0 || stats.runs >= RESIDENT_SYNC_RUNS || plateau(&stats.coverage)
+}
+
+/// Upload the pool again before the next launches, its storage snapshots were freed
+pub fn invalidate_resident() {
+    resident_stats().runs = RESIDENT_SYNC_RUNS;
+}
+
+/// Whether a run of launches added no coverage
+pub fn plateau(coverage: &Vec<u64>) -> bool {
+    match (coverage.first(), coverage.last()) {
+        (Some(first), Some(last)) => coverage.len() > 1 && first == last,
+        _ => false,
+    }
+}
+
+/// Corpus indices of the seeds to make resident among the executable candidates (index, rounds
+/// spent on its path): the scheduled seed, then the ones on the least fuzzed paths, the newest
+/// first on a tie
+pub fn select_resident(scheduled: usize, candidates: &Vec<(usize, usize)>, k: usize) -> Vec<usize> {
+    let mut ranked = candidates.clone();
+    ranked.sort_by(|(idx1, rounds1), (idx2, rounds2)| rounds1.cmp(rounds2).then(idx2.cmp(idx1)));
+    let mut selected = vec![];
+    let mut seen = HashSet::new();
+    let scheduled = candidates.iter().find(|(idx, _)| *idx == scheduled).map(|(idx, _)| *idx);
+    for idx in scheduled.into_iter().chain(ranked.into_iter().map(|(idx, _)| idx)) {
+        if selected.len() == k {
+            break;
+        }
+        if seen.insert(idx) {
+            selected.push(idx);
+        }
+    }
+    selected
+}
+
+pub fn record_sync(resident: usize) {
+    let stats = resident_stats();
+    stats.syncs += 1;
+    stats.runs = 0;
+    stats.coverage.clear();
+    if stats.syncs % RESIDENT_STATS_INTERVAL == 1 {
+        info!("[gpu-resident] {} seeds resident, {}", resident, describe_resident());
+    }
+}
+
+/// Record a run of launches: the coverage after each launch and the mutants read back
+pub fn record_launches(coverage: Vec<u64>, threads: u32, findings: usize) {
+    let stats = resident_stats();
+    stats.runs += 1;
+    stats.launches += coverage.len() as u64;
+    stats.executions += coverage.len() as u64 * threads as u64;
+    stats.findings += findings as u64;
+    stats.coverage = coverage;
+}
+
+pub fn describe_resident() -> String {
+    let stats = resident_stats();
+    format!(
+        "{} syncs, {} launches ({:.1} per sync), {} executions, {} findings",
+        stats.syncs,
+        stats.launches,
+        stats.launches as f64 / stats.syncs.max(1) as f64,
+        stats.executions,
+        stats.findings
+    )
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_resident() {
+        let candidates = vec![(0, 5), (1, 1), (2, 0), (3, 1), (4, 9)];
+        assert_eq!(select_resident(4, &candidates, 3), vec![4, 2, 3]);
+        // the scheduled seed is not executable
+        assert_eq!(select_resident(7, &candidates, 2), vec![2, 3]);
+        assert_eq!(select_resident(0, &candidates, 10).len(), 5);
+
+        assert!(!plateau(&vec![]));
+        assert!(!plateau(&vec![10]));
+        assert!(plateau(&vec![10, 10, 10]));
+        assert!(!plateau(&vec![10, 12, 12]));
+
+        assert!(needs_sync(0));
+        record_sync(3);
+        record_launches(vec![5, 8], 256, 1);
+        assert!(!needs_sync(3));
+        assert_eq!(resident_stats().executions, 512);
+        record_launches(vec![8, 8], 256, 0);
+        assert!(needs_sync(3));
+    }
+}
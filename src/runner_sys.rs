@@ -0,0 +1,363 @@
+//! Bindings of the GPU runner (`librunner.so`), resolved at runtime
+//!
+//! The runner used to be linked at build time, through `extern "C"` blocks scattered in the
+//! methods calling it: a runner missing a symbol failed the link of mau, and trying another
+//! runner build meant rebuilding mau. The library is now opened with `libloading` when the GPU
+//! is enabled ([`load_runner`], `--runner-lib`), and all the entry points listed here are
+//! resolved at once, so that a runner missing some of them is reported with their names as a
+//! [`RunnerError`] before the campaign starts, instead of a link error or a crash in the middle
+//! of it. The runner must also export `cuRunnerAbiVersion`, checked against
+//! [`RUNNER_ABI_VERSION`], the version of the signatures below: a runner built before the
+//! version was exported implements older signatures and is rejected.
+//!
+//! The entry points of optional features (PC traces, memory sanitizer reports, immutable
+//! patching, resident seeds) may be missing from older runner builds: they are resolved as
+//! `Option`s, and the campaign turns the matching feature off when [`runner_exports`] says that
+//! the runner lacks them.
+//!
+//! The wrappers keep the names and signatures of the runner. They are only reached once the
+//! GPU is enabled, that is once the runner is loaded; a call before that does nothing and
+//! returns the default value rather than jumping to a null pointer.
+use libloading::Library;
+use once_cell::sync::Lazy;
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_char;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
+
+/// Version of the signatures of the entry points below
+pub const RUNNER_ABI_VERSION: u32 = 5;
+
+/// Library opened by default, searched in the library path (`LD_LIBRARY_PATH`)
+pub const DEFAULT_RUNNER_LIB: &str = "librunner.so";
+
+/// `--runner-lib` of the mock runner, with the `mock_runner` feature
+#[cfg(feature = "mock_runner")]
+pub const MOCK_RUNNER_LIB: &str = "mock";
+
+#[derive(Debug)]
+pub enum RunnerError {
+    /// The library could not be opened
+    Load { path: String, msg: String },
+    /// Entry points the library does not export
+    MissingSymbols(Vec<&'static str>),
+    /// The library exports the entry points of another version
+    Version { expected: u32, found: u32 },
+}
+
+impl Display for RunnerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunnerError::Load { path, msg } => write!(f, "failed to load the runner {}: {}", path, msg),
+            RunnerError::MissingSymbols(symbols) => {
+                write!(f, "the runner does not export {}", symbols.join(", "))
+            }
+            RunnerError::Version { expected, found } => write!(
+                f,
+                "the runner implements version {} of the entry points, mau expects version {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RunnerError {}
+
+static mut RUNNER: Option<Runner> = None;
+
+/// Serialises the tests using the global runner, some of them install the mock
+static RUNNER_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Index of the VMState each storage snapshot on the device was taken from, by the id it is
+/// loaded at (a thread or a resident slot)
+static DEVICE_STORAGES: Lazy<Mutex<HashMap<u32, usize>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+macro_rules! runner_symbols {
+    (
+        $($name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*
+        optional {
+            $($opt_name:ident($($opt_arg:ident: $opt_ty:ty),*) $(-> $opt_ret:ty)?;)*
+        }
+    ) => {
+        /// Entry points resolved from the library, which stays open along with them
+        #[allow(non_snake_case)]
+        struct Runner {
+            _library: Option<Library>,
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+            $($opt_name: Option<unsafe extern "C" fn($($opt_ty),*) $(-> $opt_ret)?>,)*
+        }
+
+        impl Runner {
+            fn resolve(library: Library) -> Result<Self, RunnerError> {
+                let mut missing = vec![];
+                $(
+                    #[allow(non_snake_case)]
+                    let $name = match unsafe {
+                        library.get::<unsafe extern "C" fn($($ty),*) $(-> $ret)?>(
+                            concat!(stringify!($name), "\0").as_bytes(),
+                        )
+                    } {
+                        Ok(symbol) => Some(*symbol),
+                        Err(_) => {
+                            missing.push(stringify!($name));
+                            None
+                        }
+                    };
+                )*
+                let mut missing_optional: Vec<&'static str> = vec![];
+                $(
+                    #[allow(non_snake_case)]
+                    let $opt_name = match unsafe {
+                        library.get::<unsafe extern "C" fn($($opt_ty),*) $(-> $opt_ret)?>(
+                            concat!(stringify!($opt_name), "\0").as_bytes(),
+                        )
+                    } {
+                        Ok(symbol) => Some(*symbol),
+                        Err(_) => {
+                            missing_optional.push(stringify!($opt_name));
+                            None
+                        }
+                    };
+                )*
+                if !missing_optional.is_empty() {
+                    warn!(
+                        "[runner] the runner does not export {}, the matching features are disabled",
+                        missing_optional.join(", ")
+                    );
+                }
+                match ($($name,)*) {
+                    ($(Some($name),)*) => Ok(Self {
+                        _library: Some(library),
+                        $($name,)*
+                        $($opt_name,)*
+                    }),
+                    _ => Err(RunnerError::MissingSymbols(missing)),
+                }
+            }
+
+            /// Whether an entry point is resolved, the mandatory ones always are
+            fn exports(&self, symbol: &str) -> bool {
+                $(
+                    if symbol == stringify!($opt_name) {
+                        return self.$opt_name.is_some();
+                    }
+                )*
+                true
+            }
+
+            /// The mock in place of the library, implementing the same entry points
+            #[cfg(feature = "mock_runner")]
+            fn mock() -> Self {
+                Self {
+                    _library: None,
+                    $($name: crate::mock_runner::$name,)*
+                    $($opt_name: Some(crate::mock_runner::$opt_name),)*
+                }
+            }
+        }
+
+        $(
+            #[allow(non_snake_case)]
+            pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                match RUNNER.as_ref() {
+                    Some(runner) => (runner.$name)($($arg),*),
+                    None => Default::default(),
+                }
+            }
+        )*
+
+        $(
+            /// Optional entry point, does nothing when the runner does not export it
+            #[allow(non_snake_case)]
+            pub unsafe fn $opt_name($($opt_arg: $opt_ty),*) $(-> $opt_ret)? {
+                match RUNNER.as_ref().and_then(|runner| runner.$opt_name) {
+                    Some(symbol) => symbol($($opt_arg),*),
+                    None => Default::default(),
+                }
+            }
+        )*
+    };
+}
+
+runner_symbols! {
+    // context and device buffers
+    InitCudaCtx(dev: i32, path_to_kernel: *const c_char);
+    cuMallocAll();
+    cuSetLoopBound(bound: u8);
+    cuEnableOpcodeStats(enable: bool);
+    cuSetEVMVersion(version: u8);
+    cuMaxActiveThreads() -> u32;
+    cuSetBatchSize(threads: u32);
+    cuAddCallerPool(added_caller: *const u8, pool_len: u32);
+    cuAddAddressPool(added_addr: *const u8, pool_len: u32);
+
+    // seeds, storage and env of the threads
+    setEVMEnv(to: *const u8, timestamp: *const u8, blocknum: *const u8) -> bool;
+    cuLoadSeed(caller_ptr: *const u8, value_ptr: *const u8, data_ptr: *const u8, data_size: u32, state_idx: u32, thread: u32);
+    cuGetStoragePos(s_idx: u32) -> u32;
+    cuLoadStorage(src: *const u8, slot_cnt: u32, state_id: u32);
+    cuFreeStorage(state_id: u32);
+    cuLoadContinuation(
+        pc: u32,
+        stack: *const u8,
+        stack_len: u32,
+        memory: *const u8,
+        memory_len: u32,
+        call_data: *const u8,
+        call_data_len: u32,
+        output_offset: u32,
+        output_len: u32,
+        caller: *const u8,
+        value: *const u8
+    ) -> bool;
+    cuClearContinuation();
+
+    // execution and mutation
+    cuEvalTxn(nwrap: u32);
+    cuPreMutate(arg_types: *const u8, arg_types_len: u32);
+    cuMutate(calldatasize: u32);
+
+    // results of the threads
+    getCudaExecRes(pcov: *const u64, pbug: *const u64) -> bool;
+    isCudaInteresting(tid: u32) -> u8;
+    gainCov(tid: u32, raw_seed: *mut u8) -> u8;
+    cuDumpStorage(thread_id: u32);
+    cuReadEnvAccess(tid: u32) -> u32;
+    cuReadBranchCoverage(bitmap: *mut u8, len: u32) -> u32;
+    cuReadReturnData(tid: u32, output: *mut u8, cap: u32, reverted: *mut bool) -> u32;
+    cuReadOpcodeStats(ops: *mut u64, pcs: *mut u64, pcs_len: u32) -> u32;
+    cuReadControlLeak(tid: u32, pc: *mut u32, target_offset: *mut u32, args: *mut u8, args_cap: u32, args_len: *mut u32) -> bool;
+
+    optional {
+        // PC traces of the buggy threads
+        cuSetPcTraceSize(size: u32);
+        cuReadPcTrace(tid: u32, pcs: *mut u32, ops: *mut u8, len: u32) -> u32;
+
+        // memory sanitizer
+        cuEnableMemSanitizer(enable: bool);
+        cuReadSanitizerReport(tid: u32, kind: *mut u8, pc: *mut u32, operands: *mut u8) -> bool;
+
+        // kernel reuse across immutables, see crate::evm::immutables
+        cuPatchImmutable(offset: u32, value: *const u8);
+
+        // seeds resident in device memory, see crate::resident_pool
+        cuPinResidentSeed(
+            slot: u32,
+            to: *const u8,
+            timestamp: *const u8,
+            blocknum: *const u8,
+            arg_types: *const u8,
+            arg_types_len: u32
+        );
+        cuSetResidentSeeds(count: u32);
+        cuEvalResident(launches: u32, havoc: u32, coverage: *mut u64) -> u32;
+        cuReadResidentFinding(idx: u32, raw_seed: *mut u8, slot: *mut u32) -> u8;
+    }
+}
+
+/// Open the runner and resolve its entry points, the GPU cannot be enabled otherwise
+pub fn load_runner(path: &str) -> Result<(), RunnerError> {
+    #[cfg(feature = "mock_runner")]
+    if path == MOCK_RUNNER_LIB {
+        crate::mock_runner::load_mock_runner();
+        return Ok(());
+    }
+    let library = unsafe { Library::new(path) }.map_err(|e| RunnerError::Load {
+        path: path.to_string(),
+        msg: e.to_string(),
+    })?;
+    let version = unsafe { library.get::<unsafe extern "C" fn() -> u32>(b"cuRunnerAbiVersion\0") }
+        .ok()
+        .map(|version| unsafe { version() });
+    match version {
+        Some(found) if found != RUNNER_ABI_VERSION => {
+            return Err(RunnerError::Version {
+                expected: RUNNER_ABI_VERSION,
+                found,
+            })
+        }
+        Some(_) => {}
+        // built before the version was exported, with the signatures of an older version
+        None => return Err(RunnerError::MissingSymbols(vec!["cuRunnerAbiVersion"])),
+    }
+    let runner = Runner::resolve(library)?;
+    unsafe {
+        RUNNER = Some(runner);
+    }
+    info!("[runner] loaded {}", path);
+    Ok(())
+}
+
+/// Route the entry points to [`crate::mock_runner`]
+#[cfg(feature = "mock_runner")]
+pub fn install_mock_runner() {
+    unsafe {
+        RUNNER = Some(Runner::mock());
+    }
+    info!("[runner] using the mock runner");
+}
+
+/// Unload the runner, the entry points are inert again
+#[cfg(feature = "mock_runner")]
+pub fn unload_runner() {
+    unsafe {
+        RUNNER = None;
+    }
+    DEVICE_STORAGES.lock().unwrap().clear();
+}
+
+/// Exclusive use of the global runner, for the tests (see [`crate::mock_runner::mock_runner`])
+pub fn lock_runner() -> MutexGuard<'static, ()> {
+    // the guard of a failed test unloaded the mock all the same
+    RUNNER_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn runner_loaded() -> bool {
+    unsafe { RUNNER.is_some() }
+}
+
+/// Whether the loaded runner exports an entry point
+pub fn runner_exports(symbol: &str) -> bool {
+    unsafe { RUNNER.as_ref() }.map_or(false, |runner| runner.exports(symbol))
+}
+
+/// Record the VMState the storage loaded at `storage_id` was taken from
+pub fn record_device_storage(storage_id: u32, state_idx: usize) {
+    DEVICE_STORAGES.lock().unwrap().insert(storage_id, state_idx);
+}
+
+/// Free the storage snapshots on the device taken from a pruned VMState, returns their ids
+pub fn free_device_storages(state_idx: usize) -> Vec<u32> {
+    let mut storages = DEVICE_STORAGES.lock().unwrap();
+    let mut freed = storages
+        .iter()
+        .filter(|(_, state)| **state == state_idx)
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    freed.sort();
+    for id in &freed {
+        storages.remove(id);
+        unsafe { cuFreeStorage(*id) };
+    }
+    freed
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runner_not_loaded() {
+        let _runner = lock_runner();
+        assert!(matches!(
+            load_runner("/nonexistent/librunner.so"),
+            Err(RunnerError::Load { .. })
+        ));
+        // the entry points are inert until the runner is loaded
+        assert!(!runner_loaded());
+        assert_eq!(unsafe { cuMaxActiveThreads() }, 0);
+
+        let e = RunnerError::MissingSymbols(vec!["cuEvalTxn", "gainCov"]);
+        assert_eq!(e.to_string(), "the runner does not export cuEvalTxn, gainCov");
+    }
+}
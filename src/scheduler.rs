@@ -4,6 +4,7 @@
 use libafl::corpus::Corpus;
 use libafl::corpus::Testcase;
 use libafl::prelude::{HasMetadata, HasRand, Input, Rand};
+use tracing::{debug, warn};
 use libafl::schedulers::Scheduler;
 use libafl::state::HasCorpus;
 use libafl::{impl_serdeany, Error};
@@ -11,8 +12,23 @@ use libafl::{impl_serdeany, Error};
 use serde::{Deserialize, Serialize};
 
 use rand::random;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::evm::host::JMP_MAP;
+use crate::evm::scenario::schedule_scenario;
+use crate::host_mem::{account, should_shrink, MIN_STATE_CORPUS};
+
+#[cfg(feature = "cuda")]
+use crate::evm::config::GPU_ENABLE;
+
+#[cfg(feature = "cuda")]
+use crate::resident_pool::invalidate_resident;
+#[cfg(feature = "cuda")]
+use crate::runner_sys::free_device_storages;
 
 /// A trait providing functions necessary for voting mechanisms
 pub trait HasVote<I, S>
@@ -31,6 +47,93 @@ pub const PRUNE_AMT: usize = 250;
 /// If inputs (or VMState) has not been visited this many times, it will be ignored during pruning
 pub const VISIT_IGNORE_THRESHOLD: usize = 2;
 
+/// Which VMStates are evicted when the corpus is pruned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PruningPolicy {
+    /// Lowest votes / visits ratio (default)
+    Votes,
+    /// Least recently productive, i.e., voted for or led to an input corpus entry
+    Lru,
+    /// Dominated in (coverage contribution, last productive use) by another VMState first,
+    /// lowest coverage contribution first
+    Dominance,
+}
+
+impl FromStr for PruningPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "votes" => Ok(PruningPolicy::Votes),
+            "lru" => Ok(PruningPolicy::Lru),
+            "dominance" => Ok(PruningPolicy::Dominance),
+            _ => Err(format!("Unknown pruning policy: {}", s)),
+        }
+    }
+}
+
+pub static mut PRUNING_POLICY: PruningPolicy = PruningPolicy::Votes;
+/// The maximum number of VMStates to keep in the corpus before pruning
+pub static mut STATE_CORPUS_LIMIT: usize = DROP_THRESHOLD;
+
+/// [`STATE_CORPUS_LIMIT`], lowered to three quarters of the corpus size when the host memory
+/// runs short (see [`crate::host_mem`])
+pub fn state_corpus_limit(corpus_size: usize) -> usize {
+    unsafe {
+        if corpus_size > MIN_STATE_CORPUS && should_shrink("state corpus") {
+            let lowered = (corpus_size * 3 / 4).max(MIN_STATE_CORPUS).min(STATE_CORPUS_LIMIT);
+            warn!("[host-mem] memory running short, state corpus limited to {} (was {})", lowered, STATE_CORPUS_LIMIT);
+            STATE_CORPUS_LIMIT = lowered;
+        }
+        STATE_CORPUS_LIMIT
+    }
+}
+
+/// Usage of a VMState considered when pruning
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateUsage {
+    pub idx: usize,
+    pub votes: usize,
+    pub visits: usize,
+    /// Clock of the last vote, or of its addition
+    pub last_productive: usize,
+    /// Number of input corpus entries found on the VMState
+    pub contribution: usize,
+}
+
+impl StateUsage {
+    fn dominated_by(&self, other: &StateUsage) -> bool {
+        other.contribution >= self.contribution
+            && other.last_productive >= self.last_productive
+            && (other.contribution > self.contribution || other.last_productive > self.last_productive)
+    }
+}
+
+/// Select `amount` VMStates to evict according to the policy
+pub fn select_evictions(policy: PruningPolicy, usages: &Vec<StateUsage>, amount: usize) -> Vec<usize> {
+    let mut sorted = usages.clone();
+    match policy {
+        PruningPolicy::Votes => {
+            sorted.sort_by(|u1, u2| {
+                let score_1 = (u1.votes as f64) / (u1.visits as f64);
+                let score_2 = (u2.votes as f64) / (u2.visits as f64);
+                score_1.partial_cmp(&score_2).unwrap()
+            });
+        }
+        PruningPolicy::Lru => {
+            sorted.sort_by_key(|u| u.last_productive);
+        }
+        PruningPolicy::Dominance => {
+            // dominated VMStates first, then by contribution and recency
+            sorted.sort_by_cached_key(|u| {
+                let dominated = usages.iter().any(|other| u.dominated_by(other));
+                (!dominated, u.contribution, u.last_productive)
+            });
+        }
+    }
+    sorted.iter().take(amount).map(|u| u.idx).collect()
+}
+
 /// A scheduler that drops inputs (or VMState) based on a voting mechanism
 #[derive(Debug, Clone)]
 pub struct SortedDroppingScheduler<I, S> {
@@ -62,6 +165,12 @@ struct VoteData {
     pub visits_total: usize,
     /// Total number of votes, cached for performance
     pub votes_total: usize,
+    /// Logical clock, incremented on each addition and vote
+    pub clock: usize,
+    /// Map of VMState index to the clock of its last productive use
+    pub last_productive: HashMap<usize, usize>,
+    /// Map of VMState index to the number of input corpus entries found on it
+    pub contributions: HashMap<usize, usize>,
 }
 
 impl_serdeany!(VoteData);
@@ -95,6 +204,9 @@ where
                 visits_total: 1,
                 votes_total: 1,
                 prev_sorted_votes: vec![],
+                clock: 0,
+                last_productive: HashMap::new(),
+                contributions: HashMap::new(),
             });
         }
 
@@ -105,6 +217,8 @@ where
             data.visits_total += 1;
             data.votes_total += 3;
             data.sorted_votes.push(idx);
+            data.clock += 1;
+            data.last_productive.insert(idx, data.clock);
         }
 
         // this is costly, but we have to do it to keep the corpus not increasing indefinitely
@@ -119,19 +233,23 @@ where
             }
 
             // println!("SCHEDULER => to removed(); Size = {:?}", corpus_size);
-            // If the corpus is too large (> [`DROP_THRESHOLD`]), prune it
-            if corpus_size > DROP_THRESHOLD {
-                // get top 100 entries sorted by votes (descending)
-                let mut sorted: Vec<_> = data.votes_and_visits.iter().collect();
-                sorted.sort_by(|(_idx_1, (votes1, visits1)), (_idx_2, (votes2, visits2))| {
-                    let score_1 = (*votes1 as f64) / (*visits1 as f64);
-                    let score_2 = (*votes2 as f64) / (*visits2 as f64);
-                    score_1.partial_cmp(&score_2).unwrap()
-                });
-
-                for i in sorted.iter().take(PRUNE_AMT) {
-                    to_remove.push(*i.0);
-                }
+            // If the corpus is too large (> [`STATE_CORPUS_LIMIT`]), prune it
+            account("state corpus", corpus_size);
+            let limit = state_corpus_limit(corpus_size);
+            if corpus_size > limit {
+                let usages = data
+                    .votes_and_visits
+                    .iter()
+                    .map(|(idx, (votes, visits))| StateUsage {
+                        idx: *idx,
+                        votes: *votes,
+                        visits: *visits,
+                        last_productive: *data.last_productive.get(idx).unwrap_or(&0),
+                        contribution: *data.contributions.get(idx).unwrap_or(&0),
+                    })
+                    .collect::<Vec<_>>();
+                let amount = (limit * PRUNE_AMT / DROP_THRESHOLD).max(1);
+                to_remove = select_evictions(unsafe { PRUNING_POLICY }, &usages, amount);
 
                 // Remove inputs (or VMState) from metadata and corpus
                 to_remove.iter().for_each(|x| {
@@ -146,6 +264,12 @@ where
                     {
                         state.corpus_mut().remove(*x).expect("failed to remove");
                     }
+                    // release the storage snapshots taken from the VMState on the device, the
+                    // resident seeds pinned on them are uploaded again
+                    #[cfg(feature = "cuda")]
+                    if unsafe { GPU_ENABLE } && !free_device_storages(*x).is_empty() {
+                        invalidate_resident();
+                    }
                 });
             }
         }
@@ -165,6 +289,8 @@ where
         data.visits_total -= data.votes_and_visits.get(&idx).unwrap().1;
         data.votes_and_visits.remove(&idx);
         data.sorted_votes.retain(|x| *x != idx);
+        data.last_productive.remove(&idx);
+        data.contributions.remove(&idx);
         Ok(())
     }
 
@@ -177,7 +303,7 @@ where
             let data = state.metadata().get::<VoteData>().unwrap();
             use crate::r#const::DEBUG_PRINT_PERCENT;
             if random::<usize>() % DEBUG_PRINT_PERCENT == 0 {
-                println!(
+                debug!(
                     "======================= corpus size: {} =======================",
                     corpus_size
                 );
@@ -186,7 +312,7 @@ where
                     let inp = state.corpus().get(*idx).unwrap().clone();
                     match inp.into_inner().input() {
                         Some(x) => {
-                            println!(
+                            debug!(
                                 "idx: {}, votes: {}, visits: {}: {:?}",
                                 idx, votes, visits, x
                             );
@@ -194,7 +320,7 @@ where
                         _ => {}
                     }
                 }
-                println!("======================= corpus  =======================");
+                debug!("======================= corpus  =======================");
             }
         }
 
@@ -243,6 +369,10 @@ where
             increment = 1;
         }
         data.votes_total += increment;
+        data.clock += 1;
+        if data.votes_and_visits.contains_key(&idx) {
+            data.last_productive.insert(idx, data.clock);
+        }
         {
             let v = data.votes_and_visits.get_mut(&idx);
             if v.is_some() {
@@ -301,3 +431,285 @@ where
     //     self.last_idx
     // }
 }
+
+/// How much energy (consecutive rounds) each input of the input corpus gets, see AFLFast
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerSchedule {
+    /// Cycle through the inputs, one round each (default)
+    Queue,
+    /// One round per coverage path: inputs with the same coverage share their round
+    Explore,
+    /// `2^s(i) / f(i)` rounds, where `s(i)` is the number of times the input was picked and
+    /// `f(i)` the number of rounds spent on its coverage path
+    Fast,
+    /// As fast, but inputs on paths fuzzed more than the average are skipped
+    Coe,
+    /// `s(i)^2 / f(i)` rounds
+    Quad,
+}
+
+impl FromStr for PowerSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queue" => Ok(PowerSchedule::Queue),
+            "explore" => Ok(PowerSchedule::Explore),
+            "fast" => Ok(PowerSchedule::Fast),
+            "coe" => Ok(PowerSchedule::Coe),
+            "quad" => Ok(PowerSchedule::Quad),
+            _ => Err(format!("Unknown power schedule: {}", s)),
+        }
+    }
+}
+
+impl Display for PowerSchedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PowerSchedule::Queue => "queue",
+            PowerSchedule::Explore => "explore",
+            PowerSchedule::Fast => "fast",
+            PowerSchedule::Coe => "coe",
+            PowerSchedule::Quad => "quad",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub static mut POWER_SCHEDULE: PowerSchedule = PowerSchedule::Queue;
+/// Maximum number of consecutive rounds given to an input
+pub const MAX_ENERGY: usize = 16;
+
+/// Number of rounds given to an input picked `picks` times whose path got `frequency` rounds.
+/// `mean_frequency` is the average number of rounds of the paths, `same_path` the number of
+/// inputs sharing the path and `roll` a random number.
+pub fn energy(
+    schedule: PowerSchedule,
+    picks: usize,
+    frequency: usize,
+    mean_frequency: f64,
+    same_path: usize,
+    roll: u64,
+) -> usize {
+    let frequency = frequency.max(1) as f64;
+    let fast = ((1u64 << picks.min(MAX_ENERGY)) as f64 / frequency).floor() as usize;
+    match schedule {
+        PowerSchedule::Queue => 1,
+        PowerSchedule::Explore => (roll % same_path.max(1) as u64 == 0) as usize,
+        PowerSchedule::Fast => fast.min(MAX_ENERGY),
+        PowerSchedule::Coe if frequency > mean_frequency => 0,
+        PowerSchedule::Coe => fast.min(MAX_ENERGY),
+        PowerSchedule::Quad => (((picks * picks) as f64 / frequency).floor() as usize).min(MAX_ENERGY),
+    }
+}
+
+/// Metadata for [`PowerScheduler`] that is stored in the state
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct PowerData {
+    /// Map of input index to (hash of its coverage path, number of times it was picked)
+    seeds: HashMap<usize, (u64, usize)>,
+    /// Number of inputs on each path
+    path_seeds: HashMap<u64, usize>,
+    /// Number of rounds spent on each path, shared by the inputs with the same coverage
+    path_frequency: HashMap<u64, usize>,
+    /// Rounds left for the current input
+    energy_left: usize,
+    /// Extra rounds of each input before it is first picked, see [`set_prior`]
+    priors: HashMap<usize, usize>,
+}
+
+impl_serdeany!(PowerData);
+
+/// Path of an input alone on it: without coverage, or never seen by [`Scheduler::on_add`]
+fn own_path(idx: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (usize::MAX, idx).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the coverage of the execution that just ran (the input being added), unique for
+/// inputs without coverage
+fn path_hash(idx: usize) -> u64 {
+    let coverage = unsafe { &JMP_MAP };
+    if coverage.iter().all(|x| *x == 0) {
+        return own_path(idx);
+    }
+    let mut hasher = DefaultHasher::new();
+    coverage.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A scheduler for the input corpus assigning energy to the inputs according to [`POWER_SCHEDULE`],
+/// so that GPU batches are not wasted on heavily fuzzed inputs
+#[derive(Debug, Clone)]
+pub struct PowerScheduler<I, S> {
+    phantom: std::marker::PhantomData<(I, S)>,
+}
+
+impl<I, S> PowerScheduler<I, S> {
+    pub fn new() -> Self {
+        Self {
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I, S> Scheduler<I, S> for PowerScheduler<I, S>
+where
+    S: HasCorpus<I> + HasRand + HasMetadata,
+    I: Input + Debug,
+{
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        if !state.has_metadata::<PowerData>() {
+            state.metadata_mut().insert(PowerData::default());
+        }
+        let hash = path_hash(idx);
+        let data = state.metadata_mut().get_mut::<PowerData>().unwrap();
+        // the index of a replaced input is reused
+        if let Some((old_hash, _)) = data.seeds.insert(idx, (hash, 0)) {
+            data.path_seeds.entry(old_hash).and_modify(|n| *n = n.saturating_sub(1));
+            data.priors.remove(&idx);
+        }
+        *data.path_seeds.entry(hash).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        let count = state.corpus().count();
+        if count == 0 {
+            return Err(Error::empty("No entries in corpus".to_string()));
+        }
+        let schedule = unsafe { POWER_SCHEDULE };
+        let current = *state.corpus().current();
+        if !state.has_metadata::<PowerData>() {
+            state.metadata_mut().insert(PowerData::default());
+        }
+
+        // keep fuzzing the current input while it has energy left
+        if let Some(cur) = current.filter(|cur| *cur < count) {
+            let data = state.metadata_mut().get_mut::<PowerData>().unwrap();
+            if data.energy_left > 0 {
+                data.energy_left -= 1;
+                if let Some((hash, _)) = data.seeds.get(&cur) {
+                    *data.path_frequency.entry(*hash).or_insert(0) += 1;
+                }
+                return Ok(cur);
+            }
+        }
+
+        let mut idx = current.map_or(0, |cur| (cur + 1) % count);
+        let mut rounds = 0;
+        for _ in 0..count {
+            let roll = state.rand_mut().next();
+            let data = state.metadata_mut().get_mut::<PowerData>().unwrap();
+            // the coverage map holds the last execution, not the one of an input added elsewhere
+            if !data.seeds.contains_key(&idx) {
+                data.seeds.insert(idx, (own_path(idx), 0));
+                *data.path_seeds.entry(own_path(idx)).or_insert(0) += 1;
+            }
+            let (hash, picks) = data.seeds.get_mut(&idx).unwrap();
+            *picks += 1;
+            let mean_frequency = data.path_frequency.values().sum::<usize>() as f64
+                / data.path_frequency.len().max(1) as f64;
+            rounds = energy(
+                schedule,
+                *picks,
+                *data.path_frequency.get(hash).unwrap_or(&0),
+                mean_frequency,
+                *data.path_seeds.get(hash).unwrap_or(&1),
+                roll,
+            );
+            rounds = (rounds + prior_bonus(*data.priors.get(&idx).unwrap_or(&0), *picks)).min(MAX_ENERGY);
+            if rounds > 0 {
+                break;
+            }
+            idx = (idx + 1) % count;
+        }
+
+        // all the inputs were skipped during the pass
+        let rounds = rounds.max(1);
+        let data = state.metadata_mut().get_mut::<PowerData>().unwrap();
+        data.energy_left = rounds - 1;
+        if let Some((hash, _)) = data.seeds.get(&idx) {
+            *data.path_frequency.entry(*hash).or_insert(0) += 1;
+        }
+        *state.corpus_mut().current_mut() = Some(idx);
+        schedule_scenario(state);
+        Ok(idx)
+    }
+}
+
+/// Extra rounds of an input with the prior on its `picks`-th pick, halved at each pick
+pub fn prior_bonus(prior: usize, picks: usize) -> usize {
+    prior >> picks.saturating_sub(1).min(usize::BITS as usize - 1)
+}
+
+/// Give extra rounds to an input of the input corpus, e.g., from the static pass of
+/// [`crate::evm::bug_likelihood`]
+pub fn set_prior<S: HasMetadata>(state: &mut S, idx: usize, prior: usize) {
+    if !state.has_metadata::<PowerData>() {
+        state.metadata_mut().insert(PowerData::default());
+    }
+    state.metadata_mut().get_mut::<PowerData>().unwrap().priors.insert(idx, prior);
+}
+
+/// Rounds spent on the coverage path of an input of the input corpus, 0 if it was never picked
+pub fn path_rounds<S: HasMetadata>(state: &S, idx: usize) -> usize {
+    state
+        .metadata()
+        .get::<PowerData>()
+        .and_then(|data| data.seeds.get(&idx).and_then(|(hash, _)| data.path_frequency.get(hash)))
+        .cloned()
+        .unwrap_or(0)
+}
+
+/// Record that an input found on the VMState was added to the input corpus
+pub fn record_contribution<S: HasMetadata>(state: &mut S, idx: usize) {
+    if let Some(data) = state.metadata_mut().get_mut::<VoteData>() {
+        if data.votes_and_visits.contains_key(&idx) {
+            data.clock += 1;
+            data.last_productive.insert(idx, data.clock);
+            *data.contributions.entry(idx).or_insert(0) += 1;
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_evictions() {
+        let usage = |idx, votes, last_productive, contribution| StateUsage {
+            idx,
+            votes,
+            visits: 1,
+            last_productive,
+            contribution,
+        };
+        let usages = vec![usage(0, 3, 5, 2), usage(1, 9, 1, 0), usage(2, 6, 3, 1), usage(3, 1, 6, 0)];
+        assert_eq!(select_evictions(PruningPolicy::Votes, &usages, 2), vec![3, 0]);
+        assert_eq!(select_evictions(PruningPolicy::Lru, &usages, 2), vec![1, 2]);
+        // 1 and 2 are dominated by 0, 3 is the most recent
+        assert_eq!(select_evictions(PruningPolicy::Dominance, &usages, 3), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_energy() {
+        assert_eq!(energy(PowerSchedule::Queue, 10, 1000, 1.0, 1, 0), 1);
+        // rarely fuzzed paths get more rounds, capped
+        assert_eq!(energy(PowerSchedule::Fast, 3, 2, 10.0, 1, 0), 4);
+        assert_eq!(energy(PowerSchedule::Fast, 30, 1, 10.0, 1, 0), MAX_ENERGY);
+        assert_eq!(energy(PowerSchedule::Fast, 1, 100, 10.0, 1, 0), 0);
+        // paths fuzzed more than the average are skipped
+        assert_eq!(energy(PowerSchedule::Coe, 10, 20, 10.0, 1, 0), 0);
+        assert_eq!(energy(PowerSchedule::Quad, 4, 4, 10.0, 1, 0), 4);
+        // inputs sharing a path share its round
+        assert_eq!(energy(PowerSchedule::Explore, 1, 1, 1.0, 3, 1), 0);
+        assert_eq!(energy(PowerSchedule::Explore, 1, 1, 1.0, 3, 3), 1);
+        assert_eq!(PowerSchedule::from_str("coe").unwrap(), PowerSchedule::Coe);
+        // priors wear off as the input is picked
+        assert_eq!(prior_bonus(8, 1), 8);
+        assert_eq!(prior_bonus(8, 3), 2);
+        assert_eq!(prior_bonus(8, 100), 0);
+    }
+}
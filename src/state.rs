@@ -35,6 +35,8 @@ use std::path::Path;
 use crate::evm::types::EVMAddress;
 use core::{time::Duration};
 use crate::evm::config::{GPU_ENABLE};
+use crate::runner_sys::{cuAddAddressPool, cuAddCallerPool};
+use crate::workdir::{corpus_dir, solutions_dir};
 
 /// Amount of accounts and contracts that can be caller during fuzzing.
 /// We will generate random addresses for these accounts and contracts.
@@ -211,8 +213,8 @@ where
             #[cfg(not(feature = "evaluation"))]
             txn_corpus: InMemoryCorpus::new(),
             #[cfg(feature = "evaluation")]
-            txn_corpus: OnDiskCorpus::new(Path::new("corpus")).unwrap(),
-            solutions: OnDiskCorpus::new(Path::new("solutions")).unwrap(),
+            txn_corpus: OnDiskCorpus::new(Path::new(&corpus_dir())).unwrap(),
+            solutions: OnDiskCorpus::new(Path::new(&solutions_dir())).unwrap(),
             executions: 0,
             start_time: current_time(),
             metadata: Default::default(),
@@ -273,10 +275,6 @@ where
         if !self.callers_pool.contains(addr) {
             self.callers_pool.push(addr.clone());
             if unsafe { GPU_ENABLE } {
-                #[link(name = "runner")]
-                extern "C" {
-                    fn cuAddCallerPool(added_caller: *const u8, pool_len: u32);
-                }
                 let mut cuadr = addr
                     .clone()
                     .as_any()
@@ -297,10 +295,6 @@ where
         if !self.addresses_pool.contains(caller) {
             self.addresses_pool.push(caller.clone());
             if unsafe { GPU_ENABLE } {
-                #[link(name = "runner")]
-                extern "C" {
-                    fn cuAddAddressPool(added_addr: *const u8, pool_len: u32);
-                }
                 let mut cuadr = caller
                     .clone()
                     .as_any()
@@ -11,14 +11,23 @@ use std::fmt::Debug;
 use std::u8::MAX;
 
 use crate::evm::abi::BoxedABI;
+use crate::evm::adversary::{adversary_txn, parse_adversary_prefix};
+use crate::evm::corpus_format::{CorpusTxn, TxnKind};
+use crate::evm::price_feeds::parse_price_prefix;
+use crate::evm::force_feed::parse_force_feed_prefix;
+use crate::evm::block_advance::parse_block_advance_prefix;
+use crate::evm::tx_gas::parse_gas_limit_prefix;
 use crate::evm::input;
+use crate::evm::provenance::provenance_line;
+use crate::evm::summary::function_name;
 use crate::generic_vm::vm_executor::ExecutionResult;
 use crate::generic_vm::vm_state::VMStateT;
 use crate::input::VMInputT;
 use crate::state::HasInfantStateState;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use crate::evm::types::EVMU256;
+use crate::evm::types::{EVMAddress, EVMU256};
+use tracing::error;
 
 /// Represent a basic transaction using less memory.
 /// It can be serialized and converted to string.
@@ -32,6 +41,10 @@ pub struct BasicTxn<Addr> {
     pub data_abi: Option<BoxedABI>,
     #[cfg(feature = "evm")]
     pub value: Option<EVMU256>,
+    /// Block the transaction is warped to, 0 for none
+    #[cfg(feature = "evm")]
+    #[serde(default)]
+    pub warp_to: u64,
     #[cfg(feature = "full_trace")]
     pub flashloan: String,
     pub direct_data: Vec<u8>,
@@ -65,6 +78,60 @@ where
     }
 }
 
+impl<Addr> BasicTxn<Addr>
+where
+    Addr: Debug + Clone + Into<EVMAddress>,
+{
+    /// The transaction as replayed from a corpus file, preceded by the adversary transaction
+    /// landing before it (EVM Specific)
+    pub fn to_corpus_txns(&self) -> Vec<CorpusTxn> {
+        let mut txns = vec![];
+        let pretty = self.data.clone().unwrap_or_default();
+        if let Some(txn) = parse_adversary_prefix(&pretty).and_then(adversary_txn) {
+            txns.push(txn.to_corpus_txn());
+        }
+        let (kind, data) = match self.data_abi {
+            // Function calls with abi
+            Some(ref abi) => (TxnKind::Abi, hex::encode(abi.get_bytes())),
+            // Borrow txn, its randomness as data
+            None if pretty.contains("Borrow") => (TxnKind::Borrow, hex::encode(self.additional_info.as_ref().unwrap())),
+            // Transfer txn
+            None if pretty.contains("ABI") => (TxnKind::Abi, "00".to_string()),
+            None => {
+                error!("t: {:?}", self);
+                unreachable!("No abi and no borrow txn bytes");
+            }
+        };
+        txns.push(CorpusTxn {
+            kind,
+            caller: self.caller.clone().into(),
+            contract: self.contract.clone().into(),
+            data,
+            value: self.value.unwrap_or(EVMU256::ZERO),
+            // get liquidation percentage (EVM Specific)
+            liquidation_percent: pretty.split("liq percent: ").nth(1).unwrap_or("0").parse::<u8>().unwrap_or(0),
+            warp_to: self.warp_to,
+            // todo: this is repeat
+            repeat: 1,
+            // reentrancy info
+            reentrancy_limit: match kind {
+                TxnKind::Abi => self.additional_info.as_ref().unwrap_or(&vec![0])[0] as u32,
+                TxnKind::Borrow => u32::MAX,
+            },
+            step: self.data_abi.is_some() && pretty.contains("Stepping with return"),
+            // prices reported by the price feeds, ether force-fed to the target before it, block
+            // advancement from the previous transaction and gas limit of the transaction
+            feed_prices: parse_price_prefix(&pretty),
+            force_feed: parse_force_feed_prefix(&pretty),
+            block_advance: parse_block_advance_prefix(&pretty),
+            gas_limit: parse_gas_limit_prefix(&pretty),
+            function: None,
+            meta: None,
+        });
+        txns
+    }
+}
+
 /// Turn an input (VMInput + VMState) to a basic transaction
 /// Includes additional info from execution results
 pub fn build_basic_txn<Loc, Addr, VS, I, Out>(
@@ -85,6 +152,8 @@ where
         #[cfg(feature = "evm")]
         value: v.get_txn_value_temp(),
         #[cfg(feature = "evm")]
+        warp_to: v.get_warp_to(),
+        #[cfg(feature = "evm")]
         data_abi: v.get_data_abi(),
         #[cfg(feature = "full_trace")]
         flashloan: res.new_state.state.get_flashloan(),
@@ -113,6 +182,8 @@ where
         #[cfg(feature = "evm")]
         value: v.get_txn_value_temp(),
         #[cfg(feature = "evm")]
+        warp_to: v.get_warp_to(),
+        #[cfg(feature = "evm")]
         data_abi: v.get_data_abi(),
         #[cfg(feature = "full_trace")]
         flashloan: "".to_string(),
@@ -190,12 +261,39 @@ impl<Loc, Addr> TxnTrace<Loc, Addr> {
         s
     }
 
+    /// Provenance of the args of the transactions leading to the current VMState, one line per
+    /// transaction (see [`crate::evm::provenance`])
+    #[cfg(feature = "evm")]
+    pub fn provenance_str<VS, S>(&self, state: &mut S) -> String
+    where
+        S: HasInfantStateState<Loc, Addr, VS>,
+        VS: VMStateT,
+        Addr: Debug + Serialize + DeserializeOwned + Clone,
+        Loc: Debug + Serialize + DeserializeOwned + Clone,
+    {
+        let mut s = String::new();
+        if let Some(current_idx) = self.from_idx {
+            if let Ok(corpus_item) = state.get_infant_state_state().corpus().get(current_idx) {
+                let testcase = corpus_item.clone().into_inner();
+                if let Some(testcase_input) = testcase.input() {
+                    s = Self::provenance_str(&testcase_input.trace.clone(), state);
+                }
+            }
+        }
+        for t in &self.transactions {
+            if let Some(abi) = &t.data_abi {
+                s.push_str(format!("{}\n", provenance_line(&function_name(&t.data_abi), abi)).as_str());
+            }
+        }
+        s
+    }
+
     /// Serialize the trace so that it can be replayed by using --replay-file option
     pub fn to_file_str<VS, S>(&self, state: &mut S) -> String
         where
             S: HasInfantStateState<Loc, Addr, VS>,
             VS: VMStateT,
-            Addr: Debug + Serialize + DeserializeOwned + Clone,
+            Addr: Debug + Serialize + DeserializeOwned + Clone + Into<EVMAddress>,
             Loc: Debug + Serialize + DeserializeOwned + Clone,
     {
         // If from_idx is None, it means that the trace is from the initial state
@@ -219,68 +317,60 @@ impl<Loc, Addr> TxnTrace<Loc, Addr> {
 
         // Dump the current transaction
         for t in &self.transactions {
-            // get liquidation percentage (EVM Specific)
-            let liq_perct = match t.data {
-                None => 0,
-                Some(ref data) => {
-                    data.split("liq percent: ").take(2).last().unwrap_or("0").parse::<u64>().unwrap_or(0)
-                }
-            };
-            match t.data_abi {
-                None => {
-                    if t.data.is_some() && t.data.as_ref().unwrap().contains("Borrow") {
-                        // Borrow txn
-                        s.push_str("borrow ");
-                        s.push_str(format!("{:?} ", t.caller).as_str());
-                        s.push_str(format!("{:?} ", t.contract).as_str());
-                        s.push_str(format!("{} ", hex::encode(t.additional_info.as_ref().unwrap())).as_str());
-                        s.push_str(format!("{} ", t.value.unwrap_or(EVMU256::ZERO)).as_str());
-                        s.push_str(format!("{} ", liq_perct).as_str());
-                        // todo: this is warp_to
-                        s.push_str(format!("{} ", 0).as_str());
-                    } else if t.data.is_some() && t.data.as_ref().unwrap().contains("ABI") {
-                        // Transfer txn
-                        s.push_str("abi ");
-                        s.push_str(format!("{:?} ", t.caller).as_str());
-                        s.push_str(format!("{:?} ", t.contract).as_str());
-                        s.push_str("00 ");
-                        s.push_str(format!("{} ", t.value.unwrap_or(EVMU256::ZERO)).as_str());
-                        s.push_str(format!("{} ", liq_perct).as_str());
-                        // todo: this is warp_to
-                        s.push_str(format!("{} ", 0).as_str());
-                        // todo: this is repeat
-                        s.push_str(format!("{} ", 1).as_str());
-                        // reentrancy info
-                        s.push_str(format!("{} ", t.additional_info.as_ref().unwrap_or(&vec![0])[0]).as_str());
-                    } else {
-                        println!("t: {:?}", t);
-                        unreachable!("No abi and no borrow txn bytes");
-                    }
-                }
-                Some(ref abi) => {
-                    // Function calls with abi
-                    s.push_str("abi ");
-                    s.push_str(format!("{:?} ", t.caller).as_str());
-                    s.push_str(format!("{:?} ", t.contract).as_str());
-                    s.push_str(format!("{} ", hex::encode(abi.get_bytes())).as_str());
-                    s.push_str(format!("{} ", t.value.unwrap_or(EVMU256::ZERO)).as_str());
-                    s.push_str(format!("{} ", liq_perct).as_str());
-                    // todo: this is warp_to
-                    s.push_str(format!("{} ", 0).as_str());
-                    // todo: this is repeat
-                    s.push_str(format!("{} ", 1).as_str());
-                    // reentrancy info
-                    s.push_str(format!("{} ", t.additional_info.as_ref().unwrap_or(&vec![0])[0]).as_str());
-                    s.push_str(format!("{} ", t.data.as_ref().unwrap_or(&String::from("")).contains("Stepping with return")).as_str());
-                }
+            for txn in t.to_corpus_txns() {
+                s.push_str(txn.to_legacy_line().as_str());
             }
-            s.push_str("\n");
         }
         s
     }
+
+    /// Transactions leading to the current VMState, as written to the corpus files
+    /// (see [`crate::evm::corpus_format`])
+    pub fn to_corpus_txns<VS, S>(&self, state: &mut S) -> Vec<CorpusTxn>
+    where
+        S: HasInfantStateState<Loc, Addr, VS>,
+        VS: VMStateT,
+        Addr: Debug + Serialize + DeserializeOwned + Clone + Into<EVMAddress>,
+        Loc: Debug + Serialize + DeserializeOwned + Clone,
+    {
+        let mut txns = vec![];
+        if let Some(current_idx) = self.from_idx {
+            // the corpus item may be discarded when full_trace feature is not enabled
+            if let Ok(corpus_item) = state.get_infant_state_state().corpus().get(current_idx) {
+                let testcase = corpus_item.clone().into_inner();
+                if let Some(testcase_input) = testcase.input() {
+                    txns = Self::to_corpus_txns(&testcase_input.trace.clone(), state);
+                }
+            }
+        }
+        for t in &self.transactions {
+            txns.extend(t.to_corpus_txns());
+        }
+        txns
+    }
 }
 impl<Loc, Addr> Default for TxnTrace<Loc, Addr> {
     fn default() -> Self {
         Self::new()
     }
 }
+
+mod tests {
+    use super::*;
+    use crate::evm::corpus_format::parse_legacy;
+    use crate::evm::input::EVMInput;
+    use crate::evm::vm::EVMState;
+    use crate::state_input::StagedVMState;
+
+    #[test]
+    fn test_corpus_txns_warp() {
+        let legacy = "abi 0x8b21e662154b4bbc1ec0754d0238875fe3d22fa6 0x1000000000000000000000000000000000000000 00 0 0 9000 1\n";
+        let input = parse_legacy(legacy).unwrap()[0].to_input(StagedVMState::new_uninitialized());
+        let txn = build_basic_txn_from_input::<EVMAddress, EVMAddress, EVMState, EVMInput>(&input);
+        let txns = txn.to_corpus_txns();
+        assert_eq!(txns.len(), 1);
+        // the absolute warp of the input is kept, for the replays and the exported exploits
+        assert_eq!(txns[0].warp_to, 9000);
+        assert_eq!(txns[0].data, "00");
+    }
+}
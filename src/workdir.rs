@@ -0,0 +1,201 @@
+//! Layout of the work directory of a fuzzing campaign
+//!
+//! ```text
+//! <work_dir>/
+//! ├── corpus/              interesting inputs (replayable)
+//! ├── solutions/<oracle>/  inputs violating an oracle, grouped by oracle
+//! ├── stats/               coverage and other statistics
+//! ├── artifacts/           other outputs (e.g., unconfirmed GPU solutions)
+//! ├── states/              storage of the infant states (with `--dump-corpus`)
+//! └── manifest.json        description of the run
+//! ```
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha3::Sha3;
+use crate::evm::contract_metadata::{decode_metadata, ContractMetadata};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Root of the work directory, set by [`init_work_dir`]
+pub static mut WORK_DIR: String = String::new();
+
+pub const CORPUS_DIR: &str = "corpus";
+pub const SOLUTIONS_DIR: &str = "solutions";
+pub const STATS_DIR: &str = "stats";
+pub const ARTIFACTS_DIR: &str = "artifacts";
+pub const STATES_DIR: &str = "states";
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Create the work directory layout
+pub fn init_work_dir(root: &str) {
+    unsafe {
+        WORK_DIR = root.to_string();
+    }
+    for dir in [CORPUS_DIR, SOLUTIONS_DIR, STATS_DIR, ARTIFACTS_DIR] {
+        fs::create_dir_all(work_path(dir)).expect("failed to create work directory");
+    }
+}
+
+/// Path of a file or directory inside the work directory
+pub fn work_path(name: &str) -> String {
+    let root = unsafe { WORK_DIR.as_str() };
+    if root.is_empty() {
+        return name.to_string();
+    }
+    Path::new(root).join(name).to_string_lossy().to_string()
+}
+
+pub fn corpus_dir() -> String {
+    work_path(CORPUS_DIR)
+}
+
+pub fn solutions_dir() -> String {
+    work_path(SOLUTIONS_DIR)
+}
+
+pub fn stats_dir() -> String {
+    work_path(STATS_DIR)
+}
+
+pub fn artifacts_dir() -> String {
+    work_path(ARTIFACTS_DIR)
+}
+
+pub fn states_dir() -> String {
+    work_path(STATES_DIR)
+}
+
+/// Name of the oracle that produced the output, e.g., `[bug] bug() hit ...` => `bug`
+pub fn oracle_name(oracle_output: &str) -> String {
+    let name = match (oracle_output.find('['), oracle_output.find(']')) {
+        (Some(start), Some(end)) if start < end => &oracle_output[start + 1..end],
+        _ => oracle_output.split(':').next().unwrap_or_default(),
+    };
+    let name = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.is_empty() {
+        "unknown".to_string()
+    } else {
+        name
+    }
+}
+
+/// Write a file under `artifacts/<dir>/`, creating the directory. A failure is reported, it does
+/// not stop the campaign.
+pub fn write_artifact(dir: &str, name: &str, data: &str) {
+    let dir = Path::new(&artifacts_dir()).join(dir);
+    let path = dir.join(name);
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, data)) {
+        warn!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+/// Save a solution under `solutions/<oracle>/`
+pub fn save_solution(oracle_output: &str, data: &str) {
+    let dir = Path::new(&solutions_dir()).join(oracle_name(oracle_output));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_nanos();
+    let path = dir.join(format!("{}", timestamp));
+    // the solution is also printed, a failed write does not stop the campaign
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, data)) {
+        warn!("failed to save the solution {}: {}", path.display(), e);
+    }
+}
+
+/// Keccak256 of the given bytes
+pub fn hash_bytes(data: &[u8]) -> String {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    hasher.result_str()
+}
+
+/// Keccak256 of the file content, None if the file cannot be read
+pub fn hash_file(path: &str) -> Option<String> {
+    let mut data = vec![];
+    File::open(path).ok()?.read_to_end(&mut data).ok()?;
+    Some(hash_bytes(&data))
+}
+
+/// A target of the campaign, identified by the hash of its code
+#[derive(Serialize, Debug, Clone)]
+pub struct ManifestTarget {
+    pub name: String,
+    pub code_hash: String,
+    /// Compiler metadata appended to the code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ContractMetadata>,
+}
+
+/// Description of a run, making the work directory self-describing
+#[derive(Serialize, Debug, Clone)]
+pub struct Manifest {
+    pub version: String,
+    pub args: Vec<String>,
+    pub targets: Vec<ManifestTarget>,
+    pub ptx_path: Option<String>,
+    pub ptx_hash: Option<String>,
+    pub seed: u64,
+    pub start_time: u64,
+}
+
+impl Manifest {
+    pub fn new(args: Vec<String>, seed: u64) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            args,
+            targets: vec![],
+            ptx_path: None,
+            ptx_hash: None,
+            seed,
+            start_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs(),
+        }
+    }
+
+    pub fn add_target(&mut self, name: String, code: &[u8]) {
+        self.targets.push(ManifestTarget {
+            name,
+            code_hash: hash_bytes(code),
+            metadata: decode_metadata(code),
+        });
+    }
+
+    pub fn set_ptx(&mut self, ptx_path: &str) {
+        if ptx_path.len() > 0 {
+            self.ptx_path = Some(ptx_path.to_string());
+            self.ptx_hash = hash_file(ptx_path);
+        }
+    }
+
+    /// Write the manifest to `manifest.json` in the work directory
+    pub fn save(&self) {
+        let mut file = File::create(work_path(MANIFEST_FILE)).expect("failed to create manifest");
+        file.write_all(serde_json::to_string_pretty(self).unwrap().as_bytes())
+            .expect("failed to write manifest");
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_name() {
+        assert_eq!(oracle_name("[bug] bug() hit at contract 0x0"), "bug");
+        assert_eq!(oracle_name("💰[Flashloan] Earned 1 more than owed 0"), "flashloan");
+        assert_eq!(oracle_name("Imbalanced Pair: 0x0, Reserves: (1, 1) => (0, 1)"), "imbalanced_pair");
+        assert_eq!(oracle_name(""), "unknown");
+    }
+}